@@ -0,0 +1,44 @@
+use std::{ffi::CString, hint::black_box, num::NonZeroUsize, path::Path};
+
+use chimeric_engine::core::render_system_txt_key::FileOrRenderedTextKey;
+use criterion::{criterion_group, criterion_main, Criterion};
+use lru::LruCache;
+
+fn key_construction(c: &mut Criterion) {
+    let path = Path::new("assets/sprites/characters/hero_walk.png");
+    let font = Path::new("assets/fonts/TEMPSITC-REDUCED.TTF");
+    let text = CString::new("the quick brown fox jumps over the lazy dog").unwrap();
+    c.bench_function("key from path", |b| {
+        b.iter(|| FileOrRenderedTextKey::from_path(black_box(path)))
+    });
+    c.bench_function("key from text", |b| {
+        b.iter(|| FileOrRenderedTextKey::from_rendered_text(black_box(&text), font, 16))
+    });
+    c.bench_function("key from wrapped text", |b| {
+        b.iter(|| {
+            FileOrRenderedTextKey::from_rendered_wrapped_text(black_box(&text), font, 16, 200)
+        })
+    });
+}
+
+fn cache_hit(c: &mut Criterion) {
+    let paths: Vec<String> = (0..256)
+        .map(|i| format!("assets/sprites/sprite_{i}.png"))
+        .collect();
+    let mut cache = LruCache::new(NonZeroUsize::new(paths.len()).unwrap());
+    for (i, path) in paths.iter().enumerate() {
+        cache.put(FileOrRenderedTextKey::from_path(Path::new(path)), i);
+    }
+    let mut i = 0;
+    // what each copy does before drawing: build the key and look it up
+    c.bench_function("cache hit lookup", |b| {
+        b.iter(|| {
+            i = (i + 1) % paths.len();
+            let key = FileOrRenderedTextKey::from_path(Path::new(&paths[i]));
+            *cache.get(&key).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, key_construction, cache_hit);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+use std::num::NonZeroUsize;
+
+use chimeric_engine::core::system::{
+    AudioFallback, ChimericSystem, ChimericSystemSettings, System,
+};
+
+pub const WINDOW: &str = "bench";
+
+/// sdl without a display or sound card, so the benches run on ci. must be
+/// called before anything else touches sdl
+pub fn headless_system() -> System {
+    std::env::set_var("SDL_VIDEODRIVER", "offscreen");
+    std::env::set_var("SDL_AUDIODRIVER", "dummy");
+    std::env::set_var("SDL_RENDER_DRIVER", "software");
+    System::with_audio_fallback(AudioFallback::Silent).unwrap()
+}
+
+/// with one hidden window named WINDOW
+pub fn headless_chimeric_system(system: &System) -> ChimericSystem<'_> {
+    let mut chimeric = ChimericSystem::new(
+        system,
+        ChimericSystemSettings {
+            num_point_sizes_per_font: NonZeroUsize::new(8).unwrap(),
+            num_fonts: NonZeroUsize::new(4).unwrap(),
+            num_textures_per_window: NonZeroUsize::new(256).unwrap(),
+            num_text_per_window: NonZeroUsize::new(256).unwrap(),
+        },
+    );
+    let window = system
+        .video
+        .window(WINDOW, 640, 480)
+        .hidden()
+        .build()
+        .unwrap();
+    chimeric.add_window(WINDOW, window).unwrap();
+    chimeric
+}
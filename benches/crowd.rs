@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use chimeric_engine::core::{
+    crowd::{BoidParams, Crowd},
+    rng::Rng,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use sdl2::rect::FRect;
+
+fn crowd(c: &mut Criterion) {
+    for count in [500, 2000] {
+        let mut crowd = Crowd::new(
+            BoidParams {
+                bounds: FRect::new(0., 0., 1280., 720.),
+                ..Default::default()
+            },
+            "bench",
+        );
+        crowd.scatter(&mut Rng::new(1), count);
+        c.bench_function(&format!("crowd step {count} agents"), |b| {
+            b.iter(|| crowd.step(Duration::from_nanos(1_000_000_000 / 60)))
+        });
+        c.bench_function(&format!("crowd mesh {count} agents"), |b| {
+            b.iter(|| crowd.mesh())
+        });
+    }
+}
+
+criterion_group!(benches, crowd);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+use std::{ffi::CString, path::Path};
+
+use chimeric_engine::core::system::CopyStruct;
+use criterion::{criterion_group, criterion_main, Criterion};
+use sdl2::{pixels::Color, rect::Rect};
+
+mod common;
+
+use common::{headless_chimeric_system, headless_system, WINDOW};
+
+fn render(c: &mut Criterion) {
+    let system = headless_system();
+    let mut chimeric = headless_chimeric_system(&system);
+    let assets = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("examples")
+        .join("assets");
+    let image = assets.join("test.jpg");
+    let font = assets.join("TEMPSITC-REDUCED.TTF");
+    let text = CString::new("the quick brown fox").unwrap();
+
+    c.bench_function("copy cache hit", |b| {
+        b.iter(|| {
+            chimeric
+                .copy(WINDOW, &image, None, Rect::new(0, 0, 32, 32))
+                .unwrap()
+        })
+    });
+
+    let sprites: Vec<CopyStruct> = (0..1000)
+        .map(|i| CopyStruct {
+            src: Some(Rect::new(0, 0, 16, 16)),
+            dst: Some(Rect::new((i % 40) * 16, (i / 40) * 16, 16, 16)),
+        })
+        .collect();
+    c.bench_function("copy_many 1000 sprites and present", |b| {
+        b.iter(|| {
+            chimeric.clear(Color::BLACK).unwrap();
+            chimeric
+                .copy_many(WINDOW, &image, sprites.iter().copied())
+                .unwrap();
+            chimeric.present().unwrap();
+        })
+    });
+
+    c.bench_function("copy_text cache hit", |b| {
+        b.iter(|| {
+            chimeric
+                .copy_text(
+                    WINDOW,
+                    &font,
+                    16,
+                    &text,
+                    None,
+                    None,
+                    Rect::new(0, 0, 200, 20),
+                )
+                .unwrap()
+        })
+    });
+
+    // a different string each time, so every iteration renders and uploads
+    let mut i = 0u64;
+    c.bench_function("copy_text cache miss", |b| {
+        b.iter(|| {
+            i += 1;
+            let text = CString::new(format!("frame {i}")).unwrap();
+            chimeric
+                .copy_text(
+                    WINDOW,
+                    &font,
+                    16,
+                    &text,
+                    None,
+                    None,
+                    Rect::new(0, 0, 200, 20),
+                )
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
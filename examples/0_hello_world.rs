@@ -9,6 +9,7 @@ fn main() -> std::process::ExitCode {
         num_point_sizes_per_font: NonZero::new(100).unwrap(),
         num_fonts: NonZero::new(5).unwrap(),
         num_textures_per_window: NonZero::new(100).unwrap(),
+        num_text_per_window: NonZero::new(100).unwrap(),
     });
     let window = system.video
         .window("shift tab! mouse!", 200, 200)
@@ -30,7 +31,7 @@ fn main() -> std::process::ExitCode {
     
     chimeric_system.copy("main", &image_path, None, None).unwrap();
     chimeric_system.copy_text("main", &font_path, 50, c"text", None, None, Rect::new(0, 0, 200, 50)).unwrap();
-    chimeric_system.present();
+    chimeric_system.present().unwrap();
 
     sleep(Duration::from_secs(2));
 
@@ -0,0 +1,53 @@
+use std::{num::NonZero, path::Path};
+
+use chimeric_engine::core::{
+    app::{ChimericApp, ChimericAppSettings},
+    system::{ChimericSystemSettings, System},
+    world::World,
+};
+use sdl2::keyboard::Scancode;
+
+fn main() -> std::process::ExitCode {
+    let system = System::new().unwrap();
+    let mut app = ChimericApp::new(
+        &system,
+        ChimericSystemSettings {
+            num_point_sizes_per_font: NonZero::new(100).unwrap(),
+            num_fonts: NonZero::new(5).unwrap(),
+            num_textures_per_window: NonZero::new(100).unwrap(),
+            num_text_per_window: NonZero::new(100).unwrap(),
+        },
+        ChimericAppSettings::default(),
+    )
+    .unwrap();
+    let window = system.video
+        .window("escape to quit", 200, 200)
+        .resizable()
+        .position_centered()
+        .build()
+        .unwrap();
+    app.system.add_window("main", window).unwrap();
+
+    let image_path = Path::new(".")
+        .join("examples")
+        .join("assets")
+        .join("test.jpg");
+
+    let mut world = World::new();
+    app.run(
+        &mut world,
+        |world, frame| {
+            if frame.input.key_just_pressed(Scancode::Escape) {
+                frame.quit();
+            }
+            world.advance_with(frame.time).map(|_| ())
+        },
+        |world, system| {
+            system.copy("main", &image_path, None, None)?;
+            world.draw(system)
+        },
+    )
+    .unwrap();
+
+    std::process::ExitCode::SUCCESS
+}
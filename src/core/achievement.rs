@@ -0,0 +1,416 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+
+use super::vfs::Vfs;
+
+/// an achievement's definition
+#[derive(Debug, Clone)]
+pub struct AchievementDef {
+    /// unlocked once every stat reaches its threshold. unlocked only
+    /// explicitly if empty
+    pub thresholds: BTreeMap<String, f64>,
+    /// not shown until unlocked
+    pub hidden: bool,
+    /// everything else, e.g. its title, description and icon
+    pub params: toml::Table,
+}
+
+/// achievement definitions, loaded from data like prefabs. each top level
+/// table is an achievement:
+///
+/// ```toml
+/// [exterminator]
+/// title = "Exterminator"
+/// when = { rats_killed = 100 }
+///
+/// [secret_door]
+/// hidden = true
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AchievementRegistry {
+    achievements: HashMap<String, AchievementDef>,
+}
+
+impl AchievementRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add or replace an achievement
+    pub fn insert(&mut self, achievement_name: &str, achievement: AchievementDef) {
+        self.achievements
+            .insert(achievement_name.into(), achievement);
+    }
+
+    pub fn get(&self, achievement_name: &str) -> Option<&AchievementDef> {
+        self.achievements.get(achievement_name)
+    }
+
+    /// in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.achievements.keys().map(String::as_str)
+    }
+
+    /// parse achievements from toml content. achievements that already exist
+    /// are replaced
+    pub fn load_str(&mut self, content: &str) -> Result<(), String> {
+        let table: toml::Table = content
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        for (name, value) in table {
+            let mut params = match value {
+                toml::Value::Table(params) => params,
+                _ => return Err(format!("achievement \"{name}\" must be a table")),
+            };
+            let thresholds = match params.remove("when") {
+                None => BTreeMap::new(),
+                Some(toml::Value::Table(when)) => when
+                    .into_iter()
+                    .map(|(stat, value)| match value {
+                        toml::Value::Integer(v) => Ok((stat, v as f64)),
+                        toml::Value::Float(v) => Ok((stat, v)),
+                        _ => Err(format!(
+                            "achievement \"{name}\" threshold \"{stat}\" must be a number"
+                        )),
+                    })
+                    .collect::<Result<_, _>>()?,
+                Some(_) => return Err(format!("achievement \"{name}\" when must be a table")),
+            };
+            let hidden = match params.remove("hidden") {
+                None => false,
+                Some(toml::Value::Boolean(hidden)) => hidden,
+                Some(_) => return Err(format!("achievement \"{name}\" hidden must be a bool")),
+            };
+            self.insert(
+                &name,
+                AchievementDef {
+                    thresholds,
+                    hidden,
+                    params,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// load achievements from a toml file. achievements that already exist are
+    /// replaced
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        self.load_str(&content)
+    }
+
+    /// as load_file, resolving paths through the vfs
+    pub fn load_from(&mut self, assets: &Vfs, path: &Path) -> Result<(), String> {
+        self.load_str(&assets.read_to_string(path)?)
+    }
+}
+
+/// a platform's achievement service, e.g. steam. implemented by the
+/// application and given to Achievements, which forwards each change
+pub trait AchievementBackend {
+    fn unlock(&mut self, achievement: &str) -> Result<(), String>;
+
+    fn set_stat(&mut self, stat: &str, value: f64) -> Result<(), String>;
+
+    /// called after each batch of changes, for platforms which upload them
+    /// together
+    fn store(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// counters and unlocked achievements, kept locally and forwarded to any
+/// backends. local state is updated even if a backend fails
+#[derive(Default)]
+pub struct Achievements {
+    stats: BTreeMap<String, f64>,
+    unlocked: BTreeSet<String>,
+    backends: Vec<Box<dyn AchievementBackend>>,
+}
+
+impl Achievements {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_backend(&mut self, backend: Box<dyn AchievementBackend>) {
+        self.backends.push(backend);
+    }
+
+    /// zero if never set
+    pub fn stat(&self, stat: &str) -> f64 {
+        self.stats.get(stat).copied().unwrap_or(0.)
+    }
+
+    pub fn is_unlocked(&self, achievement: &str) -> bool {
+        self.unlocked.contains(achievement)
+    }
+
+    /// sorted
+    pub fn unlocked(&self) -> impl Iterator<Item = &str> {
+        self.unlocked.iter().map(String::as_str)
+    }
+
+    /// from zero to one: how close the furthest behind stat is to its
+    /// threshold
+    pub fn progress(&self, achievements: &AchievementRegistry, achievement: &str) -> f32 {
+        if self.is_unlocked(achievement) {
+            return 1.;
+        }
+        let Some(def) = achievements.get(achievement) else {
+            return 0.;
+        };
+        def.thresholds
+            .iter()
+            .map(|(stat, threshold)| {
+                if *threshold <= 0. {
+                    1.
+                } else {
+                    (self.stat(stat) / threshold).clamp(0., 1.) as f32
+                }
+            })
+            .reduce(f32::min)
+            .unwrap_or(0.)
+    }
+
+    /// add to a counter. returns the achievements this unlocked
+    pub fn increment(
+        &mut self,
+        achievements: &AchievementRegistry,
+        stat: &str,
+        amount: f64,
+    ) -> Result<Vec<String>, String> {
+        self.set_stat(achievements, stat, self.stat(stat) + amount)
+    }
+
+    /// returns the achievements this unlocked
+    pub fn set_stat(
+        &mut self,
+        achievements: &AchievementRegistry,
+        stat: &str,
+        value: f64,
+    ) -> Result<Vec<String>, String> {
+        self.stats.insert(stat.to_owned(), value);
+        let mut newly: Vec<String> = achievements
+            .achievements
+            .iter()
+            .filter(|(name, def)| {
+                !self.unlocked.contains(*name)
+                    && def.thresholds.contains_key(stat)
+                    && def
+                        .thresholds
+                        .iter()
+                        .all(|(stat, threshold)| self.stat(stat) >= *threshold)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        newly.sort_unstable();
+        self.unlocked.extend(newly.iter().cloned());
+        let result = self.forward(|backend| {
+            backend.set_stat(stat, value)?;
+            newly.iter().try_for_each(|name| backend.unlock(name))
+        });
+        result.map(|_| newly)
+    }
+
+    /// unlock regardless of thresholds. false if it already was
+    pub fn unlock(&mut self, achievement: &str) -> Result<bool, String> {
+        if !self.unlocked.insert(achievement.to_owned()) {
+            return Ok(false);
+        }
+        self.forward(|backend| backend.unlock(achievement))?;
+        Ok(true)
+    }
+
+    /// send all local state to the backends, e.g. progress made while a
+    /// platform was unavailable
+    pub fn sync(&mut self) -> Result<(), String> {
+        let stats = &self.stats;
+        let unlocked = &self.unlocked;
+        let mut first_error = None;
+        for backend in self.backends.iter_mut() {
+            let result = stats
+                .iter()
+                .try_for_each(|(stat, value)| backend.set_stat(stat, *value))
+                .and_then(|_| unlocked.iter().try_for_each(|name| backend.unlock(name)))
+                .and_then(|_| backend.store());
+            if let Err(e) = result {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// apply the change to every backend and store it, even if an earlier
+    /// one fails. returns the first error
+    fn forward(
+        &mut self,
+        mut change: impl FnMut(&mut dyn AchievementBackend) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut first_error = None;
+        for backend in self.backends.iter_mut() {
+            if let Err(e) = change(backend.as_mut()).and_then(|_| backend.store()) {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// for local persistence:
+    ///
+    /// ```toml
+    /// unlocked = ["exterminator"]
+    /// [stats]
+    /// rats_killed = 104.0
+    /// ```
+    pub fn to_toml(&self) -> toml::Table {
+        let mut table = toml::Table::new();
+        let unlocked: Vec<toml::Value> = self.unlocked.iter().map(|a| a.as_str().into()).collect();
+        table.insert("unlocked".into(), unlocked.into());
+        let stats: toml::Table = self
+            .stats
+            .iter()
+            .map(|(stat, value)| (stat.clone(), toml::Value::Float(*value)))
+            .collect();
+        table.insert("stats".into(), stats.into());
+        table
+    }
+
+    /// replace the local state. backends are kept
+    pub fn load_toml(&mut self, table: &toml::Table) -> Result<(), String> {
+        let mut unlocked = BTreeSet::new();
+        if let Some(list) = table.get("unlocked") {
+            for name in list.as_array().ok_or("unlocked must be an array")? {
+                unlocked.insert(
+                    name.as_str()
+                        .ok_or("unlocked must be an array of strings")?
+                        .to_owned(),
+                );
+            }
+        }
+        let mut stats = BTreeMap::new();
+        if let Some(values) = table.get("stats") {
+            for (stat, value) in values.as_table().ok_or("stats must be a table")? {
+                let value = value
+                    .as_float()
+                    .or(value.as_integer().map(|v| v as f64))
+                    .ok_or_else(|| format!("stat \"{stat}\" must be a number"))?;
+                stats.insert(stat.clone(), value);
+            }
+        }
+        self.unlocked = unlocked;
+        self.stats = stats;
+        Ok(())
+    }
+
+    /// nothing is loaded if the file doesn't exist yet
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        self.load_toml(&table)
+    }
+
+    pub fn save_file(&self, path: &Path) -> Result<(), String> {
+        let contents = toml::to_string(&self.to_toml()).map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl AchievementBackend for Recorder {
+        fn unlock(&mut self, achievement: &str) -> Result<(), String> {
+            self.calls
+                .borrow_mut()
+                .push(format!("unlock {achievement}"));
+            Ok(())
+        }
+
+        fn set_stat(&mut self, stat: &str, value: f64) -> Result<(), String> {
+            self.calls.borrow_mut().push(format!("{stat} = {value}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_achievements() {
+        let mut defs = AchievementRegistry::new();
+        defs.load_str(
+            r#"
+            [exterminator]
+            title = "Exterminator"
+            when = { rats_killed = 10 }
+
+            [thorough]
+            when = { rats_killed = 5, chests_opened = 2 }
+
+            [secret_door]
+            hidden = true
+            "#,
+        )
+        .unwrap();
+        assert!(defs.get("secret_door").unwrap().hidden);
+
+        let recorder = Recorder::default();
+        let calls = recorder.calls.clone();
+        let mut achievements = Achievements::new();
+        achievements.add_backend(Box::new(recorder));
+
+        assert!(achievements
+            .increment(&defs, "rats_killed", 6.)
+            .unwrap()
+            .is_empty());
+        assert_eq!(achievements.progress(&defs, "exterminator"), 0.6);
+        assert_eq!(achievements.progress(&defs, "thorough"), 0.);
+        assert_eq!(
+            achievements.set_stat(&defs, "chests_opened", 2.).unwrap(),
+            vec!["thorough"]
+        );
+        assert_eq!(
+            achievements.increment(&defs, "rats_killed", 4.).unwrap(),
+            vec!["exterminator"]
+        );
+        assert!(achievements.unlock("secret_door").unwrap());
+        assert!(!achievements.unlock("secret_door").unwrap());
+        assert_eq!(
+            *calls.borrow(),
+            [
+                "rats_killed = 6",
+                "chests_opened = 2",
+                "unlock thorough",
+                "rats_killed = 10",
+                "unlock exterminator",
+                "unlock secret_door",
+            ]
+        );
+
+        let mut loaded = Achievements::new();
+        loaded.load_toml(&achievements.to_toml()).unwrap();
+        assert_eq!(
+            loaded.unlocked().collect::<Vec<_>>(),
+            ["exterminator", "secret_door", "thorough"]
+        );
+        assert_eq!(loaded.stat("rats_killed"), 10.);
+    }
+}
@@ -0,0 +1,222 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use sdl2::rect::{FPoint, FRect, Rect};
+
+use super::{frame_clock::EngineClock, system::ChimericSystem};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationFrame {
+    /// the area in the sheet image
+    pub src: Rect,
+    pub duration: Duration,
+    /// where the src is drawn relative to the untrimmed frame's top left
+    pub offset: FPoint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayDirection {
+    #[default]
+    Forward,
+    Reverse,
+    /// forward then back, without repeating the end frames
+    PingPong,
+    PingPongReverse,
+}
+
+/// a named sequence of frames from a sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    pub direction: PlayDirection,
+    /// times played before stopping on the last frame. None loops forever
+    pub repeat: Option<u32>,
+}
+
+impl Animation {
+    /// indices into frames, in the order they're shown over one cycle
+    pub fn order(&self) -> Vec<usize> {
+        let n = self.frames.len();
+        let inner = 1..n.saturating_sub(1);
+        match self.direction {
+            PlayDirection::Forward => (0..n).collect(),
+            PlayDirection::Reverse => (0..n).rev().collect(),
+            PlayDirection::PingPong => (0..n).chain(inner.rev()).collect(),
+            PlayDirection::PingPongReverse => (0..n).rev().chain(inner).collect(),
+        }
+    }
+
+    /// duration of one cycle
+    pub fn cycle_duration(&self) -> Duration {
+        self.order().iter().map(|i| self.frames[*i].duration).sum()
+    }
+
+    pub fn is_finished(&self, elapsed: Duration) -> bool {
+        self.repeat
+            .is_some_and(|repeat| elapsed >= self.cycle_duration() * repeat)
+    }
+
+    /// the index into frames shown after the time has elapsed. None if there
+    /// are no frames
+    pub fn frame_index(&self, elapsed: Duration) -> Option<usize> {
+        let order = self.order();
+        let last = *order.last()?;
+        let cycle = self.cycle_duration();
+        if cycle.is_zero() || self.is_finished(elapsed) {
+            return Some(last);
+        }
+        let mut t = Duration::from_nanos((elapsed.as_nanos() % cycle.as_nanos()) as u64);
+        for i in order {
+            let duration = self.frames[i].duration;
+            if t < duration {
+                return Some(i);
+            }
+            t -= duration;
+        }
+        Some(last)
+    }
+
+    pub fn frame(&self, elapsed: Duration) -> Option<&AnimationFrame> {
+        self.frame_index(elapsed).map(|i| &self.frames[i])
+    }
+}
+
+/// animations sharing a sheet image
+#[derive(Debug, Clone, Default)]
+pub struct AnimationSet {
+    /// relative to the working directory
+    pub image: PathBuf,
+    pub animations: BTreeMap<String, Animation>,
+}
+
+impl AnimationSet {
+    pub fn get(&self, name: &str) -> Option<&Animation> {
+        self.animations.get(name)
+    }
+
+    /// draw the frame at its native size, with position as the top left of
+    /// the untrimmed frame. the sheet goes through the texture cache
+    pub fn draw_frame(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        frame: &AnimationFrame,
+        position: FPoint,
+    ) -> Result<(), String> {
+        let top_left = position + frame.offset;
+        let dst = FRect::new(
+            top_left.x(),
+            top_left.y(),
+            frame.src.width() as f32,
+            frame.src.height() as f32,
+        );
+        system.copy_f(window_name, &self.image, frame.src, dst)
+    }
+}
+
+/// tracks which animation is playing and for how long
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    current: Option<String>,
+    elapsed: Duration,
+    /// multiplier on the time given to update
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self {
+            current: None,
+            elapsed: Duration::ZERO,
+            speed: 1.,
+            paused: false,
+        }
+    }
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// switch to the animation. continues if it's already playing
+    pub fn play(&mut self, name: &str) {
+        if self.current.as_deref() != Some(name) {
+            self.current = Some(name.to_owned());
+            self.elapsed = Duration::ZERO;
+        }
+    }
+
+    /// play the current animation from the start
+    pub fn restart(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.elapsed = Duration::ZERO;
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn update(&mut self, elapsed: Duration) {
+        if !self.paused && self.current.is_some() {
+            self.elapsed += elapsed.mul_f32(self.speed.max(0.));
+        }
+    }
+
+    /// advance by the clock's last delta
+    pub fn update_with(&mut self, clock: &EngineClock) {
+        self.update(clock.delta());
+    }
+
+    /// the frame to show. None if nothing is playing, or the animation isn't
+    /// in the set
+    pub fn frame<'a>(&self, set: &'a AnimationSet) -> Option<&'a AnimationFrame> {
+        set.get(self.current.as_deref()?)?.frame(self.elapsed)
+    }
+
+    /// true if the current animation doesn't loop and has played out
+    pub fn is_finished(&self, set: &AnimationSet) -> bool {
+        self.current
+            .as_deref()
+            .and_then(|name| set.get(name))
+            .is_some_and(|a| a.is_finished(self.elapsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_index() {
+        let frame = AnimationFrame {
+            src: Rect::new(0, 0, 8, 8),
+            duration: Duration::from_millis(100),
+            offset: FPoint::new(0., 0.),
+        };
+        let mut animation = Animation {
+            frames: vec![frame; 3],
+            direction: PlayDirection::PingPong,
+            repeat: None,
+        };
+        assert_eq!(animation.order(), vec![0, 1, 2, 1]);
+        let at = |a: &Animation, ms| a.frame_index(Duration::from_millis(ms));
+        assert_eq!(at(&animation, 350), Some(1));
+        assert_eq!(at(&animation, 450), Some(0));
+
+        animation.direction = PlayDirection::Reverse;
+        animation.repeat = Some(2);
+        assert_eq!(at(&animation, 50), Some(2));
+        assert_eq!(at(&animation, 450), Some(1));
+        assert_eq!(at(&animation, 600), Some(0));
+        assert!(animation.is_finished(Duration::from_millis(600)));
+    }
+}
@@ -0,0 +1,311 @@
+use std::time::Duration;
+
+use sdl2::pixels::Color;
+
+use super::{
+    events::{EngineEvent, Events},
+    frame_clock::{EngineClock, FrameClock},
+    input::InputState,
+    profiler,
+    system::{ChimericSystem, ChimericSystemSettings, System},
+};
+
+/// how long a frame with nothing to redraw takes without min_frame_time,
+/// since there's no present to wait on vsync
+const IDLE_FRAME_TIME: Duration = Duration::from_millis(16);
+
+/// what the app does while none of its windows have the focus. a
+/// FocusPaused event is sent when it starts, and FocusResumed when it ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FocusLossPolicy {
+    /// stop the engine clock, so the world and effects stop with it
+    pub pause: bool,
+    /// multiplies the volume of every sound channel and the music, e.g. 0 to
+    /// mute. None leaves audio alone
+    pub duck_volume: Option<f32>,
+}
+
+impl Default for FocusLossPolicy {
+    /// pause and mute
+    fn default() -> Self {
+        Self {
+            pause: true,
+            duck_volume: Some(0.),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChimericAppSettings {
+    /// wait at the end of each frame so it takes at least this long. None
+    /// relies on vsync alone. see ChimericApp::clock to change it later
+    pub min_frame_time: Option<Duration>,
+    /// each window is cleared to this color before drawing
+    pub clear_color: Color,
+    /// None keeps running in the background
+    pub focus_loss: Option<FocusLossPolicy>,
+}
+
+impl Default for ChimericAppSettings {
+    fn default() -> Self {
+        Self {
+            min_frame_time: None,
+            clear_color: Color::BLACK,
+            focus_loss: None,
+        }
+    }
+}
+
+/// the mixer's volumes, on its scale of 0 to 128
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct Volumes {
+    /// by channel
+    channels: Vec<i32>,
+    music: i32,
+}
+
+impl Volumes {
+    #[cfg(feature = "mixer")]
+    fn current() -> Self {
+        use sdl2::mixer::{allocate_channels, Channel, Music};
+        Self {
+            // -1 queries the number of channels without changing it
+            channels: (0..allocate_channels(-1))
+                .map(|channel| Channel(channel).get_volume())
+                .collect(),
+            music: Music::get_volume(),
+        }
+    }
+
+    /// each channel's volume scaled on its own
+    #[cfg(any(feature = "mixer", test))]
+    fn scaled(&self, scale: f32) -> Self {
+        let scale = |volume: i32| (volume as f32 * scale).round() as i32;
+        Self {
+            channels: self.channels.iter().map(|volume| scale(*volume)).collect(),
+            music: scale(self.music),
+        }
+    }
+
+    #[cfg(feature = "mixer")]
+    fn set(&self) {
+        use sdl2::mixer::{Channel, Music};
+        for (channel, volume) in self.channels.iter().enumerate() {
+            Channel(channel as i32).set_volume(*volume);
+        }
+        Music::set_volume(self.music);
+    }
+}
+
+/// what was changed when the focus was lost, to put back when it returns
+#[derive(Debug, Clone)]
+struct FocusPause {
+    time_scale: Option<f32>,
+    volumes: Option<Volumes>,
+}
+
+/// given to the update callback once per frame
+pub struct Frame<'a> {
+    /// wall clock time since the previous frame started
+    pub elapsed: Duration,
+    /// every event received since the previous frame
+    pub events: &'a [EngineEvent],
+    /// keys and buttons, updated from this frame's events
+    pub input: &'a InputState,
+    /// smoothed delta and frame time stats
+    pub clock: &'a FrameClock,
+    /// scaled game time, for animations, tweens, trails and the world. set its
+    /// time scale for slow motion or pausing
+    pub time: &'a mut EngineClock,
+    quit: &'a mut bool,
+}
+
+impl Frame<'_> {
+    /// stop the loop once this frame has been drawn and presented
+    pub fn quit(&mut self) {
+        *self.quit = true;
+    }
+}
+
+/// owns the event pump and runs the main loop. for full control, use the
+/// ChimericSystem directly instead
+pub struct ChimericApp<'sdl> {
+    pub system: ChimericSystem<'sdl>,
+    pub settings: ChimericAppSettings,
+    pub events: Events,
+    pub input: InputState,
+    pub clock: FrameClock,
+    /// advanced by each frame's elapsed time. effects follow it
+    pub time: EngineClock,
+    focus_pause: Option<FocusPause>,
+    /// whether audio was opened, so there's a mixer to duck
+    has_audio: bool,
+}
+
+impl<'sdl> ChimericApp<'sdl> {
+    pub fn new(
+        system: &'sdl System,
+        system_settings: ChimericSystemSettings,
+        settings: ChimericAppSettings,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            system: ChimericSystem::new(system, system_settings),
+            settings,
+            events: Events::new(system)?,
+            input: InputState::new(),
+            clock: FrameClock::new(settings.min_frame_time),
+            time: EngineClock::new(),
+            focus_pause: None,
+            #[cfg(feature = "mixer")]
+            has_audio: system.mixer.is_some(),
+            #[cfg(not(feature = "mixer"))]
+            has_audio: false,
+        })
+    }
+
+    /// true while paused under the focus loss policy
+    pub fn is_focus_paused(&self) -> bool {
+        self.focus_pause.is_some()
+    }
+
+    /// pause or resume once the focus leaves or returns to the app's windows,
+    /// adding the event for it
+    fn apply_focus_policy(&mut self, events: &mut Vec<EngineEvent>) {
+        if !events
+            .iter()
+            .any(|e| matches!(e, EngineEvent::WindowFocus { .. }))
+        {
+            return;
+        }
+        let focused = self.events.focused().is_some();
+        match (self.focus_pause.take(), self.settings.focus_loss) {
+            (None, Some(policy)) if !focused => {
+                let time_scale = policy.pause.then(|| {
+                    let time_scale = self.time.time_scale;
+                    self.time.time_scale = 0.;
+                    time_scale
+                });
+                let volumes = policy
+                    .duck_volume
+                    .filter(|_| self.has_audio)
+                    .map(|duck| Self::duck(duck.clamp(0., 1.)));
+                self.focus_pause = Some(FocusPause {
+                    time_scale,
+                    volumes,
+                });
+                events.push(EngineEvent::FocusPaused);
+            }
+            (Some(pause), _) if focused => {
+                if let Some(time_scale) = pause.time_scale {
+                    self.time.time_scale = time_scale;
+                }
+                if let Some(volumes) = pause.volumes {
+                    Self::restore_volumes(volumes);
+                }
+                events.push(EngineEvent::FocusResumed);
+            }
+            (pause, _) => self.focus_pause = pause,
+        }
+    }
+
+    /// scale the mixer's volumes, returning the previous ones
+    #[cfg(feature = "mixer")]
+    fn duck(duck: f32) -> Volumes {
+        let volumes = Volumes::current();
+        volumes.scaled(duck).set();
+        volumes
+    }
+
+    #[cfg(not(feature = "mixer"))]
+    fn duck(_duck: f32) -> Volumes {
+        Volumes::default()
+    }
+
+    #[cfg(feature = "mixer")]
+    fn restore_volumes(volumes: Volumes) {
+        volumes.set();
+    }
+
+    #[cfg(not(feature = "mixer"))]
+    fn restore_volumes(_volumes: Volumes) {}
+
+    /// each frame: gather events, update, clear, draw, present, then wait out
+    /// the rest of the clock's target frame time. the state is passed to both
+    /// callbacks so neither needs to capture it
+    ///
+    /// returns once the update callback calls quit, or a quit event is
+    /// received (e.g. the last window was closed). an error from either
+    /// callback stops the loop and is returned
+    pub fn run<S, U, D>(&mut self, state: &mut S, mut update: U, mut draw: D) -> Result<(), String>
+    where
+        U: FnMut(&mut S, &mut Frame) -> Result<(), String>,
+        D: FnMut(&S, &mut ChimericSystem<'sdl>) -> Result<(), String>,
+    {
+        self.clock.reset();
+        loop {
+            let elapsed = self.clock.tick();
+            self.time.advance(elapsed);
+            profiler::new_frame();
+
+            profiler::begin_zone("events");
+            let mut events = self.events.poll(&mut self.system);
+            self.apply_focus_policy(&mut events);
+            let mut quit = events.iter().any(|e| matches!(e, EngineEvent::Quit));
+            self.input.update(&events);
+            profiler::end_zone();
+
+            profiler::begin_zone("update");
+            update(
+                state,
+                &mut Frame {
+                    elapsed,
+                    events: &events,
+                    input: &self.input,
+                    clock: &self.clock,
+                    time: &mut self.time,
+                    quit: &mut quit,
+                },
+            )?;
+            profiler::end_zone();
+
+            // zones left open by an error are ended with the frame
+            profiler::begin_zone("draw");
+            self.system.update_effects(self.time.delta());
+            // windows tracking dirty rects may have nothing to redraw
+            let redraw = self.system.needs_redraw();
+            if redraw {
+                self.system.clear(self.settings.clear_color)?;
+                draw(state, &mut self.system)?;
+            }
+            profiler::end_zone();
+            self.system.present()?;
+
+            if quit {
+                return Ok(());
+            }
+
+            if !redraw && self.clock.target().is_none() {
+                std::thread::sleep(IDLE_FRAME_TIME);
+            }
+            self.clock.limit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duck_volumes() {
+        let volumes = Volumes {
+            channels: vec![128, 40],
+            music: 64,
+        };
+        let ducked = volumes.scaled(0.5);
+        assert_eq!(ducked.channels, [64, 20]);
+        assert_eq!(ducked.music, 32);
+        // each channel keeps its own volume rather than the average
+        assert_eq!(volumes.scaled(1.), volumes);
+    }
+}
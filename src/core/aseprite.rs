@@ -0,0 +1,163 @@
+use std::{path::Path, time::Duration};
+
+use sdl2::rect::{FPoint, Rect};
+use serde_json::Value;
+
+use super::{
+    animation::{Animation, AnimationFrame, AnimationSet, PlayDirection},
+    vfs::Vfs,
+};
+
+/// the animation holding every frame when the sheet has no tags
+pub const UNTAGGED_ANIMATION: &str = "default";
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, String> {
+    value
+        .get(name)
+        .ok_or_else(|| format!("missing field \"{name}\""))
+}
+
+fn int(value: &Value, name: &str) -> Result<i64, String> {
+    field(value, name)?
+        .as_i64()
+        .ok_or_else(|| format!("field \"{name}\" isn't an integer"))
+}
+
+fn parse_frame(value: &Value) -> Result<AnimationFrame, String> {
+    let rect = field(value, "frame")?;
+    if value.get("rotated").and_then(Value::as_bool) == Some(true) {
+        return Err("rotated frames aren't supported".to_owned());
+    }
+    let offset = match value.get("spriteSourceSize") {
+        Some(source) => FPoint::new(int(source, "x")? as f32, int(source, "y")? as f32),
+        None => FPoint::new(0., 0.),
+    };
+    Ok(AnimationFrame {
+        src: Rect::new(
+            int(rect, "x")? as i32,
+            int(rect, "y")? as i32,
+            int(rect, "w")? as u32,
+            int(rect, "h")? as u32,
+        ),
+        duration: Duration::from_millis(int(value, "duration")?.max(0) as u64),
+        offset,
+    })
+}
+
+/// load a json sheet exported from aseprite (hash or array frames). each tag
+/// becomes an animation. the image is relative to the json file
+pub fn load_aseprite_json(path: &Path) -> Result<AnimationSet, String> {
+    load_aseprite_json_from(&Vfs::new(), path)
+}
+
+/// as load_aseprite_json, resolving paths through the vfs
+pub fn load_aseprite_json_from(assets: &Vfs, path: &Path) -> Result<AnimationSet, String> {
+    let content = assets.read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    load_aseprite_json_str(&content, dir).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// as load_aseprite_json, with the image relative to dir
+pub fn load_aseprite_json_str(content: &str, dir: &Path) -> Result<AnimationSet, String> {
+    let root: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    // hash exports keep the frames in file order (preserve_order)
+    let frames = match field(&root, "frames")? {
+        Value::Array(frames) => frames.iter().map(parse_frame).collect(),
+        Value::Object(frames) => frames.values().map(parse_frame).collect(),
+        _ => Err("field \"frames\" isn't an array or object".to_owned()),
+    }?;
+    let meta = field(&root, "meta")?;
+    let image = field(meta, "image")?
+        .as_str()
+        .ok_or_else(|| "field \"image\" isn't a string".to_owned())?;
+
+    let mut set = AnimationSet {
+        image: dir.join(image),
+        animations: Default::default(),
+    };
+    let tags = meta
+        .get("frameTags")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    if tags.is_empty() {
+        set.animations.insert(
+            UNTAGGED_ANIMATION.to_owned(),
+            Animation {
+                frames,
+                direction: PlayDirection::Forward,
+                repeat: None,
+            },
+        );
+        return Ok(set);
+    }
+    for tag in tags {
+        let name = field(tag, "name")?
+            .as_str()
+            .ok_or_else(|| "tag name isn't a string".to_owned())?;
+        let (from, to) = (int(tag, "from")? as usize, int(tag, "to")? as usize);
+        let tag_frames = frames
+            .get(from..=to)
+            .ok_or_else(|| format!("tag \"{name}\" has frames out of range"))?;
+        let direction = match tag.get("direction").and_then(Value::as_str) {
+            None | Some("forward") => PlayDirection::Forward,
+            Some("reverse") => PlayDirection::Reverse,
+            Some("pingpong") => PlayDirection::PingPong,
+            Some("pingpong_reverse") => PlayDirection::PingPongReverse,
+            Some(other) => return Err(format!("tag \"{name}\" has unknown direction \"{other}\"")),
+        };
+        // exported as a string, and omitted when the tag loops forever
+        let repeat = match tag.get("repeat") {
+            None => None,
+            Some(Value::String(s)) => Some(
+                s.parse()
+                    .map_err(|_| format!("tag \"{name}\" has invalid repeat \"{s}\""))?,
+            ),
+            Some(v) => v.as_u64().map(|n| n as u32),
+        }
+        .filter(|n| *n != 0);
+        set.animations.insert(
+            name.to_owned(),
+            Animation {
+                frames: tag_frames.to_vec(),
+                direction,
+                repeat,
+            },
+        );
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = r#"{
+ "frames": {
+  "hero 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false, "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 }, "sourceSize": { "w": 16, "h": 16 }, "duration": 100 },
+  "hero 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 14, "h": 15 }, "rotated": false, "trimmed": true, "spriteSourceSize": { "x": 1, "y": 1, "w": 14, "h": 15 }, "sourceSize": { "w": 16, "h": 16 }, "duration": 150 },
+  "hero 10.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false, "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 }, "sourceSize": { "w": 16, "h": 16 }, "duration": 50 }
+ },
+ "meta": {
+  "image": "hero.png",
+  "frameTags": [
+   { "name": "idle", "from": 0, "to": 1, "direction": "pingpong" },
+   { "name": "attack", "from": 2, "to": 2, "direction": "forward", "repeat": "1" }
+  ]
+ }
+}"#;
+
+    #[test]
+    fn test_load() {
+        let set = load_aseprite_json_str(SHEET, Path::new("sprites")).unwrap();
+        assert_eq!(set.image, Path::new("sprites/hero.png"));
+        let idle = set.get("idle").unwrap();
+        assert_eq!(idle.direction, PlayDirection::PingPong);
+        assert_eq!(idle.repeat, None);
+        assert_eq!(idle.frames[1].offset, FPoint::new(1., 1.));
+        assert_eq!(idle.frames[1].duration, Duration::from_millis(150));
+        let attack = set.get("attack").unwrap();
+        assert_eq!(attack.repeat, Some(1));
+        assert_eq!(attack.frames[0].src, Rect::new(32, 0, 16, 16));
+    }
+}
@@ -1,10 +1,22 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, num::NonZeroUsize, path::Path, time::Duration};
 
-use compact_str::CompactString;
 use lru::LruCache;
-use sdl2::{mixer::Chunk, AudioSubsystem};
+use sdl2::{
+    mixer::{Chunk, LoaderRWops},
+    rwops::RWops,
+};
 
-use super::constants;
+use super::{
+    captions::Captions,
+    system::System,
+    trace::{log_trace, log_warn},
+    vfs::Vfs,
+};
+
+const MAX_LOADED_SOUNDS: NonZeroUsize = match NonZeroUsize::new(64) {
+    Some(n) => n,
+    None => unreachable!(),
+};
 
 /// make chunk depend on audio system
 struct ChunkEntry<'sdl> {
@@ -13,20 +25,36 @@ struct ChunkEntry<'sdl> {
 }
 
 pub struct AudioSystem<'sdl> {
-    chunks: LruCache<CompactString, ChunkEntry<'sdl>>,
+    /// None if the system has no audio; playing is then a no-op
+    chunks: Option<LruCache<String, ChunkEntry<'sdl>>>,
 }
 
 impl<'sdl> AudioSystem<'sdl> {
-    pub fn new(_audio: &'sdl AudioSubsystem) -> Self {
+    pub fn new(system: &'sdl System) -> Self {
         Self {
-            chunks: LruCache::new(constants::MAX_LOADED_SOUNDS),
+            chunks: system
+                .mixer
+                .is_some()
+                .then(|| LruCache::new(MAX_LOADED_SOUNDS)),
         }
     }
 
-    pub fn play(&'sdl mut self, path: &str) -> Result<(), String> {
-        let ret = self.chunks.try_get_or_insert_mut(path.into(), || -> Result<ChunkEntry, String> {
-            let chunk = Chunk::from_file(path)?;
-            // guaranteed not null. otherwise, from_file would return error and
+    /// false if audio couldn't be opened (see AudioFallback)
+    pub fn is_enabled(&self) -> bool {
+        self.chunks.is_some()
+    }
+
+    /// the sound at the path, resolved through the vfs
+    pub fn play(&mut self, assets: &Vfs, path: &str) -> Result<(), String> {
+        let Some(chunks) = self.chunks.as_mut() else {
+            log_trace!("no audio; skipped {path}");
+            return Ok(());
+        };
+        let ret = chunks.try_get_or_insert_mut(path.into(), || -> Result<ChunkEntry, String> {
+            // the chunk is decoded from the bytes, so they aren't kept
+            let data = assets.read(Path::new(path))?;
+            let chunk = RWops::from_bytes(&data)?.load_wav()?;
+            // guaranteed not null. otherwise, load_wav would return error and
             // not reach here
             Ok(ChunkEntry{chunk, _phantom: PhantomData })
         })?;
@@ -35,7 +63,27 @@ impl<'sdl> AudioSystem<'sdl> {
         // chunk's volume is set then this will effect previous chunks that are
         // still playing. too complicated and not worth it, at least for now
 
-        sdl2::mixer::Channel::all().play(&ret.chunk, 0);
+        if let Err(e) = sdl2::mixer::Channel::all().play(&ret.chunk, 0) {
+            // every channel is busy
+            log_warn!("couldn't play {path}: {e}");
+            return Err(e);
+        }
         Ok(())
     }
+
+    /// play the sound and show the caption key's lines, timed from now (e.g.
+    /// EngineClock::total). the captions are shown even without audio
+    pub fn play_captioned(
+        &mut self,
+        assets: &Vfs,
+        path: &str,
+        caption_key: &str,
+        captions: &mut Captions,
+        now: Duration,
+    ) -> Result<(), String> {
+        if !captions.start(caption_key, now) {
+            log_warn!("no caption \"{caption_key}\" for {path}");
+        }
+        self.play(assets, path)
+    }
 }
@@ -0,0 +1,142 @@
+use sdl2::rect::{FPoint, FRect};
+
+/// maps between world coordinates and a window's logical coordinates. the
+/// camera's position is the world point shown at the center of the view
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    pub position: FPoint,
+    /// screen pixels per world unit
+    pub zoom: f32,
+    /// degrees, clockwise
+    pub rotation: f32,
+    /// the logical size of the view in screen pixels
+    pub viewport: (f32, f32),
+}
+
+impl Camera2D {
+    pub fn new(viewport_width: f32, viewport_height: f32) -> Self {
+        Self {
+            position: FPoint::new(0., 0.),
+            zoom: 1.,
+            rotation: 0.,
+            viewport: (viewport_width, viewport_height),
+        }
+    }
+
+    fn center(&self) -> FPoint {
+        FPoint::new(self.viewport.0 / 2., self.viewport.1 / 2.)
+    }
+
+    fn rotate(p: FPoint, degrees: f32) -> FPoint {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        FPoint::new(p.x() * cos - p.y() * sin, p.x() * sin + p.y() * cos)
+    }
+
+    pub fn world_to_screen(&self, world: FPoint) -> FPoint {
+        let relative = Self::rotate(world - self.position, -self.rotation);
+        relative * self.zoom + self.center()
+    }
+
+    pub fn screen_to_world(&self, screen: FPoint) -> FPoint {
+        let relative = (screen - self.center()) * (1. / self.zoom);
+        Self::rotate(relative, self.rotation) + self.position
+    }
+
+    /// the bounding box of the world region in view
+    pub fn visible_region(&self) -> FRect {
+        let (w, h) = self.viewport;
+        let corners = [
+            self.screen_to_world(FPoint::new(0., 0.)),
+            self.screen_to_world(FPoint::new(w, 0.)),
+            self.screen_to_world(FPoint::new(0., h)),
+            self.screen_to_world(FPoint::new(w, h)),
+        ];
+        let min_x = corners.iter().map(|c| c.x()).fold(f32::INFINITY, f32::min);
+        let min_y = corners.iter().map(|c| c.y()).fold(f32::INFINITY, f32::min);
+        let max_x = corners
+            .iter()
+            .map(|c| c.x())
+            .fold(f32::NEG_INFINITY, f32::max);
+        let max_y = corners
+            .iter()
+            .map(|c| c.y())
+            .fold(f32::NEG_INFINITY, f32::max);
+        FRect::new(min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+}
+
+/// map a point in window pixels to logical coordinates, given the window's
+/// output size and the logical size it's letterboxed into. returns None if the
+/// point lies in the letterbox bars
+///
+/// sdl already does this for mouse events when the canvas has a logical size;
+/// this is for positions from elsewhere (e.g. queried mouse state)
+pub fn window_to_logical(
+    point: FPoint,
+    output_size: (u32, u32),
+    logical_size: (u32, u32),
+) -> Option<FPoint> {
+    if logical_size.0 == 0 || logical_size.1 == 0 {
+        return Some(point); // no logical size
+    }
+    let logical = window_to_logical_unbounded(point, output_size, logical_size);
+    in_logical_area(logical, logical_size).then_some(logical)
+}
+
+/// as window_to_logical, but points in the letterbox bars are mapped outside
+/// of the logical area instead of being rejected
+pub fn window_to_logical_unbounded(
+    point: FPoint,
+    output_size: (u32, u32),
+    logical_size: (u32, u32),
+) -> FPoint {
+    let (out_w, out_h) = (output_size.0 as f32, output_size.1 as f32);
+    let (log_w, log_h) = (logical_size.0 as f32, logical_size.1 as f32);
+    if log_w <= 0. || log_h <= 0. {
+        return point; // no logical size
+    }
+    let scale = (out_w / log_w).min(out_h / log_h);
+    let offset = FPoint::new((out_w - log_w * scale) / 2., (out_h - log_h * scale) / 2.);
+    (point - offset) * (1. / scale)
+}
+
+pub(crate) fn in_logical_area(point: FPoint, logical_size: (u32, u32)) -> bool {
+    point.x() >= 0.
+        && point.y() >= 0.
+        && point.x() < logical_size.0 as f32
+        && point.y() < logical_size.1 as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: FPoint, b: FPoint) -> bool {
+        (a.x() - b.x()).abs() < 1e-3 && (a.y() - b.y()).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let camera = Camera2D {
+            position: FPoint::new(100., 50.),
+            zoom: 2.,
+            rotation: 30.,
+            viewport: (320., 240.),
+        };
+        let world = FPoint::new(120., 40.);
+        let screen = camera.world_to_screen(world);
+        assert!(close(camera.screen_to_world(screen), world));
+        assert!(close(
+            camera.world_to_screen(camera.position),
+            FPoint::new(160., 120.)
+        ));
+    }
+
+    #[test]
+    fn test_letterbox() {
+        // 4:3 logical in a 16:9 window has bars on the left and right
+        let logical = window_to_logical(FPoint::new(960., 540.), (1920, 1080), (320, 240));
+        assert!(close(logical.unwrap(), FPoint::new(160., 120.)));
+        assert!(window_to_logical(FPoint::new(10., 540.), (1920, 1080), (320, 240)).is_none());
+    }
+}
@@ -0,0 +1,254 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+#[cfg(feature = "ttf")]
+use std::ffi::CString;
+
+use sdl2::{pixels::Color, rect::Rect};
+
+#[cfg(feature = "ttf")]
+use super::{renderer::DrawParams, system::ChimericSystem};
+#[cfg(feature = "ttf")]
+use sdl2::rect::FRect;
+
+/// a subtitle line, timed from when its sound starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionLine {
+    pub start: Duration,
+    pub duration: Duration,
+    pub text: String,
+}
+
+/// the caption lines for each caption key, e.g. one table per language
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaptionTable {
+    captions: HashMap<String, Vec<CaptionLine>>,
+}
+
+fn seconds(table: &toml::Table, key: &str) -> Result<Duration, String> {
+    let value = table
+        .get(key)
+        .ok_or_else(|| format!("caption line is missing \"{}\"", key))?;
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|i| i as f64))
+        .and_then(|s| Duration::try_from_secs_f64(s).ok())
+        .ok_or_else(|| format!("\"{}\" isn't a non negative number of seconds", key))
+}
+
+impl CaptionTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, key: &str, lines: Vec<CaptionLine>) {
+        self.captions.insert(key.to_owned(), lines);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[CaptionLine]> {
+        self.captions.get(key).map(Vec::as_slice)
+    }
+
+    /// an array of lines per key, each with a start and duration in seconds:
+    ///
+    /// ```toml
+    /// [[door_creak]]
+    /// start = 0.0
+    /// duration = 1.5
+    /// text = "[a door creaks open]"
+    /// ```
+    pub fn from_toml(table: &toml::Table) -> Result<Self, String> {
+        let mut captions = Self::new();
+        for (key, lines) in table.iter() {
+            let lines = lines
+                .as_array()
+                .ok_or_else(|| format!("captions for \"{}\" aren't an array", key))?;
+            let lines = lines
+                .iter()
+                .map(|line| {
+                    let line = line
+                        .as_table()
+                        .ok_or_else(|| format!("a caption line for \"{}\" isn't a table", key))?;
+                    let text = line
+                        .get("text")
+                        .and_then(|t| t.as_str())
+                        .ok_or_else(|| format!("a caption line for \"{}\" has no text", key))?;
+                    Ok(CaptionLine {
+                        start: seconds(line, "start")?,
+                        duration: seconds(line, "duration")?,
+                        text: text.to_owned(),
+                    })
+                })
+                .collect::<Result<_, String>>()?;
+            captions.insert(key, lines);
+        }
+        Ok(captions)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        Self::from_toml(&table)
+    }
+}
+
+/// shows the captions of sounds as they play, in a region of a window. see
+/// AudioSystem::play_captioned
+#[derive(Debug, Clone)]
+pub struct Captions {
+    pub table: CaptionTable,
+    /// (when the sound started, its key)
+    playing: Vec<(Duration, String)>,
+    pub enabled: bool,
+    /// lines are centered in it, the latest at the bottom
+    pub region: Rect,
+    /// behind each line
+    pub background: Color,
+    pub text_padding: u32,
+}
+
+impl Captions {
+    pub fn new(table: CaptionTable, region: Rect) -> Self {
+        Self {
+            table,
+            playing: Default::default(),
+            enabled: true,
+            region,
+            background: Color::RGBA(0, 0, 0, 180),
+            text_padding: 4,
+        }
+    }
+
+    /// show the key's lines, timed from now (e.g. EngineClock::total). false
+    /// if the table has no such key
+    pub fn start(&mut self, key: &str, now: Duration) -> bool {
+        if self.table.get(key).is_none() {
+            return false;
+        }
+        self.playing.push((now, key.to_owned()));
+        true
+    }
+
+    /// drop every caption, e.g. when the sounds are stopped
+    pub fn clear(&mut self) {
+        self.playing.clear();
+    }
+
+    /// forget the sounds whose lines have all been shown
+    pub fn update(&mut self, now: Duration) {
+        let table = &self.table;
+        self.playing.retain(|(started, key)| {
+            table.get(key).is_some_and(|lines| {
+                lines
+                    .iter()
+                    .any(|line| *started + line.start + line.duration > now)
+            })
+        });
+    }
+
+    /// the lines showing now, oldest first
+    pub fn visible(&self, now: Duration) -> Vec<&str> {
+        let mut visible: Vec<(Duration, &str)> = self
+            .playing
+            .iter()
+            .filter_map(|(started, key)| Some((*started, self.table.get(key)?)))
+            .flat_map(|(started, lines)| {
+                lines.iter().filter_map(move |line| {
+                    let start = started + line.start;
+                    (start <= now && now < start + line.duration)
+                        .then_some((start, line.text.as_str()))
+                })
+            })
+            .collect();
+        visible.sort_by_key(|(start, _)| *start);
+        visible.into_iter().map(|(_, text)| text).collect()
+    }
+
+    /// draw the visible lines, as many as fit in the region
+    #[cfg(feature = "ttf")]
+    pub fn draw(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        font_file: &Path,
+        point_size: u16,
+        now: Duration,
+    ) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let padding = self.text_padding as i32;
+        let mut bottom = self.region.bottom();
+        for text in self.visible(now).into_iter().rev() {
+            let text = CString::new(text).map_err(|e| e.to_string())?;
+            let (w, h) = system.text_size(font_file, point_size, &text)?;
+            let top = bottom - h as i32 - padding * 2;
+            if top < self.region.top() {
+                break;
+            }
+            let x = self.region.center().x() - w as i32 / 2;
+            let back = FRect::new(
+                (x - padding) as f32,
+                top as f32,
+                (w as i32 + padding * 2) as f32,
+                (h as i32 + padding * 2) as f32,
+            );
+            system.fill_rect(window_name, back, self.background)?;
+            let dst = FRect::new(x as f32, (top + padding) as f32, w as f32, h as f32);
+            system.draw_text(
+                window_name,
+                font_file,
+                point_size,
+                &text,
+                None,
+                &DrawParams::new(None, Some(dst)),
+            )?;
+            bottom = top;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captions() {
+        let table: toml::Table = r#"
+            [[door]]
+            start = 0
+            duration = 1.5
+            text = "[a door creaks open]"
+
+            [[door]]
+            start = 1.0
+            duration = 1.0
+            text = "who's there?"
+        "#
+        .parse()
+        .unwrap();
+        let table = CaptionTable::from_toml(&table).unwrap();
+        assert_eq!(table.get("door").unwrap().len(), 2);
+
+        let mut captions = Captions::new(table, Rect::new(0, 400, 640, 80));
+        let secs = Duration::from_secs_f32;
+        assert!(!captions.start("missing", secs(0.)));
+        assert!(captions.start("door", secs(10.)));
+        assert_eq!(captions.visible(secs(10.5)), ["[a door creaks open]"]);
+        assert_eq!(
+            captions.visible(secs(11.25)),
+            ["[a door creaks open]", "who's there?"]
+        );
+        captions.update(secs(11.5));
+        assert_eq!(captions.visible(secs(11.75)), ["who's there?"]);
+        captions.update(secs(12.));
+        assert!(captions.visible(secs(11.75)).is_empty());
+
+        let bad: toml::Table = "[[door]]\nstart = -1\nduration = 1\ntext = \"\""
+            .parse()
+            .unwrap();
+        assert!(CaptionTable::from_toml(&bad).is_err());
+    }
+}
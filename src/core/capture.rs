@@ -0,0 +1,258 @@
+use std::{collections::VecDeque, path::Path, time::Duration};
+
+use sdl2::{pixels::PixelFormatEnum, rect::Rect, render::BlendMode, surface::Surface};
+
+use super::system::ChimericSystem;
+
+/// a downscaled copy of a window's contents
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    /// tightly packed rows, 4 bytes per pixel
+    pub rgba: Vec<u8>,
+}
+
+/// grabs frames from a window at a fixed rate, for sharing clips and for bug
+/// reports. with a max duration set it keeps a rolling clip of the most recent
+/// frames, so it can run in the background and be saved after the fact
+pub struct Recorder {
+    pub window: String,
+    fps: u32,
+    /// frames wider than this are scaled down, keeping the aspect ratio
+    pub max_width: u32,
+    max_duration: Option<Duration>,
+    frames: VecDeque<CapturedFrame>,
+    /// time since the last frame was captured
+    elapsed: Duration,
+    recording: bool,
+}
+
+impl Recorder {
+    pub fn new(window: &str, fps: u32, max_width: u32) -> Self {
+        Self {
+            window: window.to_owned(),
+            fps: fps.max(1),
+            max_width: max_width.max(1),
+            max_duration: None,
+            frames: Default::default(),
+            elapsed: Duration::ZERO,
+            recording: false,
+        }
+    }
+
+    /// only keep the most recent frames
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.fps
+    }
+
+    /// the next update captures a frame
+    pub fn start(&mut self) {
+        self.recording = true;
+        self.elapsed = self.frame_interval();
+    }
+
+    /// captured frames are kept
+    pub fn stop(&mut self) {
+        self.recording = false;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &CapturedFrame> {
+        self.frames.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// length of the clip
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.frames.len() as u64) / self.fps
+    }
+
+    /// call once per frame after drawing and before presenting. captures when a
+    /// frame is due. when the game runs slower than the capture rate, frames
+    /// are dropped rather than duplicated
+    pub fn update(&mut self, system: &ChimericSystem, dt: Duration) -> Result<(), String> {
+        if !self.recording {
+            return Ok(());
+        }
+        self.elapsed += dt;
+        let interval = self.frame_interval();
+        if self.elapsed < interval {
+            return Ok(());
+        }
+        self.elapsed = Duration::from_nanos((self.elapsed.as_nanos() % interval.as_nanos()) as u64);
+        let frame = self.capture(system)?;
+        self.push(frame);
+        Ok(())
+    }
+
+    /// grab a frame from the window now, without adding it to the clip
+    pub fn capture(&self, system: &ChimericSystem) -> Result<CapturedFrame, String> {
+        let mut screenshot = system.screenshot(&self.window)?;
+        screenshot.set_blend_mode(BlendMode::None)?;
+        let (width, height) = scaled_size(screenshot.width(), screenshot.height(), self.max_width);
+        let format = PixelFormatEnum::RGBA32;
+        let mut scaled = Surface::new(width, height, format)?;
+        screenshot.blit_scaled(None, &mut scaled, Rect::new(0, 0, width, height))?;
+        let row_len = width as usize * format.byte_size_per_pixel();
+        let pitch = scaled.pitch() as usize;
+        let mut rgba = Vec::with_capacity(row_len * height as usize);
+        scaled.with_lock(|pixels| {
+            for row in 0..height as usize {
+                rgba.extend_from_slice(&pixels[row * pitch..row * pitch + row_len]);
+            }
+        });
+        Ok(CapturedFrame {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    /// add a frame to the end of the clip, dropping the oldest frames past the
+    /// max duration
+    pub fn push(&mut self, frame: CapturedFrame) {
+        self.frames.push_back(frame);
+        if let Some(max_duration) = self.max_duration {
+            let max_frames = (max_duration.as_secs_f64() * self.fps as f64).ceil() as usize;
+            while self.frames.len() > max_frames.max(1) {
+                self.frames.pop_front();
+            }
+        }
+    }
+
+    /// gif delays are in hundredths of a second. rounding is spread over the
+    /// frames so the clip keeps the right overall speed
+    #[cfg(any(feature = "gif", test))]
+    fn delays(&self) -> impl Iterator<Item = u16> + '_ {
+        let at = |i: usize| (i as u64 * 100 + self.fps as u64 / 2) / self.fps as u64;
+        (0..self.frames.len()).map(move |i| (at(i + 1) - at(i)) as u16)
+    }
+
+    /// encode the clip as a looping gif. frames are quantized to 256 colors
+    /// each; slow for long clips
+    #[cfg(feature = "gif")]
+    pub fn to_gif(&self) -> Result<Vec<u8>, String> {
+        let width = self
+            .frames
+            .iter()
+            .map(|f| f.width)
+            .max()
+            .ok_or("no frames captured")?;
+        let height = self.frames.iter().map(|f| f.height).max().unwrap_or(1);
+        let mut out = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut out, width as u16, height as u16, &[])
+                .map_err(|e| e.to_string())?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(|e| e.to_string())?;
+            for (frame, delay) in self.frames.iter().zip(self.delays()) {
+                let mut rgba = frame.rgba.clone();
+                let mut encoded = gif::Frame::from_rgba_speed(
+                    frame.width as u16,
+                    frame.height as u16,
+                    &mut rgba,
+                    10,
+                );
+                encoded.delay = delay;
+                encoder.write_frame(&encoded).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(out)
+    }
+
+    #[cfg(feature = "gif")]
+    pub fn write_gif(&self, path: &Path) -> Result<(), String> {
+        super::save::write_atomic(path, &self.to_gif()?)
+    }
+
+    /// without an encoder, frames can still be written out as individual
+    /// images (frame_0000.png, ...) to be assembled by an external tool. bmp
+    /// without the image feature
+    pub fn write_frames(&self, dir: &Path) -> Result<(), String> {
+        let extension = if cfg!(feature = "image") { "png" } else { "bmp" };
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let mut rgba = frame.rgba.clone();
+            let surface = Surface::from_data(
+                &mut rgba,
+                frame.width,
+                frame.height,
+                frame.width * 4,
+                PixelFormatEnum::RGBA32,
+            )?;
+            super::decode::save_surface(&surface, &dir.join(format!("frame_{i:04}.{extension}")))?;
+        }
+        Ok(())
+    }
+}
+
+/// at most max_width wide, keeping the aspect ratio
+fn scaled_size(width: u32, height: u32, max_width: u32) -> (u32, u32) {
+    if width <= max_width {
+        return (width.max(1), height.max(1));
+    }
+    let height = (height as u64 * max_width as u64 / width as u64).max(1) as u32;
+    (max_width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> CapturedFrame {
+        CapturedFrame {
+            width: 2,
+            height: 1,
+            rgba: vec![255, 0, 0, 255, 0, 0, 255, 255],
+        }
+    }
+
+    #[test]
+    fn test_rolling_clip() {
+        let mut recorder = Recorder::new("main", 30, 320).with_max_duration(Duration::from_secs(1));
+        for _ in 0..45 {
+            recorder.push(frame());
+        }
+        assert_eq!(recorder.len(), 30);
+        assert_eq!(recorder.duration(), Duration::from_secs(1));
+        // 30 fps doesn't divide evenly into hundredths of a second
+        assert_eq!(recorder.delays().map(|d| d as u32).sum::<u32>(), 100);
+        assert_eq!(scaled_size(1920, 1080, 320), (320, 180));
+        assert_eq!(scaled_size(200, 100, 320), (200, 100));
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn test_gif() {
+        let mut recorder = Recorder::new("main", 10, 320);
+        recorder.push(frame());
+        recorder.push(frame());
+        assert!(recorder.to_gif().unwrap().starts_with(b"GIF89a"));
+        assert!(Recorder::new("main", 10, 320).to_gif().is_err());
+    }
+}
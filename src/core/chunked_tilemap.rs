@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use sdl2::rect::{FPoint, FRect};
+
+use super::{
+    camera::Camera2D,
+    pathfinding::CostGrid,
+    rng::stable_hash,
+    system::ChimericSystem,
+    tiled::{LayerCache, TiledMap},
+    tilemap::{TileLayer, EMPTY_TILE},
+};
+
+/// a chunk's position, in chunks from the one at the origin
+pub type ChunkCoord = (i32, i32);
+
+/// fills in chunks as they're loaded, e.g. by reading them from a save or
+/// generating them. closures taking the coord and the empty chunk are sources
+pub trait ChunkSource {
+    /// the layer is empty, and the chunk's size
+    fn load(&mut self, coord: ChunkCoord, layer: &mut TileLayer) -> Result<(), String>;
+
+    /// called before a chunk is dropped, e.g. to save what was dug out of it
+    fn unload(&mut self, _coord: ChunkCoord, _layer: &TileLayer) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<F> ChunkSource for F
+where
+    F: FnMut(ChunkCoord, &mut TileLayer) -> Result<(), String>,
+{
+    fn load(&mut self, coord: ChunkCoord, layer: &mut TileLayer) -> Result<(), String> {
+        self(coord, layer)
+    }
+}
+
+/// a seed for generating a chunk, the same each time it's loaded. e.g. for
+/// Rng::new, or a MapGenerator run per chunk
+pub fn chunk_seed(seed: u64, coord: ChunkCoord) -> u64 {
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    bytes[8..12].copy_from_slice(&coord.0.to_le_bytes());
+    bytes[12..].copy_from_slice(&coord.1.to_le_bytes());
+    stable_hash(&bytes)
+}
+
+struct Chunk {
+    layer: TileLayer,
+    cache: LayerCache,
+}
+
+/// the chunks loaded and unloaded by an update
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkChanges {
+    pub loaded: Vec<ChunkCoord>,
+    pub unloaded: Vec<ChunkCoord>,
+}
+
+/// an unbounded tile map split into square chunks, which are loaded from a
+/// source as they come near the view and dropped once far enough from it.
+/// tile coordinates are global and may be negative; the origin is the top
+/// left of chunk (0, 0). each chunk keeps its own draw cache
+pub struct ChunkedTileMap {
+    /// tiles per side of a chunk
+    chunk_size: u32,
+    tile_width: f32,
+    tile_height: f32,
+    source: Box<dyn ChunkSource>,
+    chunks: HashMap<ChunkCoord, Chunk>,
+    /// chunks within this many chunks of the view are loaded
+    pub load_radius: u32,
+    /// loaded chunks are kept until they're further than this many chunks
+    /// from the view. at least load_radius, so chunks at the edge aren't
+    /// loaded and unloaded as the view moves back and forth
+    pub unload_radius: u32,
+    /// chunks loaded per update, nearest first, to spread loading over
+    /// frames. None loads every chunk needed at once
+    pub max_loads: Option<usize>,
+}
+
+impl ChunkedTileMap {
+    pub fn new(
+        chunk_size: u32,
+        tile_width: f32,
+        tile_height: f32,
+        source: Box<dyn ChunkSource>,
+    ) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            tile_width,
+            tile_height,
+            source,
+            chunks: Default::default(),
+            load_radius: 1,
+            unload_radius: 2,
+            max_loads: None,
+        }
+    }
+
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    pub fn tile_width(&self) -> f32 {
+        self.tile_width
+    }
+
+    pub fn tile_height(&self) -> f32 {
+        self.tile_height
+    }
+
+    /// the world size of a chunk
+    fn chunk_extent(&self) -> (f32, f32) {
+        (
+            self.chunk_size as f32 * self.tile_width,
+            self.chunk_size as f32 * self.tile_height,
+        )
+    }
+
+    /// the chunk containing the tile, and the tile's position in it
+    pub fn chunk_of(&self, x: i32, y: i32) -> (ChunkCoord, (i32, i32)) {
+        let size = self.chunk_size as i32;
+        (
+            (x.div_euclid(size), y.div_euclid(size)),
+            (x.rem_euclid(size), y.rem_euclid(size)),
+        )
+    }
+
+    /// the tile coordinate containing the point
+    pub fn tile_at(&self, point: FPoint) -> (i32, i32) {
+        (
+            (point.x() / self.tile_width).floor() as i32,
+            (point.y() / self.tile_height).floor() as i32,
+        )
+    }
+
+    /// the world area covered by the chunk
+    pub fn chunk_rect(&self, coord: ChunkCoord) -> FRect {
+        let (w, h) = self.chunk_extent();
+        FRect::new(coord.0 as f32 * w, coord.1 as f32 * h, w, h)
+    }
+
+    /// None if the chunk isn't loaded
+    pub fn chunk(&self, coord: ChunkCoord) -> Option<&TileLayer> {
+        self.chunks.get(&coord).map(|chunk| &chunk.layer)
+    }
+
+    pub fn chunk_mut(&mut self, coord: ChunkCoord) -> Option<&mut TileLayer> {
+        self.chunks.get_mut(&coord).map(|chunk| &mut chunk.layer)
+    }
+
+    /// the coords of the loaded chunks, in no particular order
+    pub fn loaded(&self) -> impl Iterator<Item = ChunkCoord> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub fn is_loaded(&self, coord: ChunkCoord) -> bool {
+        self.chunks.contains_key(&coord)
+    }
+
+    /// None if the tile's chunk isn't loaded
+    pub fn get(&self, x: i32, y: i32) -> Option<u32> {
+        let (coord, (lx, ly)) = self.chunk_of(x, y);
+        self.chunk(coord)?.get(lx, ly)
+    }
+
+    /// returns the previous tile, or None (and does nothing) if the tile's
+    /// chunk isn't loaded
+    pub fn set(&mut self, x: i32, y: i32, tile: u32) -> Option<u32> {
+        let (coord, (lx, ly)) = self.chunk_of(x, y);
+        self.chunk_mut(coord)?.set(lx, ly, tile)
+    }
+
+    /// true if loaded and not empty
+    pub fn is_occupied(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_some_and(|tile| tile != EMPTY_TILE)
+    }
+
+    /// the chunks (x0, y0, x1, y1), inclusive, overlapping the region grown
+    /// by a number of chunks on each side
+    fn chunks_around(&self, region: FRect, radius: u32) -> (i32, i32, i32, i32) {
+        let (w, h) = self.chunk_extent();
+        let radius = radius as i32;
+        (
+            (region.left() / w).floor() as i32 - radius,
+            (region.top() / h).floor() as i32 - radius,
+            (region.right() / w).floor() as i32 + radius,
+            (region.bottom() / h).floor() as i32 + radius,
+        )
+    }
+
+    /// load the chunks near the view (a world region, e.g. from
+    /// Camera2D::visible_region) and unload the far ones. chunks which fail
+    /// to load are left unloaded, to be tried again next update
+    pub fn update(&mut self, view: FRect) -> Result<ChunkChanges, String> {
+        let mut changes = ChunkChanges::default();
+
+        let (x0, y0, x1, y1) = self.chunks_around(view, self.unload_radius.max(self.load_radius));
+        let mut far: Vec<ChunkCoord> = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&(x, y)| x < x0 || x > x1 || y < y0 || y > y1)
+            .collect();
+        far.sort_unstable();
+        for coord in far {
+            let chunk = self.chunks.remove(&coord).expect("loaded");
+            self.source.unload(coord, &chunk.layer)?;
+            changes.unloaded.push(coord);
+        }
+
+        let (x0, y0, x1, y1) = self.chunks_around(view, self.load_radius);
+        let center = FPoint::new(view.x() + view.width() / 2., view.y() + view.height() / 2.);
+        let mut missing: Vec<(f32, ChunkCoord)> = (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .filter(|coord| !self.chunks.contains_key(coord))
+            .map(|coord| {
+                let rect = self.chunk_rect(coord);
+                let dx = rect.x() + rect.width() / 2. - center.x();
+                let dy = rect.y() + rect.height() / 2. - center.y();
+                (dx * dx + dy * dy, coord)
+            })
+            .collect();
+        missing.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let count = self.max_loads.unwrap_or(missing.len());
+        for (_, coord) in missing.into_iter().take(count) {
+            let mut layer = TileLayer::new(
+                self.chunk_size,
+                self.chunk_size,
+                self.tile_width,
+                self.tile_height,
+            );
+            self.source.load(coord, &mut layer)?;
+            self.chunks.insert(
+                coord,
+                Chunk {
+                    layer,
+                    cache: LayerCache::new(),
+                },
+            );
+            changes.loaded.push(coord);
+        }
+        Ok(changes)
+    }
+
+    /// unload every chunk, e.g. so the source saves them before quitting
+    pub fn unload_all(&mut self) -> Result<(), String> {
+        let mut coords: Vec<ChunkCoord> = self.chunks.keys().copied().collect();
+        coords.sort_unstable();
+        for coord in coords {
+            let chunk = self.chunks.remove(&coord).expect("loaded");
+            self.source.unload(coord, &chunk.layer)?;
+        }
+        Ok(())
+    }
+
+    /// draw the loaded chunks in view with the map's tilesets. each chunk's
+    /// tiles are looked up through its own cache, so only changed tiles are
+    /// looked up again
+    pub fn draw(
+        &mut self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        tilesets: &TiledMap,
+        camera: &Camera2D,
+    ) -> Result<(), String> {
+        let (x0, y0, x1, y1) = self.chunks_around(camera.visible_region(), 0);
+        let (w, h) = self.chunk_extent();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let Some(chunk) = self.chunks.get_mut(&(x, y)) else {
+                    continue;
+                };
+                // the chunk's layer is at the origin, so move the camera
+                // instead
+                let mut local = *camera;
+                local.position = camera.position - FPoint::new(x as f32 * w, y as f32 * h);
+                tilesets.draw_layer_cached(
+                    system,
+                    window_name,
+                    &chunk.layer,
+                    &local,
+                    &mut chunk.cache,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// empty loaded tiles are passable with a cost of one. unloaded ones aren't
+impl CostGrid for ChunkedTileMap {
+    fn cost(&self, x: i32, y: i32) -> Option<f32> {
+        match self.get(x, y) {
+            Some(EMPTY_TILE) => Some(1.),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    /// a floor along y = 0, and what was unloaded
+    struct Floor {
+        unloaded: Rc<RefCell<Vec<(ChunkCoord, u32)>>>,
+    }
+
+    impl ChunkSource for Floor {
+        fn load(&mut self, coord: ChunkCoord, layer: &mut TileLayer) -> Result<(), String> {
+            if coord.1 == 0 {
+                for x in 0..layer.width() as i32 {
+                    layer.set(x, 0, 1);
+                }
+            }
+            Ok(())
+        }
+
+        fn unload(&mut self, coord: ChunkCoord, layer: &TileLayer) -> Result<(), String> {
+            let tiles = layer.tiles().iter().filter(|&&t| t != EMPTY_TILE).count();
+            self.unloaded.borrow_mut().push((coord, tiles as u32));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_chunks() {
+        let unloaded = Rc::new(RefCell::new(Vec::new()));
+        let source = Floor {
+            unloaded: unloaded.clone(),
+        };
+        // 8 tiles of 10 units per chunk side
+        let mut map = ChunkedTileMap::new(8, 10., 10., Box::new(source));
+        map.load_radius = 0;
+        map.unload_radius = 1;
+        assert_eq!(map.chunk_of(-1, 9), ((-1, 1), (7, 1)));
+
+        // a view in chunk (0, 0) spilling into (-1, 0)
+        let changes = map.update(FRect::new(-10., 10., 50., 50.)).unwrap();
+        assert_eq!(changes.loaded, [(0, 0), (-1, 0)]);
+        assert_eq!(map.get(-8, 0), Some(1));
+        assert_eq!(map.get(-9, 0), None);
+        assert_eq!(map.set(3, 0, EMPTY_TILE), Some(1));
+        assert!(!map.is_occupied(3, 0));
+        assert_eq!(map.cost(3, 0), Some(1.));
+
+        // loaded a chunk at a time, nearest first
+        map.max_loads = Some(1);
+        let changes = map.update(FRect::new(100., 10., 100., 50.)).unwrap();
+        assert_eq!(changes.unloaded, [(-1, 0)]);
+        assert_eq!(changes.loaded, [(1, 0)]);
+        let changes = map.update(FRect::new(100., 10., 100., 50.)).unwrap();
+        assert_eq!(changes.loaded, [(2, 0)]);
+        assert!(map.is_loaded((0, 0)));
+
+        map.unload_all().unwrap();
+        assert_eq!(
+            *unloaded.borrow(),
+            [((-1, 0), 8), ((0, 0), 7), ((1, 0), 8), ((2, 0), 8)]
+        );
+
+        let mut seeded = ChunkedTileMap::new(
+            4,
+            1.,
+            1.,
+            Box::new(
+                |coord: ChunkCoord, layer: &mut TileLayer| -> Result<(), String> {
+                    layer.set(0, 0, chunk_seed(7, coord) as u32 | 1);
+                    Ok(())
+                },
+            ),
+        );
+        seeded.update(FRect::new(0., 0., 1., 1.)).unwrap();
+        assert_eq!(seeded.get(0, 0), Some(chunk_seed(7, (0, 0)) as u32 | 1));
+        assert_ne!(chunk_seed(7, (0, 0)), chunk_seed(7, (0, 1)));
+    }
+}
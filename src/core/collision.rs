@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+use sdl2::rect::{FPoint, FRect};
+
+use super::{entity::EntityId, spatial::overlaps};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collider {
+    Aabb(FRect),
+    Circle { center: FPoint, radius: f32 },
+}
+
+impl Collider {
+    /// axis aligned bounding box which encloses the collider
+    pub fn bounds(&self) -> FRect {
+        match *self {
+            Collider::Aabb(rect) => rect,
+            Collider::Circle { center, radius } => FRect::new(
+                center.x() - radius,
+                center.y() - radius,
+                radius * 2.,
+                radius * 2.,
+            ),
+        }
+    }
+
+    /// true if the shapes overlap. shapes which only touch don't overlap
+    pub fn intersects(&self, other: &Collider) -> bool {
+        match (*self, *other) {
+            (Collider::Aabb(a), Collider::Aabb(b)) => overlaps(a, b),
+            (
+                Collider::Circle {
+                    center: a,
+                    radius: ra,
+                },
+                Collider::Circle {
+                    center: b,
+                    radius: rb,
+                },
+            ) => {
+                let d = a - b;
+                d.x() * d.x() + d.y() * d.y() < (ra + rb) * (ra + rb)
+            }
+            (Collider::Aabb(rect), Collider::Circle { center, radius })
+            | (Collider::Circle { center, radius }, Collider::Aabb(rect)) => {
+                let closest_x = center.x().clamp(rect.left(), rect.right());
+                let closest_y = center.y().clamp(rect.top(), rect.bottom());
+                let dx = center.x() - closest_x;
+                let dy = center.y() - closest_y;
+                dx * dx + dy * dy < radius * radius
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactPhase {
+    /// the colliders started overlapping this frame
+    Begin,
+    /// the colliders were overlapping last frame and still are
+    Stay,
+    /// the colliders were overlapping last frame but aren't anymore (or one of
+    /// them was removed)
+    End,
+}
+
+/// published to the world's event bus. a is always less than b
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contact {
+    pub a: EntityId,
+    pub b: EntityId,
+    pub phase: ContactPhase,
+}
+
+impl Contact {
+    /// the other entity in the contact, if this entity is involved in it
+    pub fn other(&self, id: EntityId) -> Option<EntityId> {
+        if self.a == id {
+            Some(self.b)
+        } else if self.b == id {
+            Some(self.a)
+        } else {
+            None
+        }
+    }
+}
+
+/// remembers which pairs were overlapping last frame
+#[derive(Debug, Clone, Default)]
+pub struct ContactTracker {
+    previous: HashSet<(EntityId, EntityId)>,
+}
+
+impl ContactTracker {
+    /// give this frame's overlapping pairs (each ordered a < b). returns the
+    /// resulting contacts in a deterministic order
+    pub fn update(&mut self, mut pairs: Vec<(EntityId, EntityId)>) -> Vec<Contact> {
+        pairs.sort_unstable();
+        pairs.dedup();
+        let mut contacts: Vec<Contact> = pairs
+            .iter()
+            .map(|&(a, b)| Contact {
+                a,
+                b,
+                phase: if self.previous.contains(&(a, b)) {
+                    ContactPhase::Stay
+                } else {
+                    ContactPhase::Begin
+                },
+            })
+            .collect();
+        let current: HashSet<(EntityId, EntityId)> = pairs.into_iter().collect();
+        let mut ended: Vec<(EntityId, EntityId)> =
+            self.previous.difference(&current).copied().collect();
+        ended.sort_unstable();
+        contacts.extend(ended.into_iter().map(|(a, b)| Contact {
+            a,
+            b,
+            phase: ContactPhase::End,
+        }));
+        self.previous = current;
+        contacts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intersects() {
+        let rect = Collider::Aabb(FRect::new(0., 0., 10., 10.));
+        let near = Collider::Circle {
+            center: FPoint::new(12., 5.),
+            radius: 3.,
+        };
+        let touching = Collider::Circle {
+            center: FPoint::new(12., 9.),
+            radius: 2.,
+        };
+        assert!(rect.intersects(&near));
+        assert!(near.intersects(&rect));
+        assert!(!rect.intersects(&touching));
+        assert!(near.intersects(&touching));
+    }
+
+    #[test]
+    fn test_tracker() {
+        let (a, b, c) = (EntityId(0), EntityId(1), EntityId(2));
+        let mut tracker = ContactTracker::default();
+        let contacts = tracker.update(vec![(a, b)]);
+        assert_eq!(contacts[0].phase, ContactPhase::Begin);
+        let contacts = tracker.update(vec![(a, b), (b, c)]);
+        assert_eq!(contacts[0].phase, ContactPhase::Stay);
+        assert_eq!(contacts[1].phase, ContactPhase::Begin);
+        let contacts = tracker.update(vec![]);
+        assert!(contacts.iter().all(|c| c.phase == ContactPhase::End));
+        assert_eq!(contacts.len(), 2);
+    }
+}
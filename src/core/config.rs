@@ -0,0 +1,321 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::video::{FullscreenType, Window};
+
+use super::{input::InputMap, vfs::Vfs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    /// changes the display mode to the resolution
+    Fullscreen,
+    /// covers the desktop at its current resolution
+    Borderless,
+}
+
+impl WindowMode {
+    pub fn to_config_string(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Fullscreen => "fullscreen",
+            WindowMode::Borderless => "borderless",
+        }
+    }
+
+    pub fn from_config_string(s: &str) -> Result<Self, String> {
+        match s {
+            "windowed" => Ok(WindowMode::Windowed),
+            "fullscreen" => Ok(WindowMode::Fullscreen),
+            "borderless" => Ok(WindowMode::Borderless),
+            _ => Err(format!("unknown window mode \"{}\"", s)),
+        }
+    }
+}
+
+/// engine and game settings. keys missing from a loaded file keep their
+/// defaults
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub window_mode: WindowMode,
+    pub resolution: (u32, u32),
+    /// volumes in [0, 1]
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    /// for Layout::set_scale and UiStyle::scale
+    pub ui_scale: f32,
+    /// whether to use a high contrast palette, e.g. ui::high_contrast
+    pub high_contrast: bool,
+    pub bindings: InputMap,
+    /// anything game specific
+    pub game: toml::Table,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window_mode: Default::default(),
+            resolution: (1280, 720),
+            master_volume: 1.,
+            music_volume: 1.,
+            effects_volume: 1.,
+            ui_scale: 1.,
+            high_contrast: false,
+            bindings: InputMap::new(),
+            game: Default::default(),
+        }
+    }
+}
+
+fn table<'a>(parent: &'a toml::Table, key: &str) -> Result<Option<&'a toml::Table>, String> {
+    parent
+        .get(key)
+        .map(|v| {
+            v.as_table()
+                .ok_or_else(|| format!("\"{}\" isn't a table", key))
+        })
+        .transpose()
+}
+
+fn integer(table: &toml::Table, key: &str, default: u32) -> Result<u32, String> {
+    match table.get(key) {
+        None => Ok(default),
+        Some(v) => v
+            .as_integer()
+            .and_then(|i| u32::try_from(i).ok())
+            .ok_or_else(|| format!("\"{}\" isn't a positive integer", key)),
+    }
+}
+
+fn volume(table: &toml::Table, key: &str, default: f32) -> Result<f32, String> {
+    match table.get(key) {
+        None => Ok(default),
+        Some(v) => v
+            .as_float()
+            .or_else(|| v.as_integer().map(|i| i as f64))
+            .map(|f| (f as f32).clamp(0., 1.))
+            .ok_or_else(|| format!("\"{}\" isn't a number", key)),
+    }
+}
+
+fn number(table: &toml::Table, key: &str, default: f32) -> Result<f32, String> {
+    match table.get(key) {
+        None => Ok(default),
+        Some(v) => v
+            .as_float()
+            .or_else(|| v.as_integer().map(|i| i as f64))
+            .map(|f| f as f32)
+            .filter(|f| *f > 0.)
+            .ok_or_else(|| format!("\"{}\" isn't a positive number", key)),
+    }
+}
+
+impl Config {
+    pub fn to_toml(&self, version: u32) -> toml::Table {
+        let mut window = toml::Table::new();
+        window.insert("mode".into(), self.window_mode.to_config_string().into());
+        window.insert("width".into(), (self.resolution.0 as i64).into());
+        window.insert("height".into(), (self.resolution.1 as i64).into());
+
+        let mut audio = toml::Table::new();
+        audio.insert("master".into(), (self.master_volume as f64).into());
+        audio.insert("music".into(), (self.music_volume as f64).into());
+        audio.insert("effects".into(), (self.effects_volume as f64).into());
+
+        let mut accessibility = toml::Table::new();
+        accessibility.insert("ui_scale".into(), (self.ui_scale as f64).into());
+        accessibility.insert("high_contrast".into(), self.high_contrast.into());
+
+        let mut root = toml::Table::new();
+        root.insert("version".into(), (version as i64).into());
+        root.insert("window".into(), window.into());
+        root.insert("audio".into(), audio.into());
+        root.insert("accessibility".into(), accessibility.into());
+        root.insert("bindings".into(), self.bindings.to_toml().into());
+        root.insert("game".into(), self.game.clone().into());
+        root
+    }
+
+    /// the table should already be migrated to the current version
+    pub fn from_toml(root: &toml::Table) -> Result<Self, String> {
+        let mut config = Self::default();
+        if let Some(window) = table(root, "window")? {
+            if let Some(mode) = window.get("mode") {
+                let mode = mode
+                    .as_str()
+                    .ok_or_else(|| "\"mode\" isn't a string".to_owned())?;
+                config.window_mode = WindowMode::from_config_string(mode)?;
+            }
+            config.resolution = (
+                integer(window, "width", config.resolution.0)?,
+                integer(window, "height", config.resolution.1)?,
+            );
+        }
+        if let Some(audio) = table(root, "audio")? {
+            config.master_volume = volume(audio, "master", config.master_volume)?;
+            config.music_volume = volume(audio, "music", config.music_volume)?;
+            config.effects_volume = volume(audio, "effects", config.effects_volume)?;
+        }
+        if let Some(accessibility) = table(root, "accessibility")? {
+            config.ui_scale = number(accessibility, "ui_scale", config.ui_scale)?;
+            if let Some(high_contrast) = accessibility.get("high_contrast") {
+                config.high_contrast = high_contrast
+                    .as_bool()
+                    .ok_or_else(|| "\"high_contrast\" isn't a boolean".to_owned())?;
+            }
+        }
+        if let Some(bindings) = table(root, "bindings")? {
+            config.bindings.load_toml(bindings)?;
+        }
+        if let Some(game) = table(root, "game")? {
+            config.game = game.clone();
+        }
+        Ok(config)
+    }
+
+    /// resize the window and set its fullscreen mode
+    pub fn apply_to_window(&self, window: &mut Window) -> Result<(), String> {
+        let fullscreen = match self.window_mode {
+            WindowMode::Windowed => FullscreenType::Off,
+            WindowMode::Fullscreen => FullscreenType::True,
+            WindowMode::Borderless => FullscreenType::Desktop,
+        };
+        // the size is the display mode when fullscreen, so set it first
+        window
+            .set_size(self.resolution.0, self.resolution.1)
+            .map_err(|e| e.to_string())?;
+        window.set_fullscreen(fullscreen)
+    }
+}
+
+/// upgrades a table from the previous version, in place
+pub type Migration = fn(&mut toml::Table) -> Result<(), String>;
+
+/// where the config is stored, and how older files are upgraded
+pub struct ConfigFile {
+    path: PathBuf,
+    /// migrations[i] upgrades version i + 1 to i + 2. the current version is
+    /// one more than the number of migrations
+    migrations: Vec<Migration>,
+}
+
+impl ConfigFile {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            migrations: Vec::new(),
+        }
+    }
+
+    /// the platform's per-user writable directory for the app (e.g.
+    /// ~/.local/share/org/app on linux, %APPDATA% on windows), which is created
+    /// if it doesn't exist
+    pub fn in_pref_dir(org: &str, app: &str, file_name: &str) -> Result<Self, String> {
+        let dir = sdl2::filesystem::pref_path(org, app).map_err(|e| e.to_string())?;
+        Ok(Self::new(&Path::new(&dir).join(file_name)))
+    }
+
+    /// add a migration, bumping the current version by one
+    pub fn with_migration(mut self, migration: Migration) -> Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+
+    /// upgrade a table to the current version. files without a version are
+    /// taken to be version 1
+    pub fn migrate(&self, root: &mut toml::Table) -> Result<(), String> {
+        let from = integer(root, "version", 1)?;
+        if from == 0 || from > self.version() {
+            return Err(format!(
+                "config version {} isn't supported (current is {})",
+                from,
+                self.version()
+            ));
+        }
+        for migration in &self.migrations[from as usize - 1..] {
+            migration(root)?;
+        }
+        root.insert("version".into(), (self.version() as i64).into());
+        Ok(())
+    }
+
+    /// read through the vfs, e.g. so a platform can mount its own defaults.
+    /// the defaults if the file doesn't exist yet
+    pub fn load(&self, assets: &Vfs) -> Result<Config, String> {
+        if !assets.is_mounted(&self.path) && !self.path.exists() {
+            return Ok(Default::default());
+        }
+        let contents = assets.read_to_string(&self.path)?;
+        let mut root: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        self.migrate(&mut root)?;
+        Config::from_toml(&root)
+    }
+
+    pub fn save(&self, config: &Config) -> Result<(), String> {
+        let contents =
+            toml::to_string(&config.to_toml(self.version())).map_err(|e| e.to_string())?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&self.path, contents).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vfs::MemoryProvider;
+
+    #[test]
+    fn test_migrate() {
+        // version 2 renamed "sfx" to "effects"
+        let file = ConfigFile::new(Path::new("settings.toml")).with_migration(|root| {
+            if let Some(audio) = root.get_mut("audio").and_then(|a| a.as_table_mut()) {
+                if let Some(sfx) = audio.remove("sfx") {
+                    audio.insert("effects".into(), sfx);
+                }
+            }
+            Ok(())
+        });
+        let mut root: toml::Table = "[window]\nmode = \"borderless\"\n[audio]\nsfx = 0.25"
+            .parse()
+            .unwrap();
+        file.migrate(&mut root).unwrap();
+        let config = Config::from_toml(&root).unwrap();
+        assert_eq!(config.window_mode, WindowMode::Borderless);
+        assert_eq!(config.effects_volume, 0.25);
+        assert_eq!(config.resolution, (1280, 720));
+
+        let saved = config.to_toml(file.version());
+        assert_eq!(saved["version"].as_integer(), Some(2));
+        assert_eq!(Config::from_toml(&saved).unwrap().effects_volume, 0.25);
+
+        let mut root: toml::Table = "[accessibility]\nui_scale = 1.5\nhigh_contrast = true"
+            .parse()
+            .unwrap();
+        file.migrate(&mut root).unwrap();
+        let config = Config::from_toml(&root).unwrap();
+        assert_eq!((config.ui_scale, config.high_contrast), (1.5, true));
+        let saved = config.to_toml(file.version());
+        assert!(Config::from_toml(&saved).unwrap().high_contrast);
+
+        // through the vfs, or the defaults if it's nowhere
+        let mut assets = Vfs::new();
+        assert_eq!(file.load(&assets).unwrap().effects_volume, 1.);
+        let mut defaults = MemoryProvider::new();
+        defaults.insert(Path::new("settings.toml"), b"[audio]\nsfx = 0.5".to_vec());
+        assets.mount(Path::new(""), defaults, 0);
+        assert_eq!(file.load(&assets).unwrap().effects_volume, 0.5);
+    }
+}
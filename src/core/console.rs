@@ -0,0 +1,597 @@
+use std::collections::{BTreeMap, VecDeque};
+#[cfg(feature = "ttf")]
+use std::{ffi::CString, path::Path};
+
+use sdl2::keyboard::Keycode;
+#[cfg(feature = "ttf")]
+use sdl2::{pixels::Color, rect::FRect};
+
+use super::{
+    entity::EntityId, events::EngineEvent, inspector::type_counts, prefab::parse_override,
+    text_input::TextBuffer, world::World,
+};
+#[cfg(feature = "ttf")]
+use super::{renderer::DrawParams, system::ChimericSystem};
+
+type CommandFn<C> = Box<dyn FnMut(&mut C, &[&str]) -> Result<String, String>>;
+type CompleterFn<C> = Box<dyn Fn(&mut C, usize) -> Vec<String>>;
+
+struct Command<C> {
+    help: String,
+    run: CommandFn<C>,
+    complete: Option<CompleterFn<C>>,
+}
+
+/// commands the console handles itself
+const BUILTINS: [(&str, &str); 3] = [
+    ("clear", "clear the output"),
+    (
+        "exec",
+        "exec <file>: run each line of the file as a command",
+    ),
+    ("help", "help [command]: list the commands, or describe one"),
+];
+
+/// how deeply exec can nest, so a file which runs itself stops
+const MAX_EXEC_DEPTH: usize = 8;
+
+/// split a command line on whitespace. double quoted arguments can contain
+/// whitespace, and \" or \\ within them
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(c) = chars.next() else {
+            return Ok(args);
+        };
+        let mut arg = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    None => return Err("unterminated quote".to_owned()),
+                    Some('"') => break,
+                    Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                        arg.extend(chars.next())
+                    }
+                    Some(c) => arg.push(c),
+                }
+            }
+        } else {
+            arg.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// the longest prefix every candidate shares
+fn common_prefix(candidates: &[String]) -> &str {
+    let first = candidates.first().map_or("", String::as_str);
+    let len = candidates.iter().fold(first.len(), |len, candidate| {
+        first[..len]
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(len.min(candidate.len()), |((i, _), _)| i)
+    });
+    &first[..len]
+}
+
+/// an in game developer console. the game registers commands which act on
+/// its context, e.g. the World, or a struct holding the world and whatever
+/// else commands need:
+///
+/// ```ignore
+/// console.register_world_commands(|game: &mut Game| &mut game.world);
+/// console.register("give", "give <item> [count]", |game, args| { ... });
+/// console.register("cache", "images cached per window", |game, _| {
+///     Ok(game.system.cache_stats())
+/// });
+/// ```
+///
+/// feed it events; while it's open it takes keyboard input (enter runs the
+/// line, up and down recall history, tab completes)
+pub struct Console<C> {
+    pub visible: bool,
+    /// opens and closes the console
+    pub toggle_key: Keycode,
+    input: TextBuffer,
+    commands: BTreeMap<String, Command<C>>,
+    output: VecDeque<String>,
+    /// older output lines are dropped
+    pub max_output: usize,
+    history: Vec<String>,
+    /// older history is dropped
+    pub max_history: usize,
+    /// while recalling history, the entry being shown
+    recalled: Option<usize>,
+    /// the text input of the key which toggled the console is dropped
+    toggled: bool,
+    exec_depth: usize,
+    /// fraction of the window's height, from the top
+    #[cfg(feature = "ttf")]
+    pub height: f32,
+    #[cfg(feature = "ttf")]
+    pub background: Color,
+}
+
+impl<C> Default for Console<C> {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            toggle_key: Keycode::Backquote,
+            input: Default::default(),
+            commands: Default::default(),
+            output: Default::default(),
+            max_output: 256,
+            history: Default::default(),
+            max_history: 100,
+            recalled: None,
+            toggled: false,
+            exec_depth: 0,
+            #[cfg(feature = "ttf")]
+            height: 0.5,
+            #[cfg(feature = "ttf")]
+            background: Color::RGBA(0, 0, 0, 192),
+        }
+    }
+}
+
+impl<C> Console<C> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add or replace a command. it's given the arguments after its name, and
+    /// returns text to print (which may be empty)
+    pub fn register<F>(&mut self, name: &str, help: &str, command: F)
+    where
+        F: FnMut(&mut C, &[&str]) -> Result<String, String> + 'static,
+    {
+        self.commands.insert(
+            name.to_owned(),
+            Command {
+                help: help.to_owned(),
+                run: Box::new(command),
+                complete: None,
+            },
+        );
+    }
+
+    /// give a registered command tab completion for its arguments. the
+    /// completer returns every valid value of the argument at the index
+    pub fn set_completer<F>(&mut self, name: &str, completer: F) -> Result<(), String>
+    where
+        F: Fn(&mut C, usize) -> Vec<String> + 'static,
+    {
+        let command = self
+            .commands
+            .get_mut(name)
+            .ok_or_else(|| format!("command \"{}\" isn't registered", name))?;
+        command.complete = Some(Box::new(completer));
+        Ok(())
+    }
+
+    /// the engine's commands on the world the context holds: spawn, despawn,
+    /// time_scale and entities
+    pub fn register_world_commands(&mut self, world: fn(&mut C) -> &mut World)
+    where
+        C: 'static,
+    {
+        self.register(
+            "spawn",
+            "spawn <prefab> [key=value ...]: spawn a prefab with overrides",
+            move |ctx, args| {
+                let (prefab, overrides) = args
+                    .split_first()
+                    .ok_or_else(|| "spawn needs a prefab".to_owned())?;
+                let mut table = toml::Table::new();
+                for arg in overrides {
+                    let (key, value) = arg
+                        .split_once('=')
+                        .ok_or_else(|| format!("\"{}\" isn't key=value", arg))?;
+                    table.insert(key.to_owned(), parse_override(value));
+                }
+                let id = world(ctx).spawn_prefab(prefab, &table)?;
+                Ok(format!("spawned {}", id.0))
+            },
+        );
+        let _ = self.set_completer("spawn", move |ctx, arg| match arg {
+            0 => world(ctx).prefabs.names().map(str::to_owned).collect(),
+            _ => Vec::new(),
+        });
+
+        self.register("despawn", "despawn <id>", move |ctx, args| {
+            let id: u64 = args
+                .first()
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| "despawn needs an entity id".to_owned())?;
+            world(ctx).despawn(EntityId(id));
+            Ok(String::new())
+        });
+        self.register(
+            "time_scale",
+            "time_scale [scale]: show or set the world's time scale",
+            move |ctx, args| {
+                let world = world(ctx);
+                if let Some(scale) = args.first() {
+                    let scale: f32 = scale
+                        .parse()
+                        .ok()
+                        .filter(|s: &f32| *s >= 0.)
+                        .ok_or_else(|| format!("\"{}\" isn't a non negative number", scale))?;
+                    world.set_time_scale(scale);
+                }
+                Ok(format!("time_scale {}", world.time_scale()))
+            },
+        );
+        self.register(
+            "entities",
+            "entities: count the entities of each type",
+            move |ctx, _| {
+                let summaries = world(ctx).entity_summaries();
+                let mut lines = vec![format!("{} entities", summaries.len())];
+                lines.extend(
+                    type_counts(&summaries)
+                        .iter()
+                        .map(|(name, count)| format!("{}: {}", name, count)),
+                );
+                Ok(lines.join("\n"))
+            },
+        );
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// the line being typed
+    pub fn input(&self) -> &str {
+        self.input.text()
+    }
+
+    /// oldest first
+    pub fn output(&self) -> impl Iterator<Item = &str> {
+        self.output.iter().map(String::as_str)
+    }
+
+    /// add to the output, one line per line of text
+    pub fn print(&mut self, text: &str) {
+        self.output.extend(text.lines().map(str::to_owned));
+        while self.output.len() > self.max_output {
+            self.output.pop_front();
+        }
+    }
+
+    /// lines run from the input, oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// e.g. restored from a previous session
+    pub fn set_history(&mut self, history: Vec<String>) {
+        self.history = history;
+        let excess = self.history.len().saturating_sub(self.max_history);
+        self.history.drain(..excess);
+        self.recalled = None;
+    }
+
+    /// run a command line, returning what it printed
+    pub fn execute(&mut self, line: &str, ctx: &mut C) -> Result<String, String> {
+        let args = split_args(line)?;
+        let Some((name, args)) = args.split_first() else {
+            return Ok(String::new());
+        };
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match name.as_str() {
+            "clear" => {
+                self.output.clear();
+                Ok(String::new())
+            }
+            "exec" => {
+                let file = args.first().ok_or_else(|| "exec needs a file".to_owned())?;
+                let script = std::fs::read_to_string(file).map_err(|e| format!("{file}: {e}"))?;
+                self.run_script(&script, ctx)?;
+                Ok(String::new())
+            }
+            "help" => match args.first() {
+                None => {
+                    let mut names: Vec<&str> = self.commands.keys().map(String::as_str).collect();
+                    names.extend(BUILTINS.iter().map(|(name, _)| *name));
+                    names.sort_unstable();
+                    Ok(names.join(" "))
+                }
+                Some(name) => BUILTINS
+                    .iter()
+                    .find(|(builtin, _)| builtin == name)
+                    .map(|(_, help)| help.to_string())
+                    .or_else(|| self.commands.get(*name).map(|c| c.help.clone()))
+                    .ok_or_else(|| format!("unknown command \"{}\"", name)),
+            },
+            _ => {
+                let command = self
+                    .commands
+                    .get_mut(name)
+                    .ok_or_else(|| format!("unknown command \"{}\"", name))?;
+                (command.run)(ctx, &args)
+            }
+        }
+    }
+
+    /// run each line as a command, e.g. an autoexec file at startup. blank
+    /// lines and lines starting with # are skipped. stops at the first error
+    pub fn run_script(&mut self, script: &str, ctx: &mut C) -> Result<(), String> {
+        if self.exec_depth >= MAX_EXEC_DEPTH {
+            return Err("exec is nested too deeply".to_owned());
+        }
+        self.exec_depth += 1;
+        let result = script
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .try_for_each(|(i, line)| {
+                let output = self
+                    .execute(line, ctx)
+                    .map_err(|e| format!("line {}: {}", i + 1, e))?;
+                self.print(&output);
+                Ok(())
+            });
+        self.exec_depth -= 1;
+        result
+    }
+
+    fn submit(&mut self, ctx: &mut C) {
+        let line = self.input.text().trim().to_owned();
+        self.input.set_text("");
+        self.recalled = None;
+        if line.is_empty() {
+            return;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            let excess = self.history.len().saturating_sub(self.max_history);
+            self.history.drain(..excess);
+        }
+        self.print(&format!("> {}", line));
+        match self.execute(&line, ctx) {
+            Ok(output) => self.print(&output),
+            Err(e) => self.print(&format!("error: {}", e)),
+        }
+    }
+
+    fn recall(&mut self, older: bool) {
+        let index = match (self.recalled, older) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => return,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => Some(i + 1).filter(|i| *i < self.history.len()),
+        };
+        self.recalled = index;
+        let text = index.map_or("", |i| self.history[i].as_str());
+        self.input.set_text(text);
+    }
+
+    /// complete the word being typed: a command name, or an argument if the
+    /// command has a completer. if several match, they're printed
+    pub fn complete(&mut self, ctx: &mut C) {
+        let text = self.input.text().to_owned();
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let new_word = words.is_empty() || text.ends_with(char::is_whitespace);
+        let prefix = if new_word { "" } else { words[words.len() - 1] };
+        let arg = words.len() - usize::from(!new_word);
+
+        let mut candidates: Vec<String> = if arg == 0 {
+            let builtins = BUILTINS.iter().map(|(name, _)| name.to_string());
+            self.commands.keys().cloned().chain(builtins).collect()
+        } else {
+            match self
+                .commands
+                .get(words[0])
+                .and_then(|c| c.complete.as_ref())
+            {
+                Some(complete) => complete(ctx, arg - 1),
+                None => return,
+            }
+        };
+        candidates.retain(|c| c.starts_with(prefix));
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let head = &text[..text.len() - prefix.len()];
+        match candidates.as_slice() {
+            [] => {}
+            [only] => self.input.set_text(&format!("{}{} ", head, only)),
+            _ => {
+                self.input
+                    .set_text(&format!("{}{}", head, common_prefix(&candidates)));
+                self.print(&candidates.join("  "));
+            }
+        }
+    }
+
+    /// returns true if the event was used. while open, all keyboard and text
+    /// input is used
+    pub fn handle_event(&mut self, event: &EngineEvent, ctx: &mut C) -> bool {
+        if let EngineEvent::KeyDown {
+            keycode: Some(keycode),
+            repeat: false,
+            ..
+        } = event
+        {
+            if *keycode == self.toggle_key {
+                self.toggle();
+                self.toggled = true;
+                return true;
+            }
+        }
+        if !self.visible {
+            return false;
+        }
+        let toggled = std::mem::take(&mut self.toggled);
+        match event {
+            EngineEvent::TextInput { .. } if toggled => {}
+            EngineEvent::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match *keycode {
+                Keycode::Return | Keycode::KpEnter => self.submit(ctx),
+                Keycode::Up => self.recall(true),
+                Keycode::Down => self.recall(false),
+                Keycode::Tab => self.complete(ctx),
+                Keycode::Escape => self.visible = false,
+                _ => {
+                    self.input.handle_event(event);
+                }
+            },
+            event => {
+                self.input.handle_event(event);
+            }
+        }
+        matches!(
+            event,
+            EngineEvent::KeyDown { .. }
+                | EngineEvent::KeyUp { .. }
+                | EngineEvent::TextInput { .. }
+                | EngineEvent::TextEditing { .. }
+        )
+    }
+
+    /// draw over the top of the window, if visible: the output, newest at the
+    /// bottom, then the line being typed
+    #[cfg(feature = "ttf")]
+    pub fn draw(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        font_file: &Path,
+        point_size: u16,
+    ) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+        let (width, height) = system.drawable_size(window_name)?;
+        let height = height as f32 * self.height.clamp(0., 1.);
+        system.fill_rect(
+            window_name,
+            FRect::new(0., 0., width as f32, height),
+            self.background,
+        )?;
+        let input = format!("> {}_", self.input.display_text());
+        let lines = std::iter::once(input).chain(self.output.iter().rev().cloned());
+        let mut bottom = height;
+        for line in lines {
+            let text = CString::new(line).map_err(|e| e.to_string())?;
+            let (w, h) = system.text_size(font_file, point_size, &text)?;
+            bottom -= h as f32;
+            if bottom < 0. {
+                break;
+            }
+            let dst = FRect::new(0., bottom, w as f32, h as f32);
+            system.draw_text(
+                window_name,
+                font_file,
+                point_size,
+                &text,
+                None,
+                &DrawParams::new(None, Some(dst)),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::entity::Entity;
+
+    struct Crate;
+
+    impl Entity for Crate {
+        fn update(&mut self, _world: &mut World) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn key(keycode: Keycode) -> EngineEvent {
+        EngineEvent::KeyDown {
+            window: None,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: sdl2::keyboard::Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn text(text: &str) -> EngineEvent {
+        EngineEvent::TextInput {
+            window: None,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_console() {
+        assert_eq!(
+            split_args(r#"say "a \"b\"" c"#).unwrap(),
+            ["say", "a \"b\"", "c"]
+        );
+        assert!(split_args("say \"a").is_err());
+
+        let mut world = World::with_seed(0);
+        world
+            .prefabs
+            .register_constructor("crate", |_| Ok(Box::new(Crate)));
+        world.prefabs.load_str("[crate]").unwrap();
+        let mut console: Console<World> = Console::new();
+        console.register_world_commands(|world| world);
+        console.register("give", "give <item>", |_, args| {
+            Ok(format!("gave {}", args[0]))
+        });
+        console
+            .set_completer("give", |_, _| vec!["sword".to_owned(), "shield".to_owned()])
+            .unwrap();
+
+        assert!(!console.handle_event(&text("a"), &mut world));
+        assert!(console.handle_event(&key(Keycode::Backquote), &mut world));
+        // the toggle key's character isn't typed
+        console.handle_event(&text("`"), &mut world);
+        for c in ["t", "i"] {
+            console.handle_event(&text(c), &mut world);
+        }
+        console.handle_event(&key(Keycode::Tab), &mut world);
+        assert_eq!(console.input(), "time_scale ");
+        console.handle_event(&text("0.5"), &mut world);
+        console.handle_event(&key(Keycode::Return), &mut world);
+        assert_eq!(world.time_scale(), 0.5);
+        assert_eq!(console.output().last(), Some("time_scale 0.5"));
+
+        console.handle_event(&text("give s"), &mut world);
+        console.handle_event(&key(Keycode::Tab), &mut world);
+        assert_eq!(console.input(), "give s");
+        assert_eq!(console.output().last(), Some("shield  sword"));
+        console.handle_event(&text("w"), &mut world);
+        console.handle_event(&key(Keycode::Tab), &mut world);
+        console.handle_event(&key(Keycode::Return), &mut world);
+        assert_eq!(console.output().last(), Some("gave sword"));
+
+        console.handle_event(&key(Keycode::Up), &mut world);
+        console.handle_event(&key(Keycode::Up), &mut world);
+        assert_eq!(console.input(), "time_scale 0.5");
+        console.handle_event(&key(Keycode::Down), &mut world);
+        console.handle_event(&key(Keycode::Down), &mut world);
+        assert_eq!(console.input(), "");
+
+        console
+            .run_script("# setup\nspawn crate x=1 name=box\n\nentities", &mut world)
+            .unwrap();
+        assert_eq!(world.len(), 1);
+        assert_eq!(console.output().nth(5), Some("spawned 0"));
+        let err = console.run_script("help\nnope", &mut world).unwrap_err();
+        assert_eq!(err, "line 2: unknown command \"nope\"");
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{HashMap, HashSet};
+
+use sdl2::{
+    controller::{Axis, Button, GameController},
+    event::Event,
+    GameControllerSubsystem,
+};
+
+use super::{events::EngineEvent, system::System};
+
+/// scale the value so the dead zone maps to zero and the remainder is
+/// stretched back to the full range
+pub fn apply_dead_zone(value: f32, dead_zone: f32) -> f32 {
+    let magnitude = value.abs();
+    if magnitude <= dead_zone {
+        return 0.;
+    }
+    let dead_zone = dead_zone.clamp(0., 0.99);
+    value.signum() * ((magnitude - dead_zone) / (1. - dead_zone)).min(1.)
+}
+
+/// buttons and axes of a single controller, kept up to date from events
+#[derive(Debug, Clone, Default)]
+pub struct ControllerState {
+    buttons: HashSet<Button>,
+    /// raw values in [-1, 1]
+    axes: HashMap<Axis, f32>,
+}
+
+impl ControllerState {
+    pub fn button(&self, button: Button) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    /// the value without a dead zone applied
+    pub fn raw_axis(&self, axis: Axis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.)
+    }
+}
+
+/// a connection change, returned from handle_event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerConnection {
+    Connected { player: usize },
+    Disconnected { player: usize },
+}
+
+struct Slot {
+    controller: GameController,
+    state: ControllerState,
+}
+
+/// opens game controllers as they're plugged in and assigns each to a player
+/// slot. a controller which is unplugged frees its slot, and the next one
+/// plugged in takes the lowest free slot
+pub struct ControllerSystem {
+    subsystem: GameControllerSubsystem,
+    players: Vec<Option<Slot>>,
+    /// applied to axis values, in [0, 1)
+    pub dead_zone: f32,
+}
+
+impl ControllerSystem {
+    /// opens every controller that's already connected
+    pub fn new(system: &System) -> Result<Self, String> {
+        let mut s = Self {
+            subsystem: system.game_controller.clone(),
+            players: Vec::new(),
+            dead_zone: 0.15,
+        };
+        for index in 0..s.subsystem.num_joysticks()? {
+            s.open(index);
+        }
+        Ok(s)
+    }
+
+    /// open the joystick at the index if it's a game controller which isn't
+    /// open yet. returns the player it was assigned to
+    fn open(&mut self, joystick_index: u32) -> Option<usize> {
+        if !self.subsystem.is_game_controller(joystick_index) {
+            return None;
+        }
+        let controller = self.subsystem.open(joystick_index).ok()?;
+        let id = controller.instance_id();
+        if self.player_of(id).is_some() {
+            return None; // sdl also sends added events for those present at startup
+        }
+        let slot = Slot {
+            controller,
+            state: Default::default(),
+        };
+        match self.players.iter().position(|p| p.is_none()) {
+            Some(player) => {
+                self.players[player] = Some(slot);
+                Some(player)
+            }
+            None => {
+                self.players.push(Some(slot));
+                Some(self.players.len() - 1)
+            }
+        }
+    }
+
+    fn player_of(&self, instance_id: u32) -> Option<usize> {
+        self.players.iter().position(|p| {
+            p.as_ref()
+                .is_some_and(|s| s.controller.instance_id() == instance_id)
+        })
+    }
+
+    fn state_mut(&mut self, instance_id: u32) -> Option<&mut ControllerState> {
+        let player = self.player_of(instance_id)?;
+        self.players[player].as_mut().map(|s| &mut s.state)
+    }
+
+    /// track connections and controller state
+    pub fn handle_event(&mut self, event: &EngineEvent) -> Option<ControllerConnection> {
+        let EngineEvent::Other(event) = event else {
+            return None;
+        };
+        match event {
+            Event::ControllerDeviceAdded { which, .. } => self
+                .open(*which)
+                .map(|player| ControllerConnection::Connected { player }),
+            Event::ControllerDeviceRemoved { which, .. } => {
+                let player = self.player_of(*which)?;
+                self.players[player] = None;
+                Some(ControllerConnection::Disconnected { player })
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.state_mut(*which)?.buttons.insert(*button);
+                None
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.state_mut(*which)?.buttons.remove(button);
+                None
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                let value = (*value as f32 / i16::MAX as f32).clamp(-1., 1.);
+                self.state_mut(*which)?.axes.insert(*axis, value);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// the number of player slots, including those which are disconnected
+    pub fn num_players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_connected(&self, player: usize) -> bool {
+        self.players.get(player).is_some_and(|p| p.is_some())
+    }
+
+    pub fn state(&self, player: usize) -> Option<&ControllerState> {
+        self.players.get(player)?.as_ref().map(|s| &s.state)
+    }
+
+    pub fn name(&self, player: usize) -> Option<String> {
+        self.players
+            .get(player)?
+            .as_ref()
+            .map(|s| s.controller.name())
+    }
+
+    /// false if the player isn't connected
+    pub fn button(&self, player: usize, button: Button) -> bool {
+        self.state(player).is_some_and(|s| s.button(button))
+    }
+
+    /// in [-1, 1] with the dead zone applied. zero if the player isn't
+    /// connected
+    pub fn axis(&self, player: usize, axis: Axis) -> f32 {
+        let raw = self.state(player).map(|s| s.raw_axis(axis)).unwrap_or(0.);
+        apply_dead_zone(raw, self.dead_zone)
+    }
+
+    /// strength of each motor in [0, 1]. replaces any rumble in progress
+    pub fn rumble(
+        &mut self,
+        player: usize,
+        low_frequency: f32,
+        high_frequency: f32,
+        duration_ms: u32,
+    ) -> Result<(), String> {
+        let slot = self
+            .players
+            .get_mut(player)
+            .and_then(|p| p.as_mut())
+            .ok_or_else(|| format!("player {} has no controller", player))?;
+        let strength = |v: f32| (v.clamp(0., 1.) * u16::MAX as f32) as u16;
+        slot.controller
+            .set_rumble(
+                strength(low_frequency),
+                strength(high_frequency),
+                duration_ms,
+            )
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dead_zone() {
+        assert_eq!(apply_dead_zone(0.1, 0.2), 0.);
+        assert_eq!(apply_dead_zone(-0.2, 0.2), 0.);
+        assert_eq!(apply_dead_zone(0.75, 0.5), 0.5);
+        assert_eq!(apply_dead_zone(-1., 0.2), -1.);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    backtrace::Backtrace,
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, TryLockError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashReportSettings {
+    /// reports are written here, one file per crash. created if needed
+    pub dir: PathBuf,
+    /// how many of the latest log lines are kept
+    pub log_lines: usize,
+    /// how many draw calls of a frame are kept. the rest are counted
+    pub draw_calls: usize,
+    /// abort once the report is written (and the previous hook has run),
+    /// instead of unwinding
+    pub abort: bool,
+}
+
+impl CrashReportSettings {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_owned(),
+            log_lines: 200,
+            draw_calls: 1000,
+            abort: true,
+        }
+    }
+}
+
+/// what a report is made from, kept up to date by the engine while installed
+#[derive(Debug, Default)]
+struct CrashState {
+    settings: Option<CrashReportSettings>,
+    /// named sections, e.g. the engine settings and cache stats
+    context: BTreeMap<String, String>,
+    log: VecDeque<String>,
+    /// the frame being drawn, and how many draw calls didn't fit
+    frame: (Vec<String>, usize),
+    last_frame: (Vec<String>, usize),
+}
+
+impl CrashState {
+    const fn new() -> Self {
+        Self {
+            settings: None,
+            context: BTreeMap::new(),
+            log: VecDeque::new(),
+            frame: (Vec::new(), 0),
+            last_frame: (Vec::new(), 0),
+        }
+    }
+
+    fn push_log(&mut self, line: String) {
+        let capacity = self.settings.as_ref().map_or(0, |s| s.log_lines);
+        if capacity == 0 {
+            return;
+        }
+        if self.log.len() >= capacity {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    fn push_draw(&mut self, call: String) {
+        let capacity = self.settings.as_ref().map_or(0, |s| s.draw_calls);
+        if self.frame.0.len() < capacity {
+            self.frame.0.push(call);
+        } else {
+            self.frame.1 += 1;
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.last_frame = std::mem::take(&mut self.frame);
+    }
+
+    fn report(&self, panic: &str, backtrace: &str) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "{panic}\n\n== backtrace\n{backtrace}");
+        for (name, section) in self.context.iter() {
+            let _ = writeln!(report, "== {name}\n{section}\n");
+        }
+        let _ = writeln!(report, "== last {} log lines", self.log.len());
+        for line in self.log.iter() {
+            let _ = writeln!(report, "{line}");
+        }
+        let (draws, dropped) = &self.last_frame;
+        let _ = writeln!(
+            report,
+            "\n== last frame's {} draw calls",
+            draws.len() + dropped
+        );
+        for call in draws.iter() {
+            let _ = writeln!(report, "{call}");
+        }
+        if *dropped != 0 {
+            let _ = writeln!(report, "... and {dropped} more");
+        }
+        report
+    }
+}
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static STATE: Mutex<CrashState> = Mutex::new(CrashState::new());
+
+fn with_state<R>(f: impl FnOnce(&mut CrashState) -> R) -> R {
+    // a panic elsewhere while it was held doesn't matter here
+    f(&mut STATE.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// set a panic hook which writes a report to the settings' directory: the
+/// panic and backtrace, the engine settings and cache stats, the latest log
+/// lines and the last frame's draw calls. the previous hook still runs after.
+/// installing again only changes the settings
+///
+/// keeping the log and draw calls costs a little each frame, so this is for
+/// release builds given to players rather than development
+pub fn install(settings: CrashReportSettings) {
+    with_state(|state| state.settings = Some(settings));
+    if INSTALLED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let abort = match write_report(info) {
+            Ok((path, abort)) => {
+                eprintln!("crash report written to {}", path.display());
+                abort
+            }
+            Err(e) => {
+                eprintln!("couldn't write crash report: {e}");
+                true
+            }
+        };
+        previous(info);
+        if abort {
+            std::process::abort();
+        }
+    }));
+}
+
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// a named section of the report, replacing any before. ignored unless
+/// installed
+pub fn set_context(name: &str, section: impl FnOnce() -> String) {
+    if is_installed() {
+        let section = section();
+        with_state(|state| state.context.insert(name.to_owned(), section));
+    }
+}
+
+/// called by the engine's logging. ignored unless installed
+pub fn record_log(level: &str, line: impl FnOnce() -> String) {
+    if is_installed() {
+        let line = format!("[{level}] {}", line());
+        with_state(|state| state.push_log(line));
+    }
+}
+
+/// called by the engine for each draw. ignored unless installed
+pub fn record_draw(call: impl FnOnce() -> String) {
+    if is_installed() {
+        let call = call();
+        with_state(|state| state.push_draw(call));
+    }
+}
+
+/// the draw calls recorded so far are the last frame's. called on present
+pub fn end_frame() {
+    if is_installed() {
+        with_state(CrashState::end_frame);
+    }
+}
+
+/// the report's path, and whether to abort
+fn write_report(info: &PanicHookInfo) -> Result<(PathBuf, bool), String> {
+    let backtrace = Backtrace::force_capture().to_string();
+    // the panic may have happened while the state was held
+    let state = match STATE.try_lock() {
+        Ok(state) => state,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(e) => return Err(e.to_string()),
+    };
+    let settings = state
+        .settings
+        .as_ref()
+        .ok_or_else(|| "no crash report settings".to_owned())?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = settings.dir.join(format!("crash-{time}.txt"));
+    std::fs::create_dir_all(&settings.dir).map_err(|e| e.to_string())?;
+    std::fs::write(&path, state.report(&info.to_string(), &backtrace))
+        .map_err(|e| e.to_string())?;
+    Ok((path, settings.abort))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut state = CrashState::new();
+        state.push_log("dropped before install".into());
+        let mut settings = CrashReportSettings::new(Path::new("crashes"));
+        settings.log_lines = 2;
+        settings.draw_calls = 1;
+        state.settings = Some(settings);
+        for line in ["a", "b", "c"] {
+            state.push_log(line.into());
+        }
+        state.push_draw("draw hero.png".into());
+        state.push_draw("fill".into());
+        state.end_frame();
+        state.push_draw("not finished".into());
+        state
+            .context
+            .insert("caches".into(), "main: 3 images".into());
+
+        let report = state.report("panicked at src/main.rs:1:1", "<backtrace>");
+        assert!(report.starts_with("panicked at src/main.rs:1:1"));
+        assert!(report.contains("== caches\nmain: 3 images"));
+        assert!(report.contains("== last 2 log lines\nb\nc\n"));
+        assert!(report.contains("draw calls\ndraw hero.png\n... and 1 more"));
+        assert!(!report.contains("not finished"));
+    }
+}
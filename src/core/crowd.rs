@@ -0,0 +1,468 @@
+use std::{path::PathBuf, time::Duration};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect},
+};
+
+use super::{
+    entity::Entity,
+    renderer::{DrawParams, Vertex},
+    rng::Rng,
+    system::ChimericSystem,
+    world::World,
+};
+
+/// steering weights and limits, in units and seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoidParams {
+    /// agents closer than this are neighbours. also the spatial hash's cell
+    /// size
+    pub neighbor_radius: f32,
+    /// neighbours closer than this are steered away from
+    pub separation_radius: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    /// towards the target, if there is one
+    pub seek: f32,
+    pub target: Option<FPoint>,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// the most each agent's velocity changes per second
+    pub max_force: f32,
+    /// agents are turned back once they're this close to the bounds' edges
+    pub bounds: FRect,
+    pub edge_margin: f32,
+}
+
+impl Default for BoidParams {
+    fn default() -> Self {
+        Self {
+            neighbor_radius: 32.,
+            separation_radius: 12.,
+            separation: 1.5,
+            alignment: 1.,
+            cohesion: 1.,
+            seek: 0.5,
+            target: None,
+            min_speed: 20.,
+            max_speed: 80.,
+            max_force: 200.,
+            bounds: FRect::new(0., 0., 640., 480.),
+            edge_margin: 32.,
+        }
+    }
+}
+
+/// many flocking agents, simulated together. each component is kept in its
+/// own array so the steering loops run over plain f32s. neighbours are found
+/// with a spatial hash over the bounds, rebuilt each step.
+///
+/// as an entity it steps in the parallel update phase and draws as a single
+/// batch: one triangle per agent, or one sprite per agent if a sprite is set
+pub struct Crowd {
+    pub params: BoidParams,
+    x: Vec<f32>,
+    y: Vec<f32>,
+    vx: Vec<f32>,
+    vy: Vec<f32>,
+    /// steering computed for each agent before any agent moves
+    ax: Vec<f32>,
+    ay: Vec<f32>,
+    /// agents sorted by cell, and where each cell's run of them begins. built
+    /// from the positions after each step
+    cell_agents: Vec<u32>,
+    cell_start: Vec<u32>,
+    agent_cell: Vec<u32>,
+    /// the bounds and radius the grid was built with
+    grid_for: Option<(FRect, f32)>,
+    /// set by the entity update from the world, for the parallel update
+    timestep: Duration,
+    pub window_name: String,
+    pub color: Color,
+    /// drawn facing right, size by size, rotated to the agent's heading
+    pub sprite: Option<PathBuf>,
+    /// of each agent's triangle or sprite
+    pub size: f32,
+}
+
+impl Crowd {
+    pub fn new(params: BoidParams, window_name: &str) -> Self {
+        Self {
+            params,
+            x: Vec::new(),
+            y: Vec::new(),
+            vx: Vec::new(),
+            vy: Vec::new(),
+            ax: Vec::new(),
+            ay: Vec::new(),
+            cell_agents: Vec::new(),
+            cell_start: Vec::new(),
+            agent_cell: Vec::new(),
+            grid_for: None,
+            timestep: Duration::ZERO,
+            window_name: window_name.to_owned(),
+            color: Color::WHITE,
+            sprite: None,
+            size: 8.,
+        }
+    }
+
+    /// returns the agent's index
+    pub fn add(&mut self, position: FPoint, velocity: FPoint) -> usize {
+        self.x.push(position.x());
+        self.y.push(position.y());
+        self.vx.push(velocity.x());
+        self.vy.push(velocity.y());
+        self.x.len() - 1
+    }
+
+    /// add agents at random places within the bounds, heading in random
+    /// directions
+    pub fn scatter(&mut self, rng: &mut Rng, count: usize) {
+        let bounds = self.params.bounds;
+        let speed = (self.params.min_speed + self.params.max_speed) / 2.;
+        for _ in 0..count {
+            let position = FPoint::new(
+                bounds.x() + rng.next_f32() * bounds.width(),
+                bounds.y() + rng.next_f32() * bounds.height(),
+            );
+            let (sin, cos) = (rng.next_f32() * std::f32::consts::TAU).sin_cos();
+            self.add(position, FPoint::new(cos * speed, sin * speed));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.x.clear();
+        self.y.clear();
+        self.vx.clear();
+        self.vy.clear();
+        self.cell_agents.clear();
+    }
+
+    pub fn position(&self, agent: usize) -> FPoint {
+        FPoint::new(self.x[agent], self.y[agent])
+    }
+
+    pub fn velocity(&self, agent: usize) -> FPoint {
+        FPoint::new(self.vx[agent], self.vy[agent])
+    }
+
+    fn grid_size(&self) -> (usize, usize) {
+        let cell = self.params.neighbor_radius.max(1.);
+        let bounds = self.params.bounds;
+        (
+            ((bounds.width() / cell).ceil() as usize).max(1),
+            ((bounds.height() / cell).ceil() as usize).max(1),
+        )
+    }
+
+    /// clamped, so agents outside the bounds share the edge cells
+    fn cell_of(&self, x: f32, y: f32, columns: usize, rows: usize) -> (usize, usize) {
+        let cell = self.params.neighbor_radius.max(1.);
+        let bounds = self.params.bounds;
+        let column = ((x - bounds.x()) / cell).max(0.) as usize;
+        let row = ((y - bounds.y()) / cell).max(0.) as usize;
+        (column.min(columns - 1), row.min(rows - 1))
+    }
+
+    fn grid_is_current(&self) -> bool {
+        self.cell_agents.len() == self.len()
+            && self.grid_for == Some((self.params.bounds, self.params.neighbor_radius))
+    }
+
+    /// counting sort of the agents by cell
+    fn rebuild_grid(&mut self) {
+        self.grid_for = Some((self.params.bounds, self.params.neighbor_radius));
+        let (columns, rows) = self.grid_size();
+        self.cell_start.clear();
+        self.cell_start.resize(columns * rows + 1, 0);
+        self.agent_cell.clear();
+        for i in 0..self.len() {
+            let (column, row) = self.cell_of(self.x[i], self.y[i], columns, rows);
+            let cell = row * columns + column;
+            self.agent_cell.push(cell as u32);
+            self.cell_start[cell + 1] += 1;
+        }
+        for cell in 0..columns * rows {
+            self.cell_start[cell + 1] += self.cell_start[cell];
+        }
+        let mut next = self.cell_start.clone();
+        self.cell_agents.clear();
+        self.cell_agents.resize(self.len(), 0);
+        for (i, cell) in self.agent_cell.iter().enumerate() {
+            let slot = &mut next[*cell as usize];
+            self.cell_agents[*slot as usize] = i as u32;
+            *slot += 1;
+        }
+    }
+
+    /// the agents within the neighbour radius of the point. includes an agent
+    /// at the point itself
+    pub fn neighbors(&self, point: FPoint) -> Vec<usize> {
+        let mut found = Vec::new();
+        if !self.grid_is_current() {
+            // the grid is stale until the next step
+            let r2 = self.params.neighbor_radius * self.params.neighbor_radius;
+            found.extend((0..self.len()).filter(|i| {
+                let (dx, dy) = (self.x[*i] - point.x(), self.y[*i] - point.y());
+                dx * dx + dy * dy < r2
+            }));
+            return found;
+        }
+        self.for_each_neighbor(point.x(), point.y(), |j, _, _, _| found.push(j));
+        found.sort_unstable();
+        found
+    }
+
+    /// calls f(agent, dx, dy, distance squared) for each agent in range,
+    /// with the offset from the point to the agent
+    fn for_each_neighbor(&self, x: f32, y: f32, mut f: impl FnMut(usize, f32, f32, f32)) {
+        let (columns, rows) = self.grid_size();
+        let (column, row) = self.cell_of(x, y, columns, rows);
+        let r2 = self.params.neighbor_radius * self.params.neighbor_radius;
+        for row in row.saturating_sub(1)..(row + 2).min(rows) {
+            let start = row * columns;
+            let first = self.cell_start[start + column.saturating_sub(1)] as usize;
+            let last = self.cell_start[start + (column + 2).min(columns)] as usize;
+            // the three cells of the row are contiguous in the sorted agents
+            for j in &self.cell_agents[first..last] {
+                let j = *j as usize;
+                let (dx, dy) = (self.x[j] - x, self.y[j] - y);
+                let d2 = dx * dx + dy * dy;
+                if d2 < r2 {
+                    f(j, dx, dy, d2);
+                }
+            }
+        }
+    }
+
+    /// advance every agent by dt
+    pub fn step(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        if dt <= 0. || self.is_empty() {
+            return;
+        }
+        if !self.grid_is_current() {
+            self.rebuild_grid();
+        }
+        let p = self.params;
+        let len = self.len();
+        self.ax.clear();
+        self.ax.resize(len, 0.);
+        self.ay.clear();
+        self.ay.resize(len, 0.);
+        let separation2 = p.separation_radius * p.separation_radius;
+
+        for i in 0..len {
+            let (x, y) = (self.x[i], self.y[i]);
+            let mut count = 0.;
+            let (mut cx, mut cy) = (0., 0.);
+            let (mut avx, mut avy) = (0., 0.);
+            let (mut sx, mut sy) = (0., 0.);
+            self.for_each_neighbor(x, y, |j, dx, dy, d2| {
+                if j == i {
+                    return;
+                }
+                count += 1.;
+                cx += dx;
+                cy += dy;
+                avx += self.vx[j];
+                avy += self.vy[j];
+                if d2 < separation2 && d2 > 0. {
+                    // stronger the closer they are
+                    sx -= dx / d2;
+                    sy -= dy / d2;
+                }
+            });
+
+            // each rule steers towards a desired velocity at full speed
+            let (vx, vy) = (self.vx[i], self.vy[i]);
+            let mut ax = 0.;
+            let mut ay = 0.;
+            let mut steer = |dx: f32, dy: f32, weight: f32| {
+                let length = (dx * dx + dy * dy).sqrt();
+                if length > f32::EPSILON {
+                    ax += (dx / length * p.max_speed - vx) * weight;
+                    ay += (dy / length * p.max_speed - vy) * weight;
+                }
+            };
+            if count > 0. {
+                steer(cx / count, cy / count, p.cohesion);
+                steer(avx / count, avy / count, p.alignment);
+                steer(sx, sy, p.separation);
+            }
+            if let Some(target) = p.target {
+                steer(target.x() - x, target.y() - y, p.seek);
+            }
+            let back_x = if x < p.bounds.x() + p.edge_margin {
+                1.
+            } else if x > p.bounds.right() - p.edge_margin {
+                -1.
+            } else {
+                0.
+            };
+            let back_y = if y < p.bounds.y() + p.edge_margin {
+                1.
+            } else if y > p.bounds.bottom() - p.edge_margin {
+                -1.
+            } else {
+                0.
+            };
+            // overrides the flock, so agents can't be crowded out of bounds
+            steer(back_x, back_y, 4.);
+
+            let length = (ax * ax + ay * ay).sqrt();
+            if length > p.max_force {
+                ax *= p.max_force / length;
+                ay *= p.max_force / length;
+            }
+            self.ax[i] = ax;
+            self.ay[i] = ay;
+        }
+
+        for i in 0..len {
+            let mut vx = self.vx[i] + self.ax[i] * dt;
+            let mut vy = self.vy[i] + self.ay[i] * dt;
+            let speed = (vx * vx + vy * vy).sqrt();
+            let clamped = speed.clamp(p.min_speed, p.max_speed);
+            if speed > f32::EPSILON && clamped != speed {
+                vx *= clamped / speed;
+                vy *= clamped / speed;
+            }
+            self.vx[i] = vx;
+            self.vy[i] = vy;
+            self.x[i] += vx * dt;
+            self.y[i] += vy * dt;
+        }
+        // kept current for neighbour queries and the next step
+        self.rebuild_grid();
+    }
+
+    /// a triangle per agent, pointing along its velocity
+    pub fn mesh(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::with_capacity(self.len() * 3);
+        let half = self.size / 2.;
+        for i in 0..self.len() {
+            let speed = (self.vx[i] * self.vx[i] + self.vy[i] * self.vy[i]).sqrt();
+            let (fx, fy) = if speed > f32::EPSILON {
+                (self.vx[i] / speed, self.vy[i] / speed)
+            } else {
+                (1., 0.)
+            };
+            let (x, y) = (self.x[i], self.y[i]);
+            for (forward, side) in [(half, 0.), (-half, half * 0.6), (-half, -half * 0.6)] {
+                vertices.push(Vertex {
+                    position: FPoint::new(
+                        x + fx * forward - fy * side,
+                        y + fy * forward + fx * side,
+                    ),
+                    color: self.color,
+                });
+            }
+        }
+        let indices = (0..vertices.len() as u32).collect();
+        (vertices, indices)
+    }
+
+    /// a sprite per agent, rotated to its heading
+    pub fn sprite_params(&self) -> Vec<DrawParams> {
+        let half = self.size / 2.;
+        (0..self.len())
+            .map(|i| {
+                let mut params = DrawParams::new(
+                    None,
+                    Some(FRect::new(
+                        self.x[i] - half,
+                        self.y[i] - half,
+                        self.size,
+                        self.size,
+                    )),
+                );
+                params.angle = (self.vy[i] as f64).atan2(self.vx[i] as f64).to_degrees();
+                params
+            })
+            .collect()
+    }
+}
+
+impl Entity for Crowd {
+    fn update(&mut self, world: &mut World) -> Result<(), String> {
+        self.timestep = world.timestep();
+        Ok(())
+    }
+
+    fn parallel_update(&mut self) -> Result<(), String> {
+        self.step(self.timestep);
+        Ok(())
+    }
+
+    fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        match &self.sprite {
+            Some(sprite) => system.draw(&self.window_name, sprite, &self.sprite_params()),
+            None => {
+                let (vertices, indices) = self.mesh();
+                system.draw_triangles(&self.window_name, &vertices, &indices)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crowd() {
+        let params = BoidParams {
+            bounds: FRect::new(0., 0., 200., 200.),
+            ..Default::default()
+        };
+        let mut crowd = Crowd::new(params, "main");
+        let a = crowd.add(FPoint::new(100., 100.), FPoint::new(40., 0.));
+        let b = crowd.add(FPoint::new(104., 100.), FPoint::new(40., 0.));
+        crowd.add(FPoint::new(10., 190.), FPoint::new(0., 60.));
+        assert_eq!(crowd.neighbors(FPoint::new(102., 100.)), vec![a, b]);
+
+        let mut rng = Rng::new(3);
+        crowd.scatter(&mut rng, 200);
+        for _ in 0..600 {
+            crowd.step(Duration::from_millis(16));
+        }
+        // the close pair spread apart, and no one left the bounds for long
+        let (pa, pb) = (crowd.position(a), crowd.position(b));
+        assert!((pa.x() - pb.x()).hypot(pa.y() - pb.y()) > 4.);
+        for i in 0..crowd.len() {
+            let p = crowd.position(i);
+            assert!(p.x() > -20. && p.x() < 220. && p.y() > -20. && p.y() < 220.);
+            let v = crowd.velocity(i);
+            assert!(v.x().hypot(v.y()) <= params.max_speed + 0.01);
+        }
+        // the grid finds the same neighbours as checking every agent
+        let point = crowd.position(0);
+        let r2 = params.neighbor_radius * params.neighbor_radius;
+        let brute: Vec<usize> = (0..crowd.len())
+            .filter(|i| {
+                let q = crowd.position(*i);
+                (q.x() - point.x()).powi(2) + (q.y() - point.y()).powi(2) < r2
+            })
+            .collect();
+        assert_eq!(crowd.neighbors(point), brute);
+
+        let (vertices, indices) = crowd.mesh();
+        assert_eq!(vertices.len(), crowd.len() * 3);
+        assert_eq!(indices.len(), vertices.len());
+    }
+}
@@ -0,0 +1,273 @@
+use std::time::Duration;
+
+use sdl2::pixels::Color;
+
+use super::{
+    entity::Entity, lighting::Lighting, post_process::EffectStack, system::ChimericSystem,
+    tween::Lerp, world::World,
+};
+
+/// how the world looks at an hour of the day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfDay {
+    /// in [0, 24)
+    pub hour: f32,
+    /// the color grade multiplied over the frame
+    pub tint: Color,
+    /// how bright the sun is, in [0, 1]. scales the tint
+    pub intensity: f32,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: f32, tint: Color, intensity: f32) -> Self {
+        Self {
+            hour,
+            tint,
+            intensity,
+        }
+    }
+}
+
+/// published on the world's bus as the clock passes them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayNightEvent {
+    Dawn {
+        day: u32,
+    },
+    Dusk {
+        day: u32,
+    },
+    /// midnight, starting this day
+    NewDay {
+        day: u32,
+    },
+}
+
+/// an in-game clock advanced by the world's simulated time. the hour picks a
+/// tint and intensity from the keyframes, which are put into a window's
+/// lighting ambient so the whole frame (and the lights over it) is graded
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayNight {
+    /// simulated time per in-game day
+    pub day_length: Duration,
+    /// hours in (0, 24) at which Dawn and Dusk are published
+    pub dawn: f32,
+    pub dusk: f32,
+    /// sorted by hour. blended between neighbors, wrapping around midnight
+    pub keys: Vec<TimeOfDay>,
+    pub paused: bool,
+    pub window_name: String,
+    day: u32,
+    hour: f32,
+    events: Vec<DayNightEvent>,
+}
+
+impl DayNight {
+    /// starts at noon of day 0
+    pub fn new(day_length: Duration, window_name: &str) -> Self {
+        Self {
+            day_length,
+            dawn: 6.,
+            dusk: 19.,
+            keys: vec![
+                TimeOfDay::new(0., Color::RGB(70, 80, 150), 0.3),
+                TimeOfDay::new(5., Color::RGB(80, 85, 150), 0.35),
+                TimeOfDay::new(7., Color::RGB(255, 180, 140), 0.8),
+                TimeOfDay::new(10., Color::WHITE, 1.),
+                TimeOfDay::new(17., Color::WHITE, 1.),
+                TimeOfDay::new(19., Color::RGB(255, 150, 110), 0.75),
+                TimeOfDay::new(21., Color::RGB(70, 80, 150), 0.3),
+            ],
+            paused: false,
+            window_name: window_name.into(),
+            day: 0,
+            hour: 12.,
+            events: Default::default(),
+        }
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    /// in [0, 24)
+    pub fn hour(&self) -> f32 {
+        self.hour
+    }
+
+    /// jump to a time without publishing the events in between
+    pub fn set_time(&mut self, day: u32, hour: f32) {
+        self.day = day + (hour / 24.).floor().max(0.) as u32;
+        self.hour = hour.rem_euclid(24.);
+    }
+
+    pub fn is_day(&self) -> bool {
+        (self.dawn..self.dusk).contains(&self.hour)
+    }
+
+    /// advance by simulated time, keeping the events passed on the way
+    pub fn update(&mut self, dt: Duration) {
+        if self.paused || self.day_length.is_zero() {
+            return;
+        }
+        let mut remaining = (dt.as_secs_f64() / self.day_length.as_secs_f64() * 24.) as f32;
+        while remaining > 0. {
+            let next = [self.dawn, self.dusk]
+                .into_iter()
+                .filter(|&hour| hour > self.hour)
+                .fold(24., f32::min);
+            let step = next - self.hour;
+            if step > remaining {
+                self.hour += remaining;
+                break;
+            }
+            remaining -= step;
+            if next >= 24. {
+                self.hour = 0.;
+                self.day += 1;
+                self.events.push(DayNightEvent::NewDay { day: self.day });
+            } else {
+                self.hour = next;
+                self.events.push(if next == self.dawn {
+                    DayNightEvent::Dawn { day: self.day }
+                } else {
+                    DayNightEvent::Dusk { day: self.day }
+                });
+            }
+        }
+    }
+
+    /// the events passed since the last call, in order
+    pub fn take_events(&mut self) -> Vec<DayNightEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// the keys blended at the current hour
+    pub fn time_of_day(&self) -> TimeOfDay {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return TimeOfDay::new(self.hour, Color::WHITE, 1.);
+        };
+        // the key at or before the hour, wrapping to yesterday's last one
+        let after = self.keys.iter().position(|key| key.hour > self.hour);
+        let (from, to) = match after {
+            Some(i) if i > 0 => (&self.keys[i - 1], &self.keys[i]),
+            _ => (last, first),
+        };
+        let span = (to.hour - from.hour).rem_euclid(24.);
+        let t = if span > 0. {
+            (self.hour - from.hour).rem_euclid(24.) / span
+        } else {
+            0.
+        };
+        TimeOfDay::new(
+            self.hour,
+            Color::lerp(from.tint, to.tint, t),
+            f32::lerp(from.intensity, to.intensity, t),
+        )
+    }
+
+    /// the tint scaled by the intensity, as an opaque color
+    pub fn ambient(&self) -> Color {
+        let time = self.time_of_day();
+        let scale = |c: u8| (c as f32 * time.intensity).round().clamp(0., 255.) as u8;
+        Color::RGB(scale(time.tint.r), scale(time.tint.g), scale(time.tint.b))
+    }
+
+    /// in [0, 1]. e.g. for the intensity of lamps which come on at night
+    pub fn darkness(&self) -> f32 {
+        1. - self.time_of_day().intensity.clamp(0., 1.)
+    }
+
+    /// set the stack's lighting ambient, adding lighting if it has none
+    pub fn apply(&self, effects: &mut EffectStack) {
+        let ambient = self.ambient();
+        match &mut effects.lighting {
+            Some(lighting) => lighting.ambient = ambient,
+            None => effects.lighting = Some(Lighting::new(ambient)),
+        }
+    }
+
+    pub fn to_toml(&self) -> toml::Table {
+        let mut table = toml::Table::new();
+        table.insert("day".into(), toml::Value::Integer(self.day as i64));
+        table.insert("hour".into(), toml::Value::Float(self.hour as f64));
+        table
+    }
+
+    /// restore the time saved by to_toml
+    pub fn load_toml(&mut self, table: &toml::Table) -> Result<(), String> {
+        let day = table
+            .get("day")
+            .and_then(toml::Value::as_integer)
+            .and_then(|day| u32::try_from(day).ok())
+            .ok_or("day must be a non-negative integer")?;
+        let hour = match table.get("hour") {
+            Some(toml::Value::Float(hour)) => *hour as f32,
+            Some(toml::Value::Integer(hour)) => *hour as f32,
+            _ => return Err("hour must be a number".into()),
+        };
+        self.set_time(day, hour);
+        Ok(())
+    }
+}
+
+impl Entity for DayNight {
+    fn update(&mut self, world: &mut World) -> Result<(), String> {
+        DayNight::update(self, world.timestep());
+        for event in self.take_events() {
+            world.events.publish(event);
+        }
+        Ok(())
+    }
+
+    fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        self.apply(system.effects(&self.window_name)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_night() {
+        // an hour per second
+        let mut clock = DayNight::new(Duration::from_secs(24), "main");
+        assert_eq!(clock.ambient(), Color::WHITE);
+        clock.update(Duration::from_secs(8));
+        assert_eq!(clock.hour(), 20.);
+        assert!(!clock.is_day());
+        assert_eq!(clock.take_events(), [DayNightEvent::Dusk { day: 0 }]);
+
+        // past midnight and the next dawn in one step
+        clock.update(Duration::from_secs(11));
+        assert_eq!(
+            clock.take_events(),
+            [
+                DayNightEvent::NewDay { day: 1 },
+                DayNightEvent::Dawn { day: 1 }
+            ]
+        );
+        assert_eq!((clock.day(), clock.hour()), (1, 7.));
+        let time = clock.time_of_day();
+        assert_eq!(
+            (time.tint, time.intensity),
+            (Color::RGB(255, 180, 140), 0.8)
+        );
+
+        // wrapping between the last and first keys
+        clock.set_time(2, 22.5);
+        assert_eq!(clock.time_of_day().tint, Color::RGB(70, 80, 150));
+        let mut effects = EffectStack::new();
+        clock.apply(&mut effects);
+        assert_eq!(
+            effects.lighting.map(|l| l.ambient),
+            Some(Color::RGB(21, 24, 45))
+        );
+
+        let mut loaded = DayNight::new(Duration::from_secs(24), "main");
+        loaded.load_toml(&clock.to_toml()).unwrap();
+        assert_eq!((loaded.day(), loaded.hour()), (2, 22.5));
+    }
+}
@@ -0,0 +1,393 @@
+use std::{collections::BTreeMap, ops::RangeInclusive, path::Path};
+
+use super::{console::Console, save::write_atomic};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlagValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+}
+
+impl std::fmt::Display for FlagValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlagValue::Bool(v) => write!(f, "{}", v),
+            FlagValue::Int(v) => write!(f, "{}", v),
+            FlagValue::Float(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl FlagValue {
+    fn to_toml(self) -> toml::Value {
+        match self {
+            FlagValue::Bool(v) => toml::Value::Boolean(v),
+            FlagValue::Int(v) => toml::Value::Integer(v as i64),
+            FlagValue::Float(v) => toml::Value::Float(v as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum FlagRange {
+    Bool,
+    Int(RangeInclusive<i32>),
+    Float(RangeInclusive<f32>),
+}
+
+#[derive(Debug, Clone)]
+struct Flag {
+    value: FlagValue,
+    default: FlagValue,
+    range: FlagRange,
+    help: String,
+    persisted: bool,
+}
+
+impl Flag {
+    /// parse and range check a value for this flag
+    fn parse(&self, s: &str) -> Result<FlagValue, String> {
+        let s = s.trim();
+        match &self.range {
+            FlagRange::Bool => match s {
+                "true" | "1" | "on" => Ok(FlagValue::Bool(true)),
+                "false" | "0" | "off" => Ok(FlagValue::Bool(false)),
+                _ => Err(format!("\"{}\" isn't true or false", s)),
+            },
+            FlagRange::Int(range) => {
+                let v: i32 = s
+                    .parse()
+                    .map_err(|_| format!("\"{}\" isn't an integer", s))?;
+                range
+                    .contains(&v)
+                    .then_some(FlagValue::Int(v))
+                    .ok_or_else(|| format!("{} isn't in {:?}", v, range))
+            }
+            FlagRange::Float(range) => {
+                let v: f32 = s.parse().map_err(|_| format!("\"{}\" isn't a number", s))?;
+                range
+                    .contains(&v)
+                    .then_some(FlagValue::Float(v))
+                    .ok_or_else(|| format!("{} isn't in {:?}", v, range))
+            }
+        }
+    }
+
+    fn read_toml(&self, value: &toml::Value) -> Result<FlagValue, String> {
+        let s = match value {
+            toml::Value::Boolean(v) => v.to_string(),
+            toml::Value::Integer(v) => v.to_string(),
+            toml::Value::Float(v) => v.to_string(),
+            _ => return Err("not a bool or number".to_owned()),
+        };
+        self.parse(&s)
+    }
+}
+
+/// named debug toggles and tunable values, e.g. "show_hitboxes" or
+/// "player_speed", in one place instead of scattered statics. they can be
+/// changed while the game runs from the console (see register_commands) or
+/// an egui panel (see ui), and chosen ones persisted between runs.
+///
+/// flags are added at startup; a value loaded before its flag is added is
+/// applied when it is
+#[derive(Debug, Clone, Default)]
+pub struct DebugFlags {
+    flags: BTreeMap<String, Flag>,
+    /// loaded values for flags which haven't been added yet
+    pending: toml::Table,
+}
+
+impl DebugFlags {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn add(&mut self, name: &str, default: FlagValue, range: FlagRange, help: &str) {
+        let mut flag = Flag {
+            value: default,
+            default,
+            range,
+            help: help.to_owned(),
+            persisted: false,
+        };
+        if let Some(old) = self.flags.get(name) {
+            flag.persisted = old.persisted;
+            if let Ok(value) = flag.parse(&old.value.to_string()) {
+                flag.value = value;
+            }
+        }
+        if let Some(loaded) = self.pending.remove(name) {
+            flag.persisted = true;
+            if let Ok(value) = flag.read_toml(&loaded) {
+                flag.value = value;
+            }
+        }
+        self.flags.insert(name.to_owned(), flag);
+    }
+
+    /// adding a flag again keeps its value if it's still valid
+    pub fn add_bool(&mut self, name: &str, default: bool, help: &str) {
+        self.add(name, FlagValue::Bool(default), FlagRange::Bool, help);
+    }
+
+    pub fn add_int(&mut self, name: &str, default: i32, range: RangeInclusive<i32>, help: &str) {
+        debug_assert!(range.contains(&default));
+        self.add(name, FlagValue::Int(default), FlagRange::Int(range), help);
+    }
+
+    pub fn add_float(&mut self, name: &str, default: f32, range: RangeInclusive<f32>, help: &str) {
+        debug_assert!(range.contains(&default));
+        self.add(
+            name,
+            FlagValue::Float(default),
+            FlagRange::Float(range),
+            help,
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<FlagValue> {
+        self.flags.get(name).map(|flag| flag.value)
+    }
+
+    /// false if there's no such bool flag
+    pub fn bool(&self, name: &str) -> bool {
+        match self.get(name) {
+            Some(FlagValue::Bool(v)) => v,
+            _ => false,
+        }
+    }
+
+    /// 0 if there's no such int flag
+    pub fn int(&self, name: &str) -> i32 {
+        match self.get(name) {
+            Some(FlagValue::Int(v)) => v,
+            _ => 0,
+        }
+    }
+
+    /// 0 if there's no such float flag
+    pub fn float(&self, name: &str) -> f32 {
+        match self.get(name) {
+            Some(FlagValue::Float(v)) => v,
+            _ => 0.,
+        }
+    }
+
+    pub fn help(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).map(|flag| flag.help.as_str())
+    }
+
+    /// sorted by name
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.flags.keys().map(String::as_str)
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Result<&mut Flag, String> {
+        self.flags
+            .get_mut(name)
+            .ok_or_else(|| format!("no debug flag \"{}\"", name))
+    }
+
+    /// parse the value, e.g. typed in the console. it must be the flag's type
+    /// and within its range
+    pub fn set(&mut self, name: &str, value: &str) -> Result<FlagValue, String> {
+        let flag = self.flag_mut(name)?;
+        flag.value = flag.parse(value)?;
+        Ok(flag.value)
+    }
+
+    pub fn set_value(&mut self, name: &str, value: FlagValue) -> Result<(), String> {
+        self.set(name, &value.to_string()).map(|_| ())
+    }
+
+    pub fn reset(&mut self, name: &str) -> Result<(), String> {
+        let flag = self.flag_mut(name)?;
+        flag.value = flag.default;
+        Ok(())
+    }
+
+    /// whether the flag is kept by to_toml and save. flags loaded from a file
+    /// are persisted
+    pub fn set_persisted(&mut self, name: &str, persisted: bool) -> Result<(), String> {
+        self.flag_mut(name)?.persisted = persisted;
+        Ok(())
+    }
+
+    /// the persisted flags' values
+    pub fn to_toml(&self) -> toml::Table {
+        let mut table = self.pending.clone();
+        table.extend(
+            self.flags
+                .iter()
+                .filter(|(_, flag)| flag.persisted)
+                .map(|(name, flag)| (name.clone(), flag.value.to_toml())),
+        );
+        table
+    }
+
+    /// restore values. values which are invalid for their flag are ignored
+    pub fn load_toml(&mut self, table: &toml::Table) {
+        for (name, value) in table {
+            match self.flags.get_mut(name) {
+                Some(flag) => {
+                    flag.persisted = true;
+                    if let Ok(value) = flag.read_toml(value) {
+                        flag.value = value;
+                    }
+                }
+                None => {
+                    self.pending.insert(name.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// load persisted values, if the file exists
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        self.load_toml(&table);
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_atomic(path, self.to_toml().to_string().as_bytes())
+    }
+
+    /// console commands on the flags the context holds:
+    /// - flag: list every flag
+    /// - flag <name>: show its value and help
+    /// - flag <name> <value>: set it
+    /// - flag_reset <name>
+    /// - flag_persist <name> <true|false>
+    pub fn register_commands<C: 'static>(console: &mut Console<C>, flags: fn(&mut C) -> &mut Self) {
+        console.register(
+            "flag",
+            "flag [name] [value]: list, show, or set debug flags",
+            move |ctx, args| {
+                let flags = flags(ctx);
+                match args {
+                    [] => Ok(flags
+                        .flags
+                        .iter()
+                        .map(|(name, flag)| format!("{} = {}", name, flag.value))
+                        .collect::<Vec<_>>()
+                        .join("\n")),
+                    [name] => {
+                        let flag = flags.flag_mut(name)?;
+                        Ok(format!("{} = {}: {}", name, flag.value, flag.help))
+                    }
+                    [name, value] => Ok(format!("{} = {}", name, flags.set(name, value)?)),
+                    _ => Err("flag takes a name and a value".to_owned()),
+                }
+            },
+        );
+        console.register(
+            "flag_reset",
+            "flag_reset <name>: restore a debug flag's default",
+            move |ctx, args| {
+                let name = args
+                    .first()
+                    .ok_or_else(|| "flag_reset needs a name".to_owned())?;
+                flags(ctx).reset(name)?;
+                Ok(String::new())
+            },
+        );
+        console.register(
+            "flag_persist",
+            "flag_persist <name> <true|false>: keep a debug flag between runs",
+            move |ctx, args| {
+                let [name, persisted] = args else {
+                    return Err("flag_persist takes a name and true or false".to_owned());
+                };
+                let persisted = persisted
+                    .parse()
+                    .map_err(|_| format!("\"{}\" isn't true or false", persisted))?;
+                flags(ctx).set_persisted(name, persisted)?;
+                Ok(String::new())
+            },
+        );
+        let names = move |ctx: &mut C, arg: usize| match arg {
+            0 => flags(ctx).names().map(str::to_owned).collect(),
+            _ => Vec::new(),
+        };
+        for command in ["flag", "flag_reset", "flag_persist"] {
+            let _ = console.set_completer(command, names);
+        }
+    }
+
+    /// a checkbox or slider for each flag
+    #[cfg(feature = "egui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        for (name, flag) in self.flags.iter_mut() {
+            let response = match (&mut flag.value, &flag.range) {
+                (FlagValue::Bool(v), _) => ui.checkbox(v, name.as_str()),
+                (FlagValue::Int(v), FlagRange::Int(range)) => {
+                    ui.add(egui::Slider::new(v, range.clone()).text(name.as_str()))
+                }
+                (FlagValue::Float(v), FlagRange::Float(range)) => {
+                    ui.add(egui::Slider::new(v, range.clone()).text(name.as_str()))
+                }
+                _ => continue,
+            };
+            response.on_hover_text(flag.help.as_str());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_flags() {
+        let mut flags = DebugFlags::new();
+        flags.add_bool("hitboxes", false, "draw collision boxes");
+        flags.add_int("lives", 3, 1..=9, "starting lives");
+        flags.add_float("speed", 1.5, 0.0..=10.0, "player speed");
+
+        assert_eq!(flags.set("hitboxes", "on"), Ok(FlagValue::Bool(true)));
+        assert!(flags.set("lives", "10").is_err());
+        assert!(flags.set("lives", "1.5").is_err());
+        assert!(flags.set("missing", "1").is_err());
+        flags.set("speed", "2.5").unwrap();
+        assert!(flags.bool("hitboxes"));
+        assert_eq!(flags.int("lives"), 3);
+        assert_eq!(flags.float("speed"), 2.5);
+        // wrong type
+        assert_eq!(flags.int("speed"), 0);
+
+        // only persisted flags are saved, and loading can precede adding
+        flags.set_persisted("speed", true).unwrap();
+        let saved = flags.to_toml();
+        assert_eq!(saved.len(), 1);
+        let mut restored = DebugFlags::new();
+        restored.load_toml(&saved);
+        restored.add_float("speed", 1.5, 0.0..=5.0, "player speed");
+        assert_eq!(restored.float("speed"), 2.5);
+        assert_eq!(restored.to_toml(), saved);
+        restored.reset("speed").unwrap();
+        assert_eq!(restored.float("speed"), 1.5);
+
+        let mut console: Console<DebugFlags> = Console::new();
+        DebugFlags::register_commands(&mut console, |flags| flags);
+        assert_eq!(
+            console.execute("flag lives 5", &mut flags).unwrap(),
+            "lives = 5"
+        );
+        assert_eq!(
+            console.execute("flag hitboxes", &mut flags).unwrap(),
+            "hitboxes = true: draw collision boxes"
+        );
+        console.execute("flag_reset lives", &mut flags).unwrap();
+        assert_eq!(flags.int("lives"), 3);
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+#[cfg(feature = "image")]
+use sdl2::image::{ImageRWops, LoadSurface, SaveSurface};
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    rwops::RWops,
+    surface::Surface,
+};
+
+use super::vfs::Vfs;
+
+// sdl objects (windows, textures, fonts, chunks) stay on the thread that
+// created them; ChimericSystem isn't Send. what's done here only touches
+// plain bytes and thread local surfaces, so it's safe on any thread
+
+/// a decoded image, ready to be uploaded as a texture
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    /// rgba32, tightly packed
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+    Image(DecodedImage),
+    /// a font file's contents. it's opened per point size on the main thread
+    Font(Box<[u8]>),
+    /// unprocessed contents, e.g. for sounds
+    Data(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeKind {
+    /// to be uploaded to the named window
+    Image {
+        window_name: String,
+    },
+    Font,
+    Data,
+}
+
+/// where a job's bytes come from
+pub enum Source {
+    /// already read, e.g. from the vfs
+    Bytes(Vec<u8>),
+    /// read from disk by the worker
+    File(PathBuf),
+}
+
+struct Job {
+    path: PathBuf,
+    kind: DecodeKind,
+    source: Source,
+}
+
+/// a completed job
+pub struct Finished {
+    pub path: PathBuf,
+    pub kind: DecodeKind,
+    pub result: Result<Decoded, String>,
+}
+
+/// copy a surface's pixels out as tightly packed rgba32
+pub(crate) fn rgba_pixels(surface: Surface) -> Result<DecodedImage, String> {
+    let surface = surface.convert_format(PixelFormatEnum::RGBA32)?;
+    let (width, height) = (surface.width(), surface.height());
+    if width == 0 || height == 0 {
+        return Err("can't upload an empty surface".into());
+    }
+    let pitch = surface.pitch() as usize;
+    let row = width as usize * 4;
+    let rgba = surface.with_lock(|pixels| {
+        pixels
+            .chunks(pitch)
+            .take(height as usize)
+            .flat_map(|line| line[..row].iter().copied())
+            .collect()
+    });
+    Ok(DecodedImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+/// make pixels of the color transparent, e.g. magenta in legacy assets
+/// without an alpha channel. rgba pixels, in place
+pub fn clear_color_key(pixels: &mut [u8], color_key: Color) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        if pixel[..3] == [color_key.r, color_key.g, color_key.b] {
+            pixel.copy_from_slice(&[0, 0, 0, 0]);
+        }
+    }
+}
+
+/// any format sdl_image supports, or only bmp without the image feature
+pub fn load_surface_bytes(data: &[u8]) -> Result<Surface<'static>, String> {
+    #[cfg(feature = "image")]
+    return RWops::from_bytes(data)?.load();
+    #[cfg(not(feature = "image"))]
+    return Surface::load_bmp_rw(&mut RWops::from_bytes(data)?);
+}
+
+/// any format sdl_image supports, or only bmp without the image feature
+pub fn load_surface_file(path: &Path) -> Result<Surface<'static>, String> {
+    #[cfg(feature = "image")]
+    return Surface::from_file(path);
+    #[cfg(not(feature = "image"))]
+    return Surface::load_bmp(path);
+}
+
+/// through the vfs if the path is mounted, otherwise from disk
+pub fn read_surface(assets: &Vfs, path: &Path) -> Result<Surface<'static>, String> {
+    if assets.is_mounted(path) {
+        load_surface_bytes(&assets.read(path)?)
+    } else {
+        load_surface_file(path)
+    }
+}
+
+/// png, or bmp without the image feature
+pub fn save_surface(surface: &Surface, path: &Path) -> Result<(), String> {
+    #[cfg(feature = "image")]
+    return surface.save(path);
+    #[cfg(not(feature = "image"))]
+    return surface.save_bmp(path);
+}
+
+/// true if the data starts like a truetype, opentype or collection file
+fn is_font(data: &[u8]) -> bool {
+    matches!(
+        data.get(..4),
+        Some([0, 1, 0, 0] | b"OTTO" | b"true" | b"ttcf")
+    )
+}
+
+fn decode(path: &Path, kind: &DecodeKind, source: Source) -> Result<Decoded, String> {
+    let data = match source {
+        Source::Bytes(data) => data,
+        Source::File(file) => {
+            std::fs::read(&file).map_err(|e| format!("{}: {e}", file.display()))?
+        }
+    };
+    match kind {
+        DecodeKind::Image { .. } => {
+            let image = load_surface_bytes(&data).and_then(rgba_pixels);
+            image
+                .map(Decoded::Image)
+                .map_err(|e| format!("{}: {e}", path.display()))
+        }
+        DecodeKind::Font => {
+            if !is_font(&data) {
+                return Err(format!("{} isn't a font file", path.display()));
+            }
+            Ok(Decoded::Font(data.into_boxed_slice()))
+        }
+        DecodeKind::Data => Ok(Decoded::Data(data)),
+    }
+}
+
+/// decodes assets on worker threads. results are collected on the main
+/// thread with poll and turned into sdl objects there
+pub struct DecodeQueue {
+    jobs: Option<Sender<Job>>,
+    results: Receiver<Finished>,
+    workers: Vec<JoinHandle<()>>,
+    pending: usize,
+}
+
+impl DecodeQueue {
+    /// at least one worker is started
+    pub fn new(threads: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                std::thread::spawn(move || loop {
+                    // the lock is released before decoding
+                    let job = match job_receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    let Ok(job) = job else {
+                        return; // queue dropped
+                    };
+                    let result = decode(&job.path, &job.kind, job.source);
+                    let finished = Finished {
+                        path: job.path,
+                        kind: job.kind,
+                        result,
+                    };
+                    if result_sender.send(finished).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+        Self {
+            jobs: Some(jobs),
+            results,
+            workers,
+            pending: 0,
+        }
+    }
+
+    /// one worker per core
+    pub fn with_available_parallelism() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    pub fn submit(&mut self, path: &Path, kind: DecodeKind, source: Source) -> Result<(), String> {
+        let job = Job {
+            path: path.to_owned(),
+            kind,
+            source,
+        };
+        self.jobs
+            .as_ref()
+            .ok_or("decode queue is shut down")?
+            .send(job)
+            .map_err(|e| e.to_string())?;
+        self.pending += 1;
+        Ok(())
+    }
+
+    /// a finished job, if any, without blocking
+    pub fn poll(&mut self) -> Option<Finished> {
+        let finished = self.results.try_recv().ok()?;
+        self.pending -= 1;
+        Some(finished)
+    }
+
+    /// submitted jobs which haven't been polled yet
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+impl Drop for DecodeQueue {
+    fn drop(&mut self) {
+        // workers stop once the channel is closed and the queue is empty
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_color_key() {
+        let mut pixels = [255, 0, 255, 255, 255, 0, 254, 255];
+        clear_color_key(&mut pixels, Color::RGB(255, 0, 255));
+        assert_eq!(pixels, [0, 0, 0, 0, 255, 0, 254, 255]);
+    }
+
+    #[test]
+    fn test_queue() {
+        assert_send::<DecodeQueue>();
+        assert_send::<Finished>();
+
+        let mut queue = DecodeQueue::new(2);
+        let font = [0u8, 1, 0, 0, 9, 9].to_vec();
+        queue
+            .submit(Path::new("a.ttf"), DecodeKind::Font, Source::Bytes(font))
+            .unwrap();
+        queue
+            .submit(
+                Path::new("b.ttf"),
+                DecodeKind::Font,
+                Source::Bytes(vec![1, 2]),
+            )
+            .unwrap();
+        queue
+            .submit(Path::new("c"), DecodeKind::Data, Source::Bytes(vec![3]))
+            .unwrap();
+        assert_eq!(queue.pending(), 3);
+
+        let mut finished = Vec::new();
+        let start = Instant::now();
+        while finished.len() < 3 && start.elapsed() < Duration::from_secs(5) {
+            finished.extend(queue.poll());
+        }
+        assert_eq!(queue.pending(), 0);
+        finished.sort_by(|a, b| a.path.cmp(&b.path));
+        assert!(matches!(finished[0].result, Ok(Decoded::Font(_))));
+        assert!(finished[1].result.is_err());
+        assert_eq!(finished[2].result, Ok(Decoded::Data(vec![3])));
+    }
+}
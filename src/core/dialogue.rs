@@ -0,0 +1,788 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+#[cfg(feature = "ttf")]
+use sdl2::rect::Rect;
+
+#[cfg(feature = "ttf")]
+use super::ui::Ui;
+use super::{
+    variables::{Value, Variables},
+    vfs::Vfs,
+};
+
+/// the most instructions run by one advance, so a node which jumps to itself
+/// without a line in between can't hang the game
+const MAX_OPS_PER_STEP: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    var: String,
+    negate: bool,
+    compare: Option<(Compare, Value)>,
+}
+
+impl Condition {
+    /// var, !var, or var followed by one of == != < <= > >= and a value
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        for (symbol, compare) in [
+            ("==", Compare::Eq),
+            ("!=", Compare::Ne),
+            ("<=", Compare::Le),
+            (">=", Compare::Ge),
+            ("<", Compare::Lt),
+            (">", Compare::Gt),
+        ] {
+            if let Some((var, value)) = text.split_once(symbol) {
+                return Ok(Self {
+                    var: parse_var(var)?,
+                    negate: false,
+                    compare: Some((compare, Value::parse(value)?)),
+                });
+            }
+        }
+        let (negate, var) = match text.strip_prefix('!') {
+            Some(var) => (true, var),
+            None => (false, text),
+        };
+        Ok(Self {
+            var: parse_var(var)?,
+            negate,
+            compare: None,
+        })
+    }
+
+    fn test(&self, vars: &Variables) -> bool {
+        let Some((compare, value)) = &self.compare else {
+            return vars.bool(&self.var) != self.negate;
+        };
+        let var = vars.get(&self.var);
+        match (compare, var, value) {
+            (Compare::Eq, var, value) => var == Some(value),
+            (Compare::Ne, var, value) => var != Some(value),
+            (compare, _, Value::Number(value)) => {
+                let var = vars.number(&self.var);
+                match compare {
+                    Compare::Lt => var < *value,
+                    Compare::Le => var <= *value,
+                    Compare::Gt => var > *value,
+                    _ => var >= *value,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse_var(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!("\"{name}\" isn't a variable name"));
+    }
+    Ok(name.to_owned())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Choice {
+    text: String,
+    condition: Option<Condition>,
+    target: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Line {
+        speaker: Option<String>,
+        text: String,
+    },
+    /// op is '=', '+' or '-'
+    Set {
+        var: String,
+        op: char,
+        value: Value,
+    },
+    /// go to otherwise unless the condition holds
+    Branch {
+        condition: Condition,
+        otherwise: usize,
+    },
+    Goto(usize),
+    Jump(String),
+    End,
+    Command(String),
+    Choices(Vec<Choice>),
+}
+
+/// branching conversations, as named nodes of lines, choices and
+/// commands:
+///
+/// ```text
+/// === gate
+/// Guard: Halt! Who goes there?
+/// <<if met_guard>>
+/// Guard: Oh, it's you again, {name}.
+/// <<else>>
+/// <<set met_guard = true>>
+/// <<endif>>
+/// -> A friend. => friend
+/// -> Here's ten gold. [if gold >= 10] => bribe
+///
+/// === bribe
+/// <<set gold -= 10>>
+/// <<play_sound coins.ogg>>
+/// Guard: Pass, then.
+/// ```
+///
+/// `{var}` in text is replaced with the variable. `<<set var = value>>`
+/// (or += and -= for numbers), `<<if condition>>`, `<<else>>`,
+/// `<<endif>>`, `<<jump node>>` and `<<end>>` are built in; any other
+/// `<<...>>` is a command for the game. consecutive `->` lines are a choice,
+/// each leading to a node. a node which runs out ends the conversation.
+/// `//` starts a comment line
+#[derive(Debug, Clone, Default)]
+pub struct Dialogue {
+    nodes: HashMap<String, Vec<Op>>,
+}
+
+impl Dialogue {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut dialogue = Self::default();
+        let mut node: Option<(String, Vec<Op>)> = None;
+        // the branch (and the goto skipping the else) of each open if
+        let mut ifs: Vec<(usize, Option<usize>)> = Vec::new();
+        let finish = |dialogue: &mut Self,
+                      node: Option<(String, Vec<Op>)>,
+                      ifs: &mut Vec<(usize, Option<usize>)>|
+         -> Result<(), String> {
+            if let Some((name, ops)) = node {
+                if !ifs.is_empty() {
+                    return Err(format!("node \"{name}\" has an if without an endif"));
+                }
+                if dialogue.nodes.insert(name.clone(), ops).is_some() {
+                    return Err(format!("node \"{name}\" is defined twice"));
+                }
+            }
+            Ok(())
+        };
+
+        for (number, line) in content.lines().enumerate() {
+            let error = |e: String| format!("line {}: {e}", number + 1);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("===") {
+                finish(&mut dialogue, node.take(), &mut ifs)?;
+                node = Some((parse_var(name).map_err(error)?, Vec::new()));
+                continue;
+            }
+            let Some((_, ops)) = node.as_mut() else {
+                return Err(error("expected \"=== node\" first".into()));
+            };
+            if let Some(choice) = line.strip_prefix("->") {
+                let choice = Self::parse_choice(choice).map_err(error)?;
+                match ops.last_mut() {
+                    Some(Op::Choices(choices)) => choices.push(choice),
+                    _ => ops.push(Op::Choices(vec![choice])),
+                }
+                continue;
+            }
+            let Some(command) = line
+                .strip_prefix("<<")
+                .and_then(|rest| rest.strip_suffix(">>"))
+            else {
+                let (speaker, text) = match line.split_once(':') {
+                    Some((speaker, text))
+                        if !speaker.trim().is_empty() && !speaker.contains(char::is_whitespace) =>
+                    {
+                        (Some(speaker.trim().to_owned()), text.trim())
+                    }
+                    _ => (None, line),
+                };
+                ops.push(Op::Line {
+                    speaker,
+                    text: text.to_owned(),
+                });
+                continue;
+            };
+            let command = command.trim();
+            let (keyword, rest) = command.split_once(' ').unwrap_or((command, ""));
+            match keyword {
+                "set" => {
+                    let (var, op, value) = [("+=", '+'), ("-=", '-'), ("=", '=')]
+                        .into_iter()
+                        .find_map(|(symbol, op)| {
+                            rest.split_once(symbol).map(|(var, value)| (var, op, value))
+                        })
+                        .ok_or_else(|| error("expected <<set var = value>>".into()))?;
+                    ops.push(Op::Set {
+                        var: parse_var(var).map_err(error)?,
+                        op,
+                        value: Value::parse(value).map_err(error)?,
+                    });
+                }
+                "if" => {
+                    ifs.push((ops.len(), None));
+                    ops.push(Op::Branch {
+                        condition: Condition::parse(rest).map_err(error)?,
+                        otherwise: 0,
+                    });
+                }
+                "else" => {
+                    let open = ifs
+                        .last_mut()
+                        .filter(|(_, skip)| skip.is_none())
+                        .ok_or_else(|| error("else without an if".into()))?;
+                    open.1 = Some(ops.len());
+                    ops.push(Op::Goto(0));
+                    let end = ops.len();
+                    if let Op::Branch { otherwise, .. } = &mut ops[open.0] {
+                        *otherwise = end;
+                    }
+                }
+                "endif" => {
+                    let (branch, skip) = ifs
+                        .pop()
+                        .ok_or_else(|| error("endif without an if".into()))?;
+                    let end = ops.len();
+                    match skip {
+                        Some(skip) => ops[skip] = Op::Goto(end),
+                        None => {
+                            if let Op::Branch { otherwise, .. } = &mut ops[branch] {
+                                *otherwise = end;
+                            }
+                        }
+                    }
+                }
+                "jump" => ops.push(Op::Jump(parse_var(rest).map_err(error)?)),
+                "end" => ops.push(Op::End),
+                _ => ops.push(Op::Command(command.to_owned())),
+            }
+        }
+        finish(&mut dialogue, node, &mut ifs)?;
+
+        for (name, ops) in &dialogue.nodes {
+            for op in ops {
+                let targets: Vec<&str> = match op {
+                    Op::Jump(target) => vec![target],
+                    Op::Choices(choices) => choices.iter().map(|c| c.target.as_str()).collect(),
+                    _ => continue,
+                };
+                if let Some(missing) = targets.iter().find(|t| !dialogue.nodes.contains_key(**t)) {
+                    return Err(format!(
+                        "node \"{name}\" leads to \"{missing}\" which doesn't exist"
+                    ));
+                }
+            }
+        }
+        Ok(dialogue)
+    }
+
+    /// text [if condition] => node
+    fn parse_choice(choice: &str) -> Result<Choice, String> {
+        let (text, target) = choice.rsplit_once("=>").ok_or("expected -> text => node")?;
+        let text = text.trim();
+        let (text, condition) = match text
+            .strip_suffix(']')
+            .and_then(|rest| rest.rsplit_once("[if "))
+        {
+            Some((text, condition)) => (text.trim(), Some(Condition::parse(condition)?)),
+            None => (text, None),
+        };
+        Ok(Choice {
+            text: text.to_owned(),
+            condition,
+            target: parse_var(target)?,
+        })
+    }
+
+    pub fn load_file(path: &Path) -> Result<Self, String> {
+        Self::load_from(&Vfs::new(), path)
+    }
+
+    /// as load_file, resolving the path through the vfs
+    pub fn load_from(assets: &Vfs, path: &Path) -> Result<Self, String> {
+        Self::parse(&assets.read_to_string(path)?).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn has_node(&self, name: &str) -> bool {
+        self.nodes.contains_key(name)
+    }
+
+    /// in no particular order
+    pub fn nodes(&self) -> impl Iterator<Item = &str> {
+        self.nodes.keys().map(String::as_str)
+    }
+}
+
+/// replace each {var} with its value. unset variables are empty
+fn interpolate(text: &str, vars: &Variables) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        if let Some(value) = vars.get(&rest[start + 1..start + len]) {
+            out.push_str(&value.to_string());
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// what the conversation needs from the game next
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogueStep {
+    Line {
+        speaker: Option<String>,
+        text: String,
+    },
+    /// the text of each choice whose condition holds. answer with choose
+    Choices(Vec<String>),
+    /// a <<command>> for the game to carry out
+    Command(String),
+    End,
+}
+
+/// where a conversation is up to in a Dialogue. variables are read and set
+/// in the given Variables, normally world.variables
+#[derive(Debug, Clone)]
+pub struct DialogueRunner {
+    node: String,
+    pc: usize,
+    /// the targets of the choices offered
+    choices: Vec<String>,
+    finished: bool,
+}
+
+impl DialogueRunner {
+    pub fn new(node: &str) -> Self {
+        Self {
+            node: node.to_owned(),
+            pc: 0,
+            choices: Vec::new(),
+            finished: false,
+        }
+    }
+
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// run until the next line, choice, command, or the end
+    pub fn advance(
+        &mut self,
+        dialogue: &Dialogue,
+        vars: &mut Variables,
+    ) -> Result<DialogueStep, String> {
+        if !self.choices.is_empty() {
+            return Err("waiting for a choice".into());
+        }
+        for _ in 0..MAX_OPS_PER_STEP {
+            if self.finished {
+                return Ok(DialogueStep::End);
+            }
+            let ops = dialogue
+                .nodes
+                .get(&self.node)
+                .ok_or_else(|| format!("node \"{}\" doesn't exist", self.node))?;
+            let Some(op) = ops.get(self.pc) else {
+                self.finished = true;
+                continue;
+            };
+            self.pc += 1;
+            match op {
+                Op::Line { speaker, text } => {
+                    return Ok(DialogueStep::Line {
+                        speaker: speaker.clone(),
+                        text: interpolate(text, vars),
+                    })
+                }
+                Op::Set { var, op, value } => {
+                    let value = match (op, value) {
+                        ('+', Value::Number(n)) => Value::Number(vars.number(var) + n),
+                        ('-', Value::Number(n)) => Value::Number(vars.number(var) - n),
+                        (_, value) => value.clone(),
+                    };
+                    vars.set(var, value);
+                }
+                Op::Branch {
+                    condition,
+                    otherwise,
+                } => {
+                    if !condition.test(vars) {
+                        self.pc = *otherwise;
+                    }
+                }
+                Op::Goto(pc) => self.pc = *pc,
+                Op::Jump(node) => {
+                    self.node = node.clone();
+                    self.pc = 0;
+                }
+                Op::End => self.finished = true,
+                Op::Command(command) => {
+                    return Ok(DialogueStep::Command(interpolate(command, vars)))
+                }
+                Op::Choices(choices) => {
+                    let available: Vec<&Choice> = choices
+                        .iter()
+                        .filter(|c| c.condition.as_ref().is_none_or(|c| c.test(vars)))
+                        .collect();
+                    if available.is_empty() {
+                        continue;
+                    }
+                    self.choices = available.iter().map(|c| c.target.clone()).collect();
+                    let texts = available
+                        .iter()
+                        .map(|c| interpolate(&c.text, vars))
+                        .collect();
+                    return Ok(DialogueStep::Choices(texts));
+                }
+            }
+        }
+        Err(format!("node \"{}\" loops without a line", self.node))
+    }
+
+    /// pick one of the choices from the last step
+    pub fn choose(&mut self, index: usize) -> Result<(), String> {
+        let target = self.choices.get(index).ok_or("no such choice")?.clone();
+        self.choices.clear();
+        self.node = target;
+        self.pc = 0;
+        Ok(())
+    }
+}
+
+/// reveals text a character at a time
+#[derive(Debug, Clone)]
+pub struct Typewriter {
+    text: String,
+    /// characters revealed, including part of the next
+    shown: f32,
+    pub chars_per_second: f32,
+}
+
+impl Typewriter {
+    pub fn new(chars_per_second: f32) -> Self {
+        Self {
+            text: String::new(),
+            shown: 0.,
+            chars_per_second,
+        }
+    }
+
+    /// start revealing new text
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_owned();
+        self.shown = 0.;
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.shown += dt.as_secs_f32() * self.chars_per_second;
+    }
+
+    /// reveal everything now
+    pub fn skip(&mut self) {
+        self.shown = f32::INFINITY;
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// how many characters are revealed
+    pub fn shown(&self) -> usize {
+        (self.shown as usize).min(self.text.chars().count())
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.shown() == self.text.chars().count()
+    }
+
+    pub fn visible(&self) -> &str {
+        match self.text.char_indices().nth(self.shown()) {
+            Some((end, _)) => &self.text[..end],
+            None => &self.text,
+        }
+    }
+}
+
+/// break the text into lines of at most width characters, at spaces where
+/// possible
+#[cfg(feature = "ttf")]
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let len = line.chars().count();
+        if len > 0 && len + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        } else if len > 0 {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+/// a conversation shown with a typewriter and choice buttons. commands are
+/// collected rather than stopping the conversation
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    runner: DialogueRunner,
+    step: DialogueStep,
+    pub typewriter: Typewriter,
+    commands: Vec<String>,
+}
+
+impl Conversation {
+    pub fn start(dialogue: &Dialogue, node: &str, vars: &mut Variables) -> Result<Self, String> {
+        let mut conversation = Self {
+            runner: DialogueRunner::new(node),
+            step: DialogueStep::End,
+            typewriter: Typewriter::new(40.),
+            commands: Vec::new(),
+        };
+        conversation.next(dialogue, vars)?;
+        Ok(conversation)
+    }
+
+    fn next(&mut self, dialogue: &Dialogue, vars: &mut Variables) -> Result<(), String> {
+        loop {
+            match self.runner.advance(dialogue, vars)? {
+                DialogueStep::Command(command) => self.commands.push(command),
+                step => {
+                    if let DialogueStep::Line { text, .. } = &step {
+                        self.typewriter.set(text);
+                    }
+                    self.step = step;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    pub fn step(&self) -> &DialogueStep {
+        &self.step
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step == DialogueStep::End
+    }
+
+    /// the commands reached since last taken, oldest first
+    pub fn take_commands(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.commands)
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.typewriter.update(dt);
+    }
+
+    /// e.g. on a confirm action: finish revealing the line, or go past it
+    pub fn confirm(&mut self, dialogue: &Dialogue, vars: &mut Variables) -> Result<(), String> {
+        if !matches!(self.step, DialogueStep::Line { .. }) {
+            return Ok(());
+        }
+        if !self.typewriter.is_done() {
+            self.typewriter.skip();
+            return Ok(());
+        }
+        self.next(dialogue, vars)
+    }
+
+    pub fn choose(
+        &mut self,
+        index: usize,
+        dialogue: &Dialogue,
+        vars: &mut Variables,
+    ) -> Result<(), String> {
+        self.runner.choose(index)?;
+        self.next(dialogue, vars)
+    }
+
+    /// declare a panel over rect with the speaker, the revealed text, and a
+    /// button per choice (or to continue, once the line is revealed)
+    #[cfg(feature = "ttf")]
+    pub fn ui(
+        &mut self,
+        ui: &mut Ui,
+        rect: Rect,
+        dialogue: &Dialogue,
+        vars: &mut Variables,
+    ) -> Result<(), String> {
+        if self.is_finished() {
+            return Ok(());
+        }
+        let padding = ui.style.scaled_padding();
+        let row = ui.style.scaled_point_size() as i32 + padding * 2;
+        let width = rect.width() as i32 - padding * 2;
+        ui.panel(rect);
+        let mut y = rect.y() + padding;
+        let mut next = || {
+            let line = Rect::new(rect.x() + padding, y, width.max(1) as u32, row as u32);
+            y += row;
+            line
+        };
+        match self.step.clone() {
+            DialogueStep::Line { speaker, .. } => {
+                if let Some(speaker) = speaker {
+                    ui.label(&speaker, next());
+                }
+                // wrapped as a whole, so words don't jump lines as they appear
+                let columns =
+                    (width as f32 / (ui.style.scaled_point_size() as f32 * 0.55)).max(1.) as usize;
+                let mut remaining = self.typewriter.shown();
+                for line in wrap(self.typewriter.text(), columns) {
+                    let len = line.chars().count();
+                    let shown: String = line.chars().take(remaining).collect();
+                    remaining = remaining.saturating_sub(len + 1);
+                    ui.label(&shown, next());
+                }
+                if self.typewriter.is_done() {
+                    let button = Rect::new(
+                        rect.right() - padding - row * 2,
+                        rect.bottom() - padding - row,
+                        (row * 2) as u32,
+                        row as u32,
+                    );
+                    if ui.button(">##dialogue", button) {
+                        self.confirm(dialogue, vars)?;
+                    }
+                }
+            }
+            DialogueStep::Choices(choices) => {
+                for (i, choice) in choices.iter().enumerate() {
+                    if ui.button(&format!("{choice}##choice{i}"), next()) {
+                        self.choose(i, dialogue, vars)?;
+                        break;
+                    }
+                }
+            }
+            DialogueStep::Command(_) | DialogueStep::End => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GATE: &str = r#"
+        === gate
+        // a comment
+        Guard: Halt! Who goes there?
+        <<if met_guard>>
+        Guard: Oh, it's you again, {name}.
+        <<else>>
+        <<set met_guard = true>>
+        <<endif>>
+        -> A friend. => friend
+        -> Here's ten gold. [if gold >= 10] => bribe
+
+        === friend
+        Guard: Prove it.
+        <<jump gate>>
+
+        === bribe
+        <<set gold -= 10>>
+        <<play_sound coins.ogg>>
+        Guard: Pass, then.
+    "#;
+
+    #[test]
+    fn test_dialogue() {
+        let dialogue = Dialogue::parse(GATE).unwrap();
+        let mut vars = Variables::new();
+        vars.set("name", Value::Text("Ann".into()));
+        let mut runner = DialogueRunner::new("gate");
+        let line = |speaker: &str, text: &str| DialogueStep::Line {
+            speaker: Some(speaker.into()),
+            text: text.into(),
+        };
+
+        assert_eq!(
+            runner.advance(&dialogue, &mut vars).unwrap(),
+            line("Guard", "Halt! Who goes there?")
+        );
+        assert_eq!(
+            runner.advance(&dialogue, &mut vars).unwrap(),
+            DialogueStep::Choices(vec!["A friend.".into()])
+        );
+        assert!(vars.bool("met_guard"));
+        assert!(runner.advance(&dialogue, &mut vars).is_err());
+        runner.choose(0).unwrap();
+        assert_eq!(
+            runner.advance(&dialogue, &mut vars).unwrap(),
+            line("Guard", "Prove it.")
+        );
+
+        vars.set("gold", Value::Number(15.));
+        runner.advance(&dialogue, &mut vars).unwrap();
+        assert_eq!(
+            runner.advance(&dialogue, &mut vars).unwrap(),
+            line("Guard", "Oh, it's you again, Ann.")
+        );
+        assert_eq!(
+            runner.advance(&dialogue, &mut vars).unwrap(),
+            DialogueStep::Choices(vec!["A friend.".into(), "Here's ten gold.".into()])
+        );
+        runner.choose(1).unwrap();
+        assert_eq!(
+            runner.advance(&dialogue, &mut vars).unwrap(),
+            DialogueStep::Command("play_sound coins.ogg".into())
+        );
+        assert_eq!(vars.number("gold"), 5.);
+        runner.advance(&dialogue, &mut vars).unwrap();
+        assert_eq!(
+            runner.advance(&dialogue, &mut vars).unwrap(),
+            DialogueStep::End
+        );
+        assert!(runner.is_finished());
+
+        assert!(Dialogue::parse("=== a\n<<jump b>>").is_err());
+        assert!(Dialogue::parse("=== a\n<<if x>>\nhi").is_err());
+        assert!(Dialogue::parse("hi").is_err());
+    }
+
+    #[test]
+    fn test_conversation() {
+        let dialogue = Dialogue::parse(GATE).unwrap();
+        let mut vars = Variables::new();
+        vars.set("gold", Value::Number(10.));
+        let mut conversation = Conversation::start(&dialogue, "gate", &mut vars).unwrap();
+        conversation.update(Duration::from_millis(100));
+        assert_eq!(conversation.typewriter.visible(), "Halt");
+        // the first confirm reveals the rest
+        conversation.confirm(&dialogue, &mut vars).unwrap();
+        assert_eq!(conversation.typewriter.visible(), "Halt! Who goes there?");
+        conversation.confirm(&dialogue, &mut vars).unwrap();
+        conversation.choose(1, &dialogue, &mut vars).unwrap();
+        assert_eq!(conversation.take_commands(), vec!["play_sound coins.ogg"]);
+        conversation.typewriter.skip();
+        conversation.confirm(&dialogue, &mut vars).unwrap();
+        assert!(conversation.is_finished());
+
+        #[cfg(feature = "ttf")]
+        assert_eq!(wrap("a bb ccc dd", 5), vec!["a bb", "ccc", "dd"]);
+    }
+}
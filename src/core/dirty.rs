@@ -0,0 +1,124 @@
+use sdl2::rect::Rect;
+
+/// past this many separate regions, they're merged into one
+const MAX_REGIONS: usize = 16;
+
+// rather than Rect's methods, which go through sdl
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.left() < b.right() && b.left() < a.right() && a.top() < b.bottom() && b.top() < a.bottom()
+}
+
+fn union(a: Rect, b: Rect) -> Rect {
+    let (left, top) = (a.left().min(b.left()), a.top().min(b.top()));
+    let (right, bottom) = (a.right().max(b.right()), a.bottom().max(b.bottom()));
+    Rect::new(left, top, (right - left) as u32, (bottom - top) as u32)
+}
+
+/// what part of a frame has to be drawn again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redraw {
+    Nothing,
+    All,
+    /// draws are clipped to it, so it covers every dirty region
+    Area(Rect),
+}
+
+/// the areas of a window that changed since it was last presented, in
+/// logical coordinates
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DirtyRegions {
+    regions: Vec<Rect>,
+    all: bool,
+}
+
+impl DirtyRegions {
+    /// everything starts dirty, since nothing was drawn yet
+    pub fn new() -> Self {
+        Self {
+            regions: Default::default(),
+            all: true,
+        }
+    }
+
+    pub fn mark(&mut self, rect: Rect) {
+        if self.all {
+            return;
+        }
+        // grow an overlapping region instead of adding one, repeatedly since
+        // the grown region may overlap others
+        let mut rect = rect;
+        while let Some(i) = self.regions.iter().position(|r| overlaps(*r, rect)) {
+            rect = union(rect, self.regions.swap_remove(i));
+        }
+        self.regions.push(rect);
+        if self.regions.len() > MAX_REGIONS {
+            let bounds = self.bounds().expect("not empty");
+            self.regions = vec![bounds];
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        self.all = true;
+        self.regions.clear();
+    }
+
+    pub fn is_clean(&self) -> bool {
+        !self.all && self.regions.is_empty()
+    }
+
+    /// separate, non overlapping regions. empty if everything is dirty
+    pub fn regions(&self) -> &[Rect] {
+        &self.regions
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        self.regions.iter().copied().reduce(union)
+    }
+
+    pub fn redraw(&self) -> Redraw {
+        if self.all {
+            return Redraw::All;
+        }
+        match self.bounds() {
+            Some(bounds) => Redraw::Area(bounds),
+            None => Redraw::Nothing,
+        }
+    }
+
+    /// after presenting
+    pub fn clear(&mut self) {
+        self.all = false;
+        self.regions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty() {
+        let mut dirty = DirtyRegions::new();
+        assert_eq!(dirty.redraw(), Redraw::All);
+        dirty.clear();
+        assert!(dirty.is_clean());
+        assert_eq!(dirty.redraw(), Redraw::Nothing);
+
+        dirty.mark(Rect::new(0, 0, 10, 10));
+        dirty.mark(Rect::new(50, 50, 10, 10));
+        assert_eq!(dirty.regions().len(), 2);
+        // joins both
+        dirty.mark(Rect::new(5, 5, 50, 50));
+        assert_eq!(dirty.regions(), [Rect::new(0, 0, 60, 60)]);
+        assert_eq!(dirty.redraw(), Redraw::Area(Rect::new(0, 0, 60, 60)));
+
+        for i in 0..MAX_REGIONS as i32 {
+            dirty.mark(Rect::new(100 + i * 10, 0, 5, 5));
+        }
+        assert_eq!(dirty.regions(), [Rect::new(0, 0, 255, 60)]);
+
+        dirty.mark_all();
+        dirty.mark(Rect::new(0, 0, 1, 1));
+        assert_eq!(dirty.redraw(), Redraw::All);
+    }
+}
@@ -0,0 +1,216 @@
+use std::f32::consts::PI;
+
+use sdl2::rect::FPoint;
+
+/// maps linear progress in [0, 1] to eased progress. used by tweens, and for
+/// anything else that should move along the same curves (camera moves, ui
+/// transitions)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    /// overshoots and springs back
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    /// like css cubic-bezier()
+    Bezier(CubicBezier),
+}
+
+fn elastic_out(t: f32) -> f32 {
+    2f32.powf(-10. * t) * ((t * 10. - 0.75) * (2. * PI / 3.)).sin() + 1.
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1. / D {
+        N * t * t
+    } else if t < 2. / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+impl Ease {
+    pub fn apply(self, t: f32) -> f32 {
+        // exact at the ends, whatever rounding the curve does
+        if t <= 0. {
+            return 0.;
+        }
+        if t >= 1. {
+            return 1.;
+        }
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1. - (1. - t) * (1. - t),
+            Ease::QuadInOut => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(2) / 2.
+                }
+            }
+            Ease::CubicIn => t * t * t,
+            Ease::CubicOut => 1. - (1. - t).powi(3),
+            Ease::CubicInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(3) / 2.
+                }
+            }
+            Ease::ElasticIn => 1. - elastic_out(1. - t),
+            Ease::ElasticOut => elastic_out(t),
+            Ease::ElasticInOut => {
+                if t < 0.5 {
+                    (1. - elastic_out(1. - 2. * t)) / 2.
+                } else {
+                    (1. + elastic_out(2. * t - 1.)) / 2.
+                }
+            }
+            Ease::BounceIn => 1. - bounce_out(1. - t),
+            Ease::BounceOut => bounce_out(t),
+            Ease::BounceInOut => {
+                if t < 0.5 {
+                    (1. - bounce_out(1. - 2. * t)) / 2.
+                } else {
+                    (1. + bounce_out(2. * t - 1.)) / 2.
+                }
+            }
+            Ease::Bezier(curve) => curve.apply(t),
+        }
+    }
+}
+
+/// a point on the cubic bezier curve with the control values
+pub fn bezier(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let u = 1. - t;
+    u * u * u * p0 + 3. * u * u * t * p1 + 3. * u * t * t * p2 + t * t * t * p3
+}
+
+/// the derivative of bezier with respect to t
+fn bezier_slope(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let u = 1. - t;
+    3. * u * u * (p1 - p0) + 6. * u * t * (p2 - p1) + 3. * t * t * (p3 - p2)
+}
+
+/// a point on the 2d cubic bezier curve with the control points
+pub fn bezier_point(p0: FPoint, p1: FPoint, p2: FPoint, p3: FPoint, t: f32) -> FPoint {
+    FPoint::new(
+        bezier(p0.x(), p1.x(), p2.x(), p3.x(), t),
+        bezier(p0.y(), p1.y(), p2.y(), p3.y(), t),
+    )
+}
+
+/// a timing curve from (0, 0) to (1, 1) with two control points, as in css.
+/// the x values should be in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubicBezier {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+impl CubicBezier {
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        Self { x1, y1, x2, y2 }
+    }
+
+    /// the curve's y where its x is the progress
+    pub fn apply(&self, t: f32) -> f32 {
+        let x = |s| bezier(0., self.x1, self.x2, 1., s);
+        // newton's method, falling back to bisection where the slope is flat
+        let mut s = t;
+        for _ in 0..8 {
+            let error = x(s) - t;
+            if error.abs() < 1e-6 {
+                return bezier(0., self.y1, self.y2, 1., s);
+            }
+            let slope = bezier_slope(0., self.x1, self.x2, 1., s);
+            if slope.abs() < 1e-6 {
+                break;
+            }
+            s -= error / slope;
+        }
+        let (mut low, mut high) = (0f32, 1f32);
+        s = t;
+        for _ in 0..32 {
+            if x(s) < t {
+                low = s;
+            } else {
+                high = s;
+            }
+            s = (low + high) / 2.;
+        }
+        bezier(0., self.y1, self.y2, 1., s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ease_bounds() {
+        for ease in [
+            Ease::Linear,
+            Ease::QuadIn,
+            Ease::QuadOut,
+            Ease::QuadInOut,
+            Ease::CubicIn,
+            Ease::CubicOut,
+            Ease::CubicInOut,
+            Ease::ElasticIn,
+            Ease::ElasticOut,
+            Ease::ElasticInOut,
+            Ease::BounceIn,
+            Ease::BounceOut,
+            Ease::BounceInOut,
+            Ease::Bezier(CubicBezier::new(0.25, 0.1, 0.25, 1.)),
+        ] {
+            assert_eq!(ease.apply(0.), 0.);
+            assert_eq!(ease.apply(1.), 1.);
+            // continuous at the halfway point of the in out curves
+            let step = (ease.apply(0.5 + 1e-4) - ease.apply(0.5 - 1e-4)).abs();
+            assert!(step < 0.01, "{:?}", ease);
+        }
+    }
+
+    #[test]
+    fn test_bezier() {
+        // a straight line is linear
+        let linear = CubicBezier::new(1. / 3., 1. / 3., 2. / 3., 2. / 3.);
+        for t in [0.1, 0.5, 0.9] {
+            assert!((linear.apply(t) - t).abs() < 1e-4);
+        }
+        let ease_in = CubicBezier::new(0.42, 0., 1., 1.);
+        assert!(ease_in.apply(0.25) < 0.25);
+
+        let point = bezier_point(
+            FPoint::new(0., 0.),
+            FPoint::new(0., 10.),
+            FPoint::new(10., 10.),
+            FPoint::new(10., 0.),
+            0.5,
+        );
+        assert_eq!(point, FPoint::new(5., 7.5));
+    }
+}
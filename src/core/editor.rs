@@ -0,0 +1,620 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::{
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
+    pixels::Color,
+    rect::{FPoint, FRect, Rect},
+};
+
+use super::{
+    camera::Camera2D,
+    events::EngineEvent,
+    input::InputState,
+    level::{Level, LevelLoader, COLLIDER_CLASS, COLLIDER_LAYER, ENTITY_LAYER, LEVEL_EXTENSION},
+    prefab::PrefabRegistry,
+    system::ChimericSystem,
+    tiled::{MapObject, ObjectLayer, ObjectShape, TiledMap},
+    tilemap::{Autotiler, TileLayer, EMPTY_TILE},
+    ui::{Ui, UiStyle},
+};
+
+const ENTITY_COLOR: Color = Color::RGBA(80, 200, 255, 200);
+const COLLIDER_COLOR: Color = Color::RGBA(255, 80, 80, 96);
+const CURSOR_COLOR: Color = Color::RGBA(255, 255, 255, 64);
+
+/// what a left click does in the map
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditorTool {
+    /// set tiles in the current layer to the global tile id
+    Paint(u32),
+    /// paint the autotiler's terrain, picking edge and corner tiles
+    Terrain(usize),
+    Erase,
+    /// place the prefab
+    Entity(String),
+    /// drag out a rect
+    Collider,
+    /// remove the object under the mouse
+    Delete,
+}
+
+fn is_native(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == LEVEL_EXTENSION)
+}
+
+/// a level editor, run in the game's window instead of the game (e.g. behind
+/// a command line flag). it paints tiles and autotiled terrain, places entities from the prefab
+/// registry, and drags out colliders, saving to a .tmx file which tiled can
+/// also open, or to a native Level if the path has the LEVEL_EXTENSION. in
+/// game, TiledMap::spawn_objects or Level::spawn_entities spawns the placed
+/// entities.
+///
+/// the middle mouse button pans and the wheel zooms. ctrl+s saves
+pub struct Editor {
+    pub map: TiledMap,
+    pub path: PathBuf,
+    pub camera: Camera2D,
+    pub tool: EditorTool,
+    /// index into the map's tile layers
+    pub layer: usize,
+    /// entities are placed at tile centers and colliders on tile edges
+    pub snap: bool,
+    /// reads and writes native levels
+    pub levels: LevelLoader,
+    /// terrains for the Terrain tool. erasing also updates the terrain
+    /// around the erased tile
+    pub autotiler: Autotiler,
+    ui: Ui,
+    /// in the world
+    mouse: Option<FPoint>,
+    /// where the collider being dragged started
+    drag_start: Option<FPoint>,
+    /// in the window, while panning
+    pan_from: Option<FPoint>,
+    /// changed since the last save
+    modified: bool,
+    status: String,
+}
+
+impl Editor {
+    /// edit the map, to be saved to the path. the camera's viewport should
+    /// be set to the window's logical size
+    pub fn new(window_name: &str, style: UiStyle, map: TiledMap, path: &Path) -> Self {
+        let mut camera = Camera2D::new(1., 1.);
+        camera.position = FPoint::new(
+            (map.width * map.tile_width) as f32 / 2.,
+            (map.height * map.tile_height) as f32 / 2.,
+        );
+        Self {
+            map,
+            path: path.to_owned(),
+            camera,
+            tool: EditorTool::Paint(1),
+            layer: 0,
+            snap: true,
+            levels: LevelLoader::new(),
+            autotiler: Autotiler::new(),
+            ui: Ui::new(window_name, style),
+            mouse: None,
+            drag_start: None,
+            pan_from: None,
+            modified: false,
+            status: String::new(),
+        }
+    }
+
+    /// edit an existing .tmx file or native level. native levels saved with
+    /// an older schema version are migrated by the loader
+    pub fn open(
+        window_name: &str,
+        style: UiStyle,
+        path: &Path,
+        levels: LevelLoader,
+    ) -> Result<Self, String> {
+        let map = if is_native(path) {
+            levels.load_file(path)?.to_tiled()
+        } else {
+            TiledMap::load_file(path)?
+        };
+        let mut editor = Self::new(window_name, style, map, path);
+        editor.levels = levels;
+        Ok(editor)
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        if is_native(&self.path) {
+            self.levels
+                .save_file(&self.path, &Level::from_tiled(&self.map))?;
+        } else {
+            self.map.save_file(&self.path)?;
+        }
+        self.modified = false;
+        self.status = format!("saved {}", self.path.display());
+        Ok(())
+    }
+
+    /// the current tile layer, adding one if the map has none
+    pub fn tile_layer_mut(&mut self) -> &mut TileLayer {
+        if self.map.tile_layers.is_empty() {
+            let layer = TileLayer::new(
+                self.map.width,
+                self.map.height,
+                self.map.tile_width as f32,
+                self.map.tile_height as f32,
+            );
+            self.map.tile_layers.push(("ground".to_owned(), layer));
+        }
+        self.layer = self.layer.min(self.map.tile_layers.len() - 1);
+        &mut self.map.tile_layers[self.layer].1
+    }
+
+    fn object_layer_mut(&mut self, name: &str) -> &mut ObjectLayer {
+        let index = match self.map.object_layers.iter().position(|l| l.name == name) {
+            Some(index) => index,
+            None => {
+                self.map.object_layers.push(ObjectLayer {
+                    name: name.to_owned(),
+                    objects: Vec::new(),
+                });
+                self.map.object_layers.len() - 1
+            }
+        };
+        &mut self.map.object_layers[index]
+    }
+
+    fn next_object_id(&self) -> u32 {
+        self.map.objects().map(|o| o.id).max().unwrap_or(0) + 1
+    }
+
+    /// the corner of the tile grid nearest the point, if snapping
+    fn snap_corner(&self, point: FPoint) -> FPoint {
+        if !self.snap {
+            return point;
+        }
+        let (w, h) = (self.map.tile_width as f32, self.map.tile_height as f32);
+        FPoint::new((point.x() / w).round() * w, (point.y() / h).round() * h)
+    }
+
+    /// set the tile under the point in the current layer. returns true if it
+    /// changed
+    pub fn paint(&mut self, point: FPoint, tile: u32) -> bool {
+        let layer = self.tile_layer_mut();
+        let (x, y) = layer.tile_at(point);
+        let changed = layer.set(x, y, tile).is_some_and(|old| old != tile);
+        self.modified |= changed;
+        changed
+    }
+
+    /// paint the terrain under the point in the current layer, updating the
+    /// tiles around it. returns true if any changed
+    pub fn paint_terrain(&mut self, point: FPoint, terrain: usize) -> bool {
+        let autotiler = std::mem::take(&mut self.autotiler);
+        let layer = self.tile_layer_mut();
+        let (x, y) = layer.tile_at(point);
+        let revision = layer.revision();
+        autotiler.paint(layer, x, y, terrain);
+        let changed = layer.revision() != revision;
+        self.autotiler = autotiler;
+        self.modified |= changed;
+        changed
+    }
+
+    /// erase the tile under the point in the current layer, updating the
+    /// terrain around it. returns true if any changed
+    pub fn erase(&mut self, point: FPoint) -> bool {
+        let autotiler = std::mem::take(&mut self.autotiler);
+        let layer = self.tile_layer_mut();
+        let (x, y) = layer.tile_at(point);
+        let revision = layer.revision();
+        autotiler.set(layer, x, y, EMPTY_TILE);
+        let changed = layer.revision() != revision;
+        self.autotiler = autotiler;
+        self.modified |= changed;
+        changed
+    }
+
+    /// place the prefab at the point (the center of its tile, if snapping)
+    pub fn place_entity(&mut self, prefab: &str, point: FPoint) -> u32 {
+        let point = if self.snap {
+            let layer = self.tile_layer_mut();
+            let (x, y) = layer.tile_at(point);
+            let rect = layer.tile_rect(x, y);
+            FPoint::new(rect.x() + rect.width() / 2., rect.y() + rect.height() / 2.)
+        } else {
+            point
+        };
+        let id = self.next_object_id();
+        self.object_layer_mut(ENTITY_LAYER).objects.push(MapObject {
+            id,
+            name: String::new(),
+            class: prefab.to_owned(),
+            shape: ObjectShape::Point(point),
+            tile: None,
+            properties: Default::default(),
+        });
+        self.modified = true;
+        id
+    }
+
+    /// add a collider covering the two corners (snapped to the tile grid, if
+    /// snapping). None if it would be empty
+    pub fn add_collider(&mut self, a: FPoint, b: FPoint) -> Option<u32> {
+        let (a, b) = (self.snap_corner(a), self.snap_corner(b));
+        let (w, h) = ((a.x() - b.x()).abs(), (a.y() - b.y()).abs());
+        if w <= 0. || h <= 0. {
+            return None;
+        }
+        let rect = FRect::new(a.x().min(b.x()), a.y().min(b.y()), w, h);
+        let id = self.next_object_id();
+        self.object_layer_mut(COLLIDER_LAYER)
+            .objects
+            .push(MapObject {
+                id,
+                name: String::new(),
+                class: COLLIDER_CLASS.to_owned(),
+                shape: ObjectShape::Rect(rect),
+                tile: None,
+                properties: Default::default(),
+            });
+        self.modified = true;
+        Some(id)
+    }
+
+    /// the area an object is picked by: its shape's bounds, or a tile around
+    /// a point
+    fn pick_rect(&self, object: &MapObject) -> FRect {
+        let (w, h) = (self.map.tile_width as f32, self.map.tile_height as f32);
+        match &object.shape {
+            ObjectShape::Rect(r) | ObjectShape::Ellipse(r) => *r,
+            ObjectShape::Point(p) => FRect::new(p.x() - w / 2., p.y() - h / 2., w, h),
+            ObjectShape::Polygon(points) | ObjectShape::Polyline(points) => {
+                let xs = points.iter().map(|p| p.x());
+                let ys = points.iter().map(|p| p.y());
+                let (x0, x1) = (
+                    xs.clone().fold(f32::INFINITY, f32::min),
+                    xs.fold(f32::NEG_INFINITY, f32::max),
+                );
+                let (y0, y1) = (
+                    ys.clone().fold(f32::INFINITY, f32::min),
+                    ys.fold(f32::NEG_INFINITY, f32::max),
+                );
+                FRect::new(x0, y0, x1 - x0, y1 - y0)
+            }
+        }
+    }
+
+    /// remove the most recently placed object under the point. returns its
+    /// id
+    pub fn delete_at(&mut self, point: FPoint) -> Option<u32> {
+        let (layer, index, id) = self
+            .map
+            .object_layers
+            .iter()
+            .enumerate()
+            .flat_map(|(l, layer)| {
+                layer
+                    .objects
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, o)| (l, i, o))
+            })
+            .filter(|(_, _, o)| self.pick_rect(o).contains_point(point))
+            .max_by_key(|(_, _, o)| o.id)
+            .map(|(l, i, o)| (l, i, o.id))?;
+        self.map.object_layers[layer].objects.remove(index);
+        self.modified = true;
+        Some(id)
+    }
+
+    /// handle a click (or a held button, for painting) at the point
+    fn apply_tool(&mut self, point: FPoint, input: &InputState) {
+        let pressed = input.mouse_just_pressed(MouseButton::Left);
+        match self.tool.clone() {
+            EditorTool::Paint(tile) => {
+                self.paint(point, tile);
+            }
+            EditorTool::Terrain(terrain) => {
+                self.paint_terrain(point, terrain);
+            }
+            EditorTool::Erase => {
+                self.erase(point);
+            }
+            EditorTool::Entity(prefab) if pressed => {
+                self.place_entity(&prefab, point);
+            }
+            EditorTool::Collider if pressed => self.drag_start = Some(point),
+            EditorTool::Delete if pressed => {
+                self.delete_at(point);
+            }
+            _ => {}
+        }
+    }
+
+    fn toolbar(&mut self, prefabs: &PrefabRegistry) -> Result<(), String> {
+        let padding = self.ui.style.scaled_padding();
+        let row = self.ui.style.scaled_point_size() as i32 + padding * 2;
+        let width = row * 5;
+        let mut names: Vec<&str> = prefabs.names().collect();
+        names.sort_unstable();
+
+        let terrains: Vec<String> = self
+            .autotiler
+            .terrains
+            .iter()
+            .map(|t| t.name.clone())
+            .collect();
+
+        let rows = 9 + terrains.len() as i32 + names.len() as i32;
+        self.ui.panel(Rect::new(
+            0,
+            0,
+            (width + padding * 2) as u32,
+            (rows * row + padding * 2) as u32,
+        ));
+        let mut y = padding;
+        let next = |y: &mut i32| {
+            let rect = Rect::new(padding, *y, width as u32, row as u32);
+            *y += row;
+            rect
+        };
+
+        let tile = match self.tool {
+            EditorTool::Paint(tile) => tile,
+            _ => 1,
+        };
+        if self
+            .ui
+            .button(&format!("paint {}##paint", tile), next(&mut y))
+        {
+            self.tool = EditorTool::Paint(tile);
+        }
+        let rect = next(&mut y);
+        let half = Rect::new(rect.x(), rect.y(), rect.width() / 2, rect.height());
+        if self.ui.button("<##tile", half) {
+            self.tool = EditorTool::Paint(tile.saturating_sub(1).max(1));
+        }
+        let half = Rect::new(
+            rect.x() + half.width() as i32,
+            rect.y(),
+            half.width(),
+            rect.height(),
+        );
+        if self.ui.button(">##tile", half) {
+            self.tool = EditorTool::Paint(tile + 1);
+        }
+        if self.ui.button("erase", next(&mut y)) {
+            self.tool = EditorTool::Erase;
+        }
+        if self.ui.button("collider", next(&mut y)) {
+            self.tool = EditorTool::Collider;
+        }
+        if self.ui.button("delete", next(&mut y)) {
+            self.tool = EditorTool::Delete;
+        }
+        let layer_name = self
+            .map
+            .tile_layers
+            .get(self.layer)
+            .map_or("ground", |(name, _)| name.as_str());
+        if self
+            .ui
+            .button(&format!("layer: {}##layer", layer_name), next(&mut y))
+        {
+            self.layer = (self.layer + 1) % self.map.tile_layers.len().max(1);
+        }
+        if self.ui.button("save", next(&mut y)) {
+            self.save()?;
+        }
+        self.ui.label("terrains:", next(&mut y));
+        for (i, name) in terrains.iter().enumerate() {
+            if self.ui.button(&format!("{}##terrain", name), next(&mut y)) {
+                self.tool = EditorTool::Terrain(i);
+            }
+        }
+        self.ui.label("entities:", next(&mut y));
+        for name in names {
+            if self.ui.button(&format!("{}##prefab", name), next(&mut y)) {
+                self.tool = EditorTool::Entity(name.to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /// declare the toolbar and apply this frame's input to the map. call
+    /// once per frame, before draw
+    pub fn update(
+        &mut self,
+        input: &InputState,
+        events: &[EngineEvent],
+        prefabs: &PrefabRegistry,
+    ) -> Result<(), String> {
+        self.ui.begin_frame(input, events);
+        self.toolbar(prefabs)?;
+        self.ui.end_frame();
+
+        let window = Some(self.ui.window_name());
+        let screen = input
+            .mouse_position()
+            .filter(|_| input.mouse_window() == window);
+        self.mouse = screen.map(|screen| self.camera.screen_to_world(screen));
+
+        for event in events {
+            match event {
+                EngineEvent::MouseWheel { y, .. } if screen.is_some() => {
+                    self.camera.zoom = (self.camera.zoom * 1.1f32.powf(*y)).clamp(0.1, 16.);
+                }
+                EngineEvent::KeyDown {
+                    keycode: Some(Keycode::S),
+                    keymod,
+                    repeat: false,
+                    ..
+                } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => self.save()?,
+                _ => {}
+            }
+        }
+
+        // panning works over the toolbar too
+        match (screen, input.mouse_down(MouseButton::Middle)) {
+            (Some(screen), true) => {
+                if let Some(from) = self.pan_from {
+                    let delta = (from - screen) * (1. / self.camera.zoom);
+                    self.camera.position += delta;
+                }
+                self.pan_from = Some(screen);
+            }
+            _ => self.pan_from = None,
+        }
+
+        if let Some(mouse) = self.mouse {
+            if !self.ui.wants_mouse() && input.mouse_down(MouseButton::Left) {
+                self.apply_tool(mouse, input);
+            }
+            if input.mouse_just_released(MouseButton::Left) {
+                if let Some(start) = self.drag_start.take() {
+                    self.add_collider(start, mouse);
+                }
+            }
+        }
+        if !input.mouse_down(MouseButton::Left) {
+            self.drag_start = None;
+        }
+        Ok(())
+    }
+
+    fn fill_world_rect(
+        &self,
+        system: &mut ChimericSystem,
+        rect: FRect,
+        color: Color,
+    ) -> Result<(), String> {
+        let top_left = self.camera.world_to_screen(FPoint::new(rect.x(), rect.y()));
+        let zoom = self.camera.zoom;
+        let dst = FRect::new(
+            top_left.x(),
+            top_left.y(),
+            rect.width() * zoom,
+            rect.height() * zoom,
+        );
+        system.fill_rect(self.ui.window_name(), dst, color)
+    }
+
+    /// draw the map, its objects, and the toolbar
+    pub fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        let window = self.ui.window_name();
+        for (_, layer) in &self.map.tile_layers {
+            self.map.draw_layer(system, window, layer, &self.camera)?;
+        }
+        for object in self.map.objects() {
+            let color = match object.class.as_str() {
+                COLLIDER_CLASS => COLLIDER_COLOR,
+                _ => ENTITY_COLOR,
+            };
+            let mut rect = self.pick_rect(object);
+            if let ObjectShape::Point(_) = object.shape {
+                // a marker smaller than the tile
+                let inset = rect.width().min(rect.height()) / 4.;
+                rect = FRect::new(
+                    rect.x() + inset,
+                    rect.y() + inset,
+                    rect.width() - inset * 2.,
+                    rect.height() - inset * 2.,
+                );
+            }
+            self.fill_world_rect(system, rect, color)?;
+        }
+        if let Some(mouse) = self.mouse {
+            let cursor = match self.drag_start {
+                Some(start) => {
+                    let (a, b) = (self.snap_corner(start), self.snap_corner(mouse));
+                    FRect::new(
+                        a.x().min(b.x()),
+                        a.y().min(b.y()),
+                        (a.x() - b.x()).abs(),
+                        (a.y() - b.y()).abs(),
+                    )
+                }
+                None => {
+                    let (w, h) = (self.map.tile_width as f32, self.map.tile_height as f32);
+                    FRect::new(
+                        (mouse.x() / w).floor() * w,
+                        (mouse.y() / h).floor() * h,
+                        w,
+                        h,
+                    )
+                }
+            };
+            self.fill_world_rect(system, cursor, CURSOR_COLOR)?;
+        }
+        self.ui.draw(system)
+    }
+
+    /// e.g. the last save, for a status bar
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::tilemap::{AutotileKind, Terrain};
+
+    #[test]
+    fn test_editing() {
+        let path = std::env::temp_dir().join(format!("chimeric-editor-{}.tmx", std::process::id()));
+        let style = UiStyle::new(Path::new("font.ttf"), 12);
+        let map = TiledMap::new(4, 3, 16, 16);
+        let mut editor = Editor::new("main", style.clone(), map, &path);
+
+        assert!(editor.paint(FPoint::new(20., 5.), 3));
+        assert!(!editor.paint(FPoint::new(20., 5.), 3));
+        assert!(!editor.paint(FPoint::new(100., 5.), 3));
+        editor.autotiler =
+            Autotiler::new().with_terrain(Terrain::sequential("grass", AutotileKind::Edges, 10));
+        assert!(editor.paint_terrain(FPoint::new(5., 20.), 0));
+        assert!(editor.paint_terrain(FPoint::new(20., 20.), 0));
+        // the west edge continues off the map. joined east too, then not
+        assert_eq!(editor.tile_layer_mut().get(0, 1), Some(20));
+        assert!(editor.erase(FPoint::new(20., 20.)));
+        assert_eq!(editor.tile_layer_mut().get(0, 1), Some(18));
+        editor.erase(FPoint::new(5., 20.));
+        let goblin = editor.place_entity("goblin", FPoint::new(3., 40.));
+        assert_eq!(
+            editor.add_collider(FPoint::new(1., 1.), FPoint::new(1., 30.)),
+            None
+        );
+        let wall = editor
+            .add_collider(FPoint::new(33., 47.), FPoint::new(1., 30.))
+            .unwrap();
+        assert!(editor.is_modified());
+        editor.save().unwrap();
+        assert!(!editor.is_modified());
+
+        let mut editor = Editor::open("main", style.clone(), &path, LevelLoader::new()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(editor.tile_layer_mut().get(1, 0), Some(3));
+        let goblin = editor.map.objects().find(|o| o.id == goblin).unwrap();
+        assert_eq!(goblin.shape, ObjectShape::Point(FPoint::new(8., 40.)));
+        let wall = editor.map.objects().find(|o| o.id == wall).unwrap();
+        assert_eq!(wall.shape, ObjectShape::Rect(FRect::new(0., 32., 32., 16.)));
+        assert_eq!(wall.class, COLLIDER_CLASS);
+        // the most recent object under the point goes first
+        assert_eq!(editor.delete_at(FPoint::new(8., 40.)), Some(2));
+        assert_eq!(editor.delete_at(FPoint::new(8., 40.)), Some(1));
+        assert_eq!(editor.delete_at(FPoint::new(8., 40.)), None);
+
+        // as a native level
+        editor.place_entity("goblin", FPoint::new(3., 40.));
+        editor.path = path.with_extension(LEVEL_EXTENSION);
+        editor.save().unwrap();
+        let mut editor = Editor::open("main", style, &editor.path, LevelLoader::new()).unwrap();
+        std::fs::remove_file(&editor.path).unwrap();
+        assert_eq!(editor.tile_layer_mut().get(1, 0), Some(3));
+        assert_eq!(editor.map.objects_with_class("goblin").count(), 1);
+    }
+}
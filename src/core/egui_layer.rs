@@ -0,0 +1,398 @@
+use std::{collections::HashMap, time::Instant};
+
+use egui::{
+    epaint::{ClippedPrimitive, ImageData, Primitive, Vertex},
+    Context, Key, Modifiers, PointerButton, Pos2, TextureFilter, TextureId, TexturesDelta,
+};
+use sdl2::{
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{Canvas, Texture},
+    video::Window,
+};
+
+use super::{
+    events::EngineEvent,
+    render_system::{CanvasAndCreator, TextureWrapper},
+    system::ChimericSystem,
+};
+
+fn key(keycode: Keycode) -> Option<Key> {
+    Some(match keycode {
+        Keycode::Left => Key::ArrowLeft,
+        Keycode::Right => Key::ArrowRight,
+        Keycode::Up => Key::ArrowUp,
+        Keycode::Down => Key::ArrowDown,
+        Keycode::Return | Keycode::KpEnter => Key::Enter,
+        Keycode::Escape => Key::Escape,
+        Keycode::Tab => Key::Tab,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Delete => Key::Delete,
+        Keycode::Insert => Key::Insert,
+        Keycode::Home => Key::Home,
+        Keycode::End => Key::End,
+        Keycode::PageUp => Key::PageUp,
+        Keycode::PageDown => Key::PageDown,
+        Keycode::Space => Key::Space,
+        Keycode::A => Key::A,
+        Keycode::C => Key::C,
+        Keycode::V => Key::V,
+        Keycode::X => Key::X,
+        Keycode::Y => Key::Y,
+        Keycode::Z => Key::Z,
+        _ => return None,
+    })
+}
+
+fn modifiers(keymod: Mod) -> Modifiers {
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    let gui = keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD);
+    let mac = cfg!(target_os = "macos");
+    Modifiers {
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        ctrl,
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        mac_cmd: mac && gui,
+        command: if mac { gui } else { ctrl },
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::X1 => Some(PointerButton::Extra1),
+        MouseButton::X2 => Some(PointerButton::Extra2),
+        MouseButton::Unknown => None,
+    }
+}
+
+/// egui drawn on a window through its canvas, in the window's logical
+/// coordinates. feed it the frame's events, then run it while drawing
+pub struct EguiLayer {
+    pub ctx: Context,
+    pub window_name: String,
+    events: Vec<egui::Event>,
+    modifiers: Modifiers,
+    /// a paste is resolved from the clipboard on the next run
+    paste: bool,
+    focused: bool,
+    start: Instant,
+}
+
+impl EguiLayer {
+    pub fn new(window_name: &str) -> Self {
+        Self {
+            ctx: Default::default(),
+            window_name: window_name.to_owned(),
+            events: Default::default(),
+            modifiers: Default::default(),
+            paste: false,
+            focused: true,
+            start: Instant::now(),
+        }
+    }
+
+    /// true if egui is using the pointer (e.g. it's over a window). the game
+    /// should ignore mouse input while this is set
+    pub fn wants_pointer(&self) -> bool {
+        self.ctx.wants_pointer_input()
+    }
+
+    /// true if a text field has focus
+    pub fn wants_keyboard(&self) -> bool {
+        self.ctx.wants_keyboard_input()
+    }
+
+    /// translate the events for this layer's window. call once per frame
+    pub fn handle_events(&mut self, events: &[EngineEvent]) {
+        let ours = |window: &Option<String>| window.as_deref() == Some(self.window_name.as_str());
+        for event in events {
+            match event {
+                EngineEvent::WindowFocus { window, focused } if *window == self.window_name => {
+                    self.focused = *focused;
+                    self.events.push(egui::Event::WindowFocused(*focused));
+                }
+                EngineEvent::KeyDown {
+                    window,
+                    keycode,
+                    keymod,
+                    repeat,
+                    ..
+                } if ours(window) => {
+                    self.modifiers = modifiers(*keymod);
+                    let Some(key) = keycode.and_then(key) else {
+                        continue;
+                    };
+                    if self.modifiers.command {
+                        match key {
+                            Key::C => self.events.push(egui::Event::Copy),
+                            Key::X => self.events.push(egui::Event::Cut),
+                            Key::V => self.paste = true,
+                            _ => {}
+                        }
+                    }
+                    self.events.push(egui::Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed: true,
+                        repeat: *repeat,
+                        modifiers: self.modifiers,
+                    });
+                }
+                EngineEvent::KeyUp {
+                    window,
+                    keycode,
+                    keymod,
+                    ..
+                } if ours(window) => {
+                    self.modifiers = modifiers(*keymod);
+                    if let Some(key) = keycode.and_then(key) {
+                        self.events.push(egui::Event::Key {
+                            key,
+                            physical_key: None,
+                            pressed: false,
+                            repeat: false,
+                            modifiers: self.modifiers,
+                        });
+                    }
+                }
+                EngineEvent::TextInput { window, text } if ours(window) => {
+                    self.events.push(egui::Event::Text(text.clone()));
+                }
+                EngineEvent::MouseMotion {
+                    window, position, ..
+                } if ours(window) => {
+                    self.events.push(egui::Event::PointerMoved(Pos2::new(
+                        position.x(),
+                        position.y(),
+                    )));
+                }
+                EngineEvent::MouseButtonDown {
+                    window,
+                    button,
+                    position,
+                    ..
+                }
+                | EngineEvent::MouseButtonUp {
+                    window,
+                    button,
+                    position,
+                    ..
+                } if ours(window) => {
+                    if let Some(button) = pointer_button(*button) {
+                        self.events.push(egui::Event::PointerButton {
+                            pos: Pos2::new(position.x(), position.y()),
+                            button,
+                            pressed: matches!(event, EngineEvent::MouseButtonDown { .. }),
+                            modifiers: self.modifiers,
+                        });
+                    }
+                }
+                EngineEvent::MouseWheel { window, x, y } if ours(window) => {
+                    self.events.push(egui::Event::MouseWheel {
+                        unit: egui::MouseWheelUnit::Line,
+                        delta: egui::vec2(-x, *y),
+                        modifiers: self.modifiers,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// build the ui, then draw it on top of what's been drawn so far
+    pub fn run(
+        &mut self,
+        system: &mut ChimericSystem,
+        ui: impl FnMut(&Context),
+    ) -> Result<(), String> {
+        if std::mem::take(&mut self.paste) {
+            self.events
+                .push(egui::Event::Paste(system.clipboard_text()?));
+        }
+        let (width, height) = system.drawable_size(&self.window_name)?;
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                Pos2::ZERO,
+                egui::vec2(width as f32, height as f32),
+            )),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: self.focused,
+            ..Default::default()
+        };
+        let output = self.ctx.run(input, ui);
+        if !output.platform_output.copied_text.is_empty() {
+            system.set_clipboard_text(&output.platform_output.copied_text)?;
+        }
+        let primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        system.paint_egui(&self.window_name, &output.textures_delta, &primitives)
+    }
+}
+
+/// blend with premultiplied alpha, which is what egui outputs. it's not one
+/// of sdl2's named blend modes, so it's set through sys
+fn set_premultiplied_blend_mode(texture: &Texture) -> Result<(), String> {
+    use sdl2::sys::{SDL_BlendFactor::*, SDL_BlendOperation::*};
+    let result = unsafe {
+        let mode = sdl2::sys::SDL_ComposeCustomBlendMode(
+            SDL_BLENDFACTOR_ONE,
+            SDL_BLENDFACTOR_ONE_MINUS_SRC_ALPHA,
+            SDL_BLENDOPERATION_ADD,
+            SDL_BLENDFACTOR_ONE,
+            SDL_BLENDFACTOR_ONE_MINUS_SRC_ALPHA,
+            SDL_BLENDOPERATION_ADD,
+        );
+        sdl2::sys::SDL_SetTextureBlendMode(texture.raw(), mode)
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(sdl2::get_error()),
+    }
+}
+
+fn update_texture(
+    cc: &mut CanvasAndCreator,
+    textures: &mut HashMap<TextureId, TextureWrapper>,
+    id: TextureId,
+    delta: &egui::epaint::ImageDelta,
+) -> Result<(), String> {
+    let [width, height] = delta.image.size();
+    let pixels: Vec<u8> = match &delta.image {
+        ImageData::Color(image) => image.pixels.iter().flat_map(|c| c.to_array()).collect(),
+        ImageData::Font(image) => image
+            .srgba_pixels(None)
+            .flat_map(|c| c.to_array())
+            .collect(),
+    };
+    let format = PixelFormatEnum::RGBA32;
+    if delta.pos.is_none() || !textures.contains_key(&id) {
+        let texture = cc
+            .creator
+            .create_texture_static(format, width as u32, height as u32)
+            .map_err(|e| e.to_string())?;
+        set_premultiplied_blend_mode(&texture)?;
+        let scale_mode = match delta.options.magnification {
+            TextureFilter::Nearest => sdl2::sys::SDL_ScaleMode::SDL_ScaleModeNearest,
+            TextureFilter::Linear => sdl2::sys::SDL_ScaleMode::SDL_ScaleModeLinear,
+        };
+        unsafe {
+            sdl2::sys::SDL_SetTextureScaleMode(texture.raw(), scale_mode);
+        }
+        textures.insert(id, TextureWrapper(texture));
+    }
+    let [x, y] = delta.pos.unwrap_or([0, 0]);
+    let texture = &mut textures.get_mut(&id).expect("just inserted").0;
+    texture
+        .update(
+            Rect::new(x as i32, y as i32, width as u32, height as u32),
+            &pixels,
+            width * 4,
+        )
+        .map_err(|e| e.to_string())
+}
+
+fn draw_mesh(
+    canvas: &mut Canvas<Window>,
+    texture: &TextureWrapper,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> Result<(), String> {
+    let stride = std::mem::size_of::<Vertex>() as i32;
+    let base = vertices.as_ptr() as *const u8;
+    let result = unsafe {
+        sdl2::sys::SDL_RenderGeometryRaw(
+            canvas.raw(),
+            texture.0.raw(),
+            base.add(std::mem::offset_of!(Vertex, pos)) as *const f32,
+            stride,
+            base.add(std::mem::offset_of!(Vertex, color)) as *const sdl2::sys::SDL_Color,
+            stride,
+            base.add(std::mem::offset_of!(Vertex, uv)) as *const f32,
+            stride,
+            vertices.len() as i32,
+            indices.as_ptr() as *const std::ffi::c_void,
+            indices.len() as i32,
+            std::mem::size_of::<u32>() as i32,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(sdl2::get_error()),
+    }
+}
+
+/// apply the texture changes and draw the triangles. textures are owned by the
+/// window's render system, so they're freed with it
+pub(crate) fn paint(
+    cc: &mut CanvasAndCreator,
+    textures: &mut HashMap<TextureId, TextureWrapper>,
+    delta: &TexturesDelta,
+    primitives: &[ClippedPrimitive],
+) -> Result<(), String> {
+    for (id, image_delta) in delta.set.iter() {
+        update_texture(cc, textures, *id, image_delta)?;
+    }
+    let clip = cc.canvas.clip_rect();
+    for primitive in primitives {
+        let Primitive::Mesh(mesh) = &primitive.primitive else {
+            // paint callbacks need a graphics api; not supported
+            continue;
+        };
+        let Some(texture) = textures.get(&mesh.texture_id) else {
+            continue;
+        };
+        let rect = primitive.clip_rect;
+        cc.canvas.set_clip_rect(Rect::new(
+            rect.min.x.floor() as i32,
+            rect.min.y.floor() as i32,
+            rect.width().ceil().max(0.) as u32,
+            rect.height().ceil().max(0.) as u32,
+        ));
+        draw_mesh(&mut cc.canvas, texture, &mesh.vertices, &mesh.indices)?;
+    }
+    cc.canvas.set_clip_rect(clip);
+    for id in delta.free.iter() {
+        textures.remove(id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events() {
+        let mut layer = EguiLayer::new("main");
+        layer.handle_events(&[
+            EngineEvent::KeyDown {
+                window: Some("main".into()),
+                keycode: Some(Keycode::V),
+                scancode: None,
+                keymod: Mod::LCTRLMOD,
+                repeat: false,
+            },
+            EngineEvent::MouseWheel {
+                window: Some("other".into()),
+                x: 0.,
+                y: 1.,
+            },
+        ]);
+        assert!(layer.paste || cfg!(target_os = "macos"));
+        assert_eq!(layer.events.len(), 1);
+        assert!(matches!(
+            layer.events[0],
+            egui::Event::Key {
+                key: Key::V,
+                pressed: true,
+                ..
+            }
+        ));
+    }
+}
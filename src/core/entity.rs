@@ -1,40 +1,105 @@
-pub struct EntityChanges {
-    /// indicates if this entity is alive! if it's dead, it's removed from the
-    /// world
-    alive: bool,
-    /// a tuple of entities to spawn in
-    /// 
-    /// first element of tuple is the 
-    /// 
-    /// what new entities are added to the world
-    spawn: Vec<Box<dyn Entity>>,
+use sdl2::rect::FRect;
+
+use super::{
+    collision::Collider, physics::KinematicBody, replication::Replicate, system::ChimericSystem,
+    transform::TransformNode, world::World,
+};
+
+/// uniquely identifies an entity for the lifetime of the world that spawned it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(pub u64);
+
+/// which pause flag applies to an entity's update phases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum UpdateGroup {
+    #[default]
+    Gameplay,
+    /// menus, hud, etc. keeps updating while gameplay is paused
+    Ui,
 }
 
 pub trait Entity {
-    /// returns true if it's still alive! if it's dead, it's removed from the
-    /// world
-    fn update(&mut self, world_data: &mut serde_json::Value) -> Result<bool, String>;
-
-    fn draw(&self)
-    
-    /// occurs each frame after each entity has been sequentially updated
-    /// 
+    /// occurs each frame. entities are updated sequentially, in spawn order
+    ///
+    /// the world can be used to spawn more entities. those are added to the
+    /// end of the world and won't be updated until the next frame
+    fn update(&mut self, world: &mut World) -> Result<(), String>;
+
+    /// the group decides if the update and parallel update phases are skipped
+    /// while the world is paused
+    fn group(&self) -> UpdateGroup {
+        UpdateGroup::Gameplay
+    }
+
+    /// entities which have a body are moved by the world's physics phase,
+    /// which occurs after each entity has been sequentially updated
+    fn body_mut(&mut self) -> Option<&mut KinematicBody> {
+        None
+    }
+
+    /// occurs each frame after the physics phase
+    ///
     /// parallel_update might be executed in parallel between all entities
-    fn parallel_update(&mut self) -> Result<(), String>;
-    
+    fn parallel_update(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
     /// occurs each frame after each entity has been updated in parallel
-    /// 
+    ///
     /// it is checked if this entity is alive or not. if it is dead, then it is
     /// removed from the world now and will not be processed further.
-    fn alive(&self) -> bool;
-    
+    fn alive(&self) -> bool {
+        true
+    }
+
+    /// entities with a transform node can be parented to other entities. world
+    /// transforms are computed after the alive check, before the draw phase
+    fn transform_mut(&mut self) -> Option<&mut TransformNode> {
+        None
+    }
+
+    /// axis aligned bounding box in world coordinates. entities which return
+    /// one are included in the world's spatial queries
+    fn aabb(&self) -> Option<FRect> {
+        None
+    }
+
+    /// shape in world coordinates. overlapping colliders produce contact
+    /// events, which can be read from the world's event bus next frame
+    fn collider(&self) -> Option<Collider> {
+        None
+    }
+
+    /// entities which expose replicated state are included in the world's
+    /// snapshots
+    fn replicate_mut(&mut self) -> Option<&mut dyn Replicate> {
+        None
+    }
+
+    /// shown by the inspector and debug dumps
+    fn debug_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// free form labels shown by the inspector and debug dumps
+    fn tags(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// the bottom edge of what's drawn, in world coordinates. orders draws
+    /// when the world's draw order is DrawOrder::YSort. the aabb's by default
+    fn draw_bottom(&self) -> Option<f32> {
+        self.aabb().map(|aabb| aabb.bottom())
+    }
+
+    /// added to the draw bottom when y sorting. e.g. a negative bias keeps a
+    /// rug under whoever's standing on it
+    fn sort_bias(&self) -> f32 {
+        0.
+    }
+
     /// occurs each frame after each entity has had its alive check
-    /// 
-    /// draw self by inputting draw command into the pipeline. the pipeline will
-    /// be flushed
-    fn draw_layer(&self) -> Result<(), String>;
+    fn draw(&self, _system: &mut ChimericSystem) -> Result<(), String> {
+        Ok(())
+    }
 }
-
-pub struct World {
-    
-}
\ No newline at end of file
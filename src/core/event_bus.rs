@@ -0,0 +1,105 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+trait Queue: Any {
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<E: 'static> Queue for Vec<E> {
+    fn clear(&mut self) {
+        Vec::clear(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// typed event queues, double buffered per frame.
+///
+/// events published during one frame can be read during the next frame, after
+/// which they're discarded. this way each event is seen by every entity,
+/// regardless of the order in which entities are updated
+#[derive(Default)]
+pub struct EventBus {
+    /// readable this frame
+    current: HashMap<TypeId, Box<dyn Queue>>,
+    /// published this frame
+    pending: HashMap<TypeId, Box<dyn Queue>>,
+}
+
+impl EventBus {
+    pub fn publish<E: 'static>(&mut self, event: E) {
+        self.pending
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Vec::<E>::new()))
+            .as_any_mut()
+            .downcast_mut::<Vec<E>>()
+            .expect("event queue keyed by its own type")
+            .push(event);
+    }
+
+    /// events of this type which were published last frame
+    pub fn read<E: 'static>(&self) -> &[E] {
+        match self.current.get(&TypeId::of::<E>()) {
+            Some(queue) => queue
+                .as_any()
+                .downcast_ref::<Vec<E>>()
+                .expect("event queue keyed by its own type"),
+            None => &[],
+        }
+    }
+
+    /// events of this type published this frame, which are readable next frame
+    pub fn pending<E: 'static>(&self) -> &[E] {
+        match self.pending.get(&TypeId::of::<E>()) {
+            Some(queue) => queue
+                .as_any()
+                .downcast_ref::<Vec<E>>()
+                .expect("event queue keyed by its own type"),
+            None => &[],
+        }
+    }
+
+    /// discard the events published this frame, e.g. when rewinding it
+    pub fn clear_pending(&mut self) {
+        self.pending.values_mut().for_each(|queue| queue.clear());
+    }
+
+    /// make the events published this frame readable, and discard the events
+    /// that were readable. called by the world at the beginning of each frame
+    pub fn advance(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.pending);
+        // queues are kept to reuse their allocations
+        self.clear_pending();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_buffer() {
+        let mut bus = EventBus::default();
+        bus.publish(1u32);
+        bus.publish("a");
+        assert!(bus.read::<u32>().is_empty());
+        bus.advance();
+        bus.publish(2u32);
+        assert_eq!(bus.read::<u32>(), &[1]);
+        assert_eq!(bus.read::<&str>(), &["a"]);
+        bus.advance();
+        assert_eq!(bus.read::<u32>(), &[2]);
+        assert!(bus.read::<&str>().is_empty());
+        assert!(bus.read::<i8>().is_empty());
+    }
+}
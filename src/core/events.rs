@@ -0,0 +1,412 @@
+use std::path::PathBuf;
+
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod, Scancode},
+    mouse::MouseButton,
+    rect::FPoint,
+    EventPump,
+};
+
+use super::system::{ChimericSystem, System};
+
+/// sdl events translated for the engine. windows are referred to by the name
+/// they were added with (None if the event isn't tied to a known window).
+/// mouse positions are in the window's logical resolution; sdl converts them
+/// when the canvas has a logical size
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+    /// the app was asked to quit, e.g. the last window was closed
+    Quit,
+    /// the window was closed and has already been removed from the system
+    WindowClosed {
+        window: String,
+    },
+    /// every window lost the focus, and the app paused under its
+    /// FocusLossPolicy. e.g. to show a pause menu
+    FocusPaused,
+    /// a window regained the focus after FocusPaused
+    FocusResumed,
+    WindowResized {
+        window: String,
+        width: u32,
+        height: u32,
+    },
+    WindowFocus {
+        window: String,
+        focused: bool,
+    },
+    KeyDown {
+        window: Option<String>,
+        keycode: Option<Keycode>,
+        scancode: Option<Scancode>,
+        keymod: Mod,
+        repeat: bool,
+    },
+    KeyUp {
+        window: Option<String>,
+        keycode: Option<Keycode>,
+        scancode: Option<Scancode>,
+        keymod: Mod,
+    },
+    MouseMotion {
+        window: Option<String>,
+        position: FPoint,
+        relative: FPoint,
+    },
+    MouseButtonDown {
+        window: Option<String>,
+        button: MouseButton,
+        clicks: u8,
+        position: FPoint,
+    },
+    MouseButtonUp {
+        window: Option<String>,
+        button: MouseButton,
+        clicks: u8,
+        position: FPoint,
+    },
+    MouseWheel {
+        window: Option<String>,
+        x: f32,
+        y: f32,
+    },
+    /// committed text, already composed by the input method
+    TextInput {
+        window: Option<String>,
+        text: String,
+    },
+    /// text being composed by the input method, not yet committed
+    TextEditing {
+        window: Option<String>,
+        text: String,
+        start: i32,
+        length: i32,
+    },
+    /// a file was dragged onto the window
+    FileDropped {
+        window: Option<String>,
+        path: PathBuf,
+    },
+    /// text was dragged onto the window
+    TextDropped {
+        window: Option<String>,
+        text: String,
+    },
+    /// a finger touched the screen. touches go to the focused window, and the
+    /// position is in its logical coordinates. timestamps are in ms
+    TouchDown {
+        window: String,
+        touch_id: i64,
+        finger_id: i64,
+        position: FPoint,
+        pressure: f32,
+        timestamp: u32,
+    },
+    TouchUp {
+        window: String,
+        touch_id: i64,
+        finger_id: i64,
+        position: FPoint,
+        pressure: f32,
+        timestamp: u32,
+    },
+    TouchMotion {
+        window: String,
+        touch_id: i64,
+        finger_id: i64,
+        position: FPoint,
+        /// in logical coordinates
+        relative: FPoint,
+        pressure: f32,
+        timestamp: u32,
+    },
+    /// anything without an engine equivalent (controllers, etc)
+    Other(Event),
+}
+
+fn point(x: i32, y: i32) -> FPoint {
+    FPoint::new(x as f32, y as f32)
+}
+
+/// owns the event pump. translates each event and handles window lifecycle
+pub struct Events {
+    pump: EventPump,
+    /// sdl doesn't say which window a touch is in; the focused one is assumed
+    focused: Option<String>,
+}
+
+impl Events {
+    pub fn new(system: &System) -> Result<Self, String> {
+        Ok(Self {
+            pump: system.sdl.event_pump()?,
+            focused: None,
+        })
+    }
+
+    /// the window with the keyboard focus. None while the app is in the
+    /// background
+    pub fn focused(&self) -> Option<&str> {
+        self.focused.as_deref()
+    }
+
+    /// for direct access (keyboard state, etc)
+    pub fn pump(&mut self) -> &mut EventPump {
+        &mut self.pump
+    }
+
+    /// translate every pending event. windows which are closed are removed
+    /// from the system
+    pub fn poll(&mut self, system: &mut ChimericSystem) -> Vec<EngineEvent> {
+        let raw: Vec<Event> = self.pump.poll_iter().collect();
+        raw.into_iter()
+            .map(|event| {
+                let event = self.translate(event, system);
+                match &event {
+                    EngineEvent::WindowFocus {
+                        window,
+                        focused: true,
+                    } => self.focused = Some(window.clone()),
+                    EngineEvent::WindowFocus { window, .. }
+                    | EngineEvent::WindowClosed { window }
+                        if self.focused.as_ref() == Some(window) =>
+                    {
+                        self.focused = None;
+                    }
+                    _ => {}
+                }
+                event
+            })
+            .collect()
+    }
+
+    fn translate(&self, event: Event, system: &mut ChimericSystem) -> EngineEvent {
+        let name = |window_id: u32| system.window_name(window_id).map(|s| s.to_owned());
+        match event {
+            Event::Quit { .. } => EngineEvent::Quit,
+            Event::Window {
+                window_id,
+                win_event,
+                ..
+            } => {
+                let Some(window) = name(window_id) else {
+                    return EngineEvent::Other(event);
+                };
+                match win_event {
+                    WindowEvent::Close => {
+                        // can't fail; the name was just looked up
+                        let _ = system.remove_window(&window);
+                        EngineEvent::WindowClosed { window }
+                    }
+                    WindowEvent::SizeChanged(width, height) => {
+                        // can't fail; the name was just looked up
+                        let _ = system.mark_dirty(&window, None);
+                        EngineEvent::WindowResized {
+                            window,
+                            width: width.max(0) as u32,
+                            height: height.max(0) as u32,
+                        }
+                    }
+                    WindowEvent::Exposed => {
+                        let _ = system.mark_dirty(&window, None);
+                        EngineEvent::Other(event)
+                    }
+                    WindowEvent::FocusGained => EngineEvent::WindowFocus {
+                        window,
+                        focused: true,
+                    },
+                    WindowEvent::FocusLost => EngineEvent::WindowFocus {
+                        window,
+                        focused: false,
+                    },
+                    _ => EngineEvent::Other(event),
+                }
+            }
+            Event::KeyDown {
+                window_id,
+                keycode,
+                scancode,
+                keymod,
+                repeat,
+                ..
+            } => EngineEvent::KeyDown {
+                window: name(window_id),
+                keycode,
+                scancode,
+                keymod,
+                repeat,
+            },
+            Event::KeyUp {
+                window_id,
+                keycode,
+                scancode,
+                keymod,
+                ..
+            } => EngineEvent::KeyUp {
+                window: name(window_id),
+                keycode,
+                scancode,
+                keymod,
+            },
+            Event::MouseMotion {
+                window_id,
+                x,
+                y,
+                xrel,
+                yrel,
+                ..
+            } => EngineEvent::MouseMotion {
+                window: name(window_id),
+                position: point(x, y),
+                relative: point(xrel, yrel),
+            },
+            Event::MouseButtonDown {
+                window_id,
+                mouse_btn,
+                clicks,
+                x,
+                y,
+                ..
+            } => EngineEvent::MouseButtonDown {
+                window: name(window_id),
+                button: mouse_btn,
+                clicks,
+                position: point(x, y),
+            },
+            Event::MouseButtonUp {
+                window_id,
+                mouse_btn,
+                clicks,
+                x,
+                y,
+                ..
+            } => EngineEvent::MouseButtonUp {
+                window: name(window_id),
+                button: mouse_btn,
+                clicks,
+                position: point(x, y),
+            },
+            Event::MouseWheel {
+                window_id,
+                precise_x,
+                precise_y,
+                ..
+            } => EngineEvent::MouseWheel {
+                window: name(window_id),
+                x: precise_x,
+                y: precise_y,
+            },
+            Event::TextInput {
+                window_id, text, ..
+            } => EngineEvent::TextInput {
+                window: name(window_id),
+                text,
+            },
+            Event::TextEditing {
+                window_id,
+                text,
+                start,
+                length,
+                ..
+            } => EngineEvent::TextEditing {
+                window: name(window_id),
+                text,
+                start,
+                length,
+            },
+            Event::DropFile {
+                window_id,
+                filename,
+                ..
+            } => EngineEvent::FileDropped {
+                window: name(window_id),
+                path: PathBuf::from(filename),
+            },
+            Event::DropText {
+                window_id,
+                filename,
+                ..
+            } => EngineEvent::TextDropped {
+                window: name(window_id),
+                text: filename,
+            },
+            Event::FingerDown {
+                timestamp,
+                touch_id,
+                finger_id,
+                x,
+                y,
+                pressure,
+                ..
+            } => {
+                let Some((window, position)) = self.touch_position(system, x, y) else {
+                    return EngineEvent::Other(event);
+                };
+                EngineEvent::TouchDown {
+                    window,
+                    touch_id,
+                    finger_id,
+                    position,
+                    pressure,
+                    timestamp,
+                }
+            }
+            Event::FingerUp {
+                timestamp,
+                touch_id,
+                finger_id,
+                x,
+                y,
+                pressure,
+                ..
+            } => {
+                let Some((window, position)) = self.touch_position(system, x, y) else {
+                    return EngineEvent::Other(event);
+                };
+                EngineEvent::TouchUp {
+                    window,
+                    touch_id,
+                    finger_id,
+                    position,
+                    pressure,
+                    timestamp,
+                }
+            }
+            Event::FingerMotion {
+                timestamp,
+                touch_id,
+                finger_id,
+                x,
+                y,
+                dx,
+                dy,
+                pressure,
+            } => {
+                let Some((window, position)) = self.touch_position(system, x, y) else {
+                    return EngineEvent::Other(event);
+                };
+                let Some((_, previous)) = self.touch_position(system, x - dx, y - dy) else {
+                    return EngineEvent::Other(event);
+                };
+                EngineEvent::TouchMotion {
+                    window,
+                    touch_id,
+                    finger_id,
+                    position,
+                    relative: position - previous,
+                    pressure,
+                    timestamp,
+                }
+            }
+            event => EngineEvent::Other(event),
+        }
+    }
+
+    /// the focused window and the normalized position in its logical
+    /// coordinates
+    fn touch_position(&self, system: &ChimericSystem, x: f32, y: f32) -> Option<(String, FPoint)> {
+        let window = self.focused.as_ref()?;
+        let position = system.normalized_to_logical(window, FPoint::new(x, y))?;
+        Some((window.clone(), position))
+    }
+}
@@ -1,10 +1,10 @@
 use std::{
+    collections::HashMap,
     ffi::CStr,
-    fs::File,
-    io::Read,
     num::NonZeroUsize,
     path::{Path, PathBuf},
     rc::Rc,
+    time::Instant,
 };
 
 use lru::LruCache;
@@ -14,11 +14,19 @@ use sdl2::{
 };
 
 use super::font::Font;
+use crate::core::{
+    decode::{rgba_pixels, DecodedImage},
+    text_path::{blit_rotated, TextPath},
+    trace::{log_debug, log_trace},
+    vfs::Vfs,
+};
 
 pub struct FontSystem<'sdl> {
     // stored for creating a new value in font_objects
     num_font_objects_per_font: NonZeroUsize,
     num_font_objects: LruCache<PathBuf, LruCache<u16, Font<'sdl>>>,
+    /// font file contents read ahead of time, used instead of the vfs
+    preloaded: HashMap<PathBuf, Box<[u8]>>,
     pub ttf: &'sdl Sdl2TtfContext,
 }
 
@@ -31,19 +39,25 @@ impl<'sdl> FontSystem<'sdl> {
         Self {
             num_font_objects_per_font,
             num_font_objects: LruCache::new(min_loaded_fonts),
+            preloaded: Default::default(),
             ttf,
         }
     }
 
-    /// render the text, loading the font file and or creating the font object
-    /// if needed and not cached
-    pub fn render(
-        &mut self,
-        font_file: &Path,
-        point_size: u16,
-        text: &CStr,
-        wrap_width: Option<u32>,
-    ) -> Result<Surface, String> {
+    /// use these contents the next time the font file needs to be loaded
+    pub fn insert_font_data(&mut self, font_file: &Path, data: Box<[u8]>) {
+        self.preloaded.insert(font_file.to_owned(), data);
+    }
+
+    /// close the font's objects, so it's read again on next use. false if it
+    /// wasn't open
+    pub fn evict(&mut self, font_file: &Path) -> bool {
+        self.num_font_objects.pop(font_file).is_some()
+    }
+
+    /// the font object, loading the font file (through the vfs) and or
+    /// creating the font object if needed and not cached
+    fn font(&mut self, assets: &Vfs, font_file: &Path, point_size: u16) -> Result<&Font<'sdl>, String> {
         let font_objects_for_font = self
             .num_font_objects
             .get_or_insert_mut_ref(font_file, || LruCache::new(self.num_font_objects_per_font));
@@ -59,18 +73,100 @@ impl<'sdl> FontSystem<'sdl> {
                 // have any font objects in it yet)
                 //
                 // need to load the data in
-                let mut font_file_contents: Vec<u8> = Vec::new();
-                let mut file = File::open(font_file).map_err(|err| err.to_string())?;
-                file.read_to_end(&mut font_file_contents)
-                    .map_err(|err| err.to_string())?;
+                let font_file_contents = match self.preloaded.remove(font_file) {
+                    Some(data) => data.into_vec(),
+                    None => assets.read(font_file)?,
+                };
+                log_debug!(
+                    "font cache miss; loaded {} ({} bytes)",
+                    font_file.display(),
+                    font_file_contents.len()
+                );
                 Rc::new(font_file_contents.into_boxed_slice())
             }
         };
 
-        let font_object = font_objects_for_font.try_get_or_insert(point_size, || {
-            Font::new(&self.ttf, point_size, font_data_rc)
-        })?;
+        font_objects_for_font.try_get_or_insert(point_size, || {
+            log_debug!("opening {} at {}pt", font_file.display(), point_size);
+            Font::new(self.ttf, point_size, font_data_rc)
+        })
+    }
 
-        font_object.render(text, wrap_width)
+    /// render the text. see font
+    pub fn render(
+        &mut self,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        wrap_width: Option<u32>,
+    ) -> Result<Surface, String> {
+        let font_object = self.font(assets, font_file, point_size)?;
+        let start = Instant::now();
+        let surface = font_object.render(text, wrap_width);
+        log_trace!("rasterized {:?} at {}pt in {:?}", text, point_size, start.elapsed());
+        surface
+    }
+
+    /// the width and height of single line text, without rendering it
+    pub fn size_of(
+        &mut self,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+    ) -> Result<(u32, u32), String> {
+        self.font(assets, font_file, point_size)?.size_of(text)
+    }
+
+    /// render the text along the path, a glyph at a time turned to follow it.
+    /// the image covers the path's area (see TextPath::area). kerning isn't
+    /// applied
+    pub fn render_on_path(
+        &mut self,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        path: &TextPath,
+    ) -> Result<DecodedImage, String> {
+        let font_object = self.font(assets, font_file, point_size)?;
+        let start = Instant::now();
+        let text_str = text.to_str().map_err(|e| e.to_string())?;
+        let chars: Vec<char> = text_str.chars().collect();
+        let advances: Vec<f32> = chars
+            .iter()
+            .map(|ch| font_object.find_glyph_metrics(*ch).map_or(0., |m| m.advance as f32))
+            .collect();
+        let area = path.area(point_size);
+        let mut image = DecodedImage {
+            width: area.width(),
+            height: area.height(),
+            rgba: vec![0; area.width() as usize * area.height() as usize * 4],
+        };
+        let ascent = font_object.ascent() as f32;
+        for (ch, placement) in chars.iter().zip(path.place(&advances)) {
+            let Some((point, angle)) = placement else {
+                break;
+            };
+            if ch.is_whitespace() {
+                continue;
+            }
+            // nul terminated
+            let mut buf = [0u8; 5];
+            let len = ch.encode_utf8(&mut buf).len();
+            let glyph_text = CStr::from_bytes_with_nul(&buf[..len + 1]).map_err(|e| e.to_string())?;
+            let glyph = rgba_pixels(font_object.render(glyph_text, None)?)?;
+            // the glyph's baseline sits on the path
+            blit_rotated(
+                &mut image,
+                &glyph,
+                (glyph.width as f32 / 2., ascent),
+                (point.x() - area.x() as f32, point.y() - area.y() as f32),
+                angle,
+            );
+        }
+        log_trace!("rasterized {:?} along a path at {}pt in {:?}", text, point_size, start.elapsed());
+        Ok(image)
     }
 }
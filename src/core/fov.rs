@@ -0,0 +1,518 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect},
+};
+
+use super::{
+    decode::DecodedImage,
+    entity::EntityId,
+    renderer::DrawParams,
+    system::ChimericSystem,
+    tilemap::{LayerVersion, TileLayer, EMPTY_TILE},
+    world::World,
+};
+
+/// (xx, xy, yx, yy) mapping each octant onto the first
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// recursive shadowcasting over one octant, between the start and end slopes
+#[allow(clippy::too_many_arguments)]
+fn cast(
+    origin: (i32, i32),
+    radius: i32,
+    row: i32,
+    mut start: f32,
+    end: f32,
+    (xx, xy, yx, yy): (i32, i32, i32, i32),
+    opaque: &impl Fn(i32, i32) -> bool,
+    see: &mut impl FnMut(i32, i32),
+) {
+    if start < end {
+        return;
+    }
+    let mut next_start = start;
+    for j in row..=radius {
+        let dy = -j;
+        let mut blocked = false;
+        for dx in -j..=0 {
+            let left = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+            let right = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+            if start < right {
+                continue;
+            }
+            if end > left {
+                break;
+            }
+            let x = origin.0 + dx * xx + dy * xy;
+            let y = origin.1 + dx * yx + dy * yy;
+            if dx * dx + dy * dy <= radius * radius {
+                see(x, y);
+            }
+            if blocked {
+                if opaque(x, y) {
+                    next_start = right;
+                } else {
+                    blocked = false;
+                    start = next_start;
+                }
+            } else if opaque(x, y) && j < radius {
+                blocked = true;
+                cast(
+                    origin,
+                    radius,
+                    j + 1,
+                    start,
+                    left,
+                    (xx, xy, yx, yy),
+                    opaque,
+                    see,
+                );
+                next_start = right;
+            }
+        }
+        if blocked {
+            break;
+        }
+    }
+}
+
+/// calls see for every cell visible from the origin within the radius
+/// (including the opaque cells which block the view, and the origin)
+pub fn shadowcast(
+    origin: (i32, i32),
+    radius: u32,
+    opaque: impl Fn(i32, i32) -> bool,
+    mut see: impl FnMut(i32, i32),
+) {
+    see(origin.0, origin.1);
+    for octant in OCTANTS {
+        cast(origin, radius as i32, 1, 1., 0., octant, &opaque, &mut see);
+    }
+}
+
+/// what visibility was last computed for, so it isn't computed again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Computed {
+    Layer {
+        origin: (i32, i32),
+        radius: u32,
+        layer: LayerVersion,
+    },
+    Tick {
+        origin: (i32, i32),
+        radius: u32,
+        tick: u64,
+    },
+}
+
+/// the tiles visible from a point, and every tile which has been, for
+/// roguelike visibility and fog of war. occupied tiles block sight, as do
+/// tiles outside the layer
+#[derive(Debug, Clone)]
+pub struct Fov {
+    width: u32,
+    height: u32,
+    visible: Vec<bool>,
+    explored: Vec<bool>,
+    computed: Option<Computed>,
+    /// bumped each time visibility is computed
+    revision: u64,
+}
+
+impl Fov {
+    /// nothing is visible or explored
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            visible: vec![false; len],
+            explored: vec![false; len],
+            computed: None,
+            revision: 0,
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width as usize + x as usize)
+    }
+
+    /// compute visibility from the origin, with custom opacity (e.g. closed
+    /// doors), regardless of the cache
+    pub fn compute(&mut self, origin: (i32, i32), radius: u32, opaque: impl Fn(i32, i32) -> bool) {
+        self.visible.fill(false);
+        let (width, height) = (self.width as i32, self.height as i32);
+        let Self {
+            visible, explored, ..
+        } = self;
+        shadowcast(origin, radius, opaque, |x, y| {
+            if x >= 0 && y >= 0 && x < width && y < height {
+                let i = (y * width + x) as usize;
+                visible[i] = true;
+                explored[i] = true;
+            }
+        });
+        self.revision += 1;
+    }
+
+    /// compute visibility from the origin through the layer's empty tiles,
+    /// unless it's already been computed from there since the layer last
+    /// changed. returns true if it was computed
+    pub fn update(&mut self, layer: &TileLayer, origin: (i32, i32), radius: u32) -> bool {
+        let key = Computed::Layer {
+            origin,
+            radius,
+            layer: layer.version(),
+        };
+        if self.computed == Some(key) {
+            return false;
+        }
+        self.compute(origin, radius, |x, y| layer.get(x, y) != Some(EMPTY_TILE));
+        self.computed = Some(key);
+        true
+    }
+
+    /// as update, with custom opacity which may change each tick (e.g.
+    /// entities blocking sight), so it's cached for the tick
+    pub fn update_for_tick(
+        &mut self,
+        tick: u64,
+        origin: (i32, i32),
+        radius: u32,
+        opaque: impl Fn(i32, i32) -> bool,
+    ) -> bool {
+        let key = Computed::Tick {
+            origin,
+            radius,
+            tick,
+        };
+        if self.computed == Some(key) {
+            return false;
+        }
+        self.compute(origin, radius, opaque);
+        self.computed = Some(key);
+        true
+    }
+
+    pub fn is_visible(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.visible[i])
+    }
+
+    /// seen at some point
+    pub fn is_explored(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.explored[i])
+    }
+
+    pub fn visible_tiles(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let width = self.width as usize;
+        self.visible
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v)
+            .map(move |(i, _)| ((i % width) as i32, (i / width) as i32))
+    }
+
+    /// forget what's been seen, e.g. on entering a new level. nothing is
+    /// visible until the next update
+    pub fn forget(&mut self) {
+        self.visible.fill(false);
+        self.explored.fill(false);
+        self.computed = None;
+        self.revision += 1;
+    }
+
+    /// changes whenever visibility does
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+/// what stopped a ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SightBlocker {
+    Tile(i32, i32),
+    Entity(EntityId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub point: FPoint,
+    /// how far along the segment, from 0 to 1
+    pub t: f32,
+    pub blocker: SightBlocker,
+}
+
+/// the first occupied tile the segment passes through, walking the tiles
+/// along it (amanatides & woo)
+pub fn raycast_tiles(layer: &TileLayer, from: FPoint, to: FPoint) -> Option<RayHit> {
+    let dir = FPoint::new(to.x() - from.x(), to.y() - from.y());
+    let (mut x, mut y) = layer.tile_at(from);
+    let axis = |o: f32, d: f32, c: i32, size: f32| -> (i32, f32, f32) {
+        if d > 0. {
+            (1, ((c + 1) as f32 * size - o) / d, size / d)
+        } else if d < 0. {
+            (-1, (c as f32 * size - o) / d, -size / d)
+        } else {
+            (0, f32::INFINITY, f32::INFINITY)
+        }
+    };
+    let (step_x, mut t_max_x, t_delta_x) = axis(from.x(), dir.x(), x, layer.tile_width());
+    let (step_y, mut t_max_y, t_delta_y) = axis(from.y(), dir.y(), y, layer.tile_height());
+    let mut t = 0.;
+    loop {
+        if layer.is_occupied(x, y) {
+            return Some(RayHit {
+                point: FPoint::new(from.x() + dir.x() * t, from.y() + dir.y() * t),
+                t,
+                blocker: SightBlocker::Tile(x, y),
+            });
+        }
+        t = t_max_x.min(t_max_y);
+        if t > 1. {
+            return None;
+        }
+        if t_max_x < t_max_y {
+            x += step_x;
+            t_max_x += t_delta_x;
+        } else {
+            y += step_y;
+            t_max_y += t_delta_y;
+        }
+    }
+}
+
+/// the first thing the segment hits: an occupied tile, or the bounds of an
+/// entity in the world for which blocks returns true (e.g. everything but
+/// the entity looking)
+pub fn raycast(
+    layer: &TileLayer,
+    world: &World,
+    from: FPoint,
+    to: FPoint,
+    blocks: impl Fn(EntityId) -> bool,
+) -> Option<RayHit> {
+    let tile = raycast_tiles(layer, from, to);
+    let dir = FPoint::new(to.x() - from.x(), to.y() - from.y());
+    let entity = world
+        .query_ray(from, dir)
+        .into_iter()
+        .filter(|(id, t)| *t <= 1. && blocks(*id))
+        .map(|(id, t)| RayHit {
+            point: FPoint::new(from.x() + dir.x() * t, from.y() + dir.y() * t),
+            t,
+            blocker: SightBlocker::Entity(id),
+        })
+        .next();
+    match (tile, entity) {
+        (Some(tile), Some(entity)) => Some(if entity.t < tile.t { entity } else { tile }),
+        (tile, entity) => tile.or(entity),
+    }
+}
+
+/// true if nothing blocks the segment between the points
+pub fn line_of_sight(
+    layer: &TileLayer,
+    world: &World,
+    from: FPoint,
+    to: FPoint,
+    blocks: impl Fn(EntityId) -> bool,
+) -> bool {
+    raycast(layer, world, from, to, blocks).is_none()
+}
+
+/// a Fov drawn over the map at a pixel per tile: unexplored tiles are
+/// covered, and explored tiles which aren't visible are shaded. cached like
+/// a Minimap, and redrawn when the Fov changes
+#[derive(Debug, Clone)]
+pub struct FogOfWar {
+    /// where the image is cached. shouldn't be a real file
+    path: PathBuf,
+    pub unexplored: Color,
+    pub explored: Color,
+    /// the fov revision the cached image is of
+    revision: Option<u64>,
+}
+
+impl FogOfWar {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            unexplored: Color::RGBA(0, 0, 0, 255),
+            explored: Color::RGBA(0, 0, 0, 160),
+            revision: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// the fog at a pixel per tile
+    pub fn render(&self, fov: &Fov) -> DecodedImage {
+        let rgba = fov
+            .visible
+            .iter()
+            .zip(&fov.explored)
+            .flat_map(|(visible, explored)| {
+                let color = match (visible, explored) {
+                    (true, _) => Color::RGBA(0, 0, 0, 0),
+                    (false, true) => self.explored,
+                    (false, false) => self.unexplored,
+                };
+                [color.r, color.g, color.b, color.a]
+            })
+            .collect();
+        DecodedImage {
+            width: fov.width,
+            height: fov.height,
+            rgba,
+        }
+    }
+
+    /// draw the fog stretched over dst, which should be where the layer is
+    /// drawn
+    pub fn draw(
+        &mut self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        fov: &Fov,
+        dst: FRect,
+    ) -> Result<(), String> {
+        if fov.width == 0 || fov.height == 0 {
+            return Ok(());
+        }
+        let draw = [DrawParams::new(None, Some(dst))];
+        if self.revision != Some(fov.revision)
+            || system.draw(window_name, &self.path, &draw).is_err()
+        {
+            // changed, or evicted from the texture cache
+            system.insert_image(window_name, &self.path, &self.render(fov))?;
+            self.revision = Some(fov.revision);
+            system.draw(window_name, &self.path, &draw)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(rows: &[&str]) -> TileLayer {
+        let tiles: Vec<u32> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { 1 } else { 0 }))
+            .collect();
+        TileLayer::from_tiles(rows[0].len() as u32, rows.len() as u32, 10., 10., tiles).unwrap()
+    }
+
+    #[test]
+    fn test_fov() {
+        let layer = layer(&[
+            ".......", //
+            ".......", //
+            "...#...", //
+            ".......", //
+            ".......", //
+        ]);
+        let mut fov = Fov::new(7, 5);
+        assert!(fov.update(&layer, (3, 4), 8));
+        assert!(!fov.update(&layer, (3, 4), 8));
+        assert!(fov.is_visible(3, 4));
+        // the wall is seen, but not what's behind it
+        assert!(fov.is_visible(3, 2));
+        assert!(!fov.is_visible(3, 1));
+        assert!(!fov.is_visible(3, 0));
+        assert!(fov.is_visible(0, 0));
+        assert!(!fov.is_visible(-1, 0));
+
+        fov.update(&layer, (0, 2), 2);
+        assert!(!fov.is_visible(3, 4));
+        assert!(fov.is_explored(3, 4));
+        assert!(fov.is_visible(2, 2) && !fov.is_visible(2, 0) && fov.is_visible(0, 0));
+        assert!(fov
+            .visible_tiles()
+            .all(|(x, y)| x * x + (y - 2) * (y - 2) <= 4));
+
+        let fog = FogOfWar::new(Path::new("fog"));
+        let image = fog.render(&fov);
+        let alpha = |x: usize, y: usize| image.rgba[(y * 7 + x) * 4 + 3];
+        assert_eq!(alpha(0, 2), 0);
+        assert_eq!(alpha(3, 4), 160);
+        assert_eq!(alpha(3, 0), 255);
+
+        fov.forget();
+        assert!(!fov.is_explored(3, 4) && !fov.is_explored(0, 2));
+        assert!(!fov.is_visible(0, 2));
+        assert!(fov.update(&layer, (0, 2), 2));
+    }
+
+    #[test]
+    fn test_fov_cache() {
+        let open = layer(&[
+            "...", //
+            "...", //
+        ]);
+        let mut fov = Fov::new(3, 2);
+        assert!(fov.update(&open, (0, 0), 4));
+        // another layer at the same revision isn't taken for the first
+        let walled = layer(&[
+            ".#.", //
+            ".#.", //
+        ]);
+        assert_eq!(walled.revision(), open.revision());
+        assert!(fov.update(&walled, (0, 0), 4));
+        assert!(!fov.is_visible(2, 0));
+        // nor is a tick which happens to equal the revision
+        assert!(fov.update_for_tick(walled.revision(), (0, 0), 4, |_, _| false));
+        assert!(fov.is_visible(2, 0));
+        assert!(fov.update(&walled, (0, 0), 4));
+        assert!(!fov.is_visible(2, 0));
+    }
+
+    #[test]
+    fn test_raycast() {
+        let layer = layer(&[
+            ".....", //
+            "..#..", //
+            ".....", //
+        ]);
+        let hit = raycast_tiles(&layer, FPoint::new(5., 15.), FPoint::new(45., 15.)).unwrap();
+        assert_eq!(hit.blocker, SightBlocker::Tile(2, 1));
+        assert_eq!(hit.point, FPoint::new(20., 15.));
+        assert_eq!(hit.t, 15. / 40.);
+        // stops short, or goes around
+        assert!(raycast_tiles(&layer, FPoint::new(5., 15.), FPoint::new(19., 15.)).is_none());
+        assert!(raycast_tiles(&layer, FPoint::new(5., 5.), FPoint::new(45., 5.)).is_none());
+
+        let world = World::with_seed(0);
+        assert!(line_of_sight(
+            &layer,
+            &world,
+            FPoint::new(5., 25.),
+            FPoint::new(45., 25.),
+            |_| true
+        ));
+        assert!(!line_of_sight(
+            &layer,
+            &world,
+            FPoint::new(5., 5.),
+            FPoint::new(45., 25.),
+            |_| true
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// number of frames kept for stats
+const HISTORY_LEN: usize = 240;
+
+/// sleep is imprecise; the last part of the wait is spun instead
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// frame time statistics over the recent history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameStats {
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    /// 99% of frames took at most this long
+    pub p99: Duration,
+}
+
+impl FrameStats {
+    pub fn from_samples(samples: &[Duration]) -> Self {
+        if samples.is_empty() {
+            return Default::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let total: Duration = sorted.iter().sum();
+        // nearest rank
+        let rank = ((sorted.len() as f64 * 0.99).ceil() as usize).clamp(1, sorted.len());
+        Self {
+            min: sorted[0],
+            avg: total / sorted.len() as u32,
+            max: sorted[sorted.len() - 1],
+            p99: sorted[rank - 1],
+        }
+    }
+}
+
+/// game time: the wall clock time between frames, scaled and summed. the
+/// things which animate are advanced with it so they agree on the time and
+/// all slow down or pause together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineClock {
+    total: Duration,
+    delta: Duration,
+    frame: u64,
+    /// multiplies the wall clock time. 0 pauses
+    pub time_scale: f32,
+    /// longer frames (e.g. after a hitch or at a breakpoint) count as this
+    /// long, so animations don't jump ahead
+    pub max_delta: Duration,
+}
+
+impl Default for EngineClock {
+    fn default() -> Self {
+        Self {
+            total: Duration::ZERO,
+            delta: Duration::ZERO,
+            frame: 0,
+            time_scale: 1.,
+            max_delta: Duration::from_millis(250),
+        }
+    }
+}
+
+impl EngineClock {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// count a frame which took the elapsed wall clock time. returns the
+    /// scaled delta
+    pub fn advance(&mut self, elapsed: Duration) -> Duration {
+        self.delta = elapsed
+            .min(self.max_delta)
+            .mul_f32(self.time_scale.max(0.));
+        self.total += self.delta;
+        self.frame += 1;
+        self.delta
+    }
+
+    /// the scaled time since the start
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// the scaled time the last frame took
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// how many frames were counted. counts while paused
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+/// measures frame times and optionally limits the frame rate
+pub struct FrameClock {
+    /// minimum duration of each frame
+    target: Option<Duration>,
+    frame_start: Instant,
+    delta: Duration,
+    smoothed: Duration,
+    /// weight of the newest frame in the smoothed delta, in (0, 1]
+    pub smoothing: f32,
+    history: VecDeque<Duration>,
+}
+
+impl FrameClock {
+    pub fn new(target: Option<Duration>) -> Self {
+        Self {
+            target,
+            frame_start: Instant::now(),
+            delta: Duration::ZERO,
+            smoothed: Duration::ZERO,
+            smoothing: 0.1,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    pub fn with_target_fps(fps: f32) -> Self {
+        Self::new(Some(Duration::from_secs_f32(1. / fps)))
+    }
+
+    pub fn target(&self) -> Option<Duration> {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Option<Duration>) {
+        self.target = target;
+    }
+
+    /// restart timing from now, e.g. before the first frame, so the time
+    /// spent loading isn't counted as a frame
+    pub fn reset(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+    /// call at the start of each frame. returns the time since the previous
+    /// call
+    pub fn tick(&mut self) -> Duration {
+        let now = Instant::now();
+        self.delta = now - self.frame_start;
+        self.frame_start = now;
+        self.smoothed = if self.history.is_empty() {
+            self.delta
+        } else {
+            let t = self.smoothing.clamp(0., 1.);
+            self.smoothed.mul_f32(1. - t) + self.delta.mul_f32(t)
+        };
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.delta);
+        self.delta
+    }
+
+    /// call at the end of each frame. waits until the target frame time has
+    /// passed since the frame started; sleeping, then spinning for precision
+    pub fn limit(&self) {
+        let Some(target) = self.target else {
+            return;
+        };
+        let deadline = self.frame_start + target;
+        let now = Instant::now();
+        if deadline <= now {
+            return;
+        }
+        let remaining = deadline - now;
+        if remaining > SPIN_THRESHOLD {
+            std::thread::sleep(remaining - SPIN_THRESHOLD);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    /// the time between the last two ticks
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// exponential moving average of the delta. less jittery for animation
+    pub fn smoothed_delta(&self) -> Duration {
+        self.smoothed
+    }
+
+    /// frames per second from the average of the recent history
+    pub fn fps(&self) -> f32 {
+        let avg = self.stats().avg;
+        if avg.is_zero() {
+            0.
+        } else {
+            1. / avg.as_secs_f32()
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        let (a, b) = self.history.as_slices();
+        if b.is_empty() {
+            FrameStats::from_samples(a)
+        } else {
+            FrameStats::from_samples(&[a, b].concat())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let samples: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        let stats = FrameStats::from_samples(&samples);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(100));
+        assert_eq!(stats.avg, Duration::from_micros(50_500));
+        assert_eq!(stats.p99, Duration::from_millis(99));
+    }
+
+    #[test]
+    fn test_engine_clock() {
+        let mut clock = EngineClock::new();
+        clock.advance(Duration::from_millis(10));
+        clock.time_scale = 0.5;
+        assert_eq!(clock.advance(Duration::from_millis(10)), Duration::from_millis(5));
+        clock.time_scale = 0.;
+        clock.advance(Duration::from_millis(10));
+        clock.time_scale = 1.;
+        // a hitch counts as the max delta
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(clock.total(), Duration::from_millis(265));
+        assert_eq!(clock.delta(), Duration::from_millis(250));
+        assert_eq!(clock.frame(), 4);
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use libloading::Library;
+
+use super::{
+    app::Frame,
+    system::ChimericSystem,
+    trace::{log_info, log_warn},
+};
+
+/// a library is only loaded by a host built against the same engine. both
+/// must also be built by the same compiler, since games are passed across
+/// with the rust abi
+pub const HOT_ABI: &str = concat!("chimeric_engine ", env!("CARGO_PKG_VERSION"));
+
+/// creates the game, given the state saved by the previous version of the
+/// library (None on the first load)
+pub type HotGameConstructor = fn(Option<&[u8]>) -> Result<Box<dyn HotGame>, String>;
+
+/// the game's logic, compiled into a cdylib so it can be rebuilt and reloaded
+/// while the game runs. the library exports it with hot_game!
+pub trait HotGame {
+    fn update(&mut self, frame: &mut Frame) -> Result<(), String>;
+
+    fn draw(&self, system: &mut ChimericSystem) -> Result<(), String>;
+
+    /// everything which should survive a reload, e.g. the world's entities
+    /// (with what's needed to spawn them again) and a World::snapshot of their
+    /// state. nothing from the old library can be kept: its code is unloaded
+    fn save(&mut self) -> Result<Vec<u8>, String>;
+}
+
+/// exports a HotGameConstructor from a cdylib, for HotGameLibrary:
+///
+/// ```ignore
+/// fn create(saved: Option<&[u8]>) -> Result<Box<dyn HotGame>, String> { ... }
+/// chimeric_engine::hot_game!(create);
+/// ```
+#[macro_export]
+macro_rules! hot_game {
+    ($constructor:path) => {
+        #[no_mangle]
+        pub fn chimeric_hot_abi() -> &'static str {
+            $crate::core::hot_reload::HOT_ABI
+        }
+
+        #[no_mangle]
+        pub fn chimeric_hot_game(
+            saved: Option<&[u8]>,
+        ) -> Result<Box<dyn $crate::core::hot_reload::HotGame>, String> {
+            let constructor: $crate::core::hot_reload::HotGameConstructor = $constructor;
+            constructor(saved)
+        }
+    };
+}
+
+/// the game from a loaded library. the game is declared first so it's
+/// dropped before the code it runs is unloaded
+struct Loaded {
+    game: Box<dyn HotGame>,
+    _library: Library,
+    /// the copy which was loaded, removed once unloaded
+    copy: PathBuf,
+}
+
+fn unload(loaded: Loaded) {
+    let copy = loaded.copy.clone();
+    drop(loaded);
+    let _ = std::fs::remove_file(copy);
+}
+
+/// a dev mode game loop which runs the game from a cdylib (e.g. a crate with
+/// crate-type = ["cdylib"] in the workspace), and reloads it whenever it's
+/// rebuilt, carrying its state across with HotGame::save. use it as the state
+/// of ChimericApp::run:
+///
+/// ```ignore
+/// let mut game = HotGameLibrary::load(Path::new("target/debug/libgame.so"))?;
+/// app.run(
+///     &mut game,
+///     |game, frame| {
+///         game.reload_if_changed()?;
+///         game.update(frame)
+///     },
+///     |game, system| game.draw(system),
+/// )
+/// ```
+///
+/// release builds should link the game statically instead
+pub struct HotGameLibrary {
+    path: PathBuf,
+    modified: SystemTime,
+    loaded: Option<Loaded>,
+    /// distinguishes each copy of the library, since a path which is already
+    /// loaded isn't loaded again
+    generation: u32,
+    /// a changed library is only loaded once it's been unchanged this long,
+    /// so a build in progress isn't loaded half written
+    pub settle: Duration,
+}
+
+impl HotGameLibrary {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut library = Self {
+            path: path.to_owned(),
+            modified: SystemTime::UNIX_EPOCH,
+            loaded: None,
+            generation: 0,
+            settle: Duration::from_millis(300),
+        };
+        library.modified = library.modified_time()?;
+        library.loaded = Some(library.open(None)?);
+        Ok(library)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn modified_time(&self) -> Result<SystemTime, String> {
+        std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("{}: {e}", self.path.display()))
+    }
+
+    fn open(&mut self, saved: Option<&[u8]>) -> Result<Loaded, String> {
+        self.generation += 1;
+        let name = self
+            .path
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let copy = std::env::temp_dir().join(format!(
+            "{}-{}-{}",
+            std::process::id(),
+            self.generation,
+            name
+        ));
+        std::fs::copy(&self.path, &copy).map_err(|e| format!("{}: {e}", self.path.display()))?;
+        let result = Self::open_copy(&copy, saved);
+        if result.is_err() {
+            let _ = std::fs::remove_file(&copy);
+        }
+        let (game, library) = result.map_err(|e| format!("{}: {e}", self.path.display()))?;
+        Ok(Loaded {
+            game,
+            _library: library,
+            copy,
+        })
+    }
+
+    fn open_copy(copy: &Path, saved: Option<&[u8]>) -> Result<(Box<dyn HotGame>, Library), String> {
+        // safety: the library's initializers are run. it's expected to be the
+        // game, exporting hot_game! with a matching abi
+        let library = unsafe { Library::new(copy) }.map_err(|e| e.to_string())?;
+        let game = unsafe {
+            let abi = library
+                .get::<fn() -> &'static str>(b"chimeric_hot_abi")
+                .map_err(|e| e.to_string())?;
+            if abi() != HOT_ABI {
+                return Err(format!("built for {}, not {}", abi(), HOT_ABI));
+            }
+            let constructor = library
+                .get::<HotGameConstructor>(b"chimeric_hot_game")
+                .map_err(|e| e.to_string())?;
+            constructor(saved)?
+        };
+        Ok((game, library))
+    }
+
+    fn loaded(&mut self) -> &mut Loaded {
+        // only None while reloading
+        self.loaded.as_mut().unwrap()
+    }
+
+    /// reload now. the new library gets the old game's saved state; if it
+    /// fails to load, the old game keeps running
+    pub fn reload(&mut self) -> Result<(), String> {
+        self.modified = self.modified_time()?;
+        let saved = self.loaded().game.save()?;
+        let loaded = self.open(Some(&saved))?;
+        if let Some(old) = self.loaded.replace(loaded) {
+            unload(old);
+        }
+        log_info!(
+            "reloaded {} ({} bytes of state)",
+            self.path.display(),
+            saved.len()
+        );
+        Ok(())
+    }
+
+    /// reload if the library was rebuilt. returns true if reloaded. errors
+    /// (e.g. a build which doesn't load) aren't retried until it changes
+    /// again
+    pub fn reload_if_changed(&mut self) -> Result<bool, String> {
+        let modified = match self.modified_time() {
+            Ok(modified) => modified,
+            // removed while cargo relinks it
+            Err(_) => return Ok(false),
+        };
+        let settled = modified
+            .elapsed()
+            .is_ok_and(|elapsed| elapsed >= self.settle);
+        if modified == self.modified || !settled {
+            return Ok(false);
+        }
+        if let Err(e) = self.reload() {
+            self.modified = modified;
+            log_warn!("{e}");
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    pub fn game(&mut self) -> &mut dyn HotGame {
+        self.loaded().game.as_mut()
+    }
+
+    pub fn update(&mut self, frame: &mut Frame) -> Result<(), String> {
+        self.loaded().game.update(frame)
+    }
+
+    pub fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        match &self.loaded {
+            Some(loaded) => loaded.game.draw(system),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for HotGameLibrary {
+    fn drop(&mut self) {
+        if let Some(loaded) = self.loaded.take() {
+            unload(loaded);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_errors() {
+        let path = std::env::temp_dir().join(format!("chimeric-hot-{}.so", std::process::id()));
+        let err = HotGameLibrary::load(&path).err().unwrap();
+        assert!(err.starts_with(&path.display().to_string()));
+
+        std::fs::write(&path, b"not a library").unwrap();
+        assert!(HotGameLibrary::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        // the copy which failed to load was removed
+        let copies = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .flatten()
+            .filter(|e| {
+                e.file_name()
+                    .to_string_lossy()
+                    .ends_with(&format!("-chimeric-hot-{}.so", std::process::id()))
+            })
+            .count();
+        assert_eq!(copies, 0);
+    }
+}
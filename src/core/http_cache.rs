@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{
+    pack::normalize,
+    save::write_atomic,
+    trace::{log_info, log_warn},
+    vfs::VfsProvider,
+};
+
+/// maps each url's vfs path to the hash of its content
+const INDEX_FILE: &str = "index.toml";
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// where a url is found once mounted: its host and path, e.g.
+/// "https://example.com/dlc/hero.png?v=2" is "example.com/dlc/hero.png"
+pub fn url_path(url: &str) -> Result<String, String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| format!("\"{}\" isn't an http(s) url", url))?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let path = normalize(Path::new(rest));
+    if path.is_empty() {
+        return Err(format!("\"{}\" has no path", url));
+    }
+    Ok(path)
+}
+
+/// downloads assets into a content addressed directory on disk, for streamed
+/// dlc or user made content. files are named by the sha-256 of their content,
+/// so identical downloads are stored once, and a file is only fetched again
+/// if it's missing. mount the provider to load fetched files through the
+/// usual texture and audio paths.
+///
+/// fetching blocks; for large downloads, call it from a worker thread
+pub struct HttpCache {
+    dir: PathBuf,
+    /// url path to content hash
+    index: BTreeMap<String, String>,
+    agent: ureq::Agent,
+    /// larger downloads fail
+    pub max_size: u64,
+}
+
+impl HttpCache {
+    /// use (or create) the cache in the directory, e.g. under the user's data
+    /// directory
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let index_path = dir.join(INDEX_FILE);
+        let index = match std::fs::read_to_string(&index_path) {
+            Ok(contents) => {
+                let table: toml::Table = contents
+                    .parse()
+                    .map_err(|e: toml::de::Error| e.to_string())?;
+                table
+                    .into_iter()
+                    .filter_map(|(path, hash)| Some((path, hash.as_str()?.to_owned())))
+                    .collect()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => return Err(e.to_string()),
+        };
+        Ok(Self {
+            dir: dir.to_owned(),
+            index,
+            agent: ureq::AgentBuilder::new().build(),
+            max_size: 256 * 1024 * 1024,
+        })
+    }
+
+    fn save_index(&self) -> Result<(), String> {
+        let table: toml::Table = self
+            .index
+            .iter()
+            .map(|(path, hash)| (path.clone(), toml::Value::String(hash.clone())))
+            .collect();
+        write_atomic(&self.dir.join(INDEX_FILE), table.to_string().as_bytes())
+    }
+
+    /// the cached file for the url, if it was fetched before
+    pub fn cached(&self, url: &str) -> Option<PathBuf> {
+        let hash = self.index.get(&url_path(url).ok()?)?;
+        Some(self.dir.join(hash)).filter(|file| file.is_file())
+    }
+
+    /// download the url unless it's cached. returns the cached file
+    pub fn fetch(&mut self, url: &str) -> Result<PathBuf, String> {
+        self.fetch_inner(url, None)
+    }
+
+    /// like fetch, but the content must have this sha-256 (hex), e.g. from a
+    /// signed manifest. a cached file with a different hash is fetched again
+    pub fn fetch_verified(&mut self, url: &str, sha256: &str) -> Result<PathBuf, String> {
+        self.fetch_inner(url, Some(&sha256.to_ascii_lowercase()))
+    }
+
+    fn fetch_inner(&mut self, url: &str, expected: Option<&str>) -> Result<PathBuf, String> {
+        let path = url_path(url)?;
+        if let Some(file) = self.cached(url) {
+            let hash = &self.index[&path];
+            if expected.is_none_or(|expected| expected == hash) {
+                return Ok(file);
+            }
+        }
+
+        let response = self.agent.get(url).call().map_err(|e| e.to_string())?;
+        let mut data: Vec<u8> = Vec::new();
+        response
+            .into_reader()
+            .take(self.max_size + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| e.to_string())?;
+        if data.len() as u64 > self.max_size {
+            return Err(format!("{} is over {} bytes", url, self.max_size));
+        }
+        let hash = sha256_hex(&data);
+        if let Some(expected) = expected {
+            if expected != hash {
+                return Err(format!("{} has sha-256 {}, not {}", url, hash, expected));
+            }
+        }
+        let file = self.dir.join(&hash);
+        if !file.is_file() {
+            write_atomic(&file, &data)?;
+        }
+        log_info!("fetched {url} ({} bytes)", data.len());
+        self.index.insert(path, hash);
+        self.save_index()?;
+        Ok(file)
+    }
+
+    /// delete cached files no url refers to any more, e.g. after a url's
+    /// content changed
+    pub fn prune(&self) -> Result<(), String> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| e.to_string())?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let referenced = self.index.values().any(|hash| *hash == name);
+            if name != INDEX_FILE && !referenced {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    log_warn!("couldn't prune {name}: {e}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the files fetched so far, under their url paths. mount it again after
+    /// fetching more
+    pub fn provider(&self) -> HttpCacheProvider {
+        HttpCacheProvider {
+            dir: self.dir.clone(),
+            index: self.index.clone(),
+        }
+    }
+}
+
+/// fetched files by url path, see url_path
+pub struct HttpCacheProvider {
+    dir: PathBuf,
+    index: BTreeMap<String, String>,
+}
+
+impl VfsProvider for HttpCacheProvider {
+    fn contains(&self, path: &str) -> bool {
+        self.index.contains_key(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        let hash = self
+            .index
+            .get(path)
+            .ok_or_else(|| format!("\"{path}\" wasn't fetched"))?;
+        let file = self.dir.join(hash);
+        std::fs::read(&file).map_err(|e| format!("{}: {e}", file.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_index() {
+        assert_eq!(
+            url_path("https://example.com/dlc/../art/hero.png?v=2").unwrap(),
+            "example.com/art/hero.png"
+        );
+        assert!(url_path("ftp://example.com/a").is_err());
+        assert!(url_path("https://").is_err());
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let dir = std::env::temp_dir().join(format!("chimeric-http-{}", std::process::id()));
+        let hash = sha256_hex(b"png");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(&hash), b"png").unwrap();
+        std::fs::write(dir.join("stale"), b"old").unwrap();
+        let index = format!("\"example.com/hero.png\" = \"{hash}\"");
+        std::fs::write(dir.join(INDEX_FILE), index).unwrap();
+
+        let mut cache = HttpCache::open(&dir).unwrap();
+        // cached, so nothing is downloaded
+        let file = cache.fetch("https://example.com/hero.png").unwrap();
+        assert_eq!(std::fs::read(file).unwrap(), b"png");
+        cache.prune().unwrap();
+        assert!(!dir.join("stale").exists());
+        let provider = cache.provider();
+        assert_eq!(provider.read("example.com/hero.png").unwrap(), b"png");
+        assert!(!provider.contains("example.com/other.png"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
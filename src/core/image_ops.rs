@@ -0,0 +1,205 @@
+use std::{
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{pixels::Color, rect::Rect};
+
+use super::decode::DecodedImage;
+
+/// a cpu side change to an image, for simple asset variants without an
+/// image editor. see ChimericSystem::derive_texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageOp {
+    FlipHorizontal,
+    FlipVertical,
+    /// quarter turns clockwise
+    Rotate90(u32),
+    /// clamped to the image
+    Crop(Rect),
+    /// multiplies each channel, like a texture's color and alpha mod
+    Tint(Color),
+    Grayscale,
+    /// nearest neighbour, so pixel art stays crisp
+    Resize(u32, u32),
+}
+
+/// names the op in derived texture paths
+impl fmt::Display for ImageOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageOp::FlipHorizontal => write!(f, "flip_h"),
+            ImageOp::FlipVertical => write!(f, "flip_v"),
+            ImageOp::Rotate90(turns) => write!(f, "rotate{}", turns % 4 * 90),
+            ImageOp::Crop(rect) => write!(
+                f,
+                "crop{},{},{},{}",
+                rect.x(),
+                rect.y(),
+                rect.width(),
+                rect.height()
+            ),
+            ImageOp::Tint(c) => write!(f, "tint{},{},{},{}", c.r, c.g, c.b, c.a),
+            ImageOp::Grayscale => write!(f, "gray"),
+            ImageOp::Resize(w, h) => write!(f, "resize{w},{h}"),
+        }
+    }
+}
+
+impl ImageOp {
+    pub fn apply(&self, image: &DecodedImage) -> DecodedImage {
+        match *self {
+            ImageOp::FlipHorizontal => remap(image, image.width, image.height, |x, y| {
+                (image.width - 1 - x, y)
+            }),
+            ImageOp::FlipVertical => remap(image, image.width, image.height, |x, y| {
+                (x, image.height - 1 - y)
+            }),
+            ImageOp::Rotate90(turns) => match turns % 4 {
+                1 => remap(image, image.height, image.width, |x, y| {
+                    (y, image.height - 1 - x)
+                }),
+                2 => remap(image, image.width, image.height, |x, y| {
+                    (image.width - 1 - x, image.height - 1 - y)
+                }),
+                3 => remap(image, image.height, image.width, |x, y| {
+                    (image.width - 1 - y, x)
+                }),
+                _ => image.clone(),
+            },
+            ImageOp::Crop(rect) => crop(image, rect),
+            ImageOp::Tint(color) => {
+                let mut image = image.clone();
+                let tint = [color.r, color.g, color.b, color.a];
+                for pixel in image.rgba.chunks_exact_mut(4) {
+                    for (c, t) in pixel.iter_mut().zip(tint) {
+                        *c = ((*c as u32 * t as u32 + 127) / 255) as u8;
+                    }
+                }
+                image
+            }
+            ImageOp::Grayscale => {
+                let mut image = image.clone();
+                for pixel in image.rgba.chunks_exact_mut(4) {
+                    let luma =
+                        0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32;
+                    let luma = luma.round() as u8;
+                    pixel[..3].copy_from_slice(&[luma; 3]);
+                }
+                image
+            }
+            ImageOp::Resize(width, height) => remap(image, width, height, |x, y| {
+                (
+                    (x as u64 * image.width as u64 / width as u64) as u32,
+                    (y as u64 * image.height as u64 / height as u64) as u32,
+                )
+            }),
+        }
+    }
+}
+
+/// an image of the size, each pixel copied from where the function says in
+/// the source
+fn remap(
+    image: &DecodedImage,
+    width: u32,
+    height: u32,
+    source: impl Fn(u32, u32) -> (u32, u32),
+) -> DecodedImage {
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let (sx, sy) = source(x, y);
+            let i = (sy as usize * image.width as usize + sx as usize) * 4;
+            rgba.extend_from_slice(&image.rgba[i..i + 4]);
+        }
+    }
+    DecodedImage {
+        width,
+        height,
+        rgba,
+    }
+}
+
+/// the part of the image in the rect, clamped to the image
+pub fn crop(image: &DecodedImage, rect: Rect) -> DecodedImage {
+    let left = rect.left().clamp(0, image.width as i32) as u32;
+    let top = rect.top().clamp(0, image.height as i32) as u32;
+    let right = rect.right().clamp(0, image.width as i32) as u32;
+    let bottom = rect.bottom().clamp(0, image.height as i32) as u32;
+    remap(image, right - left, bottom - top, |x, y| {
+        (left + x, top + y)
+    })
+}
+
+/// the ops applied in order. an error if nothing is left of the image
+pub fn apply_all(image: &DecodedImage, ops: &[ImageOp]) -> Result<DecodedImage, String> {
+    let image = ops.iter().fold(image.clone(), |image, op| op.apply(&image));
+    if image.width == 0 || image.height == 0 {
+        return Err("nothing is left of the image".into());
+    }
+    Ok(image)
+}
+
+/// where the image at the path, with the ops applied, is cached. e.g.
+/// "hero.png|flip_h|gray"
+pub fn derived_path(path: &Path, ops: &[ImageOp]) -> PathBuf {
+    let mut derived = OsString::from(path.as_os_str());
+    for op in ops {
+        derived.push(format!("|{op}"));
+    }
+    derived.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ops() {
+        // 2 by 3, each pixel's red is its index
+        let image = DecodedImage {
+            width: 2,
+            height: 3,
+            rgba: (0..6u8).flat_map(|i| [i, 10, 200, 255]).collect(),
+        };
+        let red =
+            |image: &DecodedImage| -> Vec<u8> { image.rgba.iter().step_by(4).copied().collect() };
+        assert_eq!(
+            red(&ImageOp::FlipHorizontal.apply(&image)),
+            [1, 0, 3, 2, 5, 4]
+        );
+        assert_eq!(
+            red(&ImageOp::FlipVertical.apply(&image)),
+            [4, 5, 2, 3, 0, 1]
+        );
+        let turned = ImageOp::Rotate90(1).apply(&image);
+        assert_eq!((turned.width, turned.height), (3, 2));
+        assert_eq!(red(&turned), [4, 2, 0, 5, 3, 1]);
+        assert_eq!(red(&ImageOp::Rotate90(3).apply(&turned)), red(&image));
+        assert_eq!(red(&ImageOp::Rotate90(2).apply(&image)), [5, 4, 3, 2, 1, 0]);
+        assert_eq!(
+            red(&ImageOp::Crop(Rect::new(1, 1, 5, 1)).apply(&image)),
+            [3]
+        );
+        assert_eq!(
+            red(&ImageOp::Resize(4, 3).apply(&image)),
+            [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5]
+        );
+
+        let tinted = ImageOp::Tint(Color::RGBA(255, 0, 128, 128)).apply(&image);
+        assert_eq!(tinted.rgba[..4], [0, 0, 100, 128]);
+        let gray = ImageOp::Grayscale.apply(&image);
+        assert_eq!(gray.rgba[..4], [29, 29, 29, 255]);
+
+        assert!(apply_all(&image, &[ImageOp::Crop(Rect::new(5, 5, 1, 1))]).is_err());
+        assert_eq!(
+            derived_path(
+                Path::new("hero.png"),
+                &[ImageOp::FlipHorizontal, ImageOp::Rotate90(5)]
+            ),
+            Path::new("hero.png|flip_h|rotate90")
+        );
+    }
+}
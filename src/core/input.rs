@@ -0,0 +1,668 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use sdl2::{
+    controller::{Axis, Button},
+    event::Event,
+    keyboard::Scancode,
+    mouse::MouseButton,
+    rect::FPoint,
+};
+
+use super::{
+    camera::{self, Camera2D},
+    events::EngineEvent,
+};
+
+/// a physical input which can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// by scancode, so bindings follow the physical key position regardless
+    /// of keyboard layout
+    Key(Scancode),
+    Mouse(MouseButton),
+    GamepadButton(Button),
+    /// one direction of an axis
+    GamepadAxis {
+        axis: Axis,
+        positive: bool,
+    },
+}
+
+fn mouse_button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "left",
+        MouseButton::Middle => "middle",
+        MouseButton::Right => "right",
+        MouseButton::X1 => "x1",
+        MouseButton::X2 => "x2",
+        MouseButton::Unknown => "unknown",
+    }
+}
+
+fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+    Some(match name {
+        "left" => MouseButton::Left,
+        "middle" => MouseButton::Middle,
+        "right" => MouseButton::Right,
+        "x1" => MouseButton::X1,
+        "x2" => MouseButton::X2,
+        _ => return None,
+    })
+}
+
+impl Binding {
+    /// e.g. "key:Space", "mouse:left", "button:a", "axis:leftx+"
+    pub fn to_config_string(&self) -> String {
+        match self {
+            Binding::Key(scancode) => format!("key:{}", scancode.name()),
+            Binding::Mouse(button) => format!("mouse:{}", mouse_button_name(*button)),
+            Binding::GamepadButton(button) => format!("button:{}", button.string()),
+            Binding::GamepadAxis { axis, positive } => {
+                format!(
+                    "axis:{}{}",
+                    axis.string(),
+                    if *positive { '+' } else { '-' }
+                )
+            }
+        }
+    }
+
+    pub fn from_config_string(s: &str) -> Result<Self, String> {
+        let err = || format!("invalid binding \"{}\"", s);
+        let (kind, name) = s.split_once(':').ok_or_else(err)?;
+        match kind {
+            "key" => Scancode::from_name(name).map(Binding::Key),
+            "mouse" => mouse_button_from_name(name).map(Binding::Mouse),
+            "button" => Button::from_string(name).map(Binding::GamepadButton),
+            "axis" => {
+                let (axis, positive) = if let Some(axis) = name.strip_suffix('+') {
+                    (axis, true)
+                } else if let Some(axis) = name.strip_suffix('-') {
+                    (axis, false)
+                } else {
+                    return Err(err());
+                };
+                Axis::from_string(axis).map(|axis| Binding::GamepadAxis { axis, positive })
+            }
+            _ => None,
+        }
+        .ok_or_else(err)
+    }
+}
+
+/// per frame state of keys, mouse buttons, and gamepad buttons and axes,
+/// computed from events. edges (just pressed / just released) last for the
+/// frame in which they occurred
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    down: HashSet<Binding>,
+    pressed: HashSet<Binding>,
+    released: HashSet<Binding>,
+    /// latest value of each axis in [-1, 1]
+    axes: HashMap<Axis, f32>,
+    /// axis values as of the end of the previous frame
+    previous_axes: HashMap<Axis, f32>,
+    mouse_position: Option<FPoint>,
+    mouse_window: Option<String>,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// start a new frame with this frame's events
+    pub fn update(&mut self, events: &[EngineEvent]) {
+        self.pressed.clear();
+        self.released.clear();
+        self.previous_axes.clone_from(&self.axes);
+        events.iter().for_each(|e| self.handle_event(e));
+    }
+
+    fn press(&mut self, binding: Binding) {
+        // key repeats aren't new presses
+        if self.down.insert(binding) {
+            self.pressed.insert(binding);
+        }
+    }
+
+    fn release(&mut self, binding: Binding) {
+        if self.down.remove(&binding) {
+            self.released.insert(binding);
+        }
+    }
+
+    /// apply a single event to the current frame
+    pub fn handle_event(&mut self, event: &EngineEvent) {
+        match event {
+            EngineEvent::KeyDown {
+                scancode: Some(scancode),
+                ..
+            } => self.press(Binding::Key(*scancode)),
+            EngineEvent::KeyUp {
+                scancode: Some(scancode),
+                ..
+            } => self.release(Binding::Key(*scancode)),
+            EngineEvent::MouseButtonDown {
+                button,
+                window,
+                position,
+                ..
+            } => {
+                self.press(Binding::Mouse(*button));
+                self.mouse_position = Some(*position);
+                self.mouse_window.clone_from(window);
+            }
+            EngineEvent::MouseButtonUp {
+                button,
+                window,
+                position,
+                ..
+            } => {
+                self.release(Binding::Mouse(*button));
+                self.mouse_position = Some(*position);
+                self.mouse_window.clone_from(window);
+            }
+            EngineEvent::MouseMotion {
+                window, position, ..
+            } => {
+                self.mouse_position = Some(*position);
+                self.mouse_window.clone_from(window);
+            }
+            EngineEvent::WindowFocus { focused: false, .. } => {
+                // key up events are lost while unfocused
+                let down: Vec<Binding> = self.down.iter().copied().collect();
+                down.into_iter().for_each(|b| self.release(b));
+            }
+            EngineEvent::Other(Event::ControllerButtonDown { button, .. }) => {
+                self.press(Binding::GamepadButton(*button))
+            }
+            EngineEvent::Other(Event::ControllerButtonUp { button, .. }) => {
+                self.release(Binding::GamepadButton(*button))
+            }
+            EngineEvent::Other(Event::ControllerAxisMotion { axis, value, .. }) => {
+                self.axes
+                    .insert(*axis, (*value as f32 / i16::MAX as f32).clamp(-1., 1.));
+            }
+            _ => {}
+        }
+    }
+
+    /// for digital bindings. gamepad axes are never down; see axis
+    pub fn is_down(&self, binding: Binding) -> bool {
+        self.down.contains(&binding)
+    }
+
+    /// went down this frame
+    pub fn just_pressed(&self, binding: Binding) -> bool {
+        self.pressed.contains(&binding)
+    }
+
+    /// went up this frame
+    pub fn just_released(&self, binding: Binding) -> bool {
+        self.released.contains(&binding)
+    }
+
+    pub fn key_down(&self, scancode: Scancode) -> bool {
+        self.is_down(Binding::Key(scancode))
+    }
+
+    pub fn key_just_pressed(&self, scancode: Scancode) -> bool {
+        self.just_pressed(Binding::Key(scancode))
+    }
+
+    pub fn key_just_released(&self, scancode: Scancode) -> bool {
+        self.just_released(Binding::Key(scancode))
+    }
+
+    pub fn mouse_down(&self, button: MouseButton) -> bool {
+        self.is_down(Binding::Mouse(button))
+    }
+
+    pub fn mouse_just_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed(Binding::Mouse(button))
+    }
+
+    pub fn mouse_just_released(&self, button: MouseButton) -> bool {
+        self.just_released(Binding::Mouse(button))
+    }
+
+    /// last known position of the mouse, in the logical coordinates of the
+    /// window it was in
+    pub fn mouse_position(&self) -> Option<FPoint> {
+        self.mouse_position
+    }
+
+    /// the window the mouse was last in
+    pub fn mouse_window(&self) -> Option<&str> {
+        self.mouse_window.as_deref()
+    }
+
+    /// the world point under the mouse, for a camera whose viewport is the
+    /// window's logical size. None if the mouse was last in a different
+    /// window or is over the letterbox bars
+    pub fn mouse_world_position(&self, window: &str, camera: &Camera2D) -> Option<FPoint> {
+        if self.mouse_window.as_deref() != Some(window) {
+            return None;
+        }
+        let position = self.mouse_position?;
+        let viewport = (camera.viewport.0 as u32, camera.viewport.1 as u32);
+        camera::in_logical_area(position, viewport).then(|| camera.screen_to_world(position))
+    }
+
+    /// latest value in [-1, 1], without a dead zone
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.)
+    }
+
+    /// value as of the end of the previous frame
+    pub fn previous_axis(&self, axis: Axis) -> f32 {
+        self.previous_axes.get(&axis).copied().unwrap_or(0.)
+    }
+}
+
+/// shapes a gamepad axis' raw value. the default passes it through as is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisResponse {
+    /// smaller magnitudes count as 0, so a resting stick doesn't drift
+    pub dead_zone: f32,
+    /// larger magnitudes count as 1, since sticks rarely reach the edge
+    pub saturation: f32,
+    /// applied to the magnitude once rescaled between the two. above 1 gives
+    /// finer control near the center
+    pub exponent: f32,
+}
+
+impl Default for AxisResponse {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.,
+            saturation: 1.,
+            exponent: 1.,
+        }
+    }
+}
+
+impl AxisResponse {
+    /// a value in [-1, 1], keeping its sign
+    pub fn apply(&self, value: f32) -> f32 {
+        let range = self.saturation - self.dead_zone;
+        let magnitude = if range <= 0. {
+            (value.abs() >= self.saturation) as u8 as f32
+        } else {
+            ((value.abs() - self.dead_zone) / range).clamp(0., 1.)
+        };
+        magnitude.powf(self.exponent.max(0.)).copysign(value)
+    }
+
+    pub fn to_toml(&self) -> toml::Table {
+        let mut table = toml::Table::new();
+        table.insert("dead_zone".into(), (self.dead_zone as f64).into());
+        table.insert("saturation".into(), (self.saturation as f64).into());
+        table.insert("exponent".into(), (self.exponent as f64).into());
+        table
+    }
+
+    /// missing fields keep their defaults
+    pub fn from_toml(table: &toml::Table) -> Result<Self, String> {
+        let mut response = Self::default();
+        for (name, value) in table.iter() {
+            let field = match name.as_str() {
+                "dead_zone" => &mut response.dead_zone,
+                "saturation" => &mut response.saturation,
+                "exponent" => &mut response.exponent,
+                _ => return Err(format!("unknown axis response field \"{}\"", name)),
+            };
+            *field = value
+                .as_float()
+                .or_else(|| value.as_integer().map(|i| i as f64))
+                .ok_or_else(|| format!("axis response field \"{}\" isn't a number", name))?
+                as f32;
+        }
+        Ok(response)
+    }
+}
+
+/// maps logical actions ("jump", "fire") to physical inputs, so gameplay code
+/// doesn't deal with scancodes. actions are queried against the input state
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    bindings: BTreeMap<String, Vec<Binding>>,
+    /// an axis direction counts as down past this value, after its response
+    pub axis_threshold: f32,
+    responses: HashMap<Axis, AxisResponse>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            bindings: Default::default(),
+            axis_threshold: 0.5,
+            responses: Default::default(),
+        }
+    }
+}
+
+fn axis_direction(value: f32, positive: bool) -> f32 {
+    if positive {
+        value.max(0.)
+    } else {
+        (-value).max(0.)
+    }
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add a binding to the action. an action can have many bindings
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.bindings.entry(action.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    /// replace all of the action's bindings
+    pub fn rebind(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.bindings.insert(action.to_owned(), bindings);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.bindings
+            .get(action)
+            .map(|b| b.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(|s| s.as_str())
+    }
+
+    pub fn set_axis_response(&mut self, axis: Axis, response: AxisResponse) {
+        self.responses.insert(axis, response);
+    }
+
+    pub fn axis_response(&self, axis: Axis) -> AxisResponse {
+        self.responses.get(&axis).copied().unwrap_or_default()
+    }
+
+    /// the axis' latest value, shaped by its response
+    pub fn shaped_axis(&self, state: &InputState, axis: Axis) -> f32 {
+        self.axis_response(axis).apply(state.axis(axis))
+    }
+
+    fn binding_value(&self, state: &InputState, binding: &Binding) -> f32 {
+        match binding {
+            Binding::GamepadAxis { axis, positive } => {
+                axis_direction(self.shaped_axis(state, *axis), *positive)
+            }
+            binding => {
+                if state.is_down(*binding) {
+                    1.
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+
+    /// (down last frame, down this frame)
+    fn binding_edges(&self, state: &InputState, binding: &Binding) -> (bool, bool) {
+        match binding {
+            Binding::GamepadAxis { axis, positive } => {
+                let response = self.axis_response(*axis);
+                (
+                    axis_direction(response.apply(state.previous_axis(*axis)), *positive)
+                        >= self.axis_threshold,
+                    axis_direction(response.apply(state.axis(*axis)), *positive)
+                        >= self.axis_threshold,
+                )
+            }
+            binding => (
+                state.just_released(*binding)
+                    || (state.is_down(*binding) && !state.just_pressed(*binding)),
+                state.is_down(*binding),
+            ),
+        }
+    }
+
+    /// strength of the action in [0, 1]; the largest of its bindings
+    pub fn value(&self, state: &InputState, action: &str) -> f32 {
+        self.bindings(action)
+            .iter()
+            .map(|b| self.binding_value(state, b))
+            .fold(0., f32::max)
+    }
+
+    pub fn is_down(&self, state: &InputState, action: &str) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|b| self.binding_edges(state, b).1)
+    }
+
+    /// one of the action's bindings went down this frame
+    pub fn just_pressed(&self, state: &InputState, action: &str) -> bool {
+        self.bindings(action).iter().any(|b| match b {
+            Binding::GamepadAxis { .. } => self.binding_edges(state, b) == (false, true),
+            b => state.just_pressed(*b),
+        })
+    }
+
+    /// one of the action's bindings went up this frame, and none are down
+    pub fn just_released(&self, state: &InputState, action: &str) -> bool {
+        !self.is_down(state, action)
+            && self.bindings(action).iter().any(|b| match b {
+                Binding::GamepadAxis { .. } => self.binding_edges(state, b) == (true, false),
+                b => state.just_released(*b),
+            })
+    }
+
+    /// value of the positive action minus the negative one, in [-1, 1]
+    pub fn axis(&self, state: &InputState, negative: &str, positive: &str) -> f32 {
+        self.value(state, positive) - self.value(state, negative)
+    }
+
+    /// bindings under "actions", as a table of action names to arrays of
+    /// binding strings. axis responses are under "axes", by axis name. kept
+    /// apart so any action name can be saved
+    pub fn to_toml(&self) -> toml::Table {
+        let actions: toml::Table = self
+            .bindings
+            .iter()
+            .map(|(action, bindings)| {
+                let bindings: Vec<toml::Value> = bindings
+                    .iter()
+                    .map(|b| toml::Value::String(b.to_config_string()))
+                    .collect();
+                (action.clone(), toml::Value::Array(bindings))
+            })
+            .collect();
+        let mut table = toml::Table::new();
+        table.insert("actions".into(), toml::Value::Table(actions));
+        if !self.responses.is_empty() {
+            let axes: toml::Table = self
+                .responses
+                .iter()
+                .map(|(axis, response)| (axis.string(), toml::Value::Table(response.to_toml())))
+                .collect();
+            table.insert("axes".into(), toml::Value::Table(axes));
+        }
+        table
+    }
+
+    /// replaces all bindings and axis responses
+    pub fn load_toml(&mut self, table: &toml::Table) -> Result<(), String> {
+        let mut bindings: BTreeMap<String, Vec<Binding>> = BTreeMap::new();
+        let mut responses: HashMap<Axis, AxisResponse> = HashMap::new();
+        let empty = toml::Table::new();
+        let section = |key: &str| match table.get(key) {
+            None => Ok(&empty),
+            Some(toml::Value::Table(section)) => Ok(section),
+            Some(_) => Err(format!("\"{}\" isn't a table", key)),
+        };
+        for (name, response) in section("axes")?.iter() {
+            let axis =
+                Axis::from_string(name).ok_or_else(|| format!("invalid axis \"{}\"", name))?;
+            let response = response
+                .as_table()
+                .ok_or_else(|| format!("response for \"{}\" isn't a table", name))?;
+            responses.insert(axis, AxisResponse::from_toml(response)?);
+        }
+        for (action, value) in section("actions")?.iter() {
+            let array = value
+                .as_array()
+                .ok_or_else(|| format!("bindings for \"{}\" aren't an array", action))?;
+            let parsed = array
+                .iter()
+                .map(|v| {
+                    v.as_str()
+                        .ok_or_else(|| format!("binding for \"{}\" isn't a string", action))
+                        .and_then(Binding::from_config_string)
+                })
+                .collect::<Result<Vec<Binding>, String>>()?;
+            bindings.insert(action.clone(), parsed);
+        }
+        self.bindings = bindings;
+        self.responses = responses;
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = toml::to_string(&self.to_toml()).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents).map_err(|e| e.to_string())
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        self.load_toml(&table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mouse(button: MouseButton, down: bool) -> EngineEvent {
+        let position = sdl2::rect::FPoint::new(0., 0.);
+        if down {
+            EngineEvent::MouseButtonDown {
+                window: None,
+                button,
+                clicks: 1,
+                position,
+            }
+        } else {
+            EngineEvent::MouseButtonUp {
+                window: None,
+                button,
+                clicks: 1,
+                position,
+            }
+        }
+    }
+
+    #[test]
+    fn test_edges() {
+        let mut state = InputState::new();
+        state.update(&[mouse(MouseButton::Left, true)]);
+        assert!(state.mouse_down(MouseButton::Left));
+        assert!(state.mouse_just_pressed(MouseButton::Left));
+        state.update(&[]);
+        assert!(state.mouse_down(MouseButton::Left));
+        assert!(!state.mouse_just_pressed(MouseButton::Left));
+        state.update(&[mouse(MouseButton::Left, false)]);
+        assert!(!state.mouse_down(MouseButton::Left));
+        assert!(state.mouse_just_released(MouseButton::Left));
+        // tapped within a single frame
+        state.update(&[
+            mouse(MouseButton::Left, true),
+            mouse(MouseButton::Left, false),
+        ]);
+        assert!(state.mouse_just_pressed(MouseButton::Left));
+        assert!(state.mouse_just_released(MouseButton::Left));
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut input = InputMap::new();
+        let mut state = InputState::new();
+        input.bind("fire", Binding::Mouse(MouseButton::Left));
+        input.bind("fire", Binding::Mouse(MouseButton::Right));
+        input.bind("aim", Binding::Mouse(MouseButton::Middle));
+        assert!(!input.is_down(&state, "fire"));
+        state.update(&[mouse(MouseButton::Right, true)]);
+        assert!(input.is_down(&state, "fire"));
+        assert!(input.just_pressed(&state, "fire"));
+        assert_eq!(input.axis(&state, "aim", "fire"), 1.);
+        state.update(&[mouse(MouseButton::Left, true)]);
+        state.update(&[mouse(MouseButton::Right, false)]);
+        // still held by the other binding
+        assert!(!input.just_released(&state, "fire"));
+        state.update(&[mouse(MouseButton::Left, false)]);
+        assert!(input.just_released(&state, "fire"));
+
+        input.rebind("fire", vec![Binding::Mouse(MouseButton::X1)]);
+        assert_eq!(input.bindings("fire"), [Binding::Mouse(MouseButton::X1)]);
+        let mut loaded = InputMap::new();
+        loaded.load_toml(&input.to_toml()).unwrap();
+        assert_eq!(loaded.bindings("fire"), input.bindings("fire"));
+        assert_eq!(loaded.bindings("aim"), input.bindings("aim"));
+    }
+
+    #[test]
+    fn test_section_names() {
+        // actions can share names with the saved map's sections
+        let mut input = InputMap::new();
+        input.bind("axes", Binding::Mouse(MouseButton::Left));
+        input.bind("actions", Binding::Mouse(MouseButton::Right));
+        let mut saved = input.to_toml();
+        assert_eq!(saved.keys().collect::<Vec<_>>(), ["actions"]);
+        saved.insert("axes".into(), toml::Value::Table(Default::default()));
+        let mut loaded = InputMap::new();
+        loaded.load_toml(&saved).unwrap();
+        assert_eq!(loaded.actions().collect::<Vec<_>>(), ["actions", "axes"]);
+        assert_eq!(loaded.bindings("axes"), [Binding::Mouse(MouseButton::Left)]);
+
+        saved.insert("axes".into(), toml::Value::Array(Vec::new()));
+        assert!(loaded.load_toml(&saved).is_err());
+    }
+
+    #[test]
+    fn test_axis_response() {
+        let response = AxisResponse {
+            dead_zone: 0.2,
+            saturation: 0.8,
+            exponent: 2.,
+        };
+        assert_eq!(response.apply(0.1), 0.);
+        assert_eq!(response.apply(-0.9), -1.);
+        assert!((response.apply(0.5) - 0.25).abs() < 1e-6);
+        assert!((response.apply(-0.5) + 0.25).abs() < 1e-6);
+        assert_eq!(AxisResponse::default().apply(0.3), 0.3);
+
+        let loaded = AxisResponse::from_toml(&response.to_toml()).unwrap();
+        assert!((loaded.dead_zone - 0.2).abs() < 1e-6 && loaded.exponent == 2.);
+        let mut partial = toml::Table::new();
+        partial.insert("dead_zone".into(), 0.into());
+        assert_eq!(
+            AxisResponse::from_toml(&partial),
+            Ok(AxisResponse::default())
+        );
+        partial.insert("curve".into(), 1.into());
+        assert!(AxisResponse::from_toml(&partial).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    time::Duration,
+};
+
+use super::input::{InputMap, InputState};
+
+/// the actions of an input map as of a frame in which one was pressed
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferedInput {
+    /// e.g. EngineClock::total
+    pub time: Duration,
+    pub down: BTreeSet<String>,
+    pub pressed: BTreeSet<String>,
+}
+
+/// a move made of steps, e.g. down, down + forward, forward + punch. each
+/// step is the actions held together, with one of them newly pressed
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputSequence {
+    pub steps: Vec<Vec<String>>,
+    /// from the first step to the last
+    pub window: Duration,
+}
+
+impl InputSequence {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            steps: Default::default(),
+            window,
+        }
+    }
+
+    /// chain a step
+    pub fn then(mut self, actions: &[&str]) -> Self {
+        self.steps
+            .push(actions.iter().map(|a| a.to_string()).collect());
+        self
+    }
+}
+
+impl BufferedInput {
+    fn matches(&self, step: &[String]) -> bool {
+        step.iter().all(|action| self.down.contains(action))
+            && step.iter().any(|action| self.pressed.contains(action))
+    }
+}
+
+/// a player's recent action presses, for fighting game style move detection
+#[derive(Debug, Clone)]
+pub struct InputBuffer {
+    entries: VecDeque<BufferedInput>,
+    /// the oldest entries are dropped past this many
+    pub capacity: usize,
+    /// the latest entry was pushed since the last record, so a move is only
+    /// matched in the frame it's completed
+    fresh: bool,
+}
+
+impl InputBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            fresh: false,
+        }
+    }
+
+    /// call once per frame, after the input state is updated. only frames in
+    /// which an action was pressed are kept
+    pub fn record(&mut self, map: &InputMap, state: &InputState, time: Duration) {
+        let pressed: BTreeSet<String> = map
+            .actions()
+            .filter(|action| map.just_pressed(state, action))
+            .map(str::to_owned)
+            .collect();
+        if pressed.is_empty() {
+            self.fresh = false;
+            return;
+        }
+        let down = map
+            .actions()
+            .filter(|action| map.is_down(state, action))
+            .map(str::to_owned)
+            .collect();
+        self.push(BufferedInput {
+            time,
+            down,
+            pressed,
+        });
+    }
+
+    pub fn push(&mut self, input: BufferedInput) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(input);
+        self.fresh = true;
+    }
+
+    /// oldest first
+    pub fn entries(&self) -> &VecDeque<BufferedInput> {
+        &self.entries
+    }
+
+    /// true if the sequence was completed by an entry this frame, with its
+    /// steps in order (other presses may come between them) and within its
+    /// window
+    pub fn matches(&self, sequence: &InputSequence) -> bool {
+        if !self.fresh {
+            return false;
+        }
+        let Some((last_step, steps)) = sequence.steps.split_last() else {
+            return false;
+        };
+        let Some(last) = self.entries.back() else {
+            return false;
+        };
+        if !last.matches(last_step) {
+            return false;
+        }
+        // match each earlier step to the latest entry that fits, which leaves
+        // the most room in the window
+        let mut before = self.entries.len() - 1;
+        for step in steps.iter().rev() {
+            match self.entries.range(..before).rposition(|e| e.matches(step)) {
+                Some(i) => before = i,
+                None => return false,
+            }
+        }
+        last.time.saturating_sub(self.entries[before].time) <= sequence.window
+    }
+
+    /// e.g. once a move was performed, so it isn't matched again
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.fresh = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::mouse::MouseButton;
+
+    use super::*;
+    use crate::core::{events::EngineEvent, input::Binding};
+
+    fn mouse(button: MouseButton, down: bool) -> EngineEvent {
+        let position = sdl2::rect::FPoint::new(0., 0.);
+        match down {
+            true => EngineEvent::MouseButtonDown {
+                window: None,
+                button,
+                clicks: 1,
+                position,
+            },
+            false => EngineEvent::MouseButtonUp {
+                window: None,
+                button,
+                clicks: 1,
+                position,
+            },
+        }
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut map = InputMap::new();
+        map.bind("down", Binding::Mouse(MouseButton::Left));
+        map.bind("forward", Binding::Mouse(MouseButton::Right));
+        map.bind("punch", Binding::Mouse(MouseButton::Middle));
+        let fireball = InputSequence::new(Duration::from_millis(300))
+            .then(&["down"])
+            .then(&["down", "forward"])
+            .then(&["forward", "punch"]);
+
+        let mut state = InputState::new();
+        let mut buffer = InputBuffer::new(8);
+        let mut frame = |events: &[EngineEvent], ms: u64, buffer: &mut InputBuffer| {
+            state.update(events);
+            buffer.record(&map, &state, Duration::from_millis(ms));
+            buffer.matches(&fireball)
+        };
+        assert!(!frame(&[mouse(MouseButton::Left, true)], 0, &mut buffer));
+        assert!(!frame(&[mouse(MouseButton::Right, true)], 50, &mut buffer));
+        assert!(!frame(&[mouse(MouseButton::Left, false)], 100, &mut buffer));
+        assert!(frame(&[mouse(MouseButton::Middle, true)], 150, &mut buffer));
+        // only in the frame it was completed
+        assert!(!frame(&[], 155, &mut buffer));
+        // the last step has to be the latest press
+        assert!(!frame(&[mouse(MouseButton::Left, true)], 160, &mut buffer));
+        assert_eq!(buffer.entries().len(), 4);
+
+        // too slow
+        buffer.clear();
+        let mut state = InputState::new();
+        for (events, ms) in [
+            (mouse(MouseButton::Left, true), 0),
+            (mouse(MouseButton::Right, true), 200),
+            (mouse(MouseButton::Left, false), 250),
+            (mouse(MouseButton::Middle, true), 400),
+        ] {
+            state.update(&[events]);
+            buffer.record(&map, &state, Duration::from_millis(ms));
+        }
+        assert!(!buffer.matches(&fireball));
+    }
+}
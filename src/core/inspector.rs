@@ -0,0 +1,205 @@
+#[cfg(feature = "ttf")]
+use std::ffi::CString;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "ttf")]
+use sdl2::rect::Rect;
+
+#[cfg(feature = "ttf")]
+use super::system::ChimericSystem;
+use super::world::World;
+
+/// a summary of a single entity, for debugging
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntitySummary {
+    pub id: u64,
+    pub type_name: &'static str,
+    pub tags: Vec<&'static str>,
+    /// center of the aabb, if the entity has one
+    pub position: Option<(f32, f32)>,
+}
+
+fn escape_json(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn json_f32(v: f32, out: &mut String) {
+    if v.is_finite() {
+        let _ = write!(out, "{}", v);
+    } else {
+        out.push_str("null");
+    }
+}
+
+/// the number of entities of each type
+pub fn type_counts(entities: &[EntitySummary]) -> BTreeMap<&'static str, usize> {
+    let mut counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for e in entities {
+        *counts.entry(e.type_name).or_default() += 1;
+    }
+    counts
+}
+
+/// a json document with the tick, the entity count, the count per type, and
+/// a summary of each entity
+pub fn to_json(tick: u64, entities: &[EntitySummary]) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "{{\"tick\":{},\"entity_count\":{}",
+        tick,
+        entities.len()
+    );
+    out.push_str(",\"type_counts\":{");
+    for (i, (name, count)) in type_counts(entities).iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        escape_json(name, &mut out);
+        let _ = write!(out, ":{}", count);
+    }
+    out.push_str("},\"entities\":[");
+    for (i, e) in entities.iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{{\"id\":{},\"type\":", e.id);
+        escape_json(e.type_name, &mut out);
+        out.push_str(",\"tags\":[");
+        for (j, tag) in e.tags.iter().enumerate() {
+            if j != 0 {
+                out.push(',');
+            }
+            escape_json(tag, &mut out);
+        }
+        out.push_str("],\"position\":");
+        match e.position {
+            Some((x, y)) => {
+                out.push('[');
+                json_f32(x, &mut out);
+                out.push(',');
+                json_f32(y, &mut out);
+                out.push(']');
+            }
+            None => out.push_str("null"),
+        }
+        out.push('}');
+    }
+    out.push_str("]}");
+    out
+}
+
+/// on screen overlay listing the entity count and the most common entity
+/// types. a steadily growing count usually means something never dies
+pub struct Inspector {
+    pub visible: bool,
+    pub window_name: String,
+    pub font_file: PathBuf,
+    pub point_size: u16,
+    /// the number of types listed, most common first
+    pub max_types: usize,
+}
+
+impl Inspector {
+    pub fn new(window_name: &str, font_file: &Path, point_size: u16) -> Self {
+        Self {
+            visible: false,
+            window_name: window_name.to_owned(),
+            font_file: font_file.to_owned(),
+            point_size,
+            max_types: 10,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// the text draw shows
+    pub fn lines(&self, world: &World) -> Vec<String> {
+        let summaries = world.entity_summaries();
+        let mut counts: Vec<(&'static str, usize)> = type_counts(&summaries).into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let mut lines = vec![
+            format!("tick: {}", world.tick()),
+            format!("entities: {}", summaries.len()),
+        ];
+        lines.extend(
+            counts
+                .iter()
+                .take(self.max_types)
+                .map(|(name, count)| format!("{}: {}", name, count)),
+        );
+        lines
+    }
+
+    /// draw in the top left of the window, if visible
+    #[cfg(feature = "ttf")]
+    pub fn draw(&self, world: &World, system: &mut ChimericSystem) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+        let mut y = 0;
+        for line in self.lines(world) {
+            let text = CString::new(line).map_err(|e| e.to_string())?;
+            let (texture, canvas) = system.text(
+                &self.window_name,
+                &self.font_file,
+                self.point_size,
+                &text,
+                None,
+            )?;
+            let query = texture.query();
+            canvas.copy(texture, None, Rect::new(0, y, query.width, query.height))?;
+            y += query.height as i32;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let entities = vec![
+            EntitySummary {
+                id: 0,
+                type_name: "game::Bullet",
+                tags: vec!["enemy"],
+                position: Some((1.5, 2.)),
+            },
+            EntitySummary {
+                id: 3,
+                type_name: "game::Bullet",
+                tags: vec![],
+                position: None,
+            },
+        ];
+        assert_eq!(
+            to_json(7, &entities),
+            "{\"tick\":7,\"entity_count\":2,\"type_counts\":{\"game::Bullet\":2},\"entities\":[\
+             {\"id\":0,\"type\":\"game::Bullet\",\"tags\":[\"enemy\"],\"position\":[1.5,2]},\
+             {\"id\":3,\"type\":\"game::Bullet\",\"tags\":[],\"position\":null}]}"
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use super::vfs::Vfs;
+
+/// how a modifier changes its stat. a stat is its base plus every flat
+/// modifier, scaled by one plus every percent modifier, then by every
+/// multiply modifier
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModifierKind {
+    Flat,
+    Percent,
+    Multiply,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatModifier {
+    pub stat: String,
+    pub kind: ModifierKind,
+    pub value: f32,
+    /// modifiers of the same kind in the same group don't stack: only the
+    /// strongest applies (e.g. two haste potions)
+    pub group: Option<String>,
+}
+
+impl StatModifier {
+    pub fn new(stat: &str, kind: ModifierKind, value: f32) -> Self {
+        Self {
+            stat: stat.to_owned(),
+            kind,
+            value,
+            group: None,
+        }
+    }
+
+    pub fn in_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_owned());
+        self
+    }
+
+    /// { stat = "attack", flat = 5 }, with percent or multiply instead of
+    /// flat, and optionally a group
+    fn read_toml(value: &toml::Value) -> Result<Self, String> {
+        let table = value.as_table().ok_or("a modifier must be a table")?;
+        let stat = table
+            .get("stat")
+            .and_then(toml::Value::as_str)
+            .ok_or("a modifier needs a stat")?;
+        let mut kinds = [
+            ("flat", ModifierKind::Flat),
+            ("percent", ModifierKind::Percent),
+            ("multiply", ModifierKind::Multiply),
+        ]
+        .into_iter()
+        .filter_map(|(key, kind)| table.get(key).map(|v| (kind, v)));
+        let (kind, value) = match (kinds.next(), kinds.next()) {
+            (Some(kind), None) => kind,
+            _ => {
+                return Err(format!(
+                    "modifier of \"{stat}\" needs one of flat, percent or multiply"
+                ))
+            }
+        };
+        let value = value
+            .as_float()
+            .or(value.as_integer().map(|v| v as f64))
+            .ok_or_else(|| format!("modifier of \"{stat}\" must be a number"))?;
+        let mut modifier = Self::new(stat, kind, value as f32);
+        if let Some(group) = table.get("group") {
+            modifier.group = Some(group.as_str().ok_or("group must be a string")?.to_owned());
+        }
+        Ok(modifier)
+    }
+}
+
+/// base values plus modifiers from named sources (e.g. "equipment", or a
+/// buff), which can be replaced or removed as a whole
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    base: BTreeMap<String, f32>,
+    sources: BTreeMap<String, Vec<StatModifier>>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_base(&mut self, stat: &str, value: f32) {
+        self.base.insert(stat.to_owned(), value);
+    }
+
+    /// zero if unset
+    pub fn base(&self, stat: &str) -> f32 {
+        self.base.get(stat).copied().unwrap_or(0.)
+    }
+
+    pub fn add_modifier(&mut self, source: &str, modifier: StatModifier) {
+        self.sources
+            .entry(source.to_owned())
+            .or_default()
+            .push(modifier);
+    }
+
+    /// replace every modifier from the source
+    pub fn set_source(&mut self, source: &str, modifiers: Vec<StatModifier>) {
+        self.sources.insert(source.to_owned(), modifiers);
+    }
+
+    pub fn remove_source(&mut self, source: &str) {
+        self.sources.remove(source);
+    }
+
+    /// the stat with its modifiers applied
+    pub fn get(&self, stat: &str) -> f32 {
+        let mut applied: Vec<&StatModifier> = Vec::new();
+        // strongest in each group
+        let mut grouped: BTreeMap<(&str, u8), &StatModifier> = BTreeMap::new();
+        for modifier in self.sources.values().flatten().filter(|m| m.stat == stat) {
+            let Some(group) = &modifier.group else {
+                applied.push(modifier);
+                continue;
+            };
+            let strength = |m: &StatModifier| match m.kind {
+                ModifierKind::Multiply => (m.value - 1.).abs(),
+                _ => m.value.abs(),
+            };
+            grouped
+                .entry((group.as_str(), modifier.kind as u8))
+                .and_modify(|m| {
+                    if strength(modifier) > strength(m) {
+                        *m = modifier;
+                    }
+                })
+                .or_insert(modifier);
+        }
+        applied.extend(grouped.into_values());
+
+        let sum = |kind: ModifierKind| -> f32 {
+            applied
+                .iter()
+                .filter(|m| m.kind == kind)
+                .map(|m| m.value)
+                .sum()
+        };
+        let multiply: f32 = applied
+            .iter()
+            .filter(|m| m.kind == ModifierKind::Multiply)
+            .map(|m| m.value)
+            .product();
+        (self.base(stat) + sum(ModifierKind::Flat)) * (1. + sum(ModifierKind::Percent)) * multiply
+    }
+
+    /// the base values, for saving. modifiers are left to their sources
+    pub fn to_toml(&self) -> toml::Table {
+        self.base
+            .iter()
+            .map(|(stat, value)| (stat.clone(), toml::Value::Float(*value as f64)))
+            .collect()
+    }
+
+    pub fn load_toml(&mut self, table: &toml::Table) -> Result<(), String> {
+        for (stat, value) in table {
+            let value = value
+                .as_float()
+                .or(value.as_integer().map(|v| v as f64))
+                .ok_or_else(|| format!("stat \"{stat}\" must be a number"))?;
+            self.set_base(stat, value as f32);
+        }
+        Ok(())
+    }
+}
+
+/// an item's definition, shared by every stack of it
+#[derive(Debug, Clone)]
+pub struct ItemDef {
+    pub max_stack: u32,
+    /// the equipment slot it goes in, if it can be equipped
+    pub slot: Option<String>,
+    /// applied while equipped
+    pub modifiers: Vec<StatModifier>,
+    /// everything else, e.g. its display name, icon and description
+    pub params: toml::Table,
+}
+
+/// item definitions, loaded from data like prefabs. each top level table is
+/// an item:
+///
+/// ```toml
+/// [iron_sword]
+/// name = "Iron Sword"
+/// slot = "weapon"
+/// modifiers = [{ stat = "attack", flat = 5 }]
+///
+/// [potion]
+/// max_stack = 20
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ItemRegistry {
+    items: HashMap<String, ItemDef>,
+}
+
+impl ItemRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add or replace an item
+    pub fn insert(&mut self, item_name: &str, item: ItemDef) {
+        self.items.insert(item_name.into(), item);
+    }
+
+    pub fn get(&self, item_name: &str) -> Option<&ItemDef> {
+        self.items.get(item_name)
+    }
+
+    /// in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.items.keys().map(String::as_str)
+    }
+
+    /// parse items from toml content. items that already exist are replaced
+    pub fn load_str(&mut self, content: &str) -> Result<(), String> {
+        let table: toml::Table = content
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        for (item_name, value) in table {
+            let mut params = match value {
+                toml::Value::Table(params) => params,
+                _ => return Err(format!("item \"{item_name}\" must be a table")),
+            };
+            let max_stack = match params.remove("max_stack") {
+                None => 1,
+                Some(toml::Value::Integer(n)) if n >= 1 && n <= u32::MAX as i64 => n as u32,
+                Some(_) => {
+                    return Err(format!(
+                        "item \"{item_name}\" max_stack must be a positive integer"
+                    ))
+                }
+            };
+            let slot = match params.remove("slot") {
+                None => None,
+                Some(toml::Value::String(slot)) => Some(slot),
+                Some(_) => return Err(format!("item \"{item_name}\" slot must be a string")),
+            };
+            let modifiers = match params.remove("modifiers") {
+                None => Vec::new(),
+                Some(toml::Value::Array(modifiers)) => modifiers
+                    .iter()
+                    .map(StatModifier::read_toml)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("item \"{item_name}\": {e}"))?,
+                Some(_) => return Err(format!("item \"{item_name}\" modifiers must be an array")),
+            };
+            self.insert(
+                &item_name,
+                ItemDef {
+                    max_stack,
+                    slot,
+                    modifiers,
+                    params,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// load items from a toml file. items that already exist are replaced
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        self.load_str(&content)
+    }
+
+    /// as load_file, resolving paths through the vfs
+    pub fn load_from(&mut self, assets: &Vfs, path: &Path) -> Result<(), String> {
+        self.load_str(&assets.read_to_string(path)?)
+    }
+
+    fn item(&self, item_name: &str) -> Result<&ItemDef, String> {
+        self.get(item_name)
+            .ok_or_else(|| format!("item \"{item_name}\" does not exist"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+/// a fixed number of slots holding stacks of items, plus equipped items
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    /// equipment slot to item
+    equipped: BTreeMap<String, String>,
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: vec![None; capacity],
+            equipped: BTreeMap::new(),
+        }
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn count(&self, item_name: &str) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|s| s.item == item_name)
+            .map(|s| s.count)
+            .sum()
+    }
+
+    /// top up existing stacks, then fill empty slots. returns how many
+    /// didn't fit
+    pub fn add(
+        &mut self,
+        items: &ItemRegistry,
+        item_name: &str,
+        count: u32,
+    ) -> Result<u32, String> {
+        let max_stack = items.item(item_name)?.max_stack;
+        let mut left = count;
+        for stack in self.slots.iter_mut().flatten() {
+            if stack.item == item_name && stack.count < max_stack {
+                let moved = left.min(max_stack - stack.count);
+                stack.count += moved;
+                left -= moved;
+            }
+        }
+        for slot in self.slots.iter_mut().filter(|s| s.is_none()) {
+            if left == 0 {
+                break;
+            }
+            let moved = left.min(max_stack);
+            *slot = Some(ItemStack {
+                item: item_name.to_owned(),
+                count: moved,
+            });
+            left -= moved;
+        }
+        Ok(left)
+    }
+
+    /// take from the last stacks first. fails, removing nothing, if there
+    /// aren't enough
+    pub fn remove(&mut self, item_name: &str, count: u32) -> Result<(), String> {
+        if self.count(item_name) < count {
+            return Err(format!("not enough \"{item_name}\""));
+        }
+        let mut left = count;
+        for slot in self.slots.iter_mut().rev() {
+            if let Some(stack) = slot.as_mut().filter(|s| s.item == item_name) {
+                let taken = left.min(stack.count);
+                stack.count -= taken;
+                left -= taken;
+                if stack.count == 0 {
+                    *slot = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// swap two slots, or merge the first into the second if they hold the
+    /// same item
+    pub fn move_slot(
+        &mut self,
+        items: &ItemRegistry,
+        from: usize,
+        to: usize,
+    ) -> Result<(), String> {
+        if from >= self.slots.len() || to >= self.slots.len() {
+            return Err("no such slot".into());
+        }
+        if from == to {
+            return Ok(());
+        }
+        if let (Some(a), Some(b)) = (&self.slots[from], &self.slots[to]) {
+            if a.item == b.item {
+                let max_stack = items.item(&a.item)?.max_stack;
+                let moved = a.count.min(max_stack.saturating_sub(b.count));
+                let remaining = a.count - moved;
+                self.slots[to].as_mut().unwrap().count += moved;
+                match remaining {
+                    0 => self.slots[from] = None,
+                    n => self.slots[from].as_mut().unwrap().count = n,
+                }
+                return Ok(());
+            }
+        }
+        self.slots.swap(from, to);
+        Ok(())
+    }
+
+    pub fn equipped(&self, slot: &str) -> Option<&str> {
+        self.equipped.get(slot).map(String::as_str)
+    }
+
+    /// move one of the item from the inventory into its equipment slot,
+    /// moving what was there back
+    pub fn equip(&mut self, items: &ItemRegistry, item_name: &str) -> Result<(), String> {
+        let slot = items
+            .item(item_name)?
+            .slot
+            .clone()
+            .ok_or_else(|| format!("item \"{item_name}\" can't be equipped"))?;
+        self.remove(item_name, 1)?;
+        if let Some(previous) = self.equipped.remove(&slot) {
+            if self.add(items, &previous, 1)? != 0 {
+                // undo
+                self.add(items, item_name, 1)?;
+                self.equipped.insert(slot, previous);
+                return Err("no room in the inventory".into());
+            }
+        }
+        self.equipped.insert(slot, item_name.to_owned());
+        Ok(())
+    }
+
+    /// move the item in the equipment slot back into the inventory
+    pub fn unequip(&mut self, items: &ItemRegistry, slot: &str) -> Result<(), String> {
+        let Some(item_name) = self.equipped.get(slot) else {
+            return Ok(());
+        };
+        if self.add(items, &item_name.clone(), 1)? != 0 {
+            return Err("no room in the inventory".into());
+        }
+        self.equipped.remove(slot);
+        Ok(())
+    }
+
+    /// the modifiers of every equipped item, e.g. for
+    /// stats.set_source("equipment", ...)
+    pub fn equipment_modifiers(&self, items: &ItemRegistry) -> Vec<StatModifier> {
+        self.equipped
+            .values()
+            .filter_map(|item_name| items.get(item_name))
+            .flat_map(|item| item.modifiers.iter().cloned())
+            .collect()
+    }
+
+    /// for saving. empty slots are empty tables
+    pub fn to_toml(&self) -> toml::Table {
+        let slots = self
+            .slots
+            .iter()
+            .map(|slot| {
+                let mut table = toml::Table::new();
+                if let Some(stack) = slot {
+                    table.insert("item".into(), stack.item.clone().into());
+                    table.insert("count".into(), (stack.count as i64).into());
+                }
+                toml::Value::Table(table)
+            })
+            .collect::<Vec<_>>();
+        let equipped = self
+            .equipped
+            .iter()
+            .map(|(slot, item)| (slot.clone(), toml::Value::String(item.clone())))
+            .collect::<toml::Table>();
+        let mut table = toml::Table::new();
+        table.insert("slots".into(), slots.into());
+        table.insert("equipped".into(), equipped.into());
+        table
+    }
+
+    pub fn from_toml(table: &toml::Table) -> Result<Self, String> {
+        let slots = table
+            .get("slots")
+            .and_then(toml::Value::as_array)
+            .ok_or("inventory needs slots")?
+            .iter()
+            .map(|slot| {
+                let slot = slot.as_table().ok_or("a slot must be a table")?;
+                if slot.is_empty() {
+                    return Ok(None);
+                }
+                let item = slot.get("item").and_then(toml::Value::as_str);
+                let count = slot.get("count").and_then(toml::Value::as_integer);
+                match (item, count) {
+                    (Some(item), Some(count)) if count > 0 && count <= u32::MAX as i64 => {
+                        Ok(Some(ItemStack {
+                            item: item.to_owned(),
+                            count: count as u32,
+                        }))
+                    }
+                    _ => Err("a slot needs an item and a positive count".to_owned()),
+                }
+            })
+            .collect::<Result<_, String>>()?;
+        let mut equipped = BTreeMap::new();
+        if let Some(table) = table.get("equipped").and_then(toml::Value::as_table) {
+            for (slot, item) in table {
+                let item = item.as_str().ok_or("equipped items must be strings")?;
+                equipped.insert(slot.clone(), item.to_owned());
+            }
+        }
+        Ok(Self { slots, equipped })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inventory() {
+        let mut items = ItemRegistry::new();
+        items
+            .load_str(
+                r#"
+                [sword]
+                name = "Sword"
+                slot = "weapon"
+                modifiers = [{ stat = "attack", flat = 5 }, { stat = "speed", percent = -0.1 }]
+
+                [axe]
+                slot = "weapon"
+                modifiers = [{ stat = "attack", multiply = 2 }]
+
+                [potion]
+                max_stack = 3
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            items.get("sword").unwrap().params["name"].as_str(),
+            Some("Sword")
+        );
+        assert!(items
+            .load_str("[bad]\nmodifiers = [{ stat = \"a\" }]")
+            .is_err());
+
+        let mut inventory = Inventory::new(3);
+        assert_eq!(inventory.add(&items, "potion", 4).unwrap(), 0);
+        assert_eq!(inventory.add(&items, "sword", 1).unwrap(), 0);
+        assert_eq!(inventory.add(&items, "axe", 1).unwrap(), 1);
+        assert!(inventory.add(&items, "shield", 1).is_err());
+        assert_eq!(inventory.count("potion"), 4);
+        assert!(inventory.remove("potion", 5).is_err());
+        inventory.remove("potion", 1).unwrap();
+        // the second stack is used up first
+        assert_eq!(inventory.slots()[1], None);
+        inventory.add(&items, "axe", 1).unwrap();
+
+        let mut stats = Stats::new();
+        stats.set_base("attack", 10.);
+        stats.set_base("speed", 2.);
+        inventory.equip(&items, "sword").unwrap();
+        stats.set_source("equipment", inventory.equipment_modifiers(&items));
+        assert_eq!(stats.get("attack"), 15.);
+        assert_eq!(stats.get("speed"), 1.8);
+        // swapped, with the sword put back
+        inventory.equip(&items, "axe").unwrap();
+        assert_eq!(inventory.equipped("weapon"), Some("axe"));
+        assert_eq!(inventory.count("sword"), 1);
+        stats.set_source("equipment", inventory.equipment_modifiers(&items));
+        assert_eq!(stats.get("attack"), 20.);
+
+        // only the strongest haste applies
+        stats.add_modifier(
+            "potion",
+            StatModifier::new("speed", ModifierKind::Flat, 1.).in_group("haste"),
+        );
+        stats.add_modifier(
+            "spell",
+            StatModifier::new("speed", ModifierKind::Flat, 3.).in_group("haste"),
+        );
+        assert_eq!(stats.get("speed"), 5.);
+        stats.remove_source("spell");
+        assert_eq!(stats.get("speed"), 3.);
+
+        let saved = Inventory::from_toml(&inventory.to_toml()).unwrap();
+        assert_eq!(saved, inventory);
+        let mut loaded = Stats::new();
+        loaded.load_toml(&stats.to_toml()).unwrap();
+        assert_eq!(loaded.get("attack"), 10.);
+    }
+}
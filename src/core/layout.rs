@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use sdl2::rect::Rect;
+
+/// space kept between a rect and the edges of its area
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Margins {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Margins {
+    pub fn all(margin: i32) -> Self {
+        Self {
+            left: margin,
+            top: margin,
+            right: margin,
+            bottom: margin,
+        }
+    }
+
+    /// the area with the margins taken off. at least 1x1
+    pub fn inset(&self, area: Rect) -> Rect {
+        let width = area.width() as i32 - self.left - self.right;
+        let height = area.height() as i32 - self.top - self.bottom;
+        Rect::new(
+            area.x() + self.left,
+            area.y() + self.top,
+            width.max(1) as u32,
+            height.max(1) as u32,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// where a length goes in the space available
+fn place(start: i32, available: u32, length: u32, align: Align) -> i32 {
+    let extra = available as i32 - length as i32;
+    match align {
+        Align::Start | Align::Stretch => start,
+        Align::Center => start + extra / 2,
+        Align::End => start + extra,
+    }
+}
+
+/// the rect with its position and size multiplied, e.g. by a ui scale
+pub fn scale_rect(rect: Rect, scale: f32) -> Rect {
+    Rect::new(
+        (rect.x() as f32 * scale).round() as i32,
+        (rect.y() as f32 * scale).round() as i32,
+        ((rect.width() as f32 * scale).round() as u32).max(1),
+        ((rect.height() as f32 * scale).round() as u32).max(1),
+    )
+}
+
+/// a rect of the size placed against the anchor's edges of the area, inside
+/// the margins
+pub fn anchored(area: Rect, size: (u32, u32), anchor: Anchor, margins: Margins) -> Rect {
+    let inner = margins.inset(area);
+    let (horizontal, vertical) = match anchor {
+        Anchor::TopLeft => (Align::Start, Align::Start),
+        Anchor::Top => (Align::Center, Align::Start),
+        Anchor::TopRight => (Align::End, Align::Start),
+        Anchor::Left => (Align::Start, Align::Center),
+        Anchor::Center => (Align::Center, Align::Center),
+        Anchor::Right => (Align::End, Align::Center),
+        Anchor::BottomLeft => (Align::Start, Align::End),
+        Anchor::Bottom => (Align::Center, Align::End),
+        Anchor::BottomRight => (Align::End, Align::End),
+    };
+    Rect::new(
+        place(inner.x(), inner.width(), size.0, horizontal),
+        place(inner.y(), inner.height(), size.1, vertical),
+        size.0,
+        size.1,
+    )
+}
+
+/// a child's length along a flex container's main axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Fixed(u32),
+    /// a share, by weight, of what the fixed sizes and gaps leave
+    Grow(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Row,
+    Column,
+}
+
+/// placement along the cross axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    Stretch,
+}
+
+/// splits an area into a row or column of rects, like css flexbox
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flex {
+    pub direction: Direction,
+    /// between children
+    pub gap: u32,
+    pub padding: Margins,
+    pub align: Align,
+    /// the children's cross axis length, unless stretched
+    pub cross_size: u32,
+}
+
+impl Flex {
+    pub fn row() -> Self {
+        Self {
+            direction: Direction::Row,
+            gap: 0,
+            padding: Default::default(),
+            align: Align::Stretch,
+            cross_size: 0,
+        }
+    }
+
+    pub fn column() -> Self {
+        Self {
+            direction: Direction::Column,
+            ..Self::row()
+        }
+    }
+
+    /// a rect per child, in order
+    pub fn layout(&self, area: Rect, children: &[Size]) -> Vec<Rect> {
+        let inner = self.padding.inset(area);
+        let (main_start, main_length, cross_start, cross_length) = match self.direction {
+            Direction::Row => (inner.x(), inner.width(), inner.y(), inner.height()),
+            Direction::Column => (inner.y(), inner.height(), inner.x(), inner.width()),
+        };
+        let gaps = self.gap * children.len().saturating_sub(1) as u32;
+        let fixed: u32 = children
+            .iter()
+            .map(|size| match size {
+                Size::Fixed(length) => *length,
+                Size::Grow(_) => 0,
+            })
+            .sum();
+        let total_weight: u32 = children
+            .iter()
+            .map(|size| match size {
+                Size::Fixed(_) => 0,
+                Size::Grow(weight) => *weight,
+            })
+            .sum();
+        let mut remaining = main_length.saturating_sub(fixed + gaps);
+        let mut remaining_weight = total_weight;
+
+        let cross = match self.align {
+            Align::Stretch => cross_length,
+            _ => self.cross_size,
+        };
+        let cross_position = place(cross_start, cross_length, cross, self.align);
+        let mut position = main_start;
+        children
+            .iter()
+            .map(|size| {
+                let length = match *size {
+                    Size::Fixed(length) => length,
+                    // the last share gets what's left from rounding
+                    Size::Grow(weight) if weight == remaining_weight => {
+                        remaining_weight = 0;
+                        std::mem::take(&mut remaining)
+                    }
+                    Size::Grow(weight) => {
+                        let length = (remaining as u64 * weight as u64
+                            / remaining_weight.max(1) as u64)
+                            as u32;
+                        remaining -= length;
+                        remaining_weight -= weight;
+                        length
+                    }
+                };
+                let rect = match self.direction {
+                    Direction::Row => Rect::new(position, cross_position, length, cross),
+                    Direction::Column => Rect::new(cross_position, position, cross, length),
+                };
+                position += (length + self.gap) as i32;
+                rect
+            })
+            .collect()
+    }
+}
+
+type Build = Box<dyn FnMut(Rect, &mut HashMap<String, Rect>)>;
+
+/// named rects computed from a window's drawable size (its logical size, if
+/// set; see ChimericSystem::drawable_size). build only runs again once the
+/// size changes, e.g. on resize
+///
+/// with a ui scale, build is given the area in unscaled units (the size
+/// divided by the scale) and the rects it makes are scaled back up, so a
+/// layout written for 1x grows with the scale
+pub struct Layout {
+    size: Option<(u32, u32)>,
+    rects: HashMap<String, Rect>,
+    build: Build,
+    scale: f32,
+}
+
+impl Layout {
+    /// build is given the whole area and fills in the named rects
+    pub fn new(build: impl FnMut(Rect, &mut HashMap<String, Rect>) + 'static) -> Self {
+        Self {
+            size: None,
+            rects: Default::default(),
+            build: Box::new(build),
+            scale: 1.,
+        }
+    }
+
+    /// e.g. from Config::ui_scale. the rects are recomputed on the next update
+    pub fn set_scale(&mut self, scale: f32) {
+        let scale = if scale > 0. { scale } else { 1. };
+        if scale != self.scale {
+            self.scale = scale;
+            self.size = None;
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// call every frame. true if the rects were recomputed
+    pub fn update(&mut self, size: (u32, u32)) -> bool {
+        if self.size == Some(size) {
+            return false;
+        }
+        self.size = Some(size);
+        self.rects.clear();
+        let area = Rect::new(
+            0,
+            0,
+            ((size.0 as f32 / self.scale) as u32).max(1),
+            ((size.1 as f32 / self.scale) as u32).max(1),
+        );
+        (self.build)(area, &mut self.rects);
+        if self.scale != 1. {
+            for rect in self.rects.values_mut() {
+                *rect = scale_rect(*rect, self.scale);
+            }
+        }
+        true
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rect> {
+        self.rects.get(name).copied()
+    }
+
+    /// the size the rects were computed for
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchored() {
+        let area = Rect::new(0, 0, 200, 100);
+        let margins = Margins::all(10);
+        assert_eq!(
+            anchored(area, (50, 20), Anchor::TopLeft, margins),
+            Rect::new(10, 10, 50, 20)
+        );
+        assert_eq!(
+            anchored(area, (50, 20), Anchor::Center, margins),
+            Rect::new(75, 40, 50, 20)
+        );
+        assert_eq!(
+            anchored(area, (50, 20), Anchor::BottomRight, margins),
+            Rect::new(140, 70, 50, 20)
+        );
+    }
+
+    #[test]
+    fn test_flex() {
+        let mut row = Flex::row();
+        row.gap = 10;
+        let rects = row.layout(
+            Rect::new(0, 0, 200, 30),
+            &[Size::Fixed(40), Size::Grow(1), Size::Grow(2)],
+        );
+        // 200 - 40 - 20 leaves 140, split 1:2
+        assert_eq!(
+            rects,
+            vec![
+                Rect::new(0, 0, 40, 30),
+                Rect::new(50, 0, 46, 30),
+                Rect::new(106, 0, 94, 30),
+            ]
+        );
+
+        let mut column = Flex::column();
+        column.padding = Margins::all(5);
+        column.align = Align::Center;
+        column.cross_size = 20;
+        let rects = column.layout(Rect::new(0, 0, 100, 100), &[Size::Fixed(30), Size::Grow(1)]);
+        assert_eq!(
+            rects,
+            vec![Rect::new(40, 5, 20, 30), Rect::new(40, 35, 20, 60)]
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut layout = Layout::new(|area, rects| {
+            rects.insert(
+                "menu".to_owned(),
+                anchored(area, (100, 50), Anchor::Center, Default::default()),
+            );
+        });
+        assert!(layout.update((400, 300)));
+        assert!(!layout.update((400, 300)));
+        assert_eq!(layout.get("menu"), Some(Rect::new(150, 125, 100, 50)));
+        assert!(layout.update((200, 100)));
+        assert_eq!(layout.get("menu"), Some(Rect::new(50, 25, 100, 50)));
+        assert_eq!(layout.get("missing"), None);
+
+        // built at half the size, then doubled
+        layout.set_scale(2.);
+        assert!(layout.update((200, 100)));
+        assert_eq!(layout.get("menu"), Some(Rect::new(0, 0, 200, 100)));
+    }
+}
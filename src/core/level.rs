@@ -0,0 +1,611 @@
+use std::{collections::BTreeMap, path::Path, path::PathBuf};
+
+use sdl2::rect::{FPoint, FRect};
+
+use super::{
+    entity::EntityId,
+    prefab::parse_override,
+    save::write_atomic,
+    tiled::{MapObject, ObjectLayer, ObjectShape, TiledMap, Tileset},
+    tilemap::TileLayer,
+    vfs::Vfs,
+    world::World,
+};
+
+const MAGIC: &[u8; 4] = b"CHLV";
+/// the layout of the file, which the engine decodes. distinct from the
+/// game's schema version, which its LevelMigrations upgrade
+const FORMAT_VERSION: u32 = 1;
+
+/// levels saved with this extension are native, rather than tmx
+pub const LEVEL_EXTENSION: &str = "level";
+/// in a TiledMap, objects of this class are colliders. objects with any
+/// other class are entities, whose class is the prefab
+pub const COLLIDER_CLASS: &str = "collider";
+/// the object layers a level's entities and colliders are put in when it's
+/// converted to a TiledMap
+pub const ENTITY_LAYER: &str = "entities";
+pub const COLLIDER_LAYER: &str = "colliders";
+
+/// an entity placed in a level
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelEntity {
+    pub prefab: String,
+    pub position: FPoint,
+    /// merged over the prefab's parameters, along with x and y
+    pub overrides: toml::Table,
+}
+
+/// a level: tile layers, the entities placed in it, static colliders, and
+/// metadata such as its name or music
+#[derive(Debug, Clone)]
+pub struct Level {
+    /// the game's schema version the level was written with. set by
+    /// LevelLoader
+    pub schema_version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tilesets: Vec<Tileset>,
+    pub tile_layers: Vec<(String, TileLayer)>,
+    pub entities: Vec<LevelEntity>,
+    pub colliders: Vec<ObjectShape>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// upgrades a level from the previous schema version, in place, e.g.
+/// renaming a prefab or filling in a new override
+pub type LevelMigration = fn(&mut Level) -> Result<(), String>;
+
+/// the text of an override in a map object's properties. strings are left
+/// bare unless they'd be read back as something else
+fn override_text(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) if parse_override(s) == *value => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl Level {
+    /// an empty level
+    pub fn new(width: u32, height: u32, tile_width: u32, tile_height: u32) -> Self {
+        Self {
+            schema_version: 1,
+            width,
+            height,
+            tile_width,
+            tile_height,
+            tilesets: Vec::new(),
+            tile_layers: Vec::new(),
+            entities: Vec::new(),
+            colliders: Vec::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// objects with the collider class become colliders, and other objects
+    /// with a class become entities, with their properties as overrides.
+    /// the map's properties are the metadata
+    pub fn from_tiled(map: &TiledMap) -> Self {
+        let mut level = Self::new(map.width, map.height, map.tile_width, map.tile_height);
+        level.tilesets = map.tilesets.clone();
+        level.tile_layers = map.tile_layers.clone();
+        level.metadata = map.properties.clone();
+        for object in map.objects() {
+            if object.class == COLLIDER_CLASS {
+                level.colliders.push(object.shape.clone());
+            } else if !object.class.is_empty() {
+                level.entities.push(LevelEntity {
+                    prefab: object.class.clone(),
+                    position: object.position(),
+                    overrides: object
+                        .properties
+                        .iter()
+                        .map(|(name, value)| (name.clone(), parse_override(value)))
+                        .collect(),
+                });
+            }
+        }
+        level
+    }
+
+    /// e.g. to edit in the Editor or save as tmx
+    pub fn to_tiled(&self) -> TiledMap {
+        let mut map = TiledMap::new(self.width, self.height, self.tile_width, self.tile_height);
+        map.tilesets = self.tilesets.clone();
+        map.tile_layers = self.tile_layers.clone();
+        map.properties = self.metadata.clone();
+        let mut id = 0;
+        let mut object = |class: &str, shape: ObjectShape, properties| {
+            id += 1;
+            MapObject {
+                id,
+                name: String::new(),
+                class: class.to_owned(),
+                shape,
+                tile: None,
+                properties,
+            }
+        };
+        let entities = self.entities.iter().map(|entity| {
+            let properties = entity
+                .overrides
+                .iter()
+                .map(|(name, value)| (name.clone(), override_text(value)))
+                .collect();
+            object(
+                &entity.prefab,
+                ObjectShape::Point(entity.position),
+                properties,
+            )
+        });
+        let entities = ObjectLayer {
+            name: ENTITY_LAYER.to_owned(),
+            objects: entities.collect(),
+        };
+        let colliders = ObjectLayer {
+            name: COLLIDER_LAYER.to_owned(),
+            objects: self
+                .colliders
+                .iter()
+                .map(|shape| object(COLLIDER_CLASS, shape.clone(), Default::default()))
+                .collect(),
+        };
+        map.object_layers = vec![entities, colliders];
+        map
+    }
+
+    pub fn tile_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.tile_layers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, layer)| layer)
+    }
+
+    /// spawn every entity from its prefab, with its position as the x and y
+    /// overrides
+    pub fn spawn_entities(&self, world: &mut World) -> Result<Vec<EntityId>, String> {
+        self.entities
+            .iter()
+            .map(|entity| {
+                let mut overrides = entity.overrides.clone();
+                let (x, y) = (entity.position.x(), entity.position.y());
+                overrides.insert("x".to_owned(), toml::Value::Float(x as f64));
+                overrides.insert("y".to_owned(), toml::Value::Float(y as f64));
+                world.spawn_prefab(&entity.prefab, &overrides)
+            })
+            .collect()
+    }
+
+    /// magic + u32(format version) + u32(schema version)
+    /// + u32(width) + u32(height) + u32(tile width) + u32(tile height)
+    /// + u32(count) + metadata (str key + str value)
+    /// + u32(count) + tilesets (str name + u32 first gid, tile width, tile
+    ///   height, tile count, columns, spacing, margin + str image)
+    /// + u32(count) + tile layers (str name + u32 width + u32 height + u32
+    ///   per tile)
+    /// + u32(count) + entities (str prefab + f32 x + f32 y + str overrides as
+    ///   toml)
+    /// + u32(count) + colliders (shape)
+    ///
+    /// where str is u32(len) + utf-8, and a shape is u8(kind) + f32 x, y, w,
+    /// h for rects (0) and ellipses (1), f32 x, y for points (2), or u32(count)
+    /// + f32 x, y per point for polygons (3) and polylines (4)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+        out.extend_from_slice(MAGIC);
+        for v in [
+            FORMAT_VERSION,
+            self.schema_version,
+            self.width,
+            self.height,
+            self.tile_width,
+            self.tile_height,
+        ] {
+            put_u32(&mut out, v);
+        }
+        put_u32(&mut out, self.metadata.len() as u32);
+        for (key, value) in &self.metadata {
+            put_str(&mut out, key);
+            put_str(&mut out, value);
+        }
+        put_u32(&mut out, self.tilesets.len() as u32);
+        for tileset in &self.tilesets {
+            put_str(&mut out, &tileset.name);
+            for v in [
+                tileset.first_gid,
+                tileset.tile_width,
+                tileset.tile_height,
+                tileset.tile_count,
+                tileset.columns,
+                tileset.spacing,
+                tileset.margin,
+            ] {
+                put_u32(&mut out, v);
+            }
+            put_str(
+                &mut out,
+                &tileset.image.to_string_lossy().replace('\\', "/"),
+            );
+        }
+        put_u32(&mut out, self.tile_layers.len() as u32);
+        for (name, layer) in &self.tile_layers {
+            put_str(&mut out, name);
+            put_u32(&mut out, layer.width());
+            put_u32(&mut out, layer.height());
+            for tile in layer.tiles() {
+                put_u32(&mut out, *tile);
+            }
+        }
+        put_u32(&mut out, self.entities.len() as u32);
+        for entity in &self.entities {
+            put_str(&mut out, &entity.prefab);
+            put_f32(&mut out, entity.position.x());
+            put_f32(&mut out, entity.position.y());
+            put_str(&mut out, &entity.overrides.to_string());
+        }
+        put_u32(&mut out, self.colliders.len() as u32);
+        for shape in &self.colliders {
+            put_shape(&mut out, shape);
+        }
+        out
+    }
+
+    /// decode without migrating; see LevelLoader
+    pub fn decode(mut data: &[u8]) -> Result<Self, String> {
+        let data = &mut data;
+        if take::<4>(data)? != *MAGIC {
+            return Err("not a level".into());
+        }
+        let format = take_u32(data)?;
+        // decoding of older formats goes here as the layout changes
+        if format != FORMAT_VERSION {
+            return Err(format!("unsupported level format {}", format));
+        }
+        let mut level = Self::new(0, 0, 0, 0);
+        level.schema_version = take_u32(data)?;
+        level.width = take_u32(data)?;
+        level.height = take_u32(data)?;
+        level.tile_width = take_u32(data)?;
+        level.tile_height = take_u32(data)?;
+        for _ in 0..take_u32(data)? {
+            let key = take_str(data)?;
+            level.metadata.insert(key, take_str(data)?);
+        }
+        for _ in 0..take_u32(data)? {
+            level.tilesets.push(Tileset {
+                name: take_str(data)?,
+                first_gid: take_u32(data)?,
+                tile_width: take_u32(data)?,
+                tile_height: take_u32(data)?,
+                tile_count: take_u32(data)?,
+                columns: take_u32(data)?,
+                spacing: take_u32(data)?,
+                margin: take_u32(data)?,
+                image: PathBuf::from(take_str(data)?),
+            });
+        }
+        for _ in 0..take_u32(data)? {
+            let name = take_str(data)?;
+            let (width, height) = (take_u32(data)?, take_u32(data)?);
+            let len = width as usize * height as usize;
+            if data.len() / 4 < len {
+                return Err("level is truncated".into());
+            }
+            let tiles = (0..len).map(|_| take_u32(data)).collect::<Result<_, _>>()?;
+            let layer = TileLayer::from_tiles(
+                width,
+                height,
+                level.tile_width as f32,
+                level.tile_height as f32,
+                tiles,
+            )?;
+            level.tile_layers.push((name, layer));
+        }
+        for _ in 0..take_u32(data)? {
+            let prefab = take_str(data)?;
+            let position = FPoint::new(take_f32(data)?, take_f32(data)?);
+            let overrides = take_str(data)?
+                .parse()
+                .map_err(|e: toml::de::Error| format!("entity \"{}\": {}", prefab, e))?;
+            level.entities.push(LevelEntity {
+                prefab,
+                position,
+                overrides,
+            });
+        }
+        for _ in 0..take_u32(data)? {
+            level.colliders.push(take_shape(data)?);
+        }
+        if !data.is_empty() {
+            return Err("level has trailing data".into());
+        }
+        Ok(level)
+    }
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_f32(out: &mut Vec<u8>, v: f32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_u32(out, s.len() as u32);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn put_shape(out: &mut Vec<u8>, shape: &ObjectShape) {
+    match shape {
+        ObjectShape::Rect(r) | ObjectShape::Ellipse(r) => {
+            out.push(if let ObjectShape::Rect(_) = shape {
+                0
+            } else {
+                1
+            });
+            for v in [r.x(), r.y(), r.width(), r.height()] {
+                put_f32(out, v);
+            }
+        }
+        ObjectShape::Point(p) => {
+            out.push(2);
+            put_f32(out, p.x());
+            put_f32(out, p.y());
+        }
+        ObjectShape::Polygon(points) | ObjectShape::Polyline(points) => {
+            out.push(if let ObjectShape::Polygon(_) = shape {
+                3
+            } else {
+                4
+            });
+            put_u32(out, points.len() as u32);
+            for p in points {
+                put_f32(out, p.x());
+                put_f32(out, p.y());
+            }
+        }
+    }
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("level is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(take(data)?))
+}
+
+fn take_f32(data: &mut &[u8]) -> Result<f32, String> {
+    Ok(f32::from_le_bytes(take(data)?))
+}
+
+fn take_str(data: &mut &[u8]) -> Result<String, String> {
+    let len = take_u32(data)? as usize;
+    if data.len() < len {
+        return Err("level is truncated".into());
+    }
+    let (s, rest) = data.split_at(len);
+    *data = rest;
+    String::from_utf8(s.to_vec()).map_err(|e| e.to_string())
+}
+
+fn take_shape(data: &mut &[u8]) -> Result<ObjectShape, String> {
+    let kind = take::<1>(data)?[0];
+    let mut points = || -> Result<Vec<FPoint>, String> {
+        let count = take_u32(data)? as usize;
+        if data.len() / 8 < count {
+            return Err("level is truncated".into());
+        }
+        (0..count)
+            .map(|_| Ok(FPoint::new(take_f32(data)?, take_f32(data)?)))
+            .collect()
+    };
+    Ok(match kind {
+        0 | 1 => {
+            let rect = FRect::new(
+                take_f32(data)?,
+                take_f32(data)?,
+                take_f32(data)?,
+                take_f32(data)?,
+            );
+            if kind == 0 {
+                ObjectShape::Rect(rect)
+            } else {
+                ObjectShape::Ellipse(rect)
+            }
+        }
+        2 => ObjectShape::Point(FPoint::new(take_f32(data)?, take_f32(data)?)),
+        3 => ObjectShape::Polygon(points()?),
+        4 => ObjectShape::Polyline(points()?),
+        _ => return Err(format!("unknown collider shape {}", kind)),
+    })
+}
+
+/// reads and writes the game's levels, upgrading those saved with an older
+/// schema version through its migrations, like ConfigFile does for config
+#[derive(Debug, Clone, Default)]
+pub struct LevelLoader {
+    /// migrations[i] upgrades version i + 1 to i + 2. the current version is
+    /// one more than the number of migrations
+    migrations: Vec<LevelMigration>,
+}
+
+impl LevelLoader {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add a migration, bumping the current version by one
+    pub fn with_migration(mut self, migration: LevelMigration) -> Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+
+    /// upgrade a level to the current version
+    pub fn migrate(&self, level: &mut Level) -> Result<(), String> {
+        let from = level.schema_version;
+        if from == 0 || from > self.version() {
+            return Err(format!(
+                "level version {} isn't supported (current is {})",
+                from,
+                self.version()
+            ));
+        }
+        for migration in &self.migrations[from as usize - 1..] {
+            migration(level)?;
+        }
+        level.schema_version = self.version();
+        Ok(())
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<Level, String> {
+        let mut level = Level::decode(data)?;
+        self.migrate(&mut level)?;
+        Ok(level)
+    }
+
+    /// the level as the current version
+    pub fn encode(&self, level: &Level) -> Vec<u8> {
+        let mut level = level.clone();
+        level.schema_version = self.version();
+        level.encode()
+    }
+
+    /// tileset images are relative to the level
+    pub fn load_file(&self, path: &Path) -> Result<Level, String> {
+        self.load_from(&Vfs::new(), path)
+    }
+
+    /// as load_file, resolving the level through the vfs
+    pub fn load_from(&self, assets: &Vfs, path: &Path) -> Result<Level, String> {
+        let data = assets.read(path)?;
+        let mut level = self
+            .decode(&data)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for tileset in level.tilesets.iter_mut() {
+            tileset.image = dir.join(&tileset.image);
+        }
+        Ok(level)
+    }
+
+    /// tileset images are written relative to the level
+    pub fn save_file(&self, path: &Path, level: &Level) -> Result<(), String> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut level = level.clone();
+        for tileset in level.tilesets.iter_mut() {
+            if let Ok(relative) = tileset.image.strip_prefix(dir) {
+                tileset.image = relative.to_owned();
+            }
+        }
+        write_atomic(path, &self.encode(&level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::entity::Entity;
+
+    struct Crate;
+
+    impl Entity for Crate {
+        fn update(&mut self, _world: &mut World) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn level() -> Level {
+        let mut level = Level::new(2, 2, 16, 16);
+        level
+            .metadata
+            .insert("music".to_owned(), "cave.ogg".to_owned());
+        let layer = TileLayer::from_tiles(2, 2, 16., 16., vec![1, 0, 2, 3]).unwrap();
+        level.tile_layers.push(("ground".to_owned(), layer));
+        level.entities.push(LevelEntity {
+            prefab: "box".to_owned(),
+            position: FPoint::new(8., 24.),
+            overrides: "label = \"true\"\nhealth = 3".parse().unwrap(),
+        });
+        level
+            .colliders
+            .push(ObjectShape::Rect(FRect::new(0., 0., 32., 8.)));
+        level.colliders.push(ObjectShape::Polygon(vec![
+            FPoint::new(0., 0.),
+            FPoint::new(5., 0.),
+            FPoint::new(0., 5.),
+        ]));
+        level
+    }
+
+    #[test]
+    fn test_level() {
+        let level = level();
+        let encoded = level.encode();
+        let decoded = Level::decode(&encoded).unwrap();
+        assert_eq!(decoded.metadata, level.metadata);
+        assert_eq!(decoded.entities, level.entities);
+        assert_eq!(decoded.colliders, level.colliders);
+        assert_eq!(decoded.tile_layer("ground").unwrap().tiles(), &[1, 0, 2, 3]);
+        assert!(Level::decode(&encoded[..encoded.len() - 1]).is_err());
+
+        // round trips through tiled, keeping string overrides strings
+        let map = level.to_tiled();
+        assert_eq!(
+            map.objects().next().unwrap().properties["label"],
+            "\"true\""
+        );
+        let from_tiled = Level::from_tiled(&map);
+        assert_eq!(from_tiled.entities, level.entities);
+        assert_eq!(from_tiled.colliders, level.colliders);
+
+        let mut world = World::with_seed(0);
+        world.prefabs.register_constructor("box", |params| {
+            assert_eq!(params["x"].as_float(), Some(8.));
+            Ok(Box::new(Crate))
+        });
+        world.prefabs.load_str("[box]").unwrap();
+        assert_eq!(level.spawn_entities(&mut world).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_migration() {
+        // version 1 levels are migrated through both
+        let old = LevelLoader::new().encode(&level());
+        let loader = LevelLoader::new()
+            .with_migration(|level| {
+                for entity in level.entities.iter_mut() {
+                    if entity.prefab == "box" {
+                        entity.prefab = "crate".to_owned();
+                    }
+                }
+                Ok(())
+            })
+            .with_migration(|level| {
+                level
+                    .metadata
+                    .insert("weather".to_owned(), "rain".to_owned());
+                Ok(())
+            });
+        let migrated = loader.decode(&old).unwrap();
+        assert_eq!(migrated.schema_version, 3);
+        assert_eq!(migrated.entities[0].prefab, "crate");
+        assert_eq!(migrated.metadata["weather"], "rain");
+
+        // newer than the game knows
+        let new = loader.encode(&migrated);
+        assert!(LevelLoader::new().decode(&new).is_err());
+    }
+}
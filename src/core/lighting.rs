@@ -0,0 +1,241 @@
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect},
+};
+
+use super::{camera::Camera2D, collision::Collider, renderer::Vertex};
+
+/// sides of the polygon a round collider casts shadows as
+const CIRCLE_OCCLUDER_SIDES: usize = 12;
+
+/// a round light. positions are in the window's logical coordinates; use
+/// Camera2D::world_to_screen for lights in the world
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub position: FPoint,
+    pub radius: f32,
+    pub color: Color,
+    /// scales the color. overlapping lights add up
+    pub intensity: f32,
+    /// the size of the light's source. bigger sources cast wider penumbras;
+    /// zero casts hard shadows
+    pub softness: f32,
+}
+
+impl Light {
+    pub fn new(position: FPoint, radius: f32, color: Color) -> Self {
+        Self {
+            position,
+            radius,
+            color,
+            intensity: 1.,
+            softness: 8.,
+        }
+    }
+
+    /// the area the light reaches
+    pub fn bounds(&self) -> FRect {
+        FRect::new(
+            self.position.x() - self.radius,
+            self.position.y() - self.radius,
+            self.radius * 2.,
+            self.radius * 2.,
+        )
+    }
+
+    /// the color, scaled by the intensity
+    pub fn tint(&self) -> Color {
+        let scale = |c: u8| (c as f32 * self.intensity).round().clamp(0., 255.) as u8;
+        Color::RGB(
+            scale(self.color.r),
+            scale(self.color.g),
+            scale(self.color.b),
+        )
+    }
+}
+
+/// darkness over a window's frame, with lights added onto it. it's drawn to
+/// its own target (ambient plus each light, added) and multiplied over the
+/// frame before the other post processing effects. lights and occluders are
+/// kept until cleared, so either clear and re-add them each frame or move them
+/// in place
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lighting {
+    /// what unlit areas are multiplied by. black is full darkness, white is
+    /// no change
+    pub ambient: Color,
+    pub lights: Vec<Light>,
+    /// segments which cast shadows from every light, in the same coordinates
+    /// as the lights
+    pub occluders: Vec<(FPoint, FPoint)>,
+}
+
+impl Lighting {
+    pub fn new(ambient: Color) -> Self {
+        Self {
+            ambient,
+            lights: Default::default(),
+            occluders: Default::default(),
+        }
+    }
+
+    pub fn add(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn add_occluder(&mut self, a: FPoint, b: FPoint) {
+        self.occluders.push((a, b));
+    }
+
+    /// the outline of a collider in the world, moved onto the screen
+    pub fn add_collider(&mut self, collider: &Collider, camera: &Camera2D) {
+        let outline: Vec<FPoint> = match *collider {
+            Collider::Aabb(rect) => vec![
+                FPoint::new(rect.left(), rect.top()),
+                FPoint::new(rect.right(), rect.top()),
+                FPoint::new(rect.right(), rect.bottom()),
+                FPoint::new(rect.left(), rect.bottom()),
+            ],
+            Collider::Circle { center, radius } => (0..CIRCLE_OCCLUDER_SIDES)
+                .map(|i| {
+                    let angle = i as f32 / CIRCLE_OCCLUDER_SIDES as f32 * std::f32::consts::TAU;
+                    FPoint::new(
+                        center.x() + angle.cos() * radius,
+                        center.y() + angle.sin() * radius,
+                    )
+                })
+                .collect(),
+        };
+        for (i, a) in outline.iter().enumerate() {
+            let b = outline[(i + 1) % outline.len()];
+            self.add_occluder(camera.world_to_screen(*a), camera.world_to_screen(b));
+        }
+    }
+
+    /// removes the lights and occluders
+    pub fn clear(&mut self) {
+        self.lights.clear();
+        self.occluders.clear();
+    }
+}
+
+/// black geometry for the shadows the occluders cast from the light, drawn
+/// over the light before it's added to the light map. each occluder casts an
+/// opaque quad away from the light, and a wedge at each end which fades out
+/// over the penumbra. occluders out of the light's reach are skipped
+pub fn shadow_mesh(light: &Light, occluders: &[(FPoint, FPoint)]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    let opaque = Color::RGBA(0, 0, 0, 255);
+    let clear = Color::RGBA(0, 0, 0, 0);
+    let bounds = light.bounds();
+    // far enough to leave the light's reach from anywhere inside it
+    let reach = light.radius * 2.;
+    let l = light.position;
+    for &(a, b) in occluders {
+        let outside = a.x().max(b.x()) < bounds.left()
+            || a.x().min(b.x()) > bounds.right()
+            || a.y().max(b.y()) < bounds.top()
+            || a.y().min(b.y()) > bounds.bottom();
+        if outside {
+            continue;
+        }
+        let away = |p: FPoint| -> Option<(f32, f32)> {
+            let (dx, dy) = (p.x() - l.x(), p.y() - l.y());
+            let length = dx.hypot(dy);
+            (length > f32::EPSILON).then(|| (dx / length, dy / length))
+        };
+        let (Some(da), Some(db)) = (away(a), away(b)) else {
+            continue;
+        };
+        let project =
+            |p: FPoint, d: (f32, f32)| FPoint::new(p.x() + d.0 * reach, p.y() + d.1 * reach);
+
+        let start = vertices.len() as u32;
+        for position in [a, b, project(b, db), project(a, da)] {
+            vertices.push(Vertex {
+                position,
+                color: opaque,
+            });
+        }
+        indices.extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+
+        if light.softness <= 0. {
+            continue;
+        }
+        for (end, other, d) in [(a, b, da), (b, a, db)] {
+            // perpendicular to the shadow's edge, pointing out of the shadow
+            let mut n = (-d.1, d.0);
+            if n.0 * (other.x() - end.x()) + n.1 * (other.y() - end.y()) > 0. {
+                n = (-n.0, -n.1);
+            }
+            // the edge lit by the far side of the light's source
+            let (ox, oy) = (
+                end.x() - l.x() + n.0 * light.softness,
+                end.y() - l.y() + n.1 * light.softness,
+            );
+            let length = ox.hypot(oy);
+            let outer = (ox / length, oy / length);
+            let start = vertices.len() as u32;
+            for (position, color) in [
+                (end, opaque),
+                (project(end, d), opaque),
+                (project(end, outer), clear),
+            ] {
+                vertices.push(Vertex { position, color });
+            }
+            indices.extend([start, start + 1, start + 2]);
+        }
+    }
+    (vertices, indices)
+}
+
+/// a light's brightness in [0, 1] at a distance normalized to its radius.
+/// smooth, and zero from the radius on
+pub fn falloff(distance: f32) -> f32 {
+    let d = distance.clamp(0., 1.);
+    (1. - d * d).powi(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light() {
+        assert_eq!(falloff(0.), 1.);
+        assert_eq!(falloff(1.), 0.);
+        assert_eq!(falloff(2.), 0.);
+        assert!(falloff(0.3) > falloff(0.6));
+
+        let mut light = Light::new(FPoint::new(10., 20.), 5., Color::RGB(200, 100, 0));
+        assert_eq!(light.bounds(), FRect::new(5., 15., 10., 10.));
+        light.intensity = 1.5;
+        assert_eq!(light.tint(), Color::RGB(255, 150, 0));
+    }
+
+    #[test]
+    fn test_shadow_mesh() {
+        let mut light = Light::new(FPoint::new(0., 0.), 100., Color::WHITE);
+        let wall = (FPoint::new(10., -5.), FPoint::new(10., 5.));
+        let far_away = (FPoint::new(500., 0.), FPoint::new(500., 10.));
+        let (vertices, indices) = shadow_mesh(&light, &[wall, far_away]);
+        // a quad and two penumbra wedges, for the wall only
+        assert_eq!(vertices.len(), 10);
+        assert_eq!(indices.len(), 12);
+        // the shadow is cast away from the light
+        assert!(vertices[2..4].iter().all(|v| v.position.x() > 100.));
+        // the wedges fade out past the shadow's edges
+        assert_eq!(vertices[6].color.a, 0);
+        assert!(vertices[6].position.y() < vertices[5].position.y());
+        assert!(vertices[9].position.y() > vertices[8].position.y());
+
+        light.softness = 0.;
+        assert_eq!(shadow_mesh(&light, &[wall]).0.len(), 4);
+
+        let mut lighting = Lighting::new(Color::BLACK);
+        let camera = Camera2D::new(100., 100.);
+        lighting.add_collider(&Collider::Aabb(FRect::new(0., 0., 10., 10.)), &camera);
+        assert_eq!(lighting.occluders.len(), 4);
+    }
+}
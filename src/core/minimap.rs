@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect},
+};
+
+use super::{
+    decode::DecodedImage,
+    renderer::DrawParams,
+    system::ChimericSystem,
+    tilemap::{LayerVersion, TileLayer, EMPTY_TILE},
+    world::World,
+};
+
+/// a tile layer drawn at a pixel per tile. the image is cached like a
+/// texture, under its own path, and the pixels of changed tiles are redrawn
+/// when the layer changes
+#[derive(Debug, Clone)]
+pub struct Minimap {
+    /// where the image is cached. shouldn't be a real file
+    path: PathBuf,
+    /// per tile index. empty tiles are transparent
+    pub colors: HashMap<u32, Color>,
+    /// for tiles without a color
+    pub default_color: Color,
+    /// side length of markers, in pixels of the destination
+    pub marker_size: f32,
+    /// the layer the cached image is of
+    version: Option<LayerVersion>,
+    image: Option<DecodedImage>,
+}
+
+impl Minimap {
+    pub fn new(path: &Path, default_color: Color) -> Self {
+        Self {
+            path: path.to_owned(),
+            colors: Default::default(),
+            default_color,
+            marker_size: 3.,
+            version: None,
+            image: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn color(&self, tile: u32) -> Color {
+        match tile {
+            EMPTY_TILE => Color::RGBA(0, 0, 0, 0),
+            tile => *self.colors.get(&tile).unwrap_or(&self.default_color),
+        }
+    }
+
+    /// the layer at a pixel per tile
+    pub fn render(&self, layer: &TileLayer) -> DecodedImage {
+        let rgba = layer
+            .tiles()
+            .iter()
+            .flat_map(|tile| {
+                let color = self.color(*tile);
+                [color.r, color.g, color.b, color.a]
+            })
+            .collect();
+        DecodedImage {
+            width: layer.width(),
+            height: layer.height(),
+            rgba,
+        }
+    }
+
+    /// the image with the pixels of the tiles changed since redrawn, or
+    /// rendered again if they can't be
+    fn patched(&mut self, layer: &TileLayer) -> DecodedImage {
+        let changes = self.version.and_then(|v| layer.changes_since(v));
+        match (self.image.take(), changes) {
+            (Some(mut image), Some(changes))
+                if (image.width, image.height) == (layer.width(), layer.height()) =>
+            {
+                for (x, y) in changes {
+                    let color = self.color(layer.get(x, y).unwrap_or(EMPTY_TILE));
+                    let i = (y as usize * image.width as usize + x as usize) * 4;
+                    image.rgba[i..i + 4].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+                }
+                image
+            }
+            _ => self.render(layer),
+        }
+    }
+
+    /// cache the image again if the layer changed since
+    pub fn update(
+        &mut self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        layer: &TileLayer,
+    ) -> Result<(), String> {
+        if self.version == Some(layer.version()) {
+            return Ok(());
+        }
+        let image = self.patched(layer);
+        system.insert_image(window_name, &self.path, &image)?;
+        self.image = Some(image);
+        self.version = Some(layer.version());
+        Ok(())
+    }
+
+    /// redraw the image on the next update, e.g. after changing colors
+    pub fn invalidate(&mut self) {
+        self.version = None;
+    }
+
+    /// draw the layer stretched over dst, with a marker at each world
+    /// position
+    pub fn draw(
+        &mut self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        layer: &TileLayer,
+        dst: FRect,
+        markers: &[(FPoint, Color)],
+    ) -> Result<(), String> {
+        if layer.width() == 0 || layer.height() == 0 {
+            return Ok(());
+        }
+        self.update(system, window_name, layer)?;
+        let draw = [DrawParams::new(None, Some(dst))];
+        if system.draw(window_name, &self.path, &draw).is_err() {
+            // evicted from the texture cache
+            self.invalidate();
+            self.update(system, window_name, layer)?;
+            system.draw(window_name, &self.path, &draw)?;
+        }
+        for (position, color) in markers.iter() {
+            let point = to_minimap(*position, layer, dst);
+            let half = self.marker_size / 2.;
+            let rect = FRect::new(
+                point.x() - half,
+                point.y() - half,
+                self.marker_size,
+                self.marker_size,
+            );
+            system.fill_rect(window_name, rect, *color)?;
+        }
+        Ok(())
+    }
+}
+
+/// where a world position on the layer lands on a minimap drawn over dst
+pub fn to_minimap(position: FPoint, layer: &TileLayer, dst: FRect) -> FPoint {
+    let layer_width = layer.width() as f32 * layer.tile_width();
+    let layer_height = layer.height() as f32 * layer.tile_height();
+    FPoint::new(
+        dst.x() + position.x() / layer_width * dst.width(),
+        dst.y() + position.y() / layer_height * dst.height(),
+    )
+}
+
+/// a marker at the center of each entity with an aabb, colored by its tags.
+/// entities the color function gives None for are left off
+pub fn world_markers(
+    world: &World,
+    color: impl Fn(&[&'static str]) -> Option<Color>,
+) -> Vec<(FPoint, Color)> {
+    world
+        .entity_summaries()
+        .iter()
+        .filter_map(|summary| {
+            let (x, y) = summary.position?;
+            Some((FPoint::new(x, y), color(&summary.tags)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimap() {
+        let mut layer = TileLayer::new(2, 2, 16., 16.);
+        layer.set(0, 0, 1);
+        layer.set(1, 1, 2);
+        let mut minimap = Minimap::new(Path::new("minimap"), Color::RGB(1, 2, 3));
+        minimap.colors.insert(1, Color::RGB(255, 0, 0));
+        let image = minimap.render(&layer);
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(
+            image.rgba,
+            [255, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 255]
+        );
+
+        // changes bump the revision, and setting the same tile doesn't
+        let revision = layer.revision();
+        layer.set(1, 1, 2);
+        assert_eq!(layer.revision(), revision);
+        layer.set(1, 0, 2);
+        assert!(layer.revision() != revision);
+
+        let dst = FRect::new(100., 100., 64., 64.);
+        assert_eq!(
+            to_minimap(FPoint::new(16., 8.), &layer, dst),
+            FPoint::new(132., 116.)
+        );
+    }
+
+    #[test]
+    fn test_explode_minimap() {
+        let mut layer = TileLayer::new(3, 2, 16., 16.);
+        layer.set(0, 0, 1);
+        layer.set(1, 0, 1);
+        let mut minimap = Minimap::new(Path::new("minimap"), Color::RGB(1, 2, 3));
+        minimap.image = Some(minimap.render(&layer));
+        minimap.version = Some(layer.version());
+        // only the changed pixels are redrawn
+        layer.explode(FPoint::new(8., 8.), 4.);
+        layer.set(2, 1, 2);
+        assert_eq!(minimap.patched(&layer), minimap.render(&layer));
+
+        // another layer at the same revision is rendered from scratch
+        let mut other = TileLayer::new(3, 2, 16., 16.);
+        for x in 0..3 {
+            other.set(x, 1, 1);
+        }
+        other.set(0, 0, 2);
+        assert_eq!(other.revision(), layer.revision());
+        minimap.version = Some(layer.version());
+        assert_eq!(minimap.patched(&other), minimap.render(&other));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use sdl2::rect::Rect;
+
+use super::decode::DecodedImage;
+
+/// which downscaled variant of an image is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MipLevel {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+}
+
+impl MipLevel {
+    /// the smallest variant which still has a pixel for each screen pixel at
+    /// the zoom (screen pixels per image pixel)
+    pub fn for_zoom(zoom: f32) -> Self {
+        if zoom <= 0.25 {
+            MipLevel::Quarter
+        } else if zoom <= 0.5 {
+            MipLevel::Half
+        } else {
+            MipLevel::Full
+        }
+    }
+
+    /// how many times smaller than the full image
+    pub fn divisor(self) -> u32 {
+        match self {
+            MipLevel::Full => 1,
+            MipLevel::Half => 2,
+            MipLevel::Quarter => 4,
+        }
+    }
+
+    /// where the variant of the image at the path is cached, e.g.
+    /// "sprite.png@2" for half size. the full image is at the path itself
+    pub fn path(self, path: &Path) -> PathBuf {
+        if self == MipLevel::Full {
+            return path.to_owned();
+        }
+        let mut variant = OsString::from(path.as_os_str());
+        variant.push(format!("@{}", self.divisor()));
+        variant.into()
+    }
+
+    /// a source rect on the full image, on the variant instead
+    pub fn src(self, src: Rect) -> Rect {
+        let divisor = self.divisor() as i32;
+        let (left, top) = (
+            src.left().div_euclid(divisor),
+            src.top().div_euclid(divisor),
+        );
+        let right = (src.right() + divisor - 1).div_euclid(divisor);
+        let bottom = (src.bottom() + divisor - 1).div_euclid(divisor);
+        Rect::new(left, top, (right - left) as u32, (bottom - top) as u32)
+    }
+}
+
+/// half the size (rounded up), each pixel the average of up to four. colors
+/// are weighted by alpha, so transparent pixels don't darken edges
+pub fn downscale(image: &DecodedImage) -> DecodedImage {
+    let (width, height) = (image.width.div_ceil(2), image.height.div_ceil(2));
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for (sx, sy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let (sx, sy) = (x * 2 + sx, y * 2 + sy);
+                if sx >= image.width || sy >= image.height {
+                    continue;
+                }
+                let i = (sy as usize * image.width as usize + sx as usize) * 4;
+                let p = &image.rgba[i..i + 4];
+                let alpha = p[3] as u32;
+                for c in 0..3 {
+                    sum[c] += p[c] as u32 * alpha;
+                }
+                sum[3] += alpha;
+                count += 1;
+            }
+            let alpha = sum[3];
+            if alpha == 0 {
+                rgba.extend([0, 0, 0, 0]);
+                continue;
+            }
+            for c in sum.iter().take(3) {
+                rgba.push(((c + alpha / 2) / alpha) as u8);
+            }
+            rgba.push(((alpha + count / 2) / count) as u8);
+        }
+    }
+    DecodedImage {
+        width,
+        height,
+        rgba,
+    }
+}
+
+/// the image at each level, full size first
+pub fn variants(image: DecodedImage) -> Vec<(MipLevel, DecodedImage)> {
+    let half = downscale(&image);
+    let quarter = downscale(&half);
+    vec![
+        (MipLevel::Full, image),
+        (MipLevel::Half, half),
+        (MipLevel::Quarter, quarter),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mip_level() {
+        assert_eq!(MipLevel::for_zoom(1.), MipLevel::Full);
+        assert_eq!(MipLevel::for_zoom(0.5), MipLevel::Half);
+        assert_eq!(MipLevel::for_zoom(0.1), MipLevel::Quarter);
+        assert_eq!(
+            MipLevel::Half.path(Path::new("a/b.png")),
+            Path::new("a/b.png@2")
+        );
+        assert_eq!(MipLevel::Full.path(Path::new("b.png")), Path::new("b.png"));
+        assert_eq!(
+            MipLevel::Quarter.src(Rect::new(4, 6, 9, 2)),
+            Rect::new(1, 1, 3, 1)
+        );
+    }
+
+    #[test]
+    fn test_downscale() {
+        // 3 by 2: an opaque red and transparent pixel above two opaque blue,
+        // then a lone white column
+        let image = DecodedImage {
+            width: 3,
+            height: 2,
+            rgba: vec![
+                255, 0, 0, 255, 0, 0, 0, 0, 255, 255, 255, 255, //
+                0, 0, 255, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+            ],
+        };
+        let half = downscale(&image);
+        assert_eq!((half.width, half.height), (2, 1));
+        // the transparent pixel doesn't darken the average
+        assert_eq!(half.rgba, [85, 0, 170, 191, 255, 255, 255, 255]);
+        let levels = variants(image);
+        assert_eq!(levels[2].1.width, 1);
+    }
+}
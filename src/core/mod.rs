@@ -1,5 +1,100 @@
-mod render_system_txt_key;
-pub mod system;
-pub mod render_system;
-// pub mod audio_system;
+pub mod achievement;
+pub mod animation;
+pub mod app;
+pub mod aseprite;
+#[cfg(feature = "mixer")]
+pub mod audio_system;
+pub mod camera;
+pub mod captions;
+pub mod capture;
+pub mod chunked_tilemap;
+pub mod collision;
+pub mod config;
+pub mod console;
+pub mod controller;
+pub mod crash;
+pub mod crowd;
+pub mod day_night;
+pub mod debug_flags;
+pub mod decode;
+pub mod dialogue;
+pub mod dirty;
+pub mod easing;
+#[cfg(feature = "editor")]
+pub mod editor;
+#[cfg(feature = "egui")]
+pub mod egui_layer;
+pub mod entity;
+pub mod event_bus;
+pub mod events;
+#[cfg(feature = "ttf")]
 pub mod font_system;
+pub mod fov;
+pub mod frame_clock;
+#[cfg(feature = "hot_reload")]
+pub mod hot_reload;
+#[cfg(feature = "http")]
+pub mod http_cache;
+pub mod image_ops;
+pub mod input;
+pub mod input_buffer;
+pub mod inspector;
+pub mod inventory;
+pub mod layout;
+pub mod level;
+pub mod lighting;
+pub mod minimap;
+pub mod mipmap;
+pub mod navmesh;
+pub mod net;
+pub mod pack;
+pub mod pathfinding;
+pub mod physics;
+pub mod player_slots;
+#[cfg(feature = "wasm")]
+pub mod plugin;
+pub mod post_process;
+pub mod prefab;
+pub mod procgen;
+pub mod profiler;
+pub mod quest;
+pub mod render_system;
+pub mod render_system_txt_key;
+pub mod renderer;
+pub mod replay;
+pub mod replication;
+pub mod rng;
+pub mod rollback;
+pub mod save;
+#[cfg(feature = "lua")]
+pub mod script;
+pub mod sequence;
+pub mod session;
+pub mod spatial;
+pub mod state_machine;
+pub mod streaming;
+pub mod system;
+pub mod text_input;
+#[cfg(feature = "ttf")]
+pub mod text_path;
+pub mod texture_cache;
+pub mod tiled;
+pub mod tilemap;
+pub mod timer;
+pub mod touch;
+mod trace;
+pub mod trail;
+pub mod transform;
+pub mod transition;
+pub mod tween;
+#[cfg(feature = "ttf")]
+pub mod ui;
+pub mod variables;
+pub mod vfs;
+#[cfg(feature = "ttf")]
+pub mod virtual_keyboard;
+pub mod warm_start;
+pub mod weather;
+#[cfg(feature = "wgpu")]
+pub mod wgpu_renderer;
+pub mod world;
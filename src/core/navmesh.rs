@@ -0,0 +1,515 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect},
+};
+
+use super::{renderer::Vertex, system::ChimericSystem};
+
+fn sub(a: FPoint, b: FPoint) -> FPoint {
+    FPoint::new(a.x() - b.x(), a.y() - b.y())
+}
+
+fn cross(u: FPoint, v: FPoint) -> f32 {
+    u.x() * v.y() - u.y() * v.x()
+}
+
+/// positive if c is on the positive side of a to b, which is the inside of
+/// the mesh's triangles
+fn turn(a: FPoint, b: FPoint, c: FPoint) -> f32 {
+    cross(sub(b, a), sub(c, a))
+}
+
+fn distance(a: FPoint, b: FPoint) -> f32 {
+    (a.x() - b.x()).hypot(a.y() - b.y())
+}
+
+fn signed_area(points: &[FPoint]) -> f32 {
+    (0..points.len())
+        .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+        .sum::<f32>()
+        / 2.
+}
+
+/// true if the segments intersect, including touching
+fn segments_intersect(a: FPoint, b: FPoint, c: FPoint, d: FPoint) -> bool {
+    let (d1, d2) = (turn(c, d, a), turn(c, d, b));
+    let (d3, d4) = (turn(a, b, c), turn(a, b, d));
+    let on = |p: FPoint, q: FPoint, r: FPoint| {
+        r.x() >= p.x().min(q.x())
+            && r.x() <= p.x().max(q.x())
+            && r.y() >= p.y().min(q.y())
+            && r.y() <= p.y().max(q.y())
+    };
+    ((d1 > 0.) != (d2 > 0.)
+        && d1 != 0.
+        && d2 != 0.
+        && (d3 > 0.) != (d4 > 0.)
+        && d3 != 0.
+        && d4 != 0.)
+        || (d1 == 0. && on(c, d, a))
+        || (d2 == 0. && on(c, d, b))
+        || (d3 == 0. && on(a, b, c))
+        || (d4 == 0. && on(a, b, d))
+}
+
+/// a triangulation of the space around polygon obstacles, for pathfinding in
+/// games which aren't made of tiles. obstacles should be grown by the radius
+/// of the agents using the mesh, since paths hug their corners
+#[derive(Debug, Clone)]
+pub struct NavMesh {
+    vertices: Vec<FPoint>,
+    /// positively wound indices into the vertices
+    triangles: Vec<[usize; 3]>,
+    /// the triangle across the edge from corner i to corner i + 1
+    neighbours: Vec<[Option<usize>; 3]>,
+}
+
+struct Open {
+    estimate: f32,
+    triangle: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // min heap on estimate, ties broken deterministically
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.triangle.cmp(&self.triangle))
+    }
+}
+
+impl NavMesh {
+    /// the walkable space is the bounds minus the obstacles. obstacles are
+    /// polygons (either winding) which must be inside the bounds and mustn't
+    /// overlap each other. this is quadratic or worse in the number of
+    /// vertices, so build it once per level
+    pub fn build(bounds: FRect, obstacles: &[Vec<FPoint>]) -> Result<Self, String> {
+        let mut vertices = vec![
+            FPoint::new(bounds.left(), bounds.top()),
+            FPoint::new(bounds.right(), bounds.top()),
+            FPoint::new(bounds.right(), bounds.bottom()),
+            FPoint::new(bounds.left(), bounds.bottom()),
+        ];
+        let mut ring: Vec<usize> = vec![0, 1, 2, 3];
+
+        let mut holes: Vec<Vec<usize>> = Vec::new();
+        for (i, obstacle) in obstacles.iter().enumerate() {
+            if obstacle.len() < 3 {
+                return Err(format!("obstacle {} has fewer than 3 points", i));
+            }
+            if obstacle.iter().any(|p| {
+                p.x() <= bounds.left()
+                    || p.x() >= bounds.right()
+                    || p.y() <= bounds.top()
+                    || p.y() >= bounds.bottom()
+            }) {
+                return Err(format!("obstacle {} isn't inside the bounds", i));
+            }
+            let area = signed_area(obstacle);
+            if area == 0. {
+                continue;
+            }
+            let start = vertices.len();
+            vertices.extend_from_slice(obstacle);
+            let mut hole: Vec<usize> = (start..vertices.len()).collect();
+            // the walkable side of every edge is positive, so holes wind the
+            // other way to the bounds
+            if area > 0. {
+                hole.reverse();
+            }
+            holes.push(hole);
+        }
+
+        // join each hole to the ring with a bridge, so it's one polygon
+        holes.sort_by(|a, b| {
+            let max_x = |hole: &Vec<usize>| {
+                hole.iter()
+                    .map(|&i| vertices[i].x())
+                    .fold(f32::MIN, f32::max)
+            };
+            max_x(b).total_cmp(&max_x(a))
+        });
+        for h in 0..holes.len() {
+            let hole = &holes[h];
+            let m = (0..hole.len())
+                .max_by(|&a, &b| vertices[hole[a]].x().total_cmp(&vertices[hole[b]].x()))
+                .unwrap();
+            let from = vertices[hole[m]];
+            let inside = |ring: &[usize], at: usize, toward: FPoint| {
+                let len = ring.len();
+                let prev = vertices[ring[(at + len - 1) % len]];
+                let v = vertices[ring[at]];
+                let next = vertices[ring[(at + 1) % len]];
+                let d = sub(toward, v);
+                let (left_of_next, left_of_prev) =
+                    (cross(sub(next, v), d) > 0., cross(sub(v, prev), d) > 0.);
+                if turn(prev, v, next) >= 0. {
+                    left_of_next && left_of_prev
+                } else {
+                    left_of_next || left_of_prev
+                }
+            };
+            let edges = |ring: &[usize]| {
+                (0..ring.len())
+                    .map(|i| (ring[i], ring[(i + 1) % ring.len()]))
+                    .collect::<Vec<_>>()
+            };
+            let mut blocking = edges(&ring);
+            for other in &holes[h..] {
+                blocking.extend(edges(other));
+            }
+            let bridge = (0..ring.len())
+                .filter(|&at| {
+                    let to = vertices[ring[at]];
+                    inside(&ring, at, from)
+                        && inside(hole, m, to)
+                        && !blocking.iter().any(|&(a, b)| {
+                            let (a, b) = (vertices[a], vertices[b]);
+                            let shared = [a, b].iter().any(|p| *p == from || *p == to);
+                            !shared && segments_intersect(from, to, a, b)
+                        })
+                })
+                .min_by(|&a, &b| {
+                    distance(from, vertices[ring[a]]).total_cmp(&distance(from, vertices[ring[b]]))
+                })
+                .ok_or("an obstacle can't be reached; do obstacles overlap?")?;
+            let mut merged = ring[..=bridge].to_vec();
+            merged.extend(hole[m..].iter().chain(&hole[..=m]));
+            merged.extend(&ring[bridge..]);
+            ring = merged;
+        }
+
+        let triangles = Self::clip_ears(&vertices, ring)?;
+
+        let mut edges: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+        for (t, triangle) in triangles.iter().enumerate() {
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+                edges
+                    .entry((a.min(b), a.max(b)))
+                    .or_default()
+                    .push((t, corner));
+            }
+        }
+        let mut neighbours = vec![[None; 3]; triangles.len()];
+        for sharing in edges.values() {
+            if let [(a, a_corner), (b, b_corner)] = sharing[..] {
+                neighbours[a][a_corner] = Some(b);
+                neighbours[b][b_corner] = Some(a);
+            }
+        }
+        Ok(Self {
+            vertices,
+            triangles,
+            neighbours,
+        })
+    }
+
+    fn clip_ears(vertices: &[FPoint], mut ring: Vec<usize>) -> Result<Vec<[usize; 3]>, String> {
+        let mut triangles = Vec::new();
+        while ring.len() > 3 {
+            let len = ring.len();
+            let corners = |i: usize| [ring[(i + len - 1) % len], ring[i], ring[(i + 1) % len]];
+            let ear = (0..len).find(|&i| {
+                let [a, b, c] = corners(i).map(|v| vertices[v]);
+                turn(a, b, c) > 0.
+                    && !ring.iter().any(|&v| {
+                        let p = vertices[v];
+                        p != a
+                            && p != b
+                            && p != c
+                            && turn(a, b, p) >= 0.
+                            && turn(b, c, p) >= 0.
+                            && turn(c, a, p) >= 0.
+                    })
+            });
+            match ear {
+                Some(i) => {
+                    triangles.push(corners(i));
+                    ring.remove(i);
+                }
+                None => {
+                    // no ear, but a vertex in a straight line can be dropped
+                    let straight = (0..len)
+                        .find(|&i| {
+                            let [a, b, c] = corners(i).map(|v| vertices[v]);
+                            turn(a, b, c) == 0.
+                        })
+                        .ok_or("the walkable space couldn't be triangulated")?;
+                    ring.remove(straight);
+                }
+            }
+        }
+        let [a, b, c] = [ring[0], ring[1], ring[2]];
+        if turn(vertices[a], vertices[b], vertices[c]) > 0. {
+            triangles.push([a, b, c]);
+        }
+        Ok(triangles)
+    }
+
+    pub fn vertices(&self) -> &[FPoint] {
+        &self.vertices
+    }
+
+    /// indices into the vertices
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    fn corners(&self, triangle: usize) -> [FPoint; 3] {
+        self.triangles[triangle].map(|v| self.vertices[v])
+    }
+
+    fn centroid(&self, triangle: usize) -> FPoint {
+        let [a, b, c] = self.corners(triangle);
+        FPoint::new((a.x() + b.x() + c.x()) / 3., (a.y() + b.y() + c.y()) / 3.)
+    }
+
+    /// the triangle containing the point, or None if it isn't walkable
+    pub fn triangle_at(&self, point: FPoint) -> Option<usize> {
+        (0..self.triangles.len()).find(|&t| {
+            let [a, b, c] = self.corners(t);
+            turn(a, b, point) >= 0. && turn(b, c, point) >= 0. && turn(c, a, point) >= 0.
+        })
+    }
+
+    pub fn is_walkable(&self, point: FPoint) -> bool {
+        self.triangle_at(point).is_some()
+    }
+
+    /// a* across triangles
+    fn corridor(&self, start: usize, goal: usize, to: FPoint) -> Option<Vec<usize>> {
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<usize, usize> = HashMap::new();
+        let mut cost: HashMap<usize, f32> = HashMap::new();
+        cost.insert(start, 0.);
+        open.push(Open {
+            estimate: distance(self.centroid(start), to),
+            triangle: start,
+        });
+        while let Some(Open { triangle, .. }) = open.pop() {
+            if triangle == goal {
+                let mut corridor = vec![goal];
+                while let Some(&previous) = came_from.get(corridor.last().unwrap()) {
+                    corridor.push(previous);
+                }
+                corridor.reverse();
+                return Some(corridor);
+            }
+            let here = cost[&triangle];
+            for next in self.neighbours[triangle].iter().flatten().copied() {
+                let next_cost = here + distance(self.centroid(triangle), self.centroid(next));
+                if cost.get(&next).is_some_and(|c| *c <= next_cost) {
+                    continue;
+                }
+                cost.insert(next, next_cost);
+                came_from.insert(next, triangle);
+                open.push(Open {
+                    estimate: next_cost + distance(self.centroid(next), to),
+                    triangle: next,
+                });
+            }
+        }
+        None
+    }
+
+    /// the shortest path through the triangles a* picks, from and to
+    /// inclusive, bending only at obstacle corners. None if either point
+    /// isn't walkable or they aren't connected
+    pub fn find_path(&self, from: FPoint, to: FPoint) -> Option<Vec<FPoint>> {
+        let start = self.triangle_at(from)?;
+        let goal = self.triangle_at(to)?;
+        let corridor = self.corridor(start, goal, to)?;
+
+        // (left, right) edges crossed, looking along the path
+        let mut portals = vec![(from, from)];
+        for pair in corridor.windows(2) {
+            let corner = (0..3)
+                .find(|&i| self.neighbours[pair[0]][i] == Some(pair[1]))
+                .unwrap();
+            let [a, b, c] = self.corners(pair[0]);
+            let (right, left) = [(a, b), (b, c), (c, a)][corner];
+            portals.push((left, right));
+        }
+        portals.push((to, to));
+        Some(Self::funnel(&portals))
+    }
+
+    /// the simple stupid funnel algorithm
+    fn funnel(portals: &[(FPoint, FPoint)]) -> Vec<FPoint> {
+        let mut path = vec![portals[0].0];
+        let (mut apex, mut left, mut right) = (portals[0].0, portals[0].0, portals[0].1);
+        let (mut left_index, mut right_index) = (0, 0);
+        let mut i = 1;
+        while i < portals.len() {
+            let (next_left, next_right) = portals[i];
+            if turn(apex, right, next_right) >= 0. {
+                if apex == right || turn(apex, left, next_right) < 0. {
+                    right = next_right;
+                    right_index = i;
+                } else {
+                    // crossed over the left side, which becomes a corner
+                    path.push(left);
+                    apex = left;
+                    right = apex;
+                    right_index = left_index;
+                    i = left_index + 1;
+                    continue;
+                }
+            }
+            if turn(apex, left, next_left) <= 0. {
+                if apex == left || turn(apex, right, next_left) > 0. {
+                    left = next_left;
+                    left_index = i;
+                } else {
+                    path.push(right);
+                    apex = right;
+                    left = apex;
+                    left_index = right_index;
+                    i = right_index + 1;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        let end = portals[portals.len() - 1].0;
+        if path.last() != Some(&end) {
+            path.push(end);
+        }
+        path
+    }
+
+    /// the triangles filled, with their edges as lines a unit wide, for
+    /// draw_triangles
+    pub fn debug_mesh(&self, fill: Color, edge: Color) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for triangle in 0..self.triangles.len() {
+            let corners = self.corners(triangle);
+            let start = vertices.len() as u32;
+            vertices.extend(corners.map(|position| Vertex {
+                position,
+                color: fill,
+            }));
+            indices.extend([start, start + 1, start + 2]);
+            for i in 0..3 {
+                let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                let length = distance(a, b).max(f32::EPSILON);
+                let normal = FPoint::new(
+                    -(b.y() - a.y()) / length / 2.,
+                    (b.x() - a.x()) / length / 2.,
+                );
+                let start = vertices.len() as u32;
+                for p in [a, b] {
+                    for side in [-1., 1.] {
+                        vertices.push(Vertex {
+                            position: FPoint::new(
+                                p.x() + normal.x() * side,
+                                p.y() + normal.y() * side,
+                            ),
+                            color: edge,
+                        });
+                    }
+                }
+                indices.extend([start, start + 1, start + 2, start + 1, start + 3, start + 2]);
+            }
+        }
+        (vertices, indices)
+    }
+
+    /// draw the mesh in world coordinates, for debugging
+    pub fn draw_debug(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        fill: Color,
+        edge: Color,
+    ) -> Result<(), String> {
+        let (vertices, indices) = self.debug_mesh(fill, edge);
+        if indices.is_empty() {
+            return Ok(());
+        }
+        system.draw_triangles(window_name, &vertices, &indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, w: f32, h: f32) -> Vec<FPoint> {
+        vec![
+            FPoint::new(x, y),
+            FPoint::new(x + w, y),
+            FPoint::new(x + w, y + h),
+            FPoint::new(x, y + h),
+        ]
+    }
+
+    #[test]
+    fn test_navmesh() {
+        let bounds = FRect::new(0., 0., 10., 10.);
+        let mesh = NavMesh::build(bounds, &[square(4., 2., 2., 6.)]).unwrap();
+        // the walkable area is covered exactly
+        let area: f32 = (0..mesh.triangles().len())
+            .map(|t| signed_area(&mesh.corners(t)))
+            .sum();
+        assert_eq!(area, 100. - 12.);
+        assert!(!mesh.is_walkable(FPoint::new(5., 5.)));
+        assert!(mesh
+            .find_path(FPoint::new(1., 5.), FPoint::new(5., 5.))
+            .is_none());
+
+        // in sight
+        let path = mesh
+            .find_path(FPoint::new(1., 1.), FPoint::new(9., 1.))
+            .unwrap();
+        assert_eq!(path, vec![FPoint::new(1., 1.), FPoint::new(9., 1.)]);
+
+        // around the obstacle's corners
+        let path = mesh
+            .find_path(FPoint::new(1., 5.), FPoint::new(9., 5.))
+            .unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(path[1] == FPoint::new(4., 2.) || path[1] == FPoint::new(4., 8.));
+        assert_eq!(path[2], FPoint::new(6., path[1].y()));
+
+        // concave, and touching nothing
+        let l = vec![
+            FPoint::new(2., 2.),
+            FPoint::new(8., 2.),
+            FPoint::new(8., 4.),
+            FPoint::new(4., 4.),
+            FPoint::new(4., 8.),
+            FPoint::new(2., 8.),
+        ];
+        let mesh = NavMesh::build(bounds, &[l, square(6., 6., 1., 1.)]).unwrap();
+        let path = mesh
+            .find_path(FPoint::new(5., 5.), FPoint::new(1., 1.))
+            .unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(path[1] == FPoint::new(8., 4.) || path[1] == FPoint::new(4., 8.));
+
+        assert!(NavMesh::build(bounds, &[square(8., 8., 4., 4.)]).is_err());
+    }
+}
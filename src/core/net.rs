@@ -0,0 +1,701 @@
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::trace::log_debug;
+
+const MAGIC: &[u8; 4] = b"CHNT";
+
+/// the largest packet sent, which fits in a typical mtu
+pub const MAX_PACKET: usize = 1200;
+
+/// packet header plus a message's channel and sequence
+const MESSAGE_HEADER: usize = MAGIC.len() + 1 + 1 + 4;
+
+/// the largest message that can be sent
+pub const MAX_MESSAGE: usize = MAX_PACKET - MESSAGE_HEADER;
+
+/// how a message is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Channel {
+    /// may be lost, duplicated or arrive out of order, e.g. positions sent
+    /// every tick
+    Unreliable,
+    /// resent until acknowledged and delivered in the order sent, e.g. chat
+    ReliableOrdered,
+    /// resent until acknowledged and delivered as soon as it arrives, e.g.
+    /// one off events which don't depend on each other
+    ReliableUnordered,
+}
+
+impl Channel {
+    const ALL: [Channel; 3] = [
+        Channel::Unreliable,
+        Channel::ReliableOrdered,
+        Channel::ReliableUnordered,
+    ];
+
+    fn from_u8(value: u8) -> Result<Self, String> {
+        Self::ALL
+            .get(value as usize)
+            .copied()
+            .ok_or_else(|| format!("unknown channel {}", value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Packet {
+    /// from a client, repeated until accepted. the salt tells a reconnect
+    /// from the same address apart from a repeat
+    Connect {
+        salt: u64,
+    },
+    Accept {
+        salt: u64,
+    },
+    /// the server is full or not accepting
+    Reject,
+    Disconnect,
+    Keepalive,
+    Message {
+        channel: Channel,
+        sequence: u32,
+        data: Vec<u8>,
+    },
+    Ack {
+        channel: Channel,
+        sequence: u32,
+    },
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("packet is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+impl Packet {
+    /// magic + u8(kind) + the kind's fields, little endian. a message's data
+    /// is the rest of the packet
+    fn encode(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = MAGIC.to_vec();
+        match self {
+            Packet::Connect { salt } => {
+                out.push(0);
+                out.extend_from_slice(&salt.to_le_bytes());
+            }
+            Packet::Accept { salt } => {
+                out.push(1);
+                out.extend_from_slice(&salt.to_le_bytes());
+            }
+            Packet::Reject => out.push(2),
+            Packet::Disconnect => out.push(3),
+            Packet::Keepalive => out.push(4),
+            Packet::Message {
+                channel,
+                sequence,
+                data,
+            } => {
+                out.push(5);
+                out.push(*channel as u8);
+                out.extend_from_slice(&sequence.to_le_bytes());
+                out.extend_from_slice(data);
+            }
+            Packet::Ack { channel, sequence } => {
+                out.push(6);
+                out.push(*channel as u8);
+                out.extend_from_slice(&sequence.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    fn decode(mut data: &[u8]) -> Result<Self, String> {
+        if take::<4>(&mut data)? != *MAGIC {
+            return Err("not a chimeric packet".into());
+        }
+        let [kind] = take::<1>(&mut data)?;
+        let packet = match kind {
+            0 => Packet::Connect {
+                salt: u64::from_le_bytes(take(&mut data)?),
+            },
+            1 => Packet::Accept {
+                salt: u64::from_le_bytes(take(&mut data)?),
+            },
+            2 => Packet::Reject,
+            3 => Packet::Disconnect,
+            4 => Packet::Keepalive,
+            5 => {
+                let [channel] = take::<1>(&mut data)?;
+                let sequence = u32::from_le_bytes(take(&mut data)?);
+                let packet = Packet::Message {
+                    channel: Channel::from_u8(channel)?,
+                    sequence,
+                    data: data.to_vec(),
+                };
+                data = &[];
+                packet
+            }
+            6 => {
+                let [channel] = take::<1>(&mut data)?;
+                Packet::Ack {
+                    channel: Channel::from_u8(channel)?,
+                    sequence: u32::from_le_bytes(take(&mut data)?),
+                }
+            }
+            _ => return Err(format!("unknown packet kind {}", kind)),
+        };
+        if !data.is_empty() {
+            return Err("packet has trailing data".into());
+        }
+        Ok(packet)
+    }
+}
+
+/// why a connection ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// nothing was heard from the peer for the timeout
+    TimedOut,
+    /// the peer disconnected
+    Closed,
+    /// the server refused the connection
+    Rejected,
+}
+
+/// returned from Transport::update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetEvent {
+    Connected {
+        peer: SocketAddr,
+    },
+    Disconnected {
+        peer: SocketAddr,
+        reason: DisconnectReason,
+    },
+    Message {
+        peer: SocketAddr,
+        channel: Channel,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetSettings {
+    /// a keepalive is sent when nothing else was sent for this long
+    pub keepalive: Duration,
+    /// a connection (or connection attempt) is dropped when nothing was heard
+    /// from the peer for this long
+    pub timeout: Duration,
+    /// unacknowledged reliable messages and connection requests are resent
+    /// this often
+    pub resend: Duration,
+    /// the most clients a server accepts
+    pub max_clients: usize,
+}
+
+impl Default for NetSettings {
+    fn default() -> Self {
+        Self {
+            keepalive: Duration::from_millis(250),
+            timeout: Duration::from_secs(5),
+            resend: Duration::from_millis(100),
+            max_clients: 8,
+        }
+    }
+}
+
+/// the sequencing of a channel, both ways. sequences don't wrap around
+#[derive(Debug, Clone, Default)]
+struct ChannelState {
+    next_send: u32,
+    /// sent but not acknowledged, with when they were last sent
+    unacked: BTreeMap<u32, (Vec<u8>, Duration)>,
+    /// every sequence before this one was received
+    next_receive: u32,
+    /// received after a gap. data is only kept when ordered
+    ahead: BTreeMap<u32, Vec<u8>>,
+}
+
+impl ChannelState {
+    /// the messages which can be delivered now that this one arrived. nothing
+    /// if it's a repeat
+    fn receive(&mut self, ordered: bool, sequence: u32, data: Vec<u8>) -> Vec<Vec<u8>> {
+        if sequence < self.next_receive || self.ahead.contains_key(&sequence) {
+            return Vec::new();
+        }
+        if sequence != self.next_receive {
+            if ordered {
+                self.ahead.insert(sequence, data);
+                return Vec::new();
+            }
+            self.ahead.insert(sequence, Vec::new());
+            return vec![data];
+        }
+        let mut delivered = vec![data];
+        self.next_receive += 1;
+        while let Some(data) = self.ahead.remove(&self.next_receive) {
+            if ordered {
+                delivered.push(data);
+            }
+            self.next_receive += 1;
+        }
+        delivered
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionState {
+    /// a client waiting for the server to accept
+    Connecting,
+    Connected,
+}
+
+#[derive(Debug, Clone)]
+struct Connection {
+    state: ConnectionState,
+    salt: u64,
+    last_sent: Duration,
+    last_received: Duration,
+    /// indexed by channel
+    channels: [ChannelState; 3],
+}
+
+impl Connection {
+    fn new(state: ConnectionState, salt: u64, now: Duration) -> Self {
+        Self {
+            state,
+            salt,
+            last_sent: now,
+            last_received: now,
+            channels: Default::default(),
+        }
+    }
+}
+
+/// a client or server over udp, with unreliable and reliable channels, a
+/// connection handshake and keepalives. it's polled: call update each frame
+/// to send and receive. times are e.g. EngineClock::total
+pub struct Transport {
+    socket: UdpSocket,
+    pub settings: NetSettings,
+    connections: BTreeMap<SocketAddr, Connection>,
+    /// whether new clients are accepted. false for clients
+    pub accepting: bool,
+}
+
+impl Transport {
+    fn bind(addr: impl ToSocketAddrs, settings: NetSettings) -> Result<Self, String> {
+        let socket = UdpSocket::bind(addr).map_err(|e| e.to_string())?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Self {
+            socket,
+            settings,
+            connections: Default::default(),
+            accepting: false,
+        })
+    }
+
+    /// listen for clients on the address, e.g. "0.0.0.0:7777"
+    pub fn server(addr: impl ToSocketAddrs, settings: NetSettings) -> Result<Self, String> {
+        let mut server = Self::bind(addr, settings)?;
+        server.accepting = true;
+        Ok(server)
+    }
+
+    /// start connecting to a server from any local port. a Connected (or
+    /// Disconnected) event follows from update
+    pub fn client(
+        server: impl ToSocketAddrs,
+        settings: NetSettings,
+        now: Duration,
+    ) -> Result<Self, String> {
+        let server = server
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| "no address to connect to".to_owned())?;
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let mut client = Self::bind(local, settings)?;
+        // differs between attempts from the same address
+        let salt = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+            | 1;
+        let connection = Connection::new(ConnectionState::Connecting, salt, now);
+        client.connections.insert(server, connection);
+        client.send_packet(server, &Packet::Connect { salt }, now);
+        Ok(client)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.socket.local_addr().map_err(|e| e.to_string())
+    }
+
+    /// connected peers. for a client, the server once connected
+    pub fn peers(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.connections
+            .iter()
+            .filter(|(_, c)| c.state == ConnectionState::Connected)
+            .map(|(addr, _)| *addr)
+    }
+
+    pub fn is_connected(&self, peer: SocketAddr) -> bool {
+        self.connections
+            .get(&peer)
+            .is_some_and(|c| c.state == ConnectionState::Connected)
+    }
+
+    fn send_packet(&mut self, peer: SocketAddr, packet: &Packet, now: Duration) {
+        // lost like any other packet; reliability is handled above this
+        if let Err(e) = self.socket.send_to(&packet.encode(), peer) {
+            log_debug!("couldn't send to {peer}: {e}");
+        }
+        if let Some(connection) = self.connections.get_mut(&peer) {
+            connection.last_sent = now;
+        }
+    }
+
+    /// queue a message to a connected peer. reliable messages are resent by
+    /// update until acknowledged
+    pub fn send(
+        &mut self,
+        peer: SocketAddr,
+        channel: Channel,
+        data: &[u8],
+        now: Duration,
+    ) -> Result<(), String> {
+        if data.len() > MAX_MESSAGE {
+            return Err(format!(
+                "message of {} bytes is over the limit of {}",
+                data.len(),
+                MAX_MESSAGE
+            ));
+        }
+        let connection = self
+            .connections
+            .get_mut(&peer)
+            .filter(|c| c.state == ConnectionState::Connected)
+            .ok_or_else(|| format!("not connected to {}", peer))?;
+        let state = &mut connection.channels[channel as usize];
+        let sequence = state.next_send;
+        state.next_send += 1;
+        if channel != Channel::Unreliable {
+            state.unacked.insert(sequence, (data.to_vec(), now));
+        }
+        let packet = Packet::Message {
+            channel,
+            sequence,
+            data: data.to_vec(),
+        };
+        self.send_packet(peer, &packet, now);
+        Ok(())
+    }
+
+    /// send to every connected peer
+    pub fn broadcast(
+        &mut self,
+        channel: Channel,
+        data: &[u8],
+        now: Duration,
+    ) -> Result<(), String> {
+        let peers: Vec<SocketAddr> = self.peers().collect();
+        for peer in peers {
+            self.send(peer, channel, data, now)?;
+        }
+        Ok(())
+    }
+
+    /// tell the peer and forget it. no event is returned for it
+    pub fn disconnect(&mut self, peer: SocketAddr, now: Duration) {
+        if self.connections.contains_key(&peer) {
+            self.send_packet(peer, &Packet::Disconnect, now);
+            self.connections.remove(&peer);
+        }
+    }
+
+    pub fn disconnect_all(&mut self, now: Duration) {
+        let peers: Vec<SocketAddr> = self.connections.keys().copied().collect();
+        for peer in peers {
+            self.disconnect(peer, now);
+        }
+    }
+
+    /// receive everything which arrived, then resend, keep alive and time out
+    /// connections
+    pub fn update(&mut self, now: Duration) -> Vec<NetEvent> {
+        let mut events = Vec::new();
+        let mut buf = [0u8; MAX_PACKET];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) => match Packet::decode(&buf[..len]) {
+                    Ok(packet) => self.receive(from, packet, now, &mut events),
+                    Err(e) => log_debug!("bad packet from {from}: {e}"),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // e.g. an icmp port unreachable from a closed peer, reported
+                // on some platforms. the timeout handles it
+                Err(e) => log_debug!("receive failed: {e}"),
+            }
+        }
+
+        let mut outgoing: Vec<(SocketAddr, Packet)> = Vec::new();
+        let mut timed_out: Vec<SocketAddr> = Vec::new();
+        for (addr, connection) in self.connections.iter_mut() {
+            if now.saturating_sub(connection.last_received) > self.settings.timeout {
+                timed_out.push(*addr);
+                continue;
+            }
+            if connection.state == ConnectionState::Connecting {
+                if now.saturating_sub(connection.last_sent) >= self.settings.resend {
+                    outgoing.push((
+                        *addr,
+                        Packet::Connect {
+                            salt: connection.salt,
+                        },
+                    ));
+                }
+                continue;
+            }
+            for channel in Channel::ALL {
+                let state = &mut connection.channels[channel as usize];
+                for (sequence, (data, sent)) in state.unacked.iter_mut() {
+                    if now.saturating_sub(*sent) >= self.settings.resend {
+                        *sent = now;
+                        outgoing.push((
+                            *addr,
+                            Packet::Message {
+                                channel,
+                                sequence: *sequence,
+                                data: data.clone(),
+                            },
+                        ));
+                    }
+                }
+            }
+            let sending = outgoing.last().is_some_and(|(a, _)| a == addr);
+            if !sending && now.saturating_sub(connection.last_sent) >= self.settings.keepalive {
+                outgoing.push((*addr, Packet::Keepalive));
+            }
+        }
+        for (addr, packet) in outgoing {
+            self.send_packet(addr, &packet, now);
+        }
+        for peer in timed_out {
+            self.connections.remove(&peer);
+            events.push(NetEvent::Disconnected {
+                peer,
+                reason: DisconnectReason::TimedOut,
+            });
+        }
+        events
+    }
+
+    fn receive(
+        &mut self,
+        from: SocketAddr,
+        packet: Packet,
+        now: Duration,
+        events: &mut Vec<NetEvent>,
+    ) {
+        if let Packet::Connect { salt } = packet {
+            self.receive_connect(from, salt, now, events);
+            return;
+        }
+        let Some(connection) = self.connections.get_mut(&from) else {
+            return;
+        };
+        connection.last_received = now;
+        match packet {
+            Packet::Connect { .. } => unreachable!("handled above"),
+            Packet::Accept { salt } => {
+                if connection.state == ConnectionState::Connecting && connection.salt == salt {
+                    connection.state = ConnectionState::Connected;
+                    events.push(NetEvent::Connected { peer: from });
+                }
+            }
+            Packet::Reject => {
+                if connection.state == ConnectionState::Connecting {
+                    self.connections.remove(&from);
+                    events.push(NetEvent::Disconnected {
+                        peer: from,
+                        reason: DisconnectReason::Rejected,
+                    });
+                }
+            }
+            Packet::Disconnect => {
+                self.connections.remove(&from);
+                events.push(NetEvent::Disconnected {
+                    peer: from,
+                    reason: DisconnectReason::Closed,
+                });
+            }
+            Packet::Keepalive => {}
+            Packet::Message { .. } | Packet::Ack { .. }
+                if connection.state != ConnectionState::Connected => {}
+            Packet::Message {
+                channel,
+                sequence,
+                data,
+            } => {
+                if channel == Channel::Unreliable {
+                    events.push(NetEvent::Message {
+                        peer: from,
+                        channel,
+                        data,
+                    });
+                    return;
+                }
+                let ordered = channel == Channel::ReliableOrdered;
+                let state = &mut connection.channels[channel as usize];
+                for data in state.receive(ordered, sequence, data) {
+                    events.push(NetEvent::Message {
+                        peer: from,
+                        channel,
+                        data,
+                    });
+                }
+                // repeats are acknowledged again, in case the ack was lost
+                self.send_packet(from, &Packet::Ack { channel, sequence }, now);
+            }
+            Packet::Ack { channel, sequence } => {
+                connection.channels[channel as usize]
+                    .unacked
+                    .remove(&sequence);
+            }
+        }
+    }
+
+    fn receive_connect(
+        &mut self,
+        from: SocketAddr,
+        salt: u64,
+        now: Duration,
+        events: &mut Vec<NetEvent>,
+    ) {
+        match self.connections.get_mut(&from) {
+            // the accept was lost
+            Some(connection) if connection.salt == salt => connection.last_received = now,
+            // the client restarted without disconnecting
+            Some(_) => {
+                self.connections.remove(&from);
+                events.push(NetEvent::Disconnected {
+                    peer: from,
+                    reason: DisconnectReason::Closed,
+                });
+                self.receive_connect(from, salt, now, events);
+                return;
+            }
+            None => {
+                if !self.accepting || self.connections.len() >= self.settings.max_clients {
+                    self.send_packet(from, &Packet::Reject, now);
+                    return;
+                }
+                let connection = Connection::new(ConnectionState::Connected, salt, now);
+                self.connections.insert(from, connection);
+                events.push(NetEvent::Connected { peer: from });
+            }
+        }
+        self.send_packet(from, &Packet::Accept { salt }, now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_receive() {
+        let mut ordered = ChannelState::default();
+        assert!(ordered.receive(true, 1, vec![1]).is_empty());
+        assert!(ordered.receive(true, 2, vec![2]).is_empty());
+        assert_eq!(ordered.receive(true, 0, vec![0]), [[0], [1], [2]]);
+        assert!(ordered.receive(true, 1, vec![1]).is_empty());
+
+        let mut unordered = ChannelState::default();
+        assert_eq!(unordered.receive(false, 1, vec![1]), [[1]]);
+        assert!(unordered.receive(false, 1, vec![1]).is_empty());
+        assert_eq!(unordered.receive(false, 0, vec![0]), [[0]]);
+        assert_eq!(unordered.next_receive, 2);
+
+        let packet = Packet::Message {
+            channel: Channel::ReliableUnordered,
+            sequence: 7,
+            data: b"hi".to_vec(),
+        };
+        assert_eq!(Packet::decode(&packet.encode()).unwrap(), packet);
+        assert!(Packet::decode(&packet.encode()[..6]).is_err());
+    }
+
+    #[test]
+    fn test_loopback() {
+        let settings = NetSettings {
+            max_clients: 1,
+            ..Default::default()
+        };
+        let mut server = Transport::server("127.0.0.1:0", settings.clone()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let ms = Duration::from_millis;
+        let mut client = Transport::client(addr, settings.clone(), ms(0)).unwrap();
+        let mut other = Transport::client(addr, settings, ms(0)).unwrap();
+
+        // the events of both ends over a few polls
+        let poll = |server: &mut Transport, client: &mut Transport, now| {
+            let mut events = (Vec::new(), Vec::new());
+            for _ in 0..20 {
+                events.0.extend(server.update(now));
+                events.1.extend(client.update(now));
+                std::thread::sleep(ms(5));
+            }
+            events
+        };
+        let (s, c) = poll(&mut server, &mut client, ms(0));
+        assert!(matches!(s[..], [NetEvent::Connected { .. }]));
+        assert_eq!(c, [NetEvent::Connected { peer: addr }]);
+        // full
+        let (_, c) = poll(&mut server, &mut other, ms(0));
+        assert!(matches!(
+            c[..],
+            [NetEvent::Disconnected {
+                reason: DisconnectReason::Rejected,
+                ..
+            }]
+        ));
+
+        for (i, channel) in Channel::ALL.into_iter().enumerate() {
+            client.send(addr, channel, &[i as u8], ms(10)).unwrap();
+        }
+        let mut received: Vec<u8> = poll(&mut server, &mut client, ms(10))
+            .0
+            .into_iter()
+            .flat_map(|event| match event {
+                NetEvent::Message { data, .. } => data,
+                _ => Vec::new(),
+            })
+            .collect();
+        received.sort();
+        assert_eq!(received, [0, 1, 2]);
+
+        let (s, _) = poll(&mut server, &mut client, ms(10_000));
+        assert!(matches!(
+            s[..],
+            [NetEvent::Disconnected {
+                reason: DisconnectReason::TimedOut,
+                ..
+            }]
+        ));
+        assert!(client
+            .send(addr, Channel::Unreliable, &[0; MAX_MESSAGE + 1], ms(0))
+            .is_err());
+    }
+}
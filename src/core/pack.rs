@@ -0,0 +1,343 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Component, Path},
+};
+
+use super::{save::write_atomic, trace::log_debug};
+
+const MAGIC: &[u8; 4] = b"CHPK";
+const VERSION: u32 = 1;
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("pack is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+/// the name an asset is stored under: relative, with '/' separators. "." and
+/// ".." are resolved lexically
+pub fn normalize(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PackEntry {
+    /// from the start of the pack
+    offset: u64,
+    stored_len: u64,
+    /// after decompression
+    size: u64,
+    compressed: bool,
+}
+
+/// creates a pack. entries are compressed with zstd if a compression level is
+/// set (requires the zstd feature)
+#[derive(Default)]
+pub struct PackBuilder {
+    entries: BTreeMap<String, Vec<u8>>,
+    pub compression_level: Option<i32>,
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add or replace an entry
+    pub fn add(&mut self, name: &Path, data: Vec<u8>) {
+        self.entries.insert(normalize(name), data);
+    }
+
+    /// every file under the directory, named relative to it
+    pub fn add_dir(&mut self, dir: &Path) -> Result<(), String> {
+        self.add_dir_as(dir, Path::new(""))
+    }
+
+    fn add_dir_as(&mut self, dir: &Path, prefix: &Path) -> Result<(), String> {
+        for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let name = prefix.join(entry.file_name());
+            let path = entry.path();
+            if path.is_dir() {
+                self.add_dir_as(&path, &name)?;
+            } else {
+                let data = std::fs::read(&path).map_err(|e| e.to_string())?;
+                self.add(&name, data);
+            }
+        }
+        Ok(())
+    }
+
+    fn store(&self, data: &[u8]) -> Result<(Vec<u8>, bool), String> {
+        match self.compression_level {
+            None => Ok((data.to_vec(), false)),
+            #[cfg(feature = "zstd")]
+            Some(level) => zstd::encode_all(data, level)
+                .map(|c| (c, true))
+                .map_err(|e| e.to_string()),
+            #[cfg(not(feature = "zstd"))]
+            Some(_) => Err("compressed packs require the zstd feature".into()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let stored = self
+            .entries
+            .iter()
+            .map(|(name, data)| Ok((name, data.len(), self.store(data)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let index_len: usize = stored.iter().map(|(name, ..)| 4 + name.len() + 25).sum();
+        let mut offset = (12 + index_len) as u64;
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        for (name, size, (data, compressed)) in &stored {
+            out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            out.extend_from_slice(&(*size as u64).to_le_bytes());
+            out.push(*compressed as u8);
+            offset += data.len() as u64;
+        }
+        for (_, _, (data, _)) in &stored {
+            out.extend_from_slice(data);
+        }
+        Ok(out)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write_atomic(path, &self.to_bytes()?)
+    }
+}
+
+enum Source {
+    /// with the file's length, so entries can be bounds checked before reading
+    File(RefCell<File>, u64),
+    Memory(Vec<u8>),
+}
+
+impl Source {
+    fn len(&self) -> u64 {
+        match self {
+            Source::File(_, len) => *len,
+            Source::Memory(bytes) => bytes.len() as u64,
+        }
+    }
+}
+
+/// a read only archive of assets. the index is read up front; entries are read
+/// on demand
+pub struct Pack {
+    source: Source,
+    entries: BTreeMap<String, PackEntry>,
+}
+
+impl Pack {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|e| e.to_string())?;
+        let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)
+            .map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut index = Vec::new();
+        let count = Self::parse_header(&header)?;
+        for _ in 0..count {
+            let mut len = [0u8; 4];
+            file.read_exact(&mut len).map_err(|e| e.to_string())?;
+            let rest_len = u32::from_le_bytes(len) as u64 + 25;
+            if 12 + index.len() as u64 + 4 + rest_len > file_len {
+                return Err("pack is truncated".into());
+            }
+            let mut rest = vec![0u8; rest_len as usize];
+            file.read_exact(&mut rest).map_err(|e| e.to_string())?;
+            index.extend_from_slice(&len);
+            index.extend_from_slice(&rest);
+        }
+        log_debug!("opened pack {} ({count} entries)", path.display());
+        Ok(Self {
+            entries: Self::parse_index(&index, count)?,
+            source: Source::File(RefCell::new(file), file_len),
+        })
+    }
+
+    /// e.g. from include_bytes
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        let header: [u8; 12] = bytes
+            .get(..12)
+            .ok_or("pack is truncated")?
+            .try_into()
+            .expect("length checked");
+        let count = Self::parse_header(&header)?;
+        let entries = Self::parse_index(&bytes[12..], count)?;
+        Ok(Self {
+            entries,
+            source: Source::Memory(bytes),
+        })
+    }
+
+    fn parse_header(header: &[u8; 12]) -> Result<u32, String> {
+        let mut data = &header[..];
+        if &take::<4>(&mut data)? != MAGIC {
+            return Err("not a pack file".into());
+        }
+        let version = u32::from_le_bytes(take(&mut data)?);
+        if version != VERSION {
+            return Err(format!("unsupported pack version {version}"));
+        }
+        Ok(u32::from_le_bytes(take(&mut data)?))
+    }
+
+    fn parse_index(mut data: &[u8], count: u32) -> Result<BTreeMap<String, PackEntry>, String> {
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let len = u32::from_le_bytes(take(&mut data)?) as usize;
+            if data.len() < len {
+                return Err("pack is truncated".into());
+            }
+            let (name, rest) = data.split_at(len);
+            data = rest;
+            let name = String::from_utf8(name.to_vec()).map_err(|e| e.to_string())?;
+            let entry = PackEntry {
+                offset: u64::from_le_bytes(take(&mut data)?),
+                stored_len: u64::from_le_bytes(take(&mut data)?),
+                size: u64::from_le_bytes(take(&mut data)?),
+                compressed: take::<1>(&mut data)?[0] != 0,
+            };
+            entries.insert(name, entry);
+        }
+        Ok(entries)
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entries.contains_key(&normalize(path))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|s| s.as_str())
+    }
+
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>, String> {
+        let name = normalize(path);
+        let entry = *self
+            .entries
+            .get(&name)
+            .ok_or_else(|| format!("\"{name}\" isn't in the pack"))?;
+        let end = entry
+            .offset
+            .checked_add(entry.stored_len)
+            .ok_or_else(|| format!("\"{name}\" is corrupted"))?;
+        if end > self.source.len() {
+            return Err("pack is truncated".into());
+        }
+        let stored = match &self.source {
+            Source::Memory(bytes) => bytes[entry.offset as usize..end as usize].to_vec(),
+            Source::File(file, _) => {
+                let mut file = file.borrow_mut();
+                file.seek(SeekFrom::Start(entry.offset))
+                    .map_err(|e| e.to_string())?;
+                let mut stored = vec![0u8; entry.stored_len as usize];
+                file.read_exact(&mut stored).map_err(|e| e.to_string())?;
+                stored
+            }
+        };
+        if !entry.compressed {
+            if stored.len() as u64 != entry.size {
+                return Err(format!("\"{name}\" is corrupted"));
+            }
+            return Ok(stored);
+        }
+        #[cfg(feature = "zstd")]
+        {
+            // one byte past the expected size is enough to tell it's wrong,
+            // without trusting the stream to stop on its own
+            let mut data = Vec::new();
+            zstd::stream::read::Decoder::new(stored.as_slice())
+                .map_err(|e| e.to_string())?
+                .take(entry.size.saturating_add(1))
+                .read_to_end(&mut data)
+                .map_err(|e| e.to_string())?;
+            if data.len() as u64 != entry.size {
+                return Err(format!("\"{name}\" is corrupted"));
+            }
+            Ok(data)
+        }
+        #[cfg(not(feature = "zstd"))]
+        Err(format!(
+            "\"{name}\" is compressed; requires the zstd feature"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut builder = PackBuilder::new();
+        builder.add(Path::new("sprites/hero.png"), vec![1, 2, 3]);
+        builder.add(Path::new("./maps/../maps/1.tmx"), vec![4]);
+        if cfg!(feature = "zstd") {
+            builder.compression_level = Some(3);
+        }
+        let pack = Pack::from_bytes(builder.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            pack.names().collect::<Vec<_>>(),
+            ["maps/1.tmx", "sprites/hero.png"]
+        );
+        assert_eq!(
+            pack.read(Path::new("sprites/hero.png")).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert!(pack.read(Path::new("missing.png")).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_header() {
+        let mut builder = PackBuilder::new();
+        builder.add(Path::new("a"), vec![1, 2, 3]);
+        let bytes = builder.to_bytes().unwrap();
+        // header, name length, name
+        let offset_at = 12 + 4 + 1;
+        let stored_len_at = offset_at + 8;
+
+        let mut overflowing = bytes.clone();
+        overflowing[offset_at..offset_at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let pack = Pack::from_bytes(overflowing).unwrap();
+        assert!(pack.read(Path::new("a")).is_err());
+
+        let mut oversized = bytes.clone();
+        oversized[stored_len_at..stored_len_at + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        let pack = Pack::from_bytes(oversized.clone()).unwrap();
+        assert!(pack.read(Path::new("a")).is_err());
+
+        let path = std::env::temp_dir().join(format!("chimeric-pack-{}.pak", std::process::id()));
+        std::fs::write(&path, &oversized).unwrap();
+        let pack = Pack::open(&path).unwrap();
+        assert!(pack.read(Path::new("a")).is_err());
+        // a name length far past the end of the file
+        let mut long_name = bytes;
+        long_name[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &long_name).unwrap();
+        assert!(Pack::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,485 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use sdl2::rect::FPoint;
+
+use super::tilemap::{LayerVersion, TileLayer, EMPTY_TILE};
+
+/// grid which can be navigated by find_path
+pub trait CostGrid {
+    /// the cost of moving into the cell, or None if it's impassable (or out of
+    /// bounds)
+    fn cost(&self, x: i32, y: i32) -> Option<f32>;
+
+    /// lower bound of all costs. used to keep the heuristic admissible
+    fn min_cost(&self) -> f32 {
+        1.
+    }
+}
+
+/// empty tiles are passable with a cost of one
+impl CostGrid for TileLayer {
+    fn cost(&self, x: i32, y: i32) -> Option<f32> {
+        match self.get(x, y) {
+            Some(EMPTY_TILE) => Some(1.),
+            _ => None,
+        }
+    }
+}
+
+/// the costs of a tile layer's cells, by tile, kept between searches. only
+/// the cells changed in the layer since the last update are looked up again,
+/// so layers which are dug into or blown up aren't redone for each change.
+/// use one per layer
+#[derive(Debug, Clone)]
+pub struct CostMap {
+    /// the cost of moving into a cell with the tile, or None if impassable
+    pub tile_costs: HashMap<u32, Option<f32>>,
+    /// for tiles without a cost
+    pub default_cost: Option<f32>,
+    /// the layer the costs are of
+    version: Option<LayerVersion>,
+    size: (u32, u32),
+    /// row major
+    costs: Vec<Option<f32>>,
+}
+
+impl Default for CostMap {
+    /// like the layer itself: empty tiles cost one, others are impassable
+    fn default() -> Self {
+        Self {
+            tile_costs: HashMap::from([(EMPTY_TILE, Some(1.))]),
+            default_cost: None,
+            version: None,
+            size: (0, 0),
+            costs: Default::default(),
+        }
+    }
+}
+
+impl CostMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_cost(mut self, tile: u32, cost: Option<f32>) -> Self {
+        self.tile_costs.insert(tile, cost);
+        self
+    }
+
+    fn tile_cost(&self, tile: u32) -> Option<f32> {
+        *self.tile_costs.get(&tile).unwrap_or(&self.default_cost)
+    }
+
+    /// look up every cell again on the next update, e.g. after changing costs
+    pub fn invalidate(&mut self) {
+        self.version = None;
+    }
+
+    /// look up the cells changed since the last update, or every cell if that
+    /// isn't known (e.g. it's another layer). returns false if nothing changed
+    pub fn update(&mut self, layer: &TileLayer) -> bool {
+        if self.version == Some(layer.version()) {
+            return false;
+        }
+        match self
+            .version
+            .and_then(|version| layer.changes_since(version))
+        {
+            Some(changes) => {
+                for (x, y) in changes {
+                    let tile = layer.get(x, y).expect("changes are in bounds");
+                    self.costs[y as usize * self.size.0 as usize + x as usize] =
+                        self.tile_cost(tile);
+                }
+            }
+            None => {
+                self.size = (layer.width(), layer.height());
+                self.costs = layer.tiles().iter().map(|&t| self.tile_cost(t)).collect();
+            }
+        }
+        self.version = Some(layer.version());
+        true
+    }
+}
+
+impl CostGrid for CostMap {
+    fn cost(&self, x: i32, y: i32) -> Option<f32> {
+        if x < 0 || y < 0 || x as u32 >= self.size.0 || y as u32 >= self.size.1 {
+            return None;
+        }
+        self.costs[y as usize * self.size.0 as usize + x as usize]
+    }
+
+    /// the cheapest cost a tile could have, so it holds as tiles change
+    fn min_cost(&self) -> f32 {
+        let min = self
+            .tile_costs
+            .values()
+            .chain([&self.default_cost])
+            .flatten()
+            .fold(f32::INFINITY, |min, &cost| min.min(cost));
+        if min.is_finite() {
+            min
+        } else {
+            1.
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PathOptions {
+    /// allow diagonal moves. corners can't be cut; both adjacent cells must be
+    /// passable
+    pub diagonal: bool,
+    /// give up after this many cells have been expanded
+    pub max_expanded: Option<usize>,
+    /// if the goal can't be reached, return a path to the closest cell that
+    /// was found instead of None
+    pub allow_partial: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        Self {
+            diagonal: true,
+            max_expanded: None,
+            allow_partial: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// from the start to the goal (or to the closest reachable cell), inclusive
+    pub cells: Vec<(i32, i32)>,
+    /// false if this is a partial path
+    pub complete: bool,
+    pub cost: f32,
+}
+
+impl Path {
+    /// center of each cell in world coordinates
+    pub fn world_points(&self, layer: &TileLayer) -> Vec<FPoint> {
+        self.cells
+            .iter()
+            .map(|&(x, y)| {
+                FPoint::new(
+                    (x as f32 + 0.5) * layer.tile_width(),
+                    (y as f32 + 0.5) * layer.tile_height(),
+                )
+            })
+            .collect()
+    }
+}
+
+type Cell = (i32, i32);
+
+struct Open {
+    estimate: f32,
+    cell: (i32, i32),
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // min heap on estimate, ties broken deterministically
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+fn heuristic(from: (i32, i32), to: (i32, i32), diagonal: bool) -> f32 {
+    let dx = (from.0 - to.0).abs() as f32;
+    let dy = (from.1 - to.1).abs() as f32;
+    if diagonal {
+        dx.max(dy) + (std::f32::consts::SQRT_2 - 1.) * dx.min(dy)
+    } else {
+        dx + dy
+    }
+}
+
+/// a* from start to goal
+pub fn find_path<G: CostGrid + ?Sized>(
+    grid: &G,
+    start: (i32, i32),
+    goal: (i32, i32),
+    options: PathOptions,
+) -> Option<Path> {
+    grid.cost(start.0, start.1)?;
+    let min_cost = grid.min_cost();
+    let h = |cell: (i32, i32)| heuristic(cell, goal, options.diagonal) * min_cost;
+
+    let mut open: BinaryHeap<Open> = BinaryHeap::new();
+    // cell -> (cost so far, came from)
+    let mut visited: HashMap<Cell, (f32, Option<Cell>)> = HashMap::new();
+    visited.insert(start, (0., None));
+    open.push(Open {
+        estimate: h(start),
+        cell: start,
+    });
+    let mut closest = (h(start), start);
+    let mut expanded = 0;
+    let mut reached = false;
+
+    while let Some(Open { estimate, cell }) = open.pop() {
+        let cost_so_far = visited[&cell].0;
+        if estimate > cost_so_far + h(cell) {
+            continue; // stale entry
+        }
+        if cell == goal {
+            reached = true;
+            break;
+        }
+        if options.max_expanded.is_some_and(|max| expanded >= max) {
+            break;
+        }
+        expanded += 1;
+        if h(cell) < closest.0 {
+            closest = (h(cell), cell);
+        }
+
+        const ORTHOGONAL: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+        let diagonals: &[(i32, i32)] = if options.diagonal { &DIAGONAL } else { &[] };
+        for &(dx, dy) in ORTHOGONAL.iter().chain(diagonals) {
+            let next = (cell.0 + dx, cell.1 + dy);
+            let step_cost = match grid.cost(next.0, next.1) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
+            let step_cost = if dx != 0 && dy != 0 {
+                if grid.cost(cell.0 + dx, cell.1).is_none()
+                    || grid.cost(cell.0, cell.1 + dy).is_none()
+                {
+                    continue;
+                }
+                step_cost * std::f32::consts::SQRT_2
+            } else {
+                step_cost
+            };
+            let next_cost = cost_so_far + step_cost;
+            if visited.get(&next).is_some_and(|&(c, _)| c <= next_cost) {
+                continue;
+            }
+            visited.insert(next, (next_cost, Some(cell)));
+            open.push(Open {
+                estimate: next_cost + h(next),
+                cell: next,
+            });
+        }
+    }
+
+    let end = if reached {
+        goal
+    } else if options.allow_partial {
+        closest.1
+    } else {
+        return None;
+    };
+
+    let mut cells = vec![end];
+    let mut current = end;
+    while let Some(previous) = visited[&current].1 {
+        cells.push(previous);
+        current = previous;
+    }
+    cells.reverse();
+    Some(Path {
+        cells,
+        complete: reached,
+        cost: visited[&end].0,
+    })
+}
+
+/// true if a straight line between the centers of the cells only passes
+/// through passable cells. lines passing exactly through a corner require
+/// both cells touching that corner to be passable
+pub fn line_of_sight<G: CostGrid + ?Sized>(grid: &G, from: (i32, i32), to: (i32, i32)) -> bool {
+    let dx = (to.0 - from.0).abs();
+    let dy = (to.1 - from.1).abs();
+    let step_x = (to.0 - from.0).signum();
+    let step_y = (to.1 - from.1).signum();
+    let (mut x, mut y) = from;
+    // compare crossing times scaled by 2 * dx * dy to stay in integers
+    let mut error = dx - dy;
+    let dx2 = dx * 2;
+    let dy2 = dy * 2;
+    let mut remaining = dx + dy;
+    while remaining > 0 {
+        if grid.cost(x, y).is_none() {
+            return false;
+        }
+        match error.cmp(&0) {
+            Ordering::Greater => {
+                x += step_x;
+                error -= dy2;
+                remaining -= 1;
+            }
+            Ordering::Less => {
+                y += step_y;
+                error += dx2;
+                remaining -= 1;
+            }
+            Ordering::Equal => {
+                // passes exactly through a corner
+                if grid.cost(x + step_x, y).is_none() || grid.cost(x, y + step_y).is_none() {
+                    return false;
+                }
+                x += step_x;
+                y += step_y;
+                error += dx2 - dy2;
+                remaining -= 2;
+            }
+        }
+    }
+    grid.cost(to.0, to.1).is_some()
+}
+
+/// remove waypoints which can be skipped by walking in a straight line, so
+/// movement isn't restricted to grid directions
+pub fn smooth_path<G: CostGrid + ?Sized>(grid: &G, cells: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let mut smoothed: Vec<(i32, i32)> = Vec::new();
+    let mut anchor = match cells.first() {
+        Some(&first) => first,
+        None => return smoothed,
+    };
+    smoothed.push(anchor);
+    let mut i = 1;
+    while i < cells.len() {
+        // advance as far as possible while the anchor can see the next cell
+        let mut furthest = i;
+        while furthest + 1 < cells.len() && line_of_sight(grid, anchor, cells[furthest + 1]) {
+            furthest += 1;
+        }
+        anchor = cells[furthest];
+        smoothed.push(anchor);
+        i = furthest + 1;
+    }
+    smoothed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(rows: &[&str]) -> TileLayer {
+        let tiles: Vec<u32> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { 1 } else { 0 }))
+            .collect();
+        TileLayer::from_tiles(rows[0].len() as u32, rows.len() as u32, 1., 1., tiles).unwrap()
+    }
+
+    #[test]
+    fn test_find_path() {
+        let grid = layer(&[
+            ".....", //
+            ".###.", //
+            "...#.", //
+            "####.", //
+        ]);
+        let path = find_path(
+            &grid,
+            (0, 2),
+            (4, 3),
+            PathOptions {
+                diagonal: false,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(path.complete);
+        assert_eq!(path.cells.first(), Some(&(0, 2)));
+        assert_eq!(path.cells.last(), Some(&(4, 3)));
+        assert_eq!(path.cost, 9.);
+    }
+
+    #[test]
+    fn test_partial_path() {
+        let grid = layer(&[
+            "...#.", //
+            "...#.", //
+            "...#.", //
+        ]);
+        let options = PathOptions::default();
+        assert!(find_path(&grid, (0, 1), (4, 1), options).is_none());
+        let path = find_path(
+            &grid,
+            (0, 1),
+            (4, 1),
+            PathOptions {
+                allow_partial: true,
+                ..options
+            },
+        )
+        .unwrap();
+        assert!(!path.complete);
+        assert_eq!(path.cells.last(), Some(&(2, 1)));
+    }
+
+    #[test]
+    fn test_smooth() {
+        let grid = layer(&[
+            ".....", //
+            "..#..", //
+            ".....", //
+        ]);
+        let cells = [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (4, 1), (4, 2)];
+        assert_eq!(smooth_path(&grid, &cells), vec![(0, 0), (4, 0), (4, 2)]);
+        assert!(!line_of_sight(&grid, (0, 0), (4, 1)));
+        assert!(line_of_sight(&grid, (0, 2), (4, 2)));
+        assert!(!line_of_sight(&grid, (1, 0), (3, 2)));
+        assert!(line_of_sight(&grid, (3, 1), (4, 2)));
+    }
+
+    #[test]
+    fn test_explode_costs() {
+        // digging through the wall, with mud slowing the way
+        let mut grid = layer(&[
+            "....", //
+            "####", //
+        ]);
+        let mut costs = CostMap::new().with_cost(2, Some(3.));
+        costs.update(&grid);
+        assert_eq!(costs.cost(1, 1), None);
+        grid.explode(FPoint::new(1.5, 1.5), 0.5);
+        grid.set(2, 1, 2);
+        assert!(costs.update(&grid));
+        assert!(!costs.update(&grid));
+        assert_eq!(costs.cost(1, 1), Some(1.));
+        assert_eq!(costs.cost(2, 1), Some(3.));
+        assert_eq!(costs.cost(3, 1), None);
+        assert_eq!(costs.min_cost(), 1.);
+
+        // another layer at the same revision is looked up from scratch
+        let mut other = layer(&[
+            "####", //
+            "....", //
+        ]);
+        while other.revision() < grid.revision() {
+            let tile = other.get(0, 1).unwrap();
+            other.set(0, 1, 1 - tile);
+        }
+        assert!(costs.update(&other));
+        assert_eq!(costs.cost(1, 0), None);
+        assert_eq!(costs.cost(1, 1), Some(1.));
+    }
+}
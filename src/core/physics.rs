@@ -0,0 +1,365 @@
+use std::time::Duration;
+
+use sdl2::rect::{FPoint, FRect};
+
+use super::{
+    spatial::overlaps,
+    tilemap::{LayerVersion, TileLayer},
+};
+
+/// number of times a body can be stopped and slide along a surface in a
+/// single step
+const MAX_SLIDES: usize = 4;
+
+/// which sides of a body were blocked during the last physics step
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Blocked {
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+}
+
+/// moved by the world's physics phase. it doesn't push or get pushed by other
+/// bodies; it's only stopped by static geometry
+#[derive(Debug, Clone, Copy)]
+pub struct KinematicBody {
+    pub aabb: FRect,
+    pub velocity: FPoint,
+    pub acceleration: FPoint,
+    /// multiplied with the world's gravity
+    pub gravity_scale: f32,
+    /// set by each physics step
+    pub blocked: Blocked,
+}
+
+impl KinematicBody {
+    pub fn new(aabb: FRect) -> Self {
+        Self {
+            aabb,
+            velocity: FPoint::new(0., 0.),
+            acceleration: FPoint::new(0., 0.),
+            gravity_scale: 1.,
+            blocked: Default::default(),
+        }
+    }
+
+    /// convenience for platformers
+    pub fn on_ground(&self) -> bool {
+        self.blocked.down
+    }
+}
+
+/// the solid tiles of a layer merged into a rect per horizontal run, so
+/// bodies are tested against fewer rects and don't catch on the seams
+/// between tiles. only the rows with tiles changed since are merged again
+#[derive(Debug, Clone, Default)]
+pub struct TileSolids {
+    /// the layer the runs are of
+    version: Option<LayerVersion>,
+    /// per row, the first and last x of each run
+    rows: Vec<Vec<(i32, i32)>>,
+}
+
+impl TileSolids {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_current(&self, layer: &TileLayer) -> bool {
+        self.version == Some(layer.version())
+    }
+
+    /// merge the rows changed since the last update, or every row if that
+    /// isn't known (e.g. it's another layer)
+    pub fn update(&mut self, layer: &TileLayer) {
+        if self.is_current(layer) {
+            return;
+        }
+        match self
+            .version
+            .and_then(|version| layer.changes_since(version))
+        {
+            Some(changes) => {
+                let mut rows: Vec<i32> = changes.into_iter().map(|(_, y)| y).collect();
+                rows.sort_unstable();
+                rows.dedup();
+                for y in rows {
+                    self.rows[y as usize] = runs(layer, y);
+                }
+            }
+            None => {
+                self.rows = (0..layer.height() as i32).map(|y| runs(layer, y)).collect();
+            }
+        }
+        self.version = Some(layer.version());
+    }
+
+    /// the runs in the tile coordinates (inclusive), as rects
+    fn solids_in(&self, layer: &TileLayer, tiles: (i32, i32, i32, i32), solids: &mut Vec<FRect>) {
+        let (x0, y0, x1, y1) = tiles;
+        for y in y0..=y1 {
+            for &(first, last) in self.rows[y as usize].iter() {
+                if first <= x1 && last >= x0 {
+                    let rect = layer.tile_rect(first, y);
+                    let width = (last - first + 1) as f32 * layer.tile_width();
+                    solids.push(FRect::new(rect.x(), rect.y(), width, rect.height()));
+                }
+            }
+        }
+    }
+}
+
+/// the occupied runs of a row
+fn runs(layer: &TileLayer, y: i32) -> Vec<(i32, i32)> {
+    let mut runs: Vec<(i32, i32)> = Vec::new();
+    for x in 0..layer.width() as i32 {
+        if !layer.is_occupied(x, y) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.1 == x - 1 => run.1 = x,
+            _ => runs.push((x, x)),
+        }
+    }
+    runs
+}
+
+/// static geometry and settings used by the world's physics phase
+pub struct Physics {
+    /// units per second squared. e.g. positive y for a platformer, zero for
+    /// top down
+    pub gravity: FPoint,
+    pub static_colliders: Vec<FRect>,
+    /// every non empty tile is solid
+    pub tiles: Option<TileLayer>,
+    /// the tiles' collision rects. kept up to date by sync_tiles; until then,
+    /// the tiles are tested one by one
+    pub tile_solids: TileSolids,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            gravity: FPoint::new(0., 0.),
+            static_colliders: Default::default(),
+            tiles: None,
+            tile_solids: Default::default(),
+        }
+    }
+}
+
+impl Physics {
+    /// static rects which could be hit by a box in the region
+    fn solids_in(&self, region: FRect) -> Vec<FRect> {
+        let mut solids: Vec<FRect> = self
+            .static_colliders
+            .iter()
+            .copied()
+            .filter(|&rect| overlaps(rect, region))
+            .collect();
+        if let Some(tiles) = &self.tiles {
+            if let Some((x0, y0, x1, y1)) = tiles.tiles_in(region) {
+                if self.tile_solids.is_current(tiles) {
+                    self.tile_solids
+                        .solids_in(tiles, (x0, y0, x1, y1), &mut solids);
+                    return solids;
+                }
+                for y in y0..=y1 {
+                    for x in x0..=x1 {
+                        if tiles.is_occupied(x, y) {
+                            solids.push(tiles.tile_rect(x, y));
+                        }
+                    }
+                }
+            }
+        }
+        solids
+    }
+
+    /// bring the tiles' collision rects up to date with changes to the tiles,
+    /// e.g. after digging. done by the world before its physics phase
+    pub fn sync_tiles(&mut self) {
+        if let Some(tiles) = &self.tiles {
+            self.tile_solids.update(tiles);
+        }
+    }
+
+    /// integrate the body's velocity then move it, stopping and sliding along
+    /// any static geometry in the way
+    pub fn step(&self, body: &mut KinematicBody, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        body.velocity += (body.acceleration + self.gravity * body.gravity_scale) * dt;
+        body.blocked = Default::default();
+
+        let mut delta = body.velocity * dt;
+        let swept = swept_bounds(body.aabb, delta);
+        let solids = self.solids_in(swept);
+
+        for _ in 0..MAX_SLIDES {
+            if delta.x() == 0. && delta.y() == 0. {
+                break;
+            }
+            let hit = solids
+                .iter()
+                .filter_map(|&solid| sweep(body.aabb, delta, solid))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            let (t, normal) = match hit {
+                Some(hit) => hit,
+                None => {
+                    body.aabb.set_x(body.aabb.x() + delta.x());
+                    body.aabb.set_y(body.aabb.y() + delta.y());
+                    break;
+                }
+            };
+            body.aabb.set_x(body.aabb.x() + delta.x() * t);
+            body.aabb.set_y(body.aabb.y() + delta.y() * t);
+            let remaining = delta * (1. - t);
+            // remove the component going into the surface, and slide with what
+            // remains
+            if normal.x() != 0. {
+                body.velocity.x = 0.;
+                delta = FPoint::new(0., remaining.y());
+                if normal.x() < 0. {
+                    body.blocked.right = true;
+                } else {
+                    body.blocked.left = true;
+                }
+            } else {
+                body.velocity.y = 0.;
+                delta = FPoint::new(remaining.x(), 0.);
+                if normal.y() < 0. {
+                    body.blocked.down = true;
+                } else {
+                    body.blocked.up = true;
+                }
+            }
+        }
+    }
+}
+
+fn swept_bounds(aabb: FRect, delta: FPoint) -> FRect {
+    let left = aabb.left().min(aabb.left() + delta.x());
+    let top = aabb.top().min(aabb.top() + delta.y());
+    let right = aabb.right().max(aabb.right() + delta.x());
+    let bottom = aabb.bottom().max(aabb.bottom() + delta.y());
+    FRect::new(left, top, right - left, bottom - top)
+}
+
+/// if the moving box hits the static box, the fraction of delta at which it
+/// first touches, and the surface normal. boxes which already overlap are
+/// ignored so bodies can escape geometry they were placed inside
+pub fn sweep(moving: FRect, delta: FPoint, solid: FRect) -> Option<(f32, FPoint)> {
+    let axis = |d: f32, min: f32, max: f32, solid_min: f32, solid_max: f32| {
+        if d > 0. {
+            Some(((solid_min - max) / d, (solid_max - min) / d))
+        } else if d < 0. {
+            Some(((solid_max - min) / d, (solid_min - max) / d))
+        } else if max <= solid_min || min >= solid_max {
+            None
+        } else {
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        }
+    };
+    let (x_entry, x_exit) = axis(
+        delta.x(),
+        moving.left(),
+        moving.right(),
+        solid.left(),
+        solid.right(),
+    )?;
+    let (y_entry, y_exit) = axis(
+        delta.y(),
+        moving.top(),
+        moving.bottom(),
+        solid.top(),
+        solid.bottom(),
+    )?;
+    let entry = x_entry.max(y_entry);
+    let exit = x_exit.min(y_exit);
+    if entry > exit || !(0. ..1.).contains(&entry) {
+        return None;
+    }
+    let normal = if x_entry > y_entry {
+        FPoint::new(-delta.x().signum(), 0.)
+    } else {
+        FPoint::new(0., -delta.y().signum())
+    };
+    Some((entry, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep() {
+        let moving = FRect::new(0., 0., 10., 10.);
+        let wall = FRect::new(20., -50., 10., 100.);
+        let (t, normal) = sweep(moving, FPoint::new(20., 5.), wall).unwrap();
+        assert_eq!(t, 0.5);
+        assert_eq!(normal, FPoint::new(-1., 0.));
+        assert!(sweep(moving, FPoint::new(5., 0.), wall).is_none());
+        assert!(sweep(moving, FPoint::new(-20., 0.), wall).is_none());
+    }
+
+    #[test]
+    fn test_land_and_slide() {
+        let mut tiles = TileLayer::new(10, 10, 10., 10.);
+        for x in 0..10 {
+            tiles.set(x, 5, 1);
+        }
+        let physics = Physics {
+            gravity: FPoint::new(0., 100.),
+            tiles: Some(tiles),
+            ..Default::default()
+        };
+        let mut body = KinematicBody::new(FRect::new(0., 30., 10., 10.));
+        body.velocity = FPoint::new(20., 0.);
+        for _ in 0..120 {
+            physics.step(&mut body, Duration::from_secs_f32(1. / 60.));
+        }
+        assert!(body.on_ground());
+        assert_eq!(body.aabb.bottom(), 50.);
+        assert!(body.aabb.x() > 30.);
+    }
+
+    #[test]
+    fn test_explode_solids() {
+        let mut tiles = TileLayer::new(10, 10, 10., 10.);
+        for x in 0..10 {
+            tiles.set(x, 5, 1);
+        }
+        let mut physics = Physics {
+            tiles: Some(tiles),
+            ..Default::default()
+        };
+        // the floor is one rect, until a hole is dug in it
+        physics.sync_tiles();
+        let floor = FRect::new(0., 45., 100., 10.);
+        assert_eq!(physics.solids_in(floor).len(), 1);
+        let tiles = physics.tiles.as_mut().unwrap();
+        tiles.explode(FPoint::new(55., 55.), 5.);
+        assert!(!physics.tile_solids.is_current(tiles));
+        physics.sync_tiles();
+        assert_eq!(
+            physics.solids_in(floor),
+            [
+                FRect::new(0., 50., 50., 10.),
+                FRect::new(60., 50., 40., 10.)
+            ]
+        );
+
+        // the next level's layer, the same size and at the same revision
+        let mut next = TileLayer::new(10, 10, 10., 10.);
+        for x in 0..physics.tiles.as_ref().unwrap().revision() as i32 {
+            next.set(x % 3, 5 + x / 3, 1);
+        }
+        physics.tiles = Some(next);
+        assert!(!physics
+            .tile_solids
+            .is_current(physics.tiles.as_ref().unwrap()));
+        physics.sync_tiles();
+        assert_eq!(physics.solids_in(floor), [FRect::new(0., 50., 30., 10.)]);
+    }
+}
@@ -0,0 +1,272 @@
+use sdl2::event::Event;
+
+use super::{events::EngineEvent, input::InputState};
+
+/// what a player plays with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Device {
+    /// and the mouse
+    Keyboard,
+    /// by joystick instance id. the controller must be open, e.g. by a
+    /// ControllerSystem, for its events to arrive
+    Controller(u32),
+}
+
+impl Device {
+    /// the device the event came from, if any
+    pub fn of(event: &EngineEvent) -> Option<Self> {
+        match event {
+            EngineEvent::KeyDown { .. }
+            | EngineEvent::KeyUp { .. }
+            | EngineEvent::MouseMotion { .. }
+            | EngineEvent::MouseButtonDown { .. }
+            | EngineEvent::MouseButtonUp { .. }
+            | EngineEvent::MouseWheel { .. }
+            | EngineEvent::TextInput { .. }
+            | EngineEvent::TextEditing { .. } => Some(Device::Keyboard),
+            EngineEvent::Other(
+                Event::ControllerButtonDown { which, .. }
+                | Event::ControllerButtonUp { which, .. }
+                | Event::ControllerAxisMotion { which, .. }
+                | Event::ControllerDeviceRemoved { which, .. },
+            ) => Some(Device::Controller(*which)),
+            _ => None,
+        }
+    }
+
+    /// a button press, which joins (or reconnects) an unassigned device
+    fn is_press(event: &EngineEvent) -> bool {
+        matches!(
+            event,
+            EngineEvent::KeyDown { repeat: false, .. }
+                | EngineEvent::MouseButtonDown { .. }
+                | EngineEvent::Other(Event::ControllerButtonDown { .. })
+        )
+    }
+}
+
+/// a change to the players, returned from update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotEvent {
+    Joined {
+        player: usize,
+        device: Device,
+    },
+    /// the player's controller was unplugged. their slot is kept for them
+    Disconnected {
+        player: usize,
+    },
+    /// a device took over a disconnected player's slot
+    Reconnected {
+        player: usize,
+        device: Device,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    device: Device,
+    connected: bool,
+    input: InputState,
+}
+
+/// assigns devices to player indices as they press a button to join, and
+/// gives each player an input state fed only by their own device. when a
+/// controller is unplugged its player keeps the slot, and the next unassigned
+/// device to press a button reconnects to it
+#[derive(Debug, Clone)]
+pub struct PlayerSlots {
+    slots: Vec<Option<Slot>>,
+    pub max_players: usize,
+    /// whether unassigned devices join on a press, e.g. only in the lobby.
+    /// reconnecting to a disconnected slot is always allowed
+    pub accepting: bool,
+}
+
+impl PlayerSlots {
+    pub fn new(max_players: usize) -> Self {
+        Self {
+            slots: Vec::new(),
+            max_players,
+            accepting: true,
+        }
+    }
+
+    /// start a new frame with this frame's events. events from a player's
+    /// device, and those from no device (e.g. focus changes), go to the
+    /// player's input state
+    pub fn update(&mut self, events: &[EngineEvent]) -> Vec<SlotEvent> {
+        let mut changes = Vec::new();
+        for event in events {
+            let Some(device) = Device::of(event) else {
+                continue;
+            };
+            if let EngineEvent::Other(Event::ControllerDeviceRemoved { .. }) = event {
+                if let Some(player) = self.player_of(device) {
+                    let slot = self.slots[player].as_mut().expect("player has a slot");
+                    slot.connected = false;
+                    slot.input = InputState::new();
+                    changes.push(SlotEvent::Disconnected { player });
+                }
+                continue;
+            }
+            if Device::is_press(event) && self.player_of(device).is_none() {
+                if let Some(change) = self.assign(device) {
+                    changes.push(change);
+                }
+            }
+        }
+
+        for slot in self.slots.iter_mut().flatten() {
+            let events: Vec<EngineEvent> = events
+                .iter()
+                .filter(|e| slot.connected && Device::of(e).is_none_or(|d| d == slot.device))
+                .cloned()
+                .collect();
+            slot.input.update(&events);
+        }
+        changes
+    }
+
+    /// a disconnected slot first, then the lowest free one
+    fn assign(&mut self, device: Device) -> Option<SlotEvent> {
+        let slot = Slot {
+            device,
+            connected: true,
+            input: InputState::new(),
+        };
+        let disconnected = self.disconnected().next();
+        if let Some(player) = disconnected {
+            self.slots[player] = Some(slot);
+            return Some(SlotEvent::Reconnected { player, device });
+        }
+        if !self.accepting {
+            return None;
+        }
+        let player = match self.slots.iter().position(Option::is_none) {
+            Some(player) => player,
+            None if self.slots.len() < self.max_players => {
+                self.slots.push(None);
+                self.slots.len() - 1
+            }
+            None => return None,
+        };
+        self.slots[player] = Some(slot);
+        Some(SlotEvent::Joined { player, device })
+    }
+
+    /// a connected player using the device
+    pub fn player_of(&self, device: Device) -> Option<usize> {
+        self.slots.iter().position(|s| {
+            s.as_ref()
+                .is_some_and(|s| s.connected && s.device == device)
+        })
+    }
+
+    /// the player's input, to query with an InputMap. None if the slot is free
+    pub fn input(&self, player: usize) -> Option<&InputState> {
+        self.slots.get(player)?.as_ref().map(|s| &s.input)
+    }
+
+    /// None if the slot is free. a disconnected player's last device
+    pub fn device(&self, player: usize) -> Option<Device> {
+        self.slots.get(player)?.as_ref().map(|s| s.device)
+    }
+
+    /// indices of the players who have joined, including disconnected ones
+    pub fn players(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.slots.len()).filter(|p| self.slots[*p].is_some())
+    }
+
+    /// players waiting for a device, lowest first
+    pub fn disconnected(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.slots.len()).filter(|p| self.slots[*p].as_ref().is_some_and(|s| !s.connected))
+    }
+
+    /// true while a player is disconnected, e.g. to pause with a prompt to
+    /// reconnect
+    pub fn waiting(&self) -> bool {
+        self.disconnected().next().is_some()
+    }
+
+    /// free the slot, e.g. when a disconnected player won't come back
+    pub fn remove(&mut self, player: usize) {
+        if let Some(slot) = self.slots.get_mut(player) {
+            *slot = None;
+        }
+        while self.slots.last().is_some_and(Option::is_none) {
+            self.slots.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::{controller::Button, mouse::MouseButton};
+
+    use super::*;
+    use crate::core::input::Binding;
+
+    fn button(which: u32) -> EngineEvent {
+        EngineEvent::Other(Event::ControllerButtonDown {
+            timestamp: 0,
+            which,
+            button: Button::A,
+        })
+    }
+
+    #[test]
+    fn test_slots() {
+        let mut slots = PlayerSlots::new(2);
+        let click = EngineEvent::MouseButtonDown {
+            window: None,
+            button: MouseButton::Left,
+            clicks: 1,
+            position: sdl2::rect::FPoint::new(0., 0.),
+        };
+        assert_eq!(
+            slots.update(&[button(7), click.clone()]),
+            [
+                SlotEvent::Joined {
+                    player: 0,
+                    device: Device::Controller(7)
+                },
+                SlotEvent::Joined {
+                    player: 1,
+                    device: Device::Keyboard
+                }
+            ]
+        );
+        // each player only sees their own device
+        let a = Binding::GamepadButton(Button::A);
+        assert!(slots.input(0).unwrap().just_pressed(a));
+        assert!(!slots.input(1).unwrap().just_pressed(a));
+        assert!(slots.input(1).unwrap().mouse_down(MouseButton::Left));
+        // full
+        assert!(slots.update(&[button(8)]).is_empty());
+
+        let removed = EngineEvent::Other(Event::ControllerDeviceRemoved {
+            timestamp: 0,
+            which: 7,
+        });
+        assert_eq!(
+            slots.update(&[removed]),
+            [SlotEvent::Disconnected { player: 0 }]
+        );
+        assert!(slots.waiting());
+        slots.accepting = false;
+        assert_eq!(
+            slots.update(&[button(8)]),
+            [SlotEvent::Reconnected {
+                player: 0,
+                device: Device::Controller(8)
+            }]
+        );
+        assert!(!slots.waiting());
+        assert_eq!(slots.player_of(Device::Controller(8)), Some(0));
+
+        slots.remove(1);
+        assert_eq!(slots.players().collect::<Vec<_>>(), [0]);
+        assert!(slots.update(&[click]).is_empty());
+    }
+}
@@ -0,0 +1,405 @@
+use std::{collections::BTreeSet, path::Path};
+
+use sdl2::rect::FRect;
+use wasmtime::{
+    Caller, Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+use super::{
+    entity::EntityId,
+    input::{InputMap, InputState},
+    renderer::DrawParams,
+    system::ChimericSystem,
+    trace::log_info,
+    world::World,
+};
+
+/// the version of the imports and exports below. a plugin exports
+/// `chimeric_abi` returning the version it was built against, and is refused
+/// if it differs.
+///
+/// imported from the "chimeric" module; strings are a pointer and length in
+/// the plugin's exported memory:
+/// - `log(ptr, len)`
+/// - `time() -> f64`: the world's simulated time in seconds
+/// - `spawn(ptr, len, x: f32, y: f32) -> i32`: a prefab, with x and y as
+///   overrides. 0 if over the quota
+/// - `despawn(id: i64)`
+/// - `draw(ptr, len, x, y, w, h: f32) -> i32`: an image. 0 if over the quota
+/// - `play_sound(ptr, len)`
+/// - `action_down(ptr, len) -> i32` and `action_pressed(ptr, len) -> i32`
+///
+/// exported by the plugin: `memory`, `chimeric_abi() -> i32`,
+/// `update(dt: f32)`, and optionally `init()` and `draw()`
+pub const PLUGIN_ABI: i32 = 1;
+
+/// longest string a plugin can pass
+const MAX_STRING: usize = 4096;
+
+/// limits on what each plugin can use
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginQuota {
+    /// wasm instructions (roughly) per call into the plugin
+    pub fuel: u64,
+    /// bytes of linear memory
+    pub memory: usize,
+    pub spawns_per_update: usize,
+    pub draws_per_frame: usize,
+}
+
+impl Default for PluginQuota {
+    fn default() -> Self {
+        Self {
+            fuel: 10_000_000,
+            memory: 16 * 1024 * 1024,
+            spawns_per_update: 64,
+            draws_per_frame: 1024,
+        }
+    }
+}
+
+enum PluginCommand {
+    Spawn { prefab: String, x: f32, y: f32 },
+    Despawn(EntityId),
+}
+
+struct PluginState {
+    name: String,
+    limits: StoreLimits,
+    quota: PluginQuota,
+    time: f64,
+    down: BTreeSet<String>,
+    pressed: BTreeSet<String>,
+    /// applied to the world after the plugin's update returns
+    commands: Vec<PluginCommand>,
+    spawns: usize,
+    draws: Vec<(String, FRect)>,
+    sounds: Vec<String>,
+}
+
+fn read_str(caller: &mut Caller<'_, PluginState>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|e| e.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("plugin exports no memory"))?;
+    let (start, len) = (ptr as u32 as usize, len as u32 as usize);
+    if len > MAX_STRING {
+        return Err(wasmtime::Error::msg("string is too long"));
+    }
+    let bytes = memory
+        .data(&caller)
+        .get(start..start + len)
+        .ok_or_else(|| wasmtime::Error::msg("string is out of bounds"))?;
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+/// loads mods compiled to wasm and runs them sandboxed: a plugin sees only
+/// the engine abi (see PLUGIN_ABI), and is held to a quota of fuel, memory,
+/// spawns and draws
+pub struct PluginHost {
+    engine: Engine,
+    linker: Linker<PluginState>,
+    /// given to plugins as they're loaded
+    pub quota: PluginQuota,
+}
+
+impl PluginHost {
+    pub fn new() -> Result<Self, String> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| e.to_string())?;
+        let mut linker: Linker<PluginState> = Linker::new(&engine);
+        Self::link(&mut linker).map_err(|e| e.to_string())?;
+        Ok(Self {
+            engine,
+            linker,
+            quota: Default::default(),
+        })
+    }
+
+    fn link(linker: &mut Linker<PluginState>) -> wasmtime::Result<()> {
+        linker.func_wrap(
+            "chimeric",
+            "log",
+            |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+                let line = read_str(&mut caller, ptr, len)?;
+                log_info!("[{}] {line}", caller.data().name);
+                Ok(())
+            },
+        )?;
+        linker.func_wrap("chimeric", "time", |caller: Caller<'_, PluginState>| {
+            caller.data().time
+        })?;
+        linker.func_wrap(
+            "chimeric",
+            "spawn",
+            |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32, x: f32, y: f32| {
+                let prefab = read_str(&mut caller, ptr, len)?;
+                let state = caller.data_mut();
+                if state.spawns >= state.quota.spawns_per_update {
+                    return Ok(0);
+                }
+                state.spawns += 1;
+                state.commands.push(PluginCommand::Spawn { prefab, x, y });
+                Ok(1)
+            },
+        )?;
+        linker.func_wrap(
+            "chimeric",
+            "despawn",
+            |mut caller: Caller<'_, PluginState>, id: i64| {
+                let id = EntityId(id as u64);
+                caller.data_mut().commands.push(PluginCommand::Despawn(id));
+            },
+        )?;
+        linker.func_wrap(
+            "chimeric",
+            "draw",
+            |mut caller: Caller<'_, PluginState>,
+             ptr: i32,
+             len: i32,
+             x: f32,
+             y: f32,
+             w: f32,
+             h: f32| {
+                let path = read_str(&mut caller, ptr, len)?;
+                let state = caller.data_mut();
+                if state.draws.len() >= state.quota.draws_per_frame {
+                    return Ok(0);
+                }
+                state.draws.push((path, FRect::new(x, y, w, h)));
+                Ok(1)
+            },
+        )?;
+        linker.func_wrap(
+            "chimeric",
+            "play_sound",
+            |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+                let path = read_str(&mut caller, ptr, len)?;
+                caller.data_mut().sounds.push(path);
+                Ok(())
+            },
+        )?;
+        linker.func_wrap(
+            "chimeric",
+            "action_down",
+            |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+                let action = read_str(&mut caller, ptr, len)?;
+                Ok(caller.data().down.contains(&action) as i32)
+            },
+        )?;
+        linker.func_wrap(
+            "chimeric",
+            "action_pressed",
+            |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+                let action = read_str(&mut caller, ptr, len)?;
+                Ok(caller.data().pressed.contains(&action) as i32)
+            },
+        )?;
+        Ok(())
+    }
+
+    /// compile and instantiate a plugin from wasm (or wat) bytes, checking its
+    /// abi version and calling its init
+    pub fn load(&self, name: &str, wasm: &[u8]) -> Result<Plugin, String> {
+        let err = |e: wasmtime::Error| format!("plugin \"{}\": {:#}", name, e);
+        let module = Module::new(&self.engine, wasm).map_err(err)?;
+        let state = PluginState {
+            name: name.to_owned(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.quota.memory)
+                .instances(1)
+                .build(),
+            quota: self.quota.clone(),
+            time: 0.,
+            down: Default::default(),
+            pressed: Default::default(),
+            commands: Default::default(),
+            spawns: 0,
+            draws: Default::default(),
+            sounds: Default::default(),
+        };
+        let mut store = Store::new(&self.engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.quota.fuel).map_err(err)?;
+        let instance = self.linker.instantiate(&mut store, &module).map_err(err)?;
+
+        let abi = instance
+            .get_typed_func::<(), i32>(&mut store, "chimeric_abi")
+            .map_err(err)?
+            .call(&mut store, ())
+            .map_err(err)?;
+        if abi != PLUGIN_ABI {
+            return Err(format!(
+                "plugin \"{}\" was built for abi {}, not {}",
+                name, abi, PLUGIN_ABI
+            ));
+        }
+        let update = instance
+            .get_typed_func::<f32, ()>(&mut store, "update")
+            .map_err(err)?;
+        let draw = instance.get_typed_func::<(), ()>(&mut store, "draw").ok();
+        if let Ok(init) = instance.get_typed_func::<(), ()>(&mut store, "init") {
+            init.call(&mut store, ()).map_err(err)?;
+        }
+        Ok(Plugin {
+            store,
+            update,
+            draw,
+        })
+    }
+
+    /// named after the file
+    pub fn load_file(&self, path: &Path) -> Result<Plugin, String> {
+        let wasm = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.load(&name, &wasm)
+    }
+}
+
+/// a loaded mod. once it has returned an error (e.g. it ran out of fuel) it
+/// should be unloaded
+pub struct Plugin {
+    store: Store<PluginState>,
+    update: TypedFunc<f32, ()>,
+    draw: Option<TypedFunc<(), ()>>,
+}
+
+impl Plugin {
+    pub fn name(&self) -> &str {
+        &self.store.data().name
+    }
+
+    fn err(&self, e: wasmtime::Error) -> String {
+        format!("plugin \"{}\": {:#}", self.name(), e)
+    }
+
+    /// the actions of the input map the plugin sees this frame
+    pub fn set_input(&mut self, map: &InputMap, state: &InputState) {
+        let data = self.store.data_mut();
+        data.down = map
+            .actions()
+            .filter(|a| map.is_down(state, a))
+            .map(str::to_owned)
+            .collect();
+        data.pressed = map
+            .actions()
+            .filter(|a| map.just_pressed(state, a))
+            .map(str::to_owned)
+            .collect();
+    }
+
+    /// call the plugin's update with the world's timestep, then apply what it
+    /// asked for. call once per world update, e.g. from an entity
+    pub fn update(&mut self, world: &mut World) -> Result<(), String> {
+        let data = self.store.data_mut();
+        data.time = world.time().as_secs_f64();
+        data.spawns = 0;
+        let fuel = data.quota.fuel;
+        self.store.set_fuel(fuel).map_err(|e| self.err(e))?;
+        let dt = world.timestep().as_secs_f32();
+        let result = self.update.call(&mut self.store, dt);
+        // whatever it managed before failing still applies
+        for command in std::mem::take(&mut self.store.data_mut().commands) {
+            match command {
+                PluginCommand::Spawn { prefab, x, y } => {
+                    let overrides = toml::Table::from_iter([
+                        ("x".to_owned(), toml::Value::Float(x as f64)),
+                        ("y".to_owned(), toml::Value::Float(y as f64)),
+                    ]);
+                    world.spawn_prefab(&prefab, &overrides)?;
+                }
+                PluginCommand::Despawn(id) => world.despawn(id),
+            }
+        }
+        result.map_err(|e| self.err(e))
+    }
+
+    /// call the plugin's draw, if it has one, and draw the images it asked for
+    pub fn draw(&mut self, system: &mut ChimericSystem, window_name: &str) -> Result<(), String> {
+        let Some(draw) = self.draw.clone() else {
+            return Ok(());
+        };
+        let fuel = self.store.data().quota.fuel;
+        self.store.set_fuel(fuel).map_err(|e| self.err(e))?;
+        let result = draw.call(&mut self.store, ());
+        for (path, dst) in std::mem::take(&mut self.store.data_mut().draws) {
+            system.draw(
+                window_name,
+                Path::new(&path),
+                &[DrawParams::new(None, Some(dst))],
+            )?;
+        }
+        result.map_err(|e| self.err(e))
+    }
+
+    /// sounds the plugin played since the last call, for the game to give to
+    /// its AudioSystem
+    pub fn take_sounds(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.store.data_mut().sounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::entity::Entity;
+
+    struct Crate;
+
+    impl Entity for Crate {
+        fn update(&mut self, _world: &mut World) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    const SPAWNER: &str = r#"
+        (module
+            (import "chimeric" "spawn" (func $spawn (param i32 i32 f32 f32) (result i32)))
+            (import "chimeric" "play_sound" (func $play (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "crate")
+            (data (i32.const 8) "boom.wav")
+            (func (export "chimeric_abi") (result i32) i32.const 1)
+            (func (export "init") i32.const 8 i32.const 8 call $play)
+            (func (export "update") (param f32)
+                (drop (call $spawn (i32.const 0) (i32.const 5) (f32.const 1) (f32.const 2)))
+                (drop (call $spawn (i32.const 0) (i32.const 5) (f32.const 3) (f32.const 4)))))
+    "#;
+
+    #[test]
+    fn test_plugins() {
+        let mut host = PluginHost::new().unwrap();
+        host.quota.spawns_per_update = 1;
+        let mut world = World::with_seed(0);
+        world.prefabs.register_constructor("crate", |params| {
+            assert_eq!(params["x"].as_float(), Some(1.));
+            Ok(Box::new(Crate))
+        });
+        world.prefabs.load_str("[crate]").unwrap();
+
+        let mut plugin = host.load("spawner", SPAWNER.as_bytes()).unwrap();
+        assert_eq!(plugin.take_sounds(), ["boom.wav"]);
+        plugin.update(&mut world).unwrap();
+        // the second spawn was over the quota
+        assert_eq!(world.len(), 1);
+
+        let looping = SPAWNER.replace(
+            "(func (export \"update\") (param f32)",
+            "(func (export \"update\") (param f32) (loop (br 0)))\n(func",
+        );
+        let mut plugin = host.load("looping", looping.as_bytes()).unwrap();
+        assert!(plugin.update(&mut world).is_err());
+
+        let old = SPAWNER.replace("i32.const 1)", "i32.const 0)");
+        assert!(host.load("old", old.as_bytes()).is_err());
+        let greedy = SPAWNER.replace(
+            "(memory (export \"memory\") 1)",
+            "(memory (export \"memory\") 1000)",
+        );
+        assert!(host.load("greedy", greedy.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use std::{collections::HashMap, time::Duration};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, Rect},
+};
+
+use super::{lighting::Lighting, rng::Rng};
+
+/// offsets the whole frame by a random amount each update. the amplitude
+/// decays linearly to zero
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shake {
+    /// max offset in logical pixels
+    pub amplitude: f32,
+    /// amplitude lost per second
+    pub decay: f32,
+}
+
+/// exact rgb matches (alpha is ignored) and their replacements
+pub type Palette = HashMap<(u8, u8, u8), Color>;
+
+/// the side a wipe covers the frame from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WipeDirection {
+    FromLeft,
+    FromRight,
+    FromTop,
+    FromBottom,
+}
+
+/// a kind of color blindness, by the cone type which is missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorVision {
+    /// red
+    Protanopia,
+    /// green
+    Deuteranopia,
+    /// blue
+    Tritanopia,
+}
+
+/// a color blindness filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorBlindMode {
+    /// show the frame as it'd be seen, e.g. to check a game is playable
+    Simulate(ColorVision),
+    /// shift the colors which can't be told apart into ones which can
+    Daltonize(ColorVision),
+}
+
+/// from linear rgb to lms cone responses, and back (daltonize.org)
+const RGB_TO_LMS: [[f32; 3]; 3] = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+const LMS_TO_RGB: [[f32; 3]; 3] = [
+    [0.080_944_45, -0.130_504_41, 0.116_721_07],
+    [-0.010_248_533, 0.054_019_33, -0.113_614_71],
+    [-0.000_365_296_94, -0.004_121_615, 0.693_511_4],
+];
+
+fn multiply(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    std::array::from_fn(|row| {
+        std::array::from_fn(|col| (0..3).map(|i| a[row][i] * b[i][col]).sum())
+    })
+}
+
+impl ColorVision {
+    /// what's seen in place of each rgb color
+    fn simulation(self) -> [[f32; 3]; 3] {
+        // the missing cone's response, from the other two
+        let lms = match self {
+            ColorVision::Protanopia => [[0., 2.02344, -2.52581], [0., 1., 0.], [0., 0., 1.]],
+            ColorVision::Deuteranopia => [[1., 0., 0.], [0.494207, 0., 1.24827], [0., 0., 1.]],
+            ColorVision::Tritanopia => [[1., 0., 0.], [0., 1., 0.], [-0.395913, 0.801109, 0.]],
+        };
+        multiply(&LMS_TO_RGB, &multiply(&lms, &RGB_TO_LMS))
+    }
+}
+
+impl ColorBlindMode {
+    /// applied to each pixel's rgb
+    pub fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            ColorBlindMode::Simulate(vision) => vision.simulation(),
+            ColorBlindMode::Daltonize(vision) => {
+                // the color plus the lost difference, moved into channels
+                // which are still seen: c + shift * (c - simulated)
+                let simulation = vision.simulation();
+                let shift = [[0., 0., 0.], [0.7, 1., 0.], [0.7, 0., 1.]];
+                let lost: [[f32; 3]; 3] = std::array::from_fn(|row| {
+                    std::array::from_fn(|col| (row == col) as u8 as f32 - simulation[row][col])
+                });
+                let correction = multiply(&shift, &lost);
+                std::array::from_fn(|row| {
+                    std::array::from_fn(|col| (row == col) as u8 as f32 + correction[row][col])
+                })
+            }
+        }
+    }
+}
+
+/// applied to a window's frame after everything has been drawn
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    Shake(Shake),
+    /// blend the color over the frame; its alpha is the amount
+    Fade(Color),
+    /// darken (or tint) towards the edges. radius and softness are fractions
+    /// of the distance from the center to a corner
+    Vignette {
+        color: Color,
+        radius: f32,
+        softness: f32,
+    },
+    /// one row in every spacing rows is blended with the color
+    Scanlines {
+        spacing: u32,
+        color: Color,
+    },
+    /// replace exact rgb matches (alpha is ignored). done on the cpu; slow
+    PaletteSwap(Palette),
+    /// cover the fraction (in [0, 1]) of the frame with the color
+    Wipe {
+        color: Color,
+        amount: f32,
+        direction: WipeDirection,
+    },
+    /// blocks this many logical pixels wide. 1 does nothing
+    Pixelate(u32),
+    /// the frame as it was when this effect was first applied is kept, and
+    /// blended over later frames with this alpha. for crossfades
+    Crossfade(u8),
+    /// done on the cpu; slow. see EffectStack::set_color_blind
+    ColorBlind(ColorBlindMode),
+}
+
+/// a window's ordered post processing effects. each effect sees the result of
+/// the ones before it. with no effects, frames are drawn straight to the window
+pub struct EffectStack {
+    pub effects: Vec<Effect>,
+    /// applied before the effects
+    pub lighting: Option<Lighting>,
+    /// applied after the others. see Transition
+    pub transition: Option<Effect>,
+    rng: Rng,
+    offset: FPoint,
+}
+
+impl Default for EffectStack {
+    fn default() -> Self {
+        Self {
+            effects: Default::default(),
+            lighting: None,
+            transition: None,
+            rng: Rng::new(0),
+            offset: FPoint::new(0., 0.),
+        }
+    }
+}
+
+impl EffectStack {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty() && self.lighting.is_none() && self.transition.is_none()
+    }
+
+    /// the effects in the order they're applied
+    pub fn iter(&self) -> impl Iterator<Item = &Effect> {
+        self.effects.iter().chain(self.transition.iter())
+    }
+
+    /// add to the first shake in the stack, or push a new one
+    pub fn shake(&mut self, amplitude: f32, decay: f32) {
+        for effect in self.effects.iter_mut() {
+            if let Effect::Shake(shake) = effect {
+                shake.amplitude += amplitude;
+                shake.decay = decay;
+                return;
+            }
+        }
+        self.effects.push(Effect::Shake(Shake { amplitude, decay }));
+    }
+
+    /// replace the stack's color blindness filter, or remove it with None.
+    /// it's pushed after the other effects, so it also filters their colors
+    pub fn set_color_blind(&mut self, mode: Option<ColorBlindMode>) {
+        self.effects
+            .retain(|effect| !matches!(effect, Effect::ColorBlind(_)));
+        if let Some(mode) = mode {
+            self.effects.push(Effect::ColorBlind(mode));
+        }
+    }
+
+    pub fn color_blind(&self) -> Option<ColorBlindMode> {
+        self.effects.iter().find_map(|effect| match effect {
+            Effect::ColorBlind(mode) => Some(*mode),
+            _ => None,
+        })
+    }
+
+    /// decay shakes (removing finished ones) and pick this frame's offset
+    pub fn update(&mut self, dt: Duration) {
+        let mut offset = FPoint::new(0., 0.);
+        let rng = &mut self.rng;
+        self.effects.retain_mut(|effect| {
+            let Effect::Shake(shake) = effect else {
+                return true;
+            };
+            shake.amplitude -= shake.decay * dt.as_secs_f32();
+            if shake.amplitude <= 0. {
+                return false;
+            }
+            offset += FPoint::new(
+                rng.range_f32(-1.0..1.0) * shake.amplitude,
+                rng.range_f32(-1.0..1.0) * shake.amplitude,
+            );
+            true
+        });
+        self.offset = offset;
+    }
+
+    /// where the frame is drawn, relative to the window
+    pub fn offset(&self) -> FPoint {
+        self.offset
+    }
+}
+
+/// opacity in [0, 1] of a vignette at a position normalized to [0, 1] across
+/// the frame
+pub fn vignette_alpha(x: f32, y: f32, radius: f32, softness: f32) -> f32 {
+    // 1 at the corners
+    let distance = ((x - 0.5).powi(2) + (y - 0.5).powi(2)).sqrt() / 0.5f32.hypot(0.5);
+    if softness <= 0. {
+        return if distance > radius { 1. } else { 0. };
+    }
+    ((distance - radius) / softness).clamp(0., 1.)
+}
+
+/// the covered part of a frame of the size. None if nothing is covered
+pub fn wipe_rect(size: (u32, u32), amount: f32, direction: WipeDirection) -> Option<Rect> {
+    let (width, height) = size;
+    let covered = |length: u32| (length as f32 * amount.clamp(0., 1.)).round() as u32;
+    let rect = match direction {
+        WipeDirection::FromLeft => (0, 0, covered(width), height),
+        WipeDirection::FromRight => {
+            let w = covered(width);
+            ((width - w) as i32, 0, w, height)
+        }
+        WipeDirection::FromTop => (0, 0, width, covered(height)),
+        WipeDirection::FromBottom => {
+            let h = covered(height);
+            (0, (height - h) as i32, width, h)
+        }
+    };
+    (rect.2 != 0 && rect.3 != 0).then(|| Rect::new(rect.0, rect.1, rect.2, rect.3))
+}
+
+/// rgba pixels, in place
+pub fn swap_palette(pixels: &mut [u8], palette: &Palette) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        if let Some(color) = palette.get(&(pixel[0], pixel[1], pixel[2])) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+}
+
+/// rgba pixels, in place. alpha is kept
+pub fn filter_color_blind(pixels: &mut [u8], mode: ColorBlindMode) {
+    let matrix = mode.matrix();
+    for pixel in pixels.chunks_exact_mut(4) {
+        let rgb = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+        for (channel, row) in pixel.iter_mut().zip(matrix.iter()) {
+            let value: f32 = row.iter().zip(rgb).map(|(m, c)| m * c).sum();
+            *channel = value.round().clamp(0., 255.) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effects() {
+        let mut stack = EffectStack::new();
+        stack.effects.push(Effect::Fade(Color::RGBA(0, 0, 0, 128)));
+        stack.shake(4., 8.);
+        stack.shake(4., 8.);
+        assert_eq!(stack.effects.len(), 2);
+        stack.update(Duration::from_millis(500));
+        assert!(stack.offset().x().abs() <= 4. && stack.offset().y().abs() <= 4.);
+        stack.update(Duration::from_millis(500));
+        assert_eq!(stack.effects, [Effect::Fade(Color::RGBA(0, 0, 0, 128))]);
+        assert_eq!(stack.offset(), FPoint::new(0., 0.));
+
+        assert_eq!(vignette_alpha(0.5, 0.5, 0.5, 0.2), 0.);
+        assert_eq!(vignette_alpha(0., 0., 0.5, 0.2), 1.);
+
+        let mut pixels = [1, 2, 3, 255, 4, 5, 6, 255];
+        let palette = HashMap::from([((1, 2, 3), Color::RGBA(9, 9, 9, 255))]);
+        swap_palette(&mut pixels, &palette);
+        assert_eq!(pixels, [9, 9, 9, 255, 4, 5, 6, 255]);
+    }
+
+    #[test]
+    fn test_color_blind() {
+        let close = |a: &[u8], b: &[u8]| a.iter().zip(b).all(|(a, b)| a.abs_diff(*b) <= 2);
+        for vision in [
+            ColorVision::Protanopia,
+            ColorVision::Deuteranopia,
+            ColorVision::Tritanopia,
+        ] {
+            // grays are seen the same, so aren't changed
+            for mode in [
+                ColorBlindMode::Simulate(vision),
+                ColorBlindMode::Daltonize(vision),
+            ] {
+                let mut pixels = [255, 255, 255, 255, 100, 100, 100, 7];
+                filter_color_blind(&mut pixels, mode);
+                assert!(close(&pixels, &[255, 255, 255, 255, 100, 100, 100, 7]));
+            }
+        }
+        // red and green look alike without red cones
+        let mut pixels = [200, 0, 0, 255, 0, 200, 0, 255];
+        filter_color_blind(
+            &mut pixels,
+            ColorBlindMode::Simulate(ColorVision::Protanopia),
+        );
+        assert!(pixels[0].abs_diff(pixels[1]) < 60 && pixels[4].abs_diff(pixels[5]) < 60);
+
+        let mut stack = EffectStack::new();
+        stack.set_color_blind(Some(ColorBlindMode::Simulate(ColorVision::Tritanopia)));
+        stack.set_color_blind(Some(ColorBlindMode::Daltonize(ColorVision::Tritanopia)));
+        assert_eq!(stack.effects.len(), 1);
+        assert_eq!(
+            stack.color_blind(),
+            Some(ColorBlindMode::Daltonize(ColorVision::Tritanopia))
+        );
+        stack.set_color_blind(None);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn test_wipe() {
+        assert_eq!(
+            wipe_rect((100, 50), 0.25, WipeDirection::FromRight),
+            Some(Rect::new(75, 0, 25, 50))
+        );
+        assert_eq!(
+            wipe_rect((100, 50), 1., WipeDirection::FromTop),
+            Some(Rect::new(0, 0, 100, 50))
+        );
+        assert_eq!(wipe_rect((100, 50), 0., WipeDirection::FromLeft), None);
+    }
+}
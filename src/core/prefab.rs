@@ -0,0 +1,168 @@
+use std::{collections::HashMap, path::Path};
+
+use super::{entity::Entity, vfs::Vfs};
+
+/// creates an entity from its (already merged) parameters
+pub type PrefabConstructor = Box<dyn Fn(&toml::Table) -> Result<Box<dyn Entity>, String>>;
+
+/// a named set of parameters which are given to a constructor
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    pub constructor: String,
+    pub params: toml::Table,
+}
+
+/// maps names to entity constructors and their parameters.
+///
+/// constructors are registered in code, and prefabs are defined in data. each
+/// top level table of a prefab file is a prefab. the "constructor" key selects
+/// which constructor is used, and otherwise defaults to the prefab's name:
+///
+/// ```toml
+/// [goblin]
+/// constructor = "enemy"
+/// health = 10
+/// speed = 1.5
+/// ```
+#[derive(Default)]
+pub struct PrefabRegistry {
+    constructors: HashMap<String, PrefabConstructor>,
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl PrefabRegistry {
+    pub fn register_constructor<F>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(&toml::Table) -> Result<Box<dyn Entity>, String> + 'static,
+    {
+        self.constructors.insert(name.into(), Box::new(constructor));
+    }
+
+    /// add or replace a prefab
+    pub fn insert(&mut self, prefab_name: &str, prefab: Prefab) {
+        self.prefabs.insert(prefab_name.into(), prefab);
+    }
+
+    pub fn get(&self, prefab_name: &str) -> Option<&Prefab> {
+        self.prefabs.get(prefab_name)
+    }
+
+    /// in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.keys().map(String::as_str)
+    }
+
+    /// parse prefabs from toml content. prefabs that already exist are
+    /// replaced
+    pub fn load_str(&mut self, content: &str) -> Result<(), String> {
+        let table: toml::Table = content
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        for (prefab_name, value) in table {
+            let mut params = match value {
+                toml::Value::Table(params) => params,
+                _ => return Err(format!("prefab \"{prefab_name}\" must be a table")),
+            };
+            let constructor = match params.remove("constructor") {
+                None => prefab_name.clone(),
+                Some(toml::Value::String(constructor)) => constructor,
+                Some(_) => {
+                    return Err(format!(
+                        "prefab \"{prefab_name}\" constructor must be a string"
+                    ))
+                }
+            };
+            self.insert(
+                &prefab_name,
+                Prefab {
+                    constructor,
+                    params,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// load prefabs from a toml file. prefabs that already exist are replaced
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        self.load_str(&content)
+    }
+
+    /// as load_file, resolving paths through the vfs
+    pub fn load_from(&mut self, assets: &Vfs, path: &Path) -> Result<(), String> {
+        self.load_str(&assets.read_to_string(path)?)
+    }
+
+    /// create an entity from the prefab. the overrides are merged on top of
+    /// the prefab's parameters; nested tables are merged recursively
+    pub fn instantiate(
+        &self,
+        prefab_name: &str,
+        overrides: &toml::Table,
+    ) -> Result<Box<dyn Entity>, String> {
+        let prefab = self
+            .prefabs
+            .get(prefab_name)
+            .ok_or_else(|| format!("prefab \"{prefab_name}\" does not exist"))?;
+        let constructor = self.constructors.get(&prefab.constructor).ok_or_else(|| {
+            format!(
+                "prefab \"{prefab_name}\" uses constructor \"{}\" which is not registered",
+                prefab.constructor
+            )
+        })?;
+        let mut params = prefab.params.clone();
+        merge(&mut params, overrides);
+        constructor(&params)
+    }
+}
+
+/// an override typed as text, e.g. in the console or a map editor's object
+/// properties. it's parsed as a toml value, and is otherwise a string
+pub fn parse_override(value: &str) -> toml::Value {
+    format!("v = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(value.to_owned()))
+}
+
+fn merge(base: &mut toml::Table, overrides: &toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base_inner)), toml::Value::Table(override_inner)) => {
+                merge(base_inner, override_inner)
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut base: toml::Table = "a = 1\nb = 2\n[inner]\nc = 3\nd = 4".parse().unwrap();
+        let overrides: toml::Table = "b = 5\n[inner]\nd = 6".parse().unwrap();
+        merge(&mut base, &overrides);
+        let expected: toml::Table = "a = 1\nb = 5\n[inner]\nc = 3\nd = 6".parse().unwrap();
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn test_load() {
+        let mut registry = PrefabRegistry::default();
+        registry
+            .load_str("[goblin]\nconstructor = \"enemy\"\nhealth = 10\n[enemy]\nhealth = 5")
+            .unwrap();
+        let goblin = registry.get("goblin").unwrap();
+        assert_eq!(goblin.constructor, "enemy");
+        assert!(!goblin.params.contains_key("constructor"));
+        assert_eq!(registry.get("enemy").unwrap().constructor, "enemy");
+        assert!(registry.load_str("not_a_table = 1").is_err());
+    }
+}
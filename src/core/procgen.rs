@@ -0,0 +1,417 @@
+use sdl2::rect::FPoint;
+
+use super::{
+    rng::Rng,
+    tilemap::{TileLayer, EMPTY_TILE},
+};
+
+/// a connected area of floor, e.g. a room, to place the player, enemies or
+/// items in
+#[derive(Debug, Clone)]
+pub struct SpawnRegion {
+    /// "start" and "end" for the rooms furthest apart, "room" for the others,
+    /// or "cave" or "floor"
+    pub name: String,
+    /// tile coordinates
+    pub tiles: Vec<(i32, i32)>,
+}
+
+impl SpawnRegion {
+    /// tile coordinates (x0, y0, x1, y1), inclusive. None if it has no tiles
+    pub fn bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        let first = *self.tiles.first()?;
+        Some(self.tiles.iter().fold(
+            (first.0, first.1, first.0, first.1),
+            |(x0, y0, x1, y1), &(x, y)| (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        ))
+    }
+
+    pub fn random_tile(&self, rng: &mut Rng) -> Option<(i32, i32)> {
+        rng.choose(&self.tiles).copied()
+    }
+
+    /// the center of a random tile
+    pub fn random_point(&self, rng: &mut Rng, layer: &TileLayer) -> Option<FPoint> {
+        let (x, y) = self.random_tile(rng)?;
+        let rect = layer.tile_rect(x, y);
+        Some(FPoint::new(
+            rect.x() + rect.width() / 2.,
+            rect.y() + rect.height() / 2.,
+        ))
+    }
+}
+
+/// a generated map. walls are the generator's wall tile and floor is its
+/// floor tile, so an Autotiler can pick edge tiles after (Autotiler::apply)
+#[derive(Debug, Clone)]
+pub struct Generated {
+    pub layer: TileLayer,
+    pub regions: Vec<SpawnRegion>,
+}
+
+impl Generated {
+    pub fn region(&self, name: &str) -> Option<&SpawnRegion> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+}
+
+/// split the map in two recursively, then put a room in each part and join
+/// the parts with corridors
+#[derive(Debug, Clone)]
+pub struct BspParams {
+    /// parts smaller than twice this aren't split
+    pub min_leaf: u32,
+    pub min_room: u32,
+}
+
+impl Default for BspParams {
+    fn default() -> Self {
+        Self {
+            min_leaf: 8,
+            min_room: 4,
+        }
+    }
+}
+
+/// fill the map with random walls, then smooth it like the game of life
+#[derive(Debug, Clone)]
+pub struct CaveParams {
+    /// the chance each tile starts as wall
+    pub fill: f32,
+    pub steps: u32,
+    /// floor with at least this many wall neighbours becomes wall
+    pub birth: u32,
+    /// walls with fewer wall neighbours than this become floor
+    pub survival: u32,
+    /// only keep the largest cave, filling in the others
+    pub keep_largest: bool,
+}
+
+impl Default for CaveParams {
+    fn default() -> Self {
+        Self {
+            fill: 0.45,
+            steps: 5,
+            birth: 5,
+            survival: 4,
+            keep_largest: true,
+        }
+    }
+}
+
+/// carve floor by walking randomly from the center
+#[derive(Debug, Clone)]
+pub struct WalkParams {
+    /// stop once this fraction of the map is floor
+    pub coverage: f32,
+    /// or after this many steps
+    pub max_steps: u32,
+}
+
+impl Default for WalkParams {
+    fn default() -> Self {
+        Self {
+            coverage: 0.4,
+            max_steps: 100_000,
+        }
+    }
+}
+
+/// procedural map generation. pass a stream from the world's RngService
+/// (e.g. world.rng.stream("procgen")) so the same seed gives the same map.
+/// the edge of the map is always wall
+#[derive(Debug, Clone)]
+pub struct MapGenerator {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: f32,
+    pub tile_height: f32,
+    pub floor: u32,
+    pub wall: u32,
+}
+
+/// (x, y, width, height) in tiles
+type TileRect = (i32, i32, i32, i32);
+
+fn center((x, y, w, h): TileRect) -> (i32, i32) {
+    (x + w / 2, y + h / 2)
+}
+
+impl MapGenerator {
+    /// floor is EMPTY_TILE and wall is 1
+    pub fn new(width: u32, height: u32, tile_width: f32, tile_height: f32) -> Self {
+        Self {
+            width,
+            height,
+            tile_width,
+            tile_height,
+            floor: EMPTY_TILE,
+            wall: 1,
+        }
+    }
+
+    fn walls(&self) -> TileLayer {
+        let tiles = vec![self.wall; self.width as usize * self.height as usize];
+        TileLayer::from_tiles(
+            self.width,
+            self.height,
+            self.tile_width,
+            self.tile_height,
+            tiles,
+        )
+        .expect("sized to fit")
+    }
+
+    fn is_inside(&self, x: i32, y: i32) -> bool {
+        x > 0 && y > 0 && x < self.width as i32 - 1 && y < self.height as i32 - 1
+    }
+
+    /// rooms joined by corridors. the two rooms furthest apart are "start"
+    /// and "end"
+    pub fn bsp(&self, rng: &mut Rng, params: &BspParams) -> Generated {
+        let mut layer = self.walls();
+        let mut rooms = Vec::new();
+        let whole = (0, 0, self.width as i32, self.height as i32);
+        if whole.2 >= 3 && whole.3 >= 3 {
+            self.split(rng, params, whole, &mut layer, &mut rooms);
+        }
+
+        let mut regions: Vec<SpawnRegion> = rooms
+            .iter()
+            .map(|&(x, y, w, h)| SpawnRegion {
+                name: "room".to_owned(),
+                tiles: (y..y + h)
+                    .flat_map(|ty| (x..x + w).map(move |tx| (tx, ty)))
+                    .collect(),
+            })
+            .collect();
+        let distance = |a: TileRect, b: TileRect| {
+            let ((ax, ay), (bx, by)) = (center(a), center(b));
+            (ax - bx).abs() + (ay - by).abs()
+        };
+        let furthest = (0..rooms.len())
+            .flat_map(|a| (a + 1..rooms.len()).map(move |b| (a, b)))
+            .max_by_key(|&(a, b)| distance(rooms[a], rooms[b]));
+        if let Some((start, end)) = furthest {
+            regions[start].name = "start".to_owned();
+            regions[end].name = "end".to_owned();
+        } else if let Some(only) = regions.first_mut() {
+            only.name = "start".to_owned();
+        }
+        Generated { layer, regions }
+    }
+
+    /// carve a room in each leaf, returning a point in the part's rooms
+    fn split(
+        &self,
+        rng: &mut Rng,
+        params: &BspParams,
+        part: TileRect,
+        layer: &mut TileLayer,
+        rooms: &mut Vec<TileRect>,
+    ) -> (i32, i32) {
+        let (x, y, w, h) = part;
+        let min_leaf = params.min_leaf.max(3) as i32;
+        let (can_x, can_y) = (w >= min_leaf * 2, h >= min_leaf * 2);
+        if !can_x && !can_y {
+            // inside the part's one tile margin, so neighbouring rooms
+            // don't merge
+            let (max_w, max_h) = (w - 2, h - 2);
+            let min = params.min_room.max(1) as i32;
+            let rw = rng.range_i32(min.min(max_w)..max_w + 1);
+            let rh = rng.range_i32(min.min(max_h)..max_h + 1);
+            let rx = x + 1 + rng.range_i32(0..max_w - rw + 1);
+            let ry = y + 1 + rng.range_i32(0..max_h - rh + 1);
+            for ty in ry..ry + rh {
+                for tx in rx..rx + rw {
+                    layer.set(tx, ty, self.floor);
+                }
+            }
+            let room = (rx, ry, rw, rh);
+            rooms.push(room);
+            return center(room);
+        }
+        let split_x = can_x && (!can_y || w > h || (w == h && rng.chance(0.5)));
+        let (a, b) = if split_x {
+            let at = rng.range_i32(min_leaf..w - min_leaf + 1);
+            ((x, y, at, h), (x + at, y, w - at, h))
+        } else {
+            let at = rng.range_i32(min_leaf..h - min_leaf + 1);
+            ((x, y, w, at), (x, y + at, w, h - at))
+        };
+        let a = self.split(rng, params, a, layer, rooms);
+        let b = self.split(rng, params, b, layer, rooms);
+        self.corridor(rng, layer, a, b);
+        a
+    }
+
+    /// an l shaped corridor, bending at a random end
+    fn corridor(&self, rng: &mut Rng, layer: &mut TileLayer, a: (i32, i32), b: (i32, i32)) {
+        let corner = if rng.chance(0.5) {
+            (b.0, a.1)
+        } else {
+            (a.0, b.1)
+        };
+        for (from, to) in [(a, corner), (corner, b)] {
+            let (mut x, mut y) = from;
+            loop {
+                layer.set(x, y, self.floor);
+                if (x, y) == to {
+                    break;
+                }
+                x += (to.0 - x).signum();
+                y += (to.1 - y).signum();
+            }
+        }
+    }
+
+    /// caves, as "cave" regions, largest first
+    pub fn caves(&self, rng: &mut Rng, params: &CaveParams) -> Generated {
+        let mut layer = self.walls();
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                if self.is_inside(x, y) && !rng.chance(params.fill) {
+                    layer.set(x, y, self.floor);
+                }
+            }
+        }
+        for _ in 0..params.steps {
+            let before = layer.clone();
+            for y in 0..self.height as i32 {
+                for x in 0..self.width as i32 {
+                    if !self.is_inside(x, y) {
+                        continue;
+                    }
+                    let walls = (-1..=1)
+                        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                        .filter(|&d| d != (0, 0))
+                        .filter(|(dx, dy)| before.get(x + dx, y + dy) != Some(self.floor))
+                        .count() as u32;
+                    let wall = if before.get(x, y) == Some(self.wall) {
+                        walls >= params.survival
+                    } else {
+                        walls >= params.birth
+                    };
+                    layer.set(x, y, if wall { self.wall } else { self.floor });
+                }
+            }
+        }
+
+        let mut caves = self.connected(&layer);
+        if params.keep_largest && caves.len() > 1 {
+            for (x, y) in caves.drain(1..).flatten() {
+                layer.set(x, y, self.wall);
+            }
+        }
+        let regions = caves
+            .into_iter()
+            .map(|tiles| SpawnRegion {
+                name: "cave".to_owned(),
+                tiles,
+            })
+            .collect();
+        Generated { layer, regions }
+    }
+
+    /// one "floor" region of everything carved, and a "start" region of the
+    /// tile walked from
+    pub fn walk(&self, rng: &mut Rng, params: &WalkParams) -> Generated {
+        let mut layer = self.walls();
+        let mut regions = Vec::new();
+        let (mut x, mut y) = (self.width as i32 / 2, self.height as i32 / 2);
+        if self.is_inside(x, y) {
+            let inside = (self.width - 2) as f32 * (self.height - 2) as f32;
+            let target = ((inside * params.coverage).ceil() as usize).max(1);
+            let mut carved = Vec::new();
+            regions.push(SpawnRegion {
+                name: "start".to_owned(),
+                tiles: vec![(x, y)],
+            });
+            for _ in 0..params.max_steps {
+                if layer.get(x, y) != Some(self.floor) {
+                    layer.set(x, y, self.floor);
+                    carved.push((x, y));
+                    if carved.len() >= target {
+                        break;
+                    }
+                }
+                let &(dx, dy) = rng.choose(&[(0, -1), (1, 0), (0, 1), (-1, 0)]).unwrap();
+                if self.is_inside(x + dx, y + dy) {
+                    (x, y) = (x + dx, y + dy);
+                }
+            }
+            regions.push(SpawnRegion {
+                name: "floor".to_owned(),
+                tiles: carved,
+            });
+        }
+        Generated { layer, regions }
+    }
+
+    /// the 4-connected areas of floor, largest first
+    pub fn connected(&self, layer: &TileLayer) -> Vec<Vec<(i32, i32)>> {
+        let (w, h) = (layer.width() as i32, layer.height() as i32);
+        let mut seen = vec![false; w as usize * h as usize];
+        let mut areas = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                if seen[(y * w + x) as usize] || layer.get(x, y) != Some(self.floor) {
+                    continue;
+                }
+                seen[(y * w + x) as usize] = true;
+                let mut area = Vec::new();
+                let mut stack = vec![(x, y)];
+                while let Some((x, y)) = stack.pop() {
+                    area.push((x, y));
+                    for (nx, ny) in [(x, y - 1), (x + 1, y), (x, y + 1), (x - 1, y)] {
+                        if layer.get(nx, ny) == Some(self.floor)
+                            && !std::mem::replace(&mut seen[(ny * w + nx) as usize], true)
+                        {
+                            stack.push((nx, ny));
+                        }
+                    }
+                }
+                area.sort_unstable_by_key(|&(x, y)| (y, x));
+                areas.push(area);
+            }
+        }
+        areas.sort_by_key(|area| std::cmp::Reverse(area.len()));
+        areas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let generator = MapGenerator::new(40, 30, 16., 16.);
+        let floor = |g: &Generated| {
+            g.layer
+                .tiles()
+                .iter()
+                .filter(|t| **t == generator.floor)
+                .count()
+        };
+
+        let bsp = generator.bsp(&mut Rng::new(1), &BspParams::default());
+        assert!(bsp.regions.len() >= 4);
+        assert!(bsp.region("start").is_some() && bsp.region("end").is_some());
+        // every room is reachable
+        assert_eq!(generator.connected(&bsp.layer).len(), 1);
+        let again = generator.bsp(&mut Rng::new(1), &BspParams::default());
+        assert_eq!(again.layer.tiles(), bsp.layer.tiles());
+
+        let caves = generator.caves(&mut Rng::new(2), &CaveParams::default());
+        assert_eq!(caves.regions.len(), 1);
+        assert_eq!(caves.regions[0].tiles.len(), floor(&caves));
+        let (x0, y0, x1, y1) = caves.regions[0].bounds().unwrap();
+        assert!(x0 > 0 && y0 > 0 && x1 < 39 && y1 < 29);
+
+        let walk = generator.walk(&mut Rng::new(3), &WalkParams::default());
+        assert_eq!(floor(&walk), (38. * 28. * 0.4f32).ceil() as usize);
+        assert_eq!(walk.region("start").unwrap().tiles, vec![(20, 15)]);
+        assert_eq!(generator.connected(&walk.layer).len(), 1);
+    }
+}
@@ -0,0 +1,422 @@
+#[cfg(feature = "ttf")]
+use std::ffi::CString;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use sdl2::{pixels::Color, rect::Rect};
+use serde_json::json;
+
+use super::{save::write_atomic, system::ChimericSystem};
+
+/// a timed section of a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub name: &'static str,
+    /// the number of zones it's nested in
+    pub depth: usize,
+    /// from the start of the frame
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameProfile {
+    pub index: u64,
+    /// from when the profiler was created
+    pub start: Duration,
+    pub duration: Duration,
+    /// in the order they were started
+    pub zones: Vec<Zone>,
+}
+
+/// collects per frame zone timings. the engine records into a thread local
+/// instance through the free functions in this module; it's disabled until
+/// enable is called
+pub struct Profiler {
+    epoch: Instant,
+    frame_index: u64,
+    frame_start: Option<Instant>,
+    zones: Vec<Zone>,
+    /// indices of the zones which have begun but not ended
+    open: Vec<usize>,
+    history: VecDeque<FrameProfile>,
+    /// the number of finished frames kept
+    pub history_len: usize,
+    /// every finished frame, for export. None if not recording
+    trace: Option<Vec<FrameProfile>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            frame_index: 0,
+            frame_start: None,
+            zones: Default::default(),
+            open: Default::default(),
+            history: Default::default(),
+            history_len: 120,
+            trace: None,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// finish the current frame, if any, and start the next
+    pub fn new_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(frame_start) = self.frame_start {
+            // zones left open end with the frame
+            while !self.open.is_empty() {
+                self.end_zone_at(now);
+            }
+            let frame = FrameProfile {
+                index: self.frame_index,
+                start: frame_start - self.epoch,
+                duration: now - frame_start,
+                zones: std::mem::take(&mut self.zones),
+            };
+            self.frame_index += 1;
+            if let Some(trace) = self.trace.as_mut() {
+                trace.push(frame.clone());
+            }
+            self.history.push_back(frame);
+            while self.history.len() > self.history_len.max(1) {
+                self.history.pop_front();
+            }
+        }
+        self.frame_start = Some(now);
+    }
+
+    /// ignored outside of a frame
+    pub fn begin_zone(&mut self, name: &'static str) {
+        let Some(frame_start) = self.frame_start else {
+            return;
+        };
+        self.open.push(self.zones.len());
+        self.zones.push(Zone {
+            name,
+            depth: self.open.len() - 1,
+            start: Instant::now() - frame_start,
+            duration: Duration::ZERO,
+        });
+    }
+
+    /// ends the most recently begun zone
+    pub fn end_zone(&mut self) {
+        self.end_zone_at(Instant::now());
+    }
+
+    fn end_zone_at(&mut self, now: Instant) {
+        let (Some(index), Some(frame_start)) = (self.open.pop(), self.frame_start) else {
+            return;
+        };
+        let zone = &mut self.zones[index];
+        zone.duration = (now - frame_start).saturating_sub(zone.start);
+    }
+
+    /// the most recently finished frame
+    pub fn last_frame(&self) -> Option<&FrameProfile> {
+        self.history.back()
+    }
+
+    /// oldest first
+    pub fn history(&self) -> impl Iterator<Item = &FrameProfile> {
+        self.history.iter()
+    }
+
+    /// the mean total time per frame of each zone name over the history,
+    /// slowest first
+    pub fn averages(&self) -> Vec<(&'static str, Duration)> {
+        let mut totals: Vec<(&'static str, Duration)> = Vec::new();
+        for zone in self.history.iter().flat_map(|f| f.zones.iter()) {
+            match totals.iter_mut().find(|(name, _)| *name == zone.name) {
+                Some((_, total)) => *total += zone.duration,
+                None => totals.push((zone.name, zone.duration)),
+            }
+        }
+        let frames = self.history.len().max(1) as u32;
+        totals.iter_mut().for_each(|(_, total)| *total /= frames);
+        totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+        totals
+    }
+
+    /// keep every finished frame from now on, for export
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Default::default());
+    }
+
+    /// the recorded frames in chrome's trace event format (open with
+    /// chrome://tracing or perfetto)
+    pub fn stop_trace(&mut self) -> Option<String> {
+        self.trace.take().map(|frames| chrome_trace(&frames))
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.
+}
+
+/// frames and their zones as complete ("X") events on a single thread
+pub fn chrome_trace(frames: &[FrameProfile]) -> String {
+    let mut events = Vec::new();
+    for frame in frames {
+        events.push(json!({
+            "name": format!("frame {}", frame.index),
+            "ph": "X",
+            "ts": micros(frame.start),
+            "dur": micros(frame.duration),
+            "pid": 0,
+            "tid": 0,
+        }));
+        for zone in frame.zones.iter() {
+            events.push(json!({
+                "name": zone.name,
+                "ph": "X",
+                "ts": micros(frame.start + zone.start),
+                "dur": micros(zone.duration),
+                "pid": 0,
+                "tid": 0,
+            }));
+        }
+    }
+    json!({ "traceEvents": events }).to_string()
+}
+
+thread_local! {
+    static PROFILER: RefCell<Option<Profiler>> = const { RefCell::new(None) };
+}
+
+/// start collecting on this thread. frames are delimited by new_frame
+pub fn enable() {
+    PROFILER.with_borrow_mut(|p| {
+        p.get_or_insert_with(Profiler::new);
+    });
+}
+
+/// stop collecting and discard what was collected
+pub fn disable() {
+    PROFILER.with_borrow_mut(|p| *p = None);
+}
+
+pub fn is_enabled() -> bool {
+    PROFILER.with_borrow(|p| p.is_some())
+}
+
+/// access the profiler, if enabled
+pub fn with<R>(f: impl FnOnce(&mut Profiler) -> R) -> Option<R> {
+    PROFILER.with_borrow_mut(|p| p.as_mut().map(f))
+}
+
+pub fn new_frame() {
+    with(|p| p.new_frame());
+}
+
+pub fn begin_zone(name: &'static str) {
+    with(|p| p.begin_zone(name));
+}
+
+pub fn end_zone() {
+    with(|p| p.end_zone());
+}
+
+/// ends the zone when dropped
+pub struct ZoneGuard(());
+
+impl Drop for ZoneGuard {
+    fn drop(&mut self) {
+        end_zone();
+    }
+}
+
+/// begin a zone which ends at the end of the scope
+pub fn zone(name: &'static str) -> ZoneGuard {
+    begin_zone(name);
+    ZoneGuard(())
+}
+
+/// write the recorded trace, if a trace was started
+pub fn write_trace(path: &Path) -> Result<(), String> {
+    match with(|p| p.stop_trace()).flatten() {
+        None => Err("no trace was started".into()),
+        Some(trace) => write_atomic(path, trace.as_bytes()),
+    }
+}
+
+/// a stable color per zone name
+fn zone_color(name: &str) -> Color {
+    let hash = name.bytes().fold(0x811C_9DC5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    Color::RGB(
+        96 + (hash & 0x7F) as u8,
+        96 + ((hash >> 8) & 0x7F) as u8,
+        96 + ((hash >> 16) & 0x7F) as u8,
+    )
+}
+
+/// on screen flame bars of the last frame's zones, nested zones below their
+/// parents. the width of the area is the frame budget; a bar past the marker
+/// at the right edge means a missed frame
+pub struct ProfilerOverlay {
+    pub visible: bool,
+    pub window_name: String,
+    /// where the bars are drawn, in the window's logical coordinates
+    pub area: Rect,
+    pub budget: Duration,
+    /// zone names are drawn on bars wide enough to hold them, if set. needs
+    /// the ttf feature
+    pub font_file: Option<PathBuf>,
+    pub point_size: u16,
+}
+
+impl ProfilerOverlay {
+    pub fn new(window_name: &str, area: Rect) -> Self {
+        Self {
+            visible: false,
+            window_name: window_name.to_owned(),
+            area,
+            budget: Duration::from_secs(1) / 60,
+            font_file: None,
+            point_size: 12,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// the frame's bar and then each zone's bar, with their colors
+    fn bars(&self, frame: &FrameProfile) -> Vec<(Rect, Color, &'static str)> {
+        let rows = frame.zones.iter().map(|z| z.depth + 2).max().unwrap_or(1) as u32;
+        let row_height = (self.area.height() / rows).max(1);
+        let scale = self.area.width() as f64 / self.budget.as_secs_f64().max(f64::EPSILON);
+        let x = |d: Duration| self.area.x() + (d.as_secs_f64() * scale) as i32;
+        let width = |d: Duration| ((d.as_secs_f64() * scale) as u32).max(1);
+        let frame_color = if frame.duration > self.budget {
+            Color::RGB(200, 60, 60)
+        } else {
+            Color::RGB(60, 160, 60)
+        };
+        let mut bars = vec![(
+            Rect::new(
+                self.area.x(),
+                self.area.y(),
+                width(frame.duration),
+                row_height,
+            ),
+            frame_color,
+            "frame",
+        )];
+        bars.extend(frame.zones.iter().map(|zone| {
+            (
+                Rect::new(
+                    x(zone.start),
+                    self.area.y() + ((zone.depth as u32 + 1) * row_height) as i32,
+                    width(zone.duration),
+                    row_height,
+                ),
+                zone_color(zone.name),
+                zone.name,
+            )
+        }));
+        bars
+    }
+
+    /// draw the last finished frame, if visible and profiling is enabled
+    pub fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+        let Some(bars) = with(|p| p.last_frame().map(|f| self.bars(f))).flatten() else {
+            return Ok(());
+        };
+        let canvas = system.canvas(&self.window_name)?;
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+        canvas.fill_rect(self.area)?;
+        for (rect, color, _) in bars.iter() {
+            canvas.set_draw_color(*color);
+            canvas.fill_rect(*rect)?;
+        }
+        // the budget
+        canvas.set_draw_color(Color::WHITE);
+        canvas.fill_rect(Rect::new(
+            self.area.right() - 1,
+            self.area.y(),
+            1,
+            self.area.height(),
+        ))?;
+        #[cfg(feature = "ttf")]
+        self.draw_labels(system, bars)?;
+        Ok(())
+    }
+
+    /// zone names on the bars wide enough to hold them
+    #[cfg(feature = "ttf")]
+    fn draw_labels(
+        &self,
+        system: &mut ChimericSystem,
+        bars: Vec<(Rect, Color, &'static str)>,
+    ) -> Result<(), String> {
+        let Some(font_file) = self.font_file.as_ref() else {
+            return Ok(());
+        };
+        for (rect, _, name) in bars {
+            let text = CString::new(name).map_err(|e| e.to_string())?;
+            let (texture, canvas) =
+                system.text(&self.window_name, font_file, self.point_size, &text, None)?;
+            let query = texture.query();
+            if query.width <= rect.width() {
+                canvas.copy(
+                    texture,
+                    None,
+                    Rect::new(
+                        rect.x(),
+                        rect.y(),
+                        query.width,
+                        query.height.min(rect.height()),
+                    ),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zones() {
+        let mut profiler = Profiler::new();
+        profiler.begin_zone("ignored");
+        profiler.new_frame();
+        profiler.start_trace();
+        profiler.begin_zone("update");
+        profiler.begin_zone("physics");
+        profiler.end_zone();
+        profiler.end_zone();
+        profiler.begin_zone("draw");
+        profiler.new_frame();
+
+        let frame = profiler.last_frame().unwrap();
+        let names: Vec<_> = frame.zones.iter().map(|z| (z.name, z.depth)).collect();
+        assert_eq!(names, [("update", 0), ("physics", 1), ("draw", 0)]);
+        assert!(frame.zones[1].start >= frame.zones[0].start);
+        assert_eq!(profiler.averages().len(), 3);
+
+        let trace: serde_json::Value =
+            serde_json::from_str(&profiler.stop_trace().unwrap()).unwrap();
+        assert_eq!(trace["traceEvents"].as_array().unwrap().len(), 4);
+        assert_eq!(trace["traceEvents"][2]["name"], "physics");
+    }
+}
@@ -0,0 +1,516 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use super::{event_bus::EventBus, vfs::Vfs};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectiveDef {
+    pub id: String,
+    pub description: String,
+    /// how much progress completes it
+    pub count: u32,
+    /// not needed to complete the quest
+    pub optional: bool,
+}
+
+/// a quest's definition
+#[derive(Debug, Clone)]
+pub struct QuestDef {
+    /// quests which must be completed before this one can start
+    pub requires: Vec<String>,
+    pub objectives: Vec<ObjectiveDef>,
+    /// everything else, e.g. its title, description and rewards
+    pub params: toml::Table,
+}
+
+impl QuestDef {
+    fn objective(&self, quest_name: &str, objective: &str) -> Result<&ObjectiveDef, String> {
+        self.objectives
+            .iter()
+            .find(|o| o.id == objective)
+            .ok_or_else(|| format!("quest \"{quest_name}\" has no objective \"{objective}\""))
+    }
+}
+
+/// quest definitions, loaded from data like prefabs. each top level table is
+/// a quest:
+///
+/// ```toml
+/// [rat_problem]
+/// title = "A Rat Problem"
+/// requires = ["meet_the_innkeeper"]
+/// objectives = [
+///     { id = "rats", description = "Clear the cellar of rats", count = 5 },
+///     { id = "cheese", description = "Find the missing cheese", optional = true },
+/// ]
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QuestRegistry {
+    quests: HashMap<String, QuestDef>,
+}
+
+impl QuestRegistry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add or replace a quest
+    pub fn insert(&mut self, quest_name: &str, quest: QuestDef) {
+        self.quests.insert(quest_name.into(), quest);
+    }
+
+    pub fn get(&self, quest_name: &str) -> Option<&QuestDef> {
+        self.quests.get(quest_name)
+    }
+
+    /// in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.quests.keys().map(String::as_str)
+    }
+
+    /// parse quests from toml content. quests that already exist are replaced
+    pub fn load_str(&mut self, content: &str) -> Result<(), String> {
+        let table: toml::Table = content
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        for (quest_name, value) in table {
+            let mut params = match value {
+                toml::Value::Table(params) => params,
+                _ => return Err(format!("quest \"{quest_name}\" must be a table")),
+            };
+            let requires = match params.remove("requires") {
+                None => Vec::new(),
+                Some(toml::Value::Array(requires)) => requires
+                    .into_iter()
+                    .map(|r| match r {
+                        toml::Value::String(r) => Ok(r),
+                        _ => Err(format!("quest \"{quest_name}\" requires must be strings")),
+                    })
+                    .collect::<Result<_, _>>()?,
+                Some(_) => return Err(format!("quest \"{quest_name}\" requires must be an array")),
+            };
+            let objectives = match params.remove("objectives") {
+                None => Vec::new(),
+                Some(toml::Value::Array(objectives)) => objectives
+                    .iter()
+                    .map(read_objective)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| format!("quest \"{quest_name}\": {e}"))?,
+                Some(_) => {
+                    return Err(format!(
+                        "quest \"{quest_name}\" objectives must be an array"
+                    ))
+                }
+            };
+            self.insert(
+                &quest_name,
+                QuestDef {
+                    requires,
+                    objectives,
+                    params,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// load quests from a toml file. quests that already exist are replaced
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        self.load_str(&content)
+    }
+
+    /// as load_file, resolving paths through the vfs
+    pub fn load_from(&mut self, assets: &Vfs, path: &Path) -> Result<(), String> {
+        self.load_str(&assets.read_to_string(path)?)
+    }
+
+    fn quest(&self, quest_name: &str) -> Result<&QuestDef, String> {
+        self.get(quest_name)
+            .ok_or_else(|| format!("quest \"{quest_name}\" does not exist"))
+    }
+}
+
+/// { id, description, count = 1, optional = false }
+fn read_objective(value: &toml::Value) -> Result<ObjectiveDef, String> {
+    let table = value.as_table().ok_or("each objective must be a table")?;
+    let id = table
+        .get("id")
+        .and_then(toml::Value::as_str)
+        .ok_or("objective needs an id")?;
+    let description = match table.get("description") {
+        None => "",
+        Some(toml::Value::String(description)) => description,
+        Some(_) => return Err(format!("objective \"{id}\" description must be a string")),
+    };
+    let count = match table.get("count") {
+        None => 1,
+        Some(toml::Value::Integer(n)) if *n >= 1 && *n <= u32::MAX as i64 => *n as u32,
+        Some(_) => {
+            return Err(format!(
+                "objective \"{id}\" count must be a positive integer"
+            ))
+        }
+    };
+    let optional = match table.get("optional") {
+        None => false,
+        Some(toml::Value::Boolean(optional)) => *optional,
+        Some(_) => return Err(format!("objective \"{id}\" optional must be a bool")),
+    };
+    Ok(ObjectiveDef {
+        id: id.to_owned(),
+        description: description.to_owned(),
+        count,
+        optional,
+    })
+}
+
+/// published on the world's event bus as quests change
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuestEvent {
+    Started(String),
+    ObjectiveCompleted { quest: String, objective: String },
+    Completed(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuestState {
+    Active,
+    Completed,
+    Failed,
+}
+
+impl QuestState {
+    fn name(self) -> &'static str {
+        match self {
+            QuestState::Active => "active",
+            QuestState::Completed => "completed",
+            QuestState::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct QuestProgress {
+    state: QuestState,
+    /// progress of each objective, absent if none
+    counts: BTreeMap<String, u32>,
+}
+
+/// an unfinished objective of an active quest, e.g. for a hud
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveObjective<'a> {
+    pub quest: &'a str,
+    pub objective: &'a ObjectiveDef,
+    pub progress: u32,
+}
+
+/// the player's progress through quests. quests which were never started
+/// aren't stored
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuestLog {
+    quests: BTreeMap<String, QuestProgress>,
+}
+
+impl QuestLog {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// none if never started
+    pub fn state(&self, quest_name: &str) -> Option<QuestState> {
+        self.quests.get(quest_name).map(|q| q.state)
+    }
+
+    pub fn is_completed(&self, quest_name: &str) -> bool {
+        self.state(quest_name) == Some(QuestState::Completed)
+    }
+
+    /// not yet started, with every prerequisite completed
+    pub fn can_start(&self, quests: &QuestRegistry, quest_name: &str) -> bool {
+        self.state(quest_name).is_none()
+            && quests
+                .get(quest_name)
+                .is_some_and(|q| q.requires.iter().all(|r| self.is_completed(r)))
+    }
+
+    /// the quests which can start, sorted
+    pub fn available<'a>(&self, quests: &'a QuestRegistry) -> Vec<&'a str> {
+        let mut available: Vec<&str> = quests
+            .names()
+            .filter(|q| self.can_start(quests, q))
+            .collect();
+        available.sort_unstable();
+        available
+    }
+
+    pub fn start(
+        &mut self,
+        quests: &QuestRegistry,
+        quest_name: &str,
+        events: &mut EventBus,
+    ) -> Result<(), String> {
+        quests.quest(quest_name)?;
+        if !self.can_start(quests, quest_name) {
+            return Err(format!("quest \"{quest_name}\" can't be started"));
+        }
+        self.quests.insert(
+            quest_name.to_owned(),
+            QuestProgress {
+                state: QuestState::Active,
+                counts: BTreeMap::new(),
+            },
+        );
+        events.publish(QuestEvent::Started(quest_name.to_owned()));
+        Ok(())
+    }
+
+    /// how far the objective is, up to its count
+    pub fn progress(&self, quest_name: &str, objective: &str) -> u32 {
+        self.quests
+            .get(quest_name)
+            .and_then(|q| q.counts.get(objective))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// add to an objective's progress, completing the quest once every
+    /// required objective is done. ignored unless the quest is active
+    pub fn add_progress(
+        &mut self,
+        quests: &QuestRegistry,
+        quest_name: &str,
+        objective: &str,
+        amount: u32,
+        events: &mut EventBus,
+    ) -> Result<(), String> {
+        let quest = quests.quest(quest_name)?;
+        let def = quest.objective(quest_name, objective)?;
+        let Some(progress) = self
+            .quests
+            .get_mut(quest_name)
+            .filter(|q| q.state == QuestState::Active)
+        else {
+            return Ok(());
+        };
+        let count = progress.counts.entry(objective.to_owned()).or_default();
+        let before = *count;
+        *count = count.saturating_add(amount).min(def.count);
+        if before < def.count && *count == def.count {
+            events.publish(QuestEvent::ObjectiveCompleted {
+                quest: quest_name.to_owned(),
+                objective: objective.to_owned(),
+            });
+        }
+        let done = quest
+            .objectives
+            .iter()
+            .filter(|o| !o.optional)
+            .all(|o| progress.counts.get(&o.id).is_some_and(|c| *c >= o.count));
+        if done {
+            progress.state = QuestState::Completed;
+            events.publish(QuestEvent::Completed(quest_name.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// complete an objective outright
+    pub fn complete_objective(
+        &mut self,
+        quests: &QuestRegistry,
+        quest_name: &str,
+        objective: &str,
+        events: &mut EventBus,
+    ) -> Result<(), String> {
+        let count = quests
+            .quest(quest_name)?
+            .objective(quest_name, objective)?
+            .count;
+        self.add_progress(quests, quest_name, objective, count, events)
+    }
+
+    /// end an active quest unsuccessfully
+    pub fn fail(&mut self, quest_name: &str, events: &mut EventBus) -> Result<(), String> {
+        match self.quests.get_mut(quest_name) {
+            Some(progress) if progress.state == QuestState::Active => {
+                progress.state = QuestState::Failed;
+                events.publish(QuestEvent::Failed(quest_name.to_owned()));
+                Ok(())
+            }
+            _ => Err(format!("quest \"{quest_name}\" isn't active")),
+        }
+    }
+
+    /// forget the quest, so it can be started again
+    pub fn reset(&mut self, quest_name: &str) {
+        self.quests.remove(quest_name);
+    }
+
+    /// the active quests, sorted
+    pub fn active(&self) -> impl Iterator<Item = &str> {
+        self.quests
+            .iter()
+            .filter(|(_, q)| q.state == QuestState::Active)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// the unfinished objectives of every active quest, in quest then
+    /// definition order
+    pub fn active_objectives<'a>(&'a self, quests: &'a QuestRegistry) -> Vec<ActiveObjective<'a>> {
+        let mut objectives = Vec::new();
+        for quest_name in self.active() {
+            let Some(quest) = quests.get(quest_name) else {
+                continue;
+            };
+            for objective in &quest.objectives {
+                let progress = self.progress(quest_name, &objective.id);
+                if progress < objective.count {
+                    objectives.push(ActiveObjective {
+                        quest: quest_name,
+                        objective,
+                        progress,
+                    });
+                }
+            }
+        }
+        objectives
+    }
+
+    /// for saving:
+    ///
+    /// ```toml
+    /// [rat_problem]
+    /// state = "active"
+    /// progress = { rats = 3 }
+    /// ```
+    pub fn to_toml(&self) -> toml::Table {
+        self.quests
+            .iter()
+            .map(|(name, progress)| {
+                let mut table = toml::Table::new();
+                table.insert("state".into(), progress.state.name().into());
+                let counts: toml::Table = progress
+                    .counts
+                    .iter()
+                    .map(|(o, c)| (o.clone(), toml::Value::Integer(*c as i64)))
+                    .collect();
+                table.insert("progress".into(), counts.into());
+                (name.clone(), table.into())
+            })
+            .collect()
+    }
+
+    pub fn from_toml(table: &toml::Table) -> Result<Self, String> {
+        let mut log = Self::new();
+        for (name, value) in table {
+            let entry = value
+                .as_table()
+                .ok_or_else(|| format!("quest \"{name}\" must be a table"))?;
+            let state = match entry.get("state").and_then(toml::Value::as_str) {
+                Some("active") => QuestState::Active,
+                Some("completed") => QuestState::Completed,
+                Some("failed") => QuestState::Failed,
+                _ => {
+                    return Err(format!(
+                        "quest \"{name}\" state must be active, completed or failed"
+                    ))
+                }
+            };
+            let mut counts = BTreeMap::new();
+            if let Some(progress) = entry.get("progress") {
+                let progress = progress
+                    .as_table()
+                    .ok_or_else(|| format!("quest \"{name}\" progress must be a table"))?;
+                for (objective, count) in progress {
+                    let count = count
+                        .as_integer()
+                        .and_then(|c| u32::try_from(c).ok())
+                        .ok_or_else(|| {
+                            format!("quest \"{name}\" progress must be positive integers")
+                        })?;
+                    counts.insert(objective.clone(), count);
+                }
+            }
+            log.quests
+                .insert(name.clone(), QuestProgress { state, counts });
+        }
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quests() {
+        let mut quests = QuestRegistry::new();
+        quests
+            .load_str(
+                r#"
+                [meet]
+                objectives = [{ id = "talk", description = "Talk to the innkeeper" }]
+
+                [rats]
+                requires = ["meet"]
+                title = "A Rat Problem"
+                objectives = [
+                    { id = "rats", description = "Clear the cellar", count = 5 },
+                    { id = "cheese", description = "Find the cheese", optional = true },
+                ]
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            quests.get("rats").unwrap().params["title"].as_str(),
+            Some("A Rat Problem")
+        );
+
+        let mut events = EventBus::default();
+        let mut log = QuestLog::new();
+        assert_eq!(log.available(&quests), vec!["meet"]);
+        assert!(log.start(&quests, "rats", &mut events).is_err());
+        log.start(&quests, "meet", &mut events).unwrap();
+        log.complete_objective(&quests, "meet", "talk", &mut events)
+            .unwrap();
+        assert!(log.is_completed("meet"));
+        events.advance();
+        assert_eq!(
+            events.read::<QuestEvent>(),
+            [
+                QuestEvent::Started("meet".into()),
+                QuestEvent::ObjectiveCompleted {
+                    quest: "meet".into(),
+                    objective: "talk".into()
+                },
+                QuestEvent::Completed("meet".into()),
+            ]
+        );
+
+        log.start(&quests, "rats", &mut events).unwrap();
+        log.add_progress(&quests, "rats", "rats", 3, &mut events)
+            .unwrap();
+        let hud: Vec<(&str, u32)> = log
+            .active_objectives(&quests)
+            .iter()
+            .map(|o| (o.objective.id.as_str(), o.progress))
+            .collect();
+        assert_eq!(hud, vec![("rats", 3), ("cheese", 0)]);
+        assert!(log
+            .add_progress(&quests, "rats", "dogs", 1, &mut events)
+            .is_err());
+
+        let saved = QuestLog::from_toml(&log.to_toml()).unwrap();
+        assert_eq!(saved, log);
+
+        log.add_progress(&quests, "rats", "rats", 9, &mut events)
+            .unwrap();
+        assert_eq!(log.progress("rats", "rats"), 5);
+        assert!(log.is_completed("rats"));
+        assert!(log.active_objectives(&quests).is_empty());
+        assert!(log.fail("rats", &mut events).is_err());
+    }
+}
@@ -1,14 +1,39 @@
-use std::{ffi::CStr, marker::PhantomData, num::NonZeroUsize, path::Path};
+use std::{
+    marker::PhantomData,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+#[cfg(feature = "ttf")]
+use std::ffi::CStr;
 
-use lru::LruCache;
+#[cfg(feature = "image")]
+use sdl2::image::LoadTexture;
 use sdl2::{
-    image::LoadTexture,
-    render::{Canvas, Texture, TextureCreator},
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{BlendMode, Canvas, Texture, TextureCreator},
+    surface::Surface,
     video::{Window, WindowContext},
 };
 
+#[cfg(feature = "ttf")]
+use super::{font_system::font_system::FontSystem, text_path::TextPath};
+#[cfg(not(feature = "image"))]
+use super::decode::{load_surface_bytes, load_surface_file};
 use super::{
-    font_system::font_system::FontSystem, render_system_txt_key::FileOrRenderedTextKey,
+    decode::{clear_color_key, read_surface, rgba_pixels, DecodedImage},
+    dirty::{DirtyRegions, Redraw},
+    lighting::{falloff, shadow_mesh},
+    post_process::{
+        filter_color_blind, swap_palette, vignette_alpha, wipe_rect, Effect, EffectStack, Palette,
+    },
+    profiler,
+    render_system_txt_key::FileOrRenderedTextKey,
+    renderer::render_geometry,
+    texture_cache::{CacheClass, TextureCache},
+    trace::{log_debug, log_warn},
+    vfs::Vfs,
 };
 
 /// textures must only be used with their originating canvas + creator. this
@@ -30,7 +55,7 @@ impl CanvasAndCreator {
     }
 }
 
-struct TextureWrapper(pub Texture);
+pub(crate) struct TextureWrapper(pub Texture);
 
 impl Drop for TextureWrapper {
     fn drop(&mut self) {
@@ -38,36 +63,660 @@ impl Drop for TextureWrapper {
     }
 }
 
+/// side length of the generated vignette texture. it's stretched over the frame
+const VIGNETTE_SIZE: u32 = 128;
+
+/// clips away every draw, for frames with nothing to redraw
+fn offscreen() -> Rect {
+    Rect::new(-1, -1, 1, 1)
+}
+
+/// side length of the generated light texture. it's stretched to each light
+const LIGHT_SIZE: u32 = 128;
+
+fn image_texture(creator: &TextureCreator<WindowContext>, image: &DecodedImage) -> Result<TextureWrapper, String> {
+    let mut texture = creator
+        .create_texture_static(PixelFormatEnum::RGBA32, image.width, image.height)
+        .map_err(|e| e.to_string())?;
+    texture.set_blend_mode(BlendMode::Blend);
+    let mut texture = TextureWrapper(texture);
+    texture
+        .0
+        .update(None, &image.rgba, image.width as usize * 4)
+        .map_err(|e| e.to_string())?;
+    Ok(texture)
+}
+
 /// manages loading and unloading of textures, and rendering text
 pub struct RenderSystem<'sdl> {
+    /// post processing applied on present, in order
+    pub effects: EffectStack,
     /// using unsafe_textures features, but that's ok; the creator and textures
     /// all live in the same struct - no realistic opportunity for misuse
-    textures: LruCache<FileOrRenderedTextKey, TextureWrapper>,
+    textures: TextureCache<TextureWrapper>,
+    /// the frame is drawn here instead of to the window while there are
+    /// effects. sized to the logical size
+    target: Option<(TextureWrapper, (u32, u32))>,
+    /// true while the target is bound
+    drawing_to_target: bool,
+    /// generated for the parameters of the most recently drawn vignette
+    vignette: Option<(TextureWrapper, (Color, u32, u32))>,
+    /// the ambient light plus each light, multiplied over the frame
+    light_map: Option<(TextureWrapper, (u32, u32))>,
+    /// a single light with its shadows, before it's added to the light map
+    shadow_map: Option<TextureWrapper>,
+    light: Option<TextureWrapper>,
+    /// the frame kept for a crossfade
+    snapshot: Option<TextureWrapper>,
+    /// the frame scaled down, for pixelate
+    pixelate: Option<(TextureWrapper, (u32, u32))>,
+    /// set in dirty rect mode, where the frame is kept on the target between
+    /// frames and only what changed is drawn again
+    dirty: Option<DirtyRegions>,
+    /// the area this frame's draws are clipped to, in dirty rect mode
+    dirty_clip: Option<Rect>,
+    #[cfg(feature = "egui")]
+    egui_textures: std::collections::HashMap<egui::TextureId, TextureWrapper>,
     /// dropped after textures are dropped. important, because unsafe-texture
     cc: CanvasAndCreator,
     _phantom: PhantomData<&'sdl ()>,
 }
 
 impl<'sdl> RenderSystem<'sdl> {
-    pub fn new(cc: CanvasAndCreator, num_loaded_textures: NonZeroUsize) -> Self {
+    /// caching up to the number of textures from files, and separately the
+    /// number of rendered text
+    pub fn new(cc: CanvasAndCreator, num_textures: NonZeroUsize, num_text: NonZeroUsize) -> Self {
         Self {
             cc,
-            textures: LruCache::new(num_loaded_textures),
+            effects: Default::default(),
+            textures: TextureCache::new(num_textures, num_text),
+            target: None,
+            drawing_to_target: false,
+            vignette: None,
+            light_map: None,
+            shadow_map: None,
+            light: None,
+            snapshot: None,
+            pixelate: None,
+            dirty: None,
+            dirty_clip: None,
+            #[cfg(feature = "egui")]
+            egui_textures: Default::default(),
             _phantom: Default::default(),
         }
     }
 
-    pub fn present(&mut self) {
+    /// apply the effects, if any, then show the frame
+    pub fn present(&mut self) -> Result<(), String> {
+        self.unlock_textures();
+        if let Some(dirty) = self.dirty.as_mut() {
+            if dirty.is_clean() {
+                // what's showing is still current
+                return Ok(());
+            }
+            dirty.clear();
+        }
+        if self.drawing_to_target {
+            self.apply_effects()?;
+        }
         self.cc.canvas.present();
+        Ok(())
+    }
+
+    pub fn canvas(&mut self) -> &mut Canvas<Window> {
+        &mut self.cc.canvas
+    }
+
+    pub fn window_id(&self) -> u32 {
+        self.cc.canvas.window().id()
+    }
+
+    /// the size of the canvas in pixels
+    pub fn output_size(&self) -> Result<(u32, u32), String> {
+        self.cc.canvas.output_size()
+    }
+
+    /// (0, 0) if no logical size is set
+    pub fn logical_size(&self) -> (u32, u32) {
+        self.cc.canvas.logical_size()
+    }
+
+    /// the area drawn to: the logical size if set, otherwise the output size
+    pub fn drawable_size(&self) -> Result<(u32, u32), String> {
+        match self.cc.canvas.logical_size() {
+            (0, _) | (_, 0) => self.cc.canvas.output_size(),
+            size => Ok(size),
+        }
+    }
+
+    /// draw egui's output on the canvas. its textures are kept until egui
+    /// frees them
+    #[cfg(feature = "egui")]
+    pub fn paint_egui(
+        &mut self,
+        delta: &egui::TexturesDelta,
+        primitives: &[egui::ClippedPrimitive],
+    ) -> Result<(), String> {
+        super::egui_layer::paint(&mut self.cc, &mut self.egui_textures, delta, primitives)
+    }
+
+    /// copy what's currently drawn on the canvas, before effects are applied.
+    /// slow; not for every frame
+    pub fn screenshot(&self) -> Result<Surface<'static>, String> {
+        let format = PixelFormatEnum::RGBA32;
+        let pixels = self.cc.canvas.read_pixels(None, format)?;
+        let (width, height) = match (&self.target, self.drawing_to_target) {
+            (Some((_, size)), true) => *size,
+            _ => self.cc.canvas.output_size()?,
+        };
+        let mut surface = Surface::new(width, height, format)?;
+        let row_len = width as usize * format.byte_size_per_pixel();
+        let pitch = surface.pitch() as usize;
+        surface.with_lock_mut(|dst| {
+            for (row, src) in pixels.chunks_exact(row_len).enumerate() {
+                dst[row * pitch..row * pitch + row_len].copy_from_slice(src);
+            }
+        });
+        Ok(surface)
+    }
+
+    /// starts the frame. while there are effects, the frame is drawn to an
+    /// offscreen target
+    pub fn clear(&mut self, color: Color) -> Result<(), String> {
+        if self.dirty.is_some() {
+            return self.clear_dirty(color);
+        }
+        self.bind_target(!self.effects.is_empty())?;
+        self.cc.canvas.set_clip_rect(None);
+        self.cc.canvas.set_draw_color(color);
+        self.cc.canvas.clear();
+        Ok(())
+    }
+
+    /// only draw and present what changed, for tool style apps where redrawing
+    /// every frame wastes power. the frame is kept on an offscreen target;
+    /// each frame only the dirty area is cleared, and draws are clipped to it.
+    /// frames with nothing dirty aren't presented. see mark_dirty
+    pub fn set_dirty_tracking(&mut self, enabled: bool) {
+        self.dirty = enabled.then(DirtyRegions::new);
+        self.dirty_clip = None;
+    }
+
+    /// the area (in logical coordinates) needs to be drawn again. None for all
+    /// of it. does nothing unless tracking dirty rects
+    pub fn mark_dirty(&mut self, rect: Option<Rect>) {
+        match (self.dirty.as_mut(), rect) {
+            (None, _) => {}
+            (Some(dirty), Some(rect)) => dirty.mark(rect),
+            (Some(dirty), None) => dirty.mark_all(),
+        }
+    }
+
+    /// false if tracking dirty rects and nothing is dirty
+    pub fn needs_redraw(&self) -> bool {
+        self.dirty.as_ref().is_none_or(|dirty| !dirty.is_clean())
+    }
+
+    fn clear_dirty(&mut self, color: Color) -> Result<(), String> {
+        let size = self.drawable_size()?;
+        let resized = self.target.as_ref().is_none_or(|(_, s)| *s != size);
+        let dirty = self.dirty.as_mut().expect("tracking dirty rects");
+        if resized || !self.effects.is_empty() {
+            // effects are drawn over the kept frame
+            dirty.mark_all();
+        }
+        let redraw = dirty.redraw();
+        self.bind_target(true)?;
+        self.cc.canvas.set_draw_color(color);
+        self.dirty_clip = match redraw {
+            Redraw::All => {
+                self.cc.canvas.set_clip_rect(None);
+                self.cc.canvas.clear();
+                None
+            }
+            Redraw::Area(area) => {
+                // clear ignores the clip rect
+                let blend_mode = self.cc.canvas.blend_mode();
+                self.cc.canvas.set_blend_mode(BlendMode::None);
+                self.cc.canvas.set_clip_rect(area);
+                self.cc.canvas.fill_rect(area)?;
+                self.cc.canvas.set_blend_mode(blend_mode);
+                Some(area)
+            }
+            Redraw::Nothing => {
+                self.cc.canvas.set_clip_rect(offscreen());
+                Some(offscreen())
+            }
+        };
+        Ok(())
+    }
+
+    /// restrict draws to the rect, in logical coordinates. in dirty rect
+    /// mode, draws stay within the dirty area too
+    pub fn set_clip(&mut self, clip: Option<Rect>) {
+        let clip = match (clip, self.dirty_clip) {
+            (Some(clip), Some(dirty)) => Some(clip.intersection(dirty).unwrap_or_else(offscreen)),
+            (None, dirty) => dirty,
+            (clip, None) => clip,
+        };
+        self.cc.canvas.set_clip_rect(clip);
+    }
+
+    /// None for the window
+    fn set_render_target(canvas: &mut Canvas<Window>, target: Option<&Texture>) -> Result<(), String> {
+        let texture = target.map_or(std::ptr::null_mut(), |t| t.raw());
+        match unsafe { sdl2::sys::SDL_SetRenderTarget(canvas.raw(), texture) } {
+            0 => Ok(()),
+            _ => Err(sdl2::get_error()),
+        }
+    }
+
+    fn bind_target(&mut self, bind: bool) -> Result<(), String> {
+        if !bind {
+            if self.drawing_to_target {
+                Self::set_render_target(&mut self.cc.canvas, None)?;
+                self.drawing_to_target = false;
+            }
+            return Ok(());
+        }
+        let size = self.drawable_size()?;
+        if self.target.as_ref().is_none_or(|(_, s)| *s != size) {
+            if self.drawing_to_target {
+                Self::set_render_target(&mut self.cc.canvas, None)?;
+                self.drawing_to_target = false;
+            }
+            self.target = None;
+            let texture = self
+                .cc
+                .creator
+                .create_texture_target(PixelFormatEnum::RGBA32, size.0, size.1)
+                .map_err(|e| e.to_string())?;
+            self.target = Some((TextureWrapper(texture), size));
+            log_debug!("created {}x{} post processing target", size.0, size.1);
+        }
+        Self::set_render_target(&mut self.cc.canvas, self.target.as_ref().map(|(t, _)| &t.0))?;
+        self.drawing_to_target = true;
+        Ok(())
+    }
+
+    /// draw each effect onto the target in order, then copy it to the window
+    fn apply_effects(&mut self) -> Result<(), String> {
+        let _zone = profiler::zone("post processing");
+        let (width, height) = self.target.as_ref().map(|(_, size)| *size).unwrap_or((1, 1));
+        let blend_mode = self.cc.canvas.blend_mode();
+        self.cc.canvas.set_blend_mode(BlendMode::Blend);
+        if self.effects.lighting.is_some() {
+            self.apply_lighting((width, height))?;
+        }
+        if !self.effects.iter().any(|e| matches!(e, Effect::Crossfade(_))) {
+            self.snapshot = None;
+        }
+        for i in 0..self.effects.iter().count() {
+            match self.effects.iter().nth(i).expect("in range") {
+                Effect::Shake(_) => {}
+                Effect::Fade(color) => {
+                    self.cc.canvas.set_draw_color(*color);
+                    self.cc.canvas.fill_rect(None)?;
+                }
+                Effect::Scanlines { spacing, color } => {
+                    let rows: Vec<Rect> = (0..height)
+                        .step_by((*spacing).max(1) as usize)
+                        .map(|y| Rect::new(0, y as i32, width, 1))
+                        .collect();
+                    self.cc.canvas.set_draw_color(*color);
+                    self.cc.canvas.fill_rects(&rows)?;
+                }
+                Effect::Vignette {
+                    color,
+                    radius,
+                    softness,
+                } => {
+                    let key = (*color, radius.to_bits(), softness.to_bits());
+                    if self.vignette.as_ref().is_none_or(|(_, k)| *k != key) {
+                        self.vignette = None;
+                        let texture = self.vignette_texture(*color, *radius, *softness)?;
+                        self.vignette = Some((texture, key));
+                    }
+                    let vignette = &self.vignette.as_ref().expect("just created").0 .0;
+                    self.cc.canvas.copy(vignette, None, None)?;
+                }
+                Effect::PaletteSwap(palette) => {
+                    let format = PixelFormatEnum::RGBA32;
+                    let mut pixels = self.cc.canvas.read_pixels(None, format)?;
+                    swap_palette(&mut pixels, palette);
+                    let target = &mut self.target.as_mut().expect("target is bound").0 .0;
+                    target
+                        .update(None, &pixels, width as usize * format.byte_size_per_pixel())
+                        .map_err(|e| e.to_string())?;
+                }
+                Effect::ColorBlind(mode) => {
+                    let format = PixelFormatEnum::RGBA32;
+                    let mut pixels = self.cc.canvas.read_pixels(None, format)?;
+                    filter_color_blind(&mut pixels, *mode);
+                    let target = &mut self.target.as_mut().expect("target is bound").0 .0;
+                    target
+                        .update(None, &pixels, width as usize * format.byte_size_per_pixel())
+                        .map_err(|e| e.to_string())?;
+                }
+                Effect::Wipe { color, amount, direction } => {
+                    if let Some(rect) = wipe_rect((width, height), *amount, *direction) {
+                        self.cc.canvas.set_draw_color(*color);
+                        self.cc.canvas.fill_rect(rect)?;
+                    }
+                }
+                Effect::Pixelate(block) => {
+                    if *block <= 1 {
+                        continue;
+                    }
+                    let size = (width.div_ceil(*block), height.div_ceil(*block));
+                    if self.pixelate.as_ref().is_none_or(|(_, s)| *s != size) {
+                        self.pixelate = None;
+                        let texture = self
+                            .cc
+                            .creator
+                            .create_texture_target(PixelFormatEnum::RGBA32, size.0, size.1)
+                            .map_err(|e| e.to_string())?;
+                        unsafe {
+                            sdl2::sys::SDL_SetTextureScaleMode(
+                                texture.raw(),
+                                sdl2::sys::SDL_ScaleMode::SDL_ScaleModeNearest,
+                            );
+                        }
+                        self.pixelate = Some((TextureWrapper(texture), size));
+                    }
+                    // down to the small texture and back up, unfiltered
+                    let small = &mut self.pixelate.as_mut().expect("just created").0 .0;
+                    let target = &mut self.target.as_mut().expect("target is bound").0 .0;
+                    target.set_blend_mode(BlendMode::None);
+                    small.set_blend_mode(BlendMode::None);
+                    Self::set_render_target(&mut self.cc.canvas, Some(small))?;
+                    self.cc.canvas.copy(target, None, None)?;
+                    Self::set_render_target(&mut self.cc.canvas, Some(target))?;
+                    self.cc.canvas.copy(small, None, None)?;
+                }
+                Effect::Crossfade(alpha) => {
+                    let target = &mut self.target.as_mut().expect("target is bound").0 .0;
+                    match self.snapshot.as_mut() {
+                        Some(snapshot) => {
+                            snapshot.0.set_blend_mode(BlendMode::Blend);
+                            snapshot.0.set_alpha_mod(*alpha);
+                            self.cc.canvas.copy(&snapshot.0, None, None)?;
+                        }
+                        None => {
+                            // the first frame; keep it for the ones after
+                            let snapshot = self
+                                .cc
+                                .creator
+                                .create_texture_target(PixelFormatEnum::RGBA32, width, height)
+                                .map_err(|e| e.to_string())?;
+                            target.set_blend_mode(BlendMode::None);
+                            Self::set_render_target(&mut self.cc.canvas, Some(&snapshot))?;
+                            self.cc.canvas.copy(target, None, None)?;
+                            Self::set_render_target(&mut self.cc.canvas, Some(target))?;
+                            self.snapshot = Some(TextureWrapper(snapshot));
+                        }
+                    }
+                }
+            }
+        }
+        self.cc.canvas.set_blend_mode(blend_mode);
+
+        Self::set_render_target(&mut self.cc.canvas, None)?;
+        self.drawing_to_target = false;
+        self.cc.canvas.set_draw_color(Color::BLACK);
+        self.cc.canvas.clear();
+        let offset = self.effects.offset();
+        let target = &mut self.target.as_mut().expect("target was bound").0 .0;
+        target.set_blend_mode(BlendMode::None);
+        self.cc.canvas.copy(
+            target,
+            None,
+            Rect::new(offset.x().round() as i32, offset.y().round() as i32, width, height),
+        )
+    }
+
+    /// draw the light map, then multiply it over the bound target
+    fn apply_lighting(&mut self, size: (u32, u32)) -> Result<(), String> {
+        let Some(lighting) = self.effects.lighting.as_ref() else {
+            return Ok(());
+        };
+        if self.light.is_none() {
+            self.light = Some(self.light_texture()?);
+        }
+        if self.light_map.as_ref().is_none_or(|(_, s)| *s != size) {
+            self.light_map = None;
+            let texture = self
+                .cc
+                .creator
+                .create_texture_target(PixelFormatEnum::RGBA32, size.0, size.1)
+                .map_err(|e| e.to_string())?;
+            self.light_map = Some((TextureWrapper(texture), size));
+            self.shadow_map = None;
+        }
+        let shadows = !lighting.occluders.is_empty();
+        if shadows && self.shadow_map.is_none() {
+            let mut texture = self
+                .cc
+                .creator
+                .create_texture_target(PixelFormatEnum::RGBA32, size.0, size.1)
+                .map_err(|e| e.to_string())?;
+            texture.set_blend_mode(BlendMode::Add);
+            self.shadow_map = Some(TextureWrapper(texture));
+        }
+        let light_map = &mut self.light_map.as_mut().expect("just created").0 .0;
+        let light = &mut self.light.as_mut().expect("just created").0;
+        Self::set_render_target(&mut self.cc.canvas, Some(light_map))?;
+        self.cc.canvas.set_draw_color(lighting.ambient);
+        self.cc.canvas.clear();
+        for l in lighting.lights.iter() {
+            let tint = l.tint();
+            light.set_color_mod(tint.r, tint.g, tint.b);
+            let (vertices, indices) = if shadows {
+                shadow_mesh(l, &lighting.occluders)
+            } else {
+                Default::default()
+            };
+            if indices.is_empty() {
+                self.cc.canvas.copy_f(light, None, l.bounds())?;
+                continue;
+            }
+            // the light alone on black, darkened by its shadows, then added
+            let shadow_map = &self.shadow_map.as_ref().expect("created with occluders").0;
+            Self::set_render_target(&mut self.cc.canvas, Some(shadow_map))?;
+            self.cc.canvas.set_draw_color(Color::BLACK);
+            self.cc.canvas.clear();
+            self.cc.canvas.copy_f(light, None, l.bounds())?;
+            render_geometry(&mut self.cc.canvas, &vertices, &indices)?;
+            Self::set_render_target(&mut self.cc.canvas, Some(light_map))?;
+            self.cc.canvas.copy(shadow_map, None, None)?;
+        }
+        let target = &self.target.as_ref().expect("target is bound").0 .0;
+        Self::set_render_target(&mut self.cc.canvas, Some(target))?;
+        light_map.set_blend_mode(BlendMode::Mod);
+        self.cc.canvas.copy(light_map, None, None)
+    }
+
+    /// white, fading out from the center. added onto the light map
+    fn light_texture(&self) -> Result<TextureWrapper, String> {
+        let format = PixelFormatEnum::RGBA32;
+        let mut surface = Surface::new(LIGHT_SIZE, LIGHT_SIZE, format)?;
+        let pitch = surface.pitch() as usize;
+        surface.with_lock_mut(|pixels| {
+            for y in 0..LIGHT_SIZE {
+                for x in 0..LIGHT_SIZE {
+                    let dx = (x as f32 + 0.5) / LIGHT_SIZE as f32 * 2. - 1.;
+                    let dy = (y as f32 + 0.5) / LIGHT_SIZE as f32 * 2. - 1.;
+                    let v = (falloff(dx.hypot(dy)) * 255.) as u8;
+                    let i = y as usize * pitch + x as usize * 4;
+                    pixels[i..i + 4].copy_from_slice(&[v, v, v, 255]);
+                }
+            }
+        });
+        let mut texture = self
+            .cc
+            .creator
+            .create_texture_from_surface(surface)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Add);
+        Ok(TextureWrapper(texture))
+    }
+
+    fn vignette_texture(&self, color: Color, radius: f32, softness: f32) -> Result<TextureWrapper, String> {
+        let format = PixelFormatEnum::RGBA32;
+        let mut surface = Surface::new(VIGNETTE_SIZE, VIGNETTE_SIZE, format)?;
+        let pitch = surface.pitch() as usize;
+        surface.with_lock_mut(|pixels| {
+            for y in 0..VIGNETTE_SIZE {
+                for x in 0..VIGNETTE_SIZE {
+                    let alpha = vignette_alpha(
+                        (x as f32 + 0.5) / VIGNETTE_SIZE as f32,
+                        (y as f32 + 0.5) / VIGNETTE_SIZE as f32,
+                        radius,
+                        softness,
+                    );
+                    let i = y as usize * pitch + x as usize * 4;
+                    pixels[i..i + 4].copy_from_slice(&[
+                        color.r,
+                        color.g,
+                        color.b,
+                        (alpha * color.a as f32) as u8,
+                    ]);
+                }
+            }
+        });
+        let mut texture = self
+            .cc
+            .creator
+            .create_texture_from_surface(surface)
+            .map_err(|e| e.to_string())?;
+        texture.set_blend_mode(BlendMode::Blend);
+        unsafe {
+            sdl2::sys::SDL_SetTextureScaleMode(
+                texture.raw(),
+                sdl2::sys::SDL_ScaleMode::SDL_ScaleModeLinear,
+            );
+        }
+        Ok(TextureWrapper(texture))
+    }
+
+    /// cache an image decoded elsewhere as if it was loaded from the path
+    pub fn insert_image(&mut self, path: &Path, image: &DecodedImage) -> Result<(), String> {
+        let key = FileOrRenderedTextKey::from_path(path);
+        self.trace_eviction(&key);
+        let texture = image_texture(&self.cc.creator, image)?;
+        self.textures.put(key, texture);
+        Ok(())
+    }
+
+    /// the image at the path with its colors swapped through the palette,
+    /// cached under a key derived from the path and the palette's name
+    pub fn recolored_texture(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        palette_name: &str,
+        palette: &Palette,
+    ) -> Result<(&mut Texture, &mut Canvas<Window>), String> {
+        let key = FileOrRenderedTextKey::from_path_with_palette(path, palette_name);
+        self.trace_eviction(&key);
+        let creator = &self.cc.creator;
+        let texture = self.textures.try_get_or_insert_mut(key, || {
+            let mut image = read_surface(assets, path).and_then(rgba_pixels)?;
+            swap_palette(&mut image.rgba, palette);
+            log_debug!("recolored {} with palette {}", path.display(), palette_name);
+            image_texture(creator, &image)
+        })?;
+        Ok((&mut texture.0, &mut self.cc.canvas))
+    }
+
+    /// the image at the path with the color key's pixels transparent, cached
+    /// under a key derived from the path and the color
+    pub fn color_keyed_texture(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        color_key: Color,
+    ) -> Result<(&mut Texture, &mut Canvas<Window>), String> {
+        let key = FileOrRenderedTextKey::from_path_with_color_key(path, color_key.rgb());
+        self.trace_eviction(&key);
+        let creator = &self.cc.creator;
+        let texture = self.textures.try_get_or_insert_mut(key, || {
+            let mut image = read_surface(assets, path).and_then(rgba_pixels)?;
+            clear_color_key(&mut image.rgba, color_key);
+            image_texture(creator, &image)
+        })?;
+        Ok((&mut texture.0, &mut self.cc.canvas))
+    }
+
+    /// drop the cached text rendered with the font, e.g. once the font file
+    /// changed. returns how many were dropped
+    #[cfg(feature = "ttf")]
+    pub fn evict_font_text(&mut self, font_file: &Path) -> usize {
+        self.textures.evict_font_text(font_file)
+    }
+
+    /// how many of the class are cached before the least recently used is
+    /// dropped
+    pub fn cache_capacity(&self, class: CacheClass) -> NonZeroUsize {
+        self.textures.cap(class)
+    }
+
+    /// dropping the least recently used if it's shrunk
+    pub fn set_cache_capacity(&mut self, class: CacheClass, capacity: NonZeroUsize) {
+        self.textures.resize(class, capacity);
+    }
+
+    /// the files cached textures were loaded from, most recently used first
+    pub fn cached_paths(&self) -> Vec<PathBuf> {
+        self.textures.texture_paths()
+    }
+
+    /// if the image at the path is in the cache
+    pub fn is_cached(&self, path: &Path) -> bool {
+        self.textures
+            .contains(&FileOrRenderedTextKey::from_path(path))
+    }
+
+    /// drop the image at the path from the cache, e.g. once it's no longer
+    /// needed. false if it wasn't cached
+    pub fn evict_texture(&mut self, path: &Path) -> bool {
+        self.textures
+            .pop(&FileOrRenderedTextKey::from_path(path))
+            .is_some()
+    }
+
+    /// keep the texture (once it's loaded) cached until the frame is
+    /// presented, even if it's evicted
+    pub fn lock_texture(&mut self, key: FileOrRenderedTextKey) {
+        self.textures.lock(key);
+    }
+
+    /// release the locked textures. done when presenting
+    pub fn unlock_textures(&mut self) {
+        self.textures.unlock_all();
+    }
+
+    /// log when inserting the key will evict the least recently used texture
+    fn trace_eviction(&self, key: &FileOrRenderedTextKey) {
+        if cfg!(feature = "tracing") && self.textures.is_evicting(key) {
+            let class = CacheClass::of(key);
+            log_debug!(
+                "{:?} cache is full ({}); evicting the least recently used",
+                class,
+                self.textures.cap(class)
+            );
+        }
     }
 
     /// create the texture for the rendered font, load the font as needed
     ///
     /// returns the loaded texture and the canvas to draw it on. note that
     /// changes to the texture (color mod, etc) may be retained to future calls
+    #[cfg(feature = "ttf")]
     pub fn text(
         &mut self,
         font_system: &mut FontSystem,
+        assets: &Vfs,
         font_file: &Path,
         point_size: u16,
         text: &CStr,
@@ -81,28 +730,81 @@ impl<'sdl> RenderSystem<'sdl> {
             None => FileOrRenderedTextKey::from_rendered_text(text, font_file, point_size),
         };
 
+        self.trace_eviction(&key);
         Ok((
             &mut self.textures
                 .try_get_or_insert_mut(key, || -> Result<TextureWrapper, String> {
-                    let surface = font_system.render(font_file, point_size, text, wrap_width)?;
-                    self.cc
+                    let _zone = profiler::zone("text render");
+                    let start = Instant::now();
+                    let surface = font_system.render(assets, font_file, point_size, text, wrap_width)?;
+                    let texture = self.cc
                         .creator
                         .create_texture_from_surface(surface)
-                        .map_err(|e| e.to_string()).map(|txt| TextureWrapper(txt))
+                        .map_err(|e| e.to_string()).map(|txt| TextureWrapper(txt));
+                    log_debug!("text cache miss; rendered {:?} in {:?}", text, start.elapsed());
+                    texture
                 })?.0,
             &mut self.cc.canvas,
         ))
     }
 
-    /// load the texture from the file path if its not in the cache
+    /// the texture for the text along the path, rendering it if it's not
+    /// cached. it covers the path's area (see TextPath::area)
+    #[cfg(feature = "ttf")]
+    pub fn text_on_path(
+        &mut self,
+        font_system: &mut FontSystem,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        path: &TextPath,
+    ) -> Result<(&mut Texture, &mut Canvas<Window>), String> {
+        let key = FileOrRenderedTextKey::from_text_on_path(text, font_file, point_size, path.hash_key());
+        self.trace_eviction(&key);
+        let creator = &self.cc.creator;
+        let texture = self.textures.try_get_or_insert_mut(key, || {
+            let _zone = profiler::zone("text render");
+            let start = Instant::now();
+            let image = font_system.render_on_path(assets, font_file, point_size, text, path)?;
+            log_debug!("text cache miss; rendered {:?} along a path in {:?}", text, start.elapsed());
+            image_texture(creator, &image)
+        })?;
+        Ok((&mut texture.0, &mut self.cc.canvas))
+    }
+
+    /// load the texture from the file path if its not in the cache. the path
+    /// is resolved through the vfs
     ///
     /// returns the loaded texture and the canvas to draw it on. note that
     /// changes to the texture (color mod, etc) may be retained to future calls
-    pub fn texture(&mut self, path: &Path) -> Result<(&mut Texture, &mut Canvas<Window>), String> {
+    pub fn texture(&mut self, assets: &Vfs, path: &Path) -> Result<(&mut Texture, &mut Canvas<Window>), String> {
+        let key = FileOrRenderedTextKey::from_path(path);
+        self.trace_eviction(&key);
         Ok((
             &mut self.textures
-                .try_get_or_insert_mut(FileOrRenderedTextKey::from_path(path), || {
-                    self.cc.creator.load_texture(path).map(|txt| TextureWrapper(txt))
+                .try_get_or_insert_mut(key, || {
+                    let _zone = profiler::zone("texture load");
+                    let start = Instant::now();
+                    #[cfg(feature = "image")]
+                    let texture = if assets.is_mounted(path) {
+                        self.cc.creator.load_texture_bytes(&assets.read(path)?)
+                    } else {
+                        self.cc.creator.load_texture(path)
+                    };
+                    // only bmp without sdl_image
+                    #[cfg(not(feature = "image"))]
+                    let texture = if assets.is_mounted(path) {
+                        load_surface_bytes(&assets.read(path)?)
+                    } else {
+                        load_surface_file(path)
+                    }
+                    .and_then(|surface| self.cc.creator.create_texture_from_surface(surface).map_err(|e| e.to_string()));
+                    match &texture {
+                        Ok(_) => log_debug!("texture cache miss; loaded {} in {:?}", path.display(), start.elapsed()),
+                        Err(e) => log_warn!("failed to load texture {}: {e}", path.display()),
+                    }
+                    texture.map(|txt| TextureWrapper(txt))
                 })?.0,
             &mut self.cc.canvas,
         ))
@@ -1,4 +1,4 @@
-use std::{ffi::CStr, hash::Hasher, os::unix::ffi::OsStrExt, path::Path};
+use std::{ffi::{CStr, OsStr}, hash::Hasher, os::unix::ffi::OsStrExt, path::Path};
 
 /// contains some encoding of the resource. used as lru key.
 /// 
@@ -15,6 +15,19 @@ use std::{ffi::CStr, hash::Hasher, os::unix::ffi::OsStrExt, path::Path};
 /// for rendered wrapping text:
 ///
 /// 0x02 + u16(16pt) + u32(123pix) + "some text\0" + "/path/to/font"
+///
+/// for texture from file, recolored through a palette:
+///
+/// 0x03 + "palette name\0" + "/path/to/texture"
+///
+/// for text rendered along a path:
+///
+/// 0x04 + u16(16pt) + u64(path hash) + "some text\0" + "/path/to/font"
+///
+/// for texture from file, with a color made transparent:
+///
+/// 0x05 + r + g + b + "/path/to/texture"
+#[derive(Clone)]
 pub struct FileOrRenderedTextKey {
     data: Vec<u8>,
 }
@@ -117,6 +130,75 @@ impl FileOrRenderedTextKey {
             data
         }
     }
+
+    pub fn from_path_with_palette(texture_path: &Path, palette_name: &str) -> Self {
+        let path_bytes = texture_path.as_os_str().as_bytes();
+        let data_len = 1 + palette_name.len() + 1 + path_bytes.len();
+        let mut data: Vec<u8> = Default::default();
+        data.reserve_exact(data_len);
+        data.push(b'\x03');
+        data.extend_from_slice(palette_name.as_bytes());
+        data.push(b'\0');
+        data.extend_from_slice(path_bytes);
+        debug_assert_eq!(data.len(), data_len);
+        Self {
+            data
+        }
+    }
+
+    pub fn from_path_with_color_key(texture_path: &Path, color_key: (u8, u8, u8)) -> Self {
+        let path_bytes = texture_path.as_os_str().as_bytes();
+        let data_len = 1 + 3 + path_bytes.len();
+        let mut data: Vec<u8> = Default::default();
+        data.reserve_exact(data_len);
+        data.push(b'\x05');
+        data.extend_from_slice(&[color_key.0, color_key.1, color_key.2]);
+        data.extend_from_slice(path_bytes);
+        debug_assert_eq!(data.len(), data_len);
+        Self {
+            data
+        }
+    }
+
+    /// the file the texture was loaded from. None for anything derived from
+    /// it, and for text
+    pub fn texture_path(&self) -> Option<&Path> {
+        match self.data.split_first()? {
+            (b'\x00', path) => Some(Path::new(OsStr::from_bytes(path))),
+            _ => None,
+        }
+    }
+
+    /// the font the text was rendered with. None for textures from files
+    pub fn font_file(&self) -> Option<&Path> {
+        // skip the variant's fixed size fields, then the text
+        let fields = match self.data.first()? {
+            b'\x01' => size_of::<u16>(),
+            b'\x02' => size_of::<u16>() + size_of::<u32>(),
+            b'\x04' => size_of::<u16>() + size_of::<u64>(),
+            _ => return None,
+        };
+        let text_and_font = self.data.get(1 + fields..)?;
+        let nul = text_and_font.iter().position(|&byte| byte == b'\0')?;
+        Some(Path::new(OsStr::from_bytes(&text_and_font[nul + 1..])))
+    }
+
+    pub fn from_text_on_path(text: &CStr, font_file: &Path, point_size: u16, path_hash: u64) -> Self {
+        let text_bytes = text.to_bytes_with_nul();
+        let font_file_bytes = font_file.as_os_str().as_bytes();
+        let data_len = 1 + size_of::<u16>() + size_of::<u64>() + text_bytes.len() + font_file_bytes.len();
+        let mut data: Vec<u8> = Default::default();
+        data.reserve_exact(data_len);
+        data.push(b'\x04');
+        data.extend_from_slice(&point_size.to_le_bytes());
+        data.extend_from_slice(&path_hash.to_le_bytes());
+        data.extend_from_slice(text_bytes);
+        data.extend_from_slice(font_file_bytes);
+        debug_assert_eq!(data.len(), data_len);
+        Self {
+            data
+        }
+    }
 }
 
 #[cfg(test)]
@@ -172,4 +254,38 @@ mod tests {
         rhs.extend_from_slice(b"abc");
         assert_eq!(s.data, rhs);
     }
+
+    #[test]
+    fn test_palette() {
+        let s = FileOrRenderedTextKey::from_path_with_palette(Path::new("hero.png"), "red");
+        assert_eq!(s.data, b"\x03red\0hero.png");
+        assert!(s != FileOrRenderedTextKey::from_path(Path::new("hero.png")));
+    }
+
+    #[test]
+    fn test_color_key() {
+        let s = FileOrRenderedTextKey::from_path_with_color_key(Path::new("old.bmp"), (255, 0, 255));
+        assert_eq!(s.data, b"\x05\xFF\x00\xFFold.bmp");
+        assert_eq!(s.font_file(), None);
+        assert_eq!(s.texture_path(), None);
+        let path = Path::new("old.bmp");
+        assert_eq!(FileOrRenderedTextKey::from_path(path).texture_path(), Some(path));
+    }
+
+    #[test]
+    fn test_text_on_path() {
+        let s = FileOrRenderedTextKey::from_text_on_path(c"text", Path::new("font.ttf"), 16, 0x0102);
+        assert_eq!(s.data, b"\x04\x10\x00\x02\x01\0\0\0\0\0\0text\0font.ttf");
+        assert!(s != FileOrRenderedTextKey::from_text_on_path(c"text", Path::new("font.ttf"), 16, 0x0103));
+    }
+
+    #[test]
+    fn test_font_file() {
+        let font = Path::new("fonts/font.ttf");
+        assert_eq!(FileOrRenderedTextKey::from_rendered_text(c"text", font, 16).font_file(), Some(font));
+        assert_eq!(FileOrRenderedTextKey::from_rendered_wrapped_text(c"", font, 16, 100).font_file(), Some(font));
+        assert_eq!(FileOrRenderedTextKey::from_text_on_path(c"a", font, 16, 7).font_file(), Some(font));
+        assert_eq!(FileOrRenderedTextKey::from_path(font).font_file(), None);
+        assert_eq!(FileOrRenderedTextKey::from_path_with_palette(font, "red").font_file(), None);
+    }
 }
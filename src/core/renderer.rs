@@ -0,0 +1,445 @@
+#[cfg(feature = "ttf")]
+use std::ffi::CStr;
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect, Point, Rect},
+    render::{Canvas, Texture},
+    surface::Surface,
+    video::Window,
+};
+
+use super::{
+    decode::DecodedImage, post_process::Palette, render_system::RenderSystem,
+    render_system_txt_key::FileOrRenderedTextKey, system::CopyStructExF, texture_cache::CacheClass,
+    vfs::Vfs,
+};
+#[cfg(feature = "ttf")]
+use super::{font_system::font_system::FontSystem, text_path::TextPath};
+
+/// how a texture (or rendered text) is drawn. see Canvas::copy_ex_f
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawParams {
+    /// the whole texture if None
+    pub src: Option<Rect>,
+    /// the whole logical area if None
+    pub dst: Option<FRect>,
+    /// degrees, clockwise
+    pub angle: f64,
+    /// relative to dst. its center if None
+    pub center: Option<FPoint>,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl DrawParams {
+    pub fn new(src: Option<Rect>, dst: Option<FRect>) -> Self {
+        Self {
+            src,
+            dst,
+            angle: 0.,
+            center: None,
+            flip_horizontal: false,
+            flip_vertical: false,
+        }
+    }
+
+    fn is_transformed(&self) -> bool {
+        self.angle != 0. || self.flip_horizontal || self.flip_vertical
+    }
+}
+
+impl From<CopyStructExF> for DrawParams {
+    fn from(copy: CopyStructExF) -> Self {
+        Self {
+            src: copy.src,
+            dst: copy.dst,
+            angle: copy.angle,
+            center: Some(copy.center),
+            flip_horizontal: copy.flip_horizontal,
+            flip_vertical: copy.flip_vertical,
+        }
+    }
+}
+
+/// a corner of an untextured triangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: FPoint,
+    pub color: Color,
+}
+
+pub fn rect_to_f(rect: Rect) -> FRect {
+    FRect::new(
+        rect.x() as f32,
+        rect.y() as f32,
+        rect.width() as f32,
+        rect.height() as f32,
+    )
+}
+
+pub fn point_to_f(point: Point) -> FPoint {
+    FPoint::new(point.x() as f32, point.y() as f32)
+}
+
+/// a window's drawing backend. textures and rendered text are cached per
+/// window, keyed the same way by every backend. the sdl canvas (RenderSystem)
+/// is the default; others are added with ChimericSystem::add_renderer
+pub trait Renderer<'sdl> {
+    /// the sdl window id
+    fn window_id(&self) -> u32;
+
+    /// in pixels
+    fn output_size(&self) -> Result<(u32, u32), String>;
+
+    /// (0, 0) if no logical size is set
+    fn logical_size(&self) -> (u32, u32);
+
+    /// the area drawn to: the logical size if set, otherwise the output size
+    fn drawable_size(&self) -> Result<(u32, u32), String> {
+        match self.logical_size() {
+            (0, _) | (_, 0) => self.output_size(),
+            size => Ok(size),
+        }
+    }
+
+    /// starts the frame
+    fn clear(&mut self, color: Color) -> Result<(), String>;
+
+    /// show the frame
+    fn present(&mut self) -> Result<(), String>;
+
+    /// copy what's currently drawn. slow; not for every frame
+    fn screenshot(&self) -> Result<Surface<'static>, String>;
+
+    /// draw the image at the path (resolved through the vfs), loading it if
+    /// it's not cached
+    fn draw_texture(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        draws: &[DrawParams],
+    ) -> Result<(), String>;
+
+    /// draw the image at the path with its colors swapped through the
+    /// palette. the recolored copy is cached under the palette's name
+    fn draw_texture_recolored(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        palette_name: &str,
+        palette: &Palette,
+        draws: &[DrawParams],
+    ) -> Result<(), String>;
+
+    /// draw the image at the path with the color key's pixels transparent.
+    /// the keyed copy is cached apart from the image
+    fn draw_texture_color_keyed(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        color_key: Color,
+        draws: &[DrawParams],
+    ) -> Result<(), String>;
+
+    /// the size of the image at the path, loading it if it's not cached
+    fn texture_size(&mut self, assets: &Vfs, path: &Path) -> Result<(u32, u32), String>;
+
+    /// restrict the following draws to the rect, in logical coordinates.
+    /// reset each frame
+    fn set_clip(&mut self, clip: Option<Rect>) -> Result<(), String>;
+
+    /// draw the rendered text, rendering it if it's not cached
+    #[cfg(feature = "ttf")]
+    #[allow(clippy::too_many_arguments)]
+    fn draw_text(
+        &mut self,
+        font_system: &mut FontSystem<'sdl>,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        wrap_width: Option<u32>,
+        draw: &DrawParams,
+    ) -> Result<(), String>;
+
+    /// draw the text along the path, rendering it if it's not cached. the
+    /// path is in logical coordinates
+    #[cfg(feature = "ttf")]
+    fn draw_text_on_path(
+        &mut self,
+        font_system: &mut FontSystem<'sdl>,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        path: &TextPath,
+    ) -> Result<(), String>;
+
+    /// drop the cached text rendered with the font. returns how many were
+    /// dropped
+    #[cfg(feature = "ttf")]
+    fn evict_font_text(&mut self, font_file: &Path) -> usize;
+
+    /// how many of the class are cached before the least recently used is
+    /// dropped. shrinking drops the least recently used
+    fn set_cache_capacity(&mut self, class: CacheClass, capacity: NonZeroUsize);
+
+    /// the files cached textures were loaded from, most recently used first
+    fn cached_paths(&self) -> Vec<PathBuf>;
+
+    /// if the image at the path is in the cache
+    fn is_cached(&self, path: &Path) -> bool;
+
+    /// drop the image at the path from the cache. false if it wasn't cached
+    fn evict_texture(&mut self, path: &Path) -> bool;
+
+    /// keep the texture (once it's loaded) cached until the frame is
+    /// presented, even if it's evicted, e.g. between measuring and drawing it
+    fn lock_texture(&mut self, key: FileOrRenderedTextKey);
+
+    /// release the locked textures. done when presenting
+    fn unlock_textures(&mut self);
+
+    /// cache an image decoded elsewhere (see DecodeQueue) as if it was loaded
+    /// from the path
+    fn insert_image(&mut self, path: &Path, image: &DecodedImage) -> Result<(), String>;
+
+    /// blended with the color's alpha
+    fn fill_rect(&mut self, rect: FRect, color: Color) -> Result<(), String>;
+
+    /// a triangle for each three indices into the vertices, blended with the
+    /// colors' alpha
+    fn draw_triangles(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<(), String>;
+
+    /// the sdl backend, for canvas level access
+    fn as_sdl(&mut self) -> Option<&mut RenderSystem<'sdl>> {
+        None
+    }
+
+    fn as_sdl_ref(&self) -> Option<&RenderSystem<'sdl>> {
+        None
+    }
+}
+
+fn copy(canvas: &mut Canvas<Window>, texture: &Texture, draw: &DrawParams) -> Result<(), String> {
+    if draw.is_transformed() {
+        canvas.copy_ex_f(
+            texture,
+            draw.src,
+            draw.dst,
+            draw.angle,
+            draw.center,
+            draw.flip_horizontal,
+            draw.flip_vertical,
+        )
+    } else {
+        canvas.copy_f(texture, draw.src, draw.dst)
+    }
+}
+
+/// untextured triangles, alpha blended
+pub(crate) fn render_geometry(
+    canvas: &mut Canvas<Window>,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> Result<(), String> {
+    let point = |p: FPoint| sdl2::sys::SDL_FPoint { x: p.x(), y: p.y() };
+    let vertices: Vec<sdl2::sys::SDL_Vertex> = vertices
+        .iter()
+        .map(|v| sdl2::sys::SDL_Vertex {
+            position: point(v.position),
+            color: sdl2::sys::SDL_Color {
+                r: v.color.r,
+                g: v.color.g,
+                b: v.color.b,
+                a: v.color.a,
+            },
+            tex_coord: point(FPoint::new(0., 0.)),
+        })
+        .collect();
+    let indices: Vec<i32> = indices.iter().map(|i| *i as i32).collect();
+    let blend_mode = canvas.blend_mode();
+    canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+    let result = unsafe {
+        sdl2::sys::SDL_RenderGeometry(
+            canvas.raw(),
+            std::ptr::null_mut(),
+            vertices.as_ptr(),
+            vertices.len() as i32,
+            indices.as_ptr(),
+            indices.len() as i32,
+        )
+    };
+    canvas.set_blend_mode(blend_mode);
+    match result {
+        0 => Ok(()),
+        _ => Err(sdl2::get_error()),
+    }
+}
+
+impl<'sdl> Renderer<'sdl> for RenderSystem<'sdl> {
+    fn window_id(&self) -> u32 {
+        RenderSystem::window_id(self)
+    }
+
+    fn output_size(&self) -> Result<(u32, u32), String> {
+        RenderSystem::output_size(self)
+    }
+
+    fn logical_size(&self) -> (u32, u32) {
+        RenderSystem::logical_size(self)
+    }
+
+    fn clear(&mut self, color: Color) -> Result<(), String> {
+        RenderSystem::clear(self, color)
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        RenderSystem::present(self)
+    }
+
+    fn screenshot(&self) -> Result<Surface<'static>, String> {
+        RenderSystem::screenshot(self)
+    }
+
+    fn draw_texture(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let (texture, canvas) = self.texture(assets, path)?;
+        draws
+            .iter()
+            .try_for_each(|draw| copy(canvas, texture, draw))
+    }
+
+    fn draw_texture_recolored(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        palette_name: &str,
+        palette: &Palette,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let (texture, canvas) = self.recolored_texture(assets, path, palette_name, palette)?;
+        draws
+            .iter()
+            .try_for_each(|draw| copy(canvas, texture, draw))
+    }
+
+    fn draw_texture_color_keyed(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        color_key: Color,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let (texture, canvas) = self.color_keyed_texture(assets, path, color_key)?;
+        draws
+            .iter()
+            .try_for_each(|draw| copy(canvas, texture, draw))
+    }
+
+    fn texture_size(&mut self, assets: &Vfs, path: &Path) -> Result<(u32, u32), String> {
+        let query = self.texture(assets, path)?.0.query();
+        Ok((query.width, query.height))
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) -> Result<(), String> {
+        RenderSystem::set_clip(self, clip);
+        Ok(())
+    }
+
+    #[cfg(feature = "ttf")]
+    fn draw_text(
+        &mut self,
+        font_system: &mut FontSystem<'sdl>,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        wrap_width: Option<u32>,
+        draw: &DrawParams,
+    ) -> Result<(), String> {
+        let (texture, canvas) =
+            self.text(font_system, assets, font_file, point_size, text, wrap_width)?;
+        copy(canvas, texture, draw)
+    }
+
+    #[cfg(feature = "ttf")]
+    fn draw_text_on_path(
+        &mut self,
+        font_system: &mut FontSystem<'sdl>,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        path: &TextPath,
+    ) -> Result<(), String> {
+        let area = rect_to_f(path.area(point_size));
+        let (texture, canvas) =
+            self.text_on_path(font_system, assets, font_file, point_size, text, path)?;
+        copy(canvas, texture, &DrawParams::new(None, Some(area)))
+    }
+
+    #[cfg(feature = "ttf")]
+    fn evict_font_text(&mut self, font_file: &Path) -> usize {
+        RenderSystem::evict_font_text(self, font_file)
+    }
+
+    fn set_cache_capacity(&mut self, class: CacheClass, capacity: NonZeroUsize) {
+        RenderSystem::set_cache_capacity(self, class, capacity)
+    }
+
+    fn cached_paths(&self) -> Vec<PathBuf> {
+        RenderSystem::cached_paths(self)
+    }
+
+    fn is_cached(&self, path: &Path) -> bool {
+        RenderSystem::is_cached(self, path)
+    }
+
+    fn evict_texture(&mut self, path: &Path) -> bool {
+        RenderSystem::evict_texture(self, path)
+    }
+
+    fn lock_texture(&mut self, key: FileOrRenderedTextKey) {
+        RenderSystem::lock_texture(self, key)
+    }
+
+    fn unlock_textures(&mut self) {
+        RenderSystem::unlock_textures(self)
+    }
+
+    fn insert_image(&mut self, path: &Path, image: &DecodedImage) -> Result<(), String> {
+        RenderSystem::insert_image(self, path, image)
+    }
+
+    fn fill_rect(&mut self, rect: FRect, color: Color) -> Result<(), String> {
+        let canvas = self.canvas();
+        let blend_mode = canvas.blend_mode();
+        canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+        canvas.set_draw_color(color);
+        let result = canvas.fill_frect(rect);
+        canvas.set_blend_mode(blend_mode);
+        result
+    }
+
+    fn draw_triangles(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<(), String> {
+        render_geometry(self.canvas(), vertices, indices)
+    }
+
+    fn as_sdl(&mut self) -> Option<&mut RenderSystem<'sdl>> {
+        Some(self)
+    }
+
+    fn as_sdl_ref(&self) -> Option<&RenderSystem<'sdl>> {
+        Some(self)
+    }
+}
@@ -0,0 +1,242 @@
+use std::{path::Path, time::Duration};
+
+use super::world::World;
+
+const MAGIC: &[u8; 4] = b"CHRP";
+const VERSION: u32 = 1;
+
+/// the input for a single update. it's published to the world's event bus
+/// before the update, so gameplay code reads it the same way whether it's live
+/// or being played back
+pub trait ReplayInput: Clone + PartialEq + 'static {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(data: &[u8]) -> Result<Self, String>;
+}
+
+impl ReplayInput for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, String> {
+        Ok(data.to_vec())
+    }
+}
+
+/// the rng seed, timestep, and the input given to each update. together with
+/// the fixed timestep this is enough to reproduce a run, given that the world
+/// is populated the same way before the first update
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay<I> {
+    pub seed: u64,
+    pub timestep: Duration,
+    pub inputs: Vec<I>,
+}
+
+impl<I: ReplayInput> Replay<I> {
+    /// an empty world set up to play this replay back
+    pub fn world(&self) -> World {
+        let mut world = World::with_seed(self.seed);
+        world.set_timestep(self.timestep);
+        world
+    }
+
+    /// consecutive identical inputs are run length encoded:
+    ///
+    /// magic + u32(version) + u64(seed) + u64(timestep nanos) + u32(run count)
+    /// + for each run: u32(repeat) + u32(input len) + input
+    pub fn encode(&self) -> Vec<u8> {
+        let mut runs: Vec<(u32, &I)> = Vec::new();
+        for input in self.inputs.iter() {
+            match runs.last_mut() {
+                Some((repeat, last)) if *last == input => *repeat += 1,
+                _ => runs.push((1, input)),
+            }
+        }
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&self.seed.to_le_bytes());
+        data.extend_from_slice(&(self.timestep.as_nanos() as u64).to_le_bytes());
+        data.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        let mut encoded: Vec<u8> = Vec::new();
+        for (repeat, input) in runs {
+            encoded.clear();
+            input.encode(&mut encoded);
+            data.extend_from_slice(&repeat.to_le_bytes());
+            data.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+            data.extend_from_slice(&encoded);
+        }
+        data
+    }
+
+    pub fn decode(mut data: &[u8]) -> Result<Self, String> {
+        if take::<4>(&mut data)? != *MAGIC {
+            return Err("not a replay".into());
+        }
+        let version = u32::from_le_bytes(take(&mut data)?);
+        if version != VERSION {
+            return Err(format!("unsupported replay version {}", version));
+        }
+        let seed = u64::from_le_bytes(take(&mut data)?);
+        let timestep = Duration::from_nanos(u64::from_le_bytes(take(&mut data)?));
+        if timestep.is_zero() {
+            return Err("replay timestep is zero".into());
+        }
+        let num_runs = u32::from_le_bytes(take(&mut data)?);
+        let mut inputs: Vec<I> = Vec::new();
+        for _ in 0..num_runs {
+            let repeat = u32::from_le_bytes(take(&mut data)?);
+            let len = u32::from_le_bytes(take(&mut data)?) as usize;
+            if data.len() < len {
+                return Err("replay is truncated".into());
+            }
+            let (encoded, rest) = data.split_at(len);
+            data = rest;
+            let input = I::decode(encoded)?;
+            inputs.extend(std::iter::repeat_n(input, repeat as usize));
+        }
+        Ok(Self {
+            seed,
+            timestep,
+            inputs,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.encode()).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        Self::decode(&data)
+    }
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("replay is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+/// drives the world one update at a time, recording the input given to each
+pub struct ReplayRecorder<I> {
+    replay: Replay<I>,
+}
+
+impl<I: ReplayInput> ReplayRecorder<I> {
+    /// should be created along with the world, before its first update and
+    /// before anything has drawn from its rng
+    pub fn new(world: &World) -> Self {
+        Self {
+            replay: Replay {
+                seed: world.rng.seed(),
+                timestep: world.timestep(),
+                inputs: Vec::new(),
+            },
+        }
+    }
+
+    /// publish the input and run a single update
+    pub fn step(&mut self, world: &mut World, input: I) -> Result<(), String> {
+        self.replay.inputs.push(input.clone());
+        world.events.publish(input);
+        world.update()
+    }
+
+    pub fn replay(&self) -> &Replay<I> {
+        &self.replay
+    }
+
+    pub fn finish(self) -> Replay<I> {
+        self.replay
+    }
+}
+
+/// drives the world with recorded inputs instead of live ones
+pub struct ReplayPlayer<I> {
+    replay: Replay<I>,
+    position: usize,
+}
+
+impl<I: ReplayInput> ReplayPlayer<I> {
+    /// the world should come from Replay::world, populated the same way as
+    /// when it was recorded
+    pub fn new(replay: Replay<I>) -> Self {
+        Self {
+            replay,
+            position: 0,
+        }
+    }
+
+    /// index of the next input to be played
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.replay.inputs.len()
+    }
+
+    /// publish the next recorded input and run a single update. returns false
+    /// without updating once the replay has finished
+    pub fn step(&mut self, world: &mut World) -> Result<bool, String> {
+        let Some(input) = self.replay.inputs.get(self.position) else {
+            return Ok(false);
+        };
+        self.position += 1;
+        world.events.publish(input.clone());
+        world.update()?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::entity::Entity;
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Walker {
+        trace: Rc<RefCell<Vec<u64>>>,
+    }
+
+    impl Entity for Walker {
+        fn update(&mut self, world: &mut World) -> Result<(), String> {
+            let input = world.events.read::<Vec<u8>>().first().cloned();
+            let noise = world.rng.stream("walker").next_u64();
+            let step = input.map(|i| i[0] as u64).unwrap_or(0);
+            self.trace.borrow_mut().push(noise.wrapping_add(step));
+            Ok(())
+        }
+    }
+
+    fn run(world: &mut World) -> Rc<RefCell<Vec<u64>>> {
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        world.spawn(Box::new(Walker {
+            trace: trace.clone(),
+        }));
+        trace
+    }
+
+    #[test]
+    fn test_playback() {
+        let mut world = World::new();
+        let recorded = run(&mut world);
+        let mut recorder = ReplayRecorder::new(&world);
+        for input in [[1], [1], [1], [0], [3]] {
+            recorder.step(&mut world, input.to_vec()).unwrap();
+        }
+        let replay = Replay::<Vec<u8>>::decode(&recorder.finish().encode()).unwrap();
+
+        let mut world = replay.world();
+        let played = run(&mut world);
+        let mut player = ReplayPlayer::new(replay);
+        while player.step(&mut world).unwrap() {}
+        assert_eq!(*played.borrow(), *recorded.borrow());
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use super::entity::EntityId;
+
+/// state which an entity exposes for snapshots. the encoding is up to the
+/// entity; it only needs to be read back by the same entity type
+pub trait Replicate {
+    /// true if the state has changed since the last snapshot
+    fn is_dirty(&self) -> bool;
+
+    /// called once the state has been included in a snapshot
+    fn clear_dirty(&mut self);
+
+    fn write_state(&self, out: &mut Vec<u8>);
+
+    fn read_state(&mut self, data: &[u8]) -> Result<(), String>;
+}
+
+/// replicated entity state as of some tick. a full snapshot holds every
+/// replicated entity; a delta holds only what changed since an earlier one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub entities: BTreeMap<EntityId, Vec<u8>>,
+    /// entities which existed in the earlier snapshot but not in this one.
+    /// always empty for full snapshots
+    pub removed: Vec<EntityId>,
+}
+
+impl Snapshot {
+    /// the delta which turns old into new when applied on top of it
+    pub fn diff(old: &Snapshot, new: &Snapshot) -> Snapshot {
+        Snapshot {
+            tick: new.tick,
+            entities: new
+                .entities
+                .iter()
+                .filter(|(id, state)| old.entities.get(id) != Some(state))
+                .map(|(id, state)| (*id, state.clone()))
+                .collect(),
+            removed: old
+                .entities
+                .keys()
+                .filter(|id| !new.entities.contains_key(id))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// apply a delta on top of this snapshot
+    pub fn merge(&mut self, delta: &Snapshot) {
+        self.tick = delta.tick;
+        for id in delta.removed.iter() {
+            self.entities.remove(id);
+        }
+        for (id, state) in delta.entities.iter() {
+            self.entities.insert(*id, state.clone());
+        }
+    }
+
+    /// compact little endian encoding:
+    ///
+    /// u64(tick) + u32(entity count) + for each entity:
+    ///     u64(id) + u32(state len) + state
+    /// then u32(removed count) + u64(id) for each removed
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&self.tick.to_le_bytes());
+        data.extend_from_slice(&(self.entities.len() as u32).to_le_bytes());
+        for (id, state) in self.entities.iter() {
+            data.extend_from_slice(&id.0.to_le_bytes());
+            data.extend_from_slice(&(state.len() as u32).to_le_bytes());
+            data.extend_from_slice(state);
+        }
+        data.extend_from_slice(&(self.removed.len() as u32).to_le_bytes());
+        for id in self.removed.iter() {
+            data.extend_from_slice(&id.0.to_le_bytes());
+        }
+        data
+    }
+
+    pub fn decode(mut data: &[u8]) -> Result<Self, String> {
+        let tick = u64::from_le_bytes(take(&mut data)?);
+        let num_entities = u32::from_le_bytes(take(&mut data)?);
+        let mut entities: BTreeMap<EntityId, Vec<u8>> = BTreeMap::new();
+        for _ in 0..num_entities {
+            let id = EntityId(u64::from_le_bytes(take(&mut data)?));
+            let len = u32::from_le_bytes(take(&mut data)?) as usize;
+            if data.len() < len {
+                return Err("snapshot is truncated".into());
+            }
+            let (state, rest) = data.split_at(len);
+            data = rest;
+            entities.insert(id, state.to_vec());
+        }
+        let num_removed = u32::from_le_bytes(take(&mut data)?);
+        let mut removed: Vec<EntityId> = Vec::new();
+        for _ in 0..num_removed {
+            removed.push(EntityId(u64::from_le_bytes(take(&mut data)?)));
+        }
+        if !data.is_empty() {
+            return Err("snapshot has trailing data".into());
+        }
+        Ok(Self {
+            tick,
+            entities,
+            removed,
+        })
+    }
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("snapshot is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_merge() {
+        let old = Snapshot {
+            tick: 1,
+            entities: BTreeMap::from([
+                (EntityId(0), vec![1]),
+                (EntityId(1), vec![2]),
+                (EntityId(2), vec![3]),
+            ]),
+            removed: vec![],
+        };
+        let new = Snapshot {
+            tick: 2,
+            entities: BTreeMap::from([
+                (EntityId(0), vec![1]),
+                (EntityId(1), vec![5]),
+                (EntityId(3), vec![4]),
+            ]),
+            removed: vec![],
+        };
+        let delta = Snapshot::diff(&old, &new);
+        assert_eq!(delta.entities.len(), 2);
+        assert_eq!(delta.removed, vec![EntityId(2)]);
+
+        let decoded = Snapshot::decode(&delta.encode()).unwrap();
+        assert_eq!(decoded, delta);
+
+        let mut merged = old.clone();
+        merged.merge(&decoded);
+        assert_eq!(merged, new);
+        assert!(Snapshot::decode(&delta.encode()[..5]).is_err());
+    }
+}
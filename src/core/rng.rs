@@ -0,0 +1,274 @@
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+fn split_mix_64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// fnv-1a. unlike std's hasher, it's guaranteed to be stable between builds
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// xoshiro256**. produces the same sequence on every platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut sm = seed;
+        Self {
+            state: [
+                split_mix_64(&mut sm),
+                split_mix_64(&mut sm),
+                split_mix_64(&mut sm),
+                split_mix_64(&mut sm),
+            ],
+        }
+    }
+
+    pub fn state(&self) -> [u64; 4] {
+        self.state
+    }
+
+    /// the all zero state is invalid
+    pub fn from_state(state: [u64; 4]) -> Result<Self, String> {
+        if state == [0; 4] {
+            return Err("rng state can't be all zeros".into());
+        }
+        Ok(Self { state })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        result
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// uniform in [0, bound). bound must be non zero
+    pub fn below(&mut self, bound: u64) -> u64 {
+        debug_assert!(bound != 0);
+        // rejection sampling to remove modulo bias
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let v = self.next_u64();
+            if v < zone {
+                return v % bound;
+            }
+        }
+    }
+
+    /// uniform in the range. the range must not be empty
+    pub fn range_i32(&mut self, range: Range<i32>) -> i32 {
+        debug_assert!(range.start < range.end);
+        let span = (range.end as i64 - range.start as i64) as u64;
+        (range.start as i64 + self.below(span) as i64) as i32
+    }
+
+    /// uniform in the range
+    pub fn range_f32(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// true with the given probability
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.below(items.len() as u64) as usize)
+    }
+
+    /// fisher-yates
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// engine owned randomness. each system (or entity type, etc) draws from its
+/// own named stream, so adding random calls in one system doesn't change the
+/// sequence seen by another. streams are derived from the seed and their name
+pub struct RngService {
+    seed: u64,
+    /// ordered so serialization is deterministic
+    streams: BTreeMap<String, Rng>,
+    /// the seed came from the system time rather than the game
+    clock_seeded: bool,
+}
+
+impl Default for RngService {
+    /// seeded from the system time
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            clock_seeded: true,
+            ..Self::new(seed)
+        }
+    }
+}
+
+impl RngService {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: Default::default(),
+            clock_seeded: false,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// true if made by default, so its sequence differs between runs
+    pub fn is_clock_seeded(&self) -> bool {
+        self.clock_seeded
+    }
+
+    /// get the named stream, creating it on first use
+    pub fn stream(&mut self, name: &str) -> &mut Rng {
+        if !self.streams.contains_key(name) {
+            let rng = Rng::new(self.seed ^ stable_hash(name.as_bytes()));
+            self.streams.insert(name.into(), rng);
+        }
+        self.streams.get_mut(name).expect("inserted above")
+    }
+
+    /// encode the seed and the current position of each stream. used when
+    /// saving the game:
+    ///
+    /// u64(seed) + u32(stream count) + for each stream:
+    ///     u32(name len) + name + 4 * u64(state)
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&self.seed.to_le_bytes());
+        data.extend_from_slice(&(self.streams.len() as u32).to_le_bytes());
+        for (name, rng) in self.streams.iter() {
+            data.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+            for word in rng.state() {
+                data.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    pub fn deserialize(mut data: &[u8]) -> Result<Self, String> {
+        let seed = u64::from_le_bytes(take(&mut data)?);
+        let num_streams = u32::from_le_bytes(take(&mut data)?);
+        let mut streams: BTreeMap<String, Rng> = BTreeMap::new();
+        for _ in 0..num_streams {
+            let name_len = u32::from_le_bytes(take(&mut data)?) as usize;
+            if data.len() < name_len {
+                return Err("rng state is truncated".into());
+            }
+            let (name, rest) = data.split_at(name_len);
+            data = rest;
+            let name = std::str::from_utf8(name).map_err(|e| e.to_string())?;
+            let mut state = [0u64; 4];
+            for word in state.iter_mut() {
+                *word = u64::from_le_bytes(take(&mut data)?);
+            }
+            streams.insert(name.to_owned(), Rng::from_state(state)?);
+        }
+        Ok(Self {
+            seed,
+            streams,
+            clock_seeded: false,
+        })
+    }
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("rng state is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streams_independent() {
+        let mut a = RngService::new(42);
+        let mut b = RngService::new(42);
+        let first = a.stream("ai").next_u64();
+        // drawing from another stream doesn't affect this one
+        b.stream("particles").next_u64();
+        assert_eq!(b.stream("ai").next_u64(), first);
+        assert_ne!(a.stream("particles").next_u64(), first);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut service = RngService::new(7);
+        service.stream("a").next_u64();
+        service.stream("b");
+        let mut restored = RngService::deserialize(&service.serialize()).unwrap();
+        assert_eq!(restored.seed(), 7);
+        assert_eq!(
+            restored.stream("a").next_u64(),
+            service.stream("a").next_u64()
+        );
+        assert_eq!(
+            restored.stream("b").next_u64(),
+            service.stream("b").next_u64()
+        );
+        assert!(RngService::deserialize(&service.serialize()[..10]).is_err());
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            let v = rng.range_i32(-3..4);
+            assert!((-3..4).contains(&v));
+            let f = rng.next_f32();
+            assert!((0. ..1.).contains(&f));
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use super::{
+    collision::{Contact, ContactTracker},
+    replay::ReplayInput,
+    replication::Snapshot,
+    rng::RngService,
+    world::{Schedule, World},
+};
+
+/// every player's input for an update, published to the world's event bus
+/// before it. gameplay reads this instead of local input, so each peer
+/// simulates the same thing
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollbackFrame<I> {
+    pub frame: u64,
+    /// indexed by player
+    pub inputs: Vec<I>,
+    /// true when the frame is being simulated again after a misprediction.
+    /// one off effects like sounds were already played the first time
+    pub resimulating: bool,
+}
+
+/// the world as of the start of a frame
+struct SavedState {
+    frame: u64,
+    snapshot: Snapshot,
+    schedule: Schedule,
+    rng: Vec<u8>,
+    contacts: ContactTracker,
+    /// published at the end of the frame before, to be read in this one
+    contact_events: Vec<Contact>,
+}
+
+/// ggpo style rollback for a few players in lockstep, e.g. a fighting game.
+/// each peer runs its own world, delays its local input by a few frames and
+/// sends it to the others (e.g. over a Transport). missing remote input is
+/// predicted by repeating the player's last; when the real input arrives and
+/// differs, the world is rewound to that frame and simulated again.
+///
+/// state is saved with World::capture_state and capture_schedule, and rewound
+/// with World::rewind, so it should live in replicated entities, timers and
+/// tweens. entities spawned since are removed, but despawned ones aren't
+/// brought back, and a rewind fails if a sequence ran since. collision contacts
+/// are restored too, but other events published the frame before a rewind are
+/// lost, and the world should be deterministic (see Determinism)
+pub struct Rollback<I> {
+    /// the local player's index
+    local: usize,
+    /// local input is used this many frames after it's given, which hides
+    /// that much latency without any rollback
+    input_delay: u64,
+    /// simulation stalls rather than predicting this many frames past the
+    /// last frame with every player's input
+    pub max_prediction: u64,
+    /// the next frame to simulate
+    frame: u64,
+    /// inputs received (or given locally) for each player, by frame
+    received: Vec<BTreeMap<u64, I>>,
+    /// for each player, every input before this frame was received
+    confirmed: Vec<u64>,
+    /// inputs the simulation used for each frame since the last confirmed
+    used: BTreeMap<u64, Vec<I>>,
+    /// before each frame since the last confirmed, oldest first
+    states: VecDeque<SavedState>,
+    /// the earliest frame which was mispredicted
+    rollback_to: Option<u64>,
+}
+
+impl<I: ReplayInput + Default> Rollback<I> {
+    pub fn new(players: usize, local: usize, input_delay: u64) -> Self {
+        debug_assert!(local < players);
+        Self {
+            local,
+            input_delay,
+            max_prediction: 8,
+            frame: 0,
+            received: (0..players).map(|_| BTreeMap::new()).collect(),
+            confirmed: vec![input_delay; players],
+            used: Default::default(),
+            states: Default::default(),
+            rollback_to: None,
+        }
+    }
+
+    /// the next frame to simulate
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn players(&self) -> usize {
+        self.received.len()
+    }
+
+    /// every player's input is known before this frame. inputs before the
+    /// delay are the default for everyone
+    pub fn confirmed(&self) -> u64 {
+        self.confirmed.iter().copied().min().unwrap_or(self.frame)
+    }
+
+    fn receive(&mut self, player: usize, frame: u64, input: I) {
+        self.received[player].insert(frame, input);
+        let confirmed = &mut self.confirmed[player];
+        while self.received[player].contains_key(confirmed) {
+            *confirmed += 1;
+        }
+    }
+
+    /// this frame's local input. returns the frame it's used in, which is sent
+    /// along with it to the other peers. call once per frame, before advance
+    pub fn add_local_input(&mut self, input: I) -> u64 {
+        let frame = self.frame + self.input_delay;
+        self.receive(self.local, frame, input);
+        frame
+    }
+
+    /// a remote player's input, as sent with its frame. repeats are ignored.
+    /// if it was mispredicted the world is rewound at the next advance
+    pub fn add_remote_input(&mut self, player: usize, frame: u64, input: I) -> Result<(), String> {
+        if player >= self.players() || player == self.local {
+            return Err(format!("{} isn't a remote player", player));
+        }
+        if frame < self.confirmed[player] || self.received[player].contains_key(&frame) {
+            return Ok(());
+        }
+        if let Some(used) = self.used.get(&frame) {
+            if used[player] != input {
+                self.rollback_to = Some(self.rollback_to.map_or(frame, |f| f.min(frame)));
+            }
+        }
+        self.receive(player, frame, input);
+        Ok(())
+    }
+
+    /// the input known for the frame, or the player's last known before it
+    fn input(&self, player: usize, frame: u64) -> I {
+        self.received[player]
+            .range(..=frame)
+            .next_back()
+            .map(|(_, input)| input.clone())
+            .unwrap_or_default()
+    }
+
+    /// rewind if anything was mispredicted, then simulate the next frame.
+    /// returns false without simulating while too far ahead of the other
+    /// players' input, e.g. while waiting on a lagging peer
+    pub fn advance(&mut self, world: &mut World) -> Result<bool, String> {
+        let confirmed = self.confirmed();
+        if self.frame >= confirmed + self.max_prediction {
+            return Ok(false);
+        }
+        if let Some(frame) = self.rollback_to.take() {
+            let state = self
+                .states
+                .iter()
+                .find(|s| s.frame == frame)
+                .ok_or_else(|| format!("no saved state for frame {}", frame))?;
+            world.rewind(&state.snapshot, &state.schedule)?;
+            world.rng = RngService::deserialize(&state.rng)?;
+            world.set_contacts(state.contacts.clone());
+            for contact in state.contact_events.iter() {
+                world.events.publish(*contact);
+            }
+            for frame in frame..self.frame {
+                self.simulate(world, frame, true)?;
+            }
+        }
+        self.simulate(world, self.frame, false)?;
+        self.frame += 1;
+
+        // confirmed frames can't be mispredicted, so their state isn't needed
+        let confirmed = self.confirmed().min(self.frame);
+        while self.states.front().is_some_and(|s| s.frame < confirmed) {
+            self.states.pop_front();
+        }
+        self.used = self.used.split_off(&confirmed);
+        for received in self.received.iter_mut() {
+            // the last confirmed input is kept for prediction
+            let keep = received
+                .range(..confirmed)
+                .next_back()
+                .map_or(confirmed, |(frame, _)| *frame);
+            *received = received.split_off(&keep);
+        }
+        Ok(true)
+    }
+
+    fn simulate(
+        &mut self,
+        world: &mut World,
+        frame: u64,
+        resimulating: bool,
+    ) -> Result<(), String> {
+        let state = SavedState {
+            frame,
+            snapshot: world.capture_state(),
+            schedule: world.capture_schedule(),
+            rng: world.rng.serialize(),
+            contacts: world.contacts().clone(),
+            contact_events: world.events.pending::<Contact>().to_vec(),
+        };
+        while self.states.back().is_some_and(|s| s.frame >= frame) {
+            self.states.pop_back();
+        }
+        self.states.push_back(state);
+        let inputs: Vec<I> = (0..self.players())
+            .map(|player| self.input(player, frame))
+            .collect();
+        self.used.insert(frame, inputs.clone());
+        world.events.publish(RollbackFrame {
+            frame,
+            inputs,
+            resimulating,
+        });
+        world.update()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::core::{
+        entity::Entity,
+        replication::Replicate,
+        tween::{Ease, Tween},
+    };
+
+    /// sums every player's input
+    #[derive(Default)]
+    struct Counter {
+        total: u64,
+        dirty: bool,
+    }
+
+    impl Replicate for Counter {
+        fn is_dirty(&self) -> bool {
+            self.dirty
+        }
+
+        fn clear_dirty(&mut self) {
+            self.dirty = false;
+        }
+
+        fn write_state(&self, out: &mut Vec<u8>) {
+            out.extend_from_slice(&self.total.to_le_bytes());
+        }
+
+        fn read_state(&mut self, data: &[u8]) -> Result<(), String> {
+            let data = data.try_into().map_err(|_| "bad counter".to_owned())?;
+            self.total = u64::from_le_bytes(data);
+            Ok(())
+        }
+    }
+
+    impl Entity for Counter {
+        fn update(&mut self, world: &mut World) -> Result<(), String> {
+            for frame in world.events.read::<RollbackFrame<Vec<u8>>>() {
+                let weights = frame.inputs.iter().zip([1, 100]);
+                let added = weights.map(|(i, w)| i.len() as u64 * w).sum::<u64>();
+                self.total += added;
+                self.dirty |= added != 0;
+            }
+            Ok(())
+        }
+
+        fn replicate_mut(&mut self) -> Option<&mut dyn Replicate> {
+            Some(self)
+        }
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut worlds = [World::with_seed(1), World::with_seed(1)];
+        let mut peers: Vec<Rollback<Vec<u8>>> = (0..2).map(|p| Rollback::new(2, p, 2)).collect();
+        for world in worlds.iter_mut() {
+            world.spawn(Box::<Counter>::default());
+        }
+        // peer 1's input reaches peer 0 three frames late
+        let mut in_flight: VecDeque<(u64, Vec<u8>)> = VecDeque::new();
+        for i in 0..20u8 {
+            let input = vec![0; (i % 3) as usize];
+            let frame = peers[0].add_local_input(input.clone());
+            peers[1].add_remote_input(0, frame, input).unwrap();
+            let input = vec![0; (i % 4) as usize];
+            let frame = peers[1].add_local_input(input.clone());
+            in_flight.push_back((frame, input));
+            if in_flight.len() > 3 {
+                let (frame, input) = in_flight.pop_front().unwrap();
+                peers[0].add_remote_input(1, frame, input).unwrap();
+            }
+            for (peer, world) in peers.iter_mut().zip(worlds.iter_mut()) {
+                assert!(peer.advance(world).unwrap());
+            }
+        }
+        assert!(peers[0].add_remote_input(0, 30, vec![]).is_err());
+        for (frame, input) in in_flight {
+            peers[0].add_remote_input(1, frame, input).unwrap();
+        }
+        for (peer, world) in peers.iter_mut().zip(worlds.iter_mut()) {
+            peer.advance(world).unwrap();
+        }
+        assert_eq!(peers[0].frame(), peers[1].frame());
+        let [a, b] = &mut worlds;
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        // stalls when too far ahead
+        peers[0].max_prediction = 1;
+        while peers[0].advance(&mut worlds[0]).unwrap() {}
+        assert_eq!(peers[0].frame(), peers[0].confirmed() + 1);
+    }
+
+    /// spawns a counter every third update, and another when a tween finishes
+    fn schedule_spawns(world: &mut World) {
+        let timestep = world.timestep();
+        world.every(timestep * 3, |world| {
+            world.spawn(Box::<Counter>::default());
+            Ok(())
+        });
+        let value = Rc::new(Cell::new(0f32));
+        world.tween(
+            Tween::new(&value)
+                .to(1., timestep * 7, Ease::Linear)
+                .on_complete(|world| {
+                    world.spawn(Box::<Counter>::default());
+                    Ok(())
+                }),
+        );
+    }
+
+    #[test]
+    fn test_rewind_matches_straight_run() {
+        let mut worlds = [World::with_seed(1), World::with_seed(1)];
+        let mut peers: Vec<Rollback<Vec<u8>>> = (0..2).map(|_| Rollback::new(2, 0, 2)).collect();
+        for world in worlds.iter_mut() {
+            schedule_spawns(world);
+        }
+        // player 1's input reaches the first peer four frames late, so it
+        // rewinds. the second peer gets it in time and never does
+        let mut late: VecDeque<(u64, Vec<u8>)> = VecDeque::new();
+        for i in 0..20u8 {
+            let mut frame = 0;
+            for peer in peers.iter_mut() {
+                frame = peer.add_local_input(vec![0; (i % 2) as usize]);
+            }
+            let input = vec![0; (i % 3) as usize];
+            peers[1].add_remote_input(1, frame, input.clone()).unwrap();
+            late.push_back((frame, input));
+            if late.len() > 4 {
+                let (frame, input) = late.pop_front().unwrap();
+                peers[0].add_remote_input(1, frame, input).unwrap();
+            }
+            for (peer, world) in peers.iter_mut().zip(worlds.iter_mut()) {
+                assert!(peer.advance(world).unwrap());
+            }
+        }
+        for (frame, input) in late {
+            peers[0].add_remote_input(1, frame, input).unwrap();
+        }
+        for (peer, world) in peers.iter_mut().zip(worlds.iter_mut()) {
+            peer.advance(world).unwrap();
+        }
+        let [a, b] = &mut worlds;
+        // 7 from the timer and 1 from the tween
+        assert_eq!(a.ids().count(), 8);
+        assert!(a.ids().eq(b.ids()));
+        assert_eq!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn test_rollback_replication() {
+        let mut world = World::with_seed(1);
+        let kept = world.spawn(Box::<Counter>::default());
+        let removed = world.spawn(Box::<Counter>::default());
+        world.delta_snapshot();
+        world.despawn(removed);
+
+        // saving state each frame doesn't consume what the next delta sends
+        let mut peer: Rollback<Vec<u8>> = Rollback::new(1, 0, 0);
+        for input in [vec![0], vec![]] {
+            peer.add_local_input(input);
+            assert!(peer.advance(&mut world).unwrap());
+        }
+        let delta = world.delta_snapshot();
+        assert_eq!(delta.removed, [removed]);
+        assert_eq!(delta.entities.keys().collect::<Vec<_>>(), [&kept]);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use sdl2::{rect::Rect, surface::Surface};
+
+use super::{decode::save_surface, system::ChimericSystem, trace::log_info};
+
+const MAGIC: &[u8; 4] = b"CHSV";
+const VERSION: u32 = 1;
+
+/// crc-32 (ieee)
+fn checksum(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("save is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+fn take_slice<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err("save is truncated".into());
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Ok(taken)
+}
+
+/// write to a temporary file in the same directory then rename it over the
+/// destination, so a crash mid write can't leave a half written file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = std::fs::File::create(&tmp).map_err(|e| e.to_string())?;
+    file.write_all(contents).map_err(|e| e.to_string())?;
+    file.sync_all().map_err(|e| e.to_string())?;
+    drop(file);
+    std::fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveMetadata {
+    pub slot: u32,
+    /// shown in the load menu, e.g. the level name
+    pub label: String,
+    /// when it was saved, to the second
+    pub timestamp: SystemTime,
+    /// None if the save has no thumbnail
+    pub thumbnail: Option<PathBuf>,
+}
+
+/// the label, timestamp, and game data with a header and checksum
+pub fn encode_save(label: &str, timestamp: SystemTime, data: &[u8]) -> Vec<u8> {
+    let secs = timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut out = Vec::with_capacity(data.len() + label.len() + 32);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&secs.to_le_bytes());
+    out.extend_from_slice(&(label.len() as u32).to_le_bytes());
+    out.extend_from_slice(label.as_bytes());
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(data);
+    out.extend_from_slice(&checksum(&out).to_le_bytes());
+    out
+}
+
+/// returns the label, timestamp, and game data. fails if the checksum doesn't
+/// match
+pub fn decode_save(bytes: &[u8]) -> Result<(String, SystemTime, Vec<u8>), String> {
+    if bytes.len() < 4 {
+        return Err("save is truncated".into());
+    }
+    let (body, expected) = bytes.split_at(bytes.len() - 4);
+    let expected = u32::from_le_bytes(expected.try_into().expect("length checked"));
+    let mut data = body;
+    if &take::<4>(&mut data)? != MAGIC {
+        return Err("not a save file".into());
+    }
+    let version = u32::from_le_bytes(take(&mut data)?);
+    if version != VERSION {
+        return Err(format!("unsupported save version {version}"));
+    }
+    if checksum(body) != expected {
+        return Err("save is corrupted (checksum mismatch)".into());
+    }
+    let secs = u64::from_le_bytes(take(&mut data)?);
+    let label_len = u32::from_le_bytes(take(&mut data)?) as usize;
+    let label =
+        String::from_utf8(take_slice(&mut data, label_len)?.to_vec()).map_err(|e| e.to_string())?;
+    let data_len = u64::from_le_bytes(take(&mut data)?) as usize;
+    let payload = take_slice(&mut data, data_len)?.to_vec();
+    Ok((label, UNIX_EPOCH + Duration::from_secs(secs), payload))
+}
+
+/// numbered save slots in a directory. each slot is a file with the game's
+/// data plus metadata, and optionally a png thumbnail beside it
+pub struct SaveManager {
+    dir: PathBuf,
+    /// in pixels. the height keeps the screenshot's aspect ratio
+    pub thumbnail_width: u32,
+}
+
+impl SaveManager {
+    /// the directory is created if it doesn't exist
+    pub fn new(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            thumbnail_width: 160,
+        })
+    }
+
+    /// a "saves" directory in the platform's per-user writable directory for
+    /// the app
+    pub fn in_pref_dir(org: &str, app: &str) -> Result<Self, String> {
+        let dir = sdl2::filesystem::pref_path(org, app).map_err(|e| e.to_string())?;
+        Self::new(&Path::new(&dir).join("saves"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn slot_path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("slot_{slot}.sav"))
+    }
+
+    /// a png, or a bmp without the image feature
+    pub fn thumbnail_path(&self, slot: u32) -> PathBuf {
+        let extension = if cfg!(feature = "image") { "png" } else { "bmp" };
+        self.dir.join(format!("slot_{slot}.{extension}"))
+    }
+
+    /// write the slot, replacing what was there. any old thumbnail is removed
+    pub fn save(&self, slot: u32, label: &str, data: &[u8]) -> Result<SaveMetadata, String> {
+        let thumbnail = self.thumbnail_path(slot);
+        if thumbnail.exists() {
+            std::fs::remove_file(&thumbnail).map_err(|e| e.to_string())?;
+        }
+        self.write_slot(slot, label, data)
+    }
+
+    /// as save, with a thumbnail from a screenshot of the window. call before
+    /// present
+    pub fn save_with_thumbnail(
+        &self,
+        slot: u32,
+        label: &str,
+        data: &[u8],
+        system: &ChimericSystem,
+        window_name: &str,
+    ) -> Result<SaveMetadata, String> {
+        let screenshot = system.screenshot(window_name)?;
+        let width = self.thumbnail_width.max(1);
+        let height = ((screenshot.height() as u64 * width as u64)
+            / screenshot.width().max(1) as u64)
+            .max(1) as u32;
+        let mut thumbnail = Surface::new(width, height, screenshot.pixel_format_enum())?;
+        screenshot.blit_scaled(None, &mut thumbnail, Rect::new(0, 0, width, height))?;
+        let path = self.thumbnail_path(slot);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        save_surface(&thumbnail, Path::new(&tmp))?;
+        std::fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+        self.write_slot(slot, label, data)
+    }
+
+    fn write_slot(&self, slot: u32, label: &str, data: &[u8]) -> Result<SaveMetadata, String> {
+        // whole seconds, so the result matches what's loaded back
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let timestamp = UNIX_EPOCH + Duration::from_secs(secs);
+        write_atomic(&self.slot_path(slot), &encode_save(label, timestamp, data))?;
+        log_info!("saved slot {slot} ({} bytes)", data.len());
+        Ok(self.metadata_from(slot, label.to_owned(), timestamp))
+    }
+
+    fn metadata_from(&self, slot: u32, label: String, timestamp: SystemTime) -> SaveMetadata {
+        let thumbnail = self.thumbnail_path(slot);
+        SaveMetadata {
+            slot,
+            label,
+            timestamp,
+            thumbnail: thumbnail.exists().then_some(thumbnail),
+        }
+    }
+
+    pub fn exists(&self, slot: u32) -> bool {
+        self.slot_path(slot).exists()
+    }
+
+    /// the metadata and game data. fails if the file is corrupted
+    pub fn load(&self, slot: u32) -> Result<(SaveMetadata, Vec<u8>), String> {
+        let path = self.slot_path(slot);
+        let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+        let (label, timestamp, data) =
+            decode_save(&bytes).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok((self.metadata_from(slot, label, timestamp), data))
+    }
+
+    /// every slot with a valid save, in slot order
+    pub fn slots(&self) -> Result<Vec<SaveMetadata>, String> {
+        let mut slots = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).map_err(|e| e.to_string())? {
+            let name = entry.map_err(|e| e.to_string())?.file_name();
+            let slot = name
+                .to_str()
+                .and_then(|n| n.strip_prefix("slot_"))
+                .and_then(|n| n.strip_suffix(".sav"))
+                .and_then(|n| n.parse::<u32>().ok());
+            if let Some((metadata, _)) = slot.and_then(|slot| self.load(slot).ok()) {
+                slots.push(metadata);
+            }
+        }
+        slots.sort_by_key(|m| m.slot);
+        Ok(slots)
+    }
+
+    /// remove the slot and its thumbnail
+    pub fn delete(&self, slot: u32) -> Result<(), String> {
+        for path in [self.slot_path(slot), self.thumbnail_path(slot)] {
+            if path.exists() {
+                std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(checksum(b"123456789"), 0xCBF4_3926);
+        let timestamp = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut bytes = encode_save("forest", timestamp, &[1, 2, 3]);
+        assert_eq!(
+            decode_save(&bytes).unwrap(),
+            ("forest".to_owned(), timestamp, vec![1, 2, 3])
+        );
+        let last = bytes.len() - 5;
+        bytes[last] ^= 1;
+        assert!(decode_save(&bytes).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+use mlua::{Function, Lua, LuaOptions, RegistryKey, StdLib, Table};
+use sdl2::{pixels::Color, rect::FRect};
+
+use super::{
+    entity::{Entity, EntityId},
+    input::{InputMap, InputState},
+    renderer::DrawParams,
+    system::ChimericSystem,
+    world::World,
+};
+
+fn lua_err(e: mlua::Error) -> String {
+    e.to_string()
+}
+
+/// what scripts can see of the game outside the world, and what they ask of
+/// it
+#[derive(Debug, Default)]
+struct ScriptIo {
+    down: BTreeSet<String>,
+    pressed: BTreeSet<String>,
+    sounds: Vec<String>,
+}
+
+struct Module {
+    modified: Option<SystemTime>,
+    /// the table the script returned
+    table: RegistryKey,
+    /// instances' metatable, which looks functions up in the module
+    meta: RegistryKey,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// runs entity behavior written in lua, so it can be changed without
+/// recompiling. a script returns a module table whose functions are called
+/// with the entity's own table as self:
+///
+/// ```lua
+/// local M = {}
+/// function M.init(self) self.x, self.y, self.w, self.h = 0, 0, 16, 16 end
+/// function M.update(self, world)
+///     if input.down("right") then self.x = self.x + 2 end
+///     if input.pressed("jump") then audio.play("jump.wav") end
+/// end
+/// function M.draw(self, gfx) gfx.image("hero.png", self.x, self.y, self.w, self.h) end
+/// return M
+/// ```
+///
+/// world has time(), tick(), random(stream), spawn(path, props), despawn(id)
+/// and query(x, y, w, h). gfx has image(path, x, y, w, h) and
+/// rect(x, y, w, h, r, g, b, a). an entity's x, y, w and h make its aabb, and
+/// setting alive to false removes it. only lua's table, string, math, utf8
+/// and coroutine libraries are loaded; scripts can't reach files or the os
+pub struct ScriptHost {
+    lua: Lua,
+    modules: RefCell<BTreeMap<PathBuf, Module>>,
+    io: Rc<RefCell<ScriptIo>>,
+    /// scripts draw to this window
+    pub window: String,
+}
+
+impl ScriptHost {
+    pub fn new(window: &str) -> Result<Rc<Self>, String> {
+        let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8 | StdLib::COROUTINE;
+        let lua = Lua::new_with(libs, LuaOptions::default()).map_err(lua_err)?;
+        let io: Rc<RefCell<ScriptIo>> = Default::default();
+        {
+            let input = lua.create_table().map_err(lua_err)?;
+            let state = io.clone();
+            let down = lua
+                .create_function(move |_, action: String| Ok(state.borrow().down.contains(&action)))
+                .map_err(lua_err)?;
+            let state = io.clone();
+            let pressed = lua
+                .create_function(move |_, action: String| {
+                    Ok(state.borrow().pressed.contains(&action))
+                })
+                .map_err(lua_err)?;
+            input.set("down", down).map_err(lua_err)?;
+            input.set("pressed", pressed).map_err(lua_err)?;
+            lua.globals().set("input", input).map_err(lua_err)?;
+
+            let audio = lua.create_table().map_err(lua_err)?;
+            let state = io.clone();
+            let play = lua
+                .create_function(move |_, path: String| {
+                    state.borrow_mut().sounds.push(path);
+                    Ok(())
+                })
+                .map_err(lua_err)?;
+            audio.set("play", play).map_err(lua_err)?;
+            lua.globals().set("audio", audio).map_err(lua_err)?;
+        }
+        Ok(Rc::new(Self {
+            lua,
+            modules: Default::default(),
+            io,
+            window: window.to_owned(),
+        }))
+    }
+
+    /// run the script and keep its module. done on first use by entities
+    pub fn load(&self, path: &Path) -> Result<(), String> {
+        let source =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let table: Table = self
+            .lua
+            .load(&source)
+            .set_name(path.to_string_lossy())
+            .eval()
+            .map_err(lua_err)?;
+        let meta = self.lua.create_table().map_err(lua_err)?;
+        meta.set("__index", table.clone()).map_err(lua_err)?;
+        let module = Module {
+            modified: modified(path),
+            table: self.lua.create_registry_value(table).map_err(lua_err)?,
+            meta: self.lua.create_registry_value(meta).map_err(lua_err)?,
+        };
+        self.modules.borrow_mut().insert(path.to_owned(), module);
+        Ok(())
+    }
+
+    /// load again the scripts whose files changed since they were loaded.
+    /// entities keep their state and use the new functions from their next
+    /// update. on an error the old version is kept. returns those reloaded
+    pub fn reload_changed(&self) -> Result<Vec<PathBuf>, String> {
+        let changed: Vec<PathBuf> = self
+            .modules
+            .borrow()
+            .iter()
+            .filter(|(path, module)| modified(path) != module.modified)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in changed.iter() {
+            self.load(path)?;
+        }
+        Ok(changed)
+    }
+
+    /// the actions of the input map which scripts see this frame
+    pub fn set_input(&self, map: &InputMap, state: &InputState) {
+        let mut io = self.io.borrow_mut();
+        io.down = map
+            .actions()
+            .filter(|a| map.is_down(state, a))
+            .map(str::to_owned)
+            .collect();
+        io.pressed = map
+            .actions()
+            .filter(|a| map.just_pressed(state, a))
+            .map(str::to_owned)
+            .collect();
+    }
+
+    /// sounds the scripts played since the last call, for the game to give to
+    /// its AudioSystem
+    pub fn take_sounds(&self) -> Vec<String> {
+        std::mem::take(&mut self.io.borrow_mut().sounds)
+    }
+
+    fn module_function<'lua>(
+        &'lua self,
+        path: &Path,
+        name: &str,
+    ) -> Result<Option<Function<'lua>>, String> {
+        if !self.modules.borrow().contains_key(path) {
+            self.load(path)?;
+        }
+        let modules = self.modules.borrow();
+        let table: Table = self
+            .lua
+            .registry_value(&modules[path].table)
+            .map_err(lua_err)?;
+        table.get(name).map_err(lua_err)
+    }
+
+    fn instance(&self, path: &Path, key: &RegistryKey) -> Result<Table<'_>, String> {
+        let instance: Table = self.lua.registry_value(key).map_err(lua_err)?;
+        // after a reload, methods are found in the new module
+        let modules = self.modules.borrow();
+        if let Some(module) = modules.get(path) {
+            let meta: Table = self.lua.registry_value(&module.meta).map_err(lua_err)?;
+            instance.set_metatable(Some(meta));
+        }
+        Ok(instance)
+    }
+}
+
+enum ScriptDraw {
+    Image(PathBuf, FRect),
+    Rect(FRect, Color),
+}
+
+/// an entity whose behavior is a script's module
+pub struct ScriptedEntity {
+    host: Rc<ScriptHost>,
+    path: PathBuf,
+    /// the entity's own table
+    instance: RegistryKey,
+}
+
+impl ScriptedEntity {
+    /// spawn an entity running the script. the props are copied into its
+    /// table, along with its id, before the module's init is called
+    pub fn spawn(
+        host: &Rc<ScriptHost>,
+        world: &mut World,
+        path: &Path,
+        props: Option<Table>,
+    ) -> Result<EntityId, String> {
+        let lua = &host.lua;
+        let instance = lua.create_table().map_err(lua_err)?;
+        if let Some(props) = props {
+            for pair in props.pairs::<mlua::Value, mlua::Value>() {
+                let (key, value) = pair.map_err(lua_err)?;
+                instance.set(key, value).map_err(lua_err)?;
+            }
+        }
+        let init = host.module_function(path, "init")?;
+        let entity = Self {
+            host: host.clone(),
+            path: path.to_owned(),
+            instance: lua
+                .create_registry_value(instance.clone())
+                .map_err(lua_err)?,
+        };
+        let id = world.spawn(Box::new(entity));
+        instance.set("id", id.0).map_err(lua_err)?;
+        if let Some(init) = init {
+            init.call::<_, ()>(instance).map_err(lua_err)?;
+        }
+        Ok(id)
+    }
+
+    fn number(&self, key: &str) -> Option<f32> {
+        let instance: Table = self.host.lua.registry_value(&self.instance).ok()?;
+        instance.get::<_, Option<f32>>(key).ok().flatten()
+    }
+}
+
+impl Entity for ScriptedEntity {
+    fn update(&mut self, world: &mut World) -> Result<(), String> {
+        let host = self.host.clone();
+        let Some(update) = host.module_function(&self.path, "update")? else {
+            return Ok(());
+        };
+        let instance = host.instance(&self.path, &self.instance)?;
+        let world = RefCell::new(world);
+        host.lua
+            .scope(|scope| {
+                let api = host.lua.create_table()?;
+                api.set(
+                    "time",
+                    scope.create_function(|_, ()| Ok(world.borrow().time().as_secs_f64()))?,
+                )?;
+                api.set(
+                    "tick",
+                    scope.create_function(|_, ()| Ok(world.borrow().tick()))?,
+                )?;
+                api.set(
+                    "random",
+                    scope.create_function(|_, stream: String| {
+                        Ok(world.borrow_mut().rng.stream(&stream).next_f32())
+                    })?,
+                )?;
+                api.set(
+                    "spawn",
+                    scope.create_function(|_, (path, props): (String, Option<Table>)| {
+                        let mut world = world.borrow_mut();
+                        ScriptedEntity::spawn(&host, &mut world, Path::new(&path), props)
+                            .map(|id| id.0)
+                            .map_err(mlua::Error::RuntimeError)
+                    })?,
+                )?;
+                api.set(
+                    "despawn",
+                    scope.create_function(|_, id: u64| {
+                        world.borrow_mut().despawn(EntityId(id));
+                        Ok(())
+                    })?,
+                )?;
+                api.set(
+                    "query",
+                    scope.create_function(|_, (x, y, w, h): (f32, f32, f32, f32)| {
+                        let region = FRect::new(x, y, w, h);
+                        Ok(world
+                            .borrow()
+                            .query_region(region)
+                            .into_iter()
+                            .map(|id| id.0)
+                            .collect::<Vec<u64>>())
+                    })?,
+                )?;
+                update.call::<_, ()>((instance, api))
+            })
+            .map_err(lua_err)
+    }
+
+    fn alive(&self) -> bool {
+        let Ok(instance) = self.host.lua.registry_value::<Table>(&self.instance) else {
+            return false;
+        };
+        instance.get::<_, Option<bool>>("alive").ok().flatten() != Some(false)
+    }
+
+    fn aabb(&self) -> Option<FRect> {
+        Some(FRect::new(
+            self.number("x")?,
+            self.number("y")?,
+            self.number("w")?,
+            self.number("h")?,
+        ))
+    }
+
+    fn draw_bottom(&self) -> Option<f32> {
+        self.aabb().map(|aabb| aabb.bottom())
+    }
+
+    fn debug_name(&self) -> &'static str {
+        "script"
+    }
+
+    fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        let Some(draw) = self.host.module_function(&self.path, "draw")? else {
+            return Ok(());
+        };
+        let instance = self.host.instance(&self.path, &self.instance)?;
+        let draws: RefCell<Vec<ScriptDraw>> = Default::default();
+        let lua = &self.host.lua;
+        lua.scope(|scope| {
+            let gfx = lua.create_table()?;
+            gfx.set(
+                "image",
+                scope.create_function(|_, (path, x, y, w, h): (String, f32, f32, f32, f32)| {
+                    let dst = FRect::new(x, y, w, h);
+                    draws.borrow_mut().push(ScriptDraw::Image(path.into(), dst));
+                    Ok(())
+                })?,
+            )?;
+            gfx.set(
+                "rect",
+                scope.create_function(
+                    |_, (x, y, w, h, r, g, b, a): (f32, f32, f32, f32, u8, u8, u8, Option<u8>)| {
+                        let color = Color::RGBA(r, g, b, a.unwrap_or(255));
+                        draws
+                            .borrow_mut()
+                            .push(ScriptDraw::Rect(FRect::new(x, y, w, h), color));
+                        Ok(())
+                    },
+                )?,
+            )?;
+            draw.call::<_, ()>((instance, gfx))
+        })
+        .map_err(lua_err)?;
+
+        let window = &self.host.window;
+        for draw in draws.into_inner() {
+            match draw {
+                ScriptDraw::Image(path, dst) => {
+                    system.draw(window, &path, &[DrawParams::new(None, Some(dst))])?
+                }
+                ScriptDraw::Rect(rect, color) => system.fill_rect(window, rect, color)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn test_script_entity() {
+        let dir = std::env::temp_dir().join(format!("chimeric-lua-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mover.lua");
+        let script = |step: i32| {
+            format!(
+                "local M = {{}}
+                function M.init(self) self.x, self.y, self.w, self.h = 0, 0, 1, 1 end
+                function M.update(self, world)
+                    self.x = self.x + {step}
+                    if input.pressed('jump') then audio.play('jump.wav') end
+                    if self.x >= 10 then self.alive = false end
+                end
+                return M"
+            )
+        };
+        std::fs::write(&path, script(1)).unwrap();
+
+        let host = ScriptHost::new("main").unwrap();
+        let mut world = World::with_seed(0);
+        let props = host.lua.create_table().unwrap();
+        props.set("name", "mover").unwrap();
+        ScriptedEntity::spawn(&host, &mut world, &path, Some(props)).unwrap();
+        world.update().unwrap();
+        world.update().unwrap();
+        let id = world.ids().next().unwrap();
+        assert_eq!(world.query_region(FRect::new(2., 0., 1., 1.)), [id]);
+        assert!(host.take_sounds().is_empty());
+
+        // reloaded scripts keep the entity's state
+        std::fs::write(&path, script(5)).unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(host.reload_changed().unwrap(), vec![path.clone()]);
+        world.update().unwrap();
+        assert_eq!(world.query_region(FRect::new(7., 0., 1., 1.)), [id]);
+        world.update().unwrap();
+        assert!(world.is_empty());
+
+        // a broken edit is reported, and the old version kept
+        std::fs::write(&path, "return 1 +").unwrap();
+        let later = later + std::time::Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(host.reload_changed().is_err());
+        assert!(host.module_function(&path, "update").unwrap().is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
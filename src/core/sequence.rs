@@ -0,0 +1,446 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashSet,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use sdl2::rect::FPoint;
+
+use super::{entity::EntityId, world::World};
+
+pub type SequenceFuture = Pin<Box<dyn Future<Output = Result<(), String>>>>;
+
+type WorldRequest = Box<dyn FnOnce(&mut World) -> Box<dyn Any>>;
+
+/// identifies a running sequence so it can be cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SequenceHandle(u64);
+
+/// state shared between a sequence and the world resuming it
+#[derive(Default)]
+struct Shared {
+    /// the world's simulated time as of this resume
+    now: Duration,
+    /// counts resumes, one per update
+    frame: u64,
+    /// made by Seq::world, run by the world before polling again
+    request: Option<WorldRequest>,
+    response: Option<Box<dyn Any>>,
+}
+
+/// given to a sequence, so it can wait and reach the world between waits:
+///
+/// ```ignore
+/// world.start_sequence(move |seq| async move {
+///     seq.wait_seconds(1.).await;
+///     seq.move_entity_to(guard, FPoint::new(64., 32.), 40.).await?;
+///     let door: DoorOpened = seq.wait_for_event(|_: &DoorOpened| true).await;
+///     seq.world(move |world| world.despawn(door.0)).await;
+///     Ok(())
+/// });
+/// ```
+#[derive(Clone)]
+pub struct Seq {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Seq {
+    /// the world's simulated time
+    pub fn now(&self) -> Duration {
+        self.shared.borrow().now
+    }
+
+    /// resume in the next update
+    pub fn next_frame(&self) -> NextFrame {
+        NextFrame {
+            shared: self.shared.clone(),
+            frame: self.shared.borrow().frame,
+        }
+    }
+
+    /// resume in the first update at least this much simulated time later
+    pub fn wait(&self, duration: Duration) -> Wait {
+        Wait {
+            shared: self.shared.clone(),
+            until: self.now() + duration,
+        }
+    }
+
+    pub fn wait_seconds(&self, seconds: f64) -> Wait {
+        self.wait(Duration::from_secs_f64(seconds.max(0.)))
+    }
+
+    /// run the closure with the world, without waiting for the next update
+    pub fn world<T, F>(&self, f: F) -> WorldCall<T>
+    where
+        T: 'static,
+        F: FnOnce(&mut World) -> T + 'static,
+    {
+        WorldCall {
+            shared: self.shared.clone(),
+            request: Some(Box::new(move |world| Box::new(f(world)))),
+            result: PhantomData,
+        }
+    }
+
+    /// resume in the first update (including this one) in which the
+    /// condition holds
+    pub async fn wait_until<F>(&self, condition: F)
+    where
+        F: FnMut(&mut World) -> bool + 'static,
+    {
+        let condition = Rc::new(RefCell::new(condition));
+        loop {
+            let condition = condition.clone();
+            if self
+                .world(move |world| (condition.borrow_mut())(world))
+                .await
+            {
+                return;
+            }
+            self.next_frame().await;
+        }
+    }
+
+    /// the first event on the world's bus which matches. events published
+    /// before the sequence started waiting aren't seen
+    pub async fn wait_for_event<E, F>(&self, matches: F) -> E
+    where
+        E: Clone + 'static,
+        F: Fn(&E) -> bool + 'static,
+    {
+        let matches = Rc::new(matches);
+        loop {
+            self.next_frame().await;
+            let matches = matches.clone();
+            let event = self
+                .world(move |world| {
+                    world
+                        .events
+                        .read::<E>()
+                        .iter()
+                        .find(|e| matches(e))
+                        .cloned()
+                })
+                .await;
+            if let Some(event) = event {
+                return event;
+            }
+        }
+    }
+
+    /// move the entity's local position in a straight line, at speed units per
+    /// second of simulated time. fails if the entity is gone or has no
+    /// transform
+    pub async fn move_entity_to(
+        &self,
+        id: EntityId,
+        target: FPoint,
+        speed: f32,
+    ) -> Result<(), String> {
+        loop {
+            let arrived = self
+                .world(move |world| {
+                    let step = speed * world.timestep().as_secs_f32();
+                    let node = world
+                        .entity_mut(id)
+                        .and_then(|e| e.transform_mut())
+                        .ok_or_else(|| format!("entity {} has no transform", id.0))?;
+                    let position = node.local.position;
+                    let (dx, dy) = (target.x() - position.x(), target.y() - position.y());
+                    let distance = (dx * dx + dy * dy).sqrt();
+                    if distance <= step {
+                        node.local.position = target;
+                        return Ok(true);
+                    }
+                    node.local.position = FPoint::new(
+                        position.x() + dx / distance * step,
+                        position.y() + dy / distance * step,
+                    );
+                    Ok::<_, String>(false)
+                })
+                .await?;
+            if arrived {
+                return Ok(());
+            }
+            self.next_frame().await;
+        }
+    }
+}
+
+pub struct NextFrame {
+    shared: Rc<RefCell<Shared>>,
+    frame: u64,
+}
+
+impl Future for NextFrame {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.shared.borrow().frame > self.frame {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+pub struct Wait {
+    shared: Rc<RefCell<Shared>>,
+    until: Duration,
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        if self.shared.borrow().now >= self.until {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+pub struct WorldCall<T> {
+    shared: Rc<RefCell<Shared>>,
+    request: Option<WorldRequest>,
+    result: PhantomData<T>,
+}
+
+impl<T> Unpin for WorldCall<T> {}
+
+impl<T: 'static> Future for WorldCall<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<T> {
+        let this = &mut *self;
+        let mut shared = this.shared.borrow_mut();
+        if let Some(request) = this.request.take() {
+            shared.request = Some(request);
+            return Poll::Pending;
+        }
+        match shared.response.take() {
+            Some(response) => Poll::Ready(
+                *response
+                    .downcast()
+                    .expect("response to this call's own request"),
+            ),
+            None => Poll::Pending,
+        }
+    }
+}
+
+pub(crate) struct Sequence {
+    handle: SequenceHandle,
+    shared: Rc<RefCell<Shared>>,
+    future: SequenceFuture,
+}
+
+impl Sequence {
+    pub(crate) fn handle(&self) -> SequenceHandle {
+        self.handle
+    }
+
+    /// poll until the sequence waits or finishes, running its requests for
+    /// the world in between
+    pub(crate) fn resume(&mut self, world: &mut World) -> Poll<Result<(), String>> {
+        {
+            let mut shared = self.shared.borrow_mut();
+            shared.now = world.time();
+            shared.frame += 1;
+        }
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(result) = self.future.as_mut().poll(&mut context) {
+                return Poll::Ready(result);
+            }
+            let Some(request) = self.shared.borrow_mut().request.take() else {
+                return Poll::Pending;
+            };
+            let response = request(world);
+            self.shared.borrow_mut().response = Some(response);
+        }
+    }
+}
+
+/// multi-step gameplay written as async blocks, resumed by the world once per
+/// update against its simulated clock
+#[derive(Default)]
+pub struct Sequences {
+    next_handle: u64,
+    running: Vec<Sequence>,
+    /// taken out of running to be resumed this update
+    resuming: HashSet<SequenceHandle>,
+    /// subset of resuming which was cancelled while resuming
+    cancelled: HashSet<SequenceHandle>,
+    /// every resume so far, of any sequence
+    resumes: u64,
+}
+
+/// where the world's sequences were at, for rewinding. a sequence's progress
+/// can't be copied, so only sequences which haven't been resumed since can be
+/// rewound
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SequencesMark {
+    next_handle: u64,
+    resumes: u64,
+}
+
+impl Sequences {
+    /// the body runs up to its first wait in the next update
+    pub fn start<F, Fut>(&mut self, body: F) -> SequenceHandle
+    where
+        F: FnOnce(Seq) -> Fut,
+        Fut: Future<Output = Result<(), String>> + 'static,
+    {
+        let handle = SequenceHandle(self.next_handle);
+        self.next_handle += 1;
+        let shared = Rc::new(RefCell::new(Shared::default()));
+        let future = Box::pin(body(Seq {
+            shared: shared.clone(),
+        }));
+        self.running.push(Sequence {
+            handle,
+            shared,
+            future,
+        });
+        handle
+    }
+
+    /// returns false if the sequence already finished or was already
+    /// cancelled
+    pub fn cancel(&mut self, handle: SequenceHandle) -> bool {
+        if let Some(i) = self.running.iter().position(|s| s.handle == handle) {
+            self.running.remove(i);
+            return true;
+        }
+        self.resuming.contains(&handle) && self.cancelled.insert(handle)
+    }
+
+    pub fn is_running(&self, handle: SequenceHandle) -> bool {
+        self.running.iter().any(|s| s.handle == handle)
+            || (self.resuming.contains(&handle) && !self.cancelled.contains(&handle))
+    }
+
+    pub fn len(&self) -> usize {
+        self.running.len() + self.resuming.len() - self.cancelled.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn is_cancelled(&self, handle: SequenceHandle) -> bool {
+        self.cancelled.contains(&handle)
+    }
+
+    /// take out every sequence, in start order. each must be given back with
+    /// put_back
+    pub(crate) fn take(&mut self) -> Vec<Sequence> {
+        let taken = std::mem::take(&mut self.running);
+        self.resumes += taken.len() as u64;
+        self.resuming.extend(taken.iter().map(|s| s.handle));
+        taken
+    }
+
+    pub(crate) fn mark(&self) -> SequencesMark {
+        SequencesMark {
+            next_handle: self.next_handle,
+            resumes: self.resumes,
+        }
+    }
+
+    /// drop sequences started since the mark. fails if any sequence was
+    /// resumed after it, which can't be undone
+    pub(crate) fn rewind(&mut self, mark: SequencesMark) -> Result<(), String> {
+        if self.resumes != mark.resumes {
+            return Err("a sequence was resumed since the state being rewound to".into());
+        }
+        self.running
+            .retain(|s| s.handle < SequenceHandle(mark.next_handle));
+        self.next_handle = mark.next_handle;
+        Ok(())
+    }
+
+    /// return a sequence taken out by take. finished and cancelled sequences
+    /// are dropped
+    pub(crate) fn put_back(&mut self, sequence: Sequence, finished: bool) {
+        self.resuming.remove(&sequence.handle);
+        if self.cancelled.remove(&sequence.handle) || finished {
+            return;
+        }
+        self.running.push(sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{entity::Entity, transform::TransformNode};
+
+    #[derive(Default)]
+    struct Marker {
+        transform: TransformNode,
+    }
+
+    impl Entity for Marker {
+        fn update(&mut self, _world: &mut World) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn transform_mut(&mut self) -> Option<&mut TransformNode> {
+            Some(&mut self.transform)
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Signal(u32);
+
+    #[test]
+    fn test_sequence() {
+        let mut world = World::new();
+        world.set_timestep(Duration::from_millis(100));
+        let marker = world.spawn(Box::new(Marker::default()));
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let out = log.clone();
+        let handle = world.start_sequence(move |seq| async move {
+            seq.wait_seconds(0.3).await;
+            out.borrow_mut().push(format!("waited {:?}", seq.now()));
+            let signal = seq.wait_for_event(|s: &Signal| s.0 > 1).await;
+            out.borrow_mut().push(format!("signal {}", signal.0));
+            seq.move_entity_to(marker, FPoint::new(3., 4.), 25.).await?;
+            out.borrow_mut().push(format!("arrived {:?}", seq.now()));
+            Ok(())
+        });
+
+        for _ in 0..4 {
+            world.update().unwrap();
+        }
+        assert_eq!(*log.borrow(), ["waited 400ms"]);
+        world.events.publish(Signal(1));
+        world.events.publish(Signal(2));
+        // 5 units at 2.5 per update, starting as soon as the signal is seen
+        world.update().unwrap();
+        assert_eq!(log.borrow()[1], "signal 2");
+        assert!(world.is_sequence_running(handle));
+        world.update().unwrap();
+        assert_eq!(log.borrow()[2], "arrived 600ms");
+        assert!(!world.is_sequence_running(handle));
+
+        let cancelled = world.start_sequence(|seq| async move {
+            seq.next_frame().await;
+            Err("should have been cancelled".into())
+        });
+        world.update().unwrap();
+        assert!(world.cancel_sequence(cancelled));
+        world.update().unwrap();
+    }
+}
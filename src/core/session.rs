@@ -0,0 +1,576 @@
+use std::{
+    collections::BTreeMap,
+    net::{SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use super::{
+    net::{Channel, DisconnectReason, NetEvent, NetSettings, Transport},
+    trace::log_debug,
+};
+
+/// the host is always this player
+pub const HOST: u32 = 0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionPlayer {
+    pub id: u32,
+    pub name: String,
+    pub ready: bool,
+}
+
+/// returned from Session::update, for a lobby ui to show
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// also returned for each player already there on joining, including the
+    /// local one
+    Joined {
+        player: u32,
+    },
+    Left {
+        player: u32,
+    },
+    ReadyChanged {
+        player: u32,
+        ready: bool,
+    },
+    Chat {
+        player: u32,
+        text: String,
+    },
+    /// game data sent with Session::send
+    Data {
+        player: u32,
+        channel: Channel,
+        data: Vec<u8>,
+    },
+    /// a client lost the host, or wasn't let in. the session is over
+    Ended {
+        reason: DisconnectReason,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Message {
+    /// client to host, once connected
+    Hello { name: String },
+    /// client to host
+    Ready { ready: bool },
+    /// client to host, then host to everyone with who said it
+    Chat { player: u32, text: String },
+    /// host to a client which joined
+    Welcome { player: u32 },
+    /// host to everyone, whenever a player joins, leaves or changes
+    Roster { players: Vec<SessionPlayer> },
+    /// anything else, for the game
+    Data { data: Vec<u8> },
+}
+
+fn take<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], String> {
+    if data.len() < N {
+        return Err("session message is truncated".into());
+    }
+    let (taken, rest) = data.split_at(N);
+    *data = rest;
+    Ok(taken.try_into().expect("length checked"))
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn take_str(data: &mut &[u8]) -> Result<String, String> {
+    let len = u32::from_le_bytes(take(data)?) as usize;
+    if data.len() < len {
+        return Err("session message is truncated".into());
+    }
+    let (s, rest) = data.split_at(len);
+    *data = rest;
+    String::from_utf8(s.to_vec()).map_err(|e| e.to_string())
+}
+
+impl Message {
+    /// u8(kind) + the kind's fields, little endian. strings are u32(len) +
+    /// utf-8. a roster is u32(count) + for each: u32(id) + name + u8(ready)
+    fn encode(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+        match self {
+            Message::Hello { name } => {
+                out.push(0);
+                put_str(&mut out, name);
+            }
+            Message::Ready { ready } => out.extend_from_slice(&[1, *ready as u8]),
+            Message::Chat { player, text } => {
+                out.push(2);
+                out.extend_from_slice(&player.to_le_bytes());
+                put_str(&mut out, text);
+            }
+            Message::Welcome { player } => {
+                out.push(3);
+                out.extend_from_slice(&player.to_le_bytes());
+            }
+            Message::Roster { players } => {
+                out.push(4);
+                out.extend_from_slice(&(players.len() as u32).to_le_bytes());
+                for player in players {
+                    out.extend_from_slice(&player.id.to_le_bytes());
+                    put_str(&mut out, &player.name);
+                    out.push(player.ready as u8);
+                }
+            }
+            Message::Data { data } => {
+                out.push(5);
+                out.extend_from_slice(data);
+            }
+        }
+        out
+    }
+
+    fn decode(mut data: &[u8]) -> Result<Self, String> {
+        let [kind] = take::<1>(&mut data)?;
+        let message = match kind {
+            0 => Message::Hello {
+                name: take_str(&mut data)?,
+            },
+            1 => Message::Ready {
+                ready: take::<1>(&mut data)? != [0],
+            },
+            2 => Message::Chat {
+                player: u32::from_le_bytes(take(&mut data)?),
+                text: take_str(&mut data)?,
+            },
+            3 => Message::Welcome {
+                player: u32::from_le_bytes(take(&mut data)?),
+            },
+            4 => {
+                let count = u32::from_le_bytes(take(&mut data)?);
+                let mut players = Vec::new();
+                for _ in 0..count {
+                    players.push(SessionPlayer {
+                        id: u32::from_le_bytes(take(&mut data)?),
+                        name: take_str(&mut data)?,
+                        ready: take::<1>(&mut data)? != [0],
+                    });
+                }
+                Message::Roster { players }
+            }
+            5 => {
+                return Ok(Message::Data {
+                    data: data.to_vec(),
+                })
+            }
+            _ => return Err(format!("unknown session message kind {}", kind)),
+        };
+        if !data.is_empty() {
+            return Err("session message has trailing data".into());
+        }
+        Ok(message)
+    }
+}
+
+enum Role {
+    /// the player of each client which said hello
+    Host {
+        clients: BTreeMap<SocketAddr, u32>,
+    },
+    Client {
+        host: SocketAddr,
+    },
+}
+
+/// a lobby over a Transport: players join a host by address, pick a name,
+/// mark themselves ready and chat. how addresses are found (typed in, a
+/// matchmaking service, lan discovery) is up to the game. the host relays
+/// everything, so clients only talk to it
+pub struct Session {
+    transport: Transport,
+    role: Role,
+    /// by id. a client's is the host's latest roster
+    players: BTreeMap<u32, SessionPlayer>,
+    /// None until a client is welcomed
+    local: Option<u32>,
+    /// sent once connected
+    name: String,
+    next_id: u32,
+    /// returned from the next update
+    events: Vec<SessionEvent>,
+}
+
+impl Session {
+    /// host on the address, e.g. "0.0.0.0:7777", as player 0
+    pub fn host(
+        addr: impl ToSocketAddrs,
+        name: &str,
+        settings: NetSettings,
+    ) -> Result<Self, String> {
+        let host = SessionPlayer {
+            id: HOST,
+            name: name.to_owned(),
+            ready: false,
+        };
+        Ok(Self {
+            transport: Transport::server(addr, settings)?,
+            role: Role::Host {
+                clients: Default::default(),
+            },
+            players: BTreeMap::from([(HOST, host)]),
+            local: Some(HOST),
+            name: name.to_owned(),
+            next_id: HOST + 1,
+            events: vec![SessionEvent::Joined { player: HOST }],
+        })
+    }
+
+    /// join a host. the players, including the local one, are returned as
+    /// joining from update once the host lets this one in
+    pub fn join(
+        addr: impl ToSocketAddrs,
+        name: &str,
+        settings: NetSettings,
+        now: Duration,
+    ) -> Result<Self, String> {
+        let host = addr
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| "no address to join".to_owned())?;
+        Ok(Self {
+            transport: Transport::client(host, settings, now)?,
+            role: Role::Client { host },
+            players: Default::default(),
+            local: None,
+            name: name.to_owned(),
+            next_id: HOST + 1,
+            events: Vec::new(),
+        })
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host { .. })
+    }
+
+    /// the local player's id, once joined
+    pub fn local_player(&self) -> Option<u32> {
+        self.local
+    }
+
+    /// lowest id first
+    pub fn players(&self) -> impl Iterator<Item = &SessionPlayer> + '_ {
+        self.players.values()
+    }
+
+    pub fn player(&self, id: u32) -> Option<&SessionPlayer> {
+        self.players.get(&id)
+    }
+
+    /// e.g. to start the game once everyone is
+    pub fn all_ready(&self) -> bool {
+        !self.players.is_empty() && self.players.values().all(|p| p.ready)
+    }
+
+    /// allow or refuse new players, e.g. once the game starts. only for the
+    /// host
+    pub fn set_accepting(&mut self, accepting: bool) {
+        if self.is_host() {
+            self.transport.accepting = accepting;
+        }
+    }
+
+    /// e.g. for the local address, or game traffic outside the session
+    pub fn transport_mut(&mut self) -> &mut Transport {
+        &mut self.transport
+    }
+
+    /// the local player's change comes back from update like anyone else's
+    pub fn set_ready(&mut self, ready: bool, now: Duration) -> Result<(), String> {
+        match self.role {
+            Role::Host { .. } => self.host_set_ready(HOST, ready, now),
+            Role::Client { host } => self.send_message(host, &Message::Ready { ready }, now),
+        }
+    }
+
+    /// say something to everyone. the local player's line comes back from
+    /// update like anyone else's
+    pub fn chat(&mut self, text: &str, now: Duration) -> Result<(), String> {
+        let text = text.to_owned();
+        match self.role {
+            Role::Host { .. } => self.host_chat(HOST, text, now),
+            // the host fills in who said it
+            Role::Client { host } => {
+                self.send_message(host, &Message::Chat { player: HOST, text }, now)
+            }
+        }
+    }
+
+    /// game data for a player, which arrives as SessionEvent::Data. clients
+    /// can only send to the host
+    pub fn send(
+        &mut self,
+        player: u32,
+        channel: Channel,
+        data: &[u8],
+        now: Duration,
+    ) -> Result<(), String> {
+        let peer = match &self.role {
+            Role::Host { clients } => clients
+                .iter()
+                .find(|(_, id)| **id == player)
+                .map(|(addr, _)| *addr),
+            Role::Client { host } => (player == HOST).then_some(*host),
+        }
+        .ok_or_else(|| format!("can't send to player {}", player))?;
+        let message = Message::Data {
+            data: data.to_vec(),
+        };
+        self.transport.send(peer, channel, &message.encode(), now)
+    }
+
+    /// leave, or end the session for everyone if hosting
+    pub fn leave(&mut self, now: Duration) {
+        self.transport.disconnect_all(now);
+        if let Role::Host { clients } = &mut self.role {
+            clients.clear();
+        }
+        self.players.clear();
+        self.local = None;
+    }
+
+    fn send_message(
+        &mut self,
+        peer: SocketAddr,
+        message: &Message,
+        now: Duration,
+    ) -> Result<(), String> {
+        self.transport
+            .send(peer, Channel::ReliableOrdered, &message.encode(), now)
+    }
+
+    /// replace the players, noting what changed
+    fn set_roster(&mut self, players: Vec<SessionPlayer>) {
+        let players: BTreeMap<u32, SessionPlayer> =
+            players.into_iter().map(|p| (p.id, p)).collect();
+        for id in self.players.keys() {
+            if !players.contains_key(id) {
+                self.events.push(SessionEvent::Left { player: *id });
+            }
+        }
+        for (id, player) in players.iter() {
+            let was_ready = match self.players.get(id) {
+                Some(old) => old.ready,
+                None => {
+                    self.events.push(SessionEvent::Joined { player: *id });
+                    false
+                }
+            };
+            if player.ready != was_ready {
+                self.events.push(SessionEvent::ReadyChanged {
+                    player: *id,
+                    ready: player.ready,
+                });
+            }
+        }
+        self.players = players;
+    }
+
+    /// to each client which said hello
+    fn host_broadcast(&mut self, message: &Message, now: Duration) -> Result<(), String> {
+        let Role::Host { clients } = &self.role else {
+            return Err("only the host broadcasts".into());
+        };
+        let peers: Vec<SocketAddr> = clients.keys().copied().collect();
+        for peer in peers {
+            self.send_message(peer, message, now)?;
+        }
+        Ok(())
+    }
+
+    fn host_set_roster(
+        &mut self,
+        players: Vec<SessionPlayer>,
+        now: Duration,
+    ) -> Result<(), String> {
+        self.set_roster(players);
+        let players = self.players.values().cloned().collect();
+        self.host_broadcast(&Message::Roster { players }, now)
+    }
+
+    fn host_set_ready(&mut self, player: u32, ready: bool, now: Duration) -> Result<(), String> {
+        let mut players: Vec<SessionPlayer> = self.players.values().cloned().collect();
+        for p in players.iter_mut().filter(|p| p.id == player) {
+            p.ready = ready;
+        }
+        self.host_set_roster(players, now)
+    }
+
+    fn host_chat(&mut self, player: u32, text: String, now: Duration) -> Result<(), String> {
+        let message = Message::Chat { player, text };
+        self.host_broadcast(&message, now)?;
+        if let Message::Chat { player, text } = message {
+            self.events.push(SessionEvent::Chat { player, text });
+        }
+        Ok(())
+    }
+
+    /// receive, and return what happened since the last update
+    pub fn update(&mut self, now: Duration) -> Vec<SessionEvent> {
+        for event in self.transport.update(now) {
+            let result = match self.role {
+                Role::Host { .. } => self.host_receive(event, now),
+                Role::Client { .. } => self.client_receive(event, now),
+            };
+            if let Err(e) = result {
+                log_debug!("session: {e}");
+            }
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    fn clients(&mut self) -> &mut BTreeMap<SocketAddr, u32> {
+        match &mut self.role {
+            Role::Host { clients } => clients,
+            Role::Client { .. } => unreachable!("only the host has clients"),
+        }
+    }
+
+    fn host_receive(&mut self, event: NetEvent, now: Duration) -> Result<(), String> {
+        match event {
+            // a player once they say hello
+            NetEvent::Connected { .. } => Ok(()),
+            NetEvent::Disconnected { peer, .. } => match self.clients().remove(&peer) {
+                Some(id) => {
+                    let players = self.players.values().filter(|p| p.id != id);
+                    self.host_set_roster(players.cloned().collect(), now)
+                }
+                None => Ok(()),
+            },
+            NetEvent::Message {
+                peer,
+                channel,
+                data,
+            } => match (Message::decode(&data)?, self.clients().get(&peer).copied()) {
+                (Message::Hello { name }, None) => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    self.clients().insert(peer, id);
+                    self.send_message(peer, &Message::Welcome { player: id }, now)?;
+                    let mut players: Vec<SessionPlayer> = self.players.values().cloned().collect();
+                    players.push(SessionPlayer {
+                        id,
+                        name,
+                        ready: false,
+                    });
+                    self.host_set_roster(players, now)
+                }
+                (_, None) => Err(format!("{} hasn't said hello", peer)),
+                (Message::Ready { ready }, Some(id)) => self.host_set_ready(id, ready, now),
+                (Message::Chat { text, .. }, Some(id)) => self.host_chat(id, text, now),
+                (Message::Data { data }, Some(id)) => {
+                    self.events.push(SessionEvent::Data {
+                        player: id,
+                        channel,
+                        data,
+                    });
+                    Ok(())
+                }
+                (message, Some(id)) => Err(format!("unexpected {:?} from player {}", message, id)),
+            },
+        }
+    }
+
+    fn client_receive(&mut self, event: NetEvent, now: Duration) -> Result<(), String> {
+        match event {
+            NetEvent::Connected { peer } => {
+                let hello = Message::Hello {
+                    name: self.name.clone(),
+                };
+                self.send_message(peer, &hello, now)
+            }
+            NetEvent::Disconnected { reason, .. } => {
+                self.players.clear();
+                self.local = None;
+                self.events.push(SessionEvent::Ended { reason });
+                Ok(())
+            }
+            NetEvent::Message { channel, data, .. } => {
+                match Message::decode(&data)? {
+                    Message::Welcome { player } => self.local = Some(player),
+                    Message::Roster { players } => self.set_roster(players),
+                    Message::Chat { player, text } => {
+                        self.events.push(SessionEvent::Chat { player, text })
+                    }
+                    Message::Data { data } => self.events.push(SessionEvent::Data {
+                        player: HOST,
+                        channel,
+                        data,
+                    }),
+                    message => return Err(format!("unexpected {:?} from the host", message)),
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lobby() {
+        let message = Message::Roster {
+            players: vec![SessionPlayer {
+                id: 1,
+                name: "ana".into(),
+                ready: true,
+            }],
+        };
+        assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+        assert!(Message::decode(&message.encode()[..7]).is_err());
+
+        let ms = Duration::from_millis;
+        let mut host = Session::host("127.0.0.1:0", "host", NetSettings::default()).unwrap();
+        let addr = host.transport_mut().local_addr().unwrap();
+        let mut client = Session::join(addr, "ana", NetSettings::default(), ms(0)).unwrap();
+        let poll = |host: &mut Session, client: &mut Session| {
+            let mut events = (Vec::new(), Vec::new());
+            for _ in 0..20 {
+                events.0.extend(host.update(ms(0)));
+                events.1.extend(client.update(ms(0)));
+                std::thread::sleep(ms(5));
+            }
+            events
+        };
+        let (h, c) = poll(&mut host, &mut client);
+        assert_eq!(
+            h,
+            [
+                SessionEvent::Joined { player: HOST },
+                SessionEvent::Joined { player: 1 }
+            ]
+        );
+        assert_eq!(c.len(), 2);
+        assert_eq!(client.local_player(), Some(1));
+        assert_eq!(client.player(HOST).unwrap().name, "host");
+
+        client.set_ready(true, ms(0)).unwrap();
+        client.chat("hi", ms(0)).unwrap();
+        host.set_ready(true, ms(0)).unwrap();
+        let (h, c) = poll(&mut host, &mut client);
+        let chat = SessionEvent::Chat {
+            player: 1,
+            text: "hi".into(),
+        };
+        assert!(h.contains(&chat) && c.contains(&chat));
+        assert!(c.contains(&SessionEvent::ReadyChanged {
+            player: HOST,
+            ready: true
+        }));
+        assert!(host.all_ready() && client.all_ready());
+
+        client.leave(ms(0));
+        let (h, _) = poll(&mut host, &mut client);
+        assert_eq!(h, [SessionEvent::Left { player: 1 }]);
+        assert_eq!(host.players().count(), 1);
+    }
+}
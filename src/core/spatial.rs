@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use sdl2::rect::{FPoint, FRect};
+
+/// true if the rectangles overlap. rectangles which only share an edge are not
+/// considered to overlap
+pub fn overlaps(a: FRect, b: FRect) -> bool {
+    a.left() < b.right() && b.left() < a.right() && a.top() < b.bottom() && b.top() < a.bottom()
+}
+
+/// the parametric range (t_enter, t_exit) in which the ray origin + t * dir is
+/// inside the rectangle, where t_exit >= t_enter and t_exit >= 0
+pub fn ray_range(origin: FPoint, dir: FPoint, aabb: FRect) -> Option<(f32, f32)> {
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    for (o, d, min, max) in [
+        (origin.x(), dir.x(), aabb.left(), aabb.right()),
+        (origin.y(), dir.y(), aabb.top(), aabb.bottom()),
+    ] {
+        if d == 0. {
+            if o < min || o > max {
+                return None;
+            }
+            continue;
+        }
+        let t0 = (min - o) / d;
+        let t1 = (max - o) / d;
+        t_enter = t_enter.max(t0.min(t1));
+        t_exit = t_exit.min(t0.max(t1));
+    }
+    if t_exit < t_enter || t_exit < 0. {
+        return None;
+    }
+    Some((t_enter, t_exit))
+}
+
+/// uniform grid which buckets items by their axis aligned bounding box
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    entries: Vec<(T, FRect)>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// encloses all entries
+    bounds: Option<FRect>,
+}
+
+impl<T> Default for SpatialGrid<T> {
+    fn default() -> Self {
+        Self::new(64.)
+    }
+}
+
+impl<T> SpatialGrid<T> {
+    pub fn new(cell_size: f32) -> Self {
+        debug_assert!(cell_size > 0.);
+        Self {
+            cell_size,
+            entries: Default::default(),
+            cells: Default::default(),
+            bounds: None,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.clear();
+        self.bounds = None;
+    }
+
+    fn cell_of(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, item: T, aabb: FRect) {
+        let index = self.entries.len();
+        self.entries.push((item, aabb));
+        let (x0, y0) = self.cell_of(aabb.left(), aabb.top());
+        let (x1, y1) = self.cell_of(aabb.right(), aabb.bottom());
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+        self.bounds = Some(match self.bounds {
+            Some(bounds) => FRect::new(
+                bounds.left().min(aabb.left()),
+                bounds.top().min(aabb.top()),
+                bounds.right().max(aabb.right()) - bounds.left().min(aabb.left()),
+                bounds.bottom().max(aabb.bottom()) - bounds.top().min(aabb.top()),
+            ),
+            None => aabb,
+        });
+    }
+}
+
+impl<T: Copy> SpatialGrid<T> {
+    /// all items whose bounding box overlaps the region, in insertion order
+    pub fn query_region(&self, region: FRect) -> Vec<T> {
+        let (x0, y0) = self.cell_of(region.left(), region.top());
+        let (x1, y1) = self.cell_of(region.right(), region.bottom());
+        let mut indices: Vec<usize> = Vec::new();
+        // iterate over whichever is smaller; the cells covered by the region or
+        // the occupied cells
+        let num_region_cells = (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1);
+        if num_region_cells as usize <= self.cells.len() {
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    if let Some(cell) = self.cells.get(&(x, y)) {
+                        indices.extend_from_slice(cell);
+                    }
+                }
+            }
+        } else {
+            self.cells
+                .iter()
+                .filter(|((x, y), _)| (x0..=x1).contains(x) && (y0..=y1).contains(y))
+                .for_each(|(_, cell)| indices.extend_from_slice(cell));
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+            .into_iter()
+            .filter(|&i| overlaps(self.entries[i].1, region))
+            .map(|i| self.entries[i].0)
+            .collect()
+    }
+
+    /// all items hit by the ray origin + t * dir (t >= 0), with the t at which
+    /// they're first hit. sorted nearest first
+    pub fn query_ray(&self, origin: FPoint, dir: FPoint) -> Vec<(T, f32)> {
+        if dir.x() == 0. && dir.y() == 0. {
+            return Vec::new();
+        }
+        let (t_enter, t_exit) = match self.bounds.and_then(|b| ray_range(origin, dir, b)) {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+        let t_start = t_enter.max(0.);
+
+        // walk the cells along the ray (amanatides & woo)
+        let (mut cx, mut cy) = self.cell_of(
+            origin.x() + dir.x() * t_start,
+            origin.y() + dir.y() * t_start,
+        );
+        let axis = |o: f32, d: f32, c: i32| -> (i32, f32, f32) {
+            if d > 0. {
+                (
+                    1,
+                    ((c + 1) as f32 * self.cell_size - o) / d,
+                    self.cell_size / d,
+                )
+            } else if d < 0. {
+                (-1, (c as f32 * self.cell_size - o) / d, -self.cell_size / d)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, mut t_max_x, t_delta_x) = axis(origin.x(), dir.x(), cx);
+        let (step_y, mut t_max_y, t_delta_y) = axis(origin.y(), dir.y(), cy);
+
+        let mut indices: Vec<usize> = Vec::new();
+        loop {
+            if let Some(cell) = self.cells.get(&(cx, cy)) {
+                indices.extend_from_slice(cell);
+            }
+            if t_max_x < t_max_y {
+                if t_max_x > t_exit {
+                    break;
+                }
+                cx += step_x;
+                t_max_x += t_delta_x;
+            } else {
+                if t_max_y > t_exit {
+                    break;
+                }
+                cy += step_y;
+                t_max_y += t_delta_y;
+            }
+        }
+
+        indices.sort_unstable();
+        indices.dedup();
+        let mut hits: Vec<(T, f32)> = indices
+            .into_iter()
+            .filter_map(|i| {
+                let (item, aabb) = self.entries[i];
+                ray_range(origin, dir, aabb).map(|(t, _)| (item, t.max(0.)))
+            })
+            .collect();
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_region() {
+        let mut grid = SpatialGrid::new(10.);
+        grid.insert(0, FRect::new(0., 0., 5., 5.));
+        grid.insert(1, FRect::new(8., 8., 5., 5.));
+        grid.insert(2, FRect::new(-100., -100., 250., 250.));
+        grid.insert(3, FRect::new(50., 50., 5., 5.));
+        assert_eq!(grid.query_region(FRect::new(1., 1., 2., 2.)), vec![0, 2]);
+        assert_eq!(grid.query_region(FRect::new(4., 4., 5., 5.)), vec![0, 1, 2]);
+        assert_eq!(
+            grid.query_region(FRect::new(200., 200., 5., 5.)),
+            Vec::<i32>::new()
+        );
+        assert_eq!(
+            grid.query_region(FRect::new(-1000., -1000., 2000., 2000.)),
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_query_ray() {
+        let mut grid = SpatialGrid::new(10.);
+        grid.insert(0, FRect::new(30., 0., 5., 5.));
+        grid.insert(1, FRect::new(10., 0., 5., 5.));
+        grid.insert(2, FRect::new(10., 20., 5., 5.));
+        let hits = grid.query_ray(FPoint::new(0., 2.), FPoint::new(1., 0.));
+        assert_eq!(hits, vec![(1, 10.), (0, 30.)]);
+        let hits = grid.query_ray(FPoint::new(-50., 2.), FPoint::new(-1., 0.));
+        assert!(hits.is_empty());
+        let hits = grid.query_ray(FPoint::new(12., 100.), FPoint::new(0., -2.));
+        assert_eq!(hits, vec![(2, 37.5), (1, 47.5)]);
+    }
+}
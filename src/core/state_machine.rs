@@ -0,0 +1,246 @@
+use std::{collections::HashMap, hash::Hash};
+
+type Callback<C> = Box<dyn FnMut(&mut C)>;
+type Guard<C> = Box<dyn Fn(&C) -> bool>;
+
+/// callbacks for a state. all are optional
+pub struct State<S, C> {
+    parent: Option<S>,
+    on_enter: Option<Callback<C>>,
+    on_update: Option<Callback<C>>,
+    on_exit: Option<Callback<C>>,
+}
+
+impl<S, C> Default for State<S, C> {
+    fn default() -> Self {
+        Self {
+            parent: None,
+            on_enter: None,
+            on_update: None,
+            on_exit: None,
+        }
+    }
+}
+
+impl<S, C> State<S, C> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// while this state is active its parent is active too. the parent's
+    /// transitions apply to all of its children
+    pub fn parent(mut self, parent: S) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    pub fn on_enter<F: FnMut(&mut C) + 'static>(mut self, f: F) -> Self {
+        self.on_enter = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_update<F: FnMut(&mut C) + 'static>(mut self, f: F) -> Self {
+        self.on_update = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_exit<F: FnMut(&mut C) + 'static>(mut self, f: F) -> Self {
+        self.on_exit = Some(Box::new(f));
+        self
+    }
+}
+
+struct Transition<S, C> {
+    from: S,
+    to: S,
+    guard: Guard<C>,
+}
+
+/// hierarchical state machine, intended to live inside an entity and be
+/// updated from its update with whatever context the states need
+///
+/// each update, transitions are checked from the innermost active state
+/// outward; the first (in the order added) whose guard passes is taken. then
+/// the active states are updated from the outermost inward
+pub struct StateMachine<S, C> {
+    states: HashMap<S, State<S, C>>,
+    transitions: Vec<Transition<S, C>>,
+    current: S,
+    /// the initial states are entered on the first update
+    started: bool,
+}
+
+impl<S: Copy + Eq + Hash, C> StateMachine<S, C> {
+    pub fn new(initial: S) -> Self {
+        Self {
+            states: Default::default(),
+            transitions: Default::default(),
+            current: initial,
+            started: false,
+        }
+    }
+
+    pub fn add_state(&mut self, key: S, state: State<S, C>) {
+        self.states.insert(key, state);
+    }
+
+    pub fn add_transition<G>(&mut self, from: S, to: S, guard: G)
+    where
+        G: Fn(&C) -> bool + 'static,
+    {
+        self.transitions.push(Transition {
+            from,
+            to,
+            guard: Box::new(guard),
+        });
+    }
+
+    /// the innermost active state
+    pub fn current(&self) -> S {
+        self.current
+    }
+
+    /// true if the state or one of its descendants is active
+    pub fn is_in(&self, state: S) -> bool {
+        self.path(self.current).contains(&state)
+    }
+
+    /// the state followed by its ancestors
+    fn path(&self, state: S) -> Vec<S> {
+        let mut path = vec![state];
+        let mut current = state;
+        while let Some(parent) = self.states.get(&current).and_then(|s| s.parent) {
+            if path.contains(&parent) {
+                break; // cycle
+            }
+            path.push(parent);
+            current = parent;
+        }
+        path
+    }
+
+    fn call(
+        &mut self,
+        state: S,
+        ctx: &mut C,
+        select: fn(&mut State<S, C>) -> &mut Option<Callback<C>>,
+    ) {
+        if let Some(callback) = self.states.get_mut(&state).and_then(|s| select(s).as_mut()) {
+            callback(ctx);
+        }
+    }
+
+    /// leave the current state for another, calling exit and enter on the
+    /// states that aren't shared between the two
+    pub fn transition_to(&mut self, to: S, ctx: &mut C) {
+        if !self.started {
+            self.current = to;
+            return;
+        }
+        let from_path = self.path(self.current);
+        let to_path = self.path(to);
+        // self transitions exit and re-enter the state
+        let shared = |s: &S| from_path.contains(s) && to_path.contains(s) && *s != to;
+        for &state in from_path.iter().take_while(|s| !shared(s)) {
+            self.call(state, ctx, |s| &mut s.on_exit);
+        }
+        let entering: Vec<S> = to_path.iter().copied().take_while(|s| !shared(s)).collect();
+        for &state in entering.iter().rev() {
+            self.call(state, ctx, |s| &mut s.on_enter);
+        }
+        self.current = to;
+    }
+
+    pub fn update(&mut self, ctx: &mut C) {
+        if !self.started {
+            self.started = true;
+            for state in self.path(self.current).into_iter().rev() {
+                self.call(state, ctx, |s| &mut s.on_enter);
+            }
+        }
+
+        let path = self.path(self.current);
+        let taken = path.iter().find_map(|state| {
+            self.transitions
+                .iter()
+                .find(|t| t.from == *state && (t.guard)(ctx))
+                .map(|t| t.to)
+        });
+        if let Some(to) = taken {
+            self.transition_to(to, ctx);
+        }
+
+        for state in self.path(self.current).into_iter().rev() {
+            self.call(state, ctx, |s| &mut s.on_update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Key {
+        Grounded,
+        Idle,
+        Walk,
+        Airborne,
+    }
+
+    #[derive(Default)]
+    struct Ctx {
+        speed: f32,
+        on_ground: bool,
+        log: Vec<&'static str>,
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let mut fsm: StateMachine<Key, Ctx> = StateMachine::new(Key::Idle);
+        fsm.add_state(
+            Key::Grounded,
+            State::new()
+                .on_enter(|c: &mut Ctx| c.log.push("enter grounded"))
+                .on_exit(|c: &mut Ctx| c.log.push("exit grounded")),
+        );
+        fsm.add_state(
+            Key::Idle,
+            State::new()
+                .parent(Key::Grounded)
+                .on_enter(|c: &mut Ctx| c.log.push("enter idle"))
+                .on_exit(|c: &mut Ctx| c.log.push("exit idle")),
+        );
+        fsm.add_state(
+            Key::Walk,
+            State::new()
+                .parent(Key::Grounded)
+                .on_enter(|c: &mut Ctx| c.log.push("enter walk")),
+        );
+        fsm.add_state(
+            Key::Airborne,
+            State::new().on_enter(|c: &mut Ctx| c.log.push("enter airborne")),
+        );
+        fsm.add_transition(Key::Idle, Key::Walk, |c| c.speed > 0.);
+        fsm.add_transition(Key::Grounded, Key::Airborne, |c| !c.on_ground);
+
+        let mut ctx = Ctx {
+            on_ground: true,
+            ..Default::default()
+        };
+        fsm.update(&mut ctx);
+        assert_eq!(ctx.log, ["enter grounded", "enter idle"]);
+
+        ctx.log.clear();
+        ctx.speed = 1.;
+        fsm.update(&mut ctx);
+        assert_eq!(ctx.log, ["exit idle", "enter walk"]);
+        assert!(fsm.is_in(Key::Grounded));
+
+        ctx.log.clear();
+        ctx.on_ground = false;
+        fsm.update(&mut ctx);
+        assert_eq!(ctx.log, ["exit grounded", "enter airborne"]);
+        assert_eq!(fsm.current(), Key::Airborne);
+    }
+}
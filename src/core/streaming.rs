@@ -0,0 +1,258 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use sdl2::rect::{FRect, Rect};
+
+use super::{
+    camera::Camera2D,
+    decode::{read_surface, rgba_pixels, DecodedImage},
+    image_ops::crop,
+    renderer::DrawParams,
+    system::ChimericSystem,
+    vfs::Vfs,
+};
+
+/// well within any gpu's max texture size
+pub const DEFAULT_CHUNK_SIZE: u32 = 1024;
+
+/// an image too large for one texture, e.g. a painted background. it's split
+/// into chunks which are kept in memory, and each is cached as a texture only
+/// while it's near the camera
+#[derive(Debug, Clone)]
+pub struct StreamedImage {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    chunk_size: u32,
+    columns: u32,
+    /// row major
+    chunks: Vec<DecodedImage>,
+    /// indices of the chunks uploaded to the window
+    resident: HashSet<usize>,
+    /// the world area the image covers. its size in pixels at the origin by
+    /// default
+    pub dst: FRect,
+    /// how far past the view, in world units, chunks are kept resident, so
+    /// they're uploaded before they scroll into view
+    pub margin: f32,
+}
+
+impl StreamedImage {
+    /// chunks are cached under the path, which needn't be a real file
+    pub fn new(path: &Path, image: &DecodedImage, chunk_size: u32) -> Self {
+        let chunk_size = chunk_size.max(1);
+        let mut streamed = Self {
+            path: path.to_owned(),
+            width: image.width,
+            height: image.height,
+            chunk_size,
+            columns: image.width.div_ceil(chunk_size),
+            chunks: Default::default(),
+            resident: Default::default(),
+            dst: FRect::new(0., 0., image.width as f32, image.height as f32),
+            margin: chunk_size as f32 / 2.,
+        };
+        let rows = image.height.div_ceil(chunk_size);
+        streamed.chunks = (0..(streamed.columns * rows) as usize)
+            .map(|index| crop(image, streamed.chunk_rect(index)))
+            .collect();
+        streamed
+    }
+
+    /// read and split the image, through the vfs if its path is mounted
+    pub fn load(assets: &Vfs, path: &Path, chunk_size: u32) -> Result<Self, String> {
+        let image = read_surface(assets, path).and_then(rgba_pixels)?;
+        Ok(Self::new(path, &image, chunk_size))
+    }
+
+    /// in pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// where the chunk is cached, e.g. "background.png#2,3" for the third
+    /// column and fourth row
+    pub fn chunk_path(&self, index: usize) -> PathBuf {
+        let (column, row) = (index as u32 % self.columns, index as u32 / self.columns);
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!("#{column},{row}"));
+        path.into()
+    }
+
+    /// the part of the image the chunk covers, in pixels. chunks on the right
+    /// and bottom edges may be smaller
+    pub fn chunk_rect(&self, index: usize) -> Rect {
+        let (column, row) = (index as u32 % self.columns, index as u32 / self.columns);
+        let (x, y) = (column * self.chunk_size, row * self.chunk_size);
+        Rect::new(
+            x as i32,
+            y as i32,
+            self.chunk_size.min(self.width - x),
+            self.chunk_size.min(self.height - y),
+        )
+    }
+
+    /// the world area the chunk covers
+    pub fn chunk_world_rect(&self, index: usize) -> FRect {
+        let rect = self.chunk_rect(index);
+        let (scale_x, scale_y) = (
+            self.dst.width() / self.width as f32,
+            self.dst.height() / self.height as f32,
+        );
+        FRect::new(
+            self.dst.x() + rect.x() as f32 * scale_x,
+            self.dst.y() + rect.y() as f32 * scale_y,
+            rect.width() as f32 * scale_x,
+            rect.height() as f32 * scale_y,
+        )
+    }
+
+    /// indices of the chunks which overlap the world region
+    pub fn chunks_in(&self, region: FRect) -> Vec<usize> {
+        if self.chunks.is_empty() || self.dst.width() <= 0. || self.dst.height() <= 0. {
+            return Vec::new();
+        }
+        // from world units to pixels
+        let to_pixel = |world: f32, origin: f32, extent: f32, pixels: u32| {
+            (world - origin) / extent * pixels as f32
+        };
+        let chunk_size = self.chunk_size as f32;
+        let range = |start: f32, end: f32, pixels: u32| {
+            let (start, end) = (start.max(0.), end.min(pixels as f32));
+            if start >= end {
+                return 0..0;
+            }
+            (start / chunk_size).floor() as u32..(end / chunk_size).ceil() as u32
+        };
+        let columns = range(
+            to_pixel(region.left(), self.dst.x(), self.dst.width(), self.width),
+            to_pixel(region.right(), self.dst.x(), self.dst.width(), self.width),
+            self.width,
+        );
+        let rows = range(
+            to_pixel(region.top(), self.dst.y(), self.dst.height(), self.height),
+            to_pixel(
+                region.bottom(),
+                self.dst.y(),
+                self.dst.height(),
+                self.height,
+            ),
+            self.height,
+        );
+        rows.flat_map(|row| {
+            columns
+                .clone()
+                .map(move |column| (row * self.columns + column) as usize)
+        })
+        .collect()
+    }
+
+    /// upload the chunks near the camera which aren't cached, and drop the
+    /// ones which aren't near it anymore
+    pub fn update(
+        &mut self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        camera: &Camera2D,
+    ) -> Result<(), String> {
+        let view = camera.visible_region();
+        let near: HashSet<usize> = self
+            .chunks_in(FRect::new(
+                view.x() - self.margin,
+                view.y() - self.margin,
+                view.width() + self.margin * 2.,
+                view.height() + self.margin * 2.,
+            ))
+            .into_iter()
+            .collect();
+        for index in self.resident.difference(&near) {
+            system.evict_texture(window_name, &self.chunk_path(*index))?;
+        }
+        for index in near.difference(&self.resident) {
+            system.insert_image(window_name, &self.chunk_path(*index), &self.chunks[*index])?;
+        }
+        self.resident = near;
+        Ok(())
+    }
+
+    /// draw the chunks in view, uploading them as needed
+    pub fn draw(
+        &mut self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        camera: &Camera2D,
+    ) -> Result<(), String> {
+        self.update(system, window_name, camera)?;
+        for index in self.chunks_in(camera.visible_region()) {
+            let rect = self.chunk_world_rect(index);
+            let (w, h) = (rect.width() * camera.zoom, rect.height() * camera.zoom);
+            let center = camera.world_to_screen(rect.center());
+            let mut draw = DrawParams::new(
+                None,
+                Some(FRect::new(center.x() - w / 2., center.y() - h / 2., w, h)),
+            );
+            draw.angle = -camera.rotation as f64;
+            let path = self.chunk_path(index);
+            if system.draw(window_name, &path, &[draw]).is_err() {
+                // evicted from the texture cache
+                system.insert_image(window_name, &path, &self.chunks[index])?;
+                system.draw(window_name, &path, &[draw])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// drop every chunk from the window's cache
+    pub fn unload(&mut self, system: &mut ChimericSystem, window_name: &str) -> Result<(), String> {
+        for index in std::mem::take(&mut self.resident) {
+            system.evict_texture(window_name, &self.chunk_path(index))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        // 5 by 3, each pixel's red is its index
+        let image = DecodedImage {
+            width: 5,
+            height: 3,
+            rgba: (0..15u8).flat_map(|i| [i, 0, 0, 255]).collect(),
+        };
+        let mut streamed = StreamedImage::new(Path::new("bg.png"), &image, 2);
+        assert_eq!(streamed.chunk_count(), 6);
+        assert_eq!(streamed.chunk_path(4), Path::new("bg.png#1,1"));
+        assert_eq!(streamed.chunk_rect(5), Rect::new(4, 2, 1, 1));
+        let red =
+            |chunk: &DecodedImage| -> Vec<u8> { chunk.rgba.iter().step_by(4).copied().collect() };
+        assert_eq!(red(&streamed.chunks[0]), [0, 1, 5, 6]);
+        assert_eq!(red(&streamed.chunks[2]), [4, 9]);
+        assert_eq!(red(&streamed.chunks[4]), [12, 13]);
+
+        assert_eq!(streamed.chunks_in(FRect::new(1., 1., 2., 0.5)), [0, 1]);
+        assert_eq!(
+            streamed.chunks_in(FRect::new(-10., -10., 100., 100.)),
+            [0, 1, 2, 3, 4, 5]
+        );
+        assert!(streamed.chunks_in(FRect::new(5., 0., 10., 10.)).is_empty());
+        assert!(streamed
+            .chunks_in(FRect::new(-10., 0., 10., 10.))
+            .is_empty());
+
+        // stretched over twice the size, away from the origin
+        streamed.dst = FRect::new(100., 100., 10., 6.);
+        assert_eq!(streamed.chunk_world_rect(5), FRect::new(108., 104., 2., 2.));
+        assert_eq!(streamed.chunks_in(FRect::new(109., 105., 1., 1.)), [5]);
+    }
+}
@@ -1,55 +1,153 @@
-use std::{collections::HashMap, ffi::CStr, num::NonZeroUsize, path::Path};
+#[cfg(feature = "ttf")]
+use std::ffi::CStr;
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
+#[cfg(feature = "image")]
+use sdl2::image::Sdl2ImageContext;
+#[cfg(feature = "mixer")]
+use sdl2::mixer::Sdl2MixerContext;
+#[cfg(feature = "ttf")]
+use sdl2::ttf::Sdl2TtfContext;
 use sdl2::{
-    image::Sdl2ImageContext,
-    mixer::Sdl2MixerContext,
+    pixels::Color,
     rect::{FPoint, FRect, Point, Rect},
     render::{Canvas, Texture},
-    ttf::Sdl2TtfContext,
+    surface::Surface,
     video::Window,
-    AudioSubsystem, Sdl, VideoSubsystem,
+    AudioSubsystem, GameControllerSubsystem, Sdl, VideoSubsystem,
 };
 
+#[cfg(feature = "ttf")]
+use super::{font_system::font_system::FontSystem, text_path::TextPath};
+#[cfg(feature = "mixer")]
+use super::audio_system::AudioSystem;
 use super::{
-    font_system::font_system::FontSystem,
+    camera::window_to_logical_unbounded,
+    crash,
+    decode::{
+        read_surface, rgba_pixels, save_surface, DecodeKind, DecodeQueue, Decoded, DecodedImage,
+        Source,
+    },
+    image_ops::{apply_all, derived_path, ImageOp},
+    mipmap::{variants, MipLevel},
+    post_process::{EffectStack, Palette},
+    profiler,
     render_system::{CanvasAndCreator, RenderSystem},
+    render_system_txt_key::FileOrRenderedTextKey,
+    renderer::{point_to_f, rect_to_f, DrawParams, Renderer, Vertex},
+    save::write_atomic,
+    texture_cache::CacheClass,
+    trace::{log_debug, log_warn},
+    vfs::Vfs,
+    warm_start::{decode_warm_start, encode_warm_start},
 };
 
-// use super::{audio_system::AudioSystem, render_system::{CanvasAndCreator, RenderSystem}};
+/// drop text rendered with the font from every window's cache
+#[cfg(feature = "ttf")]
+fn evict_font_text<'sdl>(windows: &mut Ordered<Box<dyn Renderer<'sdl> + 'sdl>>, font_file: &Path) {
+    let evicted: usize = windows
+        .values_mut()
+        .map(|window| window.evict_font_text(font_file))
+        .sum();
+    log_debug!("evicted {evicted} cached text for {}", font_file.display());
+}
+
+/// what System::with_audio_fallback does if audio can't be opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFallback {
+    /// fail to create the System
+    #[default]
+    Fail,
+    /// continue without audio. sounds are silently skipped
+    Silent,
+}
 
 /// core sdl2 system needed for the engine
 pub struct System {
+    #[cfg(feature = "image")]
     pub image: Sdl2ImageContext,
-    pub mixer: Sdl2MixerContext,
+    /// None if audio couldn't be opened
+    #[cfg(feature = "mixer")]
+    pub mixer: Option<Sdl2MixerContext>,
+    #[cfg(feature = "ttf")]
     pub ttf: Sdl2TtfContext,
     // dropped in member order stated
     pub video: VideoSubsystem,
-    pub audio: AudioSubsystem,
+    /// None if audio couldn't be opened
+    pub audio: Option<AudioSubsystem>,
+    pub game_controller: GameControllerSubsystem,
     // dropped last
     pub sdl: Sdl,
 }
 
+/// the audio subsystem and, with the mixer feature, the opened mixer
+#[cfg(feature = "mixer")]
+fn open_audio(sdl: &Sdl) -> Result<(AudioSubsystem, Sdl2MixerContext), String> {
+    let audio = sdl.audio()?;
+    sdl2::mixer::open_audio(
+        44_100,
+        sdl2::mixer::AUDIO_S16LSB,
+        sdl2::mixer::DEFAULT_CHANNELS,
+        1_024,
+    )?;
+    sdl2::mixer::allocate_channels(8);
+    // empty flags - the format's dynamic lib is loaded on first use
+    let mixer = match sdl2::mixer::init(sdl2::mixer::InitFlag::empty()) {
+        Ok(mixer) => mixer,
+        Err(e) => {
+            sdl2::mixer::close_audio();
+            return Err(e);
+        }
+    };
+    Ok((audio, mixer))
+}
+
 impl System {
+    /// fails if audio can't be opened
     pub fn new() -> Result<Self, String> {
+        Self::with_audio_fallback(AudioFallback::Fail)
+    }
+
+    /// e.g. AudioFallback::Silent for containers and ci, which often don't
+    /// have an audio device
+    pub fn with_audio_fallback(fallback: AudioFallback) -> Result<Self, String> {
         let sdl = sdl2::init()?;
         let video = sdl.video()?;
-        let audio = sdl.audio()?;
-        sdl2::mixer::open_audio(
-            44_100,
-            sdl2::mixer::AUDIO_S16LSB,
-            sdl2::mixer::DEFAULT_CHANNELS,
-            1_024,
-        )?;
-        sdl2::mixer::allocate_channels(8);
+        let game_controller = sdl.game_controller()?;
+        #[cfg(feature = "mixer")]
+        let opened = open_audio(&sdl);
+        #[cfg(not(feature = "mixer"))]
+        let opened = sdl.audio();
+        let opened = match (opened, fallback) {
+            (Ok(opened), _) => Some(opened),
+            (Err(e), AudioFallback::Fail) => return Err(e),
+            (Err(e), AudioFallback::Silent) => {
+                log_warn!("audio is unavailable; continuing without it: {e}");
+                None
+            }
+        };
+        #[cfg(feature = "mixer")]
+        let (audio, mixer) = opened.unzip();
+        #[cfg(not(feature = "mixer"))]
+        let audio = opened;
 
         Ok(System {
             sdl,
             video,
             audio,
+            game_controller,
             // empty flags - don't load any dynamic libs up front. they will be
             // loaded as needed the first time the respective file format is loaded
+            #[cfg(feature = "image")]
             image: sdl2::image::init(sdl2::image::InitFlag::empty())?,
-            mixer: sdl2::mixer::init(sdl2::mixer::InitFlag::empty())?,
+            #[cfg(feature = "mixer")]
+            mixer,
+            #[cfg(feature = "ttf")]
             ttf: sdl2::ttf::init().map_err(|e| e.to_string())?,
         })
     }
@@ -57,9 +155,14 @@ impl System {
 
 #[derive(Debug, Clone, Copy)]
 pub struct ChimericSystemSettings {
+    #[cfg(feature = "ttf")]
     pub num_point_sizes_per_font: NonZeroUsize,
+    #[cfg(feature = "ttf")]
     pub num_fonts: NonZeroUsize,
+    /// textures from files cached per window
     pub num_textures_per_window: NonZeroUsize,
+    /// rendered text cached per window, separately from textures
+    pub num_text_per_window: NonZeroUsize,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -94,12 +197,100 @@ pub struct CopyStructExF {
     pub flip_vertical: bool,
 }
 
-/// abstraction above sdl systems (memory management, etc)
+/// values by name, in the order they were added unless reordered. there are
+/// only ever a few windows, so lookups are a linear search
+struct Ordered<V> {
+    entries: Vec<(String, V)>,
+}
+
+impl<V> Default for Ordered<V> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<V> Ordered<V> {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|(n, _)| n == name)
+    }
+
+    fn get(&self, name: &str) -> Option<&V> {
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut V> {
+        self.entries.iter_mut().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// false if the name is taken
+    fn insert_new(&mut self, name: &str, value: V) -> bool {
+        if self.contains_key(name) {
+            return false;
+        }
+        self.entries.push((name.to_owned(), value));
+        true
+    }
+
+    fn remove(&mut self, name: &str) -> Option<V> {
+        self.position(name).map(|i| self.entries.remove(i).1)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.entries.iter().map(|(n, v)| (n, v))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut V)> {
+        self.entries.iter_mut().map(|(n, v)| (&*n, v))
+    }
+
+    fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+
+    /// the named entries first, in the given order. the rest keep their order
+    /// after them
+    fn set_order(&mut self, names: &[&str]) -> Result<(), String> {
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return Err(format!("\"{name}\" is listed more than once"));
+            }
+            if !self.contains_key(name) {
+                return Err(format!("window \"{name}\" does not exist"));
+            }
+        }
+        for (i, name) in names.iter().enumerate() {
+            if let Some(position) = self.position(name) {
+                let entry = self.entries.remove(position);
+                self.entries.insert(i, entry);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// abstraction above sdl systems (memory management, etc). it holds sdl
+/// objects, so it's used only from the thread that made them. decoding can be
+/// moved off of that thread with the preload functions
 pub struct ChimericSystem<'sdl> {
     settings: ChimericSystemSettings,
+    #[cfg(feature = "ttf")]
     font_system: FontSystem<'sdl>,
-    windows: HashMap<String, RenderSystem<'sdl>>,
-    // pub sounds: AudioSystem<'sdl>,
+    /// presented in this order
+    windows: Ordered<Box<dyn Renderer<'sdl> + 'sdl>>,
+    /// textures and fonts are loaded through this
+    pub assets: Vfs,
+    /// started on the first preload
+    decode: Option<DecodeQueue>,
+    /// by name, for draw_recolored
+    palettes: HashMap<String, Palette>,
+    #[cfg(feature = "mixer")]
+    pub sounds: AudioSystem<'sdl>,
     _system: &'sdl System,
 }
 
@@ -107,6 +298,7 @@ impl<'sdl> ChimericSystem<'sdl> {
     pub fn new(system: &'sdl System, settings: ChimericSystemSettings) -> Self {
         Self {
             settings,
+            #[cfg(feature = "ttf")]
             font_system: FontSystem::new(
                 &system.ttf,
                 settings.num_point_sizes_per_font,
@@ -114,24 +306,66 @@ impl<'sdl> ChimericSystem<'sdl> {
             ),
             _system: system,
             windows: Default::default(),
-            // sounds: AudioSystem::new(&system.audio),
+            assets: Default::default(),
+            decode: None,
+            palettes: Default::default(),
+            #[cfg(feature = "mixer")]
+            sounds: AudioSystem::new(system),
         }
     }
 
-    /// add a window to the app with a string key
+    /// add a window to the app with a string key. it's drawn with the sdl
+    /// renderer
     pub fn add_window(&mut self, window_name: &str, window: Window) -> Result<(), String> {
         let cc = CanvasAndCreator::new(window)?;
-        let sys = RenderSystem::new(cc, self.settings.num_textures_per_window);
-        let entry = self.windows.entry(window_name.into());
-        match entry {
-            std::collections::hash_map::Entry::Occupied(_occupied_entry) => Err(format!(
+        let sys = RenderSystem::new(
+            cc,
+            self.settings.num_textures_per_window,
+            self.settings.num_text_per_window,
+        );
+        self.add_renderer(window_name, Box::new(sys))
+    }
+
+    /// add a window drawn by another backend (e.g. WgpuRenderer). functions
+    /// which give out the sdl canvas or textures fail for it
+    pub fn add_renderer(
+        &mut self,
+        window_name: &str,
+        renderer: Box<dyn Renderer<'sdl> + 'sdl>,
+    ) -> Result<(), String> {
+        if !self.windows.insert_new(window_name, renderer) {
+            return Err(format!(
                 "window \"{window_name}\" can't be created because it already exists"
-            )),
-            std::collections::hash_map::Entry::Vacant(vacant_entry) => {
-                vacant_entry.insert(sys);
-                Ok(())
-            }
+            ));
         }
+        Ok(())
+    }
+
+    /// in the order they're presented: the order they were added, unless
+    /// changed with set_window_order
+    pub fn window_names(&self) -> Vec<&str> {
+        self.windows.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// present the named windows first, in this order, e.g. so the window
+    /// showing latency critical content is shown soonest. windows which
+    /// aren't named keep their order after them
+    pub fn set_window_order(&mut self, window_names: &[&str]) -> Result<(), String> {
+        self.windows.set_order(window_names)
+    }
+
+    /// the window's backend
+    pub fn renderer(&mut self, window_name: &str) -> Result<&mut (dyn Renderer<'sdl> + 'sdl), String> {
+        match self.windows.get_mut(window_name) {
+            None => Err(format!("window \"{window_name}\" does not exist")),
+            Some(window) => Ok(window.as_mut()),
+        }
+    }
+
+    fn sdl_window(&mut self, window_name: &str) -> Result<&mut RenderSystem<'sdl>, String> {
+        self.renderer(window_name)?
+            .as_sdl()
+            .ok_or_else(|| format!("window \"{window_name}\" doesn't use the sdl renderer"))
     }
 
     /// remove a window from the app by string key
@@ -144,8 +378,530 @@ impl<'sdl> ChimericSystem<'sdl> {
         }
     }
 
-    pub fn present(&mut self) {
-        self.windows.iter_mut().for_each(|v| v.1.present());
+    /// the name the window was added with, from its sdl window id
+    pub fn window_name(&self, window_id: u32) -> Option<&str> {
+        self.windows
+            .iter()
+            .find(|(_, window)| window.window_id() == window_id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// map a position normalized to [0, 1] across the window (e.g. from a
+    /// touch) to the window's logical coordinates. positions in letterbox bars
+    /// fall outside the logical area rather than being rejected
+    pub fn normalized_to_logical(&self, window_name: &str, normalized: FPoint) -> Option<FPoint> {
+        let window = self.windows.get(window_name)?;
+        let output_size = window.output_size().ok()?;
+        let pixel = FPoint::new(
+            normalized.x() * output_size.0 as f32,
+            normalized.y() * output_size.1 as f32,
+        );
+        Some(window_to_logical_unbounded(
+            pixel,
+            output_size,
+            window.logical_size(),
+        ))
+    }
+
+    /// draw the image at the path to the window, loading it if it's not cached
+    pub fn draw(&mut self, window_name: &str, path: &Path, draws: &[DrawParams]) -> Result<(), String> {
+        crash::record_draw(|| format!("{window_name}: draw {} x{}", path.display(), draws.len()));
+        let assets = &self.assets;
+        match self.windows.get_mut(window_name) {
+            None => Err(format!(
+                "can't draw texture; window \"{window_name}\" does not exist"
+            )),
+            Some(window) => window.draw_texture(assets, path, draws),
+        }
+    }
+
+    /// read and decode the image at the path (through the vfs if it's
+    /// mounted), e.g. for changing it on the cpu. see derive_texture
+    pub fn load_image(&self, path: &Path) -> Result<DecodedImage, String> {
+        read_surface(&self.assets, path).and_then(rgba_pixels)
+    }
+
+    /// cache the image at the path with the ops applied in order, unless it
+    /// already is. returns the path to draw it by, which names the ops
+    pub fn derive_texture(
+        &mut self,
+        window_name: &str,
+        path: &Path,
+        ops: &[ImageOp],
+    ) -> Result<PathBuf, String> {
+        let derived = derived_path(path, ops);
+        if !self.renderer(window_name)?.is_cached(&derived) {
+            let image = apply_all(&self.load_image(path)?, ops)?;
+            self.insert_image(window_name, &derived, &image)?;
+        }
+        Ok(derived)
+    }
+
+    /// cache the image at the path along with its half and quarter size
+    /// variants, for draw_mipmapped
+    pub fn load_mipmaps(&mut self, window_name: &str, path: &Path) -> Result<(), String> {
+        let image = self.load_image(path)?;
+        let window = self.renderer(window_name)?;
+        for (level, image) in variants(image) {
+            window.insert_image(&level.path(path), &image)?;
+        }
+        Ok(())
+    }
+
+    /// draw the downscaled variant of the image which suits the zoom (screen
+    /// pixels per image pixel, e.g. the camera's), so zoomed out sprites don't
+    /// shimmer. source rects are on the full image. the variants are made with
+    /// load_mipmaps, again if they were evicted
+    pub fn draw_mipmapped(
+        &mut self,
+        window_name: &str,
+        path: &Path,
+        zoom: f32,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let level = MipLevel::for_zoom(zoom);
+        if level == MipLevel::Full {
+            return self.draw(window_name, path, draws);
+        }
+        let draws: Vec<DrawParams> = draws
+            .iter()
+            .map(|draw| DrawParams {
+                src: draw.src.map(|src| level.src(src)),
+                ..*draw
+            })
+            .collect();
+        let variant = level.path(path);
+        if !self.renderer(window_name)?.is_cached(&variant) {
+            self.load_mipmaps(window_name, path)?;
+        }
+        self.draw(window_name, &variant, &draws)
+    }
+
+    /// a triangle for each three indices into the vertices, blended with the
+    /// colors' alpha
+    pub fn draw_triangles(&mut self, window_name: &str, vertices: &[Vertex], indices: &[u32]) -> Result<(), String> {
+        crash::record_draw(|| format!("{window_name}: {} triangles", indices.len() / 3));
+        self.renderer(window_name)?.draw_triangles(vertices, indices)
+    }
+
+    /// register a palette for draw_recolored. recolored textures already
+    /// cached keep their colors; use a new name for different colors
+    pub fn add_palette(&mut self, palette_name: &str, palette: Palette) {
+        self.palettes.insert(palette_name.to_owned(), palette);
+    }
+
+    /// draw the texture with its colors swapped through the named palette,
+    /// e.g. for character recolors without duplicate asset files
+    pub fn draw_recolored(
+        &mut self,
+        window_name: &str,
+        path: &Path,
+        palette_name: &str,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let palette = self
+            .palettes
+            .get(palette_name)
+            .ok_or_else(|| format!("palette \"{palette_name}\" does not exist"))?;
+        crash::record_draw(|| {
+            format!("{window_name}: draw {} recolored by {palette_name} x{}", path.display(), draws.len())
+        });
+        match self.windows.get_mut(window_name) {
+            None => Err(format!(
+                "can't draw texture; window \"{window_name}\" does not exist"
+            )),
+            Some(window) => window.draw_texture_recolored(&self.assets, path, palette_name, palette, draws),
+        }
+    }
+
+    /// draw the texture with pixels of the color key transparent, e.g.
+    /// magenta in legacy bmp assets. each color key is cached separately
+    pub fn draw_color_keyed(
+        &mut self,
+        window_name: &str,
+        path: &Path,
+        color_key: Color,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        crash::record_draw(|| format!("{window_name}: draw {} color keyed x{}", path.display(), draws.len()));
+        match self.windows.get_mut(window_name) {
+            None => Err(format!(
+                "can't draw texture; window \"{window_name}\" does not exist"
+            )),
+            Some(window) => window.draw_texture_color_keyed(&self.assets, path, color_key, draws),
+        }
+    }
+
+    /// draw the rendered text to the window, rendering it and loading the font
+    /// as needed
+    #[cfg(feature = "ttf")]
+    pub fn draw_text(
+        &mut self,
+        window_name: &str,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        wrap_width: Option<u32>,
+        draw: &DrawParams,
+    ) -> Result<(), String> {
+        crash::record_draw(|| format!("{window_name}: text {text:?} in {} at {point_size}pt", font_file.display()));
+        match self.windows.get_mut(window_name) {
+            None => Err(format!(
+                "can't draw text; window \"{window_name}\" does not exist"
+            )),
+            Some(window) => window.draw_text(
+                &mut self.font_system,
+                &self.assets,
+                font_file,
+                point_size,
+                text,
+                wrap_width,
+                draw,
+            ),
+        }
+    }
+
+    /// draw the text along the path (in logical coordinates), rendering it and
+    /// loading the font as needed
+    #[cfg(feature = "ttf")]
+    pub fn draw_text_on_path(
+        &mut self,
+        window_name: &str,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        path: &TextPath,
+    ) -> Result<(), String> {
+        match self.windows.get_mut(window_name) {
+            None => Err(format!(
+                "can't draw text; window \"{window_name}\" does not exist"
+            )),
+            Some(window) => window.draw_text_on_path(
+                &mut self.font_system,
+                &self.assets,
+                font_file,
+                point_size,
+                text,
+                path,
+            ),
+        }
+    }
+
+    /// blended with the color's alpha
+    pub fn fill_rect(&mut self, window_name: &str, rect: FRect, color: Color) -> Result<(), String> {
+        crash::record_draw(|| format!("{window_name}: fill {rect:?} with {color:?}"));
+        self.renderer(window_name)?.fill_rect(rect, color)
+    }
+
+    /// the size of the image at the path, loading it for the window if needed
+    pub fn texture_size(&mut self, window_name: &str, path: &Path) -> Result<(u32, u32), String> {
+        let assets = &self.assets;
+        match self.windows.get_mut(window_name) {
+            None => Err(format!(
+                "can't get texture size; window \"{window_name}\" does not exist"
+            )),
+            Some(window) => window.texture_size(assets, path),
+        }
+    }
+
+    /// close the font and drop text rendered with it from every window's
+    /// cache, so it's read and rendered again on next use. for when the font
+    /// file changed
+    #[cfg(feature = "ttf")]
+    pub fn reload_font(&mut self, font_file: &Path) {
+        self.font_system.evict(font_file);
+        evict_font_text(&mut self.windows, font_file);
+    }
+
+    /// the width and height of single line text, without rendering it
+    #[cfg(feature = "ttf")]
+    pub fn text_size(&mut self, font_file: &Path, point_size: u16, text: &CStr) -> Result<(u32, u32), String> {
+        self.font_system.size_of(&self.assets, font_file, point_size, text)
+    }
+
+    /// how many textures of the class the window caches. see
+    /// ChimericSystemSettings for the initial capacities
+    pub fn set_cache_capacity(
+        &mut self,
+        window_name: &str,
+        class: CacheClass,
+        capacity: NonZeroUsize,
+    ) -> Result<(), String> {
+        self.renderer(window_name)?.set_cache_capacity(class, capacity);
+        Ok(())
+    }
+
+    /// drop the image at the path from the window's cache. false if it
+    /// wasn't cached
+    pub fn evict_texture(&mut self, window_name: &str, path: &Path) -> Result<bool, String> {
+        Ok(self.renderer(window_name)?.evict_texture(path))
+    }
+
+    /// keep the image cached in the window until it's next presented, so it
+    /// can't be evicted between e.g. measuring it with texture_size and
+    /// drawing it
+    pub fn lock_texture(&mut self, window_name: &str, path: &Path) -> Result<(), String> {
+        self.renderer(window_name)?
+            .lock_texture(FileOrRenderedTextKey::from_path(path));
+        Ok(())
+    }
+
+    /// keep the rendered text cached in the window until it's next presented.
+    /// see lock_texture
+    #[cfg(feature = "ttf")]
+    pub fn lock_text(
+        &mut self,
+        window_name: &str,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        wrap_width: Option<u32>,
+    ) -> Result<(), String> {
+        let key = match wrap_width {
+            Some(wrap_width) => FileOrRenderedTextKey::from_rendered_wrapped_text(
+                text, font_file, point_size, wrap_width,
+            ),
+            None => FileOrRenderedTextKey::from_rendered_text(text, font_file, point_size),
+        };
+        self.renderer(window_name)?.lock_texture(key);
+        Ok(())
+    }
+
+    /// restrict the window's following draws to the rect, in logical
+    /// coordinates. reset each frame
+    pub fn set_clip(&mut self, window_name: &str, clip: Option<Rect>) -> Result<(), String> {
+        self.renderer(window_name)?.set_clip(clip)
+    }
+
+    /// cache the image for the window as if it was loaded from the path,
+    /// replacing what's there. for images made at runtime
+    pub fn insert_image(&mut self, window_name: &str, path: &Path, image: &DecodedImage) -> Result<(), String> {
+        self.renderer(window_name)?.insert_image(path, image)
+    }
+
+    /// decode the image on a worker thread. it's cached for the window once
+    /// finish_loads gets to it
+    pub fn preload_texture(&mut self, window_name: &str, path: &Path) -> Result<(), String> {
+        if !self.windows.contains_key(window_name) {
+            return Err(format!(
+                "can't preload texture; window \"{window_name}\" does not exist"
+            ));
+        }
+        let kind = DecodeKind::Image {
+            window_name: window_name.to_owned(),
+        };
+        self.submit_decode(path, kind)
+    }
+
+    /// record which images each window has cached, e.g. at shutdown, for
+    /// warm_start on the next launch
+    pub fn save_warm_start(&self, file: &Path) -> Result<(), String> {
+        let contents: Vec<(String, Vec<PathBuf>)> = self
+            .windows
+            .iter()
+            .map(|(name, window)| (name.clone(), window.cached_paths()))
+            .collect();
+        write_atomic(file, &encode_warm_start(&contents))
+    }
+
+    /// preload the images recorded by save_warm_start on worker threads, for
+    /// the windows which exist. images which are already cached or no longer
+    /// around are skipped. returns how many were queued
+    pub fn warm_start(&mut self, file: &Path) -> Result<usize, String> {
+        let bytes = std::fs::read(file).map_err(|e| format!("{}: {e}", file.display()))?;
+        let mut queued = 0;
+        for (window_name, paths) in decode_warm_start(&bytes)? {
+            let Some(window) = self.windows.get(&window_name) else {
+                continue;
+            };
+            // least recently used first, so it's the first evicted again
+            let paths: Vec<PathBuf> = paths
+                .into_iter()
+                .rev()
+                .filter(|path| !window.is_cached(path))
+                .filter(|path| self.assets.is_mounted(path) || path.is_file())
+                .collect();
+            for path in paths {
+                self.preload_texture(&window_name, &path)?;
+                queued += 1;
+            }
+        }
+        log_debug!("warm start queued {queued} textures");
+        Ok(queued)
+    }
+
+    /// read the font file on a worker thread. it's used once finish_loads gets
+    /// to it
+    #[cfg(feature = "ttf")]
+    pub fn preload_font(&mut self, font_file: &Path) -> Result<(), String> {
+        self.submit_decode(font_file, DecodeKind::Font)
+    }
+
+    fn submit_decode(&mut self, path: &Path, kind: DecodeKind) -> Result<(), String> {
+        // the vfs stays on this thread; mounted files are read here
+        let source = if self.assets.is_mounted(path) {
+            Source::Bytes(self.assets.read(path)?)
+        } else {
+            Source::File(path.to_owned())
+        };
+        self.decode
+            .get_or_insert_with(DecodeQueue::with_available_parallelism)
+            .submit(path, kind, source)
+    }
+
+    /// preloads which haven't been finished yet
+    pub fn loads_pending(&self) -> usize {
+        self.decode.as_ref().map_or(0, |decode| decode.pending())
+    }
+
+    /// turn decoded preloads into textures and fonts until the time budget is
+    /// spent. call each frame while loading so the frame rate holds up
+    pub fn finish_loads(&mut self, budget: Duration) -> Result<(), String> {
+        let Some(decode) = self.decode.as_mut() else {
+            return Ok(());
+        };
+        let _zone = profiler::zone("finish loads");
+        let start = Instant::now();
+        while start.elapsed() < budget {
+            let Some(finished) = decode.poll() else {
+                break;
+            };
+            match (finished.kind, finished.result?) {
+                (DecodeKind::Image { window_name }, Decoded::Image(image)) => {
+                    match self.windows.get_mut(&window_name) {
+                        None => {
+                            return Err(format!(
+                                "can't finish loading {}; window \"{window_name}\" does not exist",
+                                finished.path.display()
+                            ))
+                        }
+                        Some(window) => window.insert_image(&finished.path, &image)?,
+                    }
+                }
+                #[cfg(feature = "ttf")]
+                (_, Decoded::Font(data)) => {
+                    // the new contents replace a font that's already open
+                    if self.font_system.evict(&finished.path) {
+                        evict_font_text(&mut self.windows, &finished.path);
+                    }
+                    self.font_system.insert_font_data(&finished.path, data)
+                }
+                // not submitted by the system
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// apply each window's effects and show the frame
+    pub fn present(&mut self) -> Result<(), String> {
+        let _zone = profiler::zone("present");
+        crash::set_context("engine settings", || format!("{:#?}", self.settings));
+        crash::set_context("caches", || self.cache_stats());
+        crash::end_frame();
+        self.windows.iter_mut().try_for_each(|v| v.1.present())
+    }
+
+    /// how many images each window has cached
+    pub fn cache_stats(&self) -> String {
+        self.windows
+            .iter()
+            .map(|(window_name, window)| {
+                format!("{window_name}: {} images cached", window.cached_paths().len())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// the window's logical size if set, otherwise its size in pixels
+    pub fn drawable_size(&self, window_name: &str) -> Result<(u32, u32), String> {
+        match self.windows.get(window_name) {
+            None => Err(format!("window \"{window_name}\" does not exist")),
+            Some(window) => window.drawable_size(),
+        }
+    }
+
+    /// draw egui's tessellated output on the window
+    #[cfg(feature = "egui")]
+    pub fn paint_egui(
+        &mut self,
+        window_name: &str,
+        delta: &egui::TexturesDelta,
+        primitives: &[egui::ClippedPrimitive],
+    ) -> Result<(), String> {
+        self.sdl_window(window_name)?.paint_egui(delta, primitives)
+    }
+
+    /// draw directly to the window, e.g. shapes. see Canvas for more details
+    pub fn canvas(&mut self, window_name: &str) -> Result<&mut Canvas<Window>, String> {
+        Ok(self.sdl_window(window_name)?.canvas())
+    }
+
+    /// the window's post processing effects, applied on present
+    pub fn effects(&mut self, window_name: &str) -> Result<&mut EffectStack, String> {
+        Ok(&mut self.sdl_window(window_name)?.effects)
+    }
+
+    /// only redraw and present what changed in the window. see
+    /// RenderSystem::set_dirty_tracking
+    pub fn set_dirty_tracking(&mut self, window_name: &str, enabled: bool) -> Result<(), String> {
+        self.sdl_window(window_name)?.set_dirty_tracking(enabled);
+        Ok(())
+    }
+
+    /// the area of the window (None for all of it) needs to be drawn again.
+    /// does nothing for windows not tracking dirty rects
+    pub fn mark_dirty(&mut self, window_name: &str, rect: Option<Rect>) -> Result<(), String> {
+        if let Some(window) = self.renderer(window_name)?.as_sdl() {
+            window.mark_dirty(rect);
+        }
+        Ok(())
+    }
+
+    /// false if every window tracks dirty rects and none are dirty; the frame
+    /// can be skipped
+    pub fn needs_redraw(&self) -> bool {
+        self.windows
+            .iter()
+            .map(|(_, window)| window)
+            .any(|window| window.as_sdl_ref().is_none_or(|window| window.needs_redraw()))
+    }
+
+    /// advance time based effects (e.g. shake) for every window
+    pub fn update_effects(&mut self, dt: Duration) {
+        self.windows
+            .values_mut()
+            .filter_map(|window| window.as_sdl())
+            .for_each(|window| window.effects.update(dt));
+    }
+
+    pub fn clipboard_text(&self) -> Result<String, String> {
+        self._system.video.clipboard().clipboard_text()
+    }
+
+    pub fn set_clipboard_text(&self, text: &str) -> Result<(), String> {
+        self._system.video.clipboard().set_clipboard_text(text)
+    }
+
+    /// copy what's currently drawn to the window. call before present
+    pub fn screenshot(&self, window_name: &str) -> Result<Surface<'static>, String> {
+        match self.windows.get(window_name) {
+            None => Err(format!(
+                "can't take screenshot; window \"{window_name}\" does not exist"
+            )),
+            Some(window) => window.screenshot(),
+        }
+    }
+
+    /// write a screenshot of the window to a png file (bmp without the image
+    /// feature)
+    pub fn save_screenshot(&self, window_name: &str, path: &Path) -> Result<(), String> {
+        save_surface(&self.screenshot(window_name)?, path)
+    }
+
+    /// fill every window with the color, starting the frame
+    pub fn clear(&mut self, color: Color) -> Result<(), String> {
+        self.windows.iter_mut().try_for_each(|v| v.1.clear(color))
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -161,8 +917,8 @@ impl<'sdl> ChimericSystem<'sdl> {
         R1: Into<Option<Rect>>,
         R2: Into<Option<Rect>>,
     {
-        let v = self.texture(window_name, path)?;
-        v.1.copy(v.0, src, dst)
+        let draw = DrawParams::new(src.into(), dst.into().map(rect_to_f));
+        self.draw(window_name, path, &[draw])
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -176,11 +932,10 @@ impl<'sdl> ChimericSystem<'sdl> {
     where
         I: Iterator<Item = CopyStruct>,
     {
-        let v = self.texture(window_name, path)?;
-        for copy in copys.into_iter() {
-            v.1.copy(v.0, copy.src, copy.dst)?;
-        }
-        Ok(())
+        let draws: Vec<DrawParams> = copys
+            .map(|copy| DrawParams::new(copy.src, copy.dst.map(rect_to_f)))
+            .collect();
+        self.draw(window_name, path, &draws)
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -196,8 +951,7 @@ impl<'sdl> ChimericSystem<'sdl> {
         R1: Into<Option<Rect>>,
         R2: Into<Option<FRect>>,
     {
-        let v = self.texture(window_name, path)?;
-        v.1.copy_f(v.0, src, dst)
+        self.draw(window_name, path, &[DrawParams::new(src.into(), dst.into())])
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -211,11 +965,10 @@ impl<'sdl> ChimericSystem<'sdl> {
     where
         I: Iterator<Item = CopyStructF>,
     {
-        let v = self.texture(window_name, path)?;
-        for copy in copys.into_iter() {
-            v.1.copy_f(v.0, copy.src, copy.dst)?;
-        }
-        Ok(())
+        let draws: Vec<DrawParams> = copys
+            .map(|copy| DrawParams::new(copy.src, copy.dst))
+            .collect();
+        self.draw(window_name, path, &draws)
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -236,8 +989,15 @@ impl<'sdl> ChimericSystem<'sdl> {
         R2: Into<Option<Rect>>,
         P: Into<Option<Point>>,
     {
-        let v = self.texture(window_name, path)?;
-        v.1.copy_ex(v.0, src, dst, angle, center, flip_horizontal, flip_vertical)
+        let draw = DrawParams {
+            src: src.into(),
+            dst: dst.into().map(rect_to_f),
+            angle,
+            center: center.into().map(point_to_f),
+            flip_horizontal,
+            flip_vertical,
+        };
+        self.draw(window_name, path, &[draw])
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -251,19 +1011,17 @@ impl<'sdl> ChimericSystem<'sdl> {
     where
         I: Iterator<Item = CopyStructEx>,
     {
-        let v = self.texture(window_name, path)?;
-        for copy in copys.into_iter() {
-            v.1.copy_ex(
-                v.0,
-                copy.src,
-                copy.dst,
-                copy.angle,
-                copy.center,
-                copy.flip_horizontal,
-                copy.flip_vertical,
-            )?;
-        }
-        Ok(())
+        let draws: Vec<DrawParams> = copys
+            .map(|copy| DrawParams {
+                src: copy.src,
+                dst: copy.dst.map(rect_to_f),
+                angle: copy.angle,
+                center: Some(point_to_f(copy.center)),
+                flip_horizontal: copy.flip_horizontal,
+                flip_vertical: copy.flip_vertical,
+            })
+            .collect();
+        self.draw(window_name, path, &draws)
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -284,8 +1042,15 @@ impl<'sdl> ChimericSystem<'sdl> {
         R2: Into<Option<FRect>>,
         P: Into<Option<FPoint>>,
     {
-        let v = self.texture(window_name, path)?;
-        v.1.copy_ex_f(v.0, src, dst, angle, center, flip_horizontal, flip_vertical)
+        let draw = DrawParams {
+            src: src.into(),
+            dst: dst.into(),
+            angle,
+            center: center.into(),
+            flip_horizontal,
+            flip_vertical,
+        };
+        self.draw(window_name, path, &[draw])
     }
 
     /// load the texture from the file path if its not in the cache; used to
@@ -299,23 +1064,13 @@ impl<'sdl> ChimericSystem<'sdl> {
     where
         I: Iterator<Item = CopyStructExF>,
     {
-        let v = self.texture(window_name, path)?;
-        for copy in copys.into_iter() {
-            v.1.copy_ex_f(
-                v.0,
-                copy.src,
-                copy.dst,
-                copy.angle,
-                copy.center,
-                copy.flip_horizontal,
-                copy.flip_vertical,
-            )?;
-        }
-        Ok(())
+        let draws: Vec<DrawParams> = copys.map(DrawParams::from).collect();
+        self.draw(window_name, path, &draws)
     }
 
     /// create the rendered text if needed, load the font as needed; used to
     /// draw to the window specified by name
+    #[cfg(feature = "ttf")]
     pub fn copy_text<R1, R2>(
         &mut self,
         window_name: &str,
@@ -330,14 +1085,15 @@ impl<'sdl> ChimericSystem<'sdl> {
         R1: Into<Option<Rect>>,
         R2: Into<Option<Rect>>,
     {
-        let v = self.text(window_name, font_file, point_size, text, wrap_width)?;
-        v.1.copy(v.0, src, dst)
+        let draw = DrawParams::new(src.into(), dst.into().map(rect_to_f));
+        self.draw_text(window_name, font_file, point_size, text, wrap_width, &draw)
     }
 
     /// create the rendered text if needed, load the font as needed; used to
     /// draw to the window specified by name
-    pub fn copy_text_f<'me, R1, R2>(
-        &'me mut self,
+    #[cfg(feature = "ttf")]
+    pub fn copy_text_f<R1, R2>(
+        &mut self,
         window_name: &str,
         font_file: &Path,
         point_size: u16,
@@ -347,18 +1103,18 @@ impl<'sdl> ChimericSystem<'sdl> {
         dst: R2,
     ) -> Result<(), String>
     where
-        'me: 'sdl,
         R1: Into<Option<Rect>>,
         R2: Into<Option<FRect>>,
     {
-        let v = self.text(window_name, font_file, point_size, text, wrap_width)?;
-        v.1.copy_f(v.0, src, dst)
+        let draw = DrawParams::new(src.into(), dst.into());
+        self.draw_text(window_name, font_file, point_size, text, wrap_width, &draw)
     }
 
     /// create the rendered text if needed, load the font as needed; used to
     /// draw to the window specified by name
-    pub fn copy_text_ex<'me, R1, R2, P>(
-        &'me mut self,
+    #[cfg(feature = "ttf")]
+    pub fn copy_text_ex<R1, R2, P>(
+        &mut self,
         window_name: &str,
         font_file: &Path,
         point_size: u16,
@@ -372,19 +1128,26 @@ impl<'sdl> ChimericSystem<'sdl> {
         flip_vertical: bool,
     ) -> Result<(), String>
     where
-        'me: 'sdl,
         R1: Into<Option<Rect>>,
         R2: Into<Option<Rect>>,
         P: Into<Option<Point>>,
     {
-        let v = self.text(window_name, font_file, point_size, text, wrap_width)?;
-        v.1.copy_ex(v.0, src, dst, angle, center, flip_horizontal, flip_vertical)
+        let draw = DrawParams {
+            src: src.into(),
+            dst: dst.into().map(rect_to_f),
+            angle,
+            center: center.into().map(point_to_f),
+            flip_horizontal,
+            flip_vertical,
+        };
+        self.draw_text(window_name, font_file, point_size, text, wrap_width, &draw)
     }
 
     /// create the rendered text if needed, load the font as needed; used to
     /// draw to the window specified by name
-    pub fn copy_text_ex_f<'me, R1, R2, P>(
-        &'me mut self,
+    #[cfg(feature = "ttf")]
+    pub fn copy_text_ex_f<R1, R2, P>(
+        &mut self,
         window_name: &str,
         font_file: &Path,
         point_size: u16,
@@ -398,13 +1161,19 @@ impl<'sdl> ChimericSystem<'sdl> {
         flip_vertical: bool,
     ) -> Result<(), String>
     where
-        'me: 'sdl,
         R1: Into<Option<Rect>>,
         R2: Into<Option<FRect>>,
         P: Into<Option<FPoint>>,
     {
-        let v = self.text(window_name, font_file, point_size, text, wrap_width)?;
-        v.1.copy_ex_f(v.0, src, dst, angle, center, flip_horizontal, flip_vertical)
+        let draw = DrawParams {
+            src: src.into(),
+            dst: dst.into(),
+            angle,
+            center: center.into(),
+            flip_horizontal,
+            flip_vertical,
+        };
+        self.draw_text(window_name, font_file, point_size, text, wrap_width, &draw)
     }
 
     // =========================== base functions ==============================
@@ -417,16 +1186,21 @@ impl<'sdl> ChimericSystem<'sdl> {
         path: &Path,
     ) -> Result<(&mut Texture, &mut Canvas<Window>), String>
     {
-        match self.windows.get_mut(window_name.into()) {
+        let assets = &self.assets;
+        match self.windows.get_mut(window_name).map(|window| window.as_sdl()) {
             None => Err(format!(
                 "can't get texture; window \"{window_name}\" does not exist"
             )),
-            Some(window) => window.texture(path),
+            Some(None) => Err(format!(
+                "can't get texture; window \"{window_name}\" doesn't use the sdl renderer"
+            )),
+            Some(Some(window)) => window.texture(assets, path),
         }
     }
 
     /// create the texture for the rendered font, load the font as needed; used
     /// to draw to the window specified by name
+    #[cfg(feature = "ttf")]
     pub fn text(
         &mut self,
         window_name: &str,
@@ -435,12 +1209,16 @@ impl<'sdl> ChimericSystem<'sdl> {
         text: &CStr,
         wrap_width: Option<u32>,
     ) -> Result<(&mut Texture, &mut Canvas<Window>), String> {
-        match self.windows.get_mut(window_name.into()) {
+        match self.windows.get_mut(window_name).map(|window| window.as_sdl()) {
             None => Err(format!(
                 "can't get texture; window \"{window_name}\" does not exist"
             )),
-            Some(window) => window.text(
+            Some(None) => Err(format!(
+                "can't get texture; window \"{window_name}\" doesn't use the sdl renderer"
+            )),
+            Some(Some(window)) => window.text(
                 &mut self.font_system,
+                &self.assets,
                 font_file,
                 point_size,
                 text,
@@ -449,3 +1227,28 @@ impl<'sdl> ChimericSystem<'sdl> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered() {
+        let mut windows = Ordered::default();
+        assert!(windows.insert_new("main", 0));
+        assert!(windows.insert_new("map", 1));
+        assert!(windows.insert_new("debug", 2));
+        assert!(!windows.insert_new("map", 3));
+        let names = |w: &Ordered<i32>| w.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&windows), ["main", "map", "debug"]);
+
+        windows.set_order(&["debug", "main"]).unwrap();
+        assert_eq!(names(&windows), ["debug", "main", "map"]);
+        assert!(windows.set_order(&["main", "main"]).is_err());
+        assert!(windows.set_order(&["missing"]).is_err());
+
+        assert_eq!(windows.remove("main"), Some(0));
+        assert_eq!(names(&windows), ["debug", "map"]);
+        assert_eq!(windows.get("map"), Some(&1));
+    }
+}
@@ -0,0 +1,267 @@
+use sdl2::{
+    keyboard::{Keycode, Mod, TextInputUtil},
+    rect::Rect,
+};
+
+use super::{events::EngineEvent, system::System};
+
+/// sdl text input. while active, key presses produce text input events, and
+/// the input method (ime) may show its candidate window
+pub struct TextInput {
+    util: TextInputUtil,
+}
+
+impl TextInput {
+    pub fn new(system: &System) -> Self {
+        Self {
+            util: system.video.text_input(),
+        }
+    }
+
+    pub fn start(&self) {
+        self.util.start();
+    }
+
+    pub fn stop(&self) {
+        self.util.stop();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.util.is_active()
+    }
+
+    /// where the text field is, so the ime's candidate window can be placed
+    /// near it
+    pub fn set_rect(&self, rect: Rect) {
+        self.util.set_rect(rect);
+    }
+}
+
+/// an editable line of text for chat boxes, name entry, etc. feed it events
+/// while it has focus. positions are byte offsets on char boundaries
+#[derive(Debug, Clone, Default)]
+pub struct TextBuffer {
+    text: String,
+    cursor: usize,
+    /// the other end of the selection, if any
+    anchor: Option<usize>,
+    /// uncommitted ime text, shown at the cursor
+    composition: String,
+    /// in chars. None is unlimited
+    pub max_len: Option<usize>,
+}
+
+impl TextBuffer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// replaces the content, placing the cursor at the end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.to_owned();
+        self.cursor = self.text.len();
+        self.anchor = None;
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// the selected byte range, if any
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor?;
+        (anchor != self.cursor).then(|| (anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    pub fn selected_text(&self) -> &str {
+        self.selection()
+            .map(|(start, end)| &self.text[start..end])
+            .unwrap_or("")
+    }
+
+    pub fn composition(&self) -> &str {
+        &self.composition
+    }
+
+    /// the text as it should be displayed, with the composition at the cursor
+    pub fn display_text(&self) -> String {
+        let mut s = self.text.clone();
+        s.insert_str(self.cursor, &self.composition);
+        s
+    }
+
+    fn previous_boundary(&self, i: usize) -> usize {
+        self.text[..i]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    fn next_boundary(&self, i: usize) -> usize {
+        self.text[i..]
+            .chars()
+            .next()
+            .map(|c| i + c.len_utf8())
+            .unwrap_or(i)
+    }
+
+    /// remove the selection if there is one. returns true if removed
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                self.text.replace_range(start..end, "");
+                self.cursor = start;
+                self.anchor = None;
+                true
+            }
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+
+    /// replaces the selection. text past the max length is dropped
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        let available = match self.max_len {
+            Some(max) => max.saturating_sub(self.text.chars().count()),
+            None => usize::MAX,
+        };
+        let end = text
+            .char_indices()
+            .nth(available)
+            .map(|(i, _)| i)
+            .unwrap_or(text.len());
+        self.text.insert_str(self.cursor, &text[..end]);
+        self.cursor += end;
+    }
+
+    /// delete the selection, or the char before the cursor
+    pub fn backspace(&mut self) {
+        if !self.delete_selection() && self.cursor > 0 {
+            let start = self.previous_boundary(self.cursor);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    /// delete the selection, or the char after the cursor
+    pub fn delete(&mut self) {
+        if !self.delete_selection() && self.cursor < self.text.len() {
+            let end = self.next_boundary(self.cursor);
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    /// move the cursor. if selecting, the selection is extended, otherwise it's
+    /// cleared
+    pub fn move_to(&mut self, position: usize, selecting: bool) {
+        if selecting {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = position.min(self.text.len());
+    }
+
+    pub fn move_left(&mut self, selecting: bool) {
+        let position = match self.selection() {
+            Some((start, _)) if !selecting => start,
+            _ => self.previous_boundary(self.cursor),
+        };
+        self.move_to(position, selecting);
+    }
+
+    pub fn move_right(&mut self, selecting: bool) {
+        let position = match self.selection() {
+            Some((_, end)) if !selecting => end,
+            _ => self.next_boundary(self.cursor),
+        };
+        self.move_to(position, selecting);
+    }
+
+    /// remove and return the selected text
+    pub fn cut(&mut self) -> String {
+        let cut = self.selected_text().to_owned();
+        self.delete_selection();
+        cut
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+    }
+
+    /// apply text input, ime composition, and editing keys. returns true if
+    /// the event was used
+    pub fn handle_event(&mut self, event: &EngineEvent) -> bool {
+        match event {
+            EngineEvent::TextInput { text, .. } => {
+                self.composition.clear();
+                self.insert(text);
+                true
+            }
+            EngineEvent::TextEditing { text, .. } => {
+                self.composition = text.clone();
+                true
+            }
+            EngineEvent::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                ..
+            } => {
+                let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                match *keycode {
+                    Keycode::Backspace => self.backspace(),
+                    Keycode::Delete => self.delete(),
+                    Keycode::Left => self.move_left(shift),
+                    Keycode::Right => self.move_right(shift),
+                    Keycode::Home => self.move_to(0, shift),
+                    Keycode::End => self.move_to(self.text.len(), shift),
+                    Keycode::A if ctrl => self.select_all(),
+                    // copy, cut, and paste are left to the caller since they
+                    // need the clipboard; see selected_text, cut, and insert
+                    _ => return false,
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editing() {
+        let mut buffer = TextBuffer::new();
+        buffer.insert("héllo");
+        buffer.backspace();
+        assert_eq!(buffer.text(), "héll");
+        buffer.move_to(1, false);
+        buffer.move_right(true);
+        assert_eq!(buffer.selected_text(), "é");
+        buffer.insert("e");
+        assert_eq!(buffer.text(), "hell");
+        buffer.move_left(false);
+        buffer.delete();
+        assert_eq!(buffer.text(), "hll");
+
+        buffer.max_len = Some(5);
+        buffer.move_to(3, false);
+        buffer.insert("abcdef");
+        assert_eq!(buffer.text(), "hllab");
+        buffer.select_all();
+        buffer.backspace();
+        assert_eq!(buffer.text(), "");
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use sdl2::rect::{FPoint, Rect};
+
+use super::{decode::DecodedImage, easing::bezier_point};
+
+/// a polyline that text is laid along, e.g. for curved banners and circular
+/// labels. the text starts at the first point, upright when the path runs
+/// left to right
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextPath {
+    points: Vec<FPoint>,
+}
+
+impl TextPath {
+    pub fn polyline(points: Vec<FPoint>) -> Self {
+        Self { points }
+    }
+
+    /// the cubic bezier curve, split into the number of straight segments
+    pub fn bezier(p0: FPoint, p1: FPoint, p2: FPoint, p3: FPoint, segments: u32) -> Self {
+        let segments = segments.max(1);
+        Self::polyline(
+            (0..=segments)
+                .map(|i| bezier_point(p0, p1, p2, p3, i as f32 / segments as f32))
+                .collect(),
+        )
+    }
+
+    /// part of a circle, split into the number of straight segments. angles
+    /// are in degrees clockwise from the right, so 180 to 360 runs over the
+    /// top, and 180 to 0 under the bottom
+    pub fn arc(center: FPoint, radius: f32, start: f32, end: f32, segments: u32) -> Self {
+        let segments = segments.max(1);
+        Self::polyline(
+            (0..=segments)
+                .map(|i| {
+                    let angle = (start + (end - start) * i as f32 / segments as f32).to_radians();
+                    FPoint::new(
+                        center.x() + radius * angle.cos(),
+                        center.y() + radius * angle.sin(),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    pub fn points(&self) -> &[FPoint] {
+        &self.points
+    }
+
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|w| (w[1].x() - w[0].x()).hypot(w[1].y() - w[0].y()))
+            .sum()
+    }
+
+    /// the point the distance along the path, and the path's direction there
+    /// in degrees clockwise. None past either end
+    pub fn at(&self, distance: f32) -> Option<(FPoint, f32)> {
+        if distance < 0. {
+            return None;
+        }
+        let mut remaining = distance;
+        for w in self.points.windows(2) {
+            let (dx, dy) = (w[1].x() - w[0].x(), w[1].y() - w[0].y());
+            let length = dx.hypot(dy);
+            if remaining <= length && length > 0. {
+                let t = remaining / length;
+                let point = FPoint::new(w[0].x() + dx * t, w[0].y() + dy * t);
+                return Some((point, dy.atan2(dx).to_degrees()));
+            }
+            remaining -= length;
+        }
+        None
+    }
+
+    /// identifies the path in the text cache
+    pub fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for point in self.points.iter() {
+            point.x().to_bits().hash(&mut hasher);
+            point.y().to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// the area text along the path is drawn in: the path's bounds, with
+    /// room for glyphs of the point size on either side
+    pub fn area(&self, point_size: u16) -> Rect {
+        let pad = point_size as f32 * 2.;
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for point in self.points.iter() {
+            min_x = min_x.min(point.x());
+            min_y = min_y.min(point.y());
+            max_x = max_x.max(point.x());
+            max_y = max_y.max(point.y());
+        }
+        if self.points.is_empty() {
+            (min_x, min_y, max_x, max_y) = (0., 0., 0., 0.);
+        }
+        let (x, y) = ((min_x - pad).floor(), (min_y - pad).floor());
+        Rect::new(
+            x as i32,
+            y as i32,
+            ((max_x + pad).ceil() - x) as u32,
+            ((max_y + pad).ceil() - y) as u32,
+        )
+    }
+
+    /// where each glyph with these advances goes: the point under its middle
+    /// and the angle it's turned by. None for glyphs past the end
+    pub fn place(&self, advances: &[f32]) -> Vec<Option<(FPoint, f32)>> {
+        let mut pen = 0.;
+        advances
+            .iter()
+            .map(|advance| {
+                let placement = self.at(pen + advance / 2.);
+                pen += advance;
+                placement
+            })
+            .collect()
+    }
+}
+
+/// draw the source image over the destination, turned by the angle (degrees
+/// clockwise) about the pivot, which lands on the point. bilinear filtered
+pub(crate) fn blit_rotated(
+    dst: &mut DecodedImage,
+    src: &DecodedImage,
+    pivot: (f32, f32),
+    point: (f32, f32),
+    angle: f32,
+) {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // the destination pixels the turned source can cover
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for (x, y) in [
+        (0., 0.),
+        (src.width as f32, 0.),
+        (0., src.height as f32),
+        (src.width as f32, src.height as f32),
+    ] {
+        let (x, y) = (x - pivot.0, y - pivot.1);
+        let (x, y) = (point.0 + x * cos - y * sin, point.1 + x * sin + y * cos);
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    let x_range = min_x.floor().max(0.) as u32..(max_x.ceil().max(0.) as u32).min(dst.width);
+    let y_range = min_y.floor().max(0.) as u32..(max_y.ceil().max(0.) as u32).min(dst.height);
+
+    let texel = |x: i64, y: i64| -> [f32; 4] {
+        if x < 0 || y < 0 || x >= src.width as i64 || y >= src.height as i64 {
+            return [0.; 4];
+        }
+        let i = (y as usize * src.width as usize + x as usize) * 4;
+        let p = &src.rgba[i..i + 4];
+        [p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32 / 255.]
+    };
+    for y in y_range {
+        for x in x_range.clone() {
+            // back from the destination pixel's center into the source
+            let (dx, dy) = (x as f32 + 0.5 - point.0, y as f32 + 0.5 - point.1);
+            let sx = dx * cos + dy * sin + pivot.0 - 0.5;
+            let sy = -dx * sin + dy * cos + pivot.1 - 0.5;
+            let (x0, y0) = (sx.floor(), sy.floor());
+            let (fx, fy) = (sx - x0, sy - y0);
+            let (x0, y0) = (x0 as i64, y0 as i64);
+            // colors weighted by alpha, so transparent neighbours don't darken
+            let mut sum = [0f32; 4];
+            for (tx, ty, weight) in [
+                (x0, y0, (1. - fx) * (1. - fy)),
+                (x0 + 1, y0, fx * (1. - fy)),
+                (x0, y0 + 1, (1. - fx) * fy),
+                (x0 + 1, y0 + 1, fx * fy),
+            ] {
+                let t = texel(tx, ty);
+                let weight = weight * t[3];
+                for c in 0..3 {
+                    sum[c] += t[c] * weight;
+                }
+                sum[3] += weight;
+            }
+            let alpha = sum[3];
+            if alpha <= 0. {
+                continue;
+            }
+            let i = (y as usize * dst.width as usize + x as usize) * 4;
+            let d = &mut dst.rgba[i..i + 4];
+            let dst_alpha = d[3] as f32 / 255.;
+            let out_alpha = alpha + dst_alpha * (1. - alpha);
+            for c in 0..3 {
+                let src_color = sum[c] / alpha;
+                let color =
+                    (src_color * alpha + d[c] as f32 * dst_alpha * (1. - alpha)) / out_alpha;
+                d[c] = color.round().clamp(0., 255.) as u8;
+            }
+            d[3] = (out_alpha * 255.).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path() {
+        let path = TextPath::polyline(vec![
+            FPoint::new(0., 0.),
+            FPoint::new(10., 0.),
+            FPoint::new(10., 10.),
+        ]);
+        assert_eq!(path.length(), 20.);
+        assert_eq!(path.at(5.), Some((FPoint::new(5., 0.), 0.)));
+        assert_eq!(path.at(15.), Some((FPoint::new(10., 5.), 90.)));
+        assert_eq!(path.at(21.), None);
+        assert_eq!(
+            path.place(&[4., 4., 4., 20.]),
+            [
+                Some((FPoint::new(2., 0.), 0.)),
+                Some((FPoint::new(6., 0.), 0.)),
+                Some((FPoint::new(10., 0.), 0.)),
+                None,
+            ]
+        );
+        assert_eq!(path.area(2), Rect::new(-4, -4, 18, 18));
+        assert!(path.hash_key() != TextPath::polyline(vec![FPoint::new(0., 0.)]).hash_key());
+
+        // over the top of the circle, left to right
+        let arc = TextPath::arc(FPoint::new(0., 0.), 10., 180., 360., 16);
+        let (top, angle) = arc.at(arc.length() / 2.).unwrap();
+        assert!(top.x().abs() < 0.01 && (top.y() + 10.).abs() < 0.1);
+        // level, to within a segment's turn
+        assert!(angle.abs() < 180. / 16.);
+    }
+
+    #[test]
+    fn test_blit_rotated() {
+        // a red pixel left of a green one, turned a quarter clockwise about
+        // the left pixel's center
+        let src = DecodedImage {
+            width: 2,
+            height: 1,
+            rgba: vec![255, 0, 0, 255, 0, 255, 0, 255],
+        };
+        let mut dst = DecodedImage {
+            width: 3,
+            height: 3,
+            rgba: vec![0; 36],
+        };
+        blit_rotated(&mut dst, &src, (0.5, 0.5), (1.5, 1.5), 90.);
+        let pixel = |x: usize, y: usize| &dst.rgba[(y * 3 + x) * 4..(y * 3 + x) * 4 + 4];
+        assert_eq!(pixel(1, 1), [255, 0, 0, 255]);
+        assert_eq!(pixel(1, 2), [0, 255, 0, 255]);
+        assert_eq!(pixel(2, 1)[3], 0);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use lru::LruCache;
+
+use super::render_system_txt_key::FileOrRenderedTextKey;
+
+/// the kinds of cached texture, each with its own capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheClass {
+    /// from files, including recolored ones
+    Texture,
+    /// rendered text
+    Text,
+}
+
+impl CacheClass {
+    pub fn of(key: &FileOrRenderedTextKey) -> Self {
+        match key.font_file() {
+            Some(_) => CacheClass::Text,
+            None => CacheClass::Texture,
+        }
+    }
+}
+
+/// a window's cached textures: a least recently used cache per class, so e.g.
+/// a burst of dialog text can't evict every sprite
+pub(crate) struct TextureCache<V> {
+    textures: LruCache<FileOrRenderedTextKey, V>,
+    text: LruCache<FileOrRenderedTextKey, V>,
+    /// kept until unlocked, even if evicted
+    locked: HashSet<FileOrRenderedTextKey>,
+    /// locked entries which were evicted
+    pinned: HashMap<FileOrRenderedTextKey, V>,
+}
+
+impl<V> TextureCache<V> {
+    pub fn new(num_textures: NonZeroUsize, num_text: NonZeroUsize) -> Self {
+        Self {
+            textures: LruCache::new(num_textures),
+            text: LruCache::new(num_text),
+            locked: Default::default(),
+            pinned: Default::default(),
+        }
+    }
+
+    fn class(&self, class: CacheClass) -> &LruCache<FileOrRenderedTextKey, V> {
+        match class {
+            CacheClass::Texture => &self.textures,
+            CacheClass::Text => &self.text,
+        }
+    }
+
+    fn class_mut(&mut self, class: CacheClass) -> &mut LruCache<FileOrRenderedTextKey, V> {
+        match class {
+            CacheClass::Texture => &mut self.textures,
+            CacheClass::Text => &mut self.text,
+        }
+    }
+
+    /// keep an entry pushed out of its cache if it's locked
+    fn evicted(&mut self, evicted: Option<(FileOrRenderedTextKey, V)>) {
+        if let Some((key, value)) = evicted {
+            if self.locked.contains(&key) {
+                self.pinned.insert(key, value);
+            }
+        }
+    }
+
+    #[cfg(any(feature = "wgpu", test))]
+    pub fn get(&mut self, key: &FileOrRenderedTextKey) -> Option<&V> {
+        if self.pinned.contains_key(key) {
+            return self.pinned.get(key);
+        }
+        self.class_mut(CacheClass::of(key)).get(key)
+    }
+
+    pub fn put(&mut self, key: FileOrRenderedTextKey, value: V) {
+        self.pinned.remove(&key);
+        let class = CacheClass::of(&key);
+        let evicted = self
+            .class_mut(class)
+            .push(key.clone(), value)
+            .filter(|(evicted, _)| *evicted != key);
+        self.evicted(evicted);
+    }
+
+    pub fn try_get_or_insert_mut<F, E>(
+        &mut self,
+        key: FileOrRenderedTextKey,
+        f: F,
+    ) -> Result<&mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        if self.pinned.contains_key(&key) {
+            return Ok(self.pinned.get_mut(&key).expect("just checked"));
+        }
+        let class = CacheClass::of(&key);
+        if !self.class(class).contains(&key) {
+            let value = f()?;
+            let evicted = self.class_mut(class).push(key.clone(), value);
+            self.evicted(evicted);
+        }
+        Ok(self.class_mut(class).get_mut(&key).expect("just inserted"))
+    }
+
+    /// the files textures were loaded from, most recently used first
+    pub fn texture_paths(&self) -> Vec<PathBuf> {
+        self.textures
+            .iter()
+            .filter_map(|(key, _)| key.texture_path())
+            .map(Path::to_owned)
+            .collect()
+    }
+
+    /// without making it the most recently used
+    pub fn contains(&self, key: &FileOrRenderedTextKey) -> bool {
+        self.pinned.contains_key(key) || self.class(CacheClass::of(key)).contains(key)
+    }
+
+    /// drop the entry, even if it's locked
+    pub fn pop(&mut self, key: &FileOrRenderedTextKey) -> Option<V> {
+        self.locked.remove(key);
+        let pinned = self.pinned.remove(key);
+        self.class_mut(CacheClass::of(key)).pop(key).or(pinned)
+    }
+
+    /// true if inserting the key evicts the least recently used of its class
+    pub fn is_evicting(&self, key: &FileOrRenderedTextKey) -> bool {
+        let cache = self.class(CacheClass::of(key));
+        cache.len() == cache.cap().get() && !cache.contains(key)
+    }
+
+    pub fn cap(&self, class: CacheClass) -> NonZeroUsize {
+        self.class(class).cap()
+    }
+
+    /// dropping the least recently used if it's shrunk
+    pub fn resize(&mut self, class: CacheClass, cap: NonZeroUsize) {
+        while self.class(class).len() > cap.get() {
+            let evicted = self.class_mut(class).pop_lru();
+            self.evicted(evicted);
+        }
+        self.class_mut(class).resize(cap);
+    }
+
+    /// keep the entry (once it's loaded) until unlocked, even if it's evicted
+    pub fn lock(&mut self, key: FileOrRenderedTextKey) {
+        self.locked.insert(key);
+    }
+
+    /// drops locked entries that were evicted
+    pub fn unlock_all(&mut self) {
+        self.locked.clear();
+        self.pinned.clear();
+    }
+
+    /// drop the cached text rendered with the font. returns how many were
+    /// dropped
+    #[cfg(feature = "ttf")]
+    pub fn evict_font_text(&mut self, font_file: &std::path::Path) -> usize {
+        let stale: Vec<FileOrRenderedTextKey> = self
+            .text
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| key.font_file() == Some(font_file))
+            .cloned()
+            .collect();
+        stale.iter().for_each(|key| {
+            self.text.pop(key);
+        });
+        let pinned = self.pinned.len();
+        self.pinned
+            .retain(|key, _| key.font_file() != Some(font_file));
+        stale.len() + pinned - self.pinned.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_classes() {
+        let two = NonZeroUsize::new(2).unwrap();
+        let mut cache = TextureCache::new(two, two);
+        let sprite = |name: &str| FileOrRenderedTextKey::from_path(Path::new(name));
+        let text = |text: &std::ffi::CStr| {
+            FileOrRenderedTextKey::from_rendered_text(text, Path::new("font.ttf"), 16)
+        };
+        cache.put(sprite("a.png"), 1);
+        cache.put(sprite("b.png"), 2);
+        for (i, line) in [c"one", c"two", c"three"].into_iter().enumerate() {
+            cache.put(text(line), 10 + i);
+        }
+        // the text only evicted text
+        assert_eq!(cache.get(&sprite("a.png")), Some(&1));
+        assert_eq!(cache.get(&sprite("b.png")), Some(&2));
+        assert_eq!(cache.get(&text(c"one")), None);
+        assert!(cache.is_evicting(&text(c"four")));
+        assert!(!cache.is_evicting(&text(c"three")));
+
+        cache.resize(CacheClass::Texture, NonZeroUsize::new(1).unwrap());
+        assert_eq!(cache.cap(CacheClass::Texture).get(), 1);
+        // the least recently used was dropped
+        assert_eq!(cache.get(&sprite("a.png")), None);
+        assert_eq!(cache.get(&sprite("b.png")), Some(&2));
+    }
+
+    #[test]
+    fn test_texture_paths() {
+        let two = NonZeroUsize::new(2).unwrap();
+        let mut cache = TextureCache::new(two, two);
+        cache.put(FileOrRenderedTextKey::from_path(Path::new("a.png")), 1);
+        cache.put(
+            FileOrRenderedTextKey::from_path_with_palette(Path::new("a.png"), "red"),
+            2,
+        );
+        cache.put(FileOrRenderedTextKey::from_path(Path::new("b.png")), 3);
+        assert_eq!(cache.texture_paths(), [Path::new("b.png")]);
+    }
+
+    #[test]
+    fn test_lock() {
+        let one = NonZeroUsize::new(1).unwrap();
+        let mut cache = TextureCache::new(one, one);
+        let sprite = |name: &str| FileOrRenderedTextKey::from_path(Path::new(name));
+        cache.lock(sprite("a.png"));
+        cache.put(sprite("a.png"), 1);
+        cache.put(sprite("b.png"), 2);
+        // evicted, but kept while locked
+        assert_eq!(cache.get(&sprite("a.png")), Some(&1));
+        assert!(cache.contains(&sprite("a.png")));
+        assert_eq!(
+            cache.try_get_or_insert_mut(sprite("a.png"), || Err::<i32, ()>(())),
+            Ok(&mut 1)
+        );
+        cache.unlock_all();
+        assert_eq!(cache.get(&sprite("a.png")), None);
+        assert_eq!(cache.pop(&sprite("b.png")), Some(2));
+        cache.put(sprite("b.png"), 2);
+        assert_eq!(cache.get(&sprite("b.png")), Some(&2));
+
+        // replacing a locked entry doesn't keep the old value
+        cache.lock(sprite("b.png"));
+        cache.put(sprite("b.png"), 3);
+        assert_eq!(cache.get(&sprite("b.png")), Some(&3));
+        cache.put(sprite("c.png"), 4);
+        assert_eq!(cache.get(&sprite("b.png")), Some(&3));
+    }
+
+    #[test]
+    #[cfg(feature = "ttf")]
+    fn test_evict_font_text() {
+        let two = NonZeroUsize::new(2).unwrap();
+        let mut cache = TextureCache::new(two, two);
+        let text =
+            |font: &str| FileOrRenderedTextKey::from_rendered_text(c"text", Path::new(font), 16);
+        cache.put(text("a.ttf"), 1);
+        cache.put(text("b.ttf"), 2);
+        assert_eq!(cache.evict_font_text(Path::new("a.ttf")), 1);
+        assert_eq!(cache.get(&text("a.ttf")), None);
+        assert_eq!(cache.get(&text("b.ttf")), Some(&2));
+    }
+}
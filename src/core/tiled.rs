@@ -0,0 +1,886 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use roxmltree::{Document, Node};
+use sdl2::rect::{FPoint, FRect, Rect};
+
+use super::{
+    camera::Camera2D,
+    entity::EntityId,
+    prefab::parse_override,
+    save::write_atomic,
+    system::{ChimericSystem, CopyStructExF},
+    tilemap::{LayerVersion, TileLayer, EMPTY_TILE},
+    vfs::Vfs,
+    world::World,
+};
+
+/// tiled stores flips in the high bits of each tile. they're kept in the tile
+/// layers; use tile_gid to strip them
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const FLIP_FLAGS: u32 = FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY;
+
+/// the global tile id without the flip flags
+pub fn tile_gid(tile: u32) -> u32 {
+    tile & !FLIP_FLAGS
+}
+
+/// a tileset with a single image
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub name: String,
+    /// the global id of the tileset's first tile
+    pub first_gid: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    /// relative to the working directory
+    pub image: PathBuf,
+}
+
+impl Tileset {
+    pub fn contains(&self, tile: u32) -> bool {
+        let gid = tile_gid(tile);
+        gid >= self.first_gid && gid < self.first_gid + self.tile_count
+    }
+
+    /// the tile's area in the tileset image. None if it's from another tileset
+    pub fn source_rect(&self, tile: u32) -> Option<Rect> {
+        if !self.contains(tile) || self.columns == 0 {
+            return None;
+        }
+        let index = tile_gid(tile) - self.first_gid;
+        let (column, row) = (index % self.columns, index / self.columns);
+        Some(Rect::new(
+            (self.margin + column * (self.tile_width + self.spacing)) as i32,
+            (self.margin + row * (self.tile_height + self.spacing)) as i32,
+            self.tile_width,
+            self.tile_height,
+        ))
+    }
+}
+
+/// where and how a tile of a layer is drawn, in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TileDraw {
+    /// index into the map's tilesets
+    tileset: usize,
+    src: Rect,
+    center: FPoint,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+}
+
+impl TileDraw {
+    fn copy(&self, camera: &Camera2D) -> CopyStructExF {
+        let screen = camera.world_to_screen(self.center);
+        let w = self.src.width() as f32 * camera.zoom;
+        let h = self.src.height() as f32 * camera.zoom;
+        // diagonal flips (rotated tiles) aren't drawn rotated
+        CopyStructExF {
+            src: Some(self.src),
+            dst: Some(FRect::new(screen.x() - w / 2., screen.y() - h / 2., w, h)),
+            angle: -camera.rotation as f64,
+            center: FPoint::new(w / 2., h / 2.),
+            flip_horizontal: self.flip_horizontal,
+            flip_vertical: self.flip_vertical,
+        }
+    }
+}
+
+/// the tileset and source rect of each tile of a layer, kept between draws
+/// by TiledMap::draw_layer_cached. only tiles changed in the layer since are
+/// looked up again, so layers changed a few tiles at a time (e.g. dug or
+/// blown up) aren't redone every frame. use one cache per layer
+#[derive(Debug, Clone, Default)]
+pub struct LayerCache {
+    /// the layer the cache is of
+    version: Option<LayerVersion>,
+    /// row major
+    tiles: Vec<Option<TileDraw>>,
+}
+
+impl LayerCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// look up every tile again on the next update, e.g. after changing the
+    /// map's tilesets
+    pub fn invalidate(&mut self) {
+        self.version = None;
+    }
+
+    /// look up the tiles changed since the last update, or every tile if
+    /// that isn't known (e.g. it's another layer)
+    pub fn update(&mut self, map: &TiledMap, layer: &TileLayer) {
+        if self.version == Some(layer.version()) {
+            return;
+        }
+        let (width, height) = (layer.width() as i32, layer.height() as i32);
+        match self
+            .version
+            .and_then(|version| layer.changes_since(version))
+        {
+            Some(changes) => {
+                for (x, y) in changes {
+                    let i = (y * width + x) as usize;
+                    self.tiles[i] = map.tile_draw(layer, x, y);
+                }
+            }
+            None => {
+                self.tiles = (0..height)
+                    .flat_map(|y| (0..width).map(move |x| (x, y)))
+                    .map(|(x, y)| map.tile_draw(layer, x, y))
+                    .collect();
+            }
+        }
+        self.version = Some(layer.version());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    Rect(FRect),
+    Ellipse(FRect),
+    Point(FPoint),
+    /// closed. points are absolute, not relative to the object
+    Polygon(Vec<FPoint>),
+    Polyline(Vec<FPoint>),
+}
+
+#[derive(Debug, Clone)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    /// the object's class (type in older versions of tiled)
+    pub class: String,
+    pub shape: ObjectShape,
+    /// set for tile objects
+    pub tile: Option<u32>,
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<MapObject>,
+}
+
+/// a map made in the tiled editor. layers inside groups are flattened, in
+/// draw order
+#[derive(Debug, Clone)]
+pub struct TiledMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tilesets: Vec<Tileset>,
+    pub tile_layers: Vec<(String, TileLayer)>,
+    pub object_layers: Vec<ObjectLayer>,
+    pub properties: BTreeMap<String, String>,
+}
+
+fn attr<T: std::str::FromStr>(node: Node, name: &str) -> Result<T, String> {
+    let value = node.attribute(name).ok_or_else(|| {
+        format!(
+            "<{}> is missing attribute \"{name}\"",
+            node.tag_name().name()
+        )
+    })?;
+    value
+        .parse()
+        .map_err(|_| format!("attribute \"{name}\" has invalid value \"{value}\""))
+}
+
+fn attr_or<T: std::str::FromStr>(node: Node, name: &str, default: T) -> Result<T, String> {
+    match node.attribute(name) {
+        Some(_) => attr(node, name),
+        None => Ok(default),
+    }
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |n| n.is_element() && n.tag_name().name() == name)
+}
+
+fn properties(node: Node) -> BTreeMap<String, String> {
+    children(node, "properties")
+        .flat_map(|p| children(p, "property"))
+        .filter_map(|p| {
+            let name = p.attribute("name")?;
+            // multiline strings are stored as text instead of an attribute
+            let value = p.attribute("value").or_else(|| p.text()).unwrap_or("");
+            Some((name.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    fn value(c: u8) -> Result<u32, String> {
+        match c {
+            b'A'..=b'Z' => Ok((c - b'A') as u32),
+            b'a'..=b'z' => Ok((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Ok((c - b'0') as u32 + 52),
+            b'+' => Ok(62),
+            b'/' => Ok(63),
+            _ => Err(format!("invalid base64 character '{}'", c as char)),
+        }
+    }
+    let chars: Vec<u8> = text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+        .collect();
+    let mut out = Vec::with_capacity(chars.len() * 3 / 4);
+    for chunk in chars.chunks(4) {
+        let mut bits = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            bits |= value(*c)? << (18 - 6 * i);
+        }
+        let bytes = bits.to_be_bytes();
+        out.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Ok(out)
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_properties(out: &mut String, indent: &str, properties: &BTreeMap<String, String>) {
+    if properties.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "{indent}<properties>");
+    for (name, value) in properties {
+        let _ = writeln!(
+            out,
+            "{indent} <property name=\"{}\" value=\"{}\"/>",
+            escape_xml(name),
+            escape_xml(value)
+        );
+    }
+    let _ = writeln!(out, "{indent}</properties>");
+}
+
+fn write_points(points: &[FPoint], origin: FPoint) -> String {
+    points
+        .iter()
+        .map(|p| format!("{},{}", p.x() - origin.x(), p.y() - origin.y()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl MapObject {
+    /// where the object is: a rect or ellipse's top left corner, or a
+    /// polygon or polyline's first point
+    pub fn position(&self) -> FPoint {
+        match &self.shape {
+            ObjectShape::Rect(r) | ObjectShape::Ellipse(r) => FPoint::new(r.x(), r.y()),
+            ObjectShape::Point(p) => *p,
+            ObjectShape::Polygon(points) | ObjectShape::Polyline(points) => {
+                points.first().copied().unwrap_or(FPoint::new(0., 0.))
+            }
+        }
+    }
+}
+
+fn parse_points(text: &str, origin: FPoint) -> Result<Vec<FPoint>, String> {
+    text.split_whitespace()
+        .map(|pair| {
+            let (x, y) = pair
+                .split_once(',')
+                .ok_or_else(|| format!("invalid point \"{pair}\""))?;
+            let parse = |s: &str| {
+                s.parse::<f32>()
+                    .map_err(|_| format!("invalid point \"{pair}\""))
+            };
+            Ok(origin + FPoint::new(parse(x)?, parse(y)?))
+        })
+        .collect()
+}
+
+impl TiledMap {
+    /// an empty map, with no tilesets or layers
+    pub fn new(width: u32, height: u32, tile_width: u32, tile_height: u32) -> Self {
+        Self {
+            width,
+            height,
+            tile_width,
+            tile_height,
+            tilesets: Vec::new(),
+            tile_layers: Vec::new(),
+            object_layers: Vec::new(),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// parse a .tmx file. external tilesets and images are relative to it
+    pub fn load_file(path: &Path) -> Result<Self, String> {
+        Self::load_from(&Vfs::new(), path)
+    }
+
+    /// as load_file, resolving the map and external tilesets
+    /// through the vfs
+    pub fn load_from(assets: &Vfs, path: &Path) -> Result<Self, String> {
+        let content = assets.read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&content, dir, assets).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// parse the content of a .tmx file. external tilesets and images are
+    /// relative to dir
+    pub fn load_str(content: &str, dir: &Path) -> Result<Self, String> {
+        Self::parse(content, dir, &Vfs::new())
+    }
+
+    fn parse(content: &str, dir: &Path, assets: &Vfs) -> Result<Self, String> {
+        let doc = Document::parse(content).map_err(|e| e.to_string())?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "map" {
+            return Err("expected a <map> element".to_owned());
+        }
+        if attr_or(root, "infinite", 0u8)? != 0 {
+            return Err("infinite maps aren't supported".to_owned());
+        }
+        if root.attribute("orientation").unwrap_or("orthogonal") != "orthogonal" {
+            return Err("only orthogonal maps are supported".to_owned());
+        }
+
+        let mut map = Self {
+            width: attr(root, "width")?,
+            height: attr(root, "height")?,
+            tile_width: attr(root, "tilewidth")?,
+            tile_height: attr(root, "tileheight")?,
+            tilesets: Vec::new(),
+            tile_layers: Vec::new(),
+            object_layers: Vec::new(),
+            properties: properties(root),
+        };
+        for node in children(root, "tileset") {
+            let first_gid = attr(node, "firstgid")?;
+            let tileset = match node.attribute("source") {
+                Some(source) => Self::load_tsx(assets, &dir.join(source), first_gid)?,
+                None => Self::parse_tileset(node, first_gid, dir)?,
+            };
+            map.tilesets.push(tileset);
+        }
+        map.parse_layers(root)?;
+        Ok(map)
+    }
+
+    fn load_tsx(assets: &Vfs, path: &Path, first_gid: u32) -> Result<Tileset, String> {
+        let content = assets.read_to_string(path)?;
+        let doc = Document::parse(&content).map_err(|e| e.to_string())?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse_tileset(doc.root_element(), first_gid, dir)
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    fn parse_tileset(node: Node, first_gid: u32, dir: &Path) -> Result<Tileset, String> {
+        let image = children(node, "image")
+            .next()
+            .ok_or_else(|| "only tilesets with a single image are supported".to_owned())?;
+        let source: String = attr(image, "source")?;
+        Ok(Tileset {
+            name: attr_or(node, "name", String::new())?,
+            first_gid,
+            tile_width: attr(node, "tilewidth")?,
+            tile_height: attr(node, "tileheight")?,
+            tile_count: attr(node, "tilecount")?,
+            columns: attr(node, "columns")?,
+            spacing: attr_or(node, "spacing", 0)?,
+            margin: attr_or(node, "margin", 0)?,
+            image: dir.join(source),
+        })
+    }
+
+    fn parse_layers(&mut self, parent: Node) -> Result<(), String> {
+        for node in parent.children().filter(|n| n.is_element()) {
+            match node.tag_name().name() {
+                "layer" => {
+                    let name = attr_or(node, "name", String::new())?;
+                    let layer = self.parse_tile_layer(node)?;
+                    self.tile_layers.push((name, layer));
+                }
+                "objectgroup" => {
+                    let objects = children(node, "object")
+                        .map(Self::parse_object)
+                        .collect::<Result<_, _>>()?;
+                    self.object_layers.push(ObjectLayer {
+                        name: attr_or(node, "name", String::new())?,
+                        objects,
+                    });
+                }
+                "group" => self.parse_layers(node)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_tile_layer(&self, node: Node) -> Result<TileLayer, String> {
+        let width = attr(node, "width")?;
+        let height = attr(node, "height")?;
+        let data = children(node, "data")
+            .next()
+            .ok_or_else(|| "tile layer has no <data>".to_owned())?;
+        let tiles = match data.attribute("encoding") {
+            Some("csv") => data
+                .text()
+                .unwrap_or("")
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse().map_err(|_| format!("invalid tile \"{s}\"")))
+                .collect::<Result<Vec<u32>, _>>()?,
+            Some("base64") => {
+                if let Some(compression) = data.attribute("compression") {
+                    return Err(format!(
+                        "{compression} compressed layers aren't supported; use csv or uncompressed base64"
+                    ));
+                }
+                decode_base64(data.text().unwrap_or(""))?
+                    .chunks_exact(4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect()
+            }
+            Some(other) => return Err(format!("unknown tile encoding \"{other}\"")),
+            None => children(data, "tile")
+                .map(|t| attr_or(t, "gid", EMPTY_TILE))
+                .collect::<Result<_, _>>()?,
+        };
+        TileLayer::from_tiles(
+            width,
+            height,
+            self.tile_width as f32,
+            self.tile_height as f32,
+            tiles,
+        )
+    }
+
+    fn parse_object(node: Node) -> Result<MapObject, String> {
+        let x = attr_or(node, "x", 0.)?;
+        let y = attr_or(node, "y", 0.)?;
+        let width = attr_or(node, "width", 0.)?;
+        let height = attr_or(node, "height", 0.)?;
+        let tile = node
+            .attribute("gid")
+            .map(|_| attr(node, "gid"))
+            .transpose()?;
+        let origin = FPoint::new(x, y);
+        let element = |name| children(node, name).next();
+        let shape = if let Some(polygon) = element("polygon") {
+            ObjectShape::Polygon(parse_points(
+                attr::<String>(polygon, "points")?.as_str(),
+                origin,
+            )?)
+        } else if let Some(polyline) = element("polyline") {
+            ObjectShape::Polyline(parse_points(
+                attr::<String>(polyline, "points")?.as_str(),
+                origin,
+            )?)
+        } else if element("point").is_some() {
+            ObjectShape::Point(origin)
+        } else if element("ellipse").is_some() {
+            ObjectShape::Ellipse(FRect::new(x, y, width, height))
+        } else if tile.is_some() {
+            // tile objects are positioned by their bottom left corner
+            ObjectShape::Rect(FRect::new(x, y - height, width, height))
+        } else {
+            ObjectShape::Rect(FRect::new(x, y, width, height))
+        };
+        Ok(MapObject {
+            id: attr_or(node, "id", 0)?,
+            name: attr_or(node, "name", String::new())?,
+            class: node
+                .attribute("class")
+                .or_else(|| node.attribute("type"))
+                .unwrap_or("")
+                .to_owned(),
+            shape,
+            tile,
+            properties: properties(node),
+        })
+    }
+
+    /// the map as a .tmx file which tiled can open. tilesets are embedded,
+    /// with their images relative to dir. tile layers are written before
+    /// object layers, and groups aren't kept
+    pub fn to_tmx(&self, dir: &Path) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            out,
+            "<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" \
+             width=\"{}\" height=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" infinite=\"0\">",
+            self.width, self.height, self.tile_width, self.tile_height
+        );
+        write_properties(&mut out, " ", &self.properties);
+        for tileset in &self.tilesets {
+            let _ = writeln!(
+                out,
+                " <tileset firstgid=\"{}\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" \
+                 spacing=\"{}\" margin=\"{}\" tilecount=\"{}\" columns=\"{}\">",
+                tileset.first_gid,
+                escape_xml(&tileset.name),
+                tileset.tile_width,
+                tileset.tile_height,
+                tileset.spacing,
+                tileset.margin,
+                tileset.tile_count,
+                tileset.columns
+            );
+            let image = tileset.image.strip_prefix(dir).unwrap_or(&tileset.image);
+            let image = image.to_string_lossy().replace('\\', "/");
+            let _ = writeln!(out, "  <image source=\"{}\"/>", escape_xml(&image));
+            out.push_str(" </tileset>\n");
+        }
+        let mut layer_id = 0;
+        for (name, layer) in &self.tile_layers {
+            layer_id += 1;
+            let _ = writeln!(
+                out,
+                " <layer id=\"{}\" name=\"{}\" width=\"{}\" height=\"{}\">",
+                layer_id,
+                escape_xml(name),
+                layer.width(),
+                layer.height()
+            );
+            out.push_str("  <data encoding=\"csv\">\n");
+            let rows: Vec<String> = layer
+                .tiles()
+                .chunks(layer.width().max(1) as usize)
+                .map(|row| row.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+                .collect();
+            out.push_str(&rows.join(",\n"));
+            out.push_str("\n</data>\n </layer>\n");
+        }
+        for layer in &self.object_layers {
+            layer_id += 1;
+            let _ = writeln!(
+                out,
+                " <objectgroup id=\"{}\" name=\"{}\">",
+                layer_id,
+                escape_xml(&layer.name)
+            );
+            for object in &layer.objects {
+                let _ = write!(
+                    out,
+                    "  <object id=\"{}\" name=\"{}\" class=\"{}\"",
+                    object.id,
+                    escape_xml(&object.name),
+                    escape_xml(&object.class)
+                );
+                if let Some(tile) = object.tile {
+                    let _ = write!(out, " gid=\"{}\"", tile);
+                }
+                let origin = object.position();
+                let (inner, size) = match &object.shape {
+                    ObjectShape::Rect(r) => (None, Some(r)),
+                    ObjectShape::Ellipse(r) => (Some("<ellipse/>".to_owned()), Some(r)),
+                    ObjectShape::Point(_) => (Some("<point/>".to_owned()), None),
+                    ObjectShape::Polygon(points) => (
+                        Some(format!(
+                            "<polygon points=\"{}\"/>",
+                            write_points(points, origin)
+                        )),
+                        None,
+                    ),
+                    ObjectShape::Polyline(points) => (
+                        Some(format!(
+                            "<polyline points=\"{}\"/>",
+                            write_points(points, origin)
+                        )),
+                        None,
+                    ),
+                };
+                // tile objects are positioned by their bottom left corner
+                let y = match (object.tile, size) {
+                    (Some(_), Some(r)) => r.y() + r.height(),
+                    _ => origin.y(),
+                };
+                let _ = write!(out, " x=\"{}\" y=\"{}\"", origin.x(), y);
+                if let Some(r) = size {
+                    let _ = write!(out, " width=\"{}\" height=\"{}\"", r.width(), r.height());
+                }
+                out.push_str(">\n");
+                if let Some(inner) = inner {
+                    let _ = writeln!(out, "   {inner}");
+                }
+                write_properties(&mut out, "   ", &object.properties);
+                out.push_str("  </object>\n");
+            }
+            out.push_str(" </objectgroup>\n");
+        }
+        out.push_str("</map>\n");
+        out
+    }
+
+    /// write the map as a .tmx file, with tileset images relative to it
+    pub fn save_file(&self, path: &Path) -> Result<(), String> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        write_atomic(path, self.to_tmx(dir).as_bytes())
+    }
+
+    /// spawn each object whose class is a prefab in the world's registry.
+    /// the object's position is given as the x and y overrides, along with
+    /// its properties (parsed as toml values where they can be)
+    pub fn spawn_objects(&self, world: &mut World) -> Result<Vec<EntityId>, String> {
+        let mut spawned = Vec::new();
+        for object in self.objects() {
+            if world.prefabs.get(&object.class).is_none() {
+                continue;
+            }
+            let mut overrides: toml::Table = object
+                .properties
+                .iter()
+                .map(|(name, value)| (name.clone(), parse_override(value)))
+                .collect();
+            let position = object.position();
+            overrides.insert("x".to_owned(), toml::Value::Float(position.x() as f64));
+            overrides.insert("y".to_owned(), toml::Value::Float(position.y() as f64));
+            spawned.push(world.spawn_prefab(&object.class, &overrides)?);
+        }
+        Ok(spawned)
+    }
+
+    pub fn tile_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.tile_layers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, layer)| layer)
+    }
+
+    pub fn object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.object_layers.iter().find(|l| l.name == name)
+    }
+
+    /// objects from every object layer
+    pub fn objects(&self) -> impl Iterator<Item = &MapObject> {
+        self.object_layers.iter().flat_map(|l| l.objects.iter())
+    }
+
+    /// objects from every object layer with the class, e.g. "spawn"
+    pub fn objects_with_class<'a>(&'a self, class: &'a str) -> impl Iterator<Item = &'a MapObject> {
+        self.objects().filter(move |o| o.class == class)
+    }
+
+    pub fn tileset_for(&self, tile: u32) -> Option<&Tileset> {
+        self.tilesets.iter().find(|t| t.contains(tile))
+    }
+
+    /// load every tileset image into the window's texture cache ahead of time
+    pub fn load_textures(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+    ) -> Result<(), String> {
+        for tileset in &self.tilesets {
+            system.texture(window_name, &tileset.image)?;
+        }
+        Ok(())
+    }
+
+    /// draw the tiles of the layer which are in view. tileset images go
+    /// through the texture cache
+    pub fn draw_layer(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        layer: &TileLayer,
+        camera: &Camera2D,
+    ) -> Result<(), String> {
+        let Some((x0, y0, x1, y1)) = layer.tiles_in(camera.visible_region()) else {
+            return Ok(());
+        };
+        let draws: Vec<TileDraw> = (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .filter_map(|(x, y)| self.tile_draw(layer, x, y))
+            .collect();
+        self.draw_tiles(system, window_name, camera, &draws)
+    }
+
+    /// draw_layer, but with the tiles looked up through the cache, which is
+    /// patched with the layer's changes first
+    pub fn draw_layer_cached(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        layer: &TileLayer,
+        camera: &Camera2D,
+        cache: &mut LayerCache,
+    ) -> Result<(), String> {
+        cache.update(self, layer);
+        let Some((x0, y0, x1, y1)) = layer.tiles_in(camera.visible_region()) else {
+            return Ok(());
+        };
+        let width = layer.width() as usize;
+        let draws: Vec<TileDraw> = (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .filter_map(|(x, y)| cache.tiles[y as usize * width + x as usize])
+            .collect();
+        self.draw_tiles(system, window_name, camera, &draws)
+    }
+
+    fn tile_draw(&self, layer: &TileLayer, x: i32, y: i32) -> Option<TileDraw> {
+        let tile = layer.get(x, y)?;
+        let (tileset, src) = self
+            .tilesets
+            .iter()
+            .enumerate()
+            .find_map(|(i, tileset)| Some((i, tileset.source_rect(tile)?)))?;
+        let rect = layer.tile_rect(x, y);
+        // tiles wider or taller than the grid extend up and right
+        let (w, h) = (src.width() as f32, src.height() as f32);
+        let bottom_left = FPoint::new(rect.left(), rect.top() + rect.height());
+        Some(TileDraw {
+            tileset,
+            src,
+            center: bottom_left + FPoint::new(w / 2., -h / 2.),
+            flip_horizontal: tile & FLIPPED_HORIZONTALLY != 0,
+            flip_vertical: tile & FLIPPED_VERTICALLY != 0,
+        })
+    }
+
+    /// batched by tileset
+    fn draw_tiles(
+        &self,
+        system: &mut ChimericSystem,
+        window_name: &str,
+        camera: &Camera2D,
+        draws: &[TileDraw],
+    ) -> Result<(), String> {
+        for (i, tileset) in self.tilesets.iter().enumerate() {
+            let copies: Vec<CopyStructExF> = draws
+                .iter()
+                .filter(|draw| draw.tileset == i)
+                .map(|draw| draw.copy(camera))
+                .collect();
+            if !copies.is_empty() {
+                system.copy_many_ex_f(window_name, &tileset.image, copies.into_iter())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties><property name="music" value="cave.ogg"/></properties>
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" spacing="1" margin="2" tilecount="8" columns="4">
+  <image source="terrain.png" width="70" height="36"/>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,0,
+0,6,2147483649
+</data>
+ </layer>
+ <group name="g">
+  <layer id="2" name="decor" width="3" height="2">
+   <data encoding="base64">AQAAAAAAAAAAAAAAAAAAAAAAAAAIAAAA</data>
+  </layer>
+ </group>
+ <objectgroup id="3" name="objects">
+  <object id="1" name="start" type="spawn" x="8" y="24"><point/></object>
+  <object id="2" class="collider" x="0" y="0" width="48" height="8"/>
+  <object id="3" class="collider" x="10" y="10"><polygon points="0,0 5,0 0,5"/></object>
+ </objectgroup>
+</map>"#;
+
+    #[test]
+    fn test_parse() {
+        let map = TiledMap::load_str(MAP, Path::new("maps")).unwrap();
+        assert_eq!(map.properties["music"], "cave.ogg");
+        let tileset = &map.tilesets[0];
+        assert_eq!(tileset.image, Path::new("maps/terrain.png"));
+        assert_eq!(tileset.source_rect(6), Some(Rect::new(19, 19, 16, 16)));
+        assert_eq!(tileset.source_rect(9), None);
+
+        let ground = map.tile_layer("ground").unwrap();
+        assert_eq!(ground.get(1, 1), Some(6));
+        let flipped = ground.get(2, 1).unwrap();
+        assert_eq!(tile_gid(flipped), 1);
+        assert!(flipped & FLIPPED_HORIZONTALLY != 0);
+        assert_eq!(
+            map.tile_layer("decor").unwrap().tiles(),
+            &[1, 0, 0, 0, 0, 8]
+        );
+
+        let spawn = map.objects_with_class("spawn").next().unwrap();
+        assert_eq!(spawn.shape, ObjectShape::Point(FPoint::new(8., 24.)));
+        let colliders: Vec<_> = map.objects_with_class("collider").collect();
+        assert_eq!(colliders.len(), 2);
+        assert_eq!(
+            colliders[1].shape,
+            ObjectShape::Polygon(vec![
+                FPoint::new(10., 10.),
+                FPoint::new(15., 10.),
+                FPoint::new(10., 15.)
+            ])
+        );
+
+        // written maps load back the same
+        let written = map.to_tmx(Path::new("maps"));
+        let reloaded = TiledMap::load_str(&written, Path::new("maps")).unwrap();
+        assert_eq!(reloaded.tilesets[0].image, tileset.image);
+        assert_eq!(reloaded.properties, map.properties);
+        for ((name, layer), (reloaded_name, reloaded_layer)) in
+            map.tile_layers.iter().zip(reloaded.tile_layers.iter())
+        {
+            assert_eq!(name, reloaded_name);
+            assert_eq!(layer.tiles(), reloaded_layer.tiles());
+        }
+        let shapes = |map: &TiledMap| -> Vec<(String, ObjectShape)> {
+            map.objects()
+                .map(|o| (o.class.clone(), o.shape.clone()))
+                .collect()
+        };
+        assert_eq!(shapes(&reloaded), shapes(&map));
+    }
+
+    #[test]
+    fn test_layer_cache() {
+        let map = TiledMap::load_str(MAP, Path::new("maps")).unwrap();
+        let mut layer = map.tile_layer("ground").unwrap().clone();
+        let mut cache = LayerCache::new();
+        cache.update(&map, &layer);
+        assert_eq!(
+            cache.tiles[1].map(|d| d.src),
+            map.tilesets[0].source_rect(2)
+        );
+        assert!(cache.tiles[2].is_none());
+
+        layer.explode(FPoint::new(24., 8.), 4.);
+        layer.set(2, 0, 3);
+        cache.update(&map, &layer);
+        let mut rebuilt = LayerCache::new();
+        rebuilt.update(&map, &layer);
+        assert_eq!(cache.tiles, rebuilt.tiles);
+        assert!(cache.tiles[1].is_none());
+        assert_eq!(
+            cache.tiles[2].map(|d| d.src),
+            map.tilesets[0].source_rect(3)
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use sdl2::rect::{FPoint, FRect};
+
+/// tile index used for cells with nothing in them
+pub const EMPTY_TILE: u32 = 0;
+
+/// changed cells a layer remembers for changes_since
+const MAX_CHANGES: usize = 4096;
+
+static NEXT_LAYER_ID: AtomicU64 = AtomicU64::new(0);
+
+fn next_layer_id() -> u64 {
+    NEXT_LAYER_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// which layer, and how far along its changes, something derived from a layer
+/// is of. every layer starts at revision zero, so caches compare the whole
+/// version to tell e.g. the next level's layer from the one they were built
+/// from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerVersion {
+    pub layer: u64,
+    pub revision: u64,
+}
+
+/// grid of tile indices, positioned with its top left corner at the origin.
+/// clones are separate layers, with their own id
+#[derive(Debug)]
+pub struct TileLayer {
+    id: u64,
+    width: u32,
+    height: u32,
+    tile_width: f32,
+    tile_height: f32,
+    /// row major
+    tiles: Vec<u32>,
+    /// bumped on each change
+    revision: u64,
+    /// the cell of each of the latest changes, oldest first
+    changes: VecDeque<(i32, i32)>,
+}
+
+impl TileLayer {
+    /// all tiles start empty
+    pub fn new(width: u32, height: u32, tile_width: f32, tile_height: f32) -> Self {
+        Self {
+            width,
+            height,
+            tile_width,
+            tile_height,
+            id: next_layer_id(),
+            tiles: vec![EMPTY_TILE; width as usize * height as usize],
+            revision: 0,
+            changes: Default::default(),
+        }
+    }
+
+    /// tiles are row major
+    pub fn from_tiles(
+        width: u32,
+        height: u32,
+        tile_width: f32,
+        tile_height: f32,
+        tiles: Vec<u32>,
+    ) -> Result<Self, String> {
+        if tiles.len() != width as usize * height as usize {
+            return Err(format!(
+                "tile layer of {width}x{height} can't be created from {} tiles",
+                tiles.len()
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            tile_width,
+            tile_height,
+            id: next_layer_id(),
+            tiles,
+            revision: 0,
+            changes: Default::default(),
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn tile_width(&self) -> f32 {
+        self.tile_width
+    }
+
+    pub fn tile_height(&self) -> f32 {
+        self.tile_height
+    }
+
+    pub fn tiles(&self) -> &[u32] {
+        &self.tiles
+    }
+
+    /// changes whenever a tile does, for keeping things derived from the
+    /// layer (e.g. a Minimap) up to date
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// unique among the layers created by this process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn version(&self) -> LayerVersion {
+        LayerVersion {
+            layer: self.id,
+            revision: self.revision,
+        }
+    }
+
+    /// the cells changed after the version, so things derived from the layer
+    /// can be patched instead of rebuilt. may repeat cells. None if the
+    /// version is of another layer, or too much changed since (or it's newer),
+    /// in which case rebuild
+    pub fn changes_since(&self, version: LayerVersion) -> Option<Vec<(i32, i32)>> {
+        if version.layer != self.id {
+            return None;
+        }
+        let count = usize::try_from(self.revision.checked_sub(version.revision)?).ok()?;
+        if count > self.changes.len() {
+            return None;
+        }
+        Some(
+            self.changes
+                .range(self.changes.len() - count..)
+                .copied()
+                .collect(),
+        )
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width as usize + x as usize)
+    }
+
+    /// None if out of bounds
+    pub fn get(&self, x: i32, y: i32) -> Option<u32> {
+        self.index(x, y).map(|i| self.tiles[i])
+    }
+
+    /// returns the previous tile, or None (and does nothing) if out of bounds
+    pub fn set(&mut self, x: i32, y: i32, tile: u32) -> Option<u32> {
+        let previous = self
+            .index(x, y)
+            .map(|i| std::mem::replace(&mut self.tiles[i], tile));
+        if previous.is_some_and(|previous| previous != tile) {
+            self.revision += 1;
+            if self.changes.len() == MAX_CHANGES {
+                self.changes.pop_front();
+            }
+            self.changes.push_back((x, y));
+        }
+        previous
+    }
+
+    /// the cells whose centers are within the radius of the point
+    pub fn cells_in_radius(&self, center: FPoint, radius: f32) -> Vec<(i32, i32)> {
+        let region = FRect::new(
+            center.x() - radius,
+            center.y() - radius,
+            radius * 2.,
+            radius * 2.,
+        );
+        let Some((x0, y0, x1, y1)) = self.tiles_in(region) else {
+            return Vec::new();
+        };
+        (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                let rect = self.tile_rect(x, y);
+                let dx = rect.x() + rect.width() / 2. - center.x();
+                let dy = rect.y() + rect.height() / 2. - center.y();
+                dx * dx + dy * dy <= radius * radius
+            })
+            .collect()
+    }
+
+    /// empty the cells whose centers are within the radius of the point.
+    /// returns each cell which wasn't empty and its tile, e.g. for debris
+    pub fn explode(&mut self, center: FPoint, radius: f32) -> Vec<((i32, i32), u32)> {
+        self.cells_in_radius(center, radius)
+            .into_iter()
+            .filter_map(|(x, y)| match self.set(x, y, EMPTY_TILE)? {
+                EMPTY_TILE => None,
+                tile => Some(((x, y), tile)),
+            })
+            .collect()
+    }
+
+    /// true if in bounds and not empty
+    pub fn is_occupied(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_some_and(|tile| tile != EMPTY_TILE)
+    }
+
+    /// the tile coordinate containing the point. may be out of bounds
+    pub fn tile_at(&self, point: FPoint) -> (i32, i32) {
+        (
+            (point.x() / self.tile_width).floor() as i32,
+            (point.y() / self.tile_height).floor() as i32,
+        )
+    }
+
+    /// the area covered by a tile coordinate
+    pub fn tile_rect(&self, x: i32, y: i32) -> FRect {
+        FRect::new(
+            x as f32 * self.tile_width,
+            y as f32 * self.tile_height,
+            self.tile_width,
+            self.tile_height,
+        )
+    }
+
+    /// tile coordinates (x0, y0, x1, y1), inclusive and clamped to the layer,
+    /// which overlap the region. None if no tiles overlap
+    pub fn tiles_in(&self, region: FRect) -> Option<(i32, i32, i32, i32)> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let (x0, y0) = self.tile_at(FPoint::new(region.left(), region.top()));
+        let (x1, y1) = self.tile_at(FPoint::new(region.right(), region.bottom()));
+        let x0 = x0.max(0);
+        let y0 = y0.max(0);
+        let x1 = x1.min(self.width as i32 - 1);
+        let y1 = y1.min(self.height as i32 - 1);
+        if x0 > x1 || y0 > y1 {
+            return None;
+        }
+        Some((x0, y0, x1, y1))
+    }
+}
+
+impl Clone for TileLayer {
+    fn clone(&self) -> Self {
+        Self {
+            id: next_layer_id(),
+            width: self.width,
+            height: self.height,
+            tile_width: self.tile_width,
+            tile_height: self.tile_height,
+            tiles: self.tiles.clone(),
+            revision: self.revision,
+            changes: self.changes.clone(),
+        }
+    }
+}
+
+/// neighbour bits of an autotile mask, set where the neighbour is the same
+/// terrain
+pub const NORTH: u8 = 1;
+pub const NORTH_EAST: u8 = 2;
+pub const EAST: u8 = 4;
+pub const SOUTH_EAST: u8 = 8;
+pub const SOUTH: u8 = 16;
+pub const SOUTH_WEST: u8 = 32;
+pub const WEST: u8 = 64;
+pub const NORTH_WEST: u8 = 128;
+
+/// (dx, dy, bit) for each neighbour
+const NEIGHBOURS: [(i32, i32, u8); 8] = [
+    (0, -1, NORTH),
+    (1, -1, NORTH_EAST),
+    (1, 0, EAST),
+    (1, 1, SOUTH_EAST),
+    (0, 1, SOUTH),
+    (-1, 1, SOUTH_WEST),
+    (-1, 0, WEST),
+    (-1, -1, NORTH_WEST),
+];
+
+/// which neighbours pick a terrain's tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutotileKind {
+    /// the four edges only: 16 tiles (a wang edge set)
+    Edges,
+    /// edges and corners, where a corner only counts if both edges beside it
+    /// do: 47 tiles
+    Blob,
+}
+
+impl AutotileKind {
+    /// drop the bits this kind ignores
+    pub fn reduce(self, mask: u8) -> u8 {
+        let edges = mask & (NORTH | EAST | SOUTH | WEST);
+        if self == AutotileKind::Edges {
+            return edges;
+        }
+        let mut reduced = edges;
+        for (corner, a, b) in [
+            (NORTH_EAST, NORTH, EAST),
+            (SOUTH_EAST, SOUTH, EAST),
+            (SOUTH_WEST, SOUTH, WEST),
+            (NORTH_WEST, NORTH, WEST),
+        ] {
+            if mask & corner != 0 && edges & a != 0 && edges & b != 0 {
+                reduced |= corner;
+            }
+        }
+        reduced
+    }
+
+    /// every distinct reduced mask, ascending
+    pub fn masks(self) -> Vec<u8> {
+        let mut masks: Vec<u8> = (0..=255).map(|mask| self.reduce(mask)).collect();
+        masks.sort_unstable();
+        masks.dedup();
+        masks
+    }
+}
+
+/// a terrain whose tiles are chosen from its neighbours
+#[derive(Debug, Clone)]
+pub struct Terrain {
+    pub name: String,
+    pub kind: AutotileKind,
+    /// the tile for each reduced mask
+    pub tiles: BTreeMap<u8, u32>,
+    /// used for masks with no tile
+    pub fallback: u32,
+}
+
+impl Terrain {
+    pub fn new(name: &str, kind: AutotileKind, fallback: u32) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind,
+            tiles: BTreeMap::new(),
+            fallback,
+        }
+    }
+
+    /// a terrain whose tiles are consecutive from first, one per mask in the
+    /// order of AutotileKind::masks. the fallback is the fully surrounded tile
+    pub fn sequential(name: &str, kind: AutotileKind, first: u32) -> Self {
+        let masks = kind.masks();
+        let mut terrain = Self::new(name, kind, first + masks.len() as u32 - 1);
+        for (i, mask) in masks.into_iter().enumerate() {
+            terrain.tiles.insert(mask, first + i as u32);
+        }
+        terrain
+    }
+
+    /// set the tile for a mask, which is reduced first
+    pub fn with_tile(mut self, mask: u8, tile: u32) -> Self {
+        self.tiles.insert(self.kind.reduce(mask), tile);
+        self
+    }
+
+    pub fn tile(&self, mask: u8) -> u32 {
+        self.tiles
+            .get(&self.kind.reduce(mask))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+
+    pub fn contains(&self, tile: u32) -> bool {
+        tile == self.fallback || self.tiles.values().any(|t| *t == tile)
+    }
+}
+
+/// picks edge and corner tiles for terrain painted into a TileLayer, e.g. by
+/// the Editor or procgen. cells are in a terrain if their tile is one of its
+/// tiles; out of bounds neighbours count as the same terrain, so terrain
+/// continues off the edge of the layer
+#[derive(Debug, Clone, Default)]
+pub struct Autotiler {
+    pub terrains: Vec<Terrain>,
+}
+
+impl Autotiler {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_terrain(mut self, terrain: Terrain) -> Self {
+        self.terrains.push(terrain);
+        self
+    }
+
+    /// the index of the terrain the tile belongs to
+    pub fn terrain_of(&self, tile: u32) -> Option<usize> {
+        if tile == EMPTY_TILE {
+            return None;
+        }
+        self.terrains.iter().position(|t| t.contains(tile))
+    }
+
+    /// the neighbours of the cell which are in the terrain
+    pub fn mask(&self, layer: &TileLayer, x: i32, y: i32, terrain: usize) -> u8 {
+        NEIGHBOURS
+            .iter()
+            .filter(|(dx, dy, _)| match layer.get(x + dx, y + dy) {
+                Some(tile) => self.terrain_of(tile) == Some(terrain),
+                None => true,
+            })
+            .fold(0, |mask, (_, _, bit)| mask | bit)
+    }
+
+    /// pick the cell's tile from its neighbours, if it's in a terrain
+    pub fn update_tile(&self, layer: &mut TileLayer, x: i32, y: i32) {
+        let Some(terrain) = layer.get(x, y).and_then(|tile| self.terrain_of(tile)) else {
+            return;
+        };
+        let tile = self.terrains[terrain].tile(self.mask(layer, x, y, terrain));
+        layer.set(x, y, tile);
+    }
+
+    fn update_around(&self, layer: &mut TileLayer, x: i32, y: i32) {
+        self.update_tile(layer, x, y);
+        for (dx, dy, _) in NEIGHBOURS {
+            self.update_tile(layer, x + dx, y + dy);
+        }
+    }
+
+    /// set the cell to the terrain and update it and its neighbours. returns
+    /// false (and does nothing) if out of bounds
+    pub fn paint(&self, layer: &mut TileLayer, x: i32, y: i32, terrain: usize) -> bool {
+        if layer.set(x, y, self.terrains[terrain].fallback).is_none() {
+            return false;
+        }
+        self.update_around(layer, x, y);
+        true
+    }
+
+    /// set the cell to a tile (e.g. EMPTY_TILE) and update its neighbours.
+    /// returns false (and does nothing) if out of bounds
+    pub fn set(&self, layer: &mut TileLayer, x: i32, y: i32, tile: u32) -> bool {
+        if layer.set(x, y, tile).is_none() {
+            return false;
+        }
+        self.update_around(layer, x, y);
+        true
+    }
+
+    /// empty the cells whose centers are within the radius of the point and
+    /// update the terrain around the hole. returns the removed tiles, like
+    /// TileLayer::explode
+    pub fn explode(
+        &self,
+        layer: &mut TileLayer,
+        center: FPoint,
+        radius: f32,
+    ) -> Vec<((i32, i32), u32)> {
+        let removed = layer.explode(center, radius);
+        for &((x, y), _) in &removed {
+            for (dx, dy, _) in NEIGHBOURS {
+                self.update_tile(layer, x + dx, y + dy);
+            }
+        }
+        removed
+    }
+
+    /// update every cell, e.g. after a generator fills in one tile per
+    /// terrain
+    pub fn apply(&self, layer: &mut TileLayer) {
+        for y in 0..layer.height() as i32 {
+            for x in 0..layer.width() as i32 {
+                self.update_tile(layer, x, y);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autotile() {
+        assert_eq!(AutotileKind::Edges.masks().len(), 16);
+        assert_eq!(AutotileKind::Blob.masks().len(), 47);
+        assert_eq!(AutotileKind::Blob.reduce(NORTH | NORTH_EAST), NORTH);
+
+        let grass = Terrain::sequential("grass", AutotileKind::Edges, 1);
+        let autotiler = Autotiler::new().with_terrain(grass);
+        let mut layer = TileLayer::new(4, 3, 16., 16.);
+        assert!(autotiler.paint(&mut layer, 1, 1, 0));
+        assert!(!autotiler.paint(&mut layer, 9, 1, 0));
+        // alone, so no neighbours
+        assert_eq!(layer.get(1, 1), Some(1));
+        autotiler.paint(&mut layer, 2, 1, 0);
+        // masks 4 (east) and 64 (west) are the third and ninth
+        assert_eq!(layer.get(1, 1), Some(3));
+        assert_eq!(layer.get(2, 1), Some(9));
+        // the right edge continues off the layer
+        autotiler.paint(&mut layer, 3, 1, 0);
+        assert_eq!(layer.get(2, 1), Some(11));
+        assert_eq!(layer.get(3, 1), Some(11));
+        autotiler.set(&mut layer, 2, 1, EMPTY_TILE);
+        assert_eq!(layer.get(1, 1), Some(1));
+        assert_eq!(layer.get(3, 1), Some(3));
+
+        // a generator's placeholder tiles
+        let mut layer = TileLayer::from_tiles(2, 2, 16., 16., vec![16, 0, 16, 16]).unwrap();
+        autotiler.apply(&mut layer);
+        assert_eq!(layer.tiles(), &[14, 0, 16, 15]);
+    }
+
+    #[test]
+    fn test_explode() {
+        let grass = Terrain::sequential("grass", AutotileKind::Edges, 1);
+        let autotiler = Autotiler::new().with_terrain(grass);
+        let mut layer = TileLayer::new(4, 3, 16., 16.);
+        autotiler.paint(&mut layer, 1, 1, 0);
+        autotiler.paint(&mut layer, 3, 1, 0);
+        // the hole's edges are retiled
+        let removed = autotiler.explode(&mut layer, FPoint::new(24., 24.), 8.);
+        assert_eq!(removed, [((1, 1), 1)]);
+        assert_eq!(layer.get(1, 1), Some(EMPTY_TILE));
+        assert!(layer.explode(FPoint::new(24., 24.), 8.).is_empty());
+        assert_eq!(layer.cells_in_radius(FPoint::new(32., 24.), 8.).len(), 2);
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let mut layer = TileLayer::new(4, 3, 16., 16.);
+        layer.set(0, 0, 1);
+        let version = layer.version();
+        layer.set(2, 1, 1);
+        layer.set(2, 1, 1);
+        layer.explode(FPoint::new(8., 8.), 4.);
+        assert_eq!(layer.changes_since(version), Some(vec![(2, 1), (0, 0)]));
+        assert_eq!(layer.changes_since(layer.version()), Some(vec![]));
+        let newer = LayerVersion {
+            revision: layer.revision() + 1,
+            ..layer.version()
+        };
+        assert_eq!(layer.changes_since(newer), None);
+        let start = LayerVersion {
+            revision: 0,
+            ..layer.version()
+        };
+        assert_eq!(layer.changes_since(start).map(|c| c.len()), Some(3));
+
+        // other layers (including clones) at the same revision aren't patched
+        let other = TileLayer::new(4, 3, 16., 16.);
+        assert_eq!(other.revision(), start.revision);
+        assert_eq!(other.changes_since(start), None);
+        let clone = layer.clone();
+        assert_ne!(clone.id(), layer.id());
+        assert_eq!(clone.changes_since(version), None);
+    }
+}
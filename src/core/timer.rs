@@ -0,0 +1,150 @@
+use std::{cell::RefCell, collections::HashSet, rc::Rc, time::Duration};
+
+use super::world::World;
+
+pub type TimerCallback = Box<dyn FnMut(&mut World) -> Result<(), String>>;
+
+/// identifies a scheduled callback so it can be cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TimerHandle(u64);
+
+#[derive(Clone)]
+pub(crate) struct Timer {
+    handle: TimerHandle,
+    /// simulated time at which it fires
+    due: Duration,
+    /// repeats if set
+    period: Option<Duration>,
+    /// shared with copies of the timers saved for rollback
+    pub(crate) callback: Rc<RefCell<TimerCallback>>,
+}
+
+impl Timer {
+    pub(crate) fn handle(&self) -> TimerHandle {
+        self.handle
+    }
+}
+
+/// callbacks scheduled against the world's simulated clock, which advances by
+/// one timestep per update. clones share their callbacks
+#[derive(Default, Clone)]
+pub struct Timers {
+    now: Duration,
+    next_handle: u64,
+    scheduled: Vec<Timer>,
+    /// taken out of scheduled to be fired this update
+    firing: HashSet<TimerHandle>,
+    /// subset of firing which was cancelled while firing
+    cancelled: HashSet<TimerHandle>,
+}
+
+impl Timers {
+    /// total simulated time
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn schedule(
+        &mut self,
+        delay: Duration,
+        period: Option<Duration>,
+        callback: TimerCallback,
+    ) -> TimerHandle {
+        debug_assert!(period.is_none_or(|period| !period.is_zero()));
+        let handle = TimerHandle(self.next_handle);
+        self.next_handle += 1;
+        self.scheduled.push(Timer {
+            handle,
+            due: self.now + delay,
+            period,
+            callback: Rc::new(RefCell::new(callback)),
+        });
+        handle
+    }
+
+    /// returns false if the timer already finished or was already cancelled
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        if let Some(i) = self.scheduled.iter().position(|t| t.handle == handle) {
+            self.scheduled.swap_remove(i);
+            return true;
+        }
+        self.firing.contains(&handle) && self.cancelled.insert(handle)
+    }
+
+    pub fn is_scheduled(&self, handle: TimerHandle) -> bool {
+        self.scheduled.iter().any(|t| t.handle == handle)
+            || (self.firing.contains(&handle) && !self.cancelled.contains(&handle))
+    }
+
+    pub(crate) fn is_cancelled(&self, handle: TimerHandle) -> bool {
+        self.cancelled.contains(&handle)
+    }
+
+    /// advance the clock, and take out the timers which are now due, in the
+    /// order they should be fired. each must be given back with put_back
+    pub(crate) fn advance(&mut self, timestep: Duration) -> Vec<Timer> {
+        self.now += timestep;
+        let now = self.now;
+        let (mut due, scheduled): (Vec<Timer>, Vec<Timer>) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition(|t| t.due <= now);
+        self.scheduled = scheduled;
+        due.sort_by_key(|t| (t.due, t.handle));
+        self.firing.extend(due.iter().map(|t| t.handle));
+        due
+    }
+
+    /// return a timer taken out by advance. periodic timers which were fired
+    /// are rescheduled, and timers which weren't fired are kept as is
+    pub(crate) fn put_back(&mut self, mut timer: Timer, fired: bool) {
+        self.firing.remove(&timer.handle);
+        if self.cancelled.remove(&timer.handle) {
+            return;
+        }
+        if fired {
+            match timer.period {
+                Some(period) => timer.due += period,
+                None => return,
+            }
+        }
+        self.scheduled.push(timer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    #[test]
+    fn test_after_every_cancel() {
+        let mut world = World::new();
+        let timestep = world.timestep();
+        let fired_once = Rc::new(Cell::new(0));
+        let fired_repeat = Rc::new(Cell::new(0));
+
+        let counter = fired_once.clone();
+        world.after(timestep * 2, move |_| {
+            counter.set(counter.get() + 1);
+            Ok(())
+        });
+        let counter = fired_repeat.clone();
+        let repeating = world.every(timestep * 3, move |_| {
+            counter.set(counter.get() + 1);
+            Ok(())
+        });
+
+        for _ in 0..9 {
+            world.update().unwrap();
+        }
+        assert_eq!(fired_once.get(), 1);
+        assert_eq!(fired_repeat.get(), 3);
+        assert!(world.cancel(repeating));
+        assert!(!world.cancel(repeating));
+        for _ in 0..9 {
+            world.update().unwrap();
+        }
+        assert_eq!(fired_repeat.get(), 3);
+    }
+}
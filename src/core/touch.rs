@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+
+use sdl2::rect::FPoint;
+
+use super::events::EngineEvent;
+
+/// a gesture recognized from touch events. positions are in the window's
+/// logical coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gesture {
+    /// a single finger went down and up quickly without moving
+    Tap { window: String, position: FPoint },
+    /// a single finger moved
+    Drag {
+        window: String,
+        position: FPoint,
+        relative: FPoint,
+    },
+    /// two fingers moved. scale is the change in distance between them since
+    /// the previous pinch event; above 1 is spreading apart
+    Pinch {
+        window: String,
+        center: FPoint,
+        scale: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finger {
+    start: FPoint,
+    start_time: u32,
+    position: FPoint,
+    /// moved too far or was joined by another finger; can't be a tap
+    tap_cancelled: bool,
+}
+
+fn distance(a: FPoint, b: FPoint) -> f32 {
+    let d = a - b;
+    (d.x() * d.x() + d.y() * d.y()).sqrt()
+}
+
+/// recognizes taps, drags, and pinches. feed it every event
+#[derive(Debug, Clone)]
+pub struct GestureRecognizer {
+    /// keyed by (touch device, finger)
+    fingers: BTreeMap<(i64, i64), Finger>,
+    /// distance between the two fingers at the previous pinch
+    pinch_distance: Option<f32>,
+    /// ms. a tap held longer than this isn't a tap
+    pub tap_time: u32,
+    /// logical pixels. a finger which moves further than this isn't a tap
+    pub tap_distance: f32,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self {
+            fingers: Default::default(),
+            pinch_distance: None,
+            tap_time: 300,
+            tap_distance: 10.,
+        }
+    }
+}
+
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// the number of fingers currently down
+    pub fn num_fingers(&self) -> usize {
+        self.fingers.len()
+    }
+
+    fn two_fingers(&self) -> Option<(FPoint, FPoint)> {
+        if self.fingers.len() != 2 {
+            return None;
+        }
+        let mut fingers = self.fingers.values();
+        Some((fingers.next()?.position, fingers.next()?.position))
+    }
+
+    pub fn handle_event(&mut self, event: &EngineEvent) -> Option<Gesture> {
+        match event {
+            EngineEvent::TouchDown {
+                touch_id,
+                finger_id,
+                position,
+                timestamp,
+                ..
+            } => {
+                self.fingers.insert(
+                    (*touch_id, *finger_id),
+                    Finger {
+                        start: *position,
+                        start_time: *timestamp,
+                        position: *position,
+                        tap_cancelled: false,
+                    },
+                );
+                if self.fingers.len() > 1 {
+                    self.fingers
+                        .values_mut()
+                        .for_each(|f| f.tap_cancelled = true);
+                }
+                self.pinch_distance = self.two_fingers().map(|(a, b)| distance(a, b));
+                None
+            }
+            EngineEvent::TouchMotion {
+                window,
+                touch_id,
+                finger_id,
+                position,
+                relative,
+                ..
+            } => {
+                let finger = self.fingers.get_mut(&(*touch_id, *finger_id))?;
+                finger.position = *position;
+                if distance(finger.start, *position) > self.tap_distance {
+                    finger.tap_cancelled = true;
+                }
+                let dragging = finger.tap_cancelled;
+                match self.fingers.len() {
+                    1 if dragging => Some(Gesture::Drag {
+                        window: window.clone(),
+                        position: *position,
+                        relative: *relative,
+                    }),
+                    2 => {
+                        let (a, b) = self.two_fingers()?;
+                        let current = distance(a, b);
+                        let previous = self.pinch_distance.replace(current)?;
+                        if previous <= 0. {
+                            return None;
+                        }
+                        Some(Gesture::Pinch {
+                            window: window.clone(),
+                            center: (a + b) * 0.5,
+                            scale: current / previous,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            EngineEvent::TouchUp {
+                window,
+                touch_id,
+                finger_id,
+                position,
+                timestamp,
+                ..
+            } => {
+                let finger = self.fingers.remove(&(*touch_id, *finger_id))?;
+                self.pinch_distance = self.two_fingers().map(|(a, b)| distance(a, b));
+                let tap = !finger.tap_cancelled
+                    && distance(finger.start, *position) <= self.tap_distance
+                    && timestamp.wrapping_sub(finger.start_time) <= self.tap_time;
+                tap.then(|| Gesture::Tap {
+                    window: window.clone(),
+                    position: *position,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down(finger_id: i64, x: f32, y: f32, timestamp: u32) -> EngineEvent {
+        EngineEvent::TouchDown {
+            window: "main".to_owned(),
+            touch_id: 0,
+            finger_id,
+            position: FPoint::new(x, y),
+            pressure: 1.,
+            timestamp,
+        }
+    }
+
+    fn up(finger_id: i64, x: f32, y: f32, timestamp: u32) -> EngineEvent {
+        EngineEvent::TouchUp {
+            window: "main".to_owned(),
+            touch_id: 0,
+            finger_id,
+            position: FPoint::new(x, y),
+            pressure: 0.,
+            timestamp,
+        }
+    }
+
+    fn motion(finger_id: i64, x: f32, y: f32, dx: f32, dy: f32) -> EngineEvent {
+        EngineEvent::TouchMotion {
+            window: "main".to_owned(),
+            touch_id: 0,
+            finger_id,
+            position: FPoint::new(x, y),
+            relative: FPoint::new(dx, dy),
+            pressure: 1.,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_gestures() {
+        let mut g = GestureRecognizer::new();
+        assert_eq!(g.handle_event(&down(1, 10., 10., 0)), None);
+        assert!(matches!(
+            g.handle_event(&up(1, 12., 10., 100)),
+            Some(Gesture::Tap { .. })
+        ));
+
+        // held too long
+        g.handle_event(&down(1, 10., 10., 0));
+        assert_eq!(g.handle_event(&up(1, 10., 10., 1000)), None);
+
+        g.handle_event(&down(1, 10., 10., 0));
+        assert!(matches!(
+            g.handle_event(&motion(1, 50., 10., 40., 0.)),
+            Some(Gesture::Drag { .. })
+        ));
+        assert_eq!(g.handle_event(&up(1, 50., 10., 50)), None);
+
+        g.handle_event(&down(1, 0., 0., 0));
+        g.handle_event(&down(2, 10., 0., 0));
+        match g.handle_event(&motion(2, 20., 0., 10., 0.)) {
+            Some(Gesture::Pinch { center, scale, .. }) => {
+                assert_eq!(scale, 2.);
+                assert_eq!(center, FPoint::new(10., 0.));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(g.handle_event(&up(2, 20., 0., 50)), None);
+        assert_eq!(g.handle_event(&up(1, 0., 0., 50)), None);
+        assert_eq!(g.num_fingers(), 0);
+    }
+}
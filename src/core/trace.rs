@@ -0,0 +1,30 @@
+// logging macros used across the engine. with the tracing feature they
+// forward to the tracing crate (install a subscriber, e.g. tracing-subscriber,
+// to see them). without it they compile to nothing, but arguments are still
+// type checked. either way, lines are kept for crash reports once those are
+// installed
+
+macro_rules! define_level {
+    ($name:ident, $level:ident, $d:tt) => {
+        // a level can go unused when the features that log at it are off
+        #[allow(unused_macros)]
+        macro_rules! $name {
+            ($d($d arg:tt)*) => {{
+                #[cfg(feature = "tracing")]
+                tracing::$level!($d($d arg)*);
+                #[cfg(not(feature = "tracing"))]
+                if false {
+                    let _ = format_args!($d($d arg)*);
+                }
+                $crate::core::crash::record_log(stringify!($level), || format!($d($d arg)*));
+            }};
+        }
+        #[allow(unused_imports)]
+        pub(crate) use $name;
+    };
+}
+
+define_level!(log_trace, trace, $);
+define_level!(log_debug, debug, $);
+define_level!(log_info, info, $);
+define_level!(log_warn, warn, $);
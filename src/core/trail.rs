@@ -0,0 +1,165 @@
+use std::{collections::VecDeque, time::Duration};
+
+use sdl2::{pixels::Color, rect::FPoint};
+
+use super::{frame_clock::EngineClock, renderer::Vertex, system::ChimericSystem};
+
+/// a ribbon following recent positions, e.g. for sword slashes, projectiles,
+/// and skid marks. it narrows and fades towards its oldest point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trail {
+    /// newest first, with their ages
+    points: VecDeque<(FPoint, Duration)>,
+    /// at the newest point
+    pub width: f32,
+    pub color: Color,
+    /// points are dropped once this old
+    pub lifetime: Duration,
+    /// positions closer than this to the newest point aren't recorded
+    pub min_distance: f32,
+    pub max_points: usize,
+}
+
+impl Trail {
+    pub fn new(width: f32, color: Color, lifetime: Duration) -> Self {
+        Self {
+            points: Default::default(),
+            width,
+            color,
+            lifetime,
+            min_distance: 2.,
+            max_points: 64,
+        }
+    }
+
+    /// record the position of whatever is leaving the trail
+    pub fn push(&mut self, position: FPoint) {
+        if let Some((newest, _)) = self.points.front() {
+            let (dx, dy) = (position.x() - newest.x(), position.y() - newest.y());
+            if dx.hypot(dy) < self.min_distance {
+                return;
+            }
+        }
+        self.points.push_front((position, Duration::ZERO));
+        self.points.truncate(self.max_points);
+    }
+
+    /// age the points, dropping expired ones
+    pub fn update(&mut self, dt: Duration) {
+        for (_, age) in self.points.iter_mut() {
+            *age += dt;
+        }
+        while self
+            .points
+            .back()
+            .is_some_and(|(_, age)| *age >= self.lifetime)
+        {
+            self.points.pop_back();
+        }
+    }
+
+    /// age the points by the clock's last delta
+    pub fn update_with(&mut self, clock: &EngineClock) {
+        self.update(clock.delta());
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// newest first
+    pub fn points(&self) -> impl Iterator<Item = FPoint> + '_ {
+        self.points.iter().map(|(point, _)| *point)
+    }
+
+    /// a triangle strip along the points, two vertices per point. empty with
+    /// fewer than two points
+    pub fn mesh(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let len = self.points.len();
+        if len < 2 {
+            return Default::default();
+        }
+        let mut vertices = Vec::with_capacity(len * 2);
+        for i in 0..len {
+            let (point, age) = self.points[i];
+            // along the trail, from the neighbours
+            let before = self.points[i.saturating_sub(1)].0;
+            let after = self.points[(i + 1).min(len - 1)].0;
+            let (dx, dy) = (after.x() - before.x(), after.y() - before.y());
+            let length = dx.hypot(dy).max(f32::EPSILON);
+            let normal = (-dy / length, dx / length);
+
+            let life = if self.lifetime.is_zero() {
+                0.
+            } else {
+                1. - (age.as_secs_f32() / self.lifetime.as_secs_f32()).min(1.)
+            };
+            // the end of the tail comes to a point
+            let life = life * (1. - i as f32 / (len - 1) as f32);
+            let half_width = self.width / 2. * life;
+            let color = Color::RGBA(
+                self.color.r,
+                self.color.g,
+                self.color.b,
+                (self.color.a as f32 * life).round() as u8,
+            );
+            for side in [1., -1.] {
+                vertices.push(Vertex {
+                    position: FPoint::new(
+                        point.x() + normal.0 * half_width * side,
+                        point.y() + normal.1 * half_width * side,
+                    ),
+                    color,
+                });
+            }
+        }
+        let indices = (0..len as u32 - 1)
+            .flat_map(|i| {
+                let a = i * 2;
+                [a, a + 1, a + 2, a + 1, a + 3, a + 2]
+            })
+            .collect();
+        (vertices, indices)
+    }
+
+    pub fn draw(&self, system: &mut ChimericSystem, window_name: &str) -> Result<(), String> {
+        let (vertices, indices) = self.mesh();
+        if indices.is_empty() {
+            return Ok(());
+        }
+        system.draw_triangles(window_name, &vertices, &indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trail() {
+        let mut trail = Trail::new(4., Color::RGBA(255, 255, 255, 200), Duration::from_secs(1));
+        trail.push(FPoint::new(0., 0.));
+        trail.push(FPoint::new(1., 0.)); // too close
+        trail.update(Duration::from_millis(500));
+        trail.push(FPoint::new(10., 0.));
+        trail.push(FPoint::new(20., 0.));
+        assert_eq!(trail.points().count(), 3);
+
+        let (vertices, indices) = trail.mesh();
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, [0, 1, 2, 1, 3, 2, 2, 3, 4, 3, 5, 4]);
+        // the newest point is full width, across the direction of travel
+        assert_eq!(vertices[0].position, FPoint::new(20., -2.));
+        assert_eq!(vertices[1].position, FPoint::new(20., 2.));
+        assert_eq!(vertices[0].color.a, 200);
+        // the oldest comes to a point
+        assert_eq!(vertices[4].position, vertices[5].position);
+        assert_eq!(vertices[4].color.a, 0);
+
+        trail.update(Duration::from_millis(500));
+        assert_eq!(trail.points().count(), 2);
+        trail.update(Duration::from_millis(500));
+        assert_eq!(trail.points().count(), 0);
+        assert!(trail.mesh().0.is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use sdl2::rect::FPoint;
+
+use super::entity::EntityId;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: FPoint,
+    /// degrees clockwise, same as Canvas::copy_ex
+    pub rotation: f64,
+    pub scale: FPoint,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: FPoint::new(0., 0.),
+            rotation: 0.,
+            scale: FPoint::new(1., 1.),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_position(position: FPoint) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+
+    /// map a point from this transform's local space into its parent's space
+    pub fn apply(&self, point: FPoint) -> FPoint {
+        let scaled = FPoint::new(point.x() * self.scale.x(), point.y() * self.scale.y());
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (sin, cos) = (sin as f32, cos as f32);
+        FPoint::new(
+            self.position.x() + scaled.x() * cos - scaled.y() * sin,
+            self.position.y() + scaled.x() * sin + scaled.y() * cos,
+        )
+    }
+
+    /// the transform of a child, given in this transform's local space
+    pub fn then(&self, child: &Transform) -> Transform {
+        Transform {
+            position: self.apply(child.position),
+            rotation: self.rotation + child.rotation,
+            scale: FPoint::new(
+                self.scale.x() * child.scale.x(),
+                self.scale.y() * child.scale.y(),
+            ),
+        }
+    }
+}
+
+/// an entity's place in the transform hierarchy
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransformNode {
+    /// relative to the parent, or to the world if there is no parent
+    pub local: Transform,
+    /// if the parent is removed from the world then the local transform is
+    /// used as the world transform
+    pub parent: Option<EntityId>,
+    world: Transform,
+}
+
+impl TransformNode {
+    pub fn new(local: Transform, parent: Option<EntityId>) -> Self {
+        Self {
+            local,
+            parent,
+            world: local,
+        }
+    }
+
+    /// computed by the world at the end of each update, before the draw phase
+    pub fn world(&self) -> Transform {
+        self.world
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Resolution {
+    Unvisited,
+    InProgress,
+    Done(Transform),
+}
+
+/// compute the world transform of each node from its local transform and its
+/// parent's world transform. cycles are broken by treating the node where the
+/// cycle is detected as a root
+pub(crate) fn resolve(nodes: &mut [(EntityId, &mut TransformNode)]) {
+    let index_of: HashMap<EntityId, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (id, _))| (*id, i))
+        .collect();
+    let locals: Vec<(Transform, Option<usize>)> = nodes
+        .iter()
+        .map(|(_, node)| {
+            (
+                node.local,
+                node.parent
+                    .and_then(|parent| index_of.get(&parent).copied()),
+            )
+        })
+        .collect();
+
+    fn visit(
+        i: usize,
+        locals: &[(Transform, Option<usize>)],
+        resolved: &mut [Resolution],
+    ) -> Transform {
+        match resolved[i] {
+            Resolution::Done(transform) => return transform,
+            Resolution::InProgress => return locals[i].0,
+            Resolution::Unvisited => {}
+        }
+        resolved[i] = Resolution::InProgress;
+        let (local, parent) = locals[i];
+        let world = match parent {
+            Some(parent) => visit(parent, locals, resolved).then(&local),
+            None => local,
+        };
+        resolved[i] = Resolution::Done(world);
+        world
+    }
+
+    let mut resolved = vec![Resolution::Unvisited; nodes.len()];
+    for (i, (_, node)) in nodes.iter_mut().enumerate() {
+        node.world = visit(i, &locals, &mut resolved);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hierarchy() {
+        let (tank_id, turret_id, barrel_id) = (EntityId(0), EntityId(1), EntityId(2));
+        let mut tank = TransformNode::new(
+            Transform {
+                position: FPoint::new(100., 100.),
+                rotation: 90.,
+                scale: FPoint::new(2., 2.),
+            },
+            None,
+        );
+        let mut turret = TransformNode::new(
+            Transform::from_position(FPoint::new(10., 0.)),
+            Some(tank_id),
+        );
+        let mut barrel = TransformNode::new(
+            Transform::from_position(FPoint::new(5., 0.)),
+            Some(turret_id),
+        );
+        // children are listed before parents to check ordering doesn't matter
+        let mut nodes = vec![
+            (barrel_id, &mut barrel),
+            (turret_id, &mut turret),
+            (tank_id, &mut tank),
+        ];
+        resolve(&mut nodes);
+        let world = barrel.world();
+        assert!((world.position.x() - 100.).abs() < 1e-4);
+        assert!((world.position.y() - 130.).abs() < 1e-4);
+        assert_eq!(world.rotation, 90.);
+        assert_eq!(world.scale, FPoint::new(2., 2.));
+    }
+}
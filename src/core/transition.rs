@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use sdl2::pixels::Color;
+
+use super::{
+    easing::Ease,
+    post_process::{Effect, EffectStack, WipeDirection},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionKind {
+    /// to the color and back
+    Fade(Color),
+    /// the old scene's last frame fades out over the new one
+    Crossfade,
+    /// covered with the color, then uncovered
+    Wipe(Color, WipeDirection),
+    /// up to blocks this many pixels wide, and back
+    Pixelate(u32),
+}
+
+/// what the caller should do after an update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionStep {
+    Running,
+    /// switch to the next scene now; it's what's drawn from this frame on
+    Swap,
+    /// the effect can be removed
+    Finished,
+}
+
+/// a transition between two scenes, drawn as a post processing effect (so
+/// only on the sdl renderer). a SceneStack runs these; otherwise the caller
+/// owns the scenes and switches between them when update says to:
+///
+/// ```ignore
+/// match transition.update(frame.elapsed) {
+///     TransitionStep::Swap => scene = next_scene.take().unwrap(),
+///     TransitionStep::Running | TransitionStep::Finished => {}
+/// }
+/// transition.apply(&mut window.effects);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub duration: Duration,
+    /// applied to each half, or to the whole crossfade
+    pub ease: Ease,
+    elapsed: Duration,
+    swapped: bool,
+    /// a crossfade's first frame, which is the old scene's, has been kept
+    captured: bool,
+}
+
+impl Transition {
+    pub fn new(kind: TransitionKind, duration: Duration, ease: Ease) -> Self {
+        Self {
+            kind,
+            duration,
+            ease,
+            elapsed: Duration::ZERO,
+            swapped: false,
+            captured: false,
+        }
+    }
+
+    /// linear progress in [0, 1]
+    pub fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.;
+        }
+        (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.swapped && self.elapsed >= self.duration
+    }
+
+    pub fn update(&mut self, dt: Duration) -> TransitionStep {
+        if !self.swapped && self.kind == TransitionKind::Crossfade {
+            if !self.captured {
+                // the old scene is drawn once more, and kept when presented
+                self.captured = true;
+                return TransitionStep::Running;
+            }
+            self.swapped = true;
+            return TransitionStep::Swap;
+        }
+        if self.is_finished() {
+            return TransitionStep::Finished;
+        }
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        if !self.swapped && self.progress() >= 0.5 {
+            self.swapped = true;
+            return TransitionStep::Swap;
+        }
+        if self.is_finished() {
+            TransitionStep::Finished
+        } else {
+            TransitionStep::Running
+        }
+    }
+
+    /// this frame's effect. None once finished
+    pub fn effect(&self) -> Option<Effect> {
+        if self.is_finished() {
+            return None;
+        }
+        let progress = self.progress();
+        // up to the middle and back down
+        let amount = self.ease.apply(1. - (progress * 2. - 1.).abs());
+        Some(match self.kind {
+            TransitionKind::Fade(color) => {
+                let alpha = (color.a as f32 * amount).round() as u8;
+                Effect::Fade(Color::RGBA(color.r, color.g, color.b, alpha))
+            }
+            TransitionKind::Crossfade => {
+                let alpha = 255. * (1. - self.ease.apply(progress));
+                Effect::Crossfade(alpha.round() as u8)
+            }
+            TransitionKind::Wipe(color, direction) => Effect::Wipe {
+                color,
+                amount,
+                direction,
+            },
+            TransitionKind::Pixelate(block) => {
+                Effect::Pixelate(1 + (block.saturating_sub(1) as f32 * amount).round() as u32)
+            }
+        })
+    }
+
+    /// set (or clear, once finished) the window's transition effect
+    pub fn apply(&self, effects: &mut EffectStack) {
+        effects.transition = self.effect();
+    }
+}
+
+enum Change<S> {
+    Push(S),
+    Pop,
+    Replace(S),
+}
+
+/// the game's scenes (e.g. a level with a pause menu over it), changed with
+/// or without a transition. while one runs, the change is made when it
+/// swaps, so the top scene is what should be updated and drawn each frame:
+///
+/// ```ignore
+/// scenes.update(frame.elapsed);
+/// scenes.top_mut().unwrap().update(...);
+/// scenes.apply(&mut window.effects);
+/// ```
+pub struct SceneStack<S> {
+    scenes: Vec<S>,
+    transition: Option<Transition>,
+    /// made when the transition swaps
+    pending: Option<Change<S>>,
+}
+
+impl<S> SceneStack<S> {
+    pub fn new(scene: S) -> Self {
+        Self {
+            scenes: vec![scene],
+            transition: None,
+            pending: None,
+        }
+    }
+
+    /// the scene being shown
+    pub fn top(&self) -> Option<&S> {
+        self.scenes.last()
+    }
+
+    pub fn top_mut(&mut self) -> Option<&mut S> {
+        self.scenes.last_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn transition(&self) -> Option<&Transition> {
+        self.transition.as_ref()
+    }
+
+    pub fn push(&mut self, scene: S, transition: Option<Transition>) {
+        self.change(Change::Push(scene), transition);
+    }
+
+    pub fn pop(&mut self, transition: Option<Transition>) {
+        self.change(Change::Pop, transition);
+    }
+
+    /// the top scene, e.g. going to the next level
+    pub fn replace(&mut self, scene: S, transition: Option<Transition>) {
+        self.change(Change::Replace(scene), transition);
+    }
+
+    /// a change still waiting on a transition is made right away
+    fn change(&mut self, change: Change<S>, transition: Option<Transition>) {
+        self.make_pending();
+        match transition {
+            Some(transition) => {
+                self.pending = Some(change);
+                self.transition = Some(transition);
+            }
+            None => {
+                self.transition = None;
+                self.make(change);
+            }
+        }
+    }
+
+    fn make_pending(&mut self) {
+        if let Some(change) = self.pending.take() {
+            self.make(change);
+        }
+    }
+
+    fn make(&mut self, change: Change<S>) {
+        match change {
+            Change::Push(scene) => self.scenes.push(scene),
+            Change::Pop => {
+                self.scenes.pop();
+            }
+            Change::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+            }
+        }
+    }
+
+    /// advance the transition, making its change when it swaps
+    pub fn update(&mut self, dt: Duration) {
+        let Some(transition) = self.transition.as_mut() else {
+            return;
+        };
+        match transition.update(dt) {
+            TransitionStep::Running => {}
+            TransitionStep::Swap => self.make_pending(),
+            TransitionStep::Finished => self.transition = None,
+        }
+    }
+
+    /// set (or clear) the window's transition effect
+    pub fn apply(&self, effects: &mut EffectStack) {
+        effects.transition = self.transition.as_ref().and_then(Transition::effect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade() {
+        let black = Color::RGBA(0, 0, 0, 255);
+        let mut transition = Transition::new(
+            TransitionKind::Fade(black),
+            Duration::from_secs(1),
+            Ease::Linear,
+        );
+        assert_eq!(
+            transition.effect(),
+            Some(Effect::Fade(Color::RGBA(0, 0, 0, 0)))
+        );
+        assert_eq!(
+            transition.update(Duration::from_millis(250)),
+            TransitionStep::Running
+        );
+        assert_eq!(
+            transition.effect(),
+            Some(Effect::Fade(Color::RGBA(0, 0, 0, 128)))
+        );
+        assert_eq!(
+            transition.update(Duration::from_millis(250)),
+            TransitionStep::Swap
+        );
+        assert_eq!(transition.effect(), Some(Effect::Fade(black)));
+        assert_eq!(
+            transition.update(Duration::from_millis(600)),
+            TransitionStep::Finished
+        );
+        assert_eq!(transition.effect(), None);
+        assert_eq!(transition.update(Duration::ZERO), TransitionStep::Finished);
+    }
+
+    #[test]
+    fn test_crossfade() {
+        let mut transition = Transition::new(
+            TransitionKind::Crossfade,
+            Duration::from_secs(1),
+            Ease::Linear,
+        );
+        // the old scene is drawn and kept before swapping, and no time passes
+        assert_eq!(transition.effect(), Some(Effect::Crossfade(255)));
+        assert_eq!(
+            transition.update(Duration::from_millis(100)),
+            TransitionStep::Running
+        );
+        assert_eq!(transition.effect(), Some(Effect::Crossfade(255)));
+        assert_eq!(
+            transition.update(Duration::from_millis(100)),
+            TransitionStep::Swap
+        );
+        assert_eq!(transition.effect(), Some(Effect::Crossfade(255)));
+        assert_eq!(
+            transition.update(Duration::from_millis(500)),
+            TransitionStep::Running
+        );
+        assert_eq!(transition.effect(), Some(Effect::Crossfade(128)));
+    }
+
+    #[test]
+    fn test_scene_stack() {
+        let mut scenes = SceneStack::new("level 1");
+        let crossfade = Transition::new(
+            TransitionKind::Crossfade,
+            Duration::from_secs(1),
+            Ease::Linear,
+        );
+        let mut effects = EffectStack::new();
+        scenes.replace("level 2", Some(crossfade));
+        // the old scene is presented with the effect on, so it's the one kept
+        scenes.update(Duration::from_millis(16));
+        scenes.apply(&mut effects);
+        assert_eq!(scenes.top(), Some(&"level 1"));
+        assert_eq!(effects.transition, Some(Effect::Crossfade(255)));
+        scenes.update(Duration::from_millis(16));
+        assert_eq!(scenes.top(), Some(&"level 2"));
+        scenes.update(Duration::from_secs(1));
+        scenes.update(Duration::ZERO);
+        scenes.apply(&mut effects);
+        assert!(scenes.transition().is_none());
+        assert_eq!(effects.transition, None);
+
+        // a change waiting on a transition is made before the next one
+        let fade = Transition::new(
+            TransitionKind::Fade(Color::BLACK),
+            Duration::from_secs(1),
+            Ease::Linear,
+        );
+        scenes.push("pause", Some(fade));
+        assert_eq!(scenes.len(), 1);
+        scenes.pop(None);
+        assert_eq!(scenes.top(), Some(&"level 2"));
+        assert!(scenes.transition().is_none());
+    }
+}
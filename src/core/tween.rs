@@ -0,0 +1,279 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect, Point, Rect},
+};
+
+pub use super::easing::Ease;
+use super::world::World;
+
+/// values which can be interpolated
+pub trait Lerp: Copy {
+    fn lerp(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        from + (to - from) * t
+    }
+}
+
+fn lerp_i32(from: i32, to: i32, t: f32) -> i32 {
+    f32::lerp(from as f32, to as f32, t).round() as i32
+}
+
+fn lerp_u32(from: u32, to: u32, t: f32) -> u32 {
+    f32::lerp(from as f32, to as f32, t).round().max(0.) as u32
+}
+
+fn lerp_u8(from: u8, to: u8, t: f32) -> u8 {
+    f32::lerp(from as f32, to as f32, t).round().clamp(0., 255.) as u8
+}
+
+impl Lerp for FPoint {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        FPoint::new(
+            f32::lerp(from.x(), to.x(), t),
+            f32::lerp(from.y(), to.y(), t),
+        )
+    }
+}
+
+impl Lerp for Point {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        Point::new(lerp_i32(from.x(), to.x(), t), lerp_i32(from.y(), to.y(), t))
+    }
+}
+
+impl Lerp for FRect {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        FRect::new(
+            f32::lerp(from.x(), to.x(), t),
+            f32::lerp(from.y(), to.y(), t),
+            f32::lerp(from.width(), to.width(), t),
+            f32::lerp(from.height(), to.height(), t),
+        )
+    }
+}
+
+impl Lerp for Rect {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        Rect::new(
+            lerp_i32(from.x(), to.x(), t),
+            lerp_i32(from.y(), to.y(), t),
+            lerp_u32(from.width(), to.width(), t),
+            lerp_u32(from.height(), to.height(), t),
+        )
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        Color::RGBA(
+            lerp_u8(from.r, to.r, t),
+            lerp_u8(from.g, to.g, t),
+            lerp_u8(from.b, to.b, t),
+            lerp_u8(from.a, to.a, t),
+        )
+    }
+}
+
+pub type TweenCallback = Rc<RefCell<dyn FnMut(&mut World) -> Result<(), String>>>;
+
+#[derive(Clone)]
+struct Segment<T> {
+    /// holds the value if none
+    to: Option<T>,
+    duration: Duration,
+    ease: Ease,
+}
+
+/// animates a shared value through a chain of segments. the entity which
+/// reads the value keeps a clone of the target. clones share the target and
+/// the completion callback
+#[derive(Clone)]
+pub struct Tween<T> {
+    target: Rc<Cell<T>>,
+    segments: Vec<Segment<T>>,
+    current: usize,
+    elapsed: Duration,
+    /// the value when the current segment started
+    start: Option<T>,
+    on_complete: Option<TweenCallback>,
+}
+
+impl<T: Lerp> Tween<T> {
+    pub fn new(target: &Rc<Cell<T>>) -> Self {
+        Self {
+            target: target.clone(),
+            segments: Default::default(),
+            current: 0,
+            elapsed: Duration::ZERO,
+            start: None,
+            on_complete: None,
+        }
+    }
+
+    /// chain a segment which animates from the value at the time the segment
+    /// starts to the given value
+    pub fn to(mut self, value: T, duration: Duration, ease: Ease) -> Self {
+        self.segments.push(Segment {
+            to: Some(value),
+            duration,
+            ease,
+        });
+        self
+    }
+
+    /// chain a segment which holds the value
+    pub fn wait(mut self, duration: Duration) -> Self {
+        self.segments.push(Segment {
+            to: None,
+            duration,
+            ease: Ease::Linear,
+        });
+        self
+    }
+
+    /// called by the world once every segment has finished. it's called again
+    /// if the world is rewound to before then and finishes the tween again
+    pub fn on_complete<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&mut World) -> Result<(), String> + 'static,
+    {
+        self.on_complete = Some(Rc::new(RefCell::new(callback)));
+        self
+    }
+}
+
+/// type erased tween
+pub(crate) trait Animate {
+    /// returns true once finished
+    fn advance(&mut self, dt: Duration) -> bool;
+    fn on_complete(&self) -> Option<TweenCallback>;
+    fn clone_box(&self) -> Box<dyn Animate>;
+}
+
+impl<T: Lerp + 'static> Animate for Tween<T> {
+    fn advance(&mut self, mut dt: Duration) -> bool {
+        while let Some(segment) = self.segments.get(self.current) {
+            let start = match self.start {
+                Some(start) => start,
+                None => {
+                    let start = self.target.get();
+                    self.start = Some(start);
+                    start
+                }
+            };
+            let to = segment.to.unwrap_or(start);
+            let remaining = segment.duration.saturating_sub(self.elapsed);
+            if dt < remaining {
+                self.elapsed += dt;
+                let t = self.elapsed.as_secs_f32() / segment.duration.as_secs_f32();
+                self.target.set(T::lerp(start, to, segment.ease.apply(t)));
+                return false;
+            }
+            dt -= remaining;
+            self.target.set(to);
+            self.current += 1;
+            self.elapsed = Duration::ZERO;
+            self.start = None;
+        }
+        true
+    }
+
+    fn on_complete(&self) -> Option<TweenCallback> {
+        self.on_complete.clone()
+    }
+
+    fn clone_box(&self) -> Box<dyn Animate> {
+        Box::new(self.clone())
+    }
+}
+
+/// identifies a tween in the world so it can be cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TweenHandle(u64);
+
+/// tweens owned by the world, advanced by one timestep per update. clones
+/// share their targets and callbacks
+#[derive(Default)]
+pub struct Tweens {
+    next_handle: u64,
+    active: Vec<(TweenHandle, Box<dyn Animate>)>,
+}
+
+impl Clone for Tweens {
+    fn clone(&self) -> Self {
+        Self {
+            next_handle: self.next_handle,
+            active: self
+                .active
+                .iter()
+                .map(|(handle, tween)| (*handle, tween.clone_box()))
+                .collect(),
+        }
+    }
+}
+
+impl Tweens {
+    pub fn add<T: Lerp + 'static>(&mut self, tween: Tween<T>) -> TweenHandle {
+        let handle = TweenHandle(self.next_handle);
+        self.next_handle += 1;
+        self.active.push((handle, Box::new(tween)));
+        handle
+    }
+
+    /// the target keeps its current value. returns false if the tween already
+    /// finished or was already cancelled
+    pub fn cancel(&mut self, handle: TweenHandle) -> bool {
+        let len = self.active.len();
+        self.active.retain(|(h, _)| *h != handle);
+        self.active.len() != len
+    }
+
+    pub fn is_active(&self, handle: TweenHandle) -> bool {
+        self.active.iter().any(|(h, _)| *h == handle)
+    }
+
+    /// advance every tween, removing finished ones. returns the completion
+    /// callbacks of those which finished, in the order they were added
+    pub(crate) fn advance(&mut self, dt: Duration) -> Vec<TweenCallback> {
+        let mut callbacks: Vec<TweenCallback> = Vec::new();
+        self.active.retain_mut(|(_, tween)| {
+            let finished = tween.advance(dt);
+            if finished {
+                callbacks.extend(tween.on_complete());
+            }
+            !finished
+        });
+        callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain() {
+        let value = Rc::new(Cell::new(0f32));
+        let mut tween = Tween::new(&value)
+            .to(10., Duration::from_secs(1), Ease::Linear)
+            .wait(Duration::from_secs(1))
+            .to(0., Duration::from_secs(2), Ease::Linear);
+        assert!(!tween.advance(Duration::from_millis(500)));
+        assert_eq!(value.get(), 5.);
+        assert!(!tween.advance(Duration::from_millis(1000)));
+        assert_eq!(value.get(), 10.);
+        assert!(!tween.advance(Duration::from_millis(1500)));
+        assert_eq!(value.get(), 5.);
+        assert!(tween.advance(Duration::from_millis(1000)));
+        assert_eq!(value.get(), 0.);
+    }
+}
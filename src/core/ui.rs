@@ -0,0 +1,997 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    ffi::CString,
+    hash::{Hash, Hasher},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
+    pixels::Color,
+    rect::{FPoint, FRect, Rect},
+};
+
+use super::{
+    events::EngineEvent,
+    input::InputState,
+    renderer::{rect_to_f, DrawParams},
+    system::ChimericSystem,
+    text_input::TextBuffer,
+};
+
+/// an image stretched to any size while keeping its borders unscaled, e.g.
+/// for button and panel backgrounds
+#[derive(Debug, Clone, PartialEq)]
+pub struct NinePatch {
+    pub path: PathBuf,
+    /// left, top, right, bottom, in texture pixels
+    pub borders: (u32, u32, u32, u32),
+}
+
+impl NinePatch {
+    pub fn new(path: &Path, borders: (u32, u32, u32, u32)) -> Self {
+        Self {
+            path: path.to_owned(),
+            borders,
+        }
+    }
+}
+
+/// borders shrink proportionally if they don't fit
+fn fit_borders(a: u32, b: u32, length: u32) -> (u32, u32) {
+    if a + b <= length {
+        return (a, b);
+    }
+    let a = (a as u64 * length as u64 / (a + b) as u64) as u32;
+    (a, length - a)
+}
+
+/// the (src, dst) rects of the nine pieces. empty pieces are left out
+pub fn nine_patch_rects(
+    texture_size: (u32, u32),
+    borders: (u32, u32, u32, u32),
+    dst: Rect,
+) -> Vec<(Rect, Rect)> {
+    let (tw, th) = texture_size;
+    let (left, right) = fit_borders(borders.0, borders.2, tw);
+    let (top, bottom) = fit_borders(borders.1, borders.3, th);
+    let (dst_left, dst_right) = fit_borders(left, right, dst.width());
+    let (dst_top, dst_bottom) = fit_borders(top, bottom, dst.height());
+    // (offset, length) of each column and row
+    let src_columns = [(0, left), (left, tw - left - right), (tw - right, right)];
+    let src_rows = [(0, top), (top, th - top - bottom), (th - bottom, bottom)];
+    let dst_columns = [
+        (0, dst_left),
+        (dst_left, dst.width() - dst_left - dst_right),
+        (dst.width() - dst_right, dst_right),
+    ];
+    let dst_rows = [
+        (0, dst_top),
+        (dst_top, dst.height() - dst_top - dst_bottom),
+        (dst.height() - dst_bottom, dst_bottom),
+    ];
+    let mut pieces = Vec::with_capacity(9);
+    for row in 0..3 {
+        for column in 0..3 {
+            let (sx, sw) = src_columns[column];
+            let (sy, sh) = src_rows[row];
+            let (dx, dw) = dst_columns[column];
+            let (dy, dh) = dst_rows[row];
+            if sw == 0 || sh == 0 || dw == 0 || dh == 0 {
+                continue;
+            }
+            pieces.push((
+                Rect::new(sx as i32, sy as i32, sw, sh),
+                Rect::new(dst.x() + dx as i32, dst.y() + dy as i32, dw, dh),
+            ));
+        }
+    }
+    pieces
+}
+
+/// how widgets look. rendered text is always white, so the colors should be
+/// dark enough to read it on
+#[derive(Debug, Clone)]
+pub struct UiStyle {
+    pub font_file: PathBuf,
+    pub point_size: u16,
+    pub background: Color,
+    pub hovered: Color,
+    pub pressed: Color,
+    /// slider fill, check mark, caret and focus outline
+    pub accent: Color,
+    pub panel: Color,
+    /// drawn instead of the flat colors, if set
+    pub button_patch: Option<NinePatch>,
+    pub panel_patch: Option<NinePatch>,
+    /// between a widget's edge and its text
+    pub padding: i32,
+    /// pixels per mouse wheel step in scroll panels
+    pub scroll_speed: i32,
+    /// multiplies the point size and padding, e.g. from Config::ui_scale.
+    /// text is rendered at the scaled size rather than stretched. widget
+    /// rects aren't scaled; see Layout::set_scale
+    pub scale: f32,
+    /// applied to every color when drawn, e.g. high_contrast
+    pub palette: Option<fn(Color) -> Color>,
+}
+
+impl UiStyle {
+    pub fn new(font_file: &Path, point_size: u16) -> Self {
+        Self {
+            font_file: font_file.to_owned(),
+            point_size,
+            background: Color::RGB(60, 60, 70),
+            hovered: Color::RGB(80, 80, 95),
+            pressed: Color::RGB(40, 40, 50),
+            accent: Color::RGB(70, 130, 220),
+            panel: Color::RGBA(20, 20, 25, 220),
+            button_patch: None,
+            panel_patch: None,
+            padding: 4,
+            scroll_speed: 24,
+            scale: 1.,
+            palette: None,
+        }
+    }
+
+    pub fn scaled_point_size(&self) -> u16 {
+        (self.point_size as f32 * self.scale)
+            .round()
+            .clamp(1., u16::MAX as f32) as u16
+    }
+
+    pub fn scaled_padding(&self) -> i32 {
+        (self.padding as f32 * self.scale).round() as i32
+    }
+
+    fn color(&self, color: Color) -> Color {
+        self.palette.map_or(color, |palette| palette(color))
+    }
+}
+
+/// a palette for UiStyle::palette (or any color) with stronger contrast.
+/// saturated colors become yellow, and the rest black, dark blue or white by
+/// brightness, keeping white text readable on anything that isn't white
+pub fn high_contrast(color: Color) -> Color {
+    let max = color.r.max(color.g).max(color.b) as u32;
+    let min = color.r.min(color.g).min(color.b) as u32;
+    let (r, g, b) = if max > 100 && (max - min) * 2 > max {
+        (255, 220, 0)
+    } else {
+        let luma = (299 * color.r as u32 + 587 * color.g as u32 + 114 * color.b as u32) / 1000;
+        match luma {
+            0..72 => (0, 0, 0),
+            72..128 => (0, 0, 140),
+            _ => (255, 255, 255),
+        }
+    };
+    Color::RGBA(r, g, b, color.a)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Center,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Fill(Rect, Color),
+    Outline(Rect, Color),
+    Patch(NinePatch, Rect),
+    Text {
+        text: String,
+        rect: Rect,
+        align: Align,
+    },
+    /// after the text, left aligned in the rect
+    Caret {
+        text: String,
+        rect: Rect,
+    },
+    Clip(Option<Rect>),
+}
+
+/// this frame's input, for the ui's window
+#[derive(Debug, Default)]
+struct FrameInput {
+    mouse: Option<FPoint>,
+    pressed: bool,
+    down: bool,
+    released: bool,
+    /// positive is up
+    wheel: f32,
+    /// key downs, including repeats
+    keys: Vec<(Keycode, Mod, bool)>,
+    /// given to the focused text field
+    text_events: Vec<EngineEvent>,
+}
+
+impl FrameInput {
+    fn key(&self, keycode: Keycode, repeats: bool) -> Option<Mod> {
+        self.keys
+            .iter()
+            .find(|(k, _, repeat)| *k == keycode && (repeats || !repeat))
+            .map(|(_, keymod, _)| *keymod)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Interaction {
+    hovered: bool,
+    /// the mouse went down on it and hasn't been released
+    held: bool,
+    /// clicked, or activated from the keyboard
+    activated: bool,
+}
+
+/// what kind of widget has the focus, for an Announcement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WidgetRole {
+    Button,
+    Checkbox { checked: bool },
+    Slider { value: f32 },
+    TextField,
+}
+
+/// something a screen reader should say
+#[derive(Debug, Clone, PartialEq)]
+pub enum Announcement {
+    /// a widget got the focus. the text is its visible label, or a text
+    /// field's contents
+    Focus { text: String, role: WidgetRole },
+    /// e.g. from Ui::announce, or a focused checkbox being toggled
+    Text(String),
+}
+
+/// receives a ui's announcements as they happen, e.g. to speak them with the
+/// platform's text to speech. any FnMut(&Announcement) is one
+pub trait Announcer {
+    fn announce(&mut self, announcement: &Announcement);
+}
+
+impl<F: FnMut(&Announcement)> Announcer for F {
+    fn announce(&mut self, announcement: &Announcement) {
+        self(announcement)
+    }
+}
+
+/// the label, without the part after "##"
+fn visible(label: &str) -> &str {
+    label.split("##").next().unwrap_or_default()
+}
+
+fn id(label: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    label.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn contains(rect: Rect, point: FPoint) -> bool {
+    rect_to_f(rect).contains_point(point)
+}
+
+/// immediate mode ui for a window. widgets are declared every frame between
+/// begin_frame and end_frame (in update), return what happened to them, and
+/// are drawn later with draw. a widget's label is its id; make labels unique
+/// by adding "##" and a suffix, which isn't shown
+///
+/// tab and shift tab (or the up and down keys) move the focus between
+/// widgets, and enter or space activates the focused one
+pub struct Ui {
+    pub style: UiStyle,
+    window_name: String,
+    input: FrameInput,
+    commands: Vec<Command>,
+    /// focusable widgets in the order they were declared this frame
+    order: Vec<u64>,
+    /// the widget the mouse went down on
+    active: Option<u64>,
+    focused: Option<u64>,
+    focused_text_field: bool,
+    /// a widget was pressed this frame
+    press_taken: bool,
+    /// the mouse is over a widget or panel
+    hovering: bool,
+    /// (clip, content origin) of each open scroll panel
+    scopes: Vec<(Rect, (i32, i32))>,
+    scroll: HashMap<u64, i32>,
+    /// how this frame's focusable widgets are announced
+    descriptions: HashMap<u64, (String, WidgetRole)>,
+    /// the focus as of the last announcement
+    announced_focus: Option<u64>,
+    announcements: Vec<Announcement>,
+    announcer: Option<Box<dyn Announcer>>,
+}
+
+impl Ui {
+    pub fn new(window_name: &str, style: UiStyle) -> Self {
+        Self {
+            style,
+            window_name: window_name.to_owned(),
+            input: Default::default(),
+            commands: Default::default(),
+            order: Default::default(),
+            active: None,
+            focused: None,
+            focused_text_field: false,
+            press_taken: false,
+            hovering: false,
+            scopes: Default::default(),
+            scroll: Default::default(),
+            descriptions: Default::default(),
+            announced_focus: None,
+            announcements: Default::default(),
+            announcer: None,
+        }
+    }
+
+    /// announcements are also kept for the frame; see announcements
+    pub fn set_announcer(&mut self, announcer: Option<Box<dyn Announcer>>) {
+        self.announcer = announcer;
+    }
+
+    /// what was announced since begin_frame, whether or not there's an
+    /// announcer
+    pub fn announcements(&self) -> &[Announcement] {
+        &self.announcements
+    }
+
+    /// have a screen reader say the text, e.g. "level complete"
+    pub fn announce(&mut self, text: &str) {
+        self.emit(Announcement::Text(text.to_owned()));
+    }
+
+    fn emit(&mut self, announcement: Announcement) {
+        if let Some(announcer) = self.announcer.as_mut() {
+            announcer.announce(&announcement);
+        }
+        self.announcements.push(announcement);
+    }
+
+    fn describe(&mut self, id: u64, text: &str, role: WidgetRole) {
+        self.descriptions.insert(id, (text.to_owned(), role));
+    }
+
+    pub fn window_name(&self) -> &str {
+        &self.window_name
+    }
+
+    /// start declaring this frame's widgets
+    pub fn begin_frame(&mut self, input: &InputState, events: &[EngineEvent]) {
+        self.commands.clear();
+        self.order.clear();
+        self.scopes.clear();
+        self.descriptions.clear();
+        self.announcements.clear();
+        self.press_taken = false;
+        self.hovering = false;
+        self.focused_text_field = false;
+
+        let window_name = self.window_name.as_str();
+        let ours = |window: &Option<String>| window.as_deref().is_none_or(|w| w == window_name);
+        let mut frame = FrameInput {
+            mouse: input
+                .mouse_position()
+                .filter(|_| input.mouse_window() == Some(window_name)),
+            pressed: input.mouse_just_pressed(MouseButton::Left),
+            down: input.mouse_down(MouseButton::Left),
+            released: input.mouse_just_released(MouseButton::Left),
+            ..Default::default()
+        };
+        for event in events {
+            match event {
+                EngineEvent::MouseWheel { window, y, .. } if ours(window) => frame.wheel += y,
+                EngineEvent::KeyDown {
+                    window,
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat,
+                    ..
+                } if ours(window) => {
+                    frame.keys.push((*keycode, *keymod, *repeat));
+                    frame.text_events.push(event.clone());
+                }
+                EngineEvent::TextInput { window, .. } | EngineEvent::TextEditing { window, .. }
+                    if ours(window) =>
+                {
+                    frame.text_events.push(event.clone())
+                }
+                _ => {}
+            }
+        }
+        self.input = frame;
+    }
+
+    /// finish the frame's widgets and apply keyboard navigation
+    pub fn end_frame(&mut self) {
+        if self.input.released || !self.input.down {
+            self.active = None;
+        }
+        if self.input.pressed && !self.press_taken {
+            self.focused = None;
+        }
+        if self.input.key(Keycode::Escape, false).is_some() {
+            self.focused = None;
+        }
+        let backward = self.input.key(Keycode::Up, true).is_some()
+            || self
+                .input
+                .key(Keycode::Tab, true)
+                .is_some_and(|keymod| keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD));
+        let forward = !backward
+            && (self.input.key(Keycode::Down, true).is_some()
+                || self.input.key(Keycode::Tab, true).is_some());
+        // up and down move the caret in some text fields, so they don't move
+        // the focus away from one
+        let arrows = self.input.key(Keycode::Tab, true).is_none();
+        if (forward || backward) && !(arrows && self.focused_text_field) && !self.order.is_empty() {
+            let len = self.order.len();
+            let current = self
+                .focused
+                .and_then(|focused| self.order.iter().position(|id| *id == focused));
+            let next = match (current, forward) {
+                (None, true) => 0,
+                (None, false) => len - 1,
+                (Some(i), true) => (i + 1) % len,
+                (Some(i), false) => (i + len - 1) % len,
+            };
+            self.focused = Some(self.order[next]);
+        }
+        if let Some(focused) = self.focused {
+            // the widget wasn't declared this frame
+            if !self.order.contains(&focused) {
+                self.focused = None;
+            }
+        }
+        if self.focused != self.announced_focus {
+            self.announced_focus = self.focused;
+            let description = self.focused.and_then(|id| self.descriptions.get(&id));
+            if let Some((text, role)) = description.cloned() {
+                self.emit(Announcement::Focus { text, role });
+            }
+        }
+    }
+
+    /// true while a text field has the focus. start sdl text input (see
+    /// TextInput) while this is true
+    pub fn wants_text_input(&self) -> bool {
+        self.focused_text_field
+    }
+
+    pub fn is_focused(&self, label: &str) -> bool {
+        self.focused == Some(id(label))
+    }
+
+    /// give the widget the keyboard focus
+    pub fn focus(&mut self, label: &str) {
+        self.focused = Some(id(label));
+    }
+
+    /// true if the mouse is over any of this frame's widgets or panels, or is
+    /// dragging one. clicks then shouldn't also go to the game
+    pub fn wants_mouse(&self) -> bool {
+        self.hovering || self.active.is_some()
+    }
+
+    /// the rect moved into the innermost scroll panel
+    fn place(&self, rect: Rect) -> Rect {
+        match self.scopes.last() {
+            None => rect,
+            Some((_, (x, y))) => Rect::new(rect.x() + x, rect.y() + y, rect.width(), rect.height()),
+        }
+    }
+
+    fn clip(&self) -> Option<Rect> {
+        self.scopes.last().map(|(clip, _)| *clip)
+    }
+
+    fn hovered(&self, rect: Rect) -> bool {
+        match self.input.mouse {
+            None => false,
+            Some(mouse) => contains(rect, mouse) && self.clip().is_none_or(|c| contains(c, mouse)),
+        }
+    }
+
+    fn interact(&mut self, id: u64, rect: Rect) -> Interaction {
+        self.order.push(id);
+        let hovered = self.hovered(rect);
+        self.hovering |= hovered;
+        if hovered && self.input.pressed {
+            self.active = Some(id);
+            self.focused = Some(id);
+            self.press_taken = true;
+        }
+        let is_active = self.active == Some(id);
+        let clicked = is_active && self.input.released && hovered;
+        let activated = self.focused == Some(id)
+            && (self.input.key(Keycode::Return, false).is_some()
+                || self.input.key(Keycode::KpEnter, false).is_some()
+                || self.input.key(Keycode::Space, false).is_some());
+        Interaction {
+            hovered,
+            held: is_active && self.input.down,
+            activated: clicked || activated,
+        }
+    }
+
+    fn background(&mut self, rect: Rect, interaction: Interaction) {
+        match &self.style.button_patch {
+            Some(patch) => self.commands.push(Command::Patch(patch.clone(), rect)),
+            None => {
+                let color = if interaction.held {
+                    self.style.pressed
+                } else if interaction.hovered {
+                    self.style.hovered
+                } else {
+                    self.style.background
+                };
+                self.commands.push(Command::Fill(rect, color));
+            }
+        }
+    }
+
+    fn focus_outline(&mut self, id: u64, rect: Rect) {
+        if self.focused == Some(id) {
+            self.commands
+                .push(Command::Outline(rect, self.style.accent));
+        }
+    }
+
+    fn text(&mut self, text: &str, rect: Rect, align: Align) {
+        if !text.is_empty() {
+            self.commands.push(Command::Text {
+                text: text.to_owned(),
+                rect,
+                align,
+            });
+        }
+    }
+
+    /// text which can't be interacted with
+    pub fn label(&mut self, text: &str, rect: Rect) {
+        let rect = self.place(rect);
+        self.text(text, rect, Align::Left);
+    }
+
+    /// a background for a group of widgets. it takes clicks, so they don't
+    /// fall through to the game
+    pub fn panel(&mut self, rect: Rect) {
+        let rect = self.place(rect);
+        if self.hovered(rect) {
+            self.hovering = true;
+            self.press_taken |= self.input.pressed;
+        }
+        match &self.style.panel_patch {
+            Some(patch) => self.commands.push(Command::Patch(patch.clone(), rect)),
+            None => self.commands.push(Command::Fill(rect, self.style.panel)),
+        }
+    }
+
+    /// true when clicked or activated
+    pub fn button(&mut self, label: &str, rect: Rect) -> bool {
+        let rect = self.place(rect);
+        let id = id(label);
+        let interaction = self.interact(id, rect);
+        self.describe(id, visible(label), WidgetRole::Button);
+        self.background(rect, interaction);
+        self.focus_outline(id, rect);
+        self.text(visible(label), rect, Align::Center);
+        interaction.activated
+    }
+
+    /// a box at the left of the rect with the label after it. true when
+    /// toggled
+    pub fn checkbox(&mut self, label: &str, rect: Rect, value: &mut bool) -> bool {
+        let rect = self.place(rect);
+        let id = id(label);
+        let interaction = self.interact(id, rect);
+        if interaction.activated {
+            *value = !*value;
+            if self.focused == Some(id) {
+                self.announce(if *value { "checked" } else { "unchecked" });
+            }
+        }
+        self.describe(id, visible(label), WidgetRole::Checkbox { checked: *value });
+        let size = rect.height();
+        let check = Rect::new(rect.x(), rect.y(), size, size);
+        self.background(check, interaction);
+        if *value {
+            let inset = (size / 4) as i32;
+            let mark = Rect::new(
+                check.x() + inset,
+                check.y() + inset,
+                size.saturating_sub(inset as u32 * 2).max(1),
+                size.saturating_sub(inset as u32 * 2).max(1),
+            );
+            self.commands.push(Command::Fill(mark, self.style.accent));
+        }
+        self.focus_outline(id, check);
+        let text_rect = Rect::new(
+            check.right(),
+            rect.y(),
+            rect.width().saturating_sub(size).max(1),
+            rect.height(),
+        );
+        self.text(visible(label), text_rect, Align::Left);
+        interaction.activated
+    }
+
+    /// drag, or use left and right while focused, to pick a value in the
+    /// range. true when the value changed
+    pub fn slider(
+        &mut self,
+        label: &str,
+        rect: Rect,
+        value: &mut f32,
+        range: RangeInclusive<f32>,
+    ) -> bool {
+        let rect = self.place(rect);
+        let id = id(label);
+        let interaction = self.interact(id, rect);
+        let (start, end) = (*range.start(), *range.end());
+        let previous = *value;
+        if interaction.held {
+            if let Some(mouse) = self.input.mouse {
+                let t = (mouse.x() - rect.x() as f32) / rect.width() as f32;
+                *value = start + (end - start) * t.clamp(0., 1.);
+            }
+        }
+        if self.focused == Some(id) {
+            let step = (end - start) / 20.;
+            for (keycode, _, _) in self.input.keys.iter() {
+                match *keycode {
+                    Keycode::Left => *value -= step,
+                    Keycode::Right => *value += step,
+                    _ => {}
+                }
+            }
+        }
+        *value = value.clamp(start.min(end), start.max(end));
+        self.describe(id, visible(label), WidgetRole::Slider { value: *value });
+
+        self.background(rect, interaction);
+        let t = if end == start {
+            0.
+        } else {
+            (*value - start) / (end - start)
+        };
+        let filled = (rect.width() as f32 * t).round() as u32;
+        if filled != 0 {
+            let fill = Rect::new(rect.x(), rect.y(), filled, rect.height());
+            self.commands.push(Command::Fill(fill, self.style.accent));
+        }
+        self.focus_outline(id, rect);
+        self.text(visible(label), rect, Align::Center);
+        *value != previous
+    }
+
+    /// single line text entry. the label is only its id. true when the text
+    /// changed
+    pub fn text_field(&mut self, label: &str, rect: Rect, buffer: &mut TextBuffer) -> bool {
+        let rect = self.place(rect);
+        let id = id(label);
+        self.interact(id, rect);
+        let focused = self.focused == Some(id);
+        let mut changed = false;
+        if focused {
+            self.focused_text_field = true;
+            let before = buffer.text().to_owned();
+            for event in self.input.text_events.iter() {
+                buffer.handle_event(event);
+            }
+            changed = before != buffer.text();
+        }
+        self.describe(id, buffer.text(), WidgetRole::TextField);
+        self.commands.push(Command::Fill(rect, self.style.pressed));
+        self.focus_outline(id, rect);
+        self.text(&buffer.display_text(), rect, Align::Left);
+        if focused {
+            let before_cursor = &buffer.text()[..buffer.cursor()];
+            self.commands.push(Command::Caret {
+                text: format!("{}{}", before_cursor, buffer.composition()),
+                rect,
+            });
+        }
+        changed
+    }
+
+    /// widgets until end_scroll are clipped to the rect and scrolled with the
+    /// mouse wheel. their rects are relative to the top left of the content,
+    /// which is content_height tall
+    pub fn begin_scroll(&mut self, label: &str, rect: Rect, content_height: u32) {
+        let rect = self.place(rect);
+        let id = id(label);
+        let max = content_height.saturating_sub(rect.height()) as i32;
+        let mut offset = self.scroll.get(&id).copied().unwrap_or(0);
+        if self.hovered(rect) {
+            offset -= (self.input.wheel * self.style.scroll_speed as f32) as i32;
+            self.hovering = true;
+            self.press_taken |= self.input.pressed;
+        }
+        let offset = offset.clamp(0, max);
+        self.scroll.insert(id, offset);
+
+        if max > 0 {
+            // the scroll bar
+            let height =
+                (rect.height() as u64 * rect.height() as u64 / content_height as u64).max(4) as u32;
+            let travel = rect.height().saturating_sub(height) as i32;
+            let y = rect.y() + travel * offset / max;
+            let bar = Rect::new(rect.right() - 4, y, 4, height);
+            self.commands.push(Command::Fill(bar, self.style.accent));
+        }
+        let clip = match self.clip() {
+            Some(outer) => outer
+                .intersection(rect)
+                .unwrap_or(Rect::new(rect.x(), rect.y(), 1, 1)),
+            None => rect,
+        };
+        self.commands.push(Command::Clip(Some(clip)));
+        self.scopes.push((clip, (rect.x(), rect.y() - offset)));
+    }
+
+    pub fn end_scroll(&mut self) {
+        self.scopes.pop();
+        self.commands.push(Command::Clip(self.clip()));
+    }
+
+    /// draw the widgets declared this frame
+    pub fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        let window = self.window_name.as_str();
+        let font_file = self.style.font_file.as_path();
+        let point_size = self.style.scaled_point_size();
+        let padding = self.style.scaled_padding();
+        let mut clip = None;
+        for command in self.commands.iter() {
+            match command {
+                Command::Fill(rect, color) => {
+                    system.fill_rect(window, rect_to_f(*rect), self.style.color(*color))?
+                }
+                Command::Outline(rect, color) => {
+                    let (x, y, w, h) = (rect.x(), rect.y(), rect.width(), rect.height());
+                    for edge in [
+                        Rect::new(x, y, w, 1),
+                        Rect::new(x, rect.bottom() - 1, w, 1),
+                        Rect::new(x, y, 1, h),
+                        Rect::new(rect.right() - 1, y, 1, h),
+                    ] {
+                        system.fill_rect(window, rect_to_f(edge), self.style.color(*color))?;
+                    }
+                }
+                Command::Patch(patch, rect) => {
+                    let size = system.texture_size(window, &patch.path)?;
+                    let draws: Vec<DrawParams> = nine_patch_rects(size, patch.borders, *rect)
+                        .into_iter()
+                        .map(|(src, dst)| DrawParams::new(Some(src), Some(rect_to_f(dst))))
+                        .collect();
+                    system.draw(window, &patch.path, &draws)?;
+                }
+                Command::Text { text, rect, align } => {
+                    let visible_area = match clip {
+                        Some(clip) => rect.intersection(clip),
+                        None => Some(*rect),
+                    };
+                    let Some(visible_area) = visible_area else {
+                        continue;
+                    };
+                    let text = CString::new(text.as_str()).map_err(|e| e.to_string())?;
+                    let (w, h) = system.text_size(font_file, point_size, &text)?;
+                    let x = match align {
+                        Align::Left => rect.x() + padding,
+                        Align::Center => rect.center().x() - w as i32 / 2,
+                    };
+                    let y = rect.center().y() - h as i32 / 2;
+                    let dst = FRect::new(x as f32, y as f32, w as f32, h as f32);
+                    system.set_clip(window, Some(visible_area))?;
+                    system.draw_text(
+                        window,
+                        font_file,
+                        point_size,
+                        &text,
+                        None,
+                        &DrawParams::new(None, Some(dst)),
+                    )?;
+                    system.set_clip(window, clip)?;
+                }
+                Command::Caret { text, rect } => {
+                    let width = if text.is_empty() {
+                        0
+                    } else {
+                        let text = CString::new(text.as_str()).map_err(|e| e.to_string())?;
+                        system.text_size(font_file, point_size, &text)?.0
+                    };
+                    let x = rect.x() + padding + width as i32;
+                    if x < rect.right() {
+                        let caret =
+                            Rect::new(x, rect.y() + 2, 2, rect.height().saturating_sub(4).max(1));
+                        let color = self.style.color(self.style.accent);
+                        system.fill_rect(window, rect_to_f(caret), color)?;
+                    }
+                }
+                Command::Clip(rect) => {
+                    clip = *rect;
+                    system.set_clip(window, clip)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click(position: (f32, f32), down: bool) -> EngineEvent {
+        let position = FPoint::new(position.0, position.1);
+        let window = Some("main".to_owned());
+        if down {
+            EngineEvent::MouseButtonDown {
+                window,
+                button: MouseButton::Left,
+                clicks: 1,
+                position,
+            }
+        } else {
+            EngineEvent::MouseButtonUp {
+                window,
+                button: MouseButton::Left,
+                clicks: 1,
+                position,
+            }
+        }
+    }
+
+    fn key(keycode: Keycode) -> EngineEvent {
+        EngineEvent::KeyDown {
+            window: Some("main".to_owned()),
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn frame(
+        ui: &mut Ui,
+        input: &mut InputState,
+        events: &[EngineEvent],
+        value: &mut f32,
+    ) -> (bool, bool) {
+        input.update(events);
+        ui.begin_frame(input, events);
+        let a = ui.button("a", Rect::new(0, 0, 100, 20));
+        let b = ui.button("b", Rect::new(0, 30, 100, 20));
+        ui.slider("volume", Rect::new(0, 60, 100, 20), value, 0.0..=1.0);
+        ui.end_frame();
+        (a, b)
+    }
+
+    #[test]
+    fn test_style() {
+        let mut style = UiStyle::new(Path::new("font.ttf"), 12);
+        style.scale = 1.5;
+        assert_eq!((style.scaled_point_size(), style.scaled_padding()), (18, 6));
+        assert_eq!(high_contrast(style.background), Color::RGB(0, 0, 0));
+        assert_eq!(high_contrast(style.hovered), Color::RGB(0, 0, 140));
+        assert_eq!(high_contrast(style.accent), Color::RGB(255, 220, 0));
+        assert_eq!(high_contrast(style.panel).a, 220);
+    }
+
+    #[test]
+    fn test_widgets() {
+        let mut ui = Ui::new("main", UiStyle::new(Path::new("font.ttf"), 12));
+        let mut input = InputState::new();
+        let mut value = 0.;
+
+        // a click is a press and release over the same widget
+        assert_eq!(
+            frame(&mut ui, &mut input, &[click((10., 35.), true)], &mut value),
+            (false, false)
+        );
+        assert!(ui.is_focused("b"));
+        assert_eq!(
+            frame(&mut ui, &mut input, &[click((10., 35.), false)], &mut value),
+            (false, true)
+        );
+        // released somewhere else
+        frame(&mut ui, &mut input, &[click((10., 5.), true)], &mut value);
+        assert_eq!(
+            frame(&mut ui, &mut input, &[click((10., 35.), false)], &mut value),
+            (false, false)
+        );
+
+        // dragging the slider
+        frame(&mut ui, &mut input, &[click((75., 70.), true)], &mut value);
+        assert_eq!(value, 0.75);
+        let drag = EngineEvent::MouseMotion {
+            window: Some("main".to_owned()),
+            position: FPoint::new(500., 70.),
+            relative: FPoint::new(425., 0.),
+        };
+        frame(&mut ui, &mut input, &[drag], &mut value);
+        assert_eq!(value, 1.);
+        assert!(ui.wants_mouse());
+        frame(
+            &mut ui,
+            &mut input,
+            &[click((500., 70.), false)],
+            &mut value,
+        );
+
+        // keyboard navigation wraps around
+        assert!(ui.is_focused("volume"));
+        frame(&mut ui, &mut input, &[key(Keycode::Left)], &mut value);
+        assert!((value - 0.95).abs() < 1e-6);
+        frame(&mut ui, &mut input, &[key(Keycode::Tab)], &mut value);
+        assert!(ui.is_focused("a"));
+        assert_eq!(
+            frame(&mut ui, &mut input, &[key(Keycode::Return)], &mut value),
+            (true, false)
+        );
+
+        // clicking nothing drops the focus
+        frame(
+            &mut ui,
+            &mut input,
+            &[click((500., 500.), true)],
+            &mut value,
+        );
+        assert!(!ui.is_focused("a"));
+    }
+
+    #[test]
+    fn test_announcements() {
+        let mut ui = Ui::new("main", UiStyle::new(Path::new("font.ttf"), 12));
+        let heard = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = heard.clone();
+        ui.set_announcer(Some(Box::new(move |a: &Announcement| {
+            sink.borrow_mut().push(a.clone())
+        })));
+        let mut input = InputState::new();
+        let mut value = 0.5;
+
+        frame(&mut ui, &mut input, &[], &mut value);
+        assert!(ui.announcements().is_empty());
+        frame(&mut ui, &mut input, &[key(Keycode::Up)], &mut value);
+        assert_eq!(
+            ui.announcements(),
+            [Announcement::Focus {
+                text: "volume".to_owned(),
+                role: WidgetRole::Slider { value: 0.5 }
+            }]
+        );
+        // only when the focus changes
+        frame(&mut ui, &mut input, &[], &mut value);
+        assert!(ui.announcements().is_empty());
+        ui.announce("paused");
+        assert_eq!(heard.borrow().len(), 2);
+        assert_eq!(heard.borrow()[1], Announcement::Text("paused".to_owned()));
+    }
+
+    #[test]
+    fn test_nine_patch() {
+        let pieces = nine_patch_rects((30, 30), (10, 10, 10, 10), Rect::new(5, 5, 100, 50));
+        assert_eq!(pieces.len(), 9);
+        assert_eq!(
+            pieces[0],
+            (Rect::new(0, 0, 10, 10), Rect::new(5, 5, 10, 10))
+        );
+        assert_eq!(
+            pieces[4],
+            (Rect::new(10, 10, 10, 10), Rect::new(15, 15, 80, 30))
+        );
+        assert_eq!(
+            pieces[8],
+            (Rect::new(20, 20, 10, 10), Rect::new(95, 45, 10, 10))
+        );
+        // too small for the borders; they shrink and the middle is left out
+        let pieces = nine_patch_rects((30, 30), (10, 10, 10, 10), Rect::new(0, 0, 10, 30));
+        assert_eq!(pieces.len(), 6);
+        assert_eq!(pieces[0], (Rect::new(0, 0, 10, 10), Rect::new(0, 0, 5, 10)));
+    }
+}
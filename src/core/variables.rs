@@ -0,0 +1,134 @@
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    /// false, zero and empty text are false
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(v) => *v,
+            Value::Number(v) => *v != 0.,
+            Value::Text(v) => !v.is_empty(),
+        }
+    }
+
+    /// true, false, a number, or text in double quotes
+    pub fn parse(literal: &str) -> Result<Self, String> {
+        let literal = literal.trim();
+        match literal {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+        if let Some(text) = literal
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+        {
+            return Ok(Value::Text(text.to_owned()));
+        }
+        literal
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| format!("\"{literal}\" isn't a value"))
+    }
+
+    fn to_toml(&self) -> toml::Value {
+        match self {
+            Value::Bool(v) => toml::Value::Boolean(*v),
+            Value::Number(v) => toml::Value::Float(*v),
+            Value::Text(v) => toml::Value::String(v.clone()),
+        }
+    }
+
+    fn read_toml(value: &toml::Value) -> Option<Self> {
+        match value {
+            toml::Value::Boolean(v) => Some(Value::Bool(*v)),
+            toml::Value::Float(v) => Some(Value::Number(*v)),
+            toml::Value::Integer(v) => Some(Value::Number(*v as f64)),
+            toml::Value::String(v) => Some(Value::Text(v.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(v) => write!(f, "{v}"),
+            Value::Number(v) => write!(f, "{v}"),
+            Value::Text(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+/// named game state which data can read and change, e.g. flags set by
+/// dialogue and checked by quests. saved with the game
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variables {
+    values: BTreeMap<String, Value>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_owned(), value);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.values.remove(name)
+    }
+
+    /// false if unset
+    pub fn bool(&self, name: &str) -> bool {
+        self.get(name).is_some_and(Value::is_truthy)
+    }
+
+    /// zero if unset or not a number
+    pub fn number(&self, name: &str) -> f64 {
+        match self.get(name) {
+            Some(Value::Number(v)) => *v,
+            _ => 0.,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    pub fn to_toml(&self) -> toml::Table {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_toml()))
+            .collect()
+    }
+
+    /// replace every variable
+    pub fn load_toml(&mut self, table: &toml::Table) -> Result<(), String> {
+        let mut values = BTreeMap::new();
+        for (name, value) in table {
+            let value = Value::read_toml(value)
+                .ok_or_else(|| format!("variable \"{name}\" must be a bool, number or string"))?;
+            values.insert(name.clone(), value);
+        }
+        self.values = values;
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use super::pack::{normalize, Pack};
+
+/// a source of files for the vfs. paths are normalized and relative to where
+/// the provider is mounted
+pub trait VfsProvider {
+    fn contains(&self, path: &str) -> bool;
+    fn read(&self, path: &str) -> Result<Vec<u8>, String>;
+}
+
+impl VfsProvider for Pack {
+    fn contains(&self, path: &str) -> bool {
+        Pack::contains(self, Path::new(path))
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        Pack::read(self, Path::new(path))
+    }
+}
+
+/// files in a directory on disk
+pub struct DirProvider {
+    root: PathBuf,
+}
+
+impl DirProvider {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_owned(),
+        }
+    }
+}
+
+impl VfsProvider for DirProvider {
+    fn contains(&self, path: &str) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        let path = self.root.join(path);
+        std::fs::read(&path).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+/// files held in memory, e.g. generated at runtime or from include_bytes
+#[derive(Default)]
+pub struct MemoryProvider {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryProvider {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add or replace a file
+    pub fn insert(&mut self, path: &Path, data: Vec<u8>) {
+        self.files.insert(normalize(path), data);
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<Vec<u8>> {
+        self.files.remove(&normalize(path))
+    }
+}
+
+impl VfsProvider for MemoryProvider {
+    fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| format!("\"{path}\" isn't in memory"))
+    }
+}
+
+struct Mount {
+    /// normalized. empty for the root
+    prefix: String,
+    priority: i32,
+    provider: Box<dyn VfsProvider>,
+}
+
+/// providers mounted under virtual prefixes, searched in priority order (e.g.
+/// mods above the base game, or platform specific overrides). assets, maps,
+/// fonts, sounds and config are resolved through this. paths not in any
+/// provider are read from the filesystem as is
+#[derive(Default)]
+pub struct Vfs {
+    /// highest priority first
+    mounts: Vec<Mount>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// files in the provider appear under the prefix ("" for the root). higher
+    /// priorities are searched first. among equal priorities, the most
+    /// recently mounted is searched first
+    pub fn mount<P: VfsProvider + 'static>(&mut self, prefix: &Path, provider: P, priority: i32) {
+        let index = self
+            .mounts
+            .iter()
+            .position(|m| m.priority <= priority)
+            .unwrap_or(self.mounts.len());
+        self.mounts.insert(
+            index,
+            Mount {
+                prefix: normalize(prefix),
+                priority,
+                provider: Box::new(provider),
+            },
+        );
+    }
+
+    /// remove every provider mounted at the prefix
+    pub fn unmount(&mut self, prefix: &Path) {
+        let prefix = normalize(prefix);
+        self.mounts.retain(|m| m.prefix != prefix);
+    }
+
+    pub fn clear(&mut self) {
+        self.mounts.clear();
+    }
+
+    /// the provider holding the path and the path relative to its mount
+    fn resolve(&self, path: &Path) -> Option<(&dyn VfsProvider, String)> {
+        let path = normalize(path);
+        self.mounts.iter().find_map(|m| {
+            let relative = if m.prefix.is_empty() {
+                path.as_str()
+            } else {
+                path.strip_prefix(&m.prefix)?.strip_prefix('/')?
+            };
+            m.provider
+                .contains(relative)
+                .then(|| (m.provider.as_ref(), relative.to_owned()))
+        })
+    }
+
+    /// true if a mounted provider has the path. otherwise, reads go to the
+    /// filesystem
+    pub fn is_mounted(&self, path: &Path) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>, String> {
+        match self.resolve(path) {
+            Some((provider, relative)) => provider.read(&relative),
+            None => std::fs::read(path).map_err(|e| format!("{}: {e}", path.display())),
+        }
+    }
+
+    pub fn read_to_string(&self, path: &Path) -> Result<String, String> {
+        String::from_utf8(self.read(path)?).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::pack::PackBuilder;
+
+    #[test]
+    fn test_mounts() {
+        let mut base = PackBuilder::new();
+        base.add(Path::new("sprites/hero.png"), vec![1]);
+        base.add(Path::new("maps/1.tmx"), vec![2]);
+        let mut patch = MemoryProvider::new();
+        patch.insert(Path::new("sprites/hero.png"), vec![3]);
+        let mut dlc = MemoryProvider::new();
+        dlc.insert(Path::new("maps/1.tmx"), vec![4]);
+
+        let mut vfs = Vfs::new();
+        vfs.mount(
+            Path::new(""),
+            Pack::from_bytes(base.to_bytes().unwrap()).unwrap(),
+            0,
+        );
+        vfs.mount(Path::new(""), patch, 10);
+        vfs.mount(Path::new("dlc"), dlc, 20);
+        assert_eq!(vfs.read(Path::new("sprites/hero.png")).unwrap(), vec![3]);
+        assert_eq!(vfs.read(Path::new("maps/1.tmx")).unwrap(), vec![2]);
+        assert_eq!(vfs.read(Path::new("dlc/maps/1.tmx")).unwrap(), vec![4]);
+        assert!(!vfs.is_mounted(Path::new("dlcmaps/1.tmx")));
+
+        vfs.unmount(Path::new(""));
+        assert!(!vfs.is_mounted(Path::new("sprites/hero.png")));
+    }
+}
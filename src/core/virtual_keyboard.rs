@@ -0,0 +1,241 @@
+use sdl2::{controller::Button, rect::Rect};
+
+use super::{
+    input::{Binding, InputState},
+    text_input::TextBuffer,
+    ui::Ui,
+};
+
+/// a key on the on screen keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualKey {
+    Char(char),
+    /// upper cases the next character
+    Shift,
+    Space,
+    Backspace,
+    Done,
+}
+
+impl VirtualKey {
+    fn label(self, shift: bool) -> String {
+        match self {
+            VirtualKey::Char(c) if shift => c.to_uppercase().collect(),
+            VirtualKey::Char(c) => c.to_string(),
+            VirtualKey::Shift => "shift".into(),
+            VirtualKey::Space => "space".into(),
+            VirtualKey::Backspace => "del".into(),
+            VirtualKey::Done => "done".into(),
+        }
+    }
+}
+
+/// a step of navigation, e.g. from a gamepad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardNav {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// press the highlighted key
+    Select,
+    Backspace,
+    Shift,
+    Done,
+    Cancel,
+}
+
+impl KeyboardNav {
+    /// from a gamepad's buttons this frame: the d-pad moves, a presses, b
+    /// deletes, y shifts, start finishes and back cancels
+    pub fn from_gamepad(input: &InputState) -> Vec<Self> {
+        [
+            (Button::DPadUp, KeyboardNav::Up),
+            (Button::DPadDown, KeyboardNav::Down),
+            (Button::DPadLeft, KeyboardNav::Left),
+            (Button::DPadRight, KeyboardNav::Right),
+            (Button::A, KeyboardNav::Select),
+            (Button::B, KeyboardNav::Backspace),
+            (Button::Y, KeyboardNav::Shift),
+            (Button::Start, KeyboardNav::Done),
+            (Button::Back, KeyboardNav::Cancel),
+        ]
+        .into_iter()
+        .filter(|(button, _)| input.just_pressed(Binding::GamepadButton(*button)))
+        .map(|(_, nav)| nav)
+        .collect()
+    }
+}
+
+/// how the text entry ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardOutcome {
+    Done,
+    Cancelled,
+}
+
+/// an on screen keyboard which types into a text buffer, for when there's
+/// only a gamepad. a key is highlighted and moved with KeyboardNav; keys can
+/// also be clicked
+#[derive(Debug, Clone)]
+pub struct VirtualKeyboard {
+    rows: Vec<Vec<VirtualKey>>,
+    /// (row, column) of the highlighted key
+    cursor: (usize, usize),
+    /// applies to the next character
+    pub shift: bool,
+    /// suffix of the keys' ui labels, so several keyboards can coexist
+    id: String,
+}
+
+impl VirtualKeyboard {
+    /// a qwerty layout
+    pub fn new(id: &str) -> Self {
+        let mut rows: Vec<Vec<VirtualKey>> =
+            ["1234567890", "qwertyuiop", "asdfghjkl-", "zxcvbnm,.'"]
+                .iter()
+                .map(|row| row.chars().map(VirtualKey::Char).collect())
+                .collect();
+        rows.push(vec![
+            VirtualKey::Shift,
+            VirtualKey::Space,
+            VirtualKey::Backspace,
+            VirtualKey::Done,
+        ]);
+        Self::with_layout(id, rows)
+    }
+
+    /// empty rows are removed
+    pub fn with_layout(id: &str, mut rows: Vec<Vec<VirtualKey>>) -> Self {
+        rows.retain(|row| !row.is_empty());
+        Self {
+            rows,
+            cursor: (0, 0),
+            shift: false,
+            id: id.to_owned(),
+        }
+    }
+
+    pub fn highlighted(&self) -> Option<VirtualKey> {
+        self.rows.get(self.cursor.0)?.get(self.cursor.1).copied()
+    }
+
+    /// move the highlight, wrapping around the edges. moving between rows of
+    /// different lengths keeps roughly the same horizontal position
+    fn step(&mut self, rows: isize, columns: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let (row, column) = self.cursor;
+        if rows != 0 {
+            let count = self.rows.len() as isize;
+            let next = (row as isize + rows).rem_euclid(count) as usize;
+            let (from, to) = (self.rows[row].len(), self.rows[next].len());
+            self.cursor = (next, ((column * 2 + 1) * to / (from * 2)).min(to - 1));
+        } else {
+            let len = self.rows[row].len() as isize;
+            self.cursor.1 = (column as isize + columns).rem_euclid(len) as usize;
+        }
+    }
+
+    /// type the key into the buffer
+    pub fn press(&mut self, key: VirtualKey, buffer: &mut TextBuffer) -> Option<KeyboardOutcome> {
+        match key {
+            VirtualKey::Char(_) => {
+                buffer.insert(&key.label(self.shift));
+                self.shift = false;
+            }
+            VirtualKey::Shift => self.shift = !self.shift,
+            VirtualKey::Space => buffer.insert(" "),
+            VirtualKey::Backspace => buffer.backspace(),
+            VirtualKey::Done => return Some(KeyboardOutcome::Done),
+        }
+        None
+    }
+
+    pub fn navigate(
+        &mut self,
+        nav: KeyboardNav,
+        buffer: &mut TextBuffer,
+    ) -> Option<KeyboardOutcome> {
+        match nav {
+            KeyboardNav::Up => self.step(-1, 0),
+            KeyboardNav::Down => self.step(1, 0),
+            KeyboardNav::Left => self.step(0, -1),
+            KeyboardNav::Right => self.step(0, 1),
+            KeyboardNav::Select => return self.highlighted().and_then(|k| self.press(k, buffer)),
+            KeyboardNav::Backspace => buffer.backspace(),
+            KeyboardNav::Shift => self.shift = !self.shift,
+            KeyboardNav::Done => return Some(KeyboardOutcome::Done),
+            KeyboardNav::Cancel => return Some(KeyboardOutcome::Cancelled),
+        }
+        None
+    }
+
+    /// declare the keys as ui buttons filling the rect, with each row's keys
+    /// sharing its width. call between begin_frame and end_frame, after
+    /// applying this frame's navigation
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        rect: Rect,
+        buffer: &mut TextBuffer,
+    ) -> Option<KeyboardOutcome> {
+        let mut outcome = None;
+        let row_height = rect.height() / self.rows.len().max(1) as u32;
+        for row in 0..self.rows.len() {
+            let columns = self.rows[row].len() as u32;
+            let y = rect.y() + (row_height * row as u32) as i32;
+            for column in 0..self.rows[row].len() {
+                let key = self.rows[row][column];
+                let x = rect.x() + (rect.width() * column as u32 / columns) as i32;
+                let next = rect.x() + (rect.width() * (column as u32 + 1) / columns) as i32;
+                let label = format!("{}##{}{row},{column}", key.label(self.shift), self.id);
+                if self.cursor == (row, column) {
+                    ui.focus(&label);
+                }
+                let cell = Rect::new(x, y, (next - x).max(1) as u32, row_height.max(1));
+                if ui.button(&label, cell) {
+                    self.cursor = (row, column);
+                    outcome = outcome.or(self.press(key, buffer));
+                }
+            }
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_navigation() {
+        let mut keyboard = VirtualKeyboard::new("name");
+        let mut buffer = TextBuffer::new();
+        keyboard.navigate(KeyboardNav::Down, &mut buffer);
+        keyboard.navigate(KeyboardNav::Left, &mut buffer);
+        assert_eq!(keyboard.highlighted(), Some(VirtualKey::Char('p')));
+        keyboard.navigate(KeyboardNav::Shift, &mut buffer);
+        keyboard.navigate(KeyboardNav::Select, &mut buffer);
+        keyboard.navigate(KeyboardNav::Select, &mut buffer);
+        assert_eq!(buffer.text(), "Pp");
+
+        // from the end of a long row to the short bottom row and back
+        for _ in 0..3 {
+            keyboard.navigate(KeyboardNav::Down, &mut buffer);
+        }
+        assert_eq!(keyboard.highlighted(), Some(VirtualKey::Done));
+        keyboard.navigate(KeyboardNav::Left, &mut buffer);
+        assert_eq!(keyboard.navigate(KeyboardNav::Select, &mut buffer), None);
+        assert_eq!(buffer.text(), "P");
+        keyboard.navigate(KeyboardNav::Down, &mut buffer);
+        assert_eq!(keyboard.highlighted(), Some(VirtualKey::Char('7')));
+        keyboard.navigate(KeyboardNav::Up, &mut buffer);
+        assert_eq!(keyboard.highlighted(), Some(VirtualKey::Backspace));
+        assert_eq!(
+            keyboard.navigate(KeyboardNav::Cancel, &mut buffer),
+            Some(KeyboardOutcome::Cancelled)
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+
+const MAGIC: &[u8; 4] = b"CHWS";
+const VERSION: u32 = 1;
+
+/// zstd level, when the feature is enabled
+#[cfg(feature = "zstd")]
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// the images each window had cached, most recently used first
+pub type CacheContents = Vec<(String, Vec<PathBuf>)>;
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err("warm start file is truncated".into());
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Ok(taken)
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(
+        take(data, 4)?.try_into().expect("length checked"),
+    ))
+}
+
+fn push_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// compressed with zstd if the feature is enabled
+pub fn encode_warm_start(contents: &[(String, Vec<PathBuf>)]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+    for (window_name, paths) in contents {
+        push_bytes(&mut body, window_name.as_bytes());
+        body.extend_from_slice(&(paths.len() as u32).to_le_bytes());
+        for path in paths {
+            push_bytes(&mut body, path.as_os_str().as_bytes());
+        }
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 9);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    #[cfg(feature = "zstd")]
+    if let Ok(compressed) = zstd::encode_all(body.as_slice(), COMPRESSION_LEVEL) {
+        out.push(1);
+        out.extend_from_slice(&compressed);
+        return out;
+    }
+    out.push(0);
+    out.extend_from_slice(&body);
+    out
+}
+
+pub fn decode_warm_start(bytes: &[u8]) -> Result<CacheContents, String> {
+    let mut data = bytes;
+    if take(&mut data, 4)? != MAGIC {
+        return Err("not a warm start file".into());
+    }
+    let version = take_u32(&mut data)?;
+    if version != VERSION {
+        return Err(format!("unsupported warm start version {version}"));
+    }
+    let body = match take(&mut data, 1)?[0] {
+        0 => data.to_vec(),
+        #[cfg(feature = "zstd")]
+        1 => zstd::decode_all(data).map_err(|e| e.to_string())?,
+        #[cfg(not(feature = "zstd"))]
+        1 => return Err("warm start file is compressed; requires the zstd feature".into()),
+        flag => return Err(format!("unknown warm start compression {flag}")),
+    };
+
+    let mut data = body.as_slice();
+    let windows = take_u32(&mut data)?;
+    let mut contents = Vec::new();
+    for _ in 0..windows {
+        let len = take_u32(&mut data)? as usize;
+        let window_name =
+            String::from_utf8(take(&mut data, len)?.to_vec()).map_err(|e| e.to_string())?;
+        let count = take_u32(&mut data)?;
+        let mut paths = Vec::new();
+        for _ in 0..count {
+            let len = take_u32(&mut data)? as usize;
+            paths.push(PathBuf::from(OsStr::from_bytes(take(&mut data, len)?)));
+        }
+        contents.push((window_name, paths));
+    }
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warm_start() {
+        let contents = vec![
+            (
+                "main".to_owned(),
+                vec![PathBuf::from("a.png"), PathBuf::from("dir/b.png")],
+            ),
+            ("map".to_owned(), vec![]),
+        ];
+        let bytes = encode_warm_start(&contents);
+        assert_eq!(decode_warm_start(&bytes), Ok(contents));
+        assert!(decode_warm_start(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_warm_start(b"CHPK").is_err());
+    }
+}
@@ -0,0 +1,509 @@
+use std::{collections::BTreeMap, ops::Range, time::Duration};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FPoint, FRect},
+};
+
+use super::{entity::Entity, renderer::Vertex, rng::Rng, system::ChimericSystem, world::World};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WeatherKind {
+    Rain,
+    Snow,
+    Fog,
+    Wind,
+}
+
+/// how a kind of weather looks and sounds. the presets' sounds are paths for
+/// the application to provide, or replace
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeatherPreset {
+    /// particles over the area at full intensity
+    pub max_particles: usize,
+    /// downwards, units per second
+    pub fall_speed: Range<f32>,
+    /// how much of the wind's speed the particles take on
+    pub drift: f32,
+    /// the width of the particles' side to side wobble
+    pub sway: f32,
+    /// each particle's width and length, along its velocity
+    pub size: (f32, f32),
+    pub color: Color,
+    /// filled over the area, its alpha scaled by the intensity
+    pub overlay: Option<Color>,
+    /// for wind: the speed at full intensity, and of each gust on top
+    pub wind: f32,
+    pub gust: f32,
+    /// looped while the weather is on
+    pub ambient_sound: Option<String>,
+    /// played at each gust
+    pub gust_sound: Option<String>,
+}
+
+impl WeatherPreset {
+    pub fn rain() -> Self {
+        Self {
+            max_particles: 600,
+            fall_speed: 500.0..700.,
+            drift: 0.5,
+            sway: 0.,
+            size: (1., 12.),
+            color: Color::RGBA(170, 190, 230, 150),
+            overlay: Some(Color::RGBA(20, 30, 50, 60)),
+            wind: 0.,
+            gust: 0.,
+            ambient_sound: Some("rain.ogg".into()),
+            gust_sound: None,
+        }
+    }
+
+    pub fn snow() -> Self {
+        Self {
+            max_particles: 400,
+            fall_speed: 30.0..70.,
+            drift: 1.,
+            sway: 12.,
+            size: (3., 3.),
+            color: Color::RGBA(255, 255, 255, 220),
+            overlay: Some(Color::RGBA(230, 235, 255, 30)),
+            wind: 0.,
+            gust: 0.,
+            ambient_sound: None,
+            gust_sound: None,
+        }
+    }
+
+    /// large faint puffs drifting across a haze
+    pub fn fog() -> Self {
+        Self {
+            max_particles: 24,
+            fall_speed: -2.0..2.,
+            drift: 0.3,
+            sway: 20.,
+            size: (160., 90.),
+            color: Color::RGBA(200, 200, 210, 30),
+            overlay: Some(Color::RGBA(190, 190, 200, 90)),
+            wind: 0.,
+            gust: 0.,
+            ambient_sound: None,
+            gust_sound: None,
+        }
+    }
+
+    /// blows the other weather sideways, with streaks of dust
+    pub fn wind() -> Self {
+        Self {
+            max_particles: 40,
+            fall_speed: -10.0..10.,
+            drift: 1.5,
+            sway: 4.,
+            size: (1., 30.),
+            color: Color::RGBA(255, 255, 255, 40),
+            overlay: None,
+            wind: 120.,
+            gust: 200.,
+            ambient_sound: Some("wind.ogg".into()),
+            gust_sound: Some("gust.ogg".into()),
+        }
+    }
+
+    pub fn for_kind(kind: WeatherKind) -> Self {
+        match kind {
+            WeatherKind::Rain => Self::rain(),
+            WeatherKind::Snow => Self::snow(),
+            WeatherKind::Fog => Self::fog(),
+            WeatherKind::Wind => Self::wind(),
+        }
+    }
+}
+
+/// for the application to play through its audio system. also published on
+/// the world's event bus when weather is an entity
+#[derive(Debug, Clone, PartialEq)]
+pub enum WeatherSound {
+    /// start looping the sound. its volume should follow the intensity
+    Loop { kind: WeatherKind, path: String },
+    /// stop the loop started for the kind
+    StopLoop { kind: WeatherKind },
+    /// play once, at a volume from zero to one
+    Play { path: String, volume: f32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    position: FPoint,
+    fall: f32,
+    /// offsets the sway, so particles don't wobble together
+    phase: f32,
+}
+
+#[derive(Debug, Clone)]
+struct Layer {
+    preset: WeatherPreset,
+    intensity: f32,
+    target: f32,
+    particles: Vec<Particle>,
+}
+
+/// weather over an area of the screen, e.g. a whole window. each kind is
+/// faded in and out to its target intensity, from zero to one
+pub struct Weather {
+    layers: BTreeMap<WeatherKind, Layer>,
+    pub area: FRect,
+    /// off skips updating and drawing, e.g. for indoor scenes. the weather is
+    /// kept as it was
+    pub enabled: bool,
+    /// seconds to fade from nothing to full intensity
+    pub fade: f32,
+    rng: Rng,
+    /// sideways, units per second
+    wind: f32,
+    gust: f32,
+    until_gust: f32,
+    elapsed: f32,
+    sounds: Vec<WeatherSound>,
+    pub window_name: String,
+}
+
+impl Weather {
+    pub fn new(area: FRect, rng: Rng, window_name: &str) -> Self {
+        Self {
+            layers: BTreeMap::new(),
+            area,
+            enabled: true,
+            fade: 2.,
+            rng,
+            wind: 0.,
+            gust: 0.,
+            until_gust: 0.,
+            elapsed: 0.,
+            sounds: Vec::new(),
+            window_name: window_name.to_owned(),
+        }
+    }
+
+    /// fade the kind to the intensity, using its default preset if it isn't
+    /// on already
+    pub fn set(&mut self, kind: WeatherKind, intensity: f32) {
+        match self.layers.get_mut(&kind) {
+            Some(layer) => layer.target = intensity.clamp(0., 1.),
+            None => self.set_with(kind, WeatherPreset::for_kind(kind), intensity),
+        }
+    }
+
+    /// as set, replacing the kind's preset
+    pub fn set_with(&mut self, kind: WeatherKind, preset: WeatherPreset, intensity: f32) {
+        let target = intensity.clamp(0., 1.);
+        match self.layers.get_mut(&kind) {
+            Some(layer) => {
+                layer.preset = preset;
+                layer.target = target;
+            }
+            None => {
+                if target <= 0. {
+                    return;
+                }
+                if let Some(path) = &preset.ambient_sound {
+                    self.sounds.push(WeatherSound::Loop {
+                        kind,
+                        path: path.clone(),
+                    });
+                }
+                self.layers.insert(
+                    kind,
+                    Layer {
+                        preset,
+                        intensity: 0.,
+                        target,
+                        particles: Vec::new(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// fade the kind out
+    pub fn stop(&mut self, kind: WeatherKind) {
+        if let Some(layer) = self.layers.get_mut(&kind) {
+            layer.target = 0.;
+        }
+    }
+
+    /// remove all weather now, without fading
+    pub fn clear(&mut self) {
+        for (kind, layer) in std::mem::take(&mut self.layers) {
+            if layer.preset.ambient_sound.is_some() {
+                self.sounds.push(WeatherSound::StopLoop { kind });
+            }
+        }
+        self.wind = 0.;
+        self.gust = 0.;
+    }
+
+    /// the current intensity, which fades towards the one set
+    pub fn intensity(&self, kind: WeatherKind) -> f32 {
+        self.layers.get(&kind).map_or(0., |l| l.intensity)
+    }
+
+    /// the sideways speed the wind gives drifting particles
+    pub fn wind(&self) -> f32 {
+        self.wind + self.gust
+    }
+
+    /// the sound hooks since last taken, oldest first
+    pub fn take_sounds(&mut self) -> Vec<WeatherSound> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.layers.values().map(|l| l.particles.len()).sum()
+    }
+
+    fn spawn(rng: &mut Rng, area: FRect, preset: &WeatherPreset, anywhere: bool) -> Particle {
+        let y = if anywhere {
+            area.y() + rng.next_f32() * area.height()
+        } else {
+            area.y() - preset.size.1
+        };
+        Particle {
+            position: FPoint::new(area.x() + rng.next_f32() * area.width(), y),
+            fall: if preset.fall_speed.is_empty() {
+                preset.fall_speed.start
+            } else {
+                rng.range_f32(preset.fall_speed.clone())
+            },
+            phase: rng.next_f32() * std::f32::consts::TAU,
+        }
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        if !self.enabled {
+            return;
+        }
+        let dt = dt.as_secs_f32();
+        self.elapsed += dt;
+        let step = if self.fade > 0. { dt / self.fade } else { 1. };
+
+        let mut ended = Vec::new();
+        for (kind, layer) in self.layers.iter_mut() {
+            layer.intensity = if layer.intensity < layer.target {
+                (layer.intensity + step).min(layer.target)
+            } else {
+                (layer.intensity - step).max(layer.target)
+            };
+            if layer.intensity <= 0. && layer.target <= 0. {
+                ended.push(*kind);
+            }
+        }
+        for kind in ended {
+            if let Some(layer) = self.layers.remove(&kind) {
+                if layer.preset.ambient_sound.is_some() {
+                    self.sounds.push(WeatherSound::StopLoop { kind });
+                }
+            }
+        }
+
+        // the wind layer sets the wind, with gusts now and then
+        match self.layers.get(&WeatherKind::Wind) {
+            Some(layer) => {
+                let intensity = layer.intensity;
+                self.wind = layer.preset.wind * intensity;
+                self.until_gust -= dt;
+                if self.until_gust <= 0. {
+                    let strength = self.rng.range_f32(0.5..1.) * intensity;
+                    self.gust = layer.preset.gust * strength;
+                    self.until_gust = self.rng.range_f32(3.0..8.);
+                    if let Some(path) = &layer.preset.gust_sound {
+                        self.sounds.push(WeatherSound::Play {
+                            path: path.clone(),
+                            volume: strength,
+                        });
+                    }
+                }
+            }
+            None => self.wind = 0.,
+        }
+        // gusts die down over a couple of seconds
+        self.gust *= (-dt).exp();
+
+        let wind = self.wind();
+        let area = self.area;
+        let elapsed = self.elapsed;
+        for layer in self.layers.values_mut() {
+            let preset = &layer.preset;
+            let wanted = (preset.max_particles as f32 * layer.intensity).round() as usize;
+            layer.particles.truncate(wanted);
+            while layer.particles.len() < wanted {
+                // the first fill is spread out, rather than all at the top
+                let anywhere = layer.particles.is_empty() || layer.intensity >= layer.target;
+                layer
+                    .particles
+                    .push(Self::spawn(&mut self.rng, area, preset, anywhere));
+            }
+            let margin = preset.size.0.max(preset.size.1);
+            for particle in layer.particles.iter_mut() {
+                let sway = (elapsed * 2. + particle.phase).cos() * preset.sway;
+                let x = particle.position.x() + (wind * preset.drift + sway) * dt;
+                let y = particle.position.y() + particle.fall * dt;
+                particle.position = FPoint::new(x, y);
+                // wrapped around, so the area stays evenly covered
+                if y > area.bottom() + margin {
+                    *particle = Self::spawn(&mut self.rng, area, preset, false);
+                } else if y < area.top() - margin {
+                    particle.position = FPoint::new(x, area.bottom() + margin);
+                }
+                if x > area.right() + margin {
+                    particle.position = FPoint::new(area.left() - margin, particle.position.y());
+                } else if x < area.left() - margin {
+                    particle.position = FPoint::new(area.right() + margin, particle.position.y());
+                }
+            }
+        }
+    }
+
+    /// every particle as a quad stretched along its velocity, and the
+    /// overlays
+    pub fn mesh(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut quad = |corners: [FPoint; 4], color: Color| {
+            let start = vertices.len() as u32;
+            vertices.extend(corners.map(|position| Vertex { position, color }));
+            indices.extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+        };
+        let area = self.area;
+        let scale_alpha = |color: Color, intensity: f32| {
+            Color::RGBA(
+                color.r,
+                color.g,
+                color.b,
+                (color.a as f32 * intensity).round() as u8,
+            )
+        };
+        for layer in self.layers.values() {
+            if let Some(overlay) = layer.preset.overlay {
+                quad(
+                    [
+                        FPoint::new(area.left(), area.top()),
+                        FPoint::new(area.right(), area.top()),
+                        FPoint::new(area.right(), area.bottom()),
+                        FPoint::new(area.left(), area.bottom()),
+                    ],
+                    scale_alpha(overlay, layer.intensity),
+                );
+            }
+        }
+        let wind = self.wind();
+        for layer in self.layers.values() {
+            let preset = &layer.preset;
+            let color = scale_alpha(preset.color, layer.intensity.sqrt());
+            let (half_width, half_length) = (preset.size.0 / 2., preset.size.1 / 2.);
+            for particle in layer.particles.iter() {
+                let (vx, vy) = (wind * preset.drift, particle.fall);
+                let speed = vx.hypot(vy);
+                let (fx, fy) = if speed > f32::EPSILON {
+                    (vx / speed, vy / speed)
+                } else {
+                    (0., 1.)
+                };
+                let p = particle.position;
+                let corner = |along: f32, across: f32| {
+                    FPoint::new(
+                        p.x() + fx * along - fy * across,
+                        p.y() + fy * along + fx * across,
+                    )
+                };
+                quad(
+                    [
+                        corner(-half_length, -half_width),
+                        corner(-half_length, half_width),
+                        corner(half_length, half_width),
+                        corner(half_length, -half_width),
+                    ],
+                    color,
+                );
+            }
+        }
+        (vertices, indices)
+    }
+
+    pub fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        if !self.enabled || self.layers.is_empty() {
+            return Ok(());
+        }
+        let (vertices, indices) = self.mesh();
+        system.draw_triangles(&self.window_name, &vertices, &indices)
+    }
+}
+
+/// updates with the world's timestep, publishing its sounds on the event bus
+impl Entity for Weather {
+    fn update(&mut self, world: &mut World) -> Result<(), String> {
+        Weather::update(self, world.timestep());
+        for sound in self.take_sounds() {
+            world.events.publish(sound);
+        }
+        Ok(())
+    }
+
+    fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        Weather::draw(self, system)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weather() {
+        let area = FRect::new(0., 0., 320., 240.);
+        let mut weather = Weather::new(area, Rng::new(5), "main");
+        weather.fade = 1.;
+        weather.set(WeatherKind::Rain, 0.5);
+        weather.set(WeatherKind::Wind, 1.);
+        assert_eq!(
+            weather.take_sounds(),
+            [
+                WeatherSound::Loop {
+                    kind: WeatherKind::Rain,
+                    path: "rain.ogg".into()
+                },
+                WeatherSound::Loop {
+                    kind: WeatherKind::Wind,
+                    path: "wind.ogg".into()
+                },
+            ]
+        );
+
+        for _ in 0..60 {
+            weather.update(Duration::from_millis(20));
+        }
+        assert_eq!(weather.intensity(WeatherKind::Rain), 0.5);
+        assert_eq!(weather.particle_count(), 300 + 40);
+        assert!(weather.wind() >= 120.);
+        assert!(matches!(
+            weather.take_sounds()[..],
+            [WeatherSound::Play { .. }, ..]
+        ));
+        let (vertices, indices) = weather.mesh();
+        // an overlay for the rain, and a quad per particle
+        assert_eq!(vertices.len(), (1 + 340) * 4);
+        assert_eq!(indices.len(), (1 + 340) * 6);
+
+        weather.enabled = false;
+        weather.update(Duration::from_secs(5));
+        assert_eq!(weather.intensity(WeatherKind::Rain), 0.5);
+        weather.enabled = true;
+        weather.stop(WeatherKind::Rain);
+        weather.update(Duration::from_secs(1));
+        assert_eq!(weather.intensity(WeatherKind::Rain), 0.);
+        assert_eq!(
+            weather.take_sounds(),
+            [WeatherSound::StopLoop {
+                kind: WeatherKind::Rain
+            }]
+        );
+    }
+}
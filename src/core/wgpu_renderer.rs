@@ -0,0 +1,854 @@
+#[cfg(feature = "ttf")]
+use std::ffi::CStr;
+use std::{
+    num::NonZeroUsize,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
+
+use sdl2::{
+    pixels::Color,
+    rect::{FRect, Rect},
+    surface::Surface,
+    video::Window,
+};
+use wgpu::util::DeviceExt;
+
+use super::{
+    decode::{clear_color_key, read_surface, rgba_pixels, DecodedImage},
+    post_process::{swap_palette, Palette},
+    profiler,
+    render_system_txt_key::FileOrRenderedTextKey,
+    renderer::{DrawParams, Renderer, Vertex},
+    texture_cache::{CacheClass, TextureCache},
+    trace::{log_debug, log_warn},
+    vfs::Vfs,
+};
+
+const SHADER: &str = r#"
+struct Screen {
+    size: vec2<f32>,
+    _pad: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(1) @binding(0) var tex: texture_2d<f32>;
+@group(1) @binding(1) var samp: sampler;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) pos: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOut {
+    var out: VertexOut;
+    out.pos = vec4<f32>(
+        pos.x / screen.size.x * 2.0 - 1.0,
+        1.0 - pos.y / screen.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.uv = uv;
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return textureSample(tex, samp, in.uv) * in.color;
+}
+"#;
+
+/// position, uv, color
+const VERTEX_FLOATS: usize = 8;
+
+struct GpuTexture {
+    bind_group: wgpu::BindGroup,
+    size: (u32, u32),
+    _texture: wgpu::Texture,
+}
+
+/// a window drawn with wgpu instead of the sdl canvas. textures and rendered
+/// text are cached with the same keys as RenderSystem. draws are batched per
+/// texture and submitted on present
+pub struct WgpuRenderer<'sdl> {
+    /// declared before the window; it must be dropped first
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    screen: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    /// used for fill_rect
+    white: Rc<GpuTexture>,
+    /// kept in an rc so the frame's batches can outlive an eviction
+    textures: TextureCache<Rc<GpuTexture>>,
+    /// the frame so far
+    vertices: Vec<f32>,
+    /// each with the clip rect it's drawn with
+    batches: Vec<(Rc<GpuTexture>, Range<u32>, Option<Rect>)>,
+    clip: Option<Rect>,
+    clear_color: Color,
+    logical_size: (u32, u32),
+    window: Window,
+    _phantom: std::marker::PhantomData<&'sdl ()>,
+}
+
+/// the viewport (x, y, width, height) the logical area is scaled to, centered
+/// with bars on the sides that don't fit
+fn letterbox(output: (u32, u32), logical: (u32, u32)) -> (f32, f32, f32, f32) {
+    if logical.0 == 0 || logical.1 == 0 {
+        return (0., 0., output.0 as f32, output.1 as f32);
+    }
+    let scale = (output.0 as f32 / logical.0 as f32).min(output.1 as f32 / logical.1 as f32);
+    let width = logical.0 as f32 * scale;
+    let height = logical.1 as f32 * scale;
+    (
+        (output.0 as f32 - width) / 2.,
+        (output.1 as f32 - height) / 2.,
+        width,
+        height,
+    )
+}
+
+/// the clip rect, in logical coordinates, as a scissor rect (x, y, width,
+/// height) in target pixels. None if nothing is visible
+fn scissor(
+    clip: Rect,
+    viewport: (f32, f32, f32, f32),
+    area: (u32, u32),
+    target: (u32, u32),
+) -> Option<(u32, u32, u32, u32)> {
+    let (x, y, w, h) = viewport;
+    let (sx, sy) = (w / area.0.max(1) as f32, h / area.1.max(1) as f32);
+    let clamp = |v: f32, max: u32| (v.round().max(0.) as u32).min(max);
+    let left = clamp(x + clip.left() as f32 * sx, target.0);
+    let top = clamp(y + clip.top() as f32 * sy, target.1);
+    let right = clamp(x + clip.right() as f32 * sx, target.0);
+    let bottom = clamp(y + clip.bottom() as f32 * sy, target.1);
+    (right > left && bottom > top).then(|| (left, top, right - left, bottom - top))
+}
+
+/// the corners of the drawn quad (top left, top right, bottom right, bottom
+/// left) as x, y, u, v
+fn quad(draw: &DrawParams, texture_size: (u32, u32), area: (u32, u32)) -> [[f32; 4]; 4] {
+    let dst = draw
+        .dst
+        .unwrap_or_else(|| FRect::new(0., 0., area.0 as f32, area.1 as f32));
+    let (tw, th) = (texture_size.0.max(1) as f32, texture_size.1.max(1) as f32);
+    let (mut u0, mut v0, mut u1, mut v1) = match draw.src {
+        None => (0., 0., 1., 1.),
+        Some(src) => (
+            src.x() as f32 / tw,
+            src.y() as f32 / th,
+            src.right() as f32 / tw,
+            src.bottom() as f32 / th,
+        ),
+    };
+    if draw.flip_horizontal {
+        std::mem::swap(&mut u0, &mut u1);
+    }
+    if draw.flip_vertical {
+        std::mem::swap(&mut v0, &mut v1);
+    }
+    let (cx, cy) = match draw.center {
+        Some(center) => (dst.x() + center.x(), dst.y() + center.y()),
+        None => (dst.x() + dst.width() / 2., dst.y() + dst.height() / 2.),
+    };
+    let (sin, cos) = (draw.angle.to_radians() as f32).sin_cos();
+    // clockwise, since y is down
+    let corner = |x: f32, y: f32, u: f32, v: f32| {
+        let (dx, dy) = (x - cx, y - cy);
+        [cx + dx * cos - dy * sin, cy + dx * sin + dy * cos, u, v]
+    };
+    [
+        corner(dst.left(), dst.top(), u0, v0),
+        corner(dst.right(), dst.top(), u1, v0),
+        corner(dst.right(), dst.bottom(), u1, v1),
+        corner(dst.left(), dst.bottom(), u0, v1),
+    ]
+}
+
+impl<'sdl> WgpuRenderer<'sdl> {
+    /// caching up to the number of textures from files, and separately the
+    /// number of rendered text
+    pub fn new(
+        window: Window,
+        num_textures: NonZeroUsize,
+        num_text: NonZeroUsize,
+    ) -> Result<Self, String> {
+        let instance = wgpu::Instance::default();
+        // safety: the surface is dropped before the window; see field order
+        let surface = unsafe {
+            let target =
+                wgpu::SurfaceTargetUnsafe::from_window(&window).map_err(|e| e.to_string())?;
+            instance.create_surface_unsafe(target)
+        }
+        .map_err(|e| e.to_string())?;
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .ok_or("no suitable graphics adapter")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("chimeric"),
+                required_features: wgpu::Features::empty(),
+                required_limits:
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            },
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        let (width, height) = window.drawable_size();
+        let mut config = surface
+            .get_default_config(&adapter, width.max(1), height.max(1))
+            .ok_or("surface isn't supported by the adapter")?;
+        // blend in the same (non linear) space as the sdl canvas
+        let capabilities = surface.get_capabilities(&adapter);
+        if let Some(format) = capabilities.formats.iter().find(|f| !f.is_srgb()) {
+            config.format = *format;
+        }
+        config.present_mode = wgpu::PresentMode::Fifo;
+        surface.configure(&device, &config);
+
+        let screen_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("screen"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let screen = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("screen"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("screen"),
+            layout: &screen_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sprite"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sprite"),
+            bind_group_layouts: &[&screen_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sprite"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: (VERTEX_FLOATS * size_of::<f32>()) as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4],
+                }],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        // same as the sdl canvas' default scale quality
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("nearest"),
+            ..Default::default()
+        });
+
+        let mut renderer = Self {
+            surface,
+            white: Rc::new(upload(
+                &device,
+                &queue,
+                &texture_layout,
+                &sampler,
+                &[255; 4],
+                (1, 1),
+            )),
+            device,
+            queue,
+            config,
+            pipeline,
+            texture_layout,
+            sampler,
+            screen,
+            screen_bind_group,
+            textures: TextureCache::new(num_textures, num_text),
+            vertices: Default::default(),
+            batches: Default::default(),
+            clip: None,
+            clear_color: Color::BLACK,
+            logical_size: (0, 0),
+            window,
+            _phantom: Default::default(),
+        };
+        renderer.clear(Color::BLACK)?;
+        Ok(renderer)
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// draw to an area of this size, scaled to fit the window. (0, 0) draws
+    /// in pixels
+    pub fn set_logical_size(&mut self, width: u32, height: u32) {
+        self.logical_size = (width, height);
+    }
+
+    fn upload(&self, surface: Surface) -> Result<GpuTexture, String> {
+        Ok(self.upload_image(&rgba_pixels(surface)?))
+    }
+
+    fn upload_image(&self, image: &DecodedImage) -> GpuTexture {
+        upload(
+            &self.device,
+            &self.queue,
+            &self.texture_layout,
+            &self.sampler,
+            &image.rgba,
+            (image.width, image.height),
+        )
+    }
+
+    fn load(
+        &mut self,
+        key: FileOrRenderedTextKey,
+        load: impl FnOnce(&Self) -> Result<GpuTexture, String>,
+    ) -> Result<Rc<GpuTexture>, String> {
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(texture.clone());
+        }
+        let texture = Rc::new(load(self)?);
+        self.textures.put(key, texture.clone());
+        Ok(texture)
+    }
+
+    /// the image at the path (resolved through the vfs), loading it if it's
+    /// not cached
+    fn image(&mut self, assets: &Vfs, path: &Path) -> Result<Rc<GpuTexture>, String> {
+        let key = FileOrRenderedTextKey::from_path(path);
+        self.load(key, |this| {
+            let _zone = profiler::zone("texture load");
+            let start = Instant::now();
+            let texture = read_surface(assets, path).and_then(|surface| this.upload(surface));
+            match &texture {
+                Ok(_) => log_debug!(
+                    "texture cache miss; loaded {} in {:?}",
+                    path.display(),
+                    start.elapsed()
+                ),
+                Err(e) => log_warn!("failed to load texture {}: {e}", path.display()),
+            }
+            texture
+        })
+    }
+
+    /// add a draw of the texture to the frame
+    fn push(
+        &mut self,
+        texture: &Rc<GpuTexture>,
+        draw: &DrawParams,
+        color: Color,
+    ) -> Result<(), String> {
+        let area = self.drawable_size()?;
+        let corners = quad(draw, texture.size, area);
+        let color = [color.r, color.g, color.b, color.a].map(|c| c as f32 / 255.);
+        let start = (self.vertices.len() / VERTEX_FLOATS) as u32;
+        for index in [0, 1, 2, 0, 2, 3] {
+            self.vertices.extend_from_slice(&corners[index]);
+            self.vertices.extend_from_slice(&color);
+        }
+        self.extend_batch(texture, start);
+        Ok(())
+    }
+
+    /// include the vertices from start on in the frame's batches
+    fn extend_batch(&mut self, texture: &Rc<GpuTexture>, start: u32) {
+        let end = (self.vertices.len() / VERTEX_FLOATS) as u32;
+        match self.batches.last_mut() {
+            Some((last, range, clip)) if Rc::ptr_eq(last, texture) && *clip == self.clip => {
+                range.end = end
+            }
+            _ => self.batches.push((texture.clone(), start..end, self.clip)),
+        }
+    }
+
+    /// match the surface to the window's size
+    fn resize(&mut self) {
+        let (width, height) = self.window.drawable_size();
+        if (width, height) != (self.config.width, self.config.height) && width != 0 && height != 0 {
+            self.config.width = width;
+            self.config.height = height;
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+}
+
+fn upload(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    rgba: &[u8],
+    size: (u32, u32),
+) -> GpuTexture {
+    let extent = wgpu::Extent3d {
+        width: size.0,
+        height: size.1,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(size.0 * 4),
+            rows_per_image: Some(size.1),
+        },
+        extent,
+    );
+    let view = texture.create_view(&Default::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+    GpuTexture {
+        bind_group,
+        size,
+        _texture: texture,
+    }
+}
+
+impl<'sdl> Renderer<'sdl> for WgpuRenderer<'sdl> {
+    fn window_id(&self) -> u32 {
+        self.window.id()
+    }
+
+    fn output_size(&self) -> Result<(u32, u32), String> {
+        Ok(self.window.drawable_size())
+    }
+
+    fn logical_size(&self) -> (u32, u32) {
+        self.logical_size
+    }
+
+    fn clear(&mut self, color: Color) -> Result<(), String> {
+        self.clear_color = color;
+        self.vertices.clear();
+        self.batches.clear();
+        self.clip = None;
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        // the batches hold their own references
+        self.unlock_textures();
+        self.resize();
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.surface.configure(&self.device, &self.config);
+                self.surface
+                    .get_current_texture()
+                    .map_err(|e| e.to_string())?
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let view = frame.texture.create_view(&Default::default());
+
+        let (width, height) = self.drawable_size()?;
+        let screen: Vec<u8> = [width as f32, height as f32, 0., 0.]
+            .iter()
+            .flat_map(|f| f.to_ne_bytes())
+            .collect();
+        self.queue.write_buffer(&self.screen, 0, &screen);
+        let vertices = (!self.vertices.is_empty()).then(|| {
+            let contents: Vec<u8> = self.vertices.iter().flat_map(|f| f.to_ne_bytes()).collect();
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("vertices"),
+                    contents: &contents,
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let c = self.clear_color;
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("frame"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: c.r as f64 / 255.,
+                            g: c.g as f64 / 255.,
+                            b: c.b as f64 / 255.,
+                            a: c.a as f64 / 255.,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            if let Some(vertices) = vertices.as_ref() {
+                let (x, y, w, h) =
+                    letterbox((self.config.width, self.config.height), self.logical_size);
+                pass.set_viewport(x, y, w, h, 0., 1.);
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &self.screen_bind_group, &[]);
+                pass.set_vertex_buffer(0, vertices.slice(..));
+                let target = (self.config.width, self.config.height);
+                for (texture, range, clip) in self.batches.iter() {
+                    let (sx, sy, sw, sh) = match clip {
+                        None => (0, 0, target.0, target.1),
+                        Some(clip) => match scissor(*clip, (x, y, w, h), (width, height), target) {
+                            Some(scissor) => scissor,
+                            None => continue,
+                        },
+                    };
+                    pass.set_scissor_rect(sx, sy, sw, sh);
+                    pass.set_bind_group(1, &texture.bind_group, &[]);
+                    pass.draw(range.clone(), 0..1);
+                }
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+        Ok(())
+    }
+
+    fn screenshot(&self) -> Result<Surface<'static>, String> {
+        Err("screenshots aren't supported by the wgpu renderer".into())
+    }
+
+    fn draw_texture(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let texture = self.image(assets, path)?;
+        draws
+            .iter()
+            .try_for_each(|draw| self.push(&texture, draw, Color::WHITE))
+    }
+
+    fn draw_texture_recolored(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        palette_name: &str,
+        palette: &Palette,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let key = FileOrRenderedTextKey::from_path_with_palette(path, palette_name);
+        let texture = self.load(key, |this| {
+            let mut image = read_surface(assets, path).and_then(rgba_pixels)?;
+            swap_palette(&mut image.rgba, palette);
+            log_debug!("recolored {} with palette {}", path.display(), palette_name);
+            Ok(this.upload_image(&image))
+        })?;
+        draws
+            .iter()
+            .try_for_each(|draw| self.push(&texture, draw, Color::WHITE))
+    }
+
+    fn draw_texture_color_keyed(
+        &mut self,
+        assets: &Vfs,
+        path: &Path,
+        color_key: Color,
+        draws: &[DrawParams],
+    ) -> Result<(), String> {
+        let key = FileOrRenderedTextKey::from_path_with_color_key(path, color_key.rgb());
+        let texture = self.load(key, |this| {
+            let mut image = read_surface(assets, path).and_then(rgba_pixels)?;
+            clear_color_key(&mut image.rgba, color_key);
+            Ok(this.upload_image(&image))
+        })?;
+        draws
+            .iter()
+            .try_for_each(|draw| self.push(&texture, draw, Color::WHITE))
+    }
+
+    fn texture_size(&mut self, assets: &Vfs, path: &Path) -> Result<(u32, u32), String> {
+        Ok(self.image(assets, path)?.size)
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) -> Result<(), String> {
+        self.clip = clip;
+        Ok(())
+    }
+
+    #[cfg(feature = "ttf")]
+    fn draw_text(
+        &mut self,
+        font_system: &mut super::font_system::font_system::FontSystem<'sdl>,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        wrap_width: Option<u32>,
+        draw: &DrawParams,
+    ) -> Result<(), String> {
+        let key = match wrap_width {
+            Some(wrap_width) => FileOrRenderedTextKey::from_rendered_wrapped_text(
+                text, font_file, point_size, wrap_width,
+            ),
+            None => FileOrRenderedTextKey::from_rendered_text(text, font_file, point_size),
+        };
+        let texture = self.load(key, |this| {
+            let _zone = profiler::zone("text render");
+            let start = Instant::now();
+            let surface = font_system.render(assets, font_file, point_size, text, wrap_width)?;
+            let texture = this.upload(surface);
+            log_debug!(
+                "text cache miss; rendered {:?} in {:?}",
+                text,
+                start.elapsed()
+            );
+            texture
+        })?;
+        self.push(&texture, draw, Color::WHITE)
+    }
+
+    #[cfg(feature = "ttf")]
+    fn draw_text_on_path(
+        &mut self,
+        font_system: &mut super::font_system::font_system::FontSystem<'sdl>,
+        assets: &Vfs,
+        font_file: &Path,
+        point_size: u16,
+        text: &CStr,
+        path: &super::text_path::TextPath,
+    ) -> Result<(), String> {
+        let key =
+            FileOrRenderedTextKey::from_text_on_path(text, font_file, point_size, path.hash_key());
+        let texture = self.load(key, |this| {
+            let _zone = profiler::zone("text render");
+            let start = Instant::now();
+            let image = font_system.render_on_path(assets, font_file, point_size, text, path)?;
+            log_debug!(
+                "text cache miss; rendered {:?} along a path in {:?}",
+                text,
+                start.elapsed()
+            );
+            Ok(this.upload_image(&image))
+        })?;
+        let area = super::renderer::rect_to_f(path.area(point_size));
+        self.push(&texture, &DrawParams::new(None, Some(area)), Color::WHITE)
+    }
+
+    #[cfg(feature = "ttf")]
+    fn evict_font_text(&mut self, font_file: &Path) -> usize {
+        self.textures.evict_font_text(font_file)
+    }
+
+    fn set_cache_capacity(&mut self, class: CacheClass, capacity: NonZeroUsize) {
+        self.textures.resize(class, capacity);
+    }
+
+    fn cached_paths(&self) -> Vec<PathBuf> {
+        self.textures.texture_paths()
+    }
+
+    fn is_cached(&self, path: &Path) -> bool {
+        self.textures
+            .contains(&FileOrRenderedTextKey::from_path(path))
+    }
+
+    fn evict_texture(&mut self, path: &Path) -> bool {
+        self.textures
+            .pop(&FileOrRenderedTextKey::from_path(path))
+            .is_some()
+    }
+
+    fn lock_texture(&mut self, key: FileOrRenderedTextKey) {
+        self.textures.lock(key);
+    }
+
+    fn unlock_textures(&mut self) {
+        self.textures.unlock_all();
+    }
+
+    fn insert_image(&mut self, path: &Path, image: &DecodedImage) -> Result<(), String> {
+        let texture = Rc::new(self.upload_image(image));
+        self.textures
+            .put(FileOrRenderedTextKey::from_path(path), texture);
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, rect: FRect, color: Color) -> Result<(), String> {
+        let white = self.white.clone();
+        self.push(&white, &DrawParams::new(None, Some(rect)), color)
+    }
+
+    fn draw_triangles(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<(), String> {
+        if let Some(index) = indices.iter().find(|i| **i as usize >= vertices.len()) {
+            return Err(format!("vertex index {index} is out of range"));
+        }
+        let start = (self.vertices.len() / VERTEX_FLOATS) as u32;
+        for index in indices.iter() {
+            let vertex = &vertices[*index as usize];
+            let c = vertex.color;
+            self.vertices
+                .extend_from_slice(&[vertex.position.x(), vertex.position.y(), 0.5, 0.5]);
+            self.vertices
+                .extend_from_slice(&[c.r, c.g, c.b, c.a].map(|c| c as f32 / 255.));
+        }
+        let white = self.white.clone();
+        self.extend_batch(&white, start);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::{FPoint, Rect};
+
+    use super::*;
+
+    #[test]
+    fn test_quad() {
+        let draw = DrawParams::new(
+            Some(Rect::new(0, 0, 8, 16)),
+            Some(FRect::new(10., 20., 8., 16.)),
+        );
+        let corners = quad(&draw, (16, 16), (100, 100));
+        assert_eq!(corners[0], [10., 20., 0., 0.]);
+        assert_eq!(corners[2], [18., 36., 0.5, 1.]);
+
+        let draw = DrawParams {
+            angle: 90.,
+            center: Some(FPoint::new(0., 0.)),
+            flip_horizontal: true,
+            ..DrawParams::new(None, Some(FRect::new(0., 0., 4., 2.)))
+        };
+        let corners = quad(&draw, (4, 2), (100, 100));
+        // the top right corner is turned to below the origin
+        assert!((corners[1][0]).abs() < 1e-5);
+        assert!((corners[1][1] - 4.).abs() < 1e-5);
+        assert_eq!(corners[1][2], 0.);
+    }
+
+    #[test]
+    fn test_scissor() {
+        // logical 100x100 shown at 2x with 50px bars on the left and right
+        let viewport = (50., 0., 200., 200.);
+        assert_eq!(
+            scissor(Rect::new(10, 20, 30, 40), viewport, (100, 100), (300, 200)),
+            Some((70, 40, 60, 80))
+        );
+        assert_eq!(
+            scissor(
+                Rect::new(-50, 90, 100, 50),
+                viewport,
+                (100, 100),
+                (300, 200)
+            ),
+            Some((0, 180, 150, 20))
+        );
+        assert_eq!(
+            scissor(Rect::new(200, 0, 10, 10), viewport, (100, 100), (300, 200)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_letterbox() {
+        assert_eq!(letterbox((800, 600), (0, 0)), (0., 0., 800., 600.));
+        assert_eq!(letterbox((800, 600), (400, 200)), (0., 100., 800., 400.));
+        assert_eq!(letterbox((800, 600), (300, 300)), (100., 0., 600., 600.));
+    }
+}
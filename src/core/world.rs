@@ -0,0 +1,794 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+    task::Poll,
+    time::Duration,
+};
+
+use sdl2::rect::{FPoint, FRect};
+
+use super::{
+    collision::{Collider, ContactTracker},
+    entity::{Entity, EntityId, UpdateGroup},
+    frame_clock::EngineClock,
+    event_bus::EventBus,
+    inspector::{self, EntitySummary},
+    physics::Physics,
+    prefab::PrefabRegistry,
+    profiler,
+    replication::Snapshot,
+    rng::{stable_hash, RngService},
+    sequence::{Seq, SequenceHandle, Sequences, SequencesMark},
+    spatial::SpatialGrid,
+    system::ChimericSystem,
+    timer::{TimerHandle, Timers},
+    trace::log_warn,
+    transform::{self, TransformNode},
+    tween::{Lerp, Tween, TweenHandle, Tweens},
+    variables::Variables,
+};
+
+const DEFAULT_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// upper bound on the number of updates run by a single call to advance
+const MAX_STEPS_PER_ADVANCE: u32 = 8;
+
+/// parts of the update which are skipped while paused. a pause menu would
+/// typically pause everything except ui updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pause {
+    /// update and parallel update of gameplay entities
+    pub gameplay: bool,
+    /// update and parallel update of ui entities
+    pub ui: bool,
+    pub physics: bool,
+    /// simulated time stops while timers are paused
+    pub timers: bool,
+    pub tweens: bool,
+}
+
+impl Pause {
+    /// everything but ui
+    pub fn gameplay() -> Self {
+        Self {
+            gameplay: true,
+            ui: false,
+            physics: true,
+            timers: true,
+            tweens: true,
+        }
+    }
+
+    fn group(&self, group: UpdateGroup) -> bool {
+        match group {
+            UpdateGroup::Gameplay => self.gameplay,
+            UpdateGroup::Ui => self.ui,
+        }
+    }
+}
+
+/// guards against sources of nondeterminism in the simulation, for lockstep
+/// multiplayer and replays. the world's own state is kept in ordered
+/// collections either way; this catches stepping by wall clock time and an rng
+/// seeded from the system time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Determinism {
+    #[default]
+    Off,
+    /// violations are logged and kept, see World::determinism_violations
+    Flag,
+    /// violations panic
+    Assert,
+}
+
+/// the order entities are drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrawOrder {
+    #[default]
+    Spawn,
+    /// by draw bottom plus sort bias, so lower entities are drawn over higher
+    /// ones, as in top down games. entities without a draw bottom (e.g. the
+    /// hud) are drawn after, in spawn order. ties keep spawn order
+    YSort,
+}
+
+/// indices of the keys in y sorted draw order
+fn y_sorted(keys: &[Option<f32>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    // stable
+    order.sort_by(|a, b| match (keys[*a], keys[*b]) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    order
+}
+
+/// what capture_state leaves out: timers, tweens, sequences and which entity
+/// ids have been given out. holds callbacks, so it can't be sent anywhere
+#[derive(Clone)]
+pub struct Schedule {
+    next_id: u64,
+    timers: Timers,
+    tweens: Tweens,
+    sequences: SequencesMark,
+}
+
+struct EntityEntry {
+    id: EntityId,
+    entity: Box<dyn Entity>,
+}
+
+/// owns all entities and drives them through each phase of a frame
+pub struct World {
+    entities: Vec<EntityEntry>,
+    next_id: u64,
+    pub prefabs: PrefabRegistry,
+    /// rebuilt from each entity's aabb at the end of each update
+    spatial: SpatialGrid<EntityId>,
+    contacts: ContactTracker,
+    pub events: EventBus,
+    pub physics: Physics,
+    /// seeded when the world is created. systems should each draw from their
+    /// own stream
+    pub rng: RngService,
+    /// duration of each update
+    timestep: Duration,
+    /// elapsed time which hasn't been simulated yet
+    accumulator: Duration,
+    /// multiplies the elapsed time given to advance
+    time_scale: f32,
+    pub pause: Pause,
+    timers: Timers,
+    tweens: Tweens,
+    sequences: Sequences,
+    /// removed at the next alive check
+    despawned: BTreeSet<EntityId>,
+    /// number of updates run so far
+    tick: u64,
+    /// replicated entities as of the last snapshot
+    replicated: BTreeSet<EntityId>,
+    pub draw_order: DrawOrder,
+    pub determinism: Determinism,
+    /// game state shared with data, e.g. flags set by dialogue
+    pub variables: Variables,
+    /// each distinct violation found while flagging
+    violations: Vec<String>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self {
+            entities: Default::default(),
+            next_id: 0,
+            prefabs: Default::default(),
+            spatial: Default::default(),
+            contacts: Default::default(),
+            events: Default::default(),
+            physics: Default::default(),
+            rng: Default::default(),
+            timestep: DEFAULT_TIMESTEP,
+            accumulator: Duration::ZERO,
+            time_scale: 1.,
+            pause: Default::default(),
+            timers: Default::default(),
+            tweens: Default::default(),
+            sequences: Default::default(),
+            despawned: Default::default(),
+            tick: 0,
+            replicated: Default::default(),
+            draw_order: Default::default(),
+            determinism: Default::default(),
+            variables: Default::default(),
+            violations: Default::default(),
+        }
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// the rng is seeded from the system time by default. use a fixed seed so
+    /// replays and tests are reproducible
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: RngService::new(seed),
+            ..Default::default()
+        }
+    }
+
+    /// the spatial index buckets entities into square cells of this size. it
+    /// should be around the size of a typical entity
+    pub fn with_spatial_cell_size(cell_size: f32) -> Self {
+        Self {
+            spatial: SpatialGrid::new(cell_size),
+            ..Default::default()
+        }
+    }
+
+    /// the fixed duration simulated by each update
+    pub fn timestep(&self) -> Duration {
+        self.timestep
+    }
+
+    pub fn set_timestep(&mut self, timestep: Duration) {
+        debug_assert!(!timestep.is_zero());
+        self.timestep = timestep;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// slow motion (< 1) or fast forward (> 1). the timestep is unchanged;
+    /// instead the number of updates run by advance is scaled, so the
+    /// simulation stays deterministic. zero stops advance from updating
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        debug_assert!(time_scale >= 0.);
+        self.time_scale = time_scale.max(0.);
+    }
+
+    /// run as many fixed timestep updates as fit in the elapsed (wall clock)
+    /// time, multiplied by the time scale, carrying over the remainder to the
+    /// next call. returns the number of updates that were run.
+    ///
+    /// if the simulation falls too far behind then the excess time is dropped
+    /// rather than trying to catch up
+    ///
+    /// this is a determinism violation; in lockstep, call update once per tick
+    /// instead
+    pub fn advance(&mut self, elapsed: Duration) -> Result<u32, String> {
+        self.violation("advance steps by wall clock time; call update once per tick");
+        self.accumulator += elapsed.mul_f32(self.time_scale);
+        let mut steps = 0;
+        while self.accumulator >= self.timestep {
+            if steps == MAX_STEPS_PER_ADVANCE {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+            self.accumulator -= self.timestep;
+            self.update()?;
+            steps += 1;
+        }
+        Ok(steps)
+    }
+
+    /// advance by the clock's last delta, which is already scaled by its own
+    /// time scale (on top of the world's)
+    pub fn advance_with(&mut self, clock: &EngineClock) -> Result<u32, String> {
+        self.advance(clock.delta())
+    }
+
+    /// add an entity to the world. if this is called during the update phase
+    /// then the entity is first updated next frame
+    pub fn spawn(&mut self, entity: Box<dyn Entity>) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.entities.push(EntityEntry { id, entity });
+        id
+    }
+
+    /// remove an entity from the world at the next alive check, regardless of
+    /// what the entity reports
+    pub fn despawn(&mut self, id: EntityId) {
+        self.despawned.insert(id);
+    }
+
+    /// total simulated time; the number of updates multiplied by the timestep
+    pub fn time(&self) -> Duration {
+        self.timers.now()
+    }
+
+    /// call the callback once the delay has passed in simulated time. it's
+    /// called at the beginning of an update, before the update phase
+    pub fn after<F>(&mut self, delay: Duration, callback: F) -> TimerHandle
+    where
+        F: FnMut(&mut World) -> Result<(), String> + 'static,
+    {
+        self.timers.schedule(delay, None, Box::new(callback))
+    }
+
+    /// call the callback each time the period passes in simulated time. it's
+    /// called at most once per update
+    pub fn every<F>(&mut self, period: Duration, callback: F) -> TimerHandle
+    where
+        F: FnMut(&mut World) -> Result<(), String> + 'static,
+    {
+        self.timers
+            .schedule(period, Some(period), Box::new(callback))
+    }
+
+    /// returns false if the callback already finished or was already cancelled
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        self.timers.cancel(handle)
+    }
+
+    pub fn is_scheduled(&self, handle: TimerHandle) -> bool {
+        self.timers.is_scheduled(handle)
+    }
+
+    fn run_timers(&mut self) -> Result<(), String> {
+        let mut due = self.timers.advance(self.timestep).into_iter();
+        while let Some(timer) = due.next() {
+            // may have been cancelled by a previous callback
+            if self.timers.is_cancelled(timer.handle()) {
+                self.timers.put_back(timer, false);
+                continue;
+            }
+            let result = (timer.callback.borrow_mut())(self);
+            self.timers.put_back(timer, true);
+            if let Err(e) = result {
+                due.for_each(|timer| self.timers.put_back(timer, false));
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// animate a value. tweens are advanced at the beginning of each update,
+    /// after timers and before the update phase
+    pub fn tween<T: Lerp + 'static>(&mut self, tween: Tween<T>) -> TweenHandle {
+        self.tweens.add(tween)
+    }
+
+    /// the value is left as is and the completion callback isn't called.
+    /// returns false if the tween already finished or was already cancelled
+    pub fn cancel_tween(&mut self, handle: TweenHandle) -> bool {
+        self.tweens.cancel(handle)
+    }
+
+    pub fn is_tween_active(&self, handle: TweenHandle) -> bool {
+        self.tweens.is_active(handle)
+    }
+
+    fn run_tweens(&mut self) -> Result<(), String> {
+        for callback in self.tweens.advance(self.timestep) {
+            (callback.borrow_mut())(self)?;
+        }
+        Ok(())
+    }
+
+    /// run a sequence written as an async block. sequences are resumed at the
+    /// beginning of each update, after tweens and before the update phase,
+    /// unless timers are paused
+    pub fn start_sequence<F, Fut>(&mut self, body: F) -> SequenceHandle
+    where
+        F: FnOnce(Seq) -> Fut,
+        Fut: Future<Output = Result<(), String>> + 'static,
+    {
+        self.sequences.start(body)
+    }
+
+    /// returns false if the sequence already finished or was already cancelled
+    pub fn cancel_sequence(&mut self, handle: SequenceHandle) -> bool {
+        self.sequences.cancel(handle)
+    }
+
+    pub fn is_sequence_running(&self, handle: SequenceHandle) -> bool {
+        self.sequences.is_running(handle)
+    }
+
+    fn run_sequences(&mut self) -> Result<(), String> {
+        let mut taken = self.sequences.take().into_iter();
+        while let Some(mut sequence) = taken.next() {
+            // may have been cancelled by a previous sequence
+            if self.sequences.is_cancelled(sequence.handle()) {
+                self.sequences.put_back(sequence, true);
+                continue;
+            }
+            match sequence.resume(self) {
+                Poll::Pending => self.sequences.put_back(sequence, false),
+                Poll::Ready(result) => {
+                    self.sequences.put_back(sequence, true);
+                    if let Err(e) = result {
+                        taken.for_each(|sequence| self.sequences.put_back(sequence, false));
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// construct the named prefab, with the overrides replacing any of the
+    /// prefab's parameters, and add it to the world
+    pub fn spawn_prefab(
+        &mut self,
+        prefab_name: &str,
+        overrides: &toml::Table,
+    ) -> Result<EntityId, String> {
+        let entity = self.prefabs.instantiate(prefab_name, overrides)?;
+        Ok(self.spawn(entity))
+    }
+
+    /// none while entities are being updated, since they're taken out of the
+    /// world for the update phase
+    pub fn entity_mut(&mut self, id: EntityId) -> Option<&mut (dyn Entity + 'static)> {
+        self.entities
+            .iter_mut()
+            .find(|e| e.id == id)
+            .map(|e| e.entity.as_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.entities.iter().map(|e| e.id)
+    }
+
+    /// number of updates run so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// the state of every replicated entity. clears their dirty flags
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.take_snapshot(false)
+    }
+
+    /// the state of replicated entities which are dirty, plus those which were
+    /// removed since the last snapshot. clears their dirty flags
+    pub fn delta_snapshot(&mut self) -> Snapshot {
+        self.take_snapshot(true)
+    }
+
+    /// the state of every replicated entity, like snapshot, but leaving their
+    /// dirty flags and what delta_snapshot last sent alone. e.g. for rollback
+    pub fn capture_state(&mut self) -> Snapshot {
+        let mut entities: BTreeMap<EntityId, Vec<u8>> = BTreeMap::new();
+        for e in self.entities.iter_mut() {
+            if let Some(state) = e.entity.replicate_mut() {
+                let mut data: Vec<u8> = Vec::new();
+                state.write_state(&mut data);
+                entities.insert(e.id, data);
+            }
+        }
+        Snapshot {
+            tick: self.tick,
+            entities,
+            removed: Vec::new(),
+        }
+    }
+
+    /// to be given to rewind along with capture_state
+    pub fn capture_schedule(&self) -> Schedule {
+        Schedule {
+            next_id: self.next_id,
+            timers: self.timers.clone(),
+            tweens: self.tweens.clone(),
+            sequences: self.sequences.mark(),
+        }
+    }
+
+    fn take_snapshot(&mut self, only_dirty: bool) -> Snapshot {
+        let mut entities: BTreeMap<EntityId, Vec<u8>> = BTreeMap::new();
+        let mut replicated: BTreeSet<EntityId> = BTreeSet::new();
+        for e in self.entities.iter_mut() {
+            let Some(state) = e.entity.replicate_mut() else {
+                continue;
+            };
+            replicated.insert(e.id);
+            if only_dirty && !state.is_dirty() && self.replicated.contains(&e.id) {
+                continue;
+            }
+            let mut data: Vec<u8> = Vec::new();
+            state.write_state(&mut data);
+            state.clear_dirty();
+            entities.insert(e.id, data);
+        }
+        let removed = if only_dirty {
+            self.replicated.difference(&replicated).copied().collect()
+        } else {
+            Vec::new()
+        };
+        self.replicated = replicated;
+        Snapshot {
+            tick: self.tick,
+            entities,
+            removed,
+        }
+    }
+
+    /// restore the state of each entity in the snapshot, and despawn those it
+    /// lists as removed. entities must already exist in this world; snapshots
+    /// don't construct them
+    pub fn apply_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        for e in self.entities.iter_mut() {
+            if let Some(data) = snapshot.entities.get(&e.id) {
+                let state = e
+                    .entity
+                    .replicate_mut()
+                    .ok_or_else(|| format!("entity {} isn't replicated", e.id.0))?;
+                state.read_state(data)?;
+                state.clear_dirty();
+            }
+        }
+        for id in snapshot.removed.iter() {
+            self.despawn(*id);
+        }
+        Ok(())
+    }
+
+    /// go back to a full snapshot of this world (see capture_state) and the
+    /// schedule captured with it, e.g. for rollback. entities spawned since are
+    /// removed, but entities removed since can't be brought back, and only
+    /// replicated state is restored. fails if a sequence was resumed since.
+    /// events published since are discarded. dirty flags are kept, so the
+    /// rewound state still goes out in the next delta_snapshot
+    pub fn rewind(&mut self, snapshot: &Snapshot, schedule: &Schedule) -> Result<(), String> {
+        self.sequences.rewind(schedule.sequences)?;
+        self.entities.retain(|e| e.id.0 < schedule.next_id);
+        self.despawned.retain(|id| id.0 < schedule.next_id);
+        self.next_id = schedule.next_id;
+        self.timers = schedule.timers.clone();
+        self.tweens = schedule.tweens.clone();
+        for e in self.entities.iter_mut() {
+            if let Some(data) = snapshot.entities.get(&e.id) {
+                let state = e
+                    .entity
+                    .replicate_mut()
+                    .ok_or_else(|| format!("entity {} isn't replicated", e.id.0))?;
+                state.read_state(data)?;
+            }
+        }
+        self.tick = snapshot.tick;
+        self.events.clear_pending();
+        Ok(())
+    }
+
+    /// simulate a single timestep; fire due timers and advance tweens, then run
+    /// the update, physics, parallel update, and alive check phases. then compute world
+    /// transforms and detect collisions, which are published as contact events.
+    /// phases are skipped according to the pause flags
+    pub fn update(&mut self) -> Result<(), String> {
+        let _zone = profiler::zone("world update");
+        if self.rng.is_clock_seeded() {
+            self.violation("the rng is seeded from the system time; use World::with_seed");
+        }
+        self.tick += 1;
+        self.events.advance();
+        if !self.pause.timers {
+            let _zone = profiler::zone("timers");
+            self.run_timers()?;
+        }
+        if !self.pause.tweens {
+            let _zone = profiler::zone("tweens");
+            self.run_tweens()?;
+        }
+        if !self.pause.timers {
+            let _zone = profiler::zone("sequences");
+            self.run_sequences()?;
+        }
+
+        // entities are taken out of the world so they can each be given a
+        // mutable reference to it. anything spawned in the meantime is appended
+        // after the existing entities
+        let pause = self.pause;
+        let mut entities = std::mem::take(&mut self.entities);
+        profiler::begin_zone("update");
+        let result = entities
+            .iter_mut()
+            .filter(|e| !pause.group(e.entity.group()))
+            .try_for_each(|e| e.entity.update(self));
+        profiler::end_zone();
+        entities.append(&mut self.entities);
+        self.entities = entities;
+        result?;
+
+        if !self.pause.physics {
+            let _zone = profiler::zone("physics");
+            self.physics.sync_tiles();
+            for e in self.entities.iter_mut() {
+                if let Some(body) = e.entity.body_mut() {
+                    self.physics.step(body, self.timestep);
+                }
+            }
+        }
+
+        profiler::begin_zone("parallel_update");
+        let result = self
+            .entities
+            .iter_mut()
+            .filter(|e| !pause.group(e.entity.group()))
+            .try_for_each(|e| e.entity.parallel_update());
+        profiler::end_zone();
+        result?;
+
+        let despawned = std::mem::take(&mut self.despawned);
+        self.entities
+            .retain(|e| e.entity.alive() && !despawned.contains(&e.id));
+        self.resolve_transforms();
+        self.rebuild_spatial_index();
+        let _zone = profiler::zone("collisions");
+        self.detect_collisions();
+        Ok(())
+    }
+
+    fn violation(&mut self, violation: &str) {
+        match self.determinism {
+            Determinism::Off => {}
+            Determinism::Flag => {
+                if !self.violations.iter().any(|v| v == violation) {
+                    log_warn!("determinism violation: {violation}");
+                    self.violations.push(violation.to_owned());
+                }
+            }
+            Determinism::Assert => panic!("determinism violation: {violation}"),
+        }
+    }
+
+    /// those found since the determinism mode was set to flag, each once
+    pub fn determinism_violations(&self) -> &[String] {
+        &self.violations
+    }
+
+    /// a hash of the tick, rng and every replicated entity's state which is
+    /// stable between builds and platforms. peers in lockstep compare it to
+    /// detect a desync. doesn't clear dirty flags
+    pub fn state_hash(&mut self) -> u64 {
+        let mut data: Vec<u8> = self.tick.to_le_bytes().to_vec();
+        data.extend(self.rng.serialize());
+        for e in self.entities.iter_mut() {
+            if let Some(state) = e.entity.replicate_mut() {
+                data.extend_from_slice(&e.id.0.to_le_bytes());
+                state.write_state(&mut data);
+            }
+        }
+        stable_hash(&data)
+    }
+
+    fn resolve_transforms(&mut self) {
+        let mut nodes: Vec<(EntityId, &mut TransformNode)> = self
+            .entities
+            .iter_mut()
+            .filter_map(|e| {
+                let id = e.id;
+                e.entity.transform_mut().map(|node| (id, node))
+            })
+            .collect();
+        transform::resolve(&mut nodes);
+    }
+
+    fn rebuild_spatial_index(&mut self) {
+        self.spatial.clear();
+        for e in self.entities.iter() {
+            if let Some(aabb) = e.entity.aabb() {
+                self.spatial.insert(e.id, aabb);
+            }
+        }
+    }
+
+    fn detect_collisions(&mut self) {
+        let colliders: Vec<(EntityId, Collider)> = self
+            .entities
+            .iter()
+            .filter_map(|e| e.entity.collider().map(|c| (e.id, c)))
+            .collect();
+        // broad phase buckets by collider bounds, narrow phase checks shapes
+        let mut grid: SpatialGrid<usize> = SpatialGrid::new(self.spatial.cell_size());
+        for (i, (_, collider)) in colliders.iter().enumerate() {
+            grid.insert(i, collider.bounds());
+        }
+        let mut pairs: Vec<(EntityId, EntityId)> = Vec::new();
+        for (i, (id, collider)) in colliders.iter().enumerate() {
+            for j in grid.query_region(collider.bounds()) {
+                if j <= i {
+                    continue;
+                }
+                let (other_id, other_collider) = &colliders[j];
+                if collider.intersects(other_collider) {
+                    pairs.push((*id.min(other_id), *id.max(other_id)));
+                }
+            }
+        }
+        for contact in self.contacts.update(pairs) {
+            self.events.publish(contact);
+        }
+    }
+
+    /// which colliders were touching as of the end of the last update
+    pub fn contacts(&self) -> &ContactTracker {
+        &self.contacts
+    }
+
+    /// e.g. restoring them along with a rewind, so the contacts which
+    /// continue aren't published as beginning again
+    pub fn set_contacts(&mut self, contacts: ContactTracker) {
+        self.contacts = contacts;
+    }
+
+    /// entities whose aabb overlaps the region, as of the end of the last
+    /// update
+    pub fn query_region(&self, region: FRect) -> Vec<EntityId> {
+        self.spatial.query_region(region)
+    }
+
+    /// entities whose aabb is hit by the ray, as of the end of the last update.
+    /// sorted nearest first, along with the t at which origin + t * dir first
+    /// hits it
+    pub fn query_ray(&self, origin: FPoint, dir: FPoint) -> Vec<(EntityId, f32)> {
+        self.spatial.query_ray(origin, dir)
+    }
+
+    pub fn entity_summaries(&self) -> Vec<EntitySummary> {
+        self.entities
+            .iter()
+            .map(|e| EntitySummary {
+                id: e.id.0,
+                type_name: e.entity.debug_name(),
+                tags: e.entity.tags().to_vec(),
+                position: e.entity.aabb().map(|aabb| {
+                    (
+                        aabb.x() + aabb.width() / 2.,
+                        aabb.y() + aabb.height() / 2.,
+                    )
+                }),
+            })
+            .collect()
+    }
+
+    /// json dump of every entity, for diagnosing leaks and the like
+    pub fn debug_dump(&self) -> String {
+        inspector::to_json(self.tick, &self.entity_summaries())
+    }
+
+    /// draw each entity, in the draw order
+    pub fn draw(&self, system: &mut ChimericSystem) -> Result<(), String> {
+        let _zone = profiler::zone("world draw");
+        match self.draw_order {
+            DrawOrder::Spawn => self.entities.iter().try_for_each(|e| e.entity.draw(system)),
+            DrawOrder::YSort => {
+                let keys: Vec<Option<f32>> = self
+                    .entities
+                    .iter()
+                    .map(|e| e.entity.draw_bottom().map(|y| y + e.entity.sort_bias()))
+                    .collect();
+                y_sorted(&keys)
+                    .into_iter()
+                    .try_for_each(|i| self.entities[i].entity.draw(system))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_y_sorted() {
+        let keys = [None, Some(10.), Some(-5.), None, Some(10.), Some(3.)];
+        assert_eq!(y_sorted(&keys), [2, 5, 1, 4, 0, 3]);
+    }
+
+    #[test]
+    fn test_determinism() {
+        let mut world = World::new();
+        world.determinism = Determinism::Flag;
+        world.update().unwrap();
+        world.update().unwrap();
+        world.advance(Duration::from_secs(1)).unwrap();
+        assert_eq!(world.determinism_violations().len(), 2);
+
+        let mut a = World::with_seed(3);
+        let mut b = World::with_seed(3);
+        for world in [&mut a, &mut b] {
+            world.determinism = Determinism::Assert;
+            world.update().unwrap();
+            world.rng.stream("ai").next_u64();
+        }
+        assert_eq!(a.state_hash(), b.state_hash());
+        b.update().unwrap();
+        assert_ne!(a.state_hash(), b.state_hash());
+        assert!(a.determinism_violations().is_empty());
+    }
+}